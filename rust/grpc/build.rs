@@ -0,0 +1,24 @@
+// The service stubs are generated from a manual description of proto/book_service.proto,
+// so building doesn't need protoc. Keep the two in sync.
+
+fn main() {
+    let method = |name: &str, route: &str, input: &str, output: &str| {
+        tonic_build::manual::Method::builder()
+            .name(name)
+            .route_name(route)
+            .input_type(input)
+            .output_type(output)
+            .codec_path("tonic_prost::ProstCodec")
+    };
+    let service = tonic_build::manual::Service::builder()
+        .name("BookService")
+        .package("orderbook")
+        .method(method("get_snapshot", "GetSnapshot", "crate::SnapshotRequest", "crate::Snapshot").build())
+        .method(
+            method("stream_events", "StreamEvents", "crate::StreamRequest", "rust_orderbook::proto::MarketEvent")
+                .server_streaming()
+                .build(),
+        )
+        .build();
+    tonic_build::manual::Builder::new().build_client(true).compile(&[service]);
+}
@@ -0,0 +1,127 @@
+
+// gRPC service over live book state, see proto/book_service.proto. It lives in its own
+// crate because tonic needs async, which rust_orderbook's edition doesn't have.
+//
+// The service reads books through rust_orderbook's BookSource, so it works with whatever keeps the books,
+// and streams the events handed to EventPublisher to every StreamEvents call whose symbols
+// match. A subscriber that falls more than `capacity` events behind gets RESOURCE_EXHAUSTED,
+// which ends its stream, rather than silently missing events.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_stream::Stream;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+use rust_orderbook::event::MarketEvent;
+use rust_orderbook::proto;
+use rust_orderbook::query::BookSource;
+
+#[cfg(test)]
+mod test;
+
+include!(concat!(env!("OUT_DIR"), "/orderbook.BookService.rs"));
+
+pub use book_service_client::BookServiceClient;
+pub use book_service_server::BookServiceServer;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SnapshotRequest {
+    #[prost(string, tag = "1")] pub symbol : String,
+    #[prost(uint32, tag = "2")] pub depth  : u32
+}
+
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct Level {
+    #[prost(uint64, tag = "1")] pub price  : u64,
+    #[prost(uint64, tag = "2")] pub volume : u64
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Snapshot {
+    #[prost(string, tag = "1")]            pub symbol    : String,
+    #[prost(uint64, tag = "2")]            pub timestamp : u64,
+    #[prost(message, repeated, tag = "3")] pub bids      : Vec<Level>,
+    #[prost(message, repeated, tag = "4")] pub asks      : Vec<Level>
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamRequest {
+    #[prost(string, repeated, tag = "1")] pub symbols : Vec<String>
+}
+
+type Published = (Arc<str>, proto::MarketEvent);
+
+// Hands events to the service's subscribers, cheap to clone.
+#[derive(Clone)]
+pub struct EventPublisher {
+    tx : broadcast::Sender<Published>
+}
+
+impl EventPublisher {
+    // the caller knows the event's symbol even for the ones that don't carry it (deletes,
+    // executions, ...), eg. from its order id map. Returns how many streams got it.
+    pub fn publish( &self, symbol : &str, e : &MarketEvent ) -> usize {
+        self.tx.send((Arc::from(symbol), proto::MarketEvent::from(e))).unwrap_or(0)
+    }
+}
+
+pub struct BookGrpcService<S : BookSource> {
+    source : Arc<S>,
+    events : broadcast::Sender<Published>
+}
+
+impl<S : BookSource> BookGrpcService<S> {
+    pub fn new( source : Arc<S>, capacity : usize ) -> (BookGrpcService<S>, EventPublisher) {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        (BookGrpcService{ source, events : tx.clone() }, EventPublisher{ tx })
+    }
+
+    pub async fn serve( self, addr : SocketAddr ) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder().add_service(BookServiceServer::new(self)).serve(addr).await
+    }
+
+    // serves on an already bound listener, eg. one on port 0.
+    pub async fn serve_listener( self, listener : TcpListener ) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder().add_service(BookServiceServer::new(self))
+            .serve_with_incoming(TcpListenerStream::new(listener)).await
+    }
+}
+
+fn levels( levels : Vec<(u64, u64)> ) -> Vec<Level> {
+    levels.into_iter().map(|(price, volume)| Level{ price, volume }).collect()
+}
+
+#[tonic::async_trait]
+impl<S : BookSource> book_service_server::BookService for BookGrpcService<S> {
+    async fn get_snapshot( &self, request : Request<SnapshotRequest> ) -> Result<Response<Snapshot>, Status> {
+        let req = request.into_inner();
+        let book = self.source.levels(&req.symbol, req.depth as usize)
+            .ok_or_else(|| Status::not_found(format!("unknown symbol {}", req.symbol)))?;
+        Ok(Response::new(Snapshot{ symbol : req.symbol, timestamp : book.timestamp, bids : levels(book.bids),
+                                   asks : levels(book.asks) }))
+    }
+
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<proto::MarketEvent, Status>> + Send>>;
+
+    async fn stream_events( &self, request : Request<StreamRequest> ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let symbols : HashSet<String> = request.into_inner().symbols.into_iter().collect();
+        let stream = BroadcastStream::new(self.events.subscribe())
+            .filter_map(move |r| match r {
+                Ok((symbol, e)) => if symbols.is_empty() || symbols.contains(&*symbol) { Some(Ok(e)) } else { None },
+                Err(BroadcastStreamRecvError::Lagged(n)) =>
+                    Some(Err(Status::resource_exhausted(format!("fell {} events behind", n))))
+            });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
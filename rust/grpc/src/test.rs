@@ -0,0 +1,51 @@
+
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+use tokio_stream::StreamExt;
+
+use rust_orderbook::event::MarketEvent;
+use rust_orderbook::proto;
+use rust_orderbook::query::BookLevels;
+use rust_orderbook::query::BookSource;
+
+use crate::BookGrpcService;
+use crate::BookServiceClient;
+use crate::Level;
+use crate::SnapshotRequest;
+use crate::StreamRequest;
+
+struct OneBook;
+
+impl BookSource for OneBook {
+    fn levels( &self, symbol : &str, depth : usize ) -> Option<BookLevels> {
+        if symbol != "AAPL" {
+            return None;
+        }
+        let bids = vec![(1831800, 300), (1831700, 200)];
+        let depth = if depth == 0 { bids.len() } else { depth };
+        Some(BookLevels{ timestamp : 9, bids : bids.into_iter().take(depth).collect(), asks : vec![(1832000, 100)] })
+    }
+}
+
+#[tokio::test]
+async fn test_book_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (service, publisher) = BookGrpcService::new(Arc::new(OneBook), 16);
+    tokio::spawn(service.serve_listener(listener));
+    let mut client = BookServiceClient::connect(format!("http://{}", addr)).await.unwrap();
+
+    let snap = client.get_snapshot(SnapshotRequest{ symbol : String::from("AAPL"), depth : 1 }).await.unwrap().into_inner();
+    assert_eq!( snap.timestamp, 9 );
+    assert_eq!( snap.bids, vec![Level{ price : 1831800, volume : 300 }] );
+    assert_eq!( snap.asks, vec![Level{ price : 1832000, volume : 100 }] );
+    let err = client.get_snapshot(SnapshotRequest{ symbol : String::from("MSFT"), depth : 0 }).await.unwrap_err();
+    assert_eq!( err.code(), tonic::Code::NotFound );
+
+    let mut stream = client.stream_events(StreamRequest{ symbols : vec![String::from("AAPL")] }).await.unwrap().into_inner();
+    let del = MarketEvent::OrderDeleted{ timestamp : 10, order_id : 42 };
+    assert_eq!( publisher.publish("MSFT", &MarketEvent::OrderDeleted{ timestamp : 10, order_id : 1 }), 1 );
+    assert_eq!( publisher.publish("AAPL", &del), 1 );
+    assert_eq!( stream.next().await.unwrap().unwrap(), proto::MarketEvent::from(&del) );
+}
@@ -0,0 +1,112 @@
+
+// REST endpoints over live book state, for dashboards and health checks. Like the gRPC
+// service it reads books through rust_orderbook's BookSource and lives in its own crate
+// for async.
+//
+//   GET /book/{symbol}?depth=10   levels per side, best first (every level without depth)
+//   GET /bbo/{symbol}             best bid and ask, null for an empty side
+//   GET /stats/{symbol}           order/level counts and trading stats
+//   GET /symbols                  the symbols the source knows about
+//
+// Unknown symbols are 404s. Prices are 4 implied decimals, same as everywhere else.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::Json;
+use axum::Router;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::net::TcpListener;
+
+use rust_orderbook::query::BookSource;
+
+#[cfg(test)]
+mod test;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub price  : u64,
+    pub volume : u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Book {
+    pub symbol    : String,
+    pub timestamp : u64,
+    pub bids      : Vec<Level>,
+    pub asks      : Vec<Level>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bbo {
+    pub symbol    : String,
+    pub timestamp : u64,
+    pub bid       : Option<Level>,
+    pub ask       : Option<Level>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub symbol        : String,
+    pub timestamp     : u64,
+    pub orders        : usize,
+    pub bid_levels    : usize,
+    pub ask_levels    : usize,
+    pub trades        : u64,
+    pub traded_volume : u64,
+    pub last_price    : Option<u64>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepthQuery {
+    depth : Option<usize>
+}
+
+fn levels( levels : Vec<(u64, u64)> ) -> Vec<Level> {
+    levels.into_iter().map(|(price, volume)| Level{ price, volume }).collect()
+}
+
+async fn book<S : BookSource>( State(source) : State<Arc<S>>, Path(symbol) : Path<String>,
+                               Query(q) : Query<DepthQuery> ) -> Result<Json<Book>, StatusCode> {
+    let book = source.levels(&symbol, q.depth.unwrap_or(0)).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(Book{ symbol, timestamp : book.timestamp, bids : levels(book.bids), asks : levels(book.asks) }))
+}
+
+async fn bbo<S : BookSource>( State(source) : State<Arc<S>>, Path(symbol) : Path<String> ) -> Result<Json<Bbo>, StatusCode> {
+    let book = source.levels(&symbol, 1).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(Bbo{ symbol, timestamp : book.timestamp, bid : levels(book.bids).first().cloned(),
+                 ask : levels(book.asks).first().cloned() }))
+}
+
+async fn stats<S : BookSource>( State(source) : State<Arc<S>>, Path(symbol) : Path<String> ) -> Result<Json<Stats>, StatusCode> {
+    let s = source.stats(&symbol).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(Stats{ symbol, timestamp : s.timestamp, orders : s.orders, bid_levels : s.bid_levels,
+                   ask_levels : s.ask_levels, trades : s.trades, traded_volume : s.traded_volume,
+                   last_price : s.last_price }))
+}
+
+async fn symbols<S : BookSource>( State(source) : State<Arc<S>> ) -> Json<Vec<String>> {
+    let mut symbols = source.symbols();
+    symbols.sort();
+    Json(symbols)
+}
+
+pub fn router<S : BookSource>( source : Arc<S> ) -> Router {
+    Router::new()
+        .route("/book/{symbol}", get(book::<S>))
+        .route("/bbo/{symbol}", get(bbo::<S>))
+        .route("/stats/{symbol}", get(stats::<S>))
+        .route("/symbols", get(symbols::<S>))
+        .with_state(source)
+}
+
+pub async fn serve<S : BookSource>( source : Arc<S>, addr : SocketAddr ) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router(source)).await
+}
@@ -0,0 +1,94 @@
+
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::Request;
+use axum::http::StatusCode;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+use rust_orderbook::book::BookManager;
+use rust_orderbook::messages::BATSMsgFactory;
+use rust_orderbook::query::BookLevels;
+use rust_orderbook::query::BookSource;
+use rust_orderbook::query::BookStats;
+
+use crate::Bbo;
+use crate::Book;
+use crate::Level;
+use crate::Stats;
+use crate::router;
+
+struct OneBook;
+
+impl BookSource for OneBook {
+    fn levels( &self, symbol : &str, depth : usize ) -> Option<BookLevels> {
+        if symbol != "AAPL" {
+            return None;
+        }
+        let bids = vec![(1831800, 300), (1831700, 200)];
+        let depth = if depth == 0 { bids.len() } else { depth };
+        Some(BookLevels{ timestamp : 9, bids : bids.into_iter().take(depth).collect(), asks : vec![] })
+    }
+
+    fn stats( &self, symbol : &str ) -> Option<BookStats> {
+        self.levels(symbol, 0).map(|b| BookStats{ timestamp : b.timestamp, orders : 3, bid_levels : b.bids.len(),
+                                                  trades : 1, traded_volume : 100, last_price : Some(1831900),
+                                                  ..Default::default() })
+    }
+
+    fn symbols( &self ) -> Vec<String> {
+        vec![String::from("AAPL")]
+    }
+}
+
+async fn get( app : &Router, uri : &str ) -> (StatusCode, Vec<u8>) {
+    let resp = app.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap()).await.unwrap();
+    let status = resp.status();
+    (status, resp.into_body().collect().await.unwrap().to_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn test_router() {
+    let app = router(Arc::new(OneBook));
+
+    let (status, body) = get(&app, "/book/AAPL?depth=1").await;
+    assert_eq!( status, StatusCode::OK );
+    let book : Book = serde_json::from_slice(&body).unwrap();
+    assert_eq!( book.bids, vec![Level{ price : 1831800, volume : 300 }] );
+
+    let (_, body) = get(&app, "/book/AAPL").await;
+    assert_eq!( serde_json::from_slice::<Book>(&body).unwrap().bids.len(), 2 );
+
+    let (_, body) = get(&app, "/bbo/AAPL").await;
+    let bbo : Bbo = serde_json::from_slice(&body).unwrap();
+    assert_eq!( (bbo.bid, bbo.ask), (Some(Level{ price : 1831800, volume : 300 }), None) );
+
+    let (_, body) = get(&app, "/stats/AAPL").await;
+    let stats : Stats = serde_json::from_slice(&body).unwrap();
+    assert_eq!( (stats.orders, stats.bid_levels, stats.last_price), (3, 2, Some(1831900)) );
+
+    let (_, body) = get(&app, "/symbols").await;
+    assert_eq!( body, b"[\"AAPL\"]" );
+
+    assert_eq!( get(&app, "/bbo/MSFT").await.0, StatusCode::NOT_FOUND );
+    assert_eq!( get(&app, "/stats/MSFT").await.0, StatusCode::NOT_FOUND );
+}
+
+#[tokio::test]
+async fn test_router_over_books() {
+    let books = Arc::new(RwLock::new(BookManager::new()));
+    let app = router(books.clone());
+    assert_eq!( get(&app, "/book/AAPL").await.0, StatusCode::NOT_FOUND );
+
+    // the feed keeps writing, the server sees each message as it lands
+    books.write().unwrap().apply(&BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"));
+    let (_, body) = get(&app, "/bbo/AAPL").await;
+    let bbo : Bbo = serde_json::from_slice(&body).unwrap();
+    assert_eq!( (bbo.timestamp, bbo.bid, bbo.ask), (28_800_168_000_000, None, Some(Level{ price : 1831900, volume : 100 })) );
+    books.write().unwrap().apply(&BATSMsgFactory::parse("28800169X1K27GA00000Y000040"));
+    let (_, body) = get(&app, "/book/AAPL").await;
+    assert_eq!( serde_json::from_slice::<Book>(&body).unwrap().asks, vec![Level{ price : 1831900, volume : 60 }] );
+}
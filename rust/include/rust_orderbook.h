@@ -0,0 +1,114 @@
+/* C API to rust_orderbook, built with --features ffi. See src/ffi.rs for return values and ownership. */
+
+#ifndef RUST_ORDERBOOK_H
+#define RUST_ORDERBOOK_H
+
+/* Generated by cbindgen, don't edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define OB_ORDER_ADDED 0
+
+#define OB_ORDER_REDUCED 1
+
+#define OB_ORDER_DELETED 2
+
+#define OB_ORDER_REPLACED 3
+
+#define OB_TRADE 4
+
+#define OB_TRADE_BROKEN 5
+
+#define OB_STATUS_CHANGE 6
+
+#define OB_AUCTION_INFO 7
+
+#define OB_HAS_ORDER_ID 1
+
+#define OB_HAS_PRICE 2
+
+#define OB_HAS_REMAINING 4
+
+#define OB_COMPLETED 8
+
+#define OB_STATE_HALTED (char)72
+
+#define OB_STATE_SUSPENDED (char)83
+
+#define OB_STATE_QUOTE_ONLY (char)81
+
+#define OB_STATE_TRADING (char)84
+
+#define OB_STATE_CLEARED (char)67
+
+#define OB_STATE_END_OF_SESSION (char)69
+
+#define OB_SYMBOL_LEN 16
+
+#define OB_BID 0
+
+#define OB_ASK 1
+
+typedef struct ObBook ObBook;
+
+typedef struct ObEvent {
+  uint8_t kind;
+  uint8_t flags;
+  char side;
+  char state;
+  char auction_type;
+  uint64_t timestamp;
+  uint64_t order_id;
+  uint64_t new_order_id;
+  uint64_t exec_id;
+  uint64_t price;
+  uint32_t shares;
+  uint32_t remaining;
+  uint32_t buy_shares;
+  uint32_t sell_shares;
+  char symbol[OB_SYMBOL_LEN];
+} ObEvent;
+
+typedef struct ObLevel {
+  uint64_t price;
+  uint32_t volume;
+} ObLevel;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+int ob_parse(const char *msg, uintptr_t len, struct ObEvent *out);
+
+struct ObBook *ob_book_new(void);
+
+void ob_book_free(struct ObBook *book);
+
+int ob_book_apply(struct ObBook *book, const struct ObEvent *e);
+
+int ob_book_add_order(struct ObBook *book,
+                      uint64_t order_id,
+                      char side,
+                      uint64_t price,
+                      uint32_t shares);
+
+int ob_book_reduce_order(struct ObBook *book, uint64_t order_id, uint32_t shares);
+
+int ob_book_delete_order(struct ObBook *book, uint64_t order_id);
+
+uint64_t ob_book_best_bid(const struct ObBook *book);
+
+uint64_t ob_book_best_ask(const struct ObBook *book);
+
+uint32_t ob_book_volume(const struct ObBook *book, int side, uint64_t price);
+
+uintptr_t ob_book_order_count(const struct ObBook *book);
+
+uintptr_t ob_book_levels(struct ObBook *book, int side, struct ObLevel *out, uintptr_t max);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUST_ORDERBOOK_H */
@@ -0,0 +1,279 @@
+
+// Collects parsed messages into Arrow RecordBatches, one batch and schema per message type
+// (same tables and columns as the CSV export). Numbers keep their wire types, chars and
+// strings become Utf8 with padding trimmed, optional fields are nullable.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::ArrayRef;
+use arrow_array::RecordBatch;
+use arrow_array::StringArray;
+use arrow_array::UInt8Array;
+use arrow_array::UInt32Array;
+use arrow_array::UInt64Array;
+use arrow_schema::ArrowError;
+use arrow_schema::DataType;
+use arrow_schema::Field;
+use arrow_schema::Schema;
+
+use binary::BinaryAddOrderMsg;
+use binary::BinaryOrderExecutedMsg;
+use binary::BinaryTradeMsg;
+use binary::CalculatedValueMsg;
+use binary::DeleteOrderMsg;
+use binary::ModifyOrderMsg;
+use binary::OrderExecutedAtPriceSizeMsg;
+use binary::ReduceSizeMsg;
+use binary::TradeExpandedMsg;
+use binary::UnitClearMsg;
+use messages::AddOrderMsg;
+use messages::AuctionSummaryMsg;
+use messages::AuctionUpdateMsg;
+use messages::BATSMessage;
+use messages::HaltReason;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+use messages::RetailPriceImproveMsg;
+use messages::SymbolClearMsg;
+use messages::TradeBreakMsg;
+use messages::TradeMsg;
+use messages::TradingStatusMsg;
+
+pub enum Column {
+    UInt8(Vec<Option<u8>>),
+    UInt32(Vec<Option<u32>>),
+    UInt64(Vec<Option<u64>>),
+    Utf8(Vec<Option<String>>)
+}
+
+impl Column {
+    fn data_type( &self ) -> DataType {
+        match *self {
+            Column::UInt8(_)  => DataType::UInt8,
+            Column::UInt32(_) => DataType::UInt32,
+            Column::UInt64(_) => DataType::UInt64,
+            Column::Utf8(_)   => DataType::Utf8
+        }
+    }
+
+    fn len( &self ) -> usize {
+        match *self {
+            Column::UInt8(ref v)  => v.len(),
+            Column::UInt32(ref v) => v.len(),
+            Column::UInt64(ref v) => v.len(),
+            Column::Utf8(ref v)   => v.len()
+        }
+    }
+
+    fn finish( self ) -> ArrayRef {
+        match self {
+            Column::UInt8(v)  => Arc::new(UInt8Array::from(v)),
+            Column::UInt32(v) => Arc::new(UInt32Array::from(v)),
+            Column::UInt64(v) => Arc::new(UInt64Array::from(v)),
+            Column::Utf8(v)   => Arc::new(StringArray::from(v))
+        }
+    }
+}
+
+pub trait ArrowField {
+    fn column() -> Column;
+    fn nullable() -> bool { false }
+    fn push( &self, col : &mut Column );
+}
+
+fn column_for<T : ArrowField>( _ : &T ) -> (Column, bool) {
+    (T::column(), T::nullable())
+}
+
+macro_rules! create_arrow_field_impl {
+    ($t : ty, $variant : ident, $value : expr) => (
+        impl ArrowField for $t {
+            fn column() -> Column {
+                Column::$variant(vec![])
+            }
+
+            fn push( &self, col : &mut Column ) {
+                if let Column::$variant(ref mut v) = *col {
+                    v.push(Some($value(self)));
+                }
+            }
+        }
+    )
+}
+
+create_arrow_field_impl!(u8, UInt8, |v : &u8| *v);
+create_arrow_field_impl!(u32, UInt32, |v : &u32| *v);
+create_arrow_field_impl!(u64, UInt64, |v : &u64| *v);
+create_arrow_field_impl!(char, Utf8, |v : &char| v.to_string());
+create_arrow_field_impl!(String, Utf8, |v : &String| String::from(v.trim_end()));
+create_arrow_field_impl!(HaltReason, Utf8, |v : &HaltReason| v.code());
+
+impl<T : ArrowField> ArrowField for Option<T> {
+    fn column() -> Column {
+        T::column()
+    }
+
+    fn nullable() -> bool {
+        true
+    }
+
+    fn push( &self, col : &mut Column ) {
+        match *self {
+            Some(ref v) => v.push(col),
+            None        => match *col {
+                Column::UInt8(ref mut v)  => v.push(None),
+                Column::UInt32(ref mut v) => v.push(None),
+                Column::UInt64(ref mut v) => v.push(None),
+                Column::Utf8(ref mut v)   => v.push(None)
+            }
+        }
+    }
+}
+
+pub trait ArrowRecord {
+    fn table( &self ) -> &'static str;
+    fn columns( &self ) -> Vec<(&'static str, Column, bool)>;
+    fn append( &self, cols : &mut [Column] );
+}
+
+macro_rules! create_arrow_record_impl {
+    ($objname : ident, $table : expr, $($field : ident),+) => (
+        impl ArrowRecord for $objname {
+            fn table( &self ) -> &'static str {
+                $table
+            }
+
+            fn columns( &self ) -> Vec<(&'static str, Column, bool)> {
+                vec![$({ let (c, n) = column_for(&self.$field); (stringify!($field), c, n) }),+]
+            }
+
+            fn append( &self, cols : &mut [Column] ) {
+                let mut it = cols.iter_mut();
+                $(if let Some(c) = it.next() { self.$field.push(c); })+
+            }
+        }
+    )
+}
+
+create_arrow_record_impl!(AuctionSummaryMsg, "auction_summary", timestamp, msg_type, symbol, auction_type, price, shares);
+create_arrow_record_impl!(AddOrderMsg, "add_order", timestamp, msg_type, order_id, side, shares, symbol, price, display, part_id);
+create_arrow_record_impl!(AuctionUpdateMsg, "auction_update", timestamp, msg_type, symbol, auction_type, reference_price,
+                          buyshares, sellshares, indicative_price, auction_only_price);
+create_arrow_record_impl!(OrderCancelMsg, "order_cancel", timestamp, msg_type, order_id, shares);
+create_arrow_record_impl!(OrderExecutedMsg, "order_executed", timestamp, msg_type, order_id, shares, exec_id);
+create_arrow_record_impl!(RetailPriceImproveMsg, "retail_price_improve", timestamp, msg_type, symbol, retail_price_improve);
+create_arrow_record_impl!(TradeBreakMsg, "trade_break", timestamp, msg_type, exec_id);
+create_arrow_record_impl!(TradeMsg, "trade", timestamp, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_arrow_record_impl!(TradingStatusMsg, "trading_status", timestamp, msg_type, symbol, halt_status, reg_sho_action,
+                          halt_reason, halt_flag);
+create_arrow_record_impl!(SymbolClearMsg, "symbol_clear", timestamp, msg_type, symbol);
+create_arrow_record_impl!(BinaryAddOrderMsg, "binary_add_order", time_offset, msg_type, order_id, side, shares, symbol, price,
+                          display, part_id);
+create_arrow_record_impl!(BinaryOrderExecutedMsg, "binary_order_executed", time_offset, msg_type, order_id, shares, exec_id);
+create_arrow_record_impl!(ModifyOrderMsg, "modify_order", time_offset, msg_type, order_id, shares, price, display);
+create_arrow_record_impl!(DeleteOrderMsg, "delete_order", time_offset, msg_type, order_id);
+create_arrow_record_impl!(BinaryTradeMsg, "binary_trade", time_offset, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_arrow_record_impl!(ReduceSizeMsg, "reduce_size", time_offset, msg_type, order_id, shares);
+create_arrow_record_impl!(UnitClearMsg, "unit_clear", time_offset, msg_type);
+create_arrow_record_impl!(TradeExpandedMsg, "trade_expanded", time_offset, msg_type, order_id, side, shares, symbol,
+                          price, exec_id);
+create_arrow_record_impl!(OrderExecutedAtPriceSizeMsg, "order_executed_at_price_size", time_offset, msg_type, order_id,
+                          shares, remaining_shares, exec_id, price);
+create_arrow_record_impl!(CalculatedValueMsg, "calculated_value", time_offset, msg_type, symbol, value_category, value,
+                          value_timestamp);
+
+// None for messages that aren't collected, ie. the session ones.
+pub fn arrow_record( msg : &BATSMessage ) -> Option<&dyn ArrowRecord> {
+    match *msg {
+        BATSMessage::AuctionSummaryMsg(ref m)           => Some(m),
+        BATSMessage::AddOrderMsg(ref m)                 => Some(m),
+        BATSMessage::AuctionUpdateMsg(ref m)            => Some(m),
+        BATSMessage::OrderCancelMsg(ref m)              => Some(m),
+        BATSMessage::OrderExecutedMsg(ref m)            => Some(m),
+        BATSMessage::RetailPriceImproveMsg(ref m)       => Some(m),
+        BATSMessage::TradeBreakMsg(ref m)               => Some(m),
+        BATSMessage::TradeMsg(ref m)                    => Some(m),
+        BATSMessage::TradingStatusMsg(ref m)            => Some(m),
+        BATSMessage::SymbolClearMsg(ref m)              => Some(m),
+        BATSMessage::BinaryAddOrderMsg(ref m)           => Some(m),
+        BATSMessage::BinaryOrderExecutedMsg(ref m)      => Some(m),
+        BATSMessage::ReduceSizeMsg(ref m)               => Some(m),
+        BATSMessage::ModifyOrderMsg(ref m)              => Some(m),
+        BATSMessage::DeleteOrderMsg(ref m)              => Some(m),
+        BATSMessage::BinaryTradeMsg(ref m)              => Some(m),
+        BATSMessage::UnitClearMsg(ref m)                => Some(m),
+        BATSMessage::TradeExpandedMsg(ref m)            => Some(m),
+        BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => Some(m),
+        BATSMessage::CalculatedValueMsg(ref m)          => Some(m),
+        _ => None
+    }
+}
+
+struct TableBuilder {
+    schema  : Arc<Schema>,
+    columns : Vec<Column>
+}
+
+#[derive(Default)]
+pub struct ArrowCollector {
+    tables : HashMap<&'static str, TableBuilder>
+}
+
+impl ArrowCollector {
+    pub fn new() -> ArrowCollector {
+        ArrowCollector{ tables : HashMap::new() }
+    }
+
+    // returns false for messages that aren't collected.
+    pub fn push( &mut self, msg : &BATSMessage ) -> bool {
+        let rec = match arrow_record(msg) {
+            Some(r) => r,
+            None    => return false
+        };
+        let table = self.tables.entry(rec.table()).or_insert_with(|| {
+            let mut fields = vec![];
+            let mut columns = vec![];
+            for (name, col, nullable) in rec.columns() {
+                fields.push(Field::new(name, col.data_type(), nullable));
+                columns.push(col);
+            }
+            TableBuilder{ schema : Arc::new(Schema::new(fields)), columns }
+        });
+        rec.append(&mut table.columns);
+        true
+    }
+
+    pub fn extend<'a, I>( &mut self, msgs : I ) where I : IntoIterator<Item = &'a BATSMessage> {
+        for msg in msgs {
+            self.push(msg);
+        }
+    }
+
+    // rows collected so far across all the tables.
+    pub fn rows( &self ) -> usize {
+        self.tables.values().map(|t| t.columns.first().map_or(0, Column::len)).sum()
+    }
+
+    pub fn finish( mut self ) -> Result<HashMap<&'static str, RecordBatch>, ArrowError> {
+        self.take()
+    }
+
+    // the raw columns collected so far, as (table, [(column, values)]), starting over.
+    pub fn take_columns( &mut self ) -> Vec<(&'static str, Vec<(String, Column)>)> {
+        self.tables.drain().map(|(name, table)| {
+            let names = table.schema.fields().iter().map(|f| f.name().clone());
+            (name, names.zip(table.columns).collect())
+        }).collect()
+    }
+
+    // finishes the batches collected so far and starts over, eg. to stream batches out.
+    pub fn take( &mut self ) -> Result<HashMap<&'static str, RecordBatch>, ArrowError> {
+        let mut batches = HashMap::new();
+        for (name, table) in self.tables.drain() {
+            let arrays = table.columns.into_iter().map(Column::finish).collect();
+            batches.insert(name, RecordBatch::try_new(table.schema, arrays)?);
+        }
+        Ok(batches)
+    }
+}
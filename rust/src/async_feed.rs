@@ -0,0 +1,316 @@
+
+// The feed as tokio streams, for services already running an async runtime: the text reader,
+// the sequenced unit packets of a TCP session or a multicast group, and the book pipeline
+// driving either of them, all polled on the runtime rather than off on blocking threads.
+//
+//   let file = tokio::io::BufReader::new(tokio::fs::File::open("pitch.txt").await?);
+//   let applied = async_feed::apply_all(PitchStream::new(file), &mut books).await?;
+//
+// Each stream yields io::Result<FeedEvent>, with SessionComplete after an EndOfSessionMsg
+// (and at the end of a text file) as PitchReader has it. A line or packet that can't be
+// decoded is an InvalidData error rather than a panic, so one bad input doesn't take the
+// service down; the stream can go on being polled after it, unless it's lost its place in a
+// TCP session's packets.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::SocketAddrV4;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::Lines;
+use tokio::io::ReadBuf;
+use tokio::net::UdpSocket;
+use tokio_stream::Stream;
+
+use binary::split_packet;
+use binary::TimestampComposer;
+use book::BookManager;
+use event::MarketEvent;
+use feed::FeedEvent;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+use metrics::Metrics;
+use multicast;
+use multicast::decode_messages;
+use multicast::ReceiverStats;
+use multicast::Sequencer;
+
+fn invalid( what : String ) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+// queues the messages as events, each EndOfSessionMsg followed by a SessionComplete.
+fn queue( msgs : Vec<BATSMessage>, pending : &mut VecDeque<FeedEvent> ) {
+    for msg in msgs {
+        let complete = matches!(msg, BATSMessage::EndOfSessionMsg(_));
+        pending.push_back(FeedEvent::Message(msg));
+        if complete {
+            pending.push_back(FeedEvent::SessionComplete);
+        }
+    }
+}
+
+// PitchReader over an AsyncBufRead.
+pub struct PitchStream<R> {
+    lines            : Lines<R>,
+    pending_complete : bool,
+    session_complete : bool
+}
+
+impl<R : AsyncBufRead + Unpin> PitchStream<R> {
+    pub fn new( reader : R ) -> PitchStream<R> {
+        PitchStream{ lines : reader.lines(), pending_complete : false, session_complete : false }
+    }
+}
+
+impl<R : AsyncBufRead + Unpin> Stream for PitchStream<R> {
+    type Item = io::Result<FeedEvent>;
+
+    fn poll_next( self : Pin<&mut Self>, cx : &mut Context<'_> ) -> Poll<Option<io::Result<FeedEvent>>> {
+        let this = self.get_mut();
+        if this.session_complete {
+            return Poll::Ready(None);
+        }
+        if this.pending_complete {
+            this.session_complete = true;
+            return Poll::Ready(Some(Ok(FeedEvent::SessionComplete)));
+        }
+        loop {
+            match Pin::new(&mut this.lines).poll_next_line(cx) {
+                Poll::Pending               => return Poll::Pending,
+                Poll::Ready(Err(e))         => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Ok(Some(line))) => {
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let msg = match BATSMsgFactory::try_parse(&line) {
+                        Some(msg) => msg,
+                        None      => return Poll::Ready(Some(Err(invalid(format!("not a PITCH message: {:?}", line)))))
+                    };
+                    this.pending_complete = matches!(msg, BATSMessage::EndOfSessionMsg(_));
+                    return Poll::Ready(Some(Ok(FeedEvent::Message(msg))));
+                },
+                Poll::Ready(Ok(None)) => {
+                    this.session_complete = true;
+                    return Poll::Ready(Some(Ok(FeedEvent::SessionComplete)));
+                }
+            }
+        }
+    }
+}
+
+// The sequenced unit packets on an AsyncRead, one after another the way a TCP session sends
+// them (eg. a tokio TcpStream once logged in). Ends with the reader.
+pub struct PacketStream<R> {
+    reader  : R,
+    // what's been read and not yet decoded, the start of it a packet
+    buf     : Vec<u8>,
+    chunk   : Vec<u8>,
+    pending : VecDeque<FeedEvent>,
+    done    : bool,
+    metrics : Option<Arc<Metrics>>
+}
+
+impl<R : AsyncRead + Unpin> PacketStream<R> {
+    pub fn new( reader : R ) -> PacketStream<R> {
+        PacketStream{ reader, buf : Vec::new(), chunk : vec![0; 65536], pending : VecDeque::new(), done : false, metrics : None }
+    }
+
+    pub fn metrics( mut self, metrics : Arc<Metrics> ) -> PacketStream<R> {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn into_inner( self ) -> R {
+        self.reader
+    }
+
+    // decodes the packet at the start of buf if it's all there, saying whether it was.
+    fn decode( &mut self ) -> io::Result<bool> {
+        if self.buf.len() < 2 {
+            return Ok(false);
+        }
+        let len = u16::from_le_bytes([self.buf[0], self.buf[1]]) as usize;
+        if len < 8 {
+            // nothing after this can be found the start of
+            self.done = true;
+            return Err(invalid(format!("packet length {} is shorter than its header", len)));
+        }
+        if self.buf.len() < len {
+            return Ok(false);
+        }
+        let mut msgs = Vec::new();
+        let decoded = match split_packet(&self.buf[..len]) {
+            Ok((_, parts)) => { decode_messages(parts.into_iter(), self.metrics.as_deref(), &mut msgs); Ok(true) },
+            Err(_)         => Err(invalid(String::from("not a sequenced unit packet")))
+        };
+        self.buf.drain(..len);
+        queue(msgs, &mut self.pending);
+        decoded
+    }
+}
+
+impl<R : AsyncRead + Unpin> Stream for PacketStream<R> {
+    type Item = io::Result<FeedEvent>;
+
+    fn poll_next( self : Pin<&mut Self>, cx : &mut Context<'_> ) -> Poll<Option<io::Result<FeedEvent>>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match this.decode() {
+                Ok(true)  => continue,
+                Ok(false) => {},
+                Err(e)    => return Poll::Ready(Some(Err(e)))
+            }
+            let mut read = ReadBuf::new(&mut this.chunk);
+            match Pin::new(&mut this.reader).poll_read(cx, &mut read) {
+                Poll::Pending       => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Ok(())) => {
+                    let n = read.filled().len();
+                    if n == 0 {
+                        this.done = true;
+                        if !this.buf.is_empty() {
+                            return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the input ended inside a packet"))));
+                        }
+                    }
+                    this.buf.extend_from_slice(&this.chunk[..n]);
+                }
+            }
+        }
+    }
+}
+
+// MulticastReceiver on a tokio socket, one per port: packets seen before are dropped and
+// gaps counted as MulticastReceiver does it. Never ends.
+pub struct DatagramStream {
+    socket  : UdpSocket,
+    buf     : Vec<u8>,
+    pending : VecDeque<FeedEvent>,
+    seq     : Sequencer
+}
+
+impl DatagramStream {
+    // joined on `interface` to the groups on `port`. Needs to be called on the runtime.
+    pub fn join( port : u16, groups : &[SocketAddrV4], interface : Ipv4Addr ) -> io::Result<DatagramStream> {
+        Ok(DatagramStream::new(UdpSocket::from_std(multicast::bind(port, groups, interface)?)?))
+    }
+
+    pub fn new( socket : UdpSocket ) -> DatagramStream {
+        DatagramStream{ socket, buf : vec![0; 65536], pending : VecDeque::new(), seq : Sequencer::default() }
+    }
+
+    pub fn metrics( mut self, metrics : Arc<Metrics> ) -> DatagramStream {
+        self.seq.metrics = Some(metrics);
+        self
+    }
+
+    pub fn socket( &self ) -> &UdpSocket {
+        &self.socket
+    }
+
+    pub fn stats( &self ) -> ReceiverStats {
+        self.seq.stats
+    }
+}
+
+impl Stream for DatagramStream {
+    type Item = io::Result<FeedEvent>;
+
+    fn poll_next( self : Pin<&mut Self>, cx : &mut Context<'_> ) -> Poll<Option<io::Result<FeedEvent>>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            let mut read = ReadBuf::new(&mut this.buf);
+            match this.socket.poll_recv(cx, &mut read) {
+                Poll::Pending       => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Ok(())) => {
+                    let mut msgs = Vec::new();
+                    this.seq.packet(read.filled(), &mut msgs);
+                    queue(msgs, &mut this.pending);
+                }
+            }
+        }
+    }
+}
+
+// The messages of a feed stream as MarketEvents, timestamped nanoseconds past midnight. One
+// TimestampComposer is kept, so the stream should carry a single unit.
+pub struct MarketEvents<S> {
+    inner : S,
+    clock : TimestampComposer
+}
+
+pub fn market_events<S>( stream : S ) -> MarketEvents<S> where S : Stream<Item = io::Result<FeedEvent>> + Unpin {
+    MarketEvents{ inner : stream, clock : TimestampComposer::new() }
+}
+
+impl<S> Stream for MarketEvents<S> where S : Stream<Item = io::Result<FeedEvent>> + Unpin {
+    type Item = io::Result<MarketEvent>;
+
+    fn poll_next( self : Pin<&mut Self>, cx : &mut Context<'_> ) -> Poll<Option<io::Result<MarketEvent>>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(FeedEvent::Message(msg)))) => {
+                    let timestamp = this.clock.compose(&msg);
+                    if let Some(event) = MarketEvent::from_bats(&msg, timestamp) {
+                        return Poll::Ready(Some(Ok(event)));
+                    }
+                },
+                Poll::Ready(Some(Ok(FeedEvent::SessionComplete))) => {},
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None)         => return Poll::Ready(None),
+                Poll::Pending             => return Poll::Pending
+            }
+        }
+    }
+}
+
+// The future apply_all gives back.
+pub struct ApplyAll<'a, S> {
+    stream  : S,
+    books   : &'a mut BookManager,
+    applied : u64
+}
+
+// applies a feed stream's messages to the books up to the end of the session (or of the
+// stream), resolving to how many there were. Stops at the first error.
+pub fn apply_all<S>( stream : S, books : &mut BookManager ) -> ApplyAll<'_, S> where S : Stream<Item = io::Result<FeedEvent>> + Unpin {
+    ApplyAll{ stream, books, applied : 0 }
+}
+
+impl<'a, S> Future for ApplyAll<'a, S> where S : Stream<Item = io::Result<FeedEvent>> + Unpin {
+    type Output = io::Result<u64>;
+
+    fn poll( self : Pin<&mut Self>, cx : &mut Context<'_> ) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(FeedEvent::Message(msg)))) => {
+                    this.books.apply(&msg);
+                    this.applied += 1;
+                },
+                Poll::Ready(Some(Ok(FeedEvent::SessionComplete))) | Poll::Ready(None) => return Poll::Ready(Ok(this.applied)),
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                Poll::Pending             => return Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+
+// The auction a symbol is in (or last had): the prices and imbalance from its AuctionUpdateMsgs,
+// then the result once the AuctionSummaryMsg comes. A new auction type, or an update after the
+// summary, starts over.
+
+use messages::AuctionSummaryMsg;
+use messages::AuctionUpdateMsg;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuctionResult {
+    pub timestamp : u64,
+    pub price     : u64,
+    pub shares    : u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuctionState {
+    pub auction_type       : char,  // 'O'pening, 'C'losing, 'H'alt, 'I'PO, ...
+    pub reference_price    : u64,
+    pub indicative_price   : u64,
+    pub auction_only_price : u64,
+    pub buy_shares         : u32,
+    pub sell_shares        : u32,
+    pub updates            : u32,
+    pub updated            : u64,   // timestamp of the last update
+    pub result             : Option<AuctionResult>
+}
+
+impl AuctionState {
+    pub fn new( auction_type : char ) -> AuctionState {
+        AuctionState{ auction_type, reference_price : 0, indicative_price : 0, auction_only_price : 0, buy_shares : 0,
+                      sell_shares : 0, updates : 0, updated : 0, result : None }
+    }
+
+    fn continues( &self, auction_type : char ) -> bool {
+        self.auction_type == auction_type && self.result.is_none()
+    }
+
+    pub fn update( &mut self, msg : &AuctionUpdateMsg ) {
+        if !self.continues(msg.auction_type) {
+            *self = AuctionState::new(msg.auction_type);
+        }
+        self.reference_price = msg.reference_price;
+        self.indicative_price = msg.indicative_price;
+        self.auction_only_price = msg.auction_only_price;
+        self.buy_shares = msg.buyshares;
+        self.sell_shares = msg.sellshares;
+        self.updates += 1;
+        self.updated = u64::from(msg.timestamp);
+    }
+
+    // a summary for an auction no update was seen for still gives its result.
+    pub fn finalize( &mut self, msg : &AuctionSummaryMsg ) {
+        if !self.continues(msg.auction_type) {
+            *self = AuctionState::new(msg.auction_type);
+        }
+        self.result = Some(AuctionResult{ timestamp : u64::from(msg.timestamp), price : msg.price, shares : msg.shares });
+    }
+
+    // buy shares less sell shares, positive when buyers are left over.
+    pub fn imbalance( &self ) -> i64 {
+        i64::from(self.buy_shares) - i64::from(self.sell_shares)
+    }
+
+    pub fn is_final( &self ) -> bool {
+        self.result.is_some()
+    }
+}
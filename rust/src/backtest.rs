@@ -0,0 +1,499 @@
+
+// Would an order of ours have filled, and when? FillSimulator replays the day into its books
+// and puts hypothetical orders in among the real ones:
+//
+//   let mut sim = FillSimulator::new();
+//   for msg in msgs {
+//       if sim.time() >= time_of_day(10, 0, 0) && order.is_none() {
+//           order = Some(sim.place("AAPL", Side::Bid, 1831900, 100));
+//       }
+//       sim.apply(&msg);
+//   }
+//   for fill in sim.fills() { ... }
+//
+// An order priced through the other side takes what's displayed there at once, best price
+// first. The rest joins the back of its level (a QueuePosition) and fills as the real orders
+// ahead of it go and executions reach it, or straight away when the market trades through its
+// price. It's never in the books, so the real orders trade as reported: fine for orders small
+// next to the market, optimistic for big ones.
+//
+// With latencies set, an order reaches the book some time after place(): the market data
+// latency (how stale the book we decided on was) and then the order entry latency later, drawn
+// afresh for each order from a seeded generator so runs repeat. Whatever the feed does in the
+// meantime happens first. A cancel takes the order entry latency too, and fills until it lands
+// still count.
+//
+// Each fill is charged by a FeeModel as it happens, so net_pnl() is what the orders would have
+// made after fees (or with rebates) and gross_pnl() before. Fees are in the prices' own units,
+// ie. 30.0 a share is $0.003.
+
+use std::fmt;
+use std::time::Duration;
+
+use binary::TimestampComposer;
+use book::BookManager;
+use book::Side;
+use messages::BATSMessage;
+use queue::QueuePosition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimFill {
+    // what place() handed back
+    pub order      : usize,
+    // nanoseconds past midnight
+    pub timestamp  : u64,
+    pub price      : u64,
+    pub shares     : u32,
+    // took liquidity on entry, rather than being traded against
+    pub aggressive : bool
+}
+
+// splitmix64, enough for drawing latencies and the like without a dependency.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub fn new( seed : u64 ) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64( &mut self ) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut x = self.0;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^ (x >> 31)
+    }
+
+    // in [0, 1)
+    pub fn next_f64( &mut self ) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // standard normal, by Box-Muller
+    pub fn normal( &mut self ) -> f64 {
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (2.0 * ::std::f64::consts::PI * v).cos()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    Uniform { min : Duration, max : Duration },
+    // never less than zero
+    Normal { mean : Duration, sd : Duration }
+}
+
+impl Default for Latency {
+    fn default() -> Latency {
+        Latency::Fixed(Duration::from_secs(0))
+    }
+}
+
+impl Latency {
+    // in nanoseconds.
+    fn sample( &self, rng : &mut Rng ) -> u64 {
+        match *self {
+            Latency::Fixed(d)            => d.as_nanos() as u64,
+            Latency::Uniform{ min, max } => {
+                let (min, max) = (min.as_nanos() as f64, max.as_nanos() as f64);
+                (min + (max - min).max(0.0) * rng.next_f64()) as u64
+            },
+            Latency::Normal{ mean, sd }  => (mean.as_nanos() as f64 + sd.as_nanos() as f64 * rng.normal()).max(0.0) as u64
+        }
+    }
+
+    fn is_zero( &self ) -> bool {
+        *self == Latency::default()
+    }
+}
+
+// What a fill costs, in price units times shares; less than nothing for a rebate. Fills come
+// in the order they happen, so a model can keep count, eg. of volume for its tier. Closures
+// taking a fill are models too.
+pub trait FeeModel : Send {
+    fn fee( &mut self, fill : &SimFill ) -> f64;
+}
+
+impl<F : FnMut(&SimFill) -> f64 + Send> FeeModel for F {
+    fn fee( &mut self, fill : &SimFill ) -> f64 {
+        self(fill)
+    }
+}
+
+// So much a share for adding liquidity and so much for taking it, the maker's usually a rebate.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MakerTaker {
+    pub maker : f64,
+    pub taker : f64
+}
+
+impl MakerTaker {
+    pub fn new( maker : f64, taker : f64 ) -> MakerTaker {
+        MakerTaker{ maker, taker }
+    }
+
+    fn rate( &self, fill : &SimFill ) -> f64 {
+        if fill.aggressive { self.taker } else { self.maker }
+    }
+}
+
+impl FeeModel for MakerTaker {
+    fn fee( &mut self, fill : &SimFill ) -> f64 {
+        self.rate(fill) * f64::from(fill.shares)
+    }
+}
+
+// Maker/taker rates that get better with volume: a fill is charged at the tier for the shares
+// traded before it, counting those already done, eg. earlier in the month.
+#[derive(Debug, Clone)]
+pub struct Tiered {
+    // by the volume they start at
+    tiers  : Vec<(u64, MakerTaker)>,
+    volume : u64
+}
+
+impl Tiered {
+    pub fn new( base : MakerTaker ) -> Tiered {
+        Tiered{ tiers : vec![(0, base)], volume : 0 }
+    }
+
+    // the rates from `volume` shares on.
+    pub fn tier( mut self, volume : u64, rates : MakerTaker ) -> Tiered {
+        let i = self.tiers.partition_point(|&(v, _)| v < volume);
+        if self.tiers.get(i).is_some_and(|&(v, _)| v == volume) {
+            self.tiers[i].1 = rates;
+        } else {
+            self.tiers.insert(i, (volume, rates));
+        }
+        self
+    }
+
+    pub fn traded( mut self, volume : u64 ) -> Tiered {
+        self.volume = volume;
+        self
+    }
+
+    pub fn volume( &self ) -> u64 {
+        self.volume
+    }
+
+    fn rates( &self ) -> MakerTaker {
+        let i = self.tiers.partition_point(|&(v, _)| v <= self.volume);
+        self.tiers[i.max(1) - 1].1
+    }
+}
+
+impl FeeModel for Tiered {
+    fn fee( &mut self, fill : &SimFill ) -> f64 {
+        let fee = self.rates().rate(fill) * f64::from(fill.shares);
+        self.volume += u64::from(fill.shares);
+        fee
+    }
+}
+
+struct Fees(Box<dyn FeeModel>);
+
+impl fmt::Debug for Fees {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        write!(f, "Fees")
+    }
+}
+
+#[derive(Debug)]
+struct SimOrder {
+    symbol    : String,
+    side      : Side,
+    price     : u64,
+    shares    : u32,
+    filled    : u32,
+    // when it reaches the book, and its place there once it has
+    arrives   : u64,
+    queue     : Option<QueuePosition>,
+    // how much of the queue's fill has been reported
+    queued    : u32,
+    cancel_at : Option<u64>,
+    cancelled : bool
+}
+
+impl SimOrder {
+    fn open( &self ) -> bool {
+        !self.cancelled && self.filled < self.shares
+    }
+}
+
+#[derive(Debug)]
+pub struct FillSimulator {
+    books        : BookManager,
+    clock        : TimestampComposer,
+    time         : u64,
+    orders       : Vec<SimOrder>,
+    fills        : Vec<SimFill>,
+    // what each fill cost
+    fees         : Vec<f64>,
+    fee_model    : Fees,
+    market_data  : Latency,
+    order_entry  : Latency,
+    rng          : Rng
+}
+
+impl Default for FillSimulator {
+    fn default() -> FillSimulator {
+        FillSimulator{ books : BookManager::new(), clock : TimestampComposer::new(), time : 0, orders : Vec::new(), fills : Vec::new(),
+                       fees : Vec::new(), fee_model : Fees(Box::new(MakerTaker::default())), market_data : Latency::default(), order_entry : Latency::default(), rng : Rng::new(0) }
+    }
+}
+
+impl FillSimulator {
+    pub fn new() -> FillSimulator {
+        FillSimulator::default()
+    }
+
+    // for a manager set up beforehand, eg. with the book as it was at the open.
+    pub fn with_books( books : BookManager ) -> FillSimulator {
+        FillSimulator{ books, ..FillSimulator::default() }
+    }
+
+    // how far behind the exchange the book we see is.
+    pub fn market_data_latency( mut self, latency : Latency ) -> FillSimulator {
+        self.market_data = latency;
+        self
+    }
+
+    // how long an order or cancel takes to get to the exchange.
+    pub fn order_entry_latency( mut self, latency : Latency ) -> FillSimulator {
+        self.order_entry = latency;
+        self
+    }
+
+    // no fees by default.
+    pub fn fee_model<F : FeeModel + 'static>( mut self, model : F ) -> FillSimulator {
+        self.fee_model = Fees(Box::new(model));
+        self
+    }
+
+    // for the latencies drawn, 0 by default.
+    pub fn seed( mut self, seed : u64 ) -> FillSimulator {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    // a limit order at the time of the last message applied, handing back its id. It's on the
+    // book at once unless there's latency.
+    pub fn place( &mut self, symbol : &str, side : Side, price : u64, shares : u32 ) -> usize {
+        let id = self.orders.len();
+        let delay = self.market_data.sample(&mut self.rng) + self.order_entry.sample(&mut self.rng);
+        self.orders.push(SimOrder{ symbol : String::from(symbol.trim_end()), side, price, shares, filled : 0, arrives : self.time + delay,
+                                   queue : None, queued : 0, cancel_at : None, cancelled : false });
+        if delay == 0 {
+            self.arrive(id);
+        }
+        id
+    }
+
+    // when an order gets to the book, None for one that isn't there yet.
+    pub fn arrived( &self, order : usize ) -> Option<u64> {
+        self.orders.get(order).filter(|o| o.queue.is_some()).map(|o| o.arrives)
+    }
+
+    // the order takes what it crosses and joins its level, as the book is now.
+    fn arrive( &mut self, id : usize ) {
+        let (side, price, shares, at) = {
+            let o = &self.orders[id];
+            (o.side, o.price, o.shares, o.arrives)
+        };
+        let symbol = self.orders[id].symbol.clone();
+        let mut filled = 0;
+        let mut taken = Vec::new();
+        if let Some(book) = self.books.book(&symbol) {
+            let against = match side {
+                Side::Bid => book.asks(),
+                Side::Ask => book.bids()
+            };
+            for level in against {
+                let through = match side {
+                    Side::Bid => level.price() <= price,
+                    Side::Ask => level.price() >= price
+                };
+                if !through || filled == shares {
+                    break;
+                }
+                let take = (shares - filled).min(level.displayed_shares().min(u64::from(u32::MAX)) as u32);
+                if take > 0 {
+                    taken.push(SimFill{ order : id, timestamp : at, price : level.price(), shares : take, aggressive : true });
+                    filled += take;
+                }
+            }
+        }
+        for fill in taken {
+            self.record(fill);
+        }
+        let queue = match self.books.book(&symbol) {
+            Some(book) => QueuePosition::join(book, at, side, price, shares - filled),
+            None       => QueuePosition::join(&Default::default(), at, side, price, shares - filled)
+        };
+        let o = &mut self.orders[id];
+        o.filled = filled;
+        o.queue = Some(queue);
+    }
+
+    // takes what's left of an order off, once the cancel gets there. False if there was nothing
+    // left or it's already being cancelled.
+    pub fn cancel( &mut self, order : usize ) -> bool {
+        let delay = self.order_entry.sample(&mut self.rng);
+        let at = self.time + delay;
+        match self.orders.get_mut(order) {
+            Some(o) if o.open() && o.cancel_at.is_none() => {
+                o.cancel_at = Some(at);
+                o.cancelled = delay == 0;
+                true
+            },
+            _ => false
+        }
+    }
+
+    // orders and cancels that have got to the exchange by `now`, before its next message.
+    fn catch_up( &mut self, now : u64 ) {
+        let mut due : Vec<usize> = (0..self.orders.len()).filter(|&i| self.orders[i].queue.is_none() && self.orders[i].arrives <= now).collect();
+        due.sort_by_key(|&i| self.orders[i].arrives);
+        for i in due {
+            self.arrive(i);
+        }
+        for o in self.orders.iter_mut() {
+            if o.cancel_at.is_some_and(|t| t <= now) {
+                o.cancelled = true;
+            }
+        }
+    }
+
+    fn record( &mut self, fill : SimFill ) {
+        self.fees.push(self.fee_model.0.fee(&fill));
+        self.fills.push(fill);
+    }
+
+    fn fill( &mut self, order : usize, price : u64, shares : u32 ) {
+        let o = &mut self.orders[order];
+        let shares = shares.min(o.shares - o.filled);
+        if shares > 0 {
+            o.filled += shares;
+            self.record(SimFill{ order, timestamp : self.time, price, shares, aggressive : false });
+        }
+    }
+
+    // the next message of the day, the simulated orders seeing it before the books do.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        self.time = self.clock.compose(msg);
+        if !(self.market_data.is_zero() && self.order_entry.is_zero()) {
+            self.catch_up(self.time);
+        }
+        let symbol = self.books.symbol_for(msg).map(String::from);
+        if let Some(symbol) = symbol {
+            // an execution and the resting order it's against, as the books have it now
+            let executed = match *msg {
+                BATSMessage::OrderExecutedMsg(ref m) => Some((m.order_id, m.shares)),
+                BATSMessage::BinaryOrderExecutedMsg(ref m) => Some((m.order_id, m.shares)),
+                BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => Some((m.order_id, m.shares)),
+                _ => None
+            }.and_then(|(id, shares)| self.books.find_order(id).map(|o| (o.side, o.price, shares)));
+            for i in 0..self.orders.len() {
+                if !self.orders[i].open() || self.orders[i].queue.is_none() || self.orders[i].symbol != symbol {
+                    continue;
+                }
+                let (side, price) = (self.orders[i].side, self.orders[i].price);
+                if let Some(book) = self.books.book(&symbol) {
+                    let o = &mut self.orders[i];
+                    let queue = o.queue.as_mut().unwrap();
+                    queue.on_message(self.time, book, msg);
+                    let queued = queue.filled();
+                    let more = queued - o.queued;
+                    o.queued = queued;
+                    self.fill(i, price, more);
+                }
+                // someone sold below our bid (or bought above our offer), so we'd have gone first
+                if let Some((s, p, shares)) = executed {
+                    let through = s == side && match side {
+                        Side::Bid => p < price,
+                        Side::Ask => p > price
+                    };
+                    if through {
+                        self.fill(i, price, shares);
+                    }
+                }
+            }
+        }
+        self.books.apply(msg)
+    }
+
+    pub fn books( &self ) -> &BookManager {
+        &self.books
+    }
+
+    // of the last message applied, nanoseconds past midnight.
+    pub fn time( &self ) -> u64 {
+        self.time
+    }
+
+    // every fill so far, in the order they happened.
+    pub fn fills( &self ) -> &[SimFill] {
+        &self.fills
+    }
+
+    pub fn filled( &self, order : usize ) -> u32 {
+        self.orders.get(order).map_or(0, |o| o.filled)
+    }
+
+    // still working, ie. not filled or cancelled.
+    pub fn remaining( &self, order : usize ) -> u32 {
+        self.orders.get(order).filter(|o| !o.cancelled).map_or(0, |o| o.shares - o.filled)
+    }
+
+    // (shares, average price) filled, None before any fill.
+    pub fn average_price( &self, order : usize ) -> Option<(u32, f64)> {
+        let fills = self.fills.iter().filter(|f| f.order == order);
+        let (shares, notional) = fills.fold((0u64, 0f64), |(s, n), f| (s + u64::from(f.shares), n + f.price as f64 * f64::from(f.shares)));
+        if shares == 0 { None } else { Some((shares as u32, notional / shares as f64)) }
+    }
+
+    // what an order's fills cost, less than nothing if they earned rebates.
+    pub fn fees( &self, order : usize ) -> f64 {
+        self.fills.iter().zip(self.fees.iter()).filter(|&(f, _)| f.order == order).map(|(_, &fee)| fee).sum()
+    }
+
+    pub fn total_fees( &self ) -> f64 {
+        self.fees.iter().sum()
+    }
+
+    // shares bought less shares sold.
+    pub fn position( &self, symbol : &str ) -> i64 {
+        self.symbol_fills(symbol).map(|(side, f, _)| match side {
+            Side::Bid => i64::from(f.shares),
+            Side::Ask => -i64::from(f.shares)
+        }).sum()
+    }
+
+    // what's been made before fees, with the position left open valued at `mark`.
+    pub fn gross_pnl( &self, symbol : &str, mark : u64 ) -> f64 {
+        let cash : f64 = self.symbol_fills(symbol).map(|(side, f, _)| {
+            let notional = f.price as f64 * f64::from(f.shares);
+            match side {
+                Side::Bid => -notional,
+                Side::Ask => notional
+            }
+        }).sum();
+        cash + self.position(symbol) as f64 * mark as f64
+    }
+
+    pub fn net_pnl( &self, symbol : &str, mark : u64 ) -> f64 {
+        self.gross_pnl(symbol, mark) - self.symbol_fills(symbol).map(|(_, _, fee)| fee).sum::<f64>()
+    }
+
+    fn symbol_fills<'a>( &'a self, symbol : &'a str ) -> impl Iterator<Item = (Side, &'a SimFill, f64)> + 'a {
+        let symbol = symbol.trim_end();
+        self.fills.iter().zip(self.fees.iter()).filter_map(move |(f, &fee)| {
+            let o = &self.orders[f.order];
+            if o.symbol == symbol { Some((o.side, f, fee)) } else { None }
+        })
+    }
+}
@@ -0,0 +1,164 @@
+
+// OHLCV bars per symbol from the trades in the feed. Bars line up on the interval, eg. minute
+// bars start on the minute, and a bar is done once the clock (any message's time) passes its
+// end; it then goes to the listeners as BookEvent::Bar with its symbol.
+//
+// An interval without trades has no bar, unless fill_empty() is set: then the gaps between a
+// symbol's trades get flat bars at the previous close with no volume. Times are nanoseconds
+// past midnight.
+
+use std::collections::HashMap;
+use std::mem;
+
+use binary::TimestampComposer;
+use book::BookEvent;
+use book::BookListener;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarInterval {
+    Second,
+    Minute,
+    FiveMinutes
+}
+
+impl BarInterval {
+    pub fn nanos( self ) -> u64 {
+        match self {
+            BarInterval::Second      => 1_000_000_000,
+            BarInterval::Minute      => 60_000_000_000,
+            BarInterval::FiveMinutes => 300_000_000_000
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bar {
+    pub start  : u64,
+    pub open   : u64,
+    pub high   : u64,
+    pub low    : u64,
+    pub close  : u64,
+    pub volume : u64,
+    pub trades : u32
+}
+
+impl Bar {
+    fn flat( start : u64, price : u64 ) -> Bar {
+        Bar{ start, open : price, high : price, low : price, close : price, volume : 0, trades : 0 }
+    }
+
+    fn add( &mut self, price : u64, shares : u32 ) {
+        if self.trades == 0 {
+            self.open = price;
+            self.high = price;
+            self.low = price;
+        }
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += u64::from(shares);
+        self.trades += 1;
+    }
+}
+
+pub struct BarBuilder {
+    length     : u64,
+    fill_empty : bool,
+    clock      : TimestampComposer,
+    // where the bars that are open now end
+    end        : u64,
+    open       : HashMap<String, Bar>,
+    // each symbol's last close and the bar it was in, for filling gaps
+    closes     : HashMap<String, (u64, u64)>,
+    listeners  : Vec<Box<dyn BookListener>>
+}
+
+impl BarBuilder {
+    pub fn new( interval : BarInterval ) -> BarBuilder {
+        BarBuilder{ length : interval.nanos(), fill_empty : false, clock : TimestampComposer::new(), end : 0, open : HashMap::new(),
+                    closes : HashMap::new(), listeners : Vec::new() }
+    }
+
+    pub fn fill_empty( mut self, fill : bool ) -> BarBuilder {
+        self.fill_empty = fill;
+        self
+    }
+
+    pub fn add_listener<L : BookListener + 'static>( &mut self, listener : L ) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    // whether the message was a trade.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        let now = self.clock.compose(msg);
+        match *msg {
+            BATSMessage::TradeMsg(ref m) => {
+                self.record(m.symbol.trim_end(), now, m.price, m.shares);
+                true
+            },
+            BATSMessage::BinaryTradeMsg(ref m) => {
+                self.record(m.symbol.trim_end(), now, m.price, m.shares);
+                true
+            },
+            BATSMessage::TradeExpandedMsg(ref m) => {
+                self.record(m.symbol.trim_end(), now, m.price, m.shares);
+                true
+            },
+            _ => {
+                self.advance(now);
+                false
+            }
+        }
+    }
+
+    // a trade from elsewhere, eg. an execution against the book.
+    pub fn record( &mut self, symbol : &str, timestamp : u64, price : u64, shares : u32 ) {
+        self.advance(timestamp);
+        let start = timestamp - timestamp % self.length;
+        if self.fill_empty {
+            if let Some(&(from, close)) = self.closes.get(symbol) {
+                if !self.open.contains_key(symbol) {
+                    let mut gap = from + self.length;
+                    while gap < start {
+                        self.emit(symbol, Bar::flat(gap, close));
+                        gap += self.length;
+                    }
+                }
+            }
+        }
+        self.open.entry(String::from(symbol)).or_insert_with(|| Bar::flat(start, price)).add(price, shares);
+    }
+
+    // finishes the bars that end by `now`.
+    pub fn advance( &mut self, now : u64 ) {
+        if now < self.end {
+            return;
+        }
+        self.flush();
+        self.end = now - now % self.length + self.length;
+    }
+
+    // finishes the open bars, eg. at the end of the day. They go out by symbol.
+    pub fn flush( &mut self ) {
+        let mut bars : Vec<(String, Bar)> = mem::take(&mut self.open).into_iter().collect();
+        bars.sort_by(|a, b| a.0.cmp(&b.0));
+        for (symbol, bar) in bars {
+            self.emit(&symbol, bar);
+        }
+    }
+
+    // the bar still adding up.
+    pub fn current( &self, symbol : &str ) -> Option<&Bar> {
+        self.open.get(symbol.trim_end())
+    }
+
+    fn emit( &mut self, symbol : &str, bar : Bar ) {
+        self.closes.insert(String::from(symbol), (bar.start, bar.close));
+        let event = BookEvent::Bar(bar);
+        for l in &mut self.listeners {
+            l.on_event(Some(symbol), &event);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+
+// Records every change of each symbol's best bid and offer as a row of (timestamp, symbol,
+// bid, bid_size, ask, ask_size), kept column by column so a day's worth stays compact and goes
+// straight into Arrow. An empty side is a missing value: an empty CSV field, a null in Arrow.
+// Times are nanoseconds past midnight.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+
+#[cfg(feature = "arrow")]
+use arrow_array::RecordBatch;
+#[cfg(feature = "arrow")]
+use arrow_array::StringArray;
+#[cfg(feature = "arrow")]
+use arrow_array::UInt64Array;
+#[cfg(feature = "arrow")]
+use arrow_schema::ArrowError;
+#[cfg(feature = "arrow")]
+use arrow_schema::DataType;
+#[cfg(feature = "arrow")]
+use arrow_schema::Field;
+#[cfg(feature = "arrow")]
+use arrow_schema::Schema;
+
+use binary::TimestampComposer;
+use book::Bbo;
+use book::BookManager;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BboRow {
+    pub timestamp : u64,
+    pub bid       : Option<u64>,
+    pub bid_size  : Option<u64>,
+    pub ask       : Option<u64>,
+    pub ask_size  : Option<u64>
+}
+
+#[derive(Debug, Default)]
+pub struct BboRecorder {
+    clock      : TimestampComposer,
+    last       : HashMap<String, Bbo>,
+    timestamps : Vec<u64>,
+    symbols    : Vec<String>,
+    bids       : Vec<Option<u64>>,
+    bid_sizes  : Vec<Option<u64>>,
+    asks       : Vec<Option<u64>>,
+    ask_sizes  : Vec<Option<u64>>
+}
+
+fn field( v : Option<u64> ) -> String {
+    v.map(|v| v.to_string()).unwrap_or_default()
+}
+
+impl BboRecorder {
+    pub fn new() -> BboRecorder {
+        BboRecorder::default()
+    }
+
+    // applies the message to the books and records its book's bbo if that changed.
+    pub fn apply( &mut self, books : &mut BookManager, msg : &BATSMessage ) -> bool {
+        let now = self.clock.compose(msg);
+        let symbol = books.symbol_for(msg).map(String::from);
+        let changed = books.apply(msg);
+        if let Some(symbol) = symbol {
+            let bbo = books.book(&symbol).map(|b| b.bbo()).unwrap_or_default();
+            self.record(&symbol, now, bbo);
+        }
+        changed
+    }
+
+    // false when the bbo is what the symbol last had.
+    pub fn record( &mut self, symbol : &str, timestamp : u64, bbo : Bbo ) -> bool {
+        let symbol = symbol.trim_end();
+        if self.last.get(symbol) == Some(&bbo) || (bbo == Bbo::default() && !self.last.contains_key(symbol)) {
+            return false;
+        }
+        self.last.insert(String::from(symbol), bbo);
+        self.timestamps.push(timestamp);
+        self.symbols.push(String::from(symbol));
+        self.bids.push(bbo.bid.map(|q| q.price));
+        self.bid_sizes.push(bbo.bid.map(|q| q.shares));
+        self.asks.push(bbo.ask.map(|q| q.price));
+        self.ask_sizes.push(bbo.ask.map(|q| q.shares));
+        true
+    }
+
+    pub fn len( &self ) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    // one symbol's rows, oldest first.
+    pub fn rows<'a>( &'a self, symbol : &'a str ) -> impl Iterator<Item = BboRow> + 'a {
+        (0..self.len()).filter(move |&i| self.symbols[i] == symbol.trim_end())
+                       .map(move |i| BboRow{ timestamp : self.timestamps[i], bid : self.bids[i], bid_size : self.bid_sizes[i],
+                                             ask : self.asks[i], ask_size : self.ask_sizes[i] })
+    }
+
+    pub fn write_csv<W : Write>( &self, out : &mut W ) -> io::Result<()> {
+        writeln!(out, "timestamp,symbol,bid,bid_size,ask,ask_size")?;
+        for i in 0..self.len() {
+            writeln!(out, "{},{},{},{},{},{}", self.timestamps[i], self.symbols[i], field(self.bids[i]), field(self.bid_sizes[i]),
+                     field(self.asks[i]), field(self.ask_sizes[i]))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "arrow")]
+    pub fn to_record_batch( &self ) -> Result<RecordBatch, ArrowError> {
+        let schema = Schema::new(vec![Field::new("timestamp", DataType::UInt64, false),
+                                      Field::new("symbol", DataType::Utf8, false),
+                                      Field::new("bid", DataType::UInt64, true),
+                                      Field::new("bid_size", DataType::UInt64, true),
+                                      Field::new("ask", DataType::UInt64, true),
+                                      Field::new("ask_size", DataType::UInt64, true)]);
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(UInt64Array::from(self.timestamps.clone())),
+                                                    Arc::new(StringArray::from(self.symbols.clone())),
+                                                    Arc::new(UInt64Array::from(self.bids.clone())),
+                                                    Arc::new(UInt64Array::from(self.bid_sizes.clone())),
+                                                    Arc::new(UInt64Array::from(self.asks.clone())),
+                                                    Arc::new(UInt64Array::from(self.ask_sizes.clone()))])
+    }
+
+    pub fn clear( &mut self ) {
+        *self = BboRecorder::default();
+    }
+}
@@ -0,0 +1,939 @@
+
+use nom;
+use nom::{le_u8, le_u16, le_u32, le_u64};
+
+use std::result::Result;
+use std::str;
+
+use messages::BATSMessage;
+
+// Parsers for the binary (multicast/TCP) flavour of PITCH. Every binary message
+// starts with a 1 byte length and a 1 byte message type, followed by a 4 byte
+// time offset (nanoseconds past the last Time message). All integers are little endian.
+
+macro_rules! create_binary_parse_impl {
+    ($objname : ident, $parse_func : ident) => (
+        impl $objname {
+            pub fn parse_binary( msg : &[u8] ) -> Result<$objname, nom::Err<&[u8]>> {
+                $parse_func(msg).map(|o| o.1)
+            }
+        }
+    )
+}
+
+pub const ADD_ORDER_LONG     : u8 = 0x21;
+pub const ADD_ORDER_SHORT    : u8 = 0x22;
+pub const ADD_ORDER_EXPANDED : u8 = 0x2F;
+pub const ORDER_EXECUTED     : u8 = 0x23;
+pub const REDUCE_SIZE_LONG  : u8 = 0x25;
+pub const REDUCE_SIZE_SHORT : u8 = 0x26;
+pub const MODIFY_ORDER_LONG  : u8 = 0x27;
+pub const MODIFY_ORDER_SHORT : u8 = 0x28;
+pub const DELETE_ORDER       : u8 = 0x29;
+pub const TRADE_LONG         : u8 = 0x2A;
+pub const TRADE_SHORT        : u8 = 0x2B;
+pub const UNIT_CLEAR        : u8 = 0x97;
+pub const TIME              : u8 = 0x20;
+pub const TRADE_EXPANDED    : u8 = 0x30;
+pub const ORDER_EXECUTED_AT_PRICE_SIZE : u8 = 0x24;
+pub const CALCULATED_VALUE  : u8 = 0xE3;
+pub const END_OF_SESSION    : u8 = 0x2D;
+pub const LOGIN             : u8 = 0x01;
+pub const LOGIN_RESPONSE    : u8 = 0x02;
+
+#[derive(Debug)]
+pub struct SequencedUnitHeader { // precedes every packet of messages on the multicast feed
+    pub length   : u16,
+    pub count    : u8,
+    pub unit     : u8,
+    pub sequence : u32
+}
+
+impl SequencedUnitHeader {
+    pub fn parse_binary( msg : &[u8] ) -> Result<SequencedUnitHeader, nom::Err<&[u8]>> {
+        parse_sequenced_unit_header(msg).map(|o| o.1)
+    }
+
+    // the server heartbeat on a TCP session is just a header with no messages, session level
+    // messages go out on unit 0 with sequence 0.
+    pub fn heartbeat() -> SequencedUnitHeader {
+        SequencedUnitHeader{ length : 8, count : 0, unit : 0, sequence : 0 }
+    }
+
+    pub fn is_heartbeat( &self ) -> bool {
+        self.count == 0
+    }
+
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(8);
+        buf.extend_from_slice(&self.length.to_le_bytes());
+        buf.push(self.count);
+        buf.push(self.unit);
+        buf.extend_from_slice(&self.sequence.to_le_bytes());
+        buf
+    }
+}
+
+// wraps session level messages (eg. a LoginMsg) into a packet ready to be written to the socket.
+pub fn encode_packet( msgs : &[Vec<u8>] ) -> Vec<u8> {
+    let length : usize = 8 + msgs.iter().map(|m| m.len()).sum::<usize>();
+    let header = SequencedUnitHeader{ length : length as u16, count : msgs.len() as u8, unit : 0, sequence : 0 };
+    let mut buf = header.to_bytes();
+    for m in msgs {
+        buf.extend_from_slice(m);
+    }
+    buf
+}
+
+pub type Packet<'a> = (SequencedUnitHeader, Vec<&'a [u8]>);
+
+// splits a packet into its header and the individual messages using the length byte
+// carried by each one, the slices can then be handed to BATSMsgFactory::parse_binary.
+pub fn split_packet( packet : &[u8] ) -> Result<Packet<'_>, nom::Err<&[u8]>> {
+    let (mut rest, header) = parse_sequenced_unit_header(packet)?;
+    let mut msgs = Vec::with_capacity(header.count as usize);
+    for _ in 0..header.count {
+        let (remaining, msg) = parse_length_prefixed(rest)?;
+        msgs.push(msg);
+        rest = remaining;
+    }
+    Ok((header, msgs))
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeMsg { // subsequent messages on the unit carry an offset from this time
+    pub msg_type : u8,
+    pub time     : u32 // seconds past midnight
+}
+
+// Binary messages only carry a nanosecond offset from the last TimeMsg seen on their unit,
+// so we need to keep track of the time to get a comparable timestamp. Keep one composer
+// per unit as each unit sends its own TimeMsgs.
+#[derive(Debug, Default)]
+pub struct TimestampComposer {
+    seconds : u32
+}
+
+impl TimestampComposer {
+    pub fn new() -> TimestampComposer {
+        TimestampComposer{ seconds : 0 }
+    }
+
+    // returns nanoseconds past midnight, updating the current time on a TimeMsg.
+    pub fn compose( &mut self, msg : &BATSMessage ) -> u64 {
+        let base = u64::from(self.seconds) * 1_000_000_000;
+        match *msg {
+            BATSMessage::TimeMsg(ref m) => {
+                self.seconds = m.time;
+                u64::from(m.time) * 1_000_000_000
+            },
+            BATSMessage::BinaryAddOrderMsg(ref m)      => base + u64::from(m.time_offset),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => base + u64::from(m.time_offset),
+            BATSMessage::ModifyOrderMsg(ref m)         => base + u64::from(m.time_offset),
+            BATSMessage::DeleteOrderMsg(ref m)         => base + u64::from(m.time_offset),
+            BATSMessage::BinaryTradeMsg(ref m)         => base + u64::from(m.time_offset),
+            BATSMessage::ReduceSizeMsg(ref m) => base + u64::from(m.time_offset),
+            BATSMessage::UnitClearMsg(ref m)  => base + u64::from(m.time_offset),
+            BATSMessage::TradeExpandedMsg(ref m) => base + u64::from(m.time_offset),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => base + u64::from(m.time_offset),
+            BATSMessage::CalculatedValueMsg(ref m) => base + u64::from(m.time_offset),
+            BATSMessage::EndOfSessionMsg(ref m)    => base + u64::from(m.time_offset),
+            // session messages carry no time, just report the current time on the unit.
+            BATSMessage::LoginMsg(_)         => base,
+            BATSMessage::LoginResponseMsg(_) => base,
+            BATSMessage::SymbolMappingMsg(_) => base,
+            // text messages carry milliseconds past midnight.
+            BATSMessage::AuctionSummaryMsg(ref m)     => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::AddOrderMsg(ref m)           => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::AuctionUpdateMsg(ref m)      => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::OrderCancelMsg(ref m)        => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::OrderExecutedMsg(ref m)      => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::RetailPriceImproveMsg(ref m) => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::TradeBreakMsg(ref m)         => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::TradeMsg(ref m)              => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::TradingStatusMsg(ref m)      => u64::from(m.timestamp) * 1_000_000,
+            BATSMessage::SymbolClearMsg(ref m)        => u64::from(m.timestamp) * 1_000_000,
+            #[cfg(feature = "europe")]
+            BATSMessage::TradeReportMsg(ref m)        => u64::from(m.timestamp) * 1_000_000,
+        }
+    }
+}
+
+// Prices below are 4 implied decimals like the text messages', the short forms' 2 decimal
+// prices are scaled up when they're parsed.
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BinaryAddOrderMsg { // binary equivalent of AddOrderMsg, long, short or expanded
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64,
+    pub side        : char,
+    pub shares      : u32,
+    pub symbol      : String,
+    pub price       : u64,
+    pub display     : char,  // 'Y' when the add flags' display bit is set
+    pub part_id     : String // only on the expanded format
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BinaryOrderExecutedMsg { // binary equivalent of OrderExecutedMsg
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64,
+    pub shares      : u32,
+    pub exec_id     : u64
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ModifyOrderMsg { // new shares and price for a resting order, long or short
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64,
+    pub shares      : u32,
+    pub price       : u64,
+    pub display     : char
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeleteOrderMsg { // the whole order is gone
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BinaryTradeMsg { // binary equivalent of TradeMsg, long or short
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64,
+    pub side        : char,
+    pub shares      : u32,
+    pub symbol      : String,
+    pub price       : u64,
+    pub exec_id     : u64
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TradeExpandedMsg { // trade against a non-displayed order, long symbol/ids version of TradeMsg
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64,
+    pub side        : char,
+    pub shares      : u32,
+    pub symbol      : String,
+    pub price       : u64,
+    pub exec_id     : u64
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OrderExecutedAtPriceSizeMsg { // execution at a price other than the resting order's
+    pub time_offset      : u32,
+    pub msg_type         : u8,
+    pub order_id         : u64,
+    pub shares           : u32,
+    pub remaining_shares : u32,
+    pub exec_id          : u64,
+    pub price            : u64
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CalculatedValueMsg { // eg. ETF NAV/IIV values published on the feed
+    pub time_offset     : u32,
+    pub msg_type        : u8,
+    pub symbol          : String,
+    pub value_category  : char,
+    pub value           : u64,
+    pub value_timestamp : u64 // nanoseconds since epoch at which the value was calculated
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EndOfSessionMsg { // last message sent on the unit for the day
+    pub time_offset : u32,
+    pub msg_type    : u8
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LoginMsg { // sent by the client to log into a TCP session
+    pub msg_type       : u8,
+    pub session_sub_id : String,
+    pub username       : String,
+    pub password       : String
+}
+
+pub(crate) fn pad_ascii( buf : &mut Vec<u8>, field : &str, width : usize ) {
+    // alphanumeric fields are left justified and space padded
+    let bytes = field.as_bytes();
+    let n = bytes.len().min(width);
+    buf.extend_from_slice(&bytes[..n]);
+    buf.extend(::std::iter::repeat_n(b' ', width - n));
+}
+
+impl LoginMsg {
+    pub fn new( session_sub_id : &str, username : &str, password : &str ) -> LoginMsg {
+        LoginMsg{ msg_type       : LOGIN, 
+                  session_sub_id : String::from(session_sub_id),
+                  username       : String::from(username),
+                  password       : String::from(password)
+                }
+    }
+
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![22, LOGIN];
+        pad_ascii(&mut buf, &self.session_sub_id, 4);
+        pad_ascii(&mut buf, &self.username, 4);
+        pad_ascii(&mut buf, "", 2);
+        pad_ascii(&mut buf, &self.password, 10);
+        buf
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "char", from = "char"))]
+pub enum LoginStatus {
+    Accepted,
+    NotAuthorized,      // invalid username/password
+    SessionInUse,
+    InvalidSession,
+    Unknown(char)
+}
+
+impl From<char> for LoginStatus {
+    fn from(c : char) -> LoginStatus {
+        match c {
+            'A' => LoginStatus::Accepted,
+            'N' => LoginStatus::NotAuthorized,
+            'B' => LoginStatus::SessionInUse,
+            'S' => LoginStatus::InvalidSession,
+            _   => LoginStatus::Unknown(c)
+        }
+    }
+}
+
+impl From<LoginStatus> for char {
+    fn from(status : LoginStatus) -> char {
+        match status {
+            LoginStatus::Accepted       => 'A',
+            LoginStatus::NotAuthorized  => 'N',
+            LoginStatus::SessionInUse   => 'B',
+            LoginStatus::InvalidSession => 'S',
+            LoginStatus::Unknown(c)     => c
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LoginResponseMsg { // sent by the server in reply to a LoginMsg
+    pub msg_type : u8,
+    pub status   : LoginStatus
+}
+
+impl LoginResponseMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        vec![3, LOGIN_RESPONSE, char::from(self.status) as u8]
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnitClearMsg { // every order on the matching unit should be dropped
+    pub time_offset : u32,
+    pub msg_type    : u8
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReduceSizeMsg { // binary equivalent of OrderCancelMsg, ie. a partial cancel
+    pub time_offset : u32,
+    pub msg_type    : u8,
+    pub order_id    : u64,
+    pub shares      : u32
+}
+
+create_binary_parse_impl!(BinaryAddOrderMsg, parse_add_order);
+create_binary_parse_impl!(BinaryOrderExecutedMsg, parse_order_executed);
+create_binary_parse_impl!(ModifyOrderMsg, parse_modify_order);
+create_binary_parse_impl!(DeleteOrderMsg, parse_delete_order);
+create_binary_parse_impl!(BinaryTradeMsg, parse_trade);
+create_binary_parse_impl!(ReduceSizeMsg, parse_reduce_size);
+create_binary_parse_impl!(UnitClearMsg, parse_unit_clear);
+create_binary_parse_impl!(TimeMsg, parse_time);
+create_binary_parse_impl!(TradeExpandedMsg, parse_trade_expanded);
+create_binary_parse_impl!(OrderExecutedAtPriceSizeMsg, parse_order_executed_at_price_size);
+create_binary_parse_impl!(CalculatedValueMsg, parse_calculated_value);
+create_binary_parse_impl!(EndOfSessionMsg, parse_end_of_session);
+create_binary_parse_impl!(LoginMsg, parse_login);
+create_binary_parse_impl!(LoginResponseMsg, parse_login_response);
+
+// The messages as they are on the wire, length byte first, ready for encode_packet.
+
+fn flag( display : char ) -> u8 {
+    if display == 'Y' { 1 } else { 0 }
+}
+
+fn display( flags : u8 ) -> char {
+    if flags & 1 == 1 { 'Y' } else { 'N' }
+}
+
+impl BinaryAddOrderMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![0, self.msg_type];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        buf.push(self.side as u8);
+        match self.msg_type {
+            ADD_ORDER_SHORT => {
+                buf.extend_from_slice(&(self.shares as u16).to_le_bytes());
+                pad_ascii(&mut buf, &self.symbol, 6);
+                buf.extend_from_slice(&((self.price / 100) as u16).to_le_bytes());
+            },
+            ADD_ORDER_EXPANDED => {
+                buf.extend_from_slice(&self.shares.to_le_bytes());
+                pad_ascii(&mut buf, &self.symbol, 8);
+                buf.extend_from_slice(&self.price.to_le_bytes());
+            },
+            _ => {
+                buf.extend_from_slice(&self.shares.to_le_bytes());
+                pad_ascii(&mut buf, &self.symbol, 6);
+                buf.extend_from_slice(&self.price.to_le_bytes());
+            }
+        }
+        buf.push(flag(self.display));
+        if self.msg_type == ADD_ORDER_EXPANDED {
+            pad_ascii(&mut buf, &self.part_id, 4);
+        }
+        buf[0] = buf.len() as u8;
+        buf
+    }
+}
+
+impl BinaryOrderExecutedMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![26, ORDER_EXECUTED];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        buf.extend_from_slice(&self.shares.to_le_bytes());
+        buf.extend_from_slice(&self.exec_id.to_le_bytes());
+        buf
+    }
+}
+
+impl ModifyOrderMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let short = self.msg_type == MODIFY_ORDER_SHORT;
+        let mut buf = vec![if short { 19 } else { 27 }, self.msg_type];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        if short {
+            buf.extend_from_slice(&(self.shares as u16).to_le_bytes());
+            buf.extend_from_slice(&((self.price / 100) as u16).to_le_bytes());
+        } else {
+            buf.extend_from_slice(&self.shares.to_le_bytes());
+            buf.extend_from_slice(&self.price.to_le_bytes());
+        }
+        buf.push(flag(self.display));
+        buf
+    }
+}
+
+impl DeleteOrderMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![14, DELETE_ORDER];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        buf
+    }
+}
+
+impl BinaryTradeMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let short = self.msg_type == TRADE_SHORT;
+        let mut buf = vec![if short { 33 } else { 41 }, self.msg_type];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        buf.push(self.side as u8);
+        if short {
+            buf.extend_from_slice(&(self.shares as u16).to_le_bytes());
+            pad_ascii(&mut buf, &self.symbol, 6);
+            buf.extend_from_slice(&((self.price / 100) as u16).to_le_bytes());
+        } else {
+            buf.extend_from_slice(&self.shares.to_le_bytes());
+            pad_ascii(&mut buf, &self.symbol, 6);
+            buf.extend_from_slice(&self.price.to_le_bytes());
+        }
+        buf.extend_from_slice(&self.exec_id.to_le_bytes());
+        buf
+    }
+}
+
+impl ReduceSizeMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let short = self.msg_type == REDUCE_SIZE_SHORT;
+        let mut buf = vec![if short { 16 } else { 18 }, self.msg_type];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        if short {
+            buf.extend_from_slice(&(self.shares as u16).to_le_bytes());
+        } else {
+            buf.extend_from_slice(&self.shares.to_le_bytes());
+        }
+        buf
+    }
+}
+
+impl UnitClearMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![6, UNIT_CLEAR];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf
+    }
+}
+
+impl TimeMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![6, TIME];
+        buf.extend_from_slice(&self.time.to_le_bytes());
+        buf
+    }
+}
+
+impl TradeExpandedMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![43, TRADE_EXPANDED];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        buf.push(self.side as u8);
+        buf.extend_from_slice(&self.shares.to_le_bytes());
+        pad_ascii(&mut buf, &self.symbol, 8);
+        buf.extend_from_slice(&self.price.to_le_bytes());
+        buf.extend_from_slice(&self.exec_id.to_le_bytes());
+        buf
+    }
+}
+
+impl OrderExecutedAtPriceSizeMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![38, ORDER_EXECUTED_AT_PRICE_SIZE];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf.extend_from_slice(&self.order_id.to_le_bytes());
+        buf.extend_from_slice(&self.shares.to_le_bytes());
+        buf.extend_from_slice(&self.remaining_shares.to_le_bytes());
+        buf.extend_from_slice(&self.exec_id.to_le_bytes());
+        buf.extend_from_slice(&self.price.to_le_bytes());
+        buf
+    }
+}
+
+impl CalculatedValueMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![31, CALCULATED_VALUE];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        pad_ascii(&mut buf, &self.symbol, 8);
+        buf.push(self.value_category as u8);
+        buf.extend_from_slice(&self.value.to_le_bytes());
+        buf.extend_from_slice(&self.value_timestamp.to_le_bytes());
+        buf
+    }
+}
+
+impl EndOfSessionMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![6, END_OF_SESSION];
+        buf.extend_from_slice(&self.time_offset.to_le_bytes());
+        buf
+    }
+}
+
+fn from_ascii( input : &[u8] ) -> Result<String, str::Utf8Error> {
+    str::from_utf8(input).map(String::from)
+}
+
+named!(parse_sequenced_unit_header<&[u8], SequencedUnitHeader>,
+    do_parse!(
+        _1 : le_u16 >>
+        _2 : le_u8  >>
+        _3 : le_u8  >>
+        _4 : le_u32 >>
+        (SequencedUnitHeader{ length   : _1,
+                              count    : _2,
+                              unit     : _3,
+                              sequence : _4
+                            })
+    )
+);
+
+named!(parse_length_prefixed<&[u8], &[u8]>,
+    do_parse!(
+        _1 : peek!(le_u8)     >>
+        _2 : take!(_1)        >>
+        (_2)
+    )
+);
+
+named!(parse_add_order_long<&[u8], BinaryAddOrderMsg>,
+    do_parse!(
+        _1 : le_u8                                         >>
+        _2 : verify!(le_u8, |t : u8| t == ADD_ORDER_LONG)  >>
+        _3 : le_u32                                        >>
+        _4 : le_u64                                        >>
+        _5 : map!(le_u8, char::from)                       >>
+        _6 : le_u32                                        >>
+        _7 : map_res!(take!(6), from_ascii)                >>
+        _8 : le_u64                                        >>
+        _9 : map!(le_u8, display)                          >>
+        (BinaryAddOrderMsg{ time_offset : _3,
+                            msg_type    : _2,
+                            order_id    : _4,
+                            side        : _5,
+                            shares      : _6,
+                            symbol      : _7,
+                            price       : _8,
+                            display     : _9,
+                            part_id     : String::new()
+                        })
+    )
+);
+
+named!(parse_add_order_short<&[u8], BinaryAddOrderMsg>,
+    do_parse!(
+        _1 : le_u8                                         >>
+        _2 : verify!(le_u8, |t : u8| t == ADD_ORDER_SHORT) >>
+        _3 : le_u32                                        >>
+        _4 : le_u64                                        >>
+        _5 : map!(le_u8, char::from)                       >>
+        _6 : le_u16                                        >>
+        _7 : map_res!(take!(6), from_ascii)                >>
+        _8 : le_u16                                        >>
+        _9 : map!(le_u8, display)                          >>
+        (BinaryAddOrderMsg{ time_offset : _3,
+                            msg_type    : _2,
+                            order_id    : _4,
+                            side        : _5,
+                            shares      : u32::from(_6),
+                            symbol      : _7,
+                            price       : u64::from(_8) * 100,
+                            display     : _9,
+                            part_id     : String::new()
+                        })
+    )
+);
+
+named!(parse_add_order_expanded<&[u8], BinaryAddOrderMsg>,
+    do_parse!(
+        _1  : le_u8                                            >>
+        _2  : verify!(le_u8, |t : u8| t == ADD_ORDER_EXPANDED) >>
+        _3  : le_u32                                           >>
+        _4  : le_u64                                           >>
+        _5  : map!(le_u8, char::from)                          >>
+        _6  : le_u32                                           >>
+        _7  : map_res!(take!(8), from_ascii)                   >>
+        _8  : le_u64                                           >>
+        _9  : map!(le_u8, display)                             >>
+        _10 : map_res!(take!(4), from_ascii)                   >>
+        (BinaryAddOrderMsg{ time_offset : _3,
+                            msg_type    : _2,
+                            order_id    : _4,
+                            side        : _5,
+                            shares      : _6,
+                            symbol      : _7,
+                            price       : _8,
+                            display     : _9,
+                            part_id     : _10
+                        })
+    )
+);
+
+named!(parse_add_order<&[u8], BinaryAddOrderMsg>,
+    alt!(parse_add_order_long | parse_add_order_short | parse_add_order_expanded)
+);
+
+named!(parse_order_executed<&[u8], BinaryOrderExecutedMsg>,
+    do_parse!(
+        _1 : le_u8                                        >>
+        _2 : verify!(le_u8, |t : u8| t == ORDER_EXECUTED) >>
+        _3 : le_u32                                       >>
+        _4 : le_u64                                       >>
+        _5 : le_u32                                       >>
+        _6 : le_u64                                       >>
+        (BinaryOrderExecutedMsg{ time_offset : _3,
+                                 msg_type    : _2,
+                                 order_id    : _4,
+                                 shares      : _5,
+                                 exec_id     : _6
+                               })
+    )
+);
+
+named!(parse_modify_order_long<&[u8], ModifyOrderMsg>,
+    do_parse!(
+        _1 : le_u8                                            >>
+        _2 : verify!(le_u8, |t : u8| t == MODIFY_ORDER_LONG)  >>
+        _3 : le_u32                                           >>
+        _4 : le_u64                                           >>
+        _5 : le_u32                                           >>
+        _6 : le_u64                                           >>
+        _7 : map!(le_u8, display)                             >>
+        (ModifyOrderMsg{ time_offset : _3,
+                         msg_type    : _2,
+                         order_id    : _4,
+                         shares      : _5,
+                         price       : _6,
+                         display     : _7
+                       })
+    )
+);
+
+named!(parse_modify_order_short<&[u8], ModifyOrderMsg>,
+    do_parse!(
+        _1 : le_u8                                            >>
+        _2 : verify!(le_u8, |t : u8| t == MODIFY_ORDER_SHORT) >>
+        _3 : le_u32                                           >>
+        _4 : le_u64                                           >>
+        _5 : le_u16                                           >>
+        _6 : le_u16                                           >>
+        _7 : map!(le_u8, display)                             >>
+        (ModifyOrderMsg{ time_offset : _3,
+                         msg_type    : _2,
+                         order_id    : _4,
+                         shares      : u32::from(_5),
+                         price       : u64::from(_6) * 100,
+                         display     : _7
+                       })
+    )
+);
+
+named!(parse_modify_order<&[u8], ModifyOrderMsg>,
+    alt!(parse_modify_order_long | parse_modify_order_short)
+);
+
+named!(parse_delete_order<&[u8], DeleteOrderMsg>,
+    do_parse!(
+        _1 : le_u8                                      >>
+        _2 : verify!(le_u8, |t : u8| t == DELETE_ORDER) >>
+        _3 : le_u32                                     >>
+        _4 : le_u64                                     >>
+        (DeleteOrderMsg{ time_offset : _3,
+                         msg_type    : _2,
+                         order_id    : _4
+                       })
+    )
+);
+
+named!(parse_trade_long<&[u8], BinaryTradeMsg>,
+    do_parse!(
+        _1 : le_u8                                     >>
+        _2 : verify!(le_u8, |t : u8| t == TRADE_LONG)  >>
+        _3 : le_u32                                    >>
+        _4 : le_u64                                    >>
+        _5 : map!(le_u8, char::from)                   >>
+        _6 : le_u32                                    >>
+        _7 : map_res!(take!(6), from_ascii)            >>
+        _8 : le_u64                                    >>
+        _9 : le_u64                                    >>
+        (BinaryTradeMsg{ time_offset : _3,
+                         msg_type    : _2,
+                         order_id    : _4,
+                         side        : _5,
+                         shares      : _6,
+                         symbol      : _7,
+                         price       : _8,
+                         exec_id     : _9
+                       })
+    )
+);
+
+named!(parse_trade_short<&[u8], BinaryTradeMsg>,
+    do_parse!(
+        _1 : le_u8                                     >>
+        _2 : verify!(le_u8, |t : u8| t == TRADE_SHORT) >>
+        _3 : le_u32                                    >>
+        _4 : le_u64                                    >>
+        _5 : map!(le_u8, char::from)                   >>
+        _6 : le_u16                                    >>
+        _7 : map_res!(take!(6), from_ascii)            >>
+        _8 : le_u16                                    >>
+        _9 : le_u64                                    >>
+        (BinaryTradeMsg{ time_offset : _3,
+                         msg_type    : _2,
+                         order_id    : _4,
+                         side        : _5,
+                         shares      : u32::from(_6),
+                         symbol      : _7,
+                         price       : u64::from(_8) * 100,
+                         exec_id     : _9
+                       })
+    )
+);
+
+named!(parse_trade<&[u8], BinaryTradeMsg>,
+    alt!(parse_trade_long | parse_trade_short)
+);
+
+named!(parse_reduce_size_long<&[u8], ReduceSizeMsg>,
+    do_parse!(
+        _1 : le_u8                                           >>
+        _2 : verify!(le_u8, |t : u8| t == REDUCE_SIZE_LONG)  >>
+        _3 : le_u32                                          >>
+        _4 : le_u64                                          >>
+        _5 : le_u32                                          >>
+        (ReduceSizeMsg{ time_offset : _3,
+                        msg_type    : _2,
+                        order_id    : _4,
+                        shares      : _5
+                    })
+    )
+);
+
+named!(parse_reduce_size_short<&[u8], ReduceSizeMsg>,
+    do_parse!(
+        _1 : le_u8                                           >>
+        _2 : verify!(le_u8, |t : u8| t == REDUCE_SIZE_SHORT) >>
+        _3 : le_u32                                          >>
+        _4 : le_u64                                          >>
+        _5 : le_u16                                          >>
+        (ReduceSizeMsg{ time_offset : _3,
+                        msg_type    : _2,
+                        order_id    : _4,
+                        shares      : u32::from(_5)
+                    })
+    )
+);
+
+named!(parse_reduce_size<&[u8], ReduceSizeMsg>,
+    alt!(parse_reduce_size_long | parse_reduce_size_short)
+);
+
+named!(parse_unit_clear<&[u8], UnitClearMsg>,
+    do_parse!(
+        _1 : le_u8                                    >>
+        _2 : verify!(le_u8, |t : u8| t == UNIT_CLEAR) >>
+        _3 : le_u32                                   >>
+        (UnitClearMsg{ time_offset : _3,
+                       msg_type    : _2
+                    })
+    )
+);
+
+named!(parse_time<&[u8], TimeMsg>,
+    do_parse!(
+        _1 : le_u8                              >>
+        _2 : verify!(le_u8, |t : u8| t == TIME) >>
+        _3 : le_u32                             >>
+        (TimeMsg{ msg_type : _2,
+                  time     : _3
+                })
+    )
+);
+
+named!(parse_trade_expanded<&[u8], TradeExpandedMsg>,
+    do_parse!(
+        _1 : le_u8                                        >>
+        _2 : verify!(le_u8, |t : u8| t == TRADE_EXPANDED) >>
+        _3 : le_u32                                       >>
+        _4 : le_u64                                       >>
+        _5 : map!(le_u8, char::from)                      >>
+        _6 : le_u32                                       >>
+        _7 : map_res!(take!(8), from_ascii)               >>
+        _8 : le_u64                                       >>
+        _9 : le_u64                                       >>
+        (TradeExpandedMsg{ time_offset : _3,
+                           msg_type    : _2,
+                           order_id    : _4,
+                           side        : _5,
+                           shares      : _6,
+                           symbol      : _7,
+                           price       : _8,
+                           exec_id     : _9
+                        })
+    )
+);
+
+named!(parse_order_executed_at_price_size<&[u8], OrderExecutedAtPriceSizeMsg>,
+    do_parse!(
+        _1 : le_u8                                                      >>
+        _2 : verify!(le_u8, |t : u8| t == ORDER_EXECUTED_AT_PRICE_SIZE) >>
+        _3 : le_u32                                                     >>
+        _4 : le_u64                                                     >>
+        _5 : le_u32                                                     >>
+        _6 : le_u32                                                     >>
+        _7 : le_u64                                                     >>
+        _8 : le_u64                                                     >>
+        (OrderExecutedAtPriceSizeMsg{ time_offset      : _3,
+                                      msg_type         : _2,
+                                      order_id         : _4,
+                                      shares           : _5,
+                                      remaining_shares : _6,
+                                      exec_id          : _7,
+                                      price            : _8
+                                    })
+    )
+);
+
+named!(parse_calculated_value<&[u8], CalculatedValueMsg>,
+    do_parse!(
+        _1 : le_u8                                          >>
+        _2 : verify!(le_u8, |t : u8| t == CALCULATED_VALUE) >>
+        _3 : le_u32                                         >>
+        _4 : map_res!(take!(8), from_ascii)                 >>
+        _5 : map!(le_u8, char::from)                        >>
+        _6 : le_u64                                         >>
+        _7 : le_u64                                         >>
+        (CalculatedValueMsg{ time_offset     : _3,
+                             msg_type        : _2,
+                             symbol          : _4,
+                             value_category  : _5,
+                             value           : _6,
+                             value_timestamp : _7
+                           })
+    )
+);
+
+named!(parse_end_of_session<&[u8], EndOfSessionMsg>,
+    do_parse!(
+        _1 : le_u8                                        >>
+        _2 : verify!(le_u8, |t : u8| t == END_OF_SESSION) >>
+        _3 : le_u32                                       >>
+        (EndOfSessionMsg{ time_offset : _3,
+                          msg_type    : _2
+                        })
+    )
+);
+
+named!(parse_login<&[u8], LoginMsg>,
+    do_parse!(
+        _1 : le_u8                               >>
+        _2 : verify!(le_u8, |t : u8| t == LOGIN) >>
+        _3 : map_res!(take!(4), from_ascii)      >>
+        _4 : map_res!(take!(4), from_ascii)      >>
+        _5 : take!(2)                            >>
+        _6 : map_res!(take!(10), from_ascii)     >>
+        (LoginMsg{ msg_type       : _2,
+                   session_sub_id : _3,
+                   username       : _4,
+                   password       : _6
+                 })
+    )
+);
+
+named!(parse_login_response<&[u8], LoginResponseMsg>,
+    do_parse!(
+        _1 : le_u8                                        >>
+        _2 : verify!(le_u8, |t : u8| t == LOGIN_RESPONSE) >>
+        _3 : map!(le_u8, char::from)                      >>
+        (LoginResponseMsg{ msg_type : _2,
+                           status   : LoginStatus::from(_3)
+                         })
+    )
+);
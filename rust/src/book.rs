@@ -0,0 +1,1401 @@
+
+// Order book built from the feed: resting orders by id and their shares aggregated per price
+// level, maintained by applying the parsed messages. Unlike LimitOrderBook, which matches
+// incoming orders, this only follows what the exchange reports, so it never crosses orders
+// itself and executions/cancels are taken as given.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::mem;
+
+use auction::AuctionState;
+use bars::Bar;
+use binary::TimestampComposer;
+use consistency;
+use consistency::Inconsistency;
+use levels::LevelStore;
+use messages::BATSMessage;
+use slab::Slab;
+use status::SymbolState;
+use status::Update;
+use status::Violation;
+use status::ViolationPolicy;
+use trades::Execution;
+use trades::TradeTracker;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Side {
+    Bid,
+    Ask
+}
+
+impl Side {
+    // PITCH's 'B'/'S', anything but 'B' is a sell.
+    pub fn from_char( c : char ) -> Side {
+        if c == 'B' { Side::Bid } else { Side::Ask }
+    }
+
+    pub fn to_char( self ) -> char {
+        match self {
+            Side::Bid => 'B',
+            Side::Ask => 'S'
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Resting {
+    order_id  : u64,
+    side      : Side,
+    price     : u64,
+    shares    : u32,
+    timestamp : u64,
+    displayed : bool,
+    // the orders before and after it at its level, for L3
+    prev      : Option<usize>,
+    next      : Option<usize>
+}
+
+// all an L2 book keeps of an order: where its shares are and how many are left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct L2Order {
+    side      : Side,
+    price     : u64,
+    shares    : u32,
+    displayed : bool
+}
+
+impl From<&Resting> for L2Order {
+    fn from( o : &Resting ) -> L2Order {
+        L2Order{ side : o.side, price : o.price, shares : o.shares, displayed : o.displayed }
+    }
+}
+
+// a resting order as the book has it, shares being what's left. The timestamp is the add's,
+// in whatever unit the feed's messages use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Order {
+    pub order_id  : u64,
+    pub symbol    : Option<String>,
+    pub side      : Side,
+    pub price     : u64,
+    pub shares    : u32,
+    pub timestamp : u64,
+    pub displayed : bool
+}
+
+// What a book keeps per price level. Either way it has each order's side, price and shares, as
+// executions and cancels only name the order, but an L2 book keeps only those: it doesn't queue
+// the orders at each level, so Level::orders() is empty, and its orders' timestamps are 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BookMode {
+    L2,
+    #[default]
+    L3
+}
+
+// What the book keeps per price level: the shares summed, so L2 reads don't walk the queue,
+// and the ends of its queue. The queue is a list running through the orders in the slab, each
+// linking to the ones before and after it, so an execution at the front or a cancel from
+// anywhere unlinks in constant time without shifting anything. Public only for LevelStores to
+// keep, it's read through Level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelData {
+    price         : u64,
+    shares        : u64,
+    count         : u32,
+    hidden_shares : u64,
+    hidden_count  : u32,
+    // slab indexes, None for an L2 level
+    head          : Option<usize>,
+    tail          : Option<usize>
+}
+
+impl LevelData {
+    fn new( price : u64 ) -> LevelData {
+        LevelData{ price, shares : 0, count : 0, hidden_shares : 0, hidden_count : 0, head : None, tail : None }
+    }
+
+    pub fn price( &self ) -> u64 {
+        self.price
+    }
+
+    fn quote( &self ) -> Quote {
+        Quote{ price : self.price, shares : self.shares, orders : self.count }
+    }
+}
+
+// Every order resting at one price, in time priority (for L3), as borrowed from its book; L3
+// reads look the ids up with OrderBook::order_shares. Shares and counts are of every order,
+// hidden ones (an add's display flag other than 'Y') included, the displayed_ methods leave
+// those out.
+#[derive(Clone, Copy)]
+pub struct Level<'a> {
+    data   : &'a LevelData,
+    orders : &'a Slab<Resting>
+}
+
+impl<'a> Level<'a> {
+    pub fn price( self ) -> u64 {
+        self.data.price
+    }
+
+    pub fn shares( self ) -> u64 {
+        self.data.shares
+    }
+
+    pub fn order_count( self ) -> usize {
+        self.data.count as usize
+    }
+
+    // order ids, first in line first. Always empty for an L2 book.
+    pub fn orders( self ) -> impl Iterator<Item = u64> + 'a {
+        let orders = self.orders;
+        std::iter::successors(self.data.head.and_then(|i| orders.get(i)), move |o| o.next.and_then(|i| orders.get(i)))
+            .map(|o| o.order_id)
+    }
+
+    pub fn quote( self ) -> Quote {
+        self.data.quote()
+    }
+
+    pub fn hidden_shares( self ) -> u64 {
+        self.data.hidden_shares
+    }
+
+    pub fn displayed_shares( self ) -> u64 {
+        self.data.shares - self.data.hidden_shares
+    }
+
+    pub fn displayed_count( self ) -> usize {
+        (self.data.count - self.data.hidden_count) as usize
+    }
+
+    // None for a level of hidden orders only.
+    pub fn displayed_quote( self ) -> Option<Quote> {
+        if self.data.count == self.data.hidden_count {
+            None
+        } else {
+            Some(Quote{ price : self.data.price, shares : self.displayed_shares(), orders : self.data.count - self.data.hidden_count })
+        }
+    }
+}
+
+impl<'a> PartialEq for Level<'a> {
+    fn eq( &self, other : &Level ) -> bool {
+        self.data.quote() == other.data.quote() && self.data.hidden_shares == other.data.hidden_shares &&
+            self.data.hidden_count == other.data.hidden_count && self.orders().eq(other.orders())
+    }
+}
+
+impl<'a> fmt::Debug for Level<'a> {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        f.debug_struct("Level").field("price", &self.data.price).field("shares", &self.data.shares)
+         .field("count", &self.data.count).field("hidden_shares", &self.data.hidden_shares)
+         .field("orders", &self.orders().collect::<Vec<_>>()).finish()
+    }
+}
+
+// a price level, its total shares and how many orders make them up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Quote {
+    pub price  : u64,
+    pub shares : u64,
+    pub orders : u32
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bbo {
+    pub bid : Option<Quote>,
+    pub ask : Option<Quote>
+}
+
+// prices below keep the 4 implied decimals, and need both sides.
+impl Bbo {
+    fn both( &self ) -> Option<(Quote, Quote)> {
+        Some((self.bid?, self.ask?))
+    }
+
+    // ask less bid, negative for a crossed book.
+    pub fn spread( &self ) -> Option<i64> {
+        self.both().map(|(bid, ask)| ask.price as i64 - bid.price as i64)
+    }
+
+    pub fn mid( &self ) -> Option<f64> {
+        self.both().map(|(bid, ask)| (bid.price as f64 + ask.price as f64) / 2.0)
+    }
+
+    // each price weighted by the shares on the other side, so it leans toward the side more
+    // likely to trade through.
+    pub fn microprice( &self ) -> Option<f64> {
+        let (bid, ask) = self.both()?;
+        let shares = bid.shares as f64 + ask.shares as f64;
+        if shares == 0.0 {
+            return self.mid();
+        }
+        Some((bid.price as f64 * ask.shares as f64 + ask.price as f64 * bid.shares as f64) / shares)
+    }
+}
+
+#[cfg(feature = "serde")]
+fn displayed_default() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotOrder {
+    pub order_id  : u64,
+    pub side      : Side,
+    pub price     : u64,
+    pub shares    : u32,
+    pub timestamp : u64,
+    #[cfg_attr(feature = "serde", serde(default = "displayed_default"))]
+    pub displayed : bool
+}
+
+// Everything needed to rebuild a book but its listeners. Orders are bids then asks, best level
+// first and in time priority within a level, so restoring them in order gives the same queues.
+// (An L2 book hasn't got queues, its orders go by id within a level.)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BookSnapshot {
+    pub symbol   : Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mode     : BookMode,
+    pub orders   : Vec<SnapshotOrder>,
+    // the book's checksum when the snapshot was taken, to check a restore against
+    pub checksum : u64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LevelChange {
+    pub side   : Side,
+    pub before : Quote,
+    pub after  : Quote
+}
+
+// How one snapshot's levels differ from another's, bids then asks, each by price.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BookDiff {
+    pub added   : Vec<(Side, Quote)>,
+    pub removed : Vec<(Side, Quote)>,
+    // shares or order count differ
+    pub changed : Vec<LevelChange>
+}
+
+impl BookDiff {
+    pub fn is_empty( &self ) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    // shares the two books disagree on, summed over every level.
+    pub fn share_drift( &self ) -> u64 {
+        self.added.iter().chain(self.removed.iter()).map(|&(_, q)| q.shares)
+            .chain(self.changed.iter().map(|c| c.before.shares.abs_diff(c.after.shares)))
+            .sum()
+    }
+}
+
+impl BookSnapshot {
+    fn levels( &self, side : Side ) -> BTreeMap<u64, Quote> {
+        let mut levels = BTreeMap::new();
+        for o in self.orders.iter().filter(|o| o.side == side) {
+            let q = levels.entry(o.price).or_insert(Quote{ price : o.price, shares : 0, orders : 0 });
+            q.shares += u64::from(o.shares);
+            q.orders += 1;
+        }
+        levels
+    }
+
+    // what changed going from this snapshot to `other`.
+    pub fn diff( &self, other : &BookSnapshot ) -> BookDiff {
+        let mut diff = BookDiff::default();
+        for &side in &[Side::Bid, Side::Ask] {
+            let (before, after) = (self.levels(side), other.levels(side));
+            for (price, &b) in &before {
+                match after.get(price) {
+                    Some(&a) if a != b => diff.changed.push(LevelChange{ side, before : b, after : a }),
+                    Some(_)            => (),
+                    None               => diff.removed.push((side, b))
+                }
+            }
+            diff.added.extend(after.iter().filter(|&(p, _)| !before.contains_key(p)).map(|(_, &a)| (side, a)));
+        }
+        diff
+    }
+}
+
+// the top levels of each side, best first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Depth {
+    pub bids : Vec<Quote>,
+    pub asks : Vec<Quote>
+}
+
+// What a book tells its listeners as it changes. Every event follows the change it reports, a
+// new best level is LevelAdded then Bbo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookEvent {
+    // the top of the book moved, either side's price, shares or order count.
+    Bbo(Bbo),
+    LevelAdded{ side : Side, price : u64 },
+    LevelRemoved{ side : Side, price : u64 },
+    // a resting order executed, at `price` which isn't always the order's.
+    Trade{ order_id : u64, exec_id : u64, side : Side, price : u64, shares : u32 },
+    // the best bid is at or through the best ask, reported with every BBO change while it lasts.
+    // The feed never does this itself, so whatever decoded or sequenced it probably went wrong.
+    // The ids are the orders resting at each best level.
+    Crossed{ bid : Quote, ask : Quote, bid_orders : Vec<u64>, ask_orders : Vec<u64> },
+    // the exchange broke a trade, which has been taken back out of the BookManager's stats.
+    TradeBroken{ exec_id : u64, price : u64, shares : u32 },
+    // a BarBuilder finished a bar.
+    Bar(Bar)
+}
+
+// Gets the book's symbol with each event, None for a book that takes every symbol. Closures
+// taking the same arguments are listeners too.
+pub trait BookListener : Send + Sync {
+    fn on_event( &mut self, symbol : Option<&str>, event : &BookEvent );
+}
+
+impl<F : FnMut(Option<&str>, &BookEvent) + Send + Sync> BookListener for F {
+    fn on_event( &mut self, symbol : Option<&str>, event : &BookEvent ) {
+        self(symbol, event)
+    }
+}
+
+// One level's part of the book checksum, 0 for a level with no shares so an empty level counts
+// the same as a missing one. The checksum is the wrapping sum of these over both sides, which
+// doesn't depend on the order levels are visited in, so anything that can list the levels can
+// compute it to compare against.
+pub fn level_hash( side : Side, price : u64, shares : u64 ) -> u64 {
+    if shares == 0 {
+        return 0;
+    }
+    // splitmix64's finalizer
+    let mut x = price ^ shares.rotate_left(32) ^ if side == Side::Bid { 0x9e37_79b9_7f4a_7c15 } else { 0 };
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub expected : u64,
+    pub actual   : u64
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        write!(f, "book checksum {:016x}, expected {:016x}", self.actual, self.expected)
+    }
+}
+
+impl error::Error for ChecksumMismatch {}
+
+#[derive(Default)]
+struct Listeners(Vec<Box<dyn BookListener>>);
+
+impl fmt::Debug for Listeners {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        write!(f, "Listeners({})", self.0.len())
+    }
+}
+
+// a side's levels in price priority, best first.
+pub struct Levels<'a, S : LevelStore + 'a = BTreeMap<u64, LevelData>> {
+    it     : S::Iter<'a>,
+    orders : &'a Slab<Resting>,
+    // bids are walked from the top of the store
+    rev    : bool,
+    left   : usize
+}
+
+impl<'a, S : LevelStore> Iterator for Levels<'a, S> {
+    type Item = Level<'a>;
+
+    fn next( &mut self ) -> Option<Level<'a>> {
+        let data = if self.rev { self.it.next_back() } else { self.it.next() }?;
+        self.left -= 1;
+        Some(Level{ data, orders : self.orders })
+    }
+
+    fn size_hint( &self ) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, S : LevelStore> DoubleEndedIterator for Levels<'a, S> {
+    fn next_back( &mut self ) -> Option<Level<'a>> {
+        let data = if self.rev { self.it.next() } else { self.it.next_back() }?;
+        self.left -= 1;
+        Some(Level{ data, orders : self.orders })
+    }
+}
+
+impl<'a, S : LevelStore> ExactSizeIterator for Levels<'a, S> {}
+
+// The levels are kept in a BTreeMap unless the book's made with_store, see levels.rs for the
+// others.
+#[derive(Debug, Default)]
+pub struct OrderBook<S : LevelStore = BTreeMap<u64, LevelData>> {
+    symbol    : Option<String>,
+    mode      : BookMode,
+    bids      : S,
+    asks      : S,
+    // resting orders in a slab, by id through their index in it
+    orders    : Slab<Resting>,
+    ids       : HashMap<u64, usize>,
+    // an L2 book's orders instead
+    l2        : HashMap<u64, L2Order>,
+    // kept up to date as levels change, so reading the top of the book is free.
+    bbo       : Bbo,
+    // level_hash summed over every level, updated with them
+    checksum  : u64,
+    // times binary adds off the Time messages it's given
+    clock     : TimestampComposer,
+    listeners : Listeners
+}
+
+impl OrderBook {
+    // takes every order it's given, eg. when the caller already split the feed by symbol.
+    pub fn new() -> OrderBook {
+        OrderBook::default()
+    }
+
+    // only books adds (and clears) for this symbol, other orders' executions and cancels are
+    // ignored as their ids aren't known.
+    pub fn for_symbol( symbol : &str ) -> OrderBook {
+        OrderBook{ symbol : Some(String::from(symbol.trim_end())), ..OrderBook::default() }
+    }
+
+    // verify_checksum(snapshot.checksum) tells whether it came back the way it was.
+    pub fn restore( snapshot : &BookSnapshot ) -> OrderBook {
+        OrderBook::restore_into(OrderBook::default(), snapshot)
+    }
+}
+
+impl<S : LevelStore> OrderBook<S> {
+    // keeps its levels in `store` (emptied first), eg. a PriceLadder, for the symbol when one's
+    // given as with for_symbol.
+    pub fn with_store( symbol : Option<&str>, mut store : S ) -> OrderBook<S> {
+        store.clear();
+        OrderBook{ symbol : symbol.map(|s| String::from(s.trim_end())), mode : BookMode::default(), bids : store.clone(),
+                   asks : store, orders : Slab::new(), ids : HashMap::new(), l2 : HashMap::new(), bbo : Bbo::default(), checksum : 0,
+                   clock : TimestampComposer::new(), listeners : Listeners::default() }
+    }
+
+    // before any orders are added.
+    pub fn book_mode( mut self, mode : BookMode ) -> OrderBook<S> {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode( &self ) -> BookMode {
+        self.mode
+    }
+
+    // makes room for `orders` more resting orders up front, eg. for a symbol known to be busy.
+    pub fn reserve( &mut self, orders : usize ) {
+        match self.mode {
+            BookMode::L3 => {
+                self.orders.reserve(orders);
+                self.ids.reserve(orders);
+            },
+            BookMode::L2 => self.l2.reserve(orders)
+        }
+    }
+
+    // about what the resting orders take on the heap, not counting the levels.
+    pub fn order_memory( &self ) -> usize {
+        self.orders.capacity() * mem::size_of::<Resting>() + self.ids.capacity() * mem::size_of::<(u64, usize)>() +
+            self.l2.capacity() * mem::size_of::<(u64, L2Order)>()
+    }
+
+    pub fn symbol( &self ) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    pub fn snapshot( &self ) -> BookSnapshot {
+        let order = |o : &Resting| {
+            SnapshotOrder{ order_id : o.order_id, side : o.side, price : o.price, shares : o.shares, timestamp : o.timestamp,
+                           displayed : o.displayed }
+        };
+        let orders = match self.mode {
+            BookMode::L3 => self.bids().chain(self.asks()).flat_map(|l| l.orders())
+                                .filter_map(|id| self.resting(id)).map(order).collect(),
+            BookMode::L2 => {
+                let mut orders : Vec<SnapshotOrder> = self.l2.iter().map(|(&order_id, o)| {
+                    SnapshotOrder{ order_id, side : o.side, price : o.price, shares : o.shares, timestamp : 0,
+                                   displayed : o.displayed }
+                }).collect();
+                orders.sort_by_key(|o| match o.side {
+                    Side::Bid => (0, u64::MAX - o.price, o.order_id),
+                    Side::Ask => (1, o.price, o.order_id)
+                });
+                orders
+            }
+        };
+        BookSnapshot{ symbol : self.symbol.clone(), mode : self.mode, orders, checksum : self.checksum }
+    }
+
+    // restore() for a book with its own store, which is cleared first.
+    pub fn restore_into( mut book : OrderBook<S>, snapshot : &BookSnapshot ) -> OrderBook<S> {
+        book.clear();
+        book.symbol = snapshot.symbol.clone();
+        book.mode = snapshot.mode;
+        for o in &snapshot.orders {
+            book.add_order_at(o.timestamp, o.order_id, o.side, o.price, o.shares, o.displayed);
+        }
+        book
+    }
+
+    pub fn add_listener<L : BookListener + 'static>( &mut self, listener : L ) {
+        self.listeners.0.push(Box::new(listener));
+    }
+
+    fn emit( &mut self, event : BookEvent ) {
+        let symbol = self.symbol.as_deref();
+        for l in &mut self.listeners.0 {
+            l.on_event(symbol, &event);
+        }
+    }
+
+    fn resting( &self, order_id : u64 ) -> Option<&Resting> {
+        self.ids.get(&order_id).and_then(|&i| self.orders.get(i))
+    }
+
+    fn resting_mut( &mut self, order_id : u64 ) -> Option<&mut Resting> {
+        match self.ids.get(&order_id) {
+            Some(&i) => self.orders.get_mut(i),
+            None     => None
+        }
+    }
+
+    // the order in whichever way the book keeps it.
+    fn find( &self, order_id : u64 ) -> Option<L2Order> {
+        match self.mode {
+            BookMode::L3 => self.resting(order_id).map(L2Order::from),
+            BookMode::L2 => self.l2.get(&order_id).copied()
+        }
+    }
+
+    // takes the order out of the slab and its level's queue.
+    fn unrest( &mut self, order_id : u64 ) -> Option<L2Order> {
+        if self.mode == BookMode::L2 {
+            return self.l2.remove(&order_id);
+        }
+        let i = self.ids.remove(&order_id)?;
+        let o = self.orders.remove(i)?;
+        if let Some(p) = o.prev.and_then(|p| self.orders.get_mut(p)) {
+            p.next = o.next;
+        }
+        if let Some(n) = o.next.and_then(|n| self.orders.get_mut(n)) {
+            n.prev = o.prev;
+        }
+        let levels = match o.side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks
+        };
+        if let Some(level) = levels.get_mut(o.price) {
+            if level.head == Some(i) {
+                level.head = o.next;
+            }
+            if level.tail == Some(i) {
+                level.tail = o.prev;
+            }
+        }
+        Some(L2Order::from(&o))
+    }
+
+    fn is_for( &self, symbol : &str ) -> bool {
+        self.symbol.as_ref().is_none_or(|s| s == symbol.trim_end())
+    }
+
+    fn side_mut( &mut self, side : Side ) -> &mut S {
+        match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks
+        }
+    }
+
+    fn rehash( &mut self, side : Side, price : u64, before : u64, after : u64 ) {
+        self.checksum = self.checksum.wrapping_sub(level_hash(side, price, before)).wrapping_add(level_hash(side, price, after));
+    }
+
+    // the cached quote only moves when a level at or better than it changed.
+    fn touch( &mut self, side : Side, price : u64 ) {
+        let before = self.bbo;
+        match side {
+            Side::Bid => if self.bbo.bid.is_none_or(|q| price >= q.price) {
+                self.bbo.bid = self.bids.iter().next_back().map(LevelData::quote);
+            },
+            Side::Ask => if self.bbo.ask.is_none_or(|q| price <= q.price) {
+                self.bbo.ask = self.asks.iter().next().map(LevelData::quote);
+            }
+        }
+        if self.bbo != before && !self.listeners.0.is_empty() {
+            let bbo = self.bbo;
+            self.emit(BookEvent::Bbo(bbo));
+            if let (Some(bid), Some(ask)) = (bbo.bid, bbo.ask) {
+                if bid.price >= ask.price {
+                    let bid_orders = self.bids().next().map_or(vec![], |l| l.orders().collect());
+                    let ask_orders = self.asks().next().map_or(vec![], |l| l.orders().collect());
+                    self.emit(BookEvent::Crossed{ bid, ask, bid_orders, ask_orders });
+                }
+            }
+        }
+    }
+
+    // returns whether the message changed the book.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        match *msg {
+            BATSMessage::AddOrderMsg(ref m) => {
+                if !self.is_for(&m.symbol) {
+                    return false;
+                }
+                self.add_order_at(u64::from(m.timestamp), m.order_id, Side::from_char(m.side), m.price, m.shares, m.display == 'Y');
+                true
+            },
+            BATSMessage::OrderExecutedMsg(ref m) => {
+                let price = self.find(m.order_id).map(|o| o.price);
+                self.execute(m.order_id, m.exec_id, price, |shares| shares.saturating_sub(m.shares))
+            },
+            BATSMessage::BinaryAddOrderMsg(ref m) => {
+                let time = self.clock.compose(msg);
+                if !self.is_for(&m.symbol) {
+                    return false;
+                }
+                self.add_order_at(time, m.order_id, Side::from_char(m.side), m.price, m.shares, m.display == 'Y');
+                true
+            },
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => {
+                let price = self.find(m.order_id).map(|o| o.price);
+                self.execute(m.order_id, m.exec_id, price, |shares| shares.saturating_sub(m.shares))
+            },
+            BATSMessage::OrderCancelMsg(ref m)   => self.reduce_order(m.order_id, m.shares),
+            BATSMessage::ReduceSizeMsg(ref m)    => self.reduce_order(m.order_id, m.shares),
+            BATSMessage::DeleteOrderMsg(ref m)   => self.delete_order(m.order_id),
+            BATSMessage::ModifyOrderMsg(ref m)   => {
+                let time = self.clock.compose(msg);
+                self.modify_order_at(time, m.order_id, m.price, m.shares, m.display == 'Y')
+            },
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => self.execute(m.order_id, m.exec_id, Some(m.price), |_| m.remaining_shares),
+            BATSMessage::TimeMsg(_) => {
+                self.clock.compose(msg);
+                false
+            },
+            BATSMessage::SymbolClearMsg(ref m) => {
+                if !self.is_for(&m.symbol) {
+                    return false;
+                }
+                self.clear();
+                true
+            },
+            _ => false
+        }
+    }
+
+    // an execution reports a Trade before the order's level changes.
+    fn execute<F : FnOnce(u32) -> u32>( &mut self, order_id : u64, exec_id : u64, price : Option<u64>, remaining : F ) -> bool {
+        let (side, shares, remaining) = match self.find(order_id) {
+            Some(o) => (o.side, o.shares, remaining(o.shares)),
+            None    => return false
+        };
+        if let Some(price) = price {
+            self.emit(BookEvent::Trade{ order_id, exec_id, side, price, shares : shares.saturating_sub(remaining) });
+        }
+        self.set_remaining(order_id, remaining)
+    }
+
+    // an order id that's already resting replaces the old order. Orders added this way are
+    // displayed and have a timestamp of 0.
+    pub fn add_order( &mut self, order_id : u64, side : Side, price : u64, shares : u32 ) {
+        self.add_order_at(0, order_id, side, price, shares, true);
+    }
+
+    pub fn add_order_at( &mut self, timestamp : u64, order_id : u64, side : Side, price : u64, shares : u32, displayed : bool ) {
+        self.delete_order(order_id);
+        let l3 = self.mode == BookMode::L3;
+        let mut added = false;
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks
+        };
+        let level = levels.get_or_insert_with(price, || {
+            added = true;
+            LevelData::new(price)
+        });
+        let before = level.shares;
+        level.shares += u64::from(shares);
+        level.count += 1;
+        if !displayed {
+            level.hidden_shares += u64::from(shares);
+            level.hidden_count += 1;
+        }
+        let after = level.shares;
+        if l3 {
+            let index = self.orders.insert(Resting{ order_id, side, price, shares, timestamp, displayed, prev : None, next : None });
+            let prev = level.tail.replace(index);
+            if level.head.is_none() {
+                level.head = Some(index);
+            }
+            if let Some(p) = prev.and_then(|p| self.orders.get_mut(p)) {
+                p.next = Some(index);
+            }
+            if let Some(o) = self.orders.get_mut(index) {
+                o.prev = prev;
+            }
+            self.ids.insert(order_id, index);
+        } else {
+            self.l2.insert(order_id, L2Order{ side, price, shares, displayed });
+        }
+        self.rehash(side, price, before, after);
+        if added {
+            self.emit(BookEvent::LevelAdded{ side, price });
+        }
+        self.touch(side, price);
+    }
+
+    // `gone` when the order left the level altogether, it's been unrested already.
+    fn take_shares( &mut self, side : Side, price : u64, shares : u32, displayed : bool, gone : bool ) {
+        let (before, after, empty) = match self.side_mut(side).get_mut(price) {
+            Some(level) => {
+                let before = level.shares;
+                level.shares = level.shares.saturating_sub(u64::from(shares));
+                if !displayed {
+                    level.hidden_shares = level.hidden_shares.saturating_sub(u64::from(shares));
+                }
+                if gone {
+                    level.count = level.count.saturating_sub(1);
+                    if !displayed {
+                        level.hidden_count = level.hidden_count.saturating_sub(1);
+                    }
+                }
+                (before, level.shares, level.count == 0)
+            },
+            None => return
+        };
+        self.rehash(side, price, before, after);
+        if empty {
+            self.side_mut(side).remove(price);
+            self.emit(BookEvent::LevelRemoved{ side, price });
+        }
+        self.touch(side, price);
+    }
+
+    // a modify keeps the order's place only when it's for fewer shares at the same price,
+    // otherwise it goes to the back as if added at `timestamp`. False for an unknown order.
+    pub fn modify_order_at( &mut self, timestamp : u64, order_id : u64, price : u64, shares : u32, displayed : bool ) -> bool {
+        let (side, keeps) = match self.find(order_id) {
+            Some(o) => (o.side, o.price == price && shares <= o.shares && o.displayed == displayed),
+            None    => return false
+        };
+        if keeps || shares == 0 {
+            return self.set_remaining(order_id, shares);
+        }
+        self.add_order_at(timestamp, order_id, side, price, shares, displayed);
+        true
+    }
+
+    // executes or cancels `shares` of the order, taking it off the book once nothing is
+    // left. False for an unknown order.
+    pub fn reduce_order( &mut self, order_id : u64, shares : u32 ) -> bool {
+        match self.find(order_id) {
+            Some(o) => {
+                let remaining = o.shares.saturating_sub(shares);
+                self.set_remaining(order_id, remaining)
+            },
+            None => false
+        }
+    }
+
+    // for when the exchange reports what's left rather than what was taken.
+    pub fn set_remaining( &mut self, order_id : u64, remaining : u32 ) -> bool {
+        let found = match self.mode {
+            BookMode::L3 => self.resting_mut(order_id).map(|o| (o.side, o.price, &mut o.shares, o.displayed)),
+            BookMode::L2 => self.l2.get_mut(&order_id).map(|o| (o.side, o.price, &mut o.shares, o.displayed))
+        };
+        let (side, price, taken, displayed) = match found {
+            Some((side, price, shares, displayed)) => {
+                let taken = shares.saturating_sub(remaining);
+                *shares = (*shares).min(remaining);
+                (side, price, taken, displayed)
+            },
+            None => return false
+        };
+        if remaining == 0 {
+            self.unrest(order_id);
+        }
+        self.take_shares(side, price, taken, displayed, remaining == 0);
+        true
+    }
+
+    pub fn delete_order( &mut self, order_id : u64 ) -> bool {
+        match self.unrest(order_id) {
+            Some(o) => {
+                self.take_shares(o.side, o.price, o.shares, o.displayed, true);
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn clear( &mut self ) {
+        if !self.listeners.0.is_empty() {
+            let removed : Vec<BookEvent> = self.bids().map(|l| BookEvent::LevelRemoved{ side : Side::Bid, price : l.price() })
+                                               .chain(self.asks().map(|l| BookEvent::LevelRemoved{ side : Side::Ask, price : l.price() }))
+                                               .collect();
+            for event in removed {
+                self.emit(event);
+            }
+        }
+        self.bids.clear();
+        self.asks.clear();
+        self.orders.clear();
+        self.ids.clear();
+        self.l2.clear();
+        self.checksum = 0;
+        if self.bbo != Bbo::default() {
+            self.bbo = Bbo::default();
+            self.emit(BookEvent::Bbo(Bbo::default()));
+        }
+    }
+
+    pub fn best_bid( &self ) -> Option<Quote> {
+        self.bbo.bid
+    }
+
+    pub fn best_ask( &self ) -> Option<Quote> {
+        self.bbo.ask
+    }
+
+    pub fn bbo( &self ) -> Bbo {
+        self.bbo
+    }
+
+    // off the cached bbo, so as cheap as best_bid().
+    pub fn spread( &self ) -> Option<i64> {
+        self.bbo.spread()
+    }
+
+    pub fn mid( &self ) -> Option<f64> {
+        self.bbo.mid()
+    }
+
+    pub fn microprice( &self ) -> Option<f64> {
+        self.bbo.microprice()
+    }
+
+    // kept up to date as the book changes.
+    pub fn checksum( &self ) -> u64 {
+        self.checksum
+    }
+
+    // the checksum worked out from the levels as they are, what checksum() should always equal.
+    pub fn recompute_checksum( &self ) -> u64 {
+        self.bids.iter().map(|l| level_hash(Side::Bid, l.price, l.shares))
+            .chain(self.asks.iter().map(|l| level_hash(Side::Ask, l.price, l.shares)))
+            .fold(0, u64::wrapping_add)
+    }
+
+    pub fn verify_checksum( &self, expected : u64 ) -> Result<(), ChecksumMismatch> {
+        if self.checksum == expected {
+            Ok(())
+        } else {
+            Err(ChecksumMismatch{ expected, actual : self.checksum })
+        }
+    }
+
+    // the best bid at or through the best ask, locked being exactly at it.
+    pub fn is_crossed( &self ) -> bool {
+        match (self.bbo.bid, self.bbo.ask) {
+            (Some(bid), Some(ask)) => bid.price >= ask.price,
+            _                      => false
+        }
+    }
+
+    pub fn is_locked( &self ) -> bool {
+        match (self.bbo.bid, self.bbo.ask) {
+            (Some(bid), Some(ask)) => bid.price == ask.price,
+            _                      => false
+        }
+    }
+
+    // the best `n` levels a side, every level for 0.
+    pub fn depth( &self, n : usize ) -> Depth {
+        let n = if n == 0 { usize::MAX } else { n };
+        Depth{ bids : self.bids().take(n).map(Level::quote).collect(),
+               asks : self.asks().take(n).map(Level::quote).collect() }
+    }
+
+    // depth() as the market sees it, without hidden orders or any level only they make up.
+    pub fn displayed_depth( &self, n : usize ) -> Depth {
+        let n = if n == 0 { usize::MAX } else { n };
+        Depth{ bids : self.bids().filter_map(Level::displayed_quote).take(n).collect(),
+               asks : self.asks().filter_map(Level::displayed_quote).take(n).collect() }
+    }
+
+    // highest price first.
+    pub fn bids( &self ) -> Levels<'_, S> {
+        Levels{ it : self.bids.iter(), orders : &self.orders, rev : true, left : self.bids.len() }
+    }
+
+    // lowest price first.
+    pub fn asks( &self ) -> Levels<'_, S> {
+        Levels{ it : self.asks.iter(), orders : &self.orders, rev : false, left : self.asks.len() }
+    }
+
+    pub fn levels( &self, side : Side ) -> Levels<'_, S> {
+        match side {
+            Side::Bid => self.bids(),
+            Side::Ask => self.asks()
+        }
+    }
+
+    pub fn contains( &self, order_id : u64 ) -> bool {
+        self.ids.contains_key(&order_id) || self.l2.contains_key(&order_id)
+    }
+
+    pub fn level( &self, side : Side, price : u64 ) -> Option<Level<'_>> {
+        let data = match side {
+            Side::Bid => self.bids.get(price),
+            Side::Ask => self.asks.get(price)
+        };
+        data.map(|data| Level{ data, orders : &self.orders })
+    }
+
+    // the orders resting at a price in time priority, first in line first. None rest in an L2
+    // book's levels.
+    pub fn orders_at( &self, side : Side, price : u64 ) -> impl Iterator<Item = Order> + '_ {
+        self.level(side, price).into_iter().flat_map(Level::orders).filter_map(move |id| self.get_order(id))
+    }
+
+    // an L2 book's orders have a timestamp of 0.
+    pub fn get_order( &self, order_id : u64 ) -> Option<Order> {
+        let timestamp = self.resting(order_id).map_or(0, |o| o.timestamp);
+        self.find(order_id).map(|o| Order{ order_id, symbol : self.symbol.clone(), side : o.side, price : o.price,
+                                           shares : o.shares, timestamp, displayed : o.displayed })
+    }
+
+    // what's left of a resting order.
+    pub fn order_shares( &self, order_id : u64 ) -> Option<u32> {
+        self.find(order_id).map(|o| o.shares)
+    }
+
+    pub fn shares_at( &self, side : Side, price : u64 ) -> u64 {
+        self.level(side, price).map_or(0, Level::shares)
+    }
+
+    // resting orders.
+    pub fn len( &self ) -> usize {
+        self.orders.len() + self.l2.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.len() == 0
+    }
+}
+
+// OrderBooks for every symbol on the feed. Adds carry their symbol, executions and cancels find
+// their book through the order id. Executions and trades also go into the trade stats, which
+// is the only place breaks are applied. Updates a symbol's trading state doesn't allow (eg. an
+// execution while halted) are kept as violations, and dropped too with ViolationPolicy::Reject.
+// With check_consistency() on, messages that don't fit the books are kept as inconsistencies.
+// A Unit Clear drops the books its unit's adds built, as apply_unit() was told them; messages
+// given to plain apply() count as one unit of their own. Binary messages are timed off their
+// unit's last Time message, text ones keep their milliseconds.
+#[derive(Debug, Default)]
+pub struct BookManager {
+    books      : HashMap<String, OrderBook>,
+    owners     : HashMap<u64, String>,
+    // by symbol, the unit its adds came in on
+    units      : HashMap<String, u8>,
+    // of the message being applied, when it's known
+    unit       : Option<u8>,
+    clocks     : HashMap<Option<u8>, TimestampComposer>,
+    // of the last message applied, ns past midnight
+    time       : u64,
+    trades     : TradeTracker,
+    states     : HashMap<String, SymbolState>,
+    auctions   : HashMap<String, AuctionState>,
+    mode       : BookMode,
+    policy     : ViolationPolicy,
+    violations : Vec<Violation>,
+    checking   : bool,
+    problems   : Vec<Inconsistency>,
+    // lent to whichever book a message changes.
+    listeners : Listeners
+}
+
+fn lend<R, F : FnOnce(&mut OrderBook) -> R>( book : &mut OrderBook, listeners : &mut Listeners, f : F ) -> R {
+    let own = book.listeners.0.len();
+    book.listeners.0.append(&mut listeners.0);
+    let r = f(book);
+    listeners.0 = book.listeners.0.split_off(own);
+    r
+}
+
+impl BookManager {
+    pub fn new() -> BookManager {
+        BookManager::default()
+    }
+
+    // for the books it makes from here on.
+    pub fn book_mode( mut self, mode : BookMode ) -> BookManager {
+        self.mode = mode;
+        self
+    }
+
+    pub fn violation_policy( mut self, policy : ViolationPolicy ) -> BookManager {
+        self.policy = policy;
+        self
+    }
+
+    // checks every message against the books before applying it, which costs a lookup each.
+    pub fn check_consistency( mut self, on : bool ) -> BookManager {
+        self.checking = on;
+        self
+    }
+
+    // hears every book's events, the symbol tells them apart.
+    pub fn add_listener<L : BookListener + 'static>( &mut self, listener : L ) {
+        self.listeners.0.push(Box::new(listener));
+    }
+
+    // false when the update isn't allowed in its symbol's state and the policy rejects it.
+    fn admit( &mut self, msg : &BATSMessage, time : u64 ) -> bool {
+        let (timestamp, symbol, update, order_id) = match *msg {
+            BATSMessage::AddOrderMsg(ref m) => (u64::from(m.timestamp), m.symbol.trim_end(), Update::Add, Some(m.order_id)),
+            BATSMessage::BinaryAddOrderMsg(ref m) => (time, m.symbol.trim_end(), Update::Add, Some(m.order_id)),
+            BATSMessage::TradeMsg(ref m)    => (u64::from(m.timestamp), m.symbol.trim_end(), Update::Execution, Some(m.order_id)),
+            BATSMessage::BinaryTradeMsg(ref m) => (time, m.symbol.trim_end(), Update::Execution, Some(m.order_id)),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => match self.owners.get(&m.order_id) {
+                Some(s) => (time, s.as_str(), Update::Execution, Some(m.order_id)),
+                None    => return true
+            },
+            BATSMessage::OrderExecutedMsg(ref m) => match self.owners.get(&m.order_id) {
+                Some(s) => (u64::from(m.timestamp), s.as_str(), Update::Execution, Some(m.order_id)),
+                None    => return true
+            },
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => match self.owners.get(&m.order_id) {
+                Some(s) => (time, s.as_str(), Update::Execution, Some(m.order_id)),
+                None    => return true
+            },
+            _ => return true
+        };
+        let state = self.states.get(symbol).cloned().unwrap_or_default();
+        if state.permits(update) {
+            return true;
+        }
+        let rejected = self.policy == ViolationPolicy::Reject;
+        self.violations.push(Violation{ timestamp, symbol : String::from(symbol), state, update,
+                                        order_id, rejected });
+        !rejected
+    }
+
+    // a message from one of a sequenced feed's units, so that unit's Unit Clear drops just the
+    // books it built.
+    pub fn apply_unit( &mut self, unit : u8, msg : &BATSMessage ) -> bool {
+        self.unit = Some(unit);
+        let changed = self.apply(msg);
+        self.unit = None;
+        changed
+    }
+
+    // returns whether the message changed a book.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        let time = self.clocks.entry(self.unit).or_default().compose(msg);
+        self.time = time;
+        if !self.admit(msg, time) {
+            return false;
+        }
+        if self.checking {
+            let found = consistency::check(msg, time, |id| self.find_order(id).map(|o| (o.symbol.unwrap_or_default(), o.shares)));
+            self.problems.extend(found);
+        }
+        match *msg {
+            BATSMessage::AddOrderMsg(ref m) =>
+                self.add(Order{ order_id : m.order_id, symbol : Some(String::from(m.symbol.trim_end())), side : Side::from_char(m.side),
+                                price : m.price, shares : m.shares, timestamp : u64::from(m.timestamp), displayed : m.display == 'Y' }),
+            BATSMessage::BinaryAddOrderMsg(ref m) =>
+                self.add(Order{ order_id : m.order_id, symbol : Some(String::from(m.symbol.trim_end())), side : Side::from_char(m.side),
+                                price : m.price, shares : m.shares, timestamp : time, displayed : m.display == 'Y' }),
+            BATSMessage::OrderExecutedMsg(ref m) => {
+                self.executed(u64::from(m.timestamp), m.order_id, m.shares, m.exec_id);
+                self.on_order(m.order_id, msg)
+            },
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => {
+                self.executed(time, m.order_id, m.shares, m.exec_id);
+                self.on_order(m.order_id, msg)
+            },
+            BATSMessage::OrderCancelMsg(ref m)   => self.on_order(m.order_id, msg),
+            BATSMessage::ReduceSizeMsg(ref m)    => self.on_order(m.order_id, msg),
+            BATSMessage::DeleteOrderMsg(ref m)   => self.on_order(m.order_id, msg),
+            BATSMessage::ModifyOrderMsg(ref m)   =>
+                self.on_order_with(m.order_id, |b| b.modify_order_at(time, m.order_id, m.price, m.shares, m.display == 'Y')),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => {
+                if let Some(symbol) = self.owners.get(&m.order_id) {
+                    self.trades.record(Execution{ exec_id : m.exec_id, symbol : symbol.clone(), price : m.price,
+                                                  shares : m.shares, timestamp : time });
+                }
+                self.on_order(m.order_id, msg)
+            },
+            // off-book, only counted in the stats.
+            BATSMessage::TradeMsg(ref m) => {
+                self.trades.record(Execution{ exec_id : m.exec_id, symbol : String::from(m.symbol.trim_end()), price : m.price,
+                                              shares : m.shares, timestamp : u64::from(m.timestamp) });
+                false
+            },
+            BATSMessage::BinaryTradeMsg(ref m) => {
+                self.trades.record(Execution{ exec_id : m.exec_id, symbol : String::from(m.symbol.trim_end()), price : m.price,
+                                              shares : m.shares, timestamp : time });
+                false
+            },
+            BATSMessage::TradeBreakMsg(ref m) => {
+                if let Some(e) = self.trades.retract(m.exec_id) {
+                    let event = BookEvent::TradeBroken{ exec_id : e.exec_id, price : e.price, shares : e.shares };
+                    for l in &mut self.listeners.0 {
+                        l.on_event(Some(&e.symbol), &event);
+                    }
+                }
+                false
+            },
+            BATSMessage::AuctionUpdateMsg(ref m) => {
+                self.auctions.entry(String::from(m.symbol.trim_end())).or_insert_with(|| AuctionState::new(m.auction_type))
+                             .update(m);
+                false
+            },
+            BATSMessage::AuctionSummaryMsg(ref m) => {
+                self.auctions.entry(String::from(m.symbol.trim_end())).or_insert_with(|| AuctionState::new(m.auction_type))
+                             .finalize(m);
+                false
+            },
+            BATSMessage::TradingStatusMsg(ref m) => {
+                if let Some(state) = SymbolState::from_msg(m) {
+                    self.states.insert(String::from(m.symbol.trim_end()), state);
+                }
+                false
+            },
+            BATSMessage::SymbolClearMsg(ref m) => self.clear(&[String::from(m.symbol.trim_end())]),
+            BATSMessage::UnitClearMsg(_) => {
+                let unit = self.unit;
+                let symbols : Vec<String> = self.books.keys().filter(|s| self.units.get(*s).cloned() == unit).cloned().collect();
+                self.clear(&symbols)
+            },
+            _ => false
+        }
+    }
+
+    // drops the symbols' books and their orders, whether there were any.
+    fn clear( &mut self, symbols : &[String] ) -> bool {
+        let mut cleared = false;
+        for symbol in symbols {
+            self.units.remove(symbol);
+            if let Some(mut book) = self.books.remove(symbol) {
+                lend(&mut book, &mut self.listeners, OrderBook::clear);
+                cleared = true;
+            }
+        }
+        if cleared {
+            let books = &self.books;
+            self.owners.retain(|_, s| books.contains_key(s));
+        }
+        cleared
+    }
+
+    // onto the book for the order's symbol, made if it's the first.
+    fn add( &mut self, o : Order ) -> bool {
+        let Order{ order_id, symbol, side, price, shares, timestamp, displayed } = o;
+        let symbol = symbol.unwrap_or_default();
+        // a reused id moves the order, wherever it was resting.
+        if self.owners.get(&order_id).is_some_and(|s| *s != symbol) {
+            self.delete_order(order_id);
+        }
+        let mode = self.mode;
+        let book = self.books.entry(symbol.clone()).or_insert_with(|| OrderBook::for_symbol(&symbol).book_mode(mode));
+        lend(book, &mut self.listeners, |b| b.add_order_at(timestamp, order_id, side, price, shares, displayed));
+        if let Some(unit) = self.unit {
+            self.units.insert(symbol.clone(), unit);
+        }
+        self.owners.insert(order_id, symbol);
+        true
+    }
+
+    // into the trade stats at the resting order's price.
+    fn executed( &mut self, timestamp : u64, order_id : u64, shares : u32, exec_id : u64 ) {
+        if let Some(o) = self.find_order(order_id) {
+            self.trades.record(Execution{ exec_id, symbol : o.symbol.unwrap_or_default(), price : o.price, shares, timestamp });
+        }
+    }
+
+    fn on_order( &mut self, order_id : u64, msg : &BATSMessage ) -> bool {
+        self.on_order_with(order_id, |b| b.apply(msg))
+    }
+
+    fn on_order_with<F : FnOnce(&mut OrderBook) -> bool>( &mut self, order_id : u64, f : F ) -> bool {
+        let books = &mut self.books;
+        let book = match self.owners.get(&order_id).and_then(|s| books.get_mut(s)) {
+            Some(book) => book,
+            None       => return false
+        };
+        let changed = lend(book, &mut self.listeners, f);
+        if !book.contains(order_id) {
+            self.owners.remove(&order_id);
+        }
+        changed
+    }
+
+    pub fn delete_order( &mut self, order_id : u64 ) -> bool {
+        match self.owners.remove(&order_id) {
+            Some(symbol) => match self.books.get_mut(&symbol) {
+                Some(book) => lend(book, &mut self.listeners, |b| b.delete_order(order_id)),
+                None       => false
+            },
+            None         => false
+        }
+    }
+
+    pub fn book( &self, symbol : &str ) -> Option<&OrderBook> {
+        self.books.get(symbol.trim_end())
+    }
+
+    pub fn book_mut( &mut self, symbol : &str ) -> Option<&mut OrderBook> {
+        self.books.get_mut(symbol.trim_end())
+    }
+
+    pub fn state( &self, symbol : &str ) -> SymbolState {
+        self.states.get(symbol.trim_end()).cloned().unwrap_or_default()
+    }
+
+    // the auction the symbol is in, or the last one it had.
+    pub fn auction( &self, symbol : &str ) -> Option<&AuctionState> {
+        self.auctions.get(symbol.trim_end())
+    }
+
+    pub fn violations( &self ) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn take_violations( &mut self ) -> Vec<Violation> {
+        std::mem::take(&mut self.violations)
+    }
+
+    pub fn inconsistencies( &self ) -> &[Inconsistency] {
+        &self.problems
+    }
+
+    pub fn take_inconsistencies( &mut self ) -> Vec<Inconsistency> {
+        std::mem::take(&mut self.problems)
+    }
+
+    // when the last message applied happened, in nanoseconds past midnight.
+    pub fn time( &self ) -> u64 {
+        self.time
+    }
+
+    pub fn trades( &self ) -> &TradeTracker {
+        &self.trades
+    }
+
+    pub fn find_order( &self, order_id : u64 ) -> Option<Order> {
+        self.owners.get(&order_id).and_then(|s| self.books.get(s)).and_then(|b| b.get_order(order_id))
+    }
+
+    // the symbol an order is resting on.
+    pub fn symbol_of( &self, order_id : u64 ) -> Option<&str> {
+        self.owners.get(&order_id).map(|s| s.as_str())
+    }
+
+    // the symbol a book or trade message is for, by its symbol, order or exec id.
+    pub fn symbol_for<'a>( &'a self, msg : &'a BATSMessage ) -> Option<&'a str> {
+        match *msg {
+            BATSMessage::AddOrderMsg(ref m)    => Some(m.symbol.trim_end()),
+            BATSMessage::SymbolClearMsg(ref m) => Some(m.symbol.trim_end()),
+            BATSMessage::TradeMsg(ref m)       => Some(m.symbol.trim_end()),
+            BATSMessage::TradeBreakMsg(ref m)  => self.trades.execution(m.exec_id).map(|e| e.symbol.as_str()),
+            BATSMessage::OrderExecutedMsg(ref m) => self.symbol_of(m.order_id),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => self.symbol_of(m.order_id),
+            BATSMessage::OrderCancelMsg(ref m) => self.symbol_of(m.order_id),
+            BATSMessage::ReduceSizeMsg(ref m)  => self.symbol_of(m.order_id),
+            BATSMessage::BinaryAddOrderMsg(ref m)      => Some(m.symbol.trim_end()),
+            BATSMessage::BinaryTradeMsg(ref m)         => Some(m.symbol.trim_end()),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => self.symbol_of(m.order_id),
+            BATSMessage::ModifyOrderMsg(ref m)         => self.symbol_of(m.order_id),
+            BATSMessage::DeleteOrderMsg(ref m)         => self.symbol_of(m.order_id),
+            _ => None
+        }
+    }
+
+    // in no particular order, see symbols() for a sorted list.
+    pub fn iter( &self ) -> impl Iterator<Item = (&str, &OrderBook)> {
+        self.books.iter().map(|(s, b)| (s.as_str(), b))
+    }
+
+    pub fn iter_mut( &mut self ) -> impl Iterator<Item = (&str, &mut OrderBook)> {
+        self.books.iter_mut().map(|(s, b)| (s.as_str(), b))
+    }
+
+    pub fn symbols( &self ) -> Vec<&str> {
+        let mut symbols : Vec<&str> = self.books.keys().map(|s| s.as_str()).collect();
+        symbols.sort();
+        symbols
+    }
+
+    // resting orders across every book.
+    pub fn orders( &self ) -> usize {
+        self.owners.len()
+    }
+
+    // books.
+    pub fn len( &self ) -> usize {
+        self.books.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.books.is_empty()
+    }
+
+    // drops everything the feed built up, for a new session. The settings and listeners stay.
+    pub fn reset( &mut self ) {
+        self.books.clear();
+        self.owners.clear();
+        self.units.clear();
+        self.trades.clear();
+        self.states.clear();
+        self.auctions.clear();
+        self.violations.clear();
+        self.problems.clear();
+    }
+
+    // every book, by symbol.
+    pub fn snapshot( &self ) -> Vec<BookSnapshot> {
+        let mut books : Vec<&String> = self.books.keys().collect();
+        books.sort();
+        books.into_iter().map(|s| self.books[s].snapshot()).collect()
+    }
+
+    // resets, then rebuilds the books from snapshot(). Trading states and trade stats aren't
+    // in the snapshots, they start over.
+    pub fn restore( &mut self, snapshots : &[BookSnapshot] ) {
+        self.reset();
+        for snapshot in snapshots {
+            let symbol = snapshot.symbol.clone().unwrap_or_default();
+            for o in snapshot.orders.iter() {
+                self.owners.insert(o.order_id, symbol.clone());
+            }
+            self.books.insert(symbol, OrderBook::restore(snapshot));
+        }
+    }
+}
@@ -0,0 +1,640 @@
+
+// Decoder, books and sinks on threads of their own, joined by bounded channels so a burst the
+// books or a sink can't keep up with waits, drops or goes to disk instead of piling up in
+// memory until the process is killed:
+//
+//   let mut pipeline = PipelineBuilder::new()
+//       .capacity(65536)
+//       .events(Backpressure::Spill(PathBuf::from("/var/tmp")))
+//       .sink(move |e : &MarketEvent| influx.write_event(e).map(|_| ()))
+//       .spawn(msgs);
+//   let books = pipeline.join()?;
+//
+// The books get every decoded message, and each sink every MarketEvent they make of them, in
+// feed order. Under Spill a full channel writes what comes next to a file in the given
+// directory and reads it back once the queue's drained, so nothing is lost or reordered, just
+// slower; the file goes when the channel does.
+
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use binary::BinaryAddOrderMsg;
+use binary::BinaryOrderExecutedMsg;
+use binary::BinaryTradeMsg;
+use binary::CalculatedValueMsg;
+use binary::DeleteOrderMsg;
+use binary::EndOfSessionMsg;
+use binary::LoginMsg;
+use binary::LoginResponseMsg;
+use binary::LoginStatus;
+use binary::ModifyOrderMsg;
+use binary::OrderExecutedAtPriceSizeMsg;
+use binary::ReduceSizeMsg;
+use binary::TimeMsg;
+use binary::TimestampComposer;
+use binary::TradeExpandedMsg;
+use binary::UnitClearMsg;
+use book::BookManager;
+#[cfg(feature = "europe")]
+use europe::TradeReportMsg;
+use event::MarketEvent;
+use messages::AddOrderMsg;
+use messages::AuctionSummaryMsg;
+use messages::AuctionUpdateMsg;
+use messages::BATSMessage;
+use messages::HaltReason;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+use messages::RetailPriceImproveMsg;
+use messages::SymbolClearMsg;
+use messages::TradeBreakMsg;
+use messages::TradeMsg;
+use messages::TradingStatusMsg;
+use options::SymbolMappingMsg;
+
+// what a sender does with a value when the channel's full.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    // until the receiver makes room
+    #[default]
+    Block,
+    // makes room by dropping the oldest queued value, counted in the stats
+    DropOldest,
+    // to a file in this directory
+    Spill(PathBuf)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub sent       : u64,
+    pub received   : u64,
+    pub dropped    : u64,
+    pub spilled    : u64,
+    // sends that found the channel full and waited
+    pub blocked    : u64,
+    // most values ever queued in memory at once
+    pub high_water : u64
+}
+
+// What can be spilled to disk: written out and read back as the same value.
+pub trait Spill : Sized {
+    fn spill<W : Write>( &self, w : &mut W ) -> io::Result<()>;
+    fn unspill<R : Read>( r : &mut R ) -> io::Result<Self>;
+}
+
+impl Spill for MarketEvent {
+    fn spill<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        self.write_to(w)
+    }
+
+    fn unspill<R : Read>( r : &mut R ) -> io::Result<MarketEvent> {
+        MarketEvent::read_from(r)?.ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+}
+
+// Each field as it is in memory, so a message comes back exactly as it went out whatever its
+// wire forms would make of it: integers little endian, chars as u32s, strings a u16 length
+// then the bytes, options a 0/1 byte then the value.
+trait SpillField : Sized {
+    fn put<W : Write>( &self, w : &mut W ) -> io::Result<()>;
+    fn get<R : Read>( r : &mut R ) -> io::Result<Self>;
+}
+
+fn bad_spill( what : &str ) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("spilled {}", what))
+}
+
+macro_rules! create_spill_field_impl {
+    ($($t : ty),+) => (
+        $(impl SpillField for $t {
+            fn put<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+                w.write_all(&self.to_le_bytes())
+            }
+
+            fn get<R : Read>( r : &mut R ) -> io::Result<$t> {
+                let mut b = [0u8; ::std::mem::size_of::<$t>()];
+                r.read_exact(&mut b)?;
+                Ok(<$t>::from_le_bytes(b))
+            }
+        })+
+    )
+}
+
+create_spill_field_impl!(u8, u16, u32, u64);
+
+impl SpillField for char {
+    fn put<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        u32::from(*self).put(w)
+    }
+
+    fn get<R : Read>( r : &mut R ) -> io::Result<char> {
+        char::from_u32(u32::get(r)?).ok_or_else(|| bad_spill("char isn't one"))
+    }
+}
+
+impl SpillField for String {
+    fn put<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        u16::try_from(self.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "string too long to spill"))?.put(w)?;
+        w.write_all(self.as_bytes())
+    }
+
+    fn get<R : Read>( r : &mut R ) -> io::Result<String> {
+        let mut b = vec![0u8; u16::get(r)? as usize];
+        r.read_exact(&mut b)?;
+        String::from_utf8(b).map_err(|_| bad_spill("string isn't UTF-8"))
+    }
+}
+
+impl SpillField for HaltReason {
+    fn put<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        self.code().put(w)
+    }
+
+    fn get<R : Read>( r : &mut R ) -> io::Result<HaltReason> {
+        Ok(HaltReason::from_code(&String::get(r)?))
+    }
+}
+
+impl SpillField for LoginStatus {
+    fn put<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        char::from(*self).put(w)
+    }
+
+    fn get<R : Read>( r : &mut R ) -> io::Result<LoginStatus> {
+        Ok(LoginStatus::from(char::get(r)?))
+    }
+}
+
+impl<T : SpillField> SpillField for Option<T> {
+    fn put<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        match *self {
+            Some(ref v) => { 1u8.put(w)?; v.put(w) },
+            None        => 0u8.put(w)
+        }
+    }
+
+    fn get<R : Read>( r : &mut R ) -> io::Result<Option<T>> {
+        if u8::get(r)? == 0 { Ok(None) } else { T::get(r).map(Some) }
+    }
+}
+
+macro_rules! create_spill_impl {
+    ($objname : ident, $($field : ident),+) => (
+        impl Spill for $objname {
+            fn spill<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+                $(self.$field.put(w)?;)+
+                Ok(())
+            }
+
+            fn unspill<R : Read>( r : &mut R ) -> io::Result<$objname> {
+                Ok($objname{ $($field : SpillField::get(r)?),+ })
+            }
+        }
+    )
+}
+
+create_spill_impl!(AuctionSummaryMsg, timestamp, msg_type, symbol, auction_type, price, shares);
+create_spill_impl!(AddOrderMsg, timestamp, msg_type, order_id, side, shares, symbol, price, display, part_id);
+create_spill_impl!(AuctionUpdateMsg, timestamp, msg_type, symbol, auction_type, reference_price, buyshares,
+                   sellshares, indicative_price, auction_only_price);
+create_spill_impl!(OrderCancelMsg, timestamp, msg_type, order_id, shares);
+create_spill_impl!(OrderExecutedMsg, timestamp, msg_type, order_id, shares, exec_id);
+create_spill_impl!(RetailPriceImproveMsg, timestamp, msg_type, symbol, retail_price_improve);
+create_spill_impl!(TradeBreakMsg, timestamp, msg_type, exec_id);
+create_spill_impl!(TradeMsg, timestamp, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_spill_impl!(TradingStatusMsg, timestamp, msg_type, symbol, halt_status, reg_sho_action, reserved1, reserved2,
+                   halt_reason, halt_flag);
+create_spill_impl!(SymbolClearMsg, timestamp, msg_type, symbol);
+create_spill_impl!(BinaryAddOrderMsg, time_offset, msg_type, order_id, side, shares, symbol, price, display, part_id);
+create_spill_impl!(BinaryOrderExecutedMsg, time_offset, msg_type, order_id, shares, exec_id);
+create_spill_impl!(ReduceSizeMsg, time_offset, msg_type, order_id, shares);
+create_spill_impl!(ModifyOrderMsg, time_offset, msg_type, order_id, shares, price, display);
+create_spill_impl!(DeleteOrderMsg, time_offset, msg_type, order_id);
+create_spill_impl!(BinaryTradeMsg, time_offset, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_spill_impl!(UnitClearMsg, time_offset, msg_type);
+create_spill_impl!(TimeMsg, msg_type, time);
+create_spill_impl!(TradeExpandedMsg, time_offset, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_spill_impl!(OrderExecutedAtPriceSizeMsg, time_offset, msg_type, order_id, shares, remaining_shares, exec_id,
+                   price);
+create_spill_impl!(CalculatedValueMsg, time_offset, msg_type, symbol, value_category, value, value_timestamp);
+create_spill_impl!(EndOfSessionMsg, time_offset, msg_type);
+create_spill_impl!(LoginMsg, msg_type, session_sub_id, username, password);
+create_spill_impl!(LoginResponseMsg, msg_type, status);
+create_spill_impl!(SymbolMappingMsg, msg_type, feed_symbol, osi_symbol, symbol_condition, underlying);
+#[cfg(feature = "europe")]
+create_spill_impl!(TradeReportMsg, timestamp, msg_type, exec_id, shares, symbol, price, currency, flags);
+
+// a byte for the type then its fields.
+macro_rules! create_message_spill_impl {
+    ($($(#[$attr : meta])* $code : literal => $objname : ident),+) => (
+        impl Spill for BATSMessage {
+            fn spill<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+                match *self {
+                    $($(#[$attr])* BATSMessage::$objname(ref m) => { w.write_all(&[$code])?; m.spill(w) }),+
+                }
+            }
+
+            fn unspill<R : Read>( r : &mut R ) -> io::Result<BATSMessage> {
+                match u8::get(r)? {
+                    $($(#[$attr])* $code => Ok(BATSMessage::$objname($objname::unspill(r)?)),)+
+                    _ => Err(bad_spill("message type isn't known"))
+                }
+            }
+        }
+    )
+}
+
+create_message_spill_impl!(
+    0 => AuctionSummaryMsg,
+    1 => AddOrderMsg,
+    2 => AuctionUpdateMsg,
+    3 => OrderCancelMsg,
+    4 => OrderExecutedMsg,
+    5 => RetailPriceImproveMsg,
+    6 => TradeBreakMsg,
+    7 => TradeMsg,
+    8 => TradingStatusMsg,
+    9 => SymbolClearMsg,
+    10 => BinaryAddOrderMsg,
+    11 => BinaryOrderExecutedMsg,
+    12 => ReduceSizeMsg,
+    13 => ModifyOrderMsg,
+    14 => DeleteOrderMsg,
+    15 => BinaryTradeMsg,
+    16 => UnitClearMsg,
+    17 => TimeMsg,
+    18 => TradeExpandedMsg,
+    19 => OrderExecutedAtPriceSizeMsg,
+    20 => CalculatedValueMsg,
+    21 => EndOfSessionMsg,
+    22 => LoginMsg,
+    23 => LoginResponseMsg,
+    24 => SymbolMappingMsg,
+    #[cfg(feature = "europe")]
+    25 => TradeReportMsg
+);
+
+static SPILL_FILES : AtomicUsize = AtomicUsize::new(0);
+
+struct SpillFile {
+    path    : PathBuf,
+    writer  : BufWriter<File>,
+    reader  : BufReader<File>,
+    written : u64,
+    read    : u64
+}
+
+impl SpillFile {
+    fn create( dir : &Path ) -> io::Result<SpillFile> {
+        let n = SPILL_FILES.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("orderbook-{}-{}.spill", process::id(), n));
+        let writer = OpenOptions::new().create_new(true).write(true).open(&path)?;
+        let reader = File::open(&path)?;
+        Ok(SpillFile{ path, writer : BufWriter::new(writer), reader : BufReader::new(reader), written : 0, read : 0 })
+    }
+
+    fn pending( &self ) -> bool {
+        self.read < self.written
+    }
+
+    fn push<T : Spill>( &mut self, value : &T ) -> io::Result<()> {
+        value.spill(&mut self.writer)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    fn pop<T : Spill>( &mut self ) -> io::Result<T> {
+        self.writer.flush()?;
+        let value = T::unspill(&mut self.reader)?;
+        self.read += 1;
+        if !self.pending() {
+            // all read back, start the file over rather than let it grow all day
+            self.writer.get_ref().set_len(0)?;
+            self.writer.seek(SeekFrom::Start(0))?;
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+        Ok(value)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop( &mut self ) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+struct State<T> {
+    queue  : VecDeque<T>,
+    spill  : Option<SpillFile>,
+    closed : bool,   // the sender's been dropped
+    gone   : bool,   // the receiver has
+    stats  : ChannelStats
+}
+
+struct Shared<T> {
+    state     : Mutex<State<T>>,
+    not_empty : Condvar,
+    not_full  : Condvar,
+    capacity  : usize,
+    policy    : Backpressure
+}
+
+impl<T> Shared<T> {
+    fn stats( &self ) -> ChannelStats {
+        self.state.lock().unwrap().stats
+    }
+}
+
+pub struct Sender<T> {
+    shared : Arc<Shared<T>>
+}
+
+pub struct Receiver<T> {
+    shared : Arc<Shared<T>>
+}
+
+pub fn channel<T : Spill>( capacity : usize, policy : Backpressure ) -> (Sender<T>, Receiver<T>) {
+    let state = State{ queue : VecDeque::new(), spill : None, closed : false, gone : false, stats : ChannelStats::default() };
+    let shared = Arc::new(Shared{ state : Mutex::new(state), not_empty : Condvar::new(), not_full : Condvar::new(),
+                                  capacity : capacity.max(1), policy });
+    (Sender{ shared : shared.clone() }, Receiver{ shared })
+}
+
+impl<T : Spill> Sender<T> {
+    // a BrokenPipe error once the receiver's gone, or the spill file's error.
+    pub fn send( &self, value : T ) -> io::Result<()> {
+        let shared = &*self.shared;
+        let mut state = shared.state.lock().unwrap();
+        if state.gone {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        let full = state.queue.len() >= shared.capacity;
+        match shared.policy {
+            Backpressure::Block if full => {
+                state.stats.blocked += 1;
+                while state.queue.len() >= shared.capacity && !state.gone {
+                    state = shared.not_full.wait(state).unwrap();
+                }
+                if state.gone {
+                    return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+                }
+            },
+            Backpressure::DropOldest if full => {
+                state.queue.pop_front();
+                state.stats.dropped += 1;
+            },
+            Backpressure::Spill(ref dir) if full || state.spill.as_ref().is_some_and(SpillFile::pending) => {
+                // once spilling, everything goes to the file until it's read back, to keep the order
+                if state.spill.is_none() {
+                    state.spill = Some(SpillFile::create(dir)?);
+                }
+                state.spill.as_mut().unwrap().push(&value)?;
+                state.stats.spilled += 1;
+                state.stats.sent += 1;
+                shared.not_empty.notify_one();
+                return Ok(());
+            },
+            _ => {}
+        }
+        state.queue.push_back(value);
+        state.stats.sent += 1;
+        state.stats.high_water = state.stats.high_water.max(state.queue.len() as u64);
+        shared.not_empty.notify_one();
+        Ok(())
+    }
+
+    pub fn stats( &self ) -> ChannelStats {
+        self.shared.stats()
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop( &mut self ) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.not_empty.notify_all();
+    }
+}
+
+impl<T : Spill> Receiver<T> {
+    // waits for the next value, None once the sender's gone and everything's been taken.
+    pub fn recv( &self ) -> io::Result<Option<T>> {
+        let shared = &*self.shared;
+        let mut state = shared.state.lock().unwrap();
+        loop {
+            if let Some(value) = state.queue.pop_front() {
+                state.stats.received += 1;
+                shared.not_full.notify_one();
+                return Ok(Some(value));
+            }
+            if let Some(ref mut spill) = state.spill {
+                if spill.pending() {
+                    let value = spill.pop()?;
+                    state.stats.received += 1;
+                    return Ok(Some(value));
+                }
+            }
+            if state.closed {
+                return Ok(None);
+            }
+            state = shared.not_empty.wait(state).unwrap();
+        }
+    }
+
+    // values waiting, in memory and on disk.
+    pub fn len( &self ) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.queue.len() + state.spill.as_ref().map_or(0, |s| (s.written - s.read) as usize)
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats( &self ) -> ChannelStats {
+        self.shared.stats()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop( &mut self ) {
+        self.shared.state.lock().unwrap().gone = true;
+        self.shared.not_full.notify_all();
+    }
+}
+
+// Where the events end up, eg. a recorder or a publisher. Runs on a thread of its own.
+pub trait EventSink : Send {
+    fn event( &mut self, event : &MarketEvent ) -> io::Result<()>;
+
+    // after the last event.
+    fn finish( &mut self ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F> EventSink for F where F : FnMut(&MarketEvent) -> io::Result<()> + Send {
+    fn event( &mut self, event : &MarketEvent ) -> io::Result<()> {
+        self(event)
+    }
+}
+
+pub struct PipelineBuilder {
+    capacity : usize,
+    decoded  : Backpressure,
+    events   : Backpressure,
+    books    : BookManager,
+    sinks    : Vec<Box<dyn EventSink>>
+}
+
+impl Default for PipelineBuilder {
+    fn default() -> PipelineBuilder {
+        PipelineBuilder{ capacity : 65536, decoded : Backpressure::Block, events : Backpressure::Block, books : BookManager::new(),
+                         sinks : Vec::new() }
+    }
+}
+
+impl PipelineBuilder {
+    pub fn new() -> PipelineBuilder {
+        PipelineBuilder::default()
+    }
+
+    // of each channel, in values.
+    pub fn capacity( mut self, capacity : usize ) -> PipelineBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    // between the decoder and the books.
+    pub fn decoded( mut self, policy : Backpressure ) -> PipelineBuilder {
+        self.decoded = policy;
+        self
+    }
+
+    // between the books and each sink.
+    pub fn events( mut self, policy : Backpressure ) -> PipelineBuilder {
+        self.events = policy;
+        self
+    }
+
+    // to start from, eg. one set up with a ViolationPolicy.
+    pub fn books( mut self, books : BookManager ) -> PipelineBuilder {
+        self.books = books;
+        self
+    }
+
+    pub fn sink<S : EventSink + 'static>( mut self, sink : S ) -> PipelineBuilder {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    // starts the threads, the decoder pulling messages from `msgs`.
+    pub fn spawn<I>( self, msgs : I ) -> BoundedPipeline where I : Iterator<Item = BATSMessage> + Send + 'static {
+        let (tx, rx) = channel(self.capacity, self.decoded);
+        let decoded = tx.shared.clone();
+        let decoder = thread::spawn(move || {
+            for msg in msgs {
+                tx.send(msg)?;
+            }
+            Ok(())
+        });
+
+        let mut senders = Vec::new();
+        let mut events = Vec::new();
+        let mut sinks = Vec::new();
+        for mut sink in self.sinks {
+            let (tx, rx) = channel::<MarketEvent>(self.capacity, self.events.clone());
+            events.push(tx.shared.clone());
+            senders.push(Some(tx));
+            sinks.push(thread::spawn(move || {
+                while let Some(event) = rx.recv()? {
+                    sink.event(&event)?;
+                }
+                sink.finish()
+            }));
+        }
+
+        let mut books = self.books;
+        let book = thread::spawn(move || {
+            let mut clock = TimestampComposer::new();
+            loop {
+                let msg = match rx.recv() {
+                    Ok(Some(msg)) => msg,
+                    Ok(None)      => return (books, Ok(())),
+                    Err(e)        => return (books, Err(e))
+                };
+                books.apply(&msg);
+                let timestamp = clock.compose(&msg);
+                if let Some(event) = MarketEvent::from_bats(&msg, timestamp) {
+                    for tx in senders.iter_mut() {
+                        // a sink that's failed is left behind, its error comes out of join
+                        if tx.as_ref().is_some_and(|s| s.send(event.clone()).is_err()) {
+                            *tx = None;
+                        }
+                    }
+                }
+            }
+        });
+        BoundedPipeline{ decoder, book, sinks, decoded, events }
+    }
+}
+
+type BookResult = (BookManager, io::Result<()>);
+
+pub struct BoundedPipeline {
+    decoder : thread::JoinHandle<io::Result<()>>,
+    book    : thread::JoinHandle<BookResult>,
+    sinks   : Vec<thread::JoinHandle<io::Result<()>>>,
+    decoded : Arc<Shared<BATSMessage>>,
+    events  : Vec<Arc<Shared<MarketEvent>>>
+}
+
+fn joined<R>( handle : thread::JoinHandle<R> ) -> R {
+    match handle.join() {
+        Ok(r)  => r,
+        // a stage that panicked takes the pipeline down with it
+        Err(e) => std::panic::resume_unwind(e)
+    }
+}
+
+impl BoundedPipeline {
+    // of the channel between the decoder and the books.
+    pub fn decoded_stats( &self ) -> ChannelStats {
+        self.decoded.stats()
+    }
+
+    // of the channel to each sink, in the order they were added.
+    pub fn event_stats( &self ) -> Vec<ChannelStats> {
+        self.events.iter().map(|e| e.stats()).collect()
+    }
+
+    // waits for the decoder to run out and everything after it to finish, then hands the
+    // books back. The first error any stage had, if there was one.
+    pub fn join( self ) -> io::Result<BookManager> {
+        let decoded = joined(self.decoder);
+        let (books, applied) = joined(self.book);
+        let mut result = decoded.and(applied);
+        for sink in self.sinks {
+            result = result.and(joined(sink));
+        }
+        result.map(|_| books)
+    }
+}
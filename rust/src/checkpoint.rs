@@ -0,0 +1,282 @@
+
+// The books saved every so often with how far into the feed they are, so a restart picks up
+// from the last save rather than replaying the day:
+//
+//   let mut checkpoints = Checkpointer::open("/var/lib/books.ckpt", &mut books)?.every(1_000_000);
+//   for msg in msgs.skip(checkpoints.position() as usize) {
+//       checkpoints.apply(&msg, &mut books)?;
+//   }
+//
+// How far in is kept two ways: the messages applied through apply() (a position to skip a file
+// to), and the next sequence number of each unit through apply_sequenced(), whose messages
+// from before the checkpoint are passed over. A save goes to a temporary file renamed over the
+// last, so a crash while saving leaves the one before. Each book's checksum is checked again
+// once it's restored.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use binary::TimestampComposer;
+use book::BookManager;
+use book::BookMode;
+use book::BookSnapshot;
+use book::Side;
+use book::SnapshotOrder;
+use messages::BATSMessage;
+
+const MAGIC   : [u8; 4] = *b"OBCP";
+const VERSION : u16 = 1;
+
+fn invalid( what : String ) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    // messages applied, from the start of the feed
+    pub position  : u64,
+    // by unit, the sequence number of the next message
+    pub sequences : BTreeMap<u8, u32>,
+    // of the last message applied, nanoseconds past midnight
+    pub timestamp : u64,
+    pub books     : Vec<BookSnapshot>
+}
+
+struct Fields<'a, R : 'a> {
+    r : &'a mut R
+}
+
+impl<'a, R : Read> Fields<'a, R> {
+    fn bytes<const N : usize>( &mut self ) -> io::Result<[u8; N]> {
+        let mut b = [0u8; N];
+        self.r.read_exact(&mut b)?;
+        Ok(b)
+    }
+
+    fn u8( &mut self ) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32( &mut self ) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64( &mut self ) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn string( &mut self ) -> io::Result<String> {
+        let mut s = vec![0u8; self.u8()? as usize];
+        self.r.read_exact(&mut s)?;
+        String::from_utf8(s).map_err(|_| invalid(String::from("a symbol isn't utf-8")))
+    }
+}
+
+impl Checkpoint {
+    pub fn take( books : &BookManager, position : u64, sequences : BTreeMap<u8, u32>, timestamp : u64 ) -> Checkpoint {
+        Checkpoint{ position, sequences, timestamp, books : books.snapshot() }
+    }
+
+    // restores the books, an InvalidData error if one's checksum doesn't come out the same.
+    pub fn restore( &self, books : &mut BookManager ) -> io::Result<()> {
+        books.restore(&self.books);
+        for snapshot in self.books.iter() {
+            let symbol = snapshot.symbol.as_deref().unwrap_or_default();
+            if let Some(book) = books.book(symbol) {
+                book.verify_checksum(snapshot.checksum).map_err(|e| invalid(format!("{}: {}", symbol, e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_to<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        w.write_all(&MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.position.to_le_bytes())?;
+        w.write_all(&self.timestamp.to_le_bytes())?;
+        w.write_all(&[self.sequences.len() as u8])?;
+        for (&unit, &next) in self.sequences.iter() {
+            w.write_all(&[unit])?;
+            w.write_all(&next.to_le_bytes())?;
+        }
+        w.write_all(&(self.books.len() as u32).to_le_bytes())?;
+        for book in self.books.iter() {
+            let symbol = book.symbol.as_deref().unwrap_or_default().as_bytes();
+            let n = symbol.len().min(255);
+            w.write_all(&[n as u8])?;
+            w.write_all(&symbol[..n])?;
+            w.write_all(&[match book.mode { BookMode::L2 => 2, BookMode::L3 => 3 }])?;
+            w.write_all(&book.checksum.to_le_bytes())?;
+            w.write_all(&(book.orders.len() as u32).to_le_bytes())?;
+            for o in book.orders.iter() {
+                w.write_all(&o.order_id.to_le_bytes())?;
+                w.write_all(&[o.side.to_char() as u8])?;
+                w.write_all(&o.price.to_le_bytes())?;
+                w.write_all(&o.shares.to_le_bytes())?;
+                w.write_all(&o.timestamp.to_le_bytes())?;
+                w.write_all(&[o.displayed as u8])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R : Read>( r : &mut R ) -> io::Result<Checkpoint> {
+        let mut f = Fields{ r };
+        if f.bytes::<4>()? != MAGIC {
+            return Err(invalid(String::from("not a checkpoint")));
+        }
+        let version = u16::from_le_bytes(f.bytes()?);
+        if version != VERSION {
+            return Err(invalid(format!("checkpoint version {} isn't supported", version)));
+        }
+        let position = f.u64()?;
+        let timestamp = f.u64()?;
+        let mut sequences = BTreeMap::new();
+        for _ in 0..f.u8()? {
+            let unit = f.u8()?;
+            sequences.insert(unit, f.u32()?);
+        }
+        let mut books = Vec::new();
+        for _ in 0..f.u32()? {
+            let symbol = f.string()?;
+            let mode = match f.u8()? {
+                2 => BookMode::L2,
+                3 => BookMode::L3,
+                m => return Err(invalid(format!("unknown book mode {}", m)))
+            };
+            let checksum = f.u64()?;
+            let n = f.u32()?;
+            let mut orders = Vec::with_capacity(n.min(1 << 20) as usize);
+            for _ in 0..n {
+                orders.push(SnapshotOrder{ order_id : f.u64()?, side : Side::from_char(f.u8()? as char), price : f.u64()?,
+                                           shares : f.u32()?, timestamp : f.u64()?, displayed : f.u8()? != 0 });
+            }
+            books.push(BookSnapshot{ symbol : Some(symbol), mode, orders, checksum });
+        }
+        Ok(Checkpoint{ position, sequences, timestamp, books })
+    }
+
+    // by way of a temporary file next to it.
+    pub fn save<P : AsRef<Path>>( &self, path : P ) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        self.write_to(&mut w)?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    // None when there's no checkpoint there yet.
+    pub fn load<P : AsRef<Path>>( path : P ) -> io::Result<Option<Checkpoint>> {
+        match File::open(path) {
+            Ok(file)                                          => Checkpoint::read_from(&mut BufReader::new(file)).map(Some),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e)                                            => Err(e)
+        }
+    }
+}
+
+// Applies messages to the books, saving a checkpoint every so many.
+pub struct Checkpointer {
+    path      : PathBuf,
+    every     : u64,
+    since     : u64,
+    position  : u64,
+    sequences : BTreeMap<u8, u32>,
+    clock     : TimestampComposer,
+    timestamp : u64,
+    resumed   : bool,
+    saved     : u64
+}
+
+impl Checkpointer {
+    // restores the books from the checkpoint at `path` if there is one, or leaves them be.
+    pub fn open<P : AsRef<Path>>( path : P, books : &mut BookManager ) -> io::Result<Checkpointer> {
+        let mut c = Checkpointer{ path : path.as_ref().to_path_buf(), every : 100_000, since : 0, position : 0, sequences : BTreeMap::new(),
+                                  clock : TimestampComposer::new(), timestamp : 0, resumed : false, saved : 0 };
+        if let Some(checkpoint) = Checkpoint::load(&c.path)? {
+            checkpoint.restore(books)?;
+            c.position = checkpoint.position;
+            c.sequences = checkpoint.sequences;
+            c.timestamp = checkpoint.timestamp;
+            c.resumed = true;
+        }
+        Ok(c)
+    }
+
+    // messages between saves, 100,000 by default.
+    pub fn every( mut self, messages : u64 ) -> Checkpointer {
+        self.every = messages.max(1);
+        self
+    }
+
+    // whether open() found a checkpoint.
+    pub fn resumed( &self ) -> bool {
+        self.resumed
+    }
+
+    // messages applied, the ones before the checkpoint included.
+    pub fn position( &self ) -> u64 {
+        self.position
+    }
+
+    pub fn next_sequence( &self, unit : u8 ) -> Option<u32> {
+        self.sequences.get(&unit).cloned()
+    }
+
+    pub fn timestamp( &self ) -> u64 {
+        self.timestamp
+    }
+
+    // checkpoints written since open().
+    pub fn saved( &self ) -> u64 {
+        self.saved
+    }
+
+    pub fn apply( &mut self, msg : &BATSMessage, books : &mut BookManager ) -> io::Result<()> {
+        self.record(None, msg, books)
+    }
+
+    fn record( &mut self, unit : Option<u8>, msg : &BATSMessage, books : &mut BookManager ) -> io::Result<()> {
+        self.timestamp = self.clock.compose(msg);
+        match unit {
+            Some(unit) => books.apply_unit(unit, msg),
+            None       => books.apply(msg)
+        };
+        self.position += 1;
+        self.since += 1;
+        if self.since >= self.every {
+            self.save(books)?;
+        }
+        Ok(())
+    }
+
+    // false for a message the checkpoint already has in it.
+    pub fn apply_sequenced( &mut self, unit : u8, sequence : u32, msg : &BATSMessage, books : &mut BookManager ) -> io::Result<bool> {
+        if sequence != 0 && self.sequences.get(&unit).is_some_and(|&next| sequence < next) {
+            return Ok(false);
+        }
+        if sequence != 0 {
+            self.sequences.insert(unit, sequence.wrapping_add(1));
+        }
+        self.record(Some(unit), msg, books)?;
+        Ok(true)
+    }
+
+    // saves now, eg. on a clean shutdown.
+    pub fn save( &mut self, books : &BookManager ) -> io::Result<()> {
+        Checkpoint::take(books, self.position, self.sequences.clone(), self.timestamp).save(&self.path)?;
+        self.since = 0;
+        self.saved += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+
+// Reading captures whether or not they're compressed: open() looks at the first bytes of the
+// file and decompresses gzip (.gz, with the gzip feature) or zstd (.zst, with the zstd feature)
+// on the fly, anything else is read as it is.
+//
+//   for event in compressed::open_pitch("pitch_20251014.txt.zst")? { ... }
+//
+// A compressed file whose feature isn't built in is an Unsupported error rather than garbage.
+
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+
+use feed::PitchReader;
+
+const GZIP_MAGIC : [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC : [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd
+}
+
+impl Compression {
+    // from the start of the data, which needs to be at least 4 bytes unless that's all there is.
+    pub fn detect( start : &[u8] ) -> Compression {
+        if start.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if start.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+pub type Input = Box<dyn BufRead + Send>;
+
+#[cfg(not(all(feature = "gzip", feature = "zstd")))]
+fn unsupported( feature : &str ) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, format!("compressed input needs the {} feature", feature))
+}
+
+// takes a 4 byte look at `reader` to tell what it holds.
+pub fn reader<R : Read + Send + 'static>( reader : R ) -> io::Result<Input> {
+    let mut reader = BufReader::with_capacity(1 << 16, reader);
+    let compression = Compression::detect(reader.fill_buf()?);
+    match compression {
+        Compression::None => Ok(Box::new(reader)),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader)))),
+        #[cfg(not(feature = "gzip"))]
+        Compression::Gzip => Err(unsupported("gzip")),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(BufReader::new(::zstd::stream::read::Decoder::with_buffer(reader)?))),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err(unsupported("zstd"))
+    }
+}
+
+pub fn open<P : AsRef<Path>>( path : P ) -> io::Result<Input> {
+    reader(File::open(path)?)
+}
+
+pub fn open_pitch<P : AsRef<Path>>( path : P ) -> io::Result<PitchReader<Input>> {
+    Ok(PitchReader::new(open(path)?))
+}
@@ -0,0 +1,67 @@
+
+// Messages that can't be right for the book they're applied to: executions or cancels for more
+// shares than the order has left or for an order that isn't resting, adds reusing the id of an
+// order that still is, and executions reporting a remainder that doesn't add up. The book
+// still applies them as best it can (an order reduced past nothing is gone), these say where
+// it stopped matching the exchange's.
+
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Problem {
+    UnknownOrder,
+    Overfill{ remaining : u32, shares : u32 },
+    Overcancel{ remaining : u32, shares : u32 },
+    DuplicateOrder{ symbol : String, remaining : u32 },
+    // an execution's own remainder against the shares it leaves by the book
+    RemainingMismatch{ expected : u32, reported : u32 }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Inconsistency {
+    pub timestamp : u64,
+    pub msg_type  : char,
+    pub order_id  : u64,
+    // the book it's for, when that's known
+    pub symbol    : Option<String>,
+    pub problem   : Problem
+}
+
+// `resting` looks an order up as (symbol, shares left). Binary messages are given `time`, the
+// composed time of the message, text ones keep their own timestamp as BookManager's violations do.
+// Deletes and modifies only check that the order's there.
+pub fn check<F : Fn(u64) -> Option<(String, u32)>>( msg : &BATSMessage, time : u64, resting : F ) -> Option<Inconsistency> {
+    let duplicate = |timestamp, msg_type, order_id| {
+        let (symbol, remaining) = resting(order_id)?;
+        Some(Inconsistency{ timestamp, msg_type, order_id, symbol : Some(symbol.clone()),
+                            problem : Problem::DuplicateOrder{ symbol, remaining } })
+    };
+    let (timestamp, msg_type, order_id, shares, reported, executed) = match *msg {
+        BATSMessage::AddOrderMsg(ref m)       => return duplicate(u64::from(m.timestamp), m.msg_type, m.order_id),
+        BATSMessage::BinaryAddOrderMsg(ref m) => return duplicate(time, char::from(m.msg_type), m.order_id),
+        BATSMessage::OrderExecutedMsg(ref m) => (u64::from(m.timestamp), m.msg_type, m.order_id, m.shares, None, true),
+        BATSMessage::BinaryOrderExecutedMsg(ref m) => (time, char::from(m.msg_type), m.order_id, m.shares, None, true),
+        BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) =>
+            (time, char::from(m.msg_type), m.order_id, m.shares, Some(m.remaining_shares), true),
+        BATSMessage::OrderCancelMsg(ref m) => (u64::from(m.timestamp), m.msg_type, m.order_id, m.shares, None, false),
+        BATSMessage::ReduceSizeMsg(ref m)  => (time, char::from(m.msg_type), m.order_id, m.shares, None, false),
+        BATSMessage::DeleteOrderMsg(ref m) => (time, char::from(m.msg_type), m.order_id, 0, None, false),
+        BATSMessage::ModifyOrderMsg(ref m) => (time, char::from(m.msg_type), m.order_id, 0, None, false),
+        _ => return None
+    };
+    let found = resting(order_id);
+    let problem = match found {
+        None => Problem::UnknownOrder,
+        Some((_, remaining)) if shares > remaining => {
+            if executed { Problem::Overfill{ remaining, shares } } else { Problem::Overcancel{ remaining, shares } }
+        },
+        // a remainder below what's left is the rest cancelled, above it is wrong
+        Some((_, remaining)) => match reported {
+            Some(r) if r > remaining - shares => Problem::RemainingMismatch{ expected : remaining - shares, reported : r },
+            _ => return None
+        }
+    };
+    Some(Inconsistency{ timestamp, msg_type, order_id, symbol : found.map(|(s, _)| s), problem })
+}
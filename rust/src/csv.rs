@@ -0,0 +1,225 @@
+
+// CSV export, one table per message type since the fields differ so much between them.
+// Every table's columns are the struct's fields in declaration order, symbols and other
+// text fields have their padding trimmed and missing optional values are left empty.
+// Session messages (login, time, ...) aren't exported.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use binary::BinaryAddOrderMsg;
+use binary::BinaryOrderExecutedMsg;
+use binary::BinaryTradeMsg;
+use binary::CalculatedValueMsg;
+use binary::DeleteOrderMsg;
+use binary::ModifyOrderMsg;
+use binary::OrderExecutedAtPriceSizeMsg;
+use binary::ReduceSizeMsg;
+use binary::TradeExpandedMsg;
+use binary::UnitClearMsg;
+use messages::AddOrderMsg;
+use messages::AuctionSummaryMsg;
+use messages::AuctionUpdateMsg;
+use messages::BATSMessage;
+use messages::HaltReason;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+use messages::RetailPriceImproveMsg;
+use messages::SymbolClearMsg;
+use messages::TradeBreakMsg;
+use messages::TradeMsg;
+use messages::TradingStatusMsg;
+
+pub trait CsvField {
+    fn to_csv( &self ) -> String;
+}
+
+macro_rules! create_csv_field_impl {
+    ($($t : ty),+) => (
+        $(impl CsvField for $t {
+            fn to_csv( &self ) -> String {
+                self.to_string()
+            }
+        })+
+    )
+}
+
+create_csv_field_impl!(u8, u32, u64, char);
+
+impl CsvField for String {
+    fn to_csv( &self ) -> String {
+        let s = self.trim_end();
+        if s.contains(&[',', '"', '\n'][..]) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            String::from(s)
+        }
+    }
+}
+
+impl CsvField for HaltReason {
+    fn to_csv( &self ) -> String {
+        self.code()
+    }
+}
+
+impl<T : CsvField> CsvField for Option<T> {
+    fn to_csv( &self ) -> String {
+        match *self {
+            Some(ref v) => v.to_csv(),
+            None        => String::new()
+        }
+    }
+}
+
+pub trait CsvRecord {
+    fn table() -> &'static str;
+    fn header() -> &'static [&'static str];
+    fn record( &self ) -> Vec<String>;
+}
+
+macro_rules! create_csv_record_impl {
+    ($objname : ident, $table : expr, $($field : ident),+) => (
+        impl CsvRecord for $objname {
+            fn table() -> &'static str {
+                $table
+            }
+
+            fn header() -> &'static [&'static str] {
+                &[$(stringify!($field)),+]
+            }
+
+            fn record( &self ) -> Vec<String> {
+                vec![$(self.$field.to_csv()),+]
+            }
+        }
+    )
+}
+
+create_csv_record_impl!(AuctionSummaryMsg, "auction_summary", timestamp, msg_type, symbol, auction_type, price, shares);
+create_csv_record_impl!(AddOrderMsg, "add_order", timestamp, msg_type, order_id, side, shares, symbol, price, display, part_id);
+create_csv_record_impl!(AuctionUpdateMsg, "auction_update", timestamp, msg_type, symbol, auction_type, reference_price,
+                        buyshares, sellshares, indicative_price, auction_only_price);
+create_csv_record_impl!(OrderCancelMsg, "order_cancel", timestamp, msg_type, order_id, shares);
+create_csv_record_impl!(OrderExecutedMsg, "order_executed", timestamp, msg_type, order_id, shares, exec_id);
+create_csv_record_impl!(RetailPriceImproveMsg, "retail_price_improve", timestamp, msg_type, symbol, retail_price_improve);
+create_csv_record_impl!(TradeBreakMsg, "trade_break", timestamp, msg_type, exec_id);
+create_csv_record_impl!(TradeMsg, "trade", timestamp, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_csv_record_impl!(TradingStatusMsg, "trading_status", timestamp, msg_type, symbol, halt_status, reg_sho_action,
+                        halt_reason, halt_flag);
+create_csv_record_impl!(SymbolClearMsg, "symbol_clear", timestamp, msg_type, symbol);
+create_csv_record_impl!(BinaryAddOrderMsg, "binary_add_order", time_offset, msg_type, order_id, side, shares, symbol, price,
+                        display, part_id);
+create_csv_record_impl!(BinaryOrderExecutedMsg, "binary_order_executed", time_offset, msg_type, order_id, shares, exec_id);
+create_csv_record_impl!(ModifyOrderMsg, "modify_order", time_offset, msg_type, order_id, shares, price, display);
+create_csv_record_impl!(DeleteOrderMsg, "delete_order", time_offset, msg_type, order_id);
+create_csv_record_impl!(BinaryTradeMsg, "binary_trade", time_offset, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_csv_record_impl!(ReduceSizeMsg, "reduce_size", time_offset, msg_type, order_id, shares);
+create_csv_record_impl!(UnitClearMsg, "unit_clear", time_offset, msg_type);
+create_csv_record_impl!(TradeExpandedMsg, "trade_expanded", time_offset, msg_type, order_id, side, shares, symbol,
+                        price, exec_id);
+create_csv_record_impl!(OrderExecutedAtPriceSizeMsg, "order_executed_at_price_size", time_offset, msg_type, order_id,
+                        shares, remaining_shares, exec_id, price);
+create_csv_record_impl!(CalculatedValueMsg, "calculated_value", time_offset, msg_type, symbol, value_category, value,
+                        value_timestamp);
+
+pub type CsvRow = (&'static str, &'static [&'static str], Vec<String>);
+
+fn row<T : CsvRecord>( msg : &T ) -> Option<CsvRow> {
+    Some((T::table(), T::header(), msg.record()))
+}
+
+// table name, header and values for a message, None if it isn't exported.
+pub fn csv_row( msg : &BATSMessage ) -> Option<CsvRow> {
+    match *msg {
+        BATSMessage::AuctionSummaryMsg(ref m)           => row(m),
+        BATSMessage::AddOrderMsg(ref m)                 => row(m),
+        BATSMessage::AuctionUpdateMsg(ref m)            => row(m),
+        BATSMessage::OrderCancelMsg(ref m)              => row(m),
+        BATSMessage::OrderExecutedMsg(ref m)            => row(m),
+        BATSMessage::RetailPriceImproveMsg(ref m)       => row(m),
+        BATSMessage::TradeBreakMsg(ref m)               => row(m),
+        BATSMessage::TradeMsg(ref m)                    => row(m),
+        BATSMessage::TradingStatusMsg(ref m)            => row(m),
+        BATSMessage::SymbolClearMsg(ref m)              => row(m),
+        BATSMessage::BinaryAddOrderMsg(ref m)           => row(m),
+        BATSMessage::BinaryOrderExecutedMsg(ref m)      => row(m),
+        BATSMessage::ReduceSizeMsg(ref m)               => row(m),
+        BATSMessage::ModifyOrderMsg(ref m)              => row(m),
+        BATSMessage::DeleteOrderMsg(ref m)              => row(m),
+        BATSMessage::BinaryTradeMsg(ref m)              => row(m),
+        BATSMessage::UnitClearMsg(ref m)                => row(m),
+        BATSMessage::TradeExpandedMsg(ref m)            => row(m),
+        BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => row(m),
+        BATSMessage::CalculatedValueMsg(ref m)          => row(m),
+        _ => None
+    }
+}
+
+type Opener<W> = Box<dyn FnMut(&str) -> io::Result<W>>;
+
+// Writes each table to its own writer, opened (and given a header) on the table's first
+// row. to_dir puts them in <dir>/<table>.csv.
+pub struct CsvExporter<W : Write> {
+    open    : Opener<W>,
+    writers : HashMap<&'static str, W>
+}
+
+impl CsvExporter<BufWriter<File>> {
+    pub fn to_dir<P : AsRef<Path>>( dir : P ) -> CsvExporter<BufWriter<File>> {
+        let dir : PathBuf = dir.as_ref().to_path_buf();
+        CsvExporter::new(move |table| {
+            File::create(dir.join(format!("{}.csv", table))).map(BufWriter::new)
+        })
+    }
+}
+
+impl<W : Write> CsvExporter<W> {
+    pub fn new<F>( open : F ) -> CsvExporter<W> where F : FnMut(&str) -> io::Result<W> + 'static {
+        CsvExporter{ open : Box::new(open), writers : HashMap::new() }
+    }
+
+    // returns false for messages that aren't exported.
+    pub fn write( &mut self, msg : &BATSMessage ) -> io::Result<bool> {
+        let (table, header, values) = match csv_row(msg) {
+            Some(r) => r,
+            None    => return Ok(false)
+        };
+        if !self.writers.contains_key(table) {
+            let mut w = (self.open)(table)?;
+            writeln!(w, "{}", header.join(","))?;
+            self.writers.insert(table, w);
+        }
+        if let Some(w) = self.writers.get_mut(table) {
+            writeln!(w, "{}", values.join(","))?;
+        }
+        Ok(true)
+    }
+
+    pub fn write_all<I>( &mut self, msgs : I ) -> io::Result<usize>
+        where I : IntoIterator<Item = BATSMessage> {
+        let mut n = 0;
+        for msg in msgs {
+            if self.write(&msg)? {
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+
+    pub fn flush( &mut self ) -> io::Result<()> {
+        for w in self.writers.values_mut() {
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner( self ) -> HashMap<&'static str, W> {
+        self.writers
+    }
+}
@@ -0,0 +1,66 @@
+
+// Polars DataFrames from parsed messages or book levels. Messages go through the Arrow
+// collector's columns so the frames have the same tables and column types as the Arrow
+// and Parquet output.
+
+use std::collections::HashMap;
+
+use polars::prelude::Column as PlColumn;
+use polars::prelude::DataFrame;
+use polars::prelude::NamedFrom;
+use polars::prelude::PolarsResult;
+use polars::prelude::Series;
+
+use arrow::ArrowCollector;
+use arrow::Column;
+use messages::BATSMessage;
+
+fn to_series( name : &str, col : Column ) -> PlColumn {
+    let s = match col {
+        Column::UInt8(v)  => Series::new(name.into(), v),
+        Column::UInt32(v) => Series::new(name.into(), v),
+        Column::UInt64(v) => Series::new(name.into(), v),
+        Column::Utf8(v)   => Series::new(name.into(), v)
+    };
+    PlColumn::from(s)
+}
+
+// one frame per message type, keyed by table name (eg. "add_order").
+pub fn to_dataframes<'a, I>( msgs : I ) -> PolarsResult<HashMap<&'static str, DataFrame>>
+    where I : IntoIterator<Item = &'a BATSMessage> {
+    let mut collector = ArrowCollector::new();
+    collector.extend(msgs);
+    let mut frames = HashMap::new();
+    for (table, columns) in collector.take_columns() {
+        let columns = columns.into_iter().map(|(name, col)| to_series(&name, col)).collect();
+        frames.insert(table, DataFrame::new_infer_height(columns)?);
+    }
+    Ok(frames)
+}
+
+// a single message type's frame, empty when none of the messages are of that type.
+pub fn to_dataframe<'a, I>( msgs : I, table : &str ) -> PolarsResult<DataFrame>
+    where I : IntoIterator<Item = &'a BATSMessage> {
+    Ok(to_dataframes(msgs)?.remove(table).unwrap_or_else(DataFrame::empty))
+}
+
+// book levels as (price, volume) from the top down, one row per level with
+// side ("B"/"S"), level, price and volume columns.
+pub fn levels_to_dataframe( bids : &[(u64, u32)], asks : &[(u64, u32)] ) -> PolarsResult<DataFrame> {
+    let mut side = vec![];
+    let mut level = vec![];
+    let mut price = vec![];
+    let mut volume = vec![];
+    for &(s, levels) in [("B", bids), ("S", asks)].iter() {
+        for (i, &(p, v)) in levels.iter().enumerate() {
+            side.push(s);
+            level.push(i as u32);
+            price.push(p);
+            volume.push(v);
+        }
+    }
+    DataFrame::new_infer_height(vec![PlColumn::from(Series::new("side".into(), side)),
+                                     PlColumn::from(Series::new("level".into(), level)),
+                                     PlColumn::from(Series::new("price".into(), price)),
+                                     PlColumn::from(Series::new("volume".into(), volume))])
+}
@@ -0,0 +1,120 @@
+
+// Pluggable feed decoders. A FeedDecoder turns raw bytes (one packet/frame from the wire or
+// a capture) into MarketEvents, and the registry maps venue names to decoder constructors so
+// proprietary decoders can be plugged in from outside the crate. Decoders are stateful (eg.
+// the time last seen on a unit), hence the registry hands out new instances.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::result::Result;
+
+use nom;
+
+use binary::split_packet;
+use binary::TimestampComposer;
+use event::MarketEvent;
+use messages::BATSMsgFactory;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Incomplete,
+    Invalid(String)
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        match *self {
+            DecodeError::Incomplete     => write!(f, "incomplete input"),
+            DecodeError::Invalid(ref s) => write!(f, "invalid input: {}", s)
+        }
+    }
+}
+
+impl<'a> From<nom::Err<&'a [u8]>> for DecodeError {
+    fn from( e : nom::Err<&'a [u8]> ) -> DecodeError {
+        match e {
+            nom::Err::Incomplete(_) => DecodeError::Incomplete,
+            e                       => DecodeError::Invalid(format!("{:?}", e))
+        }
+    }
+}
+
+pub trait FeedDecoder {
+    fn name( &self ) -> &str;
+
+    // decodes one packet, appending its events to out. Messages that don't map to a
+    // MarketEvent are skipped.
+    fn decode( &mut self, data : &[u8], out : &mut Vec<MarketEvent> ) -> Result<(), DecodeError>;
+}
+
+pub type DecoderFactory = Box<dyn Fn() -> Box<dyn FeedDecoder + Send> + Send + Sync>;
+
+pub struct DecoderRegistry {
+    factories : HashMap<String, DecoderFactory>
+}
+
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        DecoderRegistry::new()
+    }
+}
+
+impl DecoderRegistry {
+    // registry with the decoders that ship with the crate.
+    pub fn new() -> DecoderRegistry {
+        let mut r = DecoderRegistry::empty();
+        r.register("bats", || Box::new(BatsDecoder::new()));
+        r
+    }
+
+    pub fn empty() -> DecoderRegistry {
+        DecoderRegistry{ factories : HashMap::new() }
+    }
+
+    // registering an existing name replaces its decoder.
+    pub fn register<F>( &mut self, name : &str, factory : F )
+        where F : Fn() -> Box<dyn FeedDecoder + Send> + Send + Sync + 'static {
+        self.factories.insert(String::from(name), Box::new(factory));
+    }
+
+    pub fn create( &self, name : &str ) -> Option<Box<dyn FeedDecoder + Send>> {
+        self.factories.get(name).map(|f| f())
+    }
+
+    pub fn names( &self ) -> Vec<&str> {
+        let mut names : Vec<&str> = self.factories.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names
+    }
+}
+
+// Binary PITCH, one sequenced unit packet per call. Use one decoder per unit.
+#[derive(Debug, Default)]
+pub struct BatsDecoder {
+    composer : TimestampComposer
+}
+
+impl BatsDecoder {
+    pub fn new() -> BatsDecoder {
+        BatsDecoder{ composer : TimestampComposer::new() }
+    }
+}
+
+impl FeedDecoder for BatsDecoder {
+    fn name( &self ) -> &str {
+        "bats"
+    }
+
+    fn decode( &mut self, data : &[u8], out : &mut Vec<MarketEvent> ) -> Result<(), DecodeError> {
+        let (_, msgs) = split_packet(data)?;
+        for m in msgs {
+            if let Some(msg) = BATSMsgFactory::try_parse_binary(m)? {
+                let timestamp = self.composer.compose(&msg);
+                if let Some(e) = MarketEvent::from_bats(&msg, timestamp) {
+                    out.push(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+
+// Parsers for the Cboe Europe flavour of PITCH. The European feed uses 8 char symbols
+// throughout, 10 digit share quantities and 19 digit prices with 7 implied decimal places.
+// Prices are brought back to the 4 implied decimal places used by the rest of the crate
+// so that messages map into the common BATSMessage types, with the exception of the
+// off-book Trade Report which has no US equivalent. A price with anything in its last three
+// decimals can't be, so its message doesn't parse rather than being booked at the wrong price.
+
+use nom;
+
+use std::str::FromStr;
+use std::result::Result;
+
+use decoder::DecodeError;
+use messages::from_base36;
+use messages::BATSMessage;
+use messages::AddOrderMsg;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+use messages::TradeMsg;
+
+macro_rules! create_europe_parse_impl {
+    ($objname : ident, $parse_func : ident) => (
+        pub fn $parse_func( msg : &str ) -> Result<$objname, nom::Err<&str>> {
+            europe_parsers::$parse_func(msg).map(|o| o.1)
+        }
+    )
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TradeReportMsg { // off-book trade reported to the exchange
+    pub timestamp : u32, 
+    pub msg_type  : char,
+    pub exec_id   : u64, 
+    pub shares    : u32, 
+    pub symbol    : String, 
+    pub price     : u64, 
+    pub currency  : String, 
+    pub flags     : char
+}
+
+fn from_europe_price( input : &str ) -> Result<u64, &'static str> {
+    let p = u64::from_str(input).map_err(|_| "not a price")?;
+    if p % 1000 != 0 {
+        return Err("price has more than 4 decimal places");
+    }
+    Ok(p / 1000)
+}
+
+create_europe_parse_impl!(AddOrderMsg, parse_add_order);
+create_europe_parse_impl!(OrderCancelMsg, parse_order_cancel);
+create_europe_parse_impl!(OrderExecutedMsg, parse_order_executed);
+create_europe_parse_impl!(TradeMsg, parse_trade);
+create_europe_parse_impl!(TradeReportMsg, parse_trade_report);
+
+pub struct EuropeMsgFactory {}
+
+impl EuropeMsgFactory {
+    // a line that isn't a message we decode comes back in the error.
+    pub fn parse( msg : &str ) -> Result<BATSMessage, DecodeError> {
+        EuropeMsgFactory::try_parse(msg).ok_or_else(|| DecodeError::Invalid(String::from(msg)))
+    }
+
+    // same as parse but gives None for anything that isn't a well formed message we decode.
+    pub fn try_parse( msg : &str ) -> Option<BATSMessage> {
+        let obj = match msg.get(8..9)? {
+            "A" => BATSMessage::AddOrderMsg( parse_add_order(msg).ok()? ), 
+            "X" => BATSMessage::OrderCancelMsg( parse_order_cancel(msg).ok()? ),
+            "E" => BATSMessage::OrderExecutedMsg( parse_order_executed(msg).ok()? ),
+            "P" => BATSMessage::TradeMsg( parse_trade(msg).ok()? ),
+            "T" => BATSMessage::TradeReportMsg( parse_trade_report(msg).ok()? ),
+            &_  => return None,
+        };
+        Some(obj)
+    }
+}
+
+mod europe_parsers {
+
+    use std::str::FromStr;
+
+    use messages::AddOrderMsg;
+    use messages::OrderCancelMsg;
+    use messages::OrderExecutedMsg;
+    use messages::TradeMsg;
+    use super::TradeReportMsg;
+    use super::from_base36;
+    use super::from_europe_price;
+
+    named!(pub parse_add_order<&str, AddOrderMsg>,  
+        do_parse!(
+            _1 : map_res!(take!(8),  FromStr::from_str) >>
+            _2 : char!('A')                             >>
+            _3 : map_res!(take!(12), from_base36)       >>
+            _4 : map_res!(take!(1),  FromStr::from_str) >>
+            _5 : map_res!(take!(10), FromStr::from_str) >>
+            _6 : map_res!(take!(8),  FromStr::from_str) >>
+            _7 : map_res!(take!(19), from_europe_price) >>
+            _8 : map_res!(take!(1),  FromStr::from_str) >>
+            (AddOrderMsg{ timestamp : _1, 
+                          msg_type  : _2, 
+                          order_id  : _3, 
+                          side      : _4,
+                          shares    : _5, 
+                          symbol    : _6, 
+                          price     : _7, 
+                          display   : _8,
+                          part_id   : String::from("")
+                        })  
+        )
+    );
+
+    named!(pub parse_order_cancel<&str, OrderCancelMsg>,  
+        do_parse!(
+            _1 : map_res!(take!(8),  FromStr::from_str) >>
+            _2 : char!('X')                             >>
+            _3 : map_res!(take!(12), from_base36)       >>
+            _4 : map_res!(take!(10), FromStr::from_str) >>
+            (OrderCancelMsg{ timestamp : _1, 
+                             msg_type  : _2, 
+                             order_id  : _3, 
+                             shares    : _4,
+                        })  
+        )
+    );
+
+    named!(pub parse_order_executed<&str, OrderExecutedMsg>,  
+        do_parse!(
+            _1 : map_res!(take!(8),  FromStr::from_str) >>
+            _2 : char!('E')                             >>
+            _3 : map_res!(take!(12), from_base36)       >>
+            _4 : map_res!(take!(10), FromStr::from_str) >>
+            _5 : map_res!(take!(12), from_base36)       >>
+            (OrderExecutedMsg{ timestamp : _1, 
+                               msg_type  : _2, 
+                               order_id  : _3, 
+                               shares    : _4,
+                               exec_id   : _5
+                        })  
+        )
+    );
+
+    named!(pub parse_trade<&str, TradeMsg>,  
+        do_parse!(
+            _1 : map_res!(take!(8),  FromStr::from_str) >>
+            _2 : char!('P')                             >>
+            _3 : map_res!(take!(12), from_base36)       >>  
+            _4 : alt!(char!('B') | char!('S') )         >>
+            _5 : map_res!(take!(10), FromStr::from_str) >>
+            _6 : map_res!(take!(8),  FromStr::from_str) >>
+            _7 : map_res!(take!(19), from_europe_price) >>
+            _8 : map_res!(take!(12), from_base36)       >>  
+            (TradeMsg{ timestamp : _1, 
+                       msg_type  : _2, 
+                       order_id  : _3, 
+                       side      : _4, 
+                       shares    : _5, 
+                       symbol    : _6, 
+                       price     : _7, 
+                       exec_id   : _8, 
+                        })  
+        )
+    );
+
+    named!(pub parse_trade_report<&str, TradeReportMsg>,  
+        do_parse!(
+            _1 : map_res!(take!(8),  FromStr::from_str) >>
+            _2 : char!('T')                             >>
+            _3 : map_res!(take!(12), from_base36)       >>  
+            _4 : map_res!(take!(10), FromStr::from_str) >>
+            _5 : map_res!(take!(8),  FromStr::from_str) >>
+            _6 : map_res!(take!(19), from_europe_price) >>
+            _7 : map_res!(take!(3),  FromStr::from_str) >>
+            _8 : map_res!(take!(1),  FromStr::from_str) >>
+            (TradeReportMsg{ timestamp : _1, 
+                             msg_type  : _2, 
+                             exec_id   : _3, 
+                             shares    : _4, 
+                             symbol    : _5, 
+                             price     : _6, 
+                             currency  : _7, 
+                             flags     : _8
+                        })  
+        )
+    );
+}
@@ -0,0 +1,327 @@
+
+// Protocol agnostic view of the feeds. Each wire message maps to at most one MarketEvent so
+// book construction and analytics can be written once against these instead of against the
+// BATS/ITCH structs. Timestamps are nanoseconds past midnight, prices 4 implied decimals,
+// sides 'B'/'S' and symbols have their padding trimmed.
+
+use std::io;
+use std::io::Read;
+use std::io::Write;
+
+use messages::BATSMessage;
+use itch::ItchMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradingState {
+    Halted,
+    Suspended,
+    QuoteOnly,
+    Trading,
+    Cleared,      // resting orders are gone, eg. a SymbolClear/UnitClear
+    EndOfSession,
+    Other(char)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketEvent {
+    OrderAdded    { timestamp : u64, order_id : u64, side : char, price : u64, shares : u32, symbol : String },
+    OrderReduced  { timestamp : u64, order_id : u64, shares : u32 },   // shares cancelled
+    OrderDeleted  { timestamp : u64, order_id : u64 },
+    OrderReplaced { timestamp : u64, order_id : u64, new_order_id : u64, price : u64, shares : u32 },
+    // order_id is the resting order that was hit, None for hidden/off-book trades. Executions
+    // against a resting order don't repeat its price or symbol, so those are optional too.
+    Trade         { timestamp : u64, order_id : Option<u64>, symbol : Option<String>, side : Option<char>,
+                    price : Option<u64>, shares : u32, remaining : Option<u32>, exec_id : u64 },
+    TradeBroken   { timestamp : u64, exec_id : u64 },
+    StatusChange  { timestamp : u64, symbol : Option<String>, state : TradingState }, // None is the whole unit
+    AuctionInfo   { timestamp : u64, symbol : String, auction_type : char, price : u64,
+                    buy_shares : u32, sell_shares : u32, executed_shares : u32, completed : bool }
+}
+
+fn trim( symbol : &str ) -> String {
+    String::from(symbol.trim_end())
+}
+
+// The fields of the binary form written by MarketEvent::write_to: integers little endian,
+// chars a byte, strings a length byte then the bytes, options a 0/1 byte then the value.
+fn put_str( buf : &mut Vec<u8>, s : &str ) {
+    let n = s.len().min(255);
+    buf.push(n as u8);
+    buf.extend_from_slice(&s.as_bytes()[..n]);
+}
+
+fn put_opt<T, F>( buf : &mut Vec<u8>, v : &Option<T>, put : F ) where F : FnOnce(&mut Vec<u8>, &T) {
+    match *v {
+        Some(ref v) => { buf.push(1); put(buf, v); },
+        None        => buf.push(0)
+    }
+}
+
+fn put_state( buf : &mut Vec<u8>, state : TradingState ) {
+    let (code, c) = match state {
+        TradingState::Halted       => (0, 0),
+        TradingState::Suspended    => (1, 0),
+        TradingState::QuoteOnly    => (2, 0),
+        TradingState::Trading      => (3, 0),
+        TradingState::Cleared      => (4, 0),
+        TradingState::EndOfSession => (5, 0),
+        TradingState::Other(c)     => (6, c as u8)
+    };
+    buf.push(code);
+    buf.push(c);
+}
+
+struct Fields<'a, R : Read + 'a> {
+    r : &'a mut R
+}
+
+impl<'a, R : Read> Fields<'a, R> {
+    fn bytes<const N : usize>( &mut self ) -> io::Result<[u8; N]> {
+        let mut b = [0u8; N];
+        self.r.read_exact(&mut b)?;
+        Ok(b)
+    }
+
+    fn u8( &mut self ) -> io::Result<u8> { Ok(self.bytes::<1>()?[0]) }
+    fn u32( &mut self ) -> io::Result<u32> { Ok(u32::from_le_bytes(self.bytes()?)) }
+    fn u64( &mut self ) -> io::Result<u64> { Ok(u64::from_le_bytes(self.bytes()?)) }
+    fn char( &mut self ) -> io::Result<char> { Ok(char::from(self.u8()?)) }
+    fn bool( &mut self ) -> io::Result<bool> { Ok(self.u8()? != 0) }
+
+    fn string( &mut self ) -> io::Result<String> {
+        let mut s = vec![0u8; self.u8()? as usize];
+        self.r.read_exact(&mut s)?;
+        String::from_utf8(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn opt<T, F>( &mut self, get : F ) -> io::Result<Option<T>> where F : FnOnce(&mut Self) -> io::Result<T> {
+        if self.bool()? { get(self).map(Some) } else { Ok(None) }
+    }
+
+    fn state( &mut self ) -> io::Result<TradingState> {
+        let (code, c) = (self.u8()?, self.char()?);
+        Ok(match code {
+            0 => TradingState::Halted,
+            1 => TradingState::Suspended,
+            2 => TradingState::QuoteOnly,
+            3 => TradingState::Trading,
+            4 => TradingState::Cleared,
+            5 => TradingState::EndOfSession,
+            _ => TradingState::Other(c)
+        })
+    }
+}
+
+impl MarketEvent {
+    pub fn timestamp( &self ) -> u64 {
+        match *self {
+            MarketEvent::OrderAdded{ timestamp, .. }    |
+            MarketEvent::OrderReduced{ timestamp, .. }  |
+            MarketEvent::OrderDeleted{ timestamp, .. }  |
+            MarketEvent::OrderReplaced{ timestamp, .. } |
+            MarketEvent::Trade{ timestamp, .. }         |
+            MarketEvent::TradeBroken{ timestamp, .. }   |
+            MarketEvent::StatusChange{ timestamp, .. }  |
+            MarketEvent::AuctionInfo{ timestamp, .. }   => timestamp
+        }
+    }
+
+    // binary messages only carry an offset into the current second, so the caller passes the
+    // full time in, eg. from TimestampComposer::compose.
+    pub fn from_bats( msg : &BATSMessage, timestamp : u64 ) -> Option<MarketEvent> {
+        match *msg {
+            BATSMessage::AddOrderMsg(ref m) =>
+                Some(MarketEvent::OrderAdded{ timestamp, order_id : m.order_id, side : m.side,
+                                              price : m.price, shares : m.shares, symbol : trim(&m.symbol) }),
+            BATSMessage::BinaryAddOrderMsg(ref m) =>
+                Some(MarketEvent::OrderAdded{ timestamp, order_id : m.order_id, side : m.side,
+                                              price : m.price, shares : m.shares, symbol : trim(&m.symbol) }),
+            BATSMessage::OrderCancelMsg(ref m) =>
+                Some(MarketEvent::OrderReduced{ timestamp, order_id : m.order_id, shares : m.shares }),
+            BATSMessage::DeleteOrderMsg(ref m) =>
+                Some(MarketEvent::OrderDeleted{ timestamp, order_id : m.order_id }),
+            // the order keeps its id
+            BATSMessage::ModifyOrderMsg(ref m) =>
+                Some(MarketEvent::OrderReplaced{ timestamp, order_id : m.order_id, new_order_id : m.order_id,
+                                                 price : m.price, shares : m.shares }),
+            BATSMessage::ReduceSizeMsg(ref m) =>
+                Some(MarketEvent::OrderReduced{ timestamp, order_id : m.order_id, shares : m.shares }),
+            BATSMessage::OrderExecutedMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : Some(m.order_id), symbol : None, side : None,
+                                         price : None, shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : Some(m.order_id), symbol : None, side : None,
+                                         price : None, shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : Some(m.order_id), symbol : None, side : None,
+                                         price : Some(m.price), shares : m.shares,
+                                         remaining : Some(m.remaining_shares), exec_id : m.exec_id }),
+            BATSMessage::TradeMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : None, symbol : Some(trim(&m.symbol)), side : Some(m.side),
+                                         price : Some(m.price), shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            BATSMessage::BinaryTradeMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : None, symbol : Some(trim(&m.symbol)), side : Some(m.side),
+                                         price : Some(m.price), shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            BATSMessage::TradeExpandedMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : None, symbol : Some(trim(&m.symbol)), side : Some(m.side),
+                                         price : Some(m.price), shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            #[cfg(feature = "europe")]
+            BATSMessage::TradeReportMsg(ref m) =>
+                Some(MarketEvent::Trade{ timestamp, order_id : None, symbol : Some(trim(&m.symbol)), side : None,
+                                         price : Some(m.price), shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            BATSMessage::TradeBreakMsg(ref m) =>
+                Some(MarketEvent::TradeBroken{ timestamp, exec_id : m.exec_id }),
+            BATSMessage::TradingStatusMsg(ref m) => {
+                let state = match m.halt_status {
+                    'H' => TradingState::Halted,
+                    'S' => TradingState::Suspended,
+                    'Q' => TradingState::QuoteOnly,
+                    'T' => TradingState::Trading,
+                    c   => TradingState::Other(c)
+                };
+                Some(MarketEvent::StatusChange{ timestamp, symbol : Some(trim(&m.symbol)), state })
+            },
+            BATSMessage::SymbolClearMsg(ref m) =>
+                Some(MarketEvent::StatusChange{ timestamp, symbol : Some(trim(&m.symbol)), state : TradingState::Cleared }),
+            BATSMessage::UnitClearMsg(_) =>
+                Some(MarketEvent::StatusChange{ timestamp, symbol : None, state : TradingState::Cleared }),
+            BATSMessage::EndOfSessionMsg(_) =>
+                Some(MarketEvent::StatusChange{ timestamp, symbol : None, state : TradingState::EndOfSession }),
+            BATSMessage::AuctionUpdateMsg(ref m) =>
+                Some(MarketEvent::AuctionInfo{ timestamp, symbol : trim(&m.symbol), auction_type : m.auction_type,
+                                               price : m.indicative_price, buy_shares : m.buyshares,
+                                               sell_shares : m.sellshares, executed_shares : 0, completed : false }),
+            BATSMessage::AuctionSummaryMsg(ref m) =>
+                Some(MarketEvent::AuctionInfo{ timestamp, symbol : trim(&m.symbol), auction_type : m.auction_type,
+                                               price : m.price, buy_shares : 0, sell_shares : 0,
+                                               executed_shares : m.shares, completed : true }),
+            _ => None
+        }
+    }
+
+    // a compact binary form, a type byte then the fields, that read_from reads back.
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(48);
+        match *self {
+            MarketEvent::OrderAdded{ timestamp, order_id, side, price, shares, ref symbol } => {
+                buf.push(0);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                buf.extend_from_slice(&order_id.to_le_bytes());
+                buf.push(side as u8);
+                buf.extend_from_slice(&price.to_le_bytes());
+                buf.extend_from_slice(&shares.to_le_bytes());
+                put_str(&mut buf, symbol);
+            },
+            MarketEvent::OrderReduced{ timestamp, order_id, shares } => {
+                buf.push(1);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                buf.extend_from_slice(&order_id.to_le_bytes());
+                buf.extend_from_slice(&shares.to_le_bytes());
+            },
+            MarketEvent::OrderDeleted{ timestamp, order_id } => {
+                buf.push(2);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                buf.extend_from_slice(&order_id.to_le_bytes());
+            },
+            MarketEvent::OrderReplaced{ timestamp, order_id, new_order_id, price, shares } => {
+                buf.push(3);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                buf.extend_from_slice(&order_id.to_le_bytes());
+                buf.extend_from_slice(&new_order_id.to_le_bytes());
+                buf.extend_from_slice(&price.to_le_bytes());
+                buf.extend_from_slice(&shares.to_le_bytes());
+            },
+            MarketEvent::Trade{ timestamp, order_id, ref symbol, side, price, shares, remaining, exec_id } => {
+                buf.push(4);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                put_opt(&mut buf, &order_id, |b, v| b.extend_from_slice(&v.to_le_bytes()));
+                put_opt(&mut buf, symbol, |b, v| put_str(b, v));
+                put_opt(&mut buf, &side, |b, v| b.push(*v as u8));
+                put_opt(&mut buf, &price, |b, v| b.extend_from_slice(&v.to_le_bytes()));
+                buf.extend_from_slice(&shares.to_le_bytes());
+                put_opt(&mut buf, &remaining, |b, v| b.extend_from_slice(&v.to_le_bytes()));
+                buf.extend_from_slice(&exec_id.to_le_bytes());
+            },
+            MarketEvent::TradeBroken{ timestamp, exec_id } => {
+                buf.push(5);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                buf.extend_from_slice(&exec_id.to_le_bytes());
+            },
+            MarketEvent::StatusChange{ timestamp, ref symbol, state } => {
+                buf.push(6);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                put_opt(&mut buf, symbol, |b, v| put_str(b, v));
+                put_state(&mut buf, state);
+            },
+            MarketEvent::AuctionInfo{ timestamp, ref symbol, auction_type, price, buy_shares, sell_shares, executed_shares, completed } => {
+                buf.push(7);
+                buf.extend_from_slice(&timestamp.to_le_bytes());
+                put_str(&mut buf, symbol);
+                buf.push(auction_type as u8);
+                buf.extend_from_slice(&price.to_le_bytes());
+                buf.extend_from_slice(&buy_shares.to_le_bytes());
+                buf.extend_from_slice(&sell_shares.to_le_bytes());
+                buf.extend_from_slice(&executed_shares.to_le_bytes());
+                buf.push(completed as u8);
+            }
+        }
+        buf
+    }
+
+    pub fn write_to<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+        w.write_all(&self.to_bytes())
+    }
+
+    // the next event written by write_to, None at the end of the input.
+    pub fn read_from<R : Read>( r : &mut R ) -> io::Result<Option<MarketEvent>> {
+        let mut kind = [0u8; 1];
+        if r.read(&mut kind)? == 0 {
+            return Ok(None);
+        }
+        let mut f = Fields{ r };
+        let event = match kind[0] {
+            0 => MarketEvent::OrderAdded{ timestamp : f.u64()?, order_id : f.u64()?, side : f.char()?, price : f.u64()?,
+                                          shares : f.u32()?, symbol : f.string()? },
+            1 => MarketEvent::OrderReduced{ timestamp : f.u64()?, order_id : f.u64()?, shares : f.u32()? },
+            2 => MarketEvent::OrderDeleted{ timestamp : f.u64()?, order_id : f.u64()? },
+            3 => MarketEvent::OrderReplaced{ timestamp : f.u64()?, order_id : f.u64()?, new_order_id : f.u64()?,
+                                             price : f.u64()?, shares : f.u32()? },
+            4 => MarketEvent::Trade{ timestamp : f.u64()?, order_id : f.opt(|f| f.u64())?, symbol : f.opt(|f| f.string())?,
+                                     side : f.opt(|f| f.char())?, price : f.opt(|f| f.u64())?, shares : f.u32()?,
+                                     remaining : f.opt(|f| f.u32())?, exec_id : f.u64()? },
+            5 => MarketEvent::TradeBroken{ timestamp : f.u64()?, exec_id : f.u64()? },
+            6 => MarketEvent::StatusChange{ timestamp : f.u64()?, symbol : f.opt(|f| f.string())?, state : f.state()? },
+            7 => MarketEvent::AuctionInfo{ timestamp : f.u64()?, symbol : f.string()?, auction_type : f.char()?, price : f.u64()?,
+                                           buy_shares : f.u32()?, sell_shares : f.u32()?, executed_shares : f.u32()?,
+                                           completed : f.bool()? },
+            k => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown event type {}", k)))
+        };
+        Ok(Some(event))
+    }
+
+    pub fn from_itch( msg : &ItchMessage ) -> Option<MarketEvent> {
+        match *msg {
+            ItchMessage::AddOrder(ref m) =>
+                Some(MarketEvent::OrderAdded{ timestamp : m.timestamp, order_id : m.order_id, side : m.side,
+                                              price : m.price, shares : m.shares, symbol : trim(&m.symbol) }),
+            ItchMessage::OrderCancel(ref m) =>
+                Some(MarketEvent::OrderReduced{ timestamp : m.timestamp, order_id : m.order_id, shares : m.shares }),
+            ItchMessage::OrderDelete(ref m) =>
+                Some(MarketEvent::OrderDeleted{ timestamp : m.timestamp, order_id : m.order_id }),
+            ItchMessage::OrderReplace(ref m) =>
+                Some(MarketEvent::OrderReplaced{ timestamp : m.timestamp, order_id : m.original_order_id,
+                                                 new_order_id : m.order_id, price : m.price, shares : m.shares }),
+            ItchMessage::OrderExecuted(ref m) =>
+                Some(MarketEvent::Trade{ timestamp : m.timestamp, order_id : Some(m.order_id), symbol : None, side : None,
+                                         price : None, shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            ItchMessage::OrderExecutedWithPrice(ref m) =>
+                Some(MarketEvent::Trade{ timestamp : m.timestamp, order_id : Some(m.order_id), symbol : None, side : None,
+                                         price : Some(m.price), shares : m.shares, remaining : None, exec_id : m.exec_id }),
+            ItchMessage::Trade(ref m) =>
+                Some(MarketEvent::Trade{ timestamp : m.timestamp, order_id : None, symbol : Some(trim(&m.symbol)),
+                                         side : Some(m.side), price : Some(m.price), shares : m.shares,
+                                         remaining : None, exec_id : m.exec_id }),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,340 @@
+
+// FlatBuffers form of MarketEvent and of book level deltas, matching fbs/orderbook.fbs. The
+// tables are written with the runtime's builder directly (no flatc step) and EventView /
+// BookDeltaView read fields in place, same as generated code would. Chars go out as their
+// ASCII byte.
+
+use flatbuffers::FlatBufferBuilder;
+use flatbuffers::Follow;
+use flatbuffers::ForwardsUOffset;
+use flatbuffers::InvalidFlatbuffer;
+use flatbuffers::Table;
+use flatbuffers::Verifiable;
+use flatbuffers::Verifier;
+use flatbuffers::VOffsetT;
+
+use event::MarketEvent;
+use event::TradingState;
+use messages::BATSMessage;
+
+pub const ORDER_ADDED    : u8 = 0;
+pub const ORDER_REDUCED  : u8 = 1;
+pub const ORDER_DELETED  : u8 = 2;
+pub const ORDER_REPLACED : u8 = 3;
+pub const TRADE          : u8 = 4;
+pub const TRADE_BROKEN   : u8 = 5;
+pub const STATUS_CHANGE  : u8 = 6;
+pub const AUCTION_INFO   : u8 = 7;
+
+// vtable offsets, 4 + 2 * the field's index in the schema.
+const EV_KIND            : VOffsetT = 4;
+const EV_TIMESTAMP       : VOffsetT = 6;
+const EV_ORDER_ID        : VOffsetT = 8;
+const EV_NEW_ORDER_ID    : VOffsetT = 10;
+const EV_EXEC_ID         : VOffsetT = 12;
+const EV_SIDE            : VOffsetT = 14;
+const EV_PRICE           : VOffsetT = 16;
+const EV_SHARES          : VOffsetT = 18;
+const EV_REMAINING       : VOffsetT = 20;
+const EV_SYMBOL          : VOffsetT = 22;
+const EV_STATE           : VOffsetT = 24;
+const EV_OTHER_STATE     : VOffsetT = 26;
+const EV_AUCTION_TYPE    : VOffsetT = 28;
+const EV_BUY_SHARES      : VOffsetT = 30;
+const EV_SELL_SHARES     : VOffsetT = 32;
+const EV_EXECUTED_SHARES : VOffsetT = 34;
+const EV_COMPLETED       : VOffsetT = 36;
+
+const BD_TIMESTAMP : VOffsetT = 4;
+const BD_SYMBOL    : VOffsetT = 6;
+const BD_SIDE      : VOffsetT = 8;
+const BD_PRICE     : VOffsetT = 10;
+const BD_VOLUME    : VOffsetT = 12;
+
+fn state_code( state : TradingState ) -> (u8, u8) {
+    match state {
+        TradingState::Other(c)     => (0, c as u8),
+        TradingState::Halted       => (1, 0),
+        TradingState::Suspended    => (2, 0),
+        TradingState::QuoteOnly    => (3, 0),
+        TradingState::Trading      => (4, 0),
+        TradingState::Cleared      => (5, 0),
+        TradingState::EndOfSession => (6, 0)
+    }
+}
+
+fn state_from_code( code : u8, other : u8 ) -> TradingState {
+    match code {
+        1 => TradingState::Halted,
+        2 => TradingState::Suspended,
+        3 => TradingState::QuoteOnly,
+        4 => TradingState::Trading,
+        5 => TradingState::Cleared,
+        6 => TradingState::EndOfSession,
+        _ => TradingState::Other(other as char)
+    }
+}
+
+#[derive(Default)]
+struct EventFields<'a> {
+    kind            : u8,
+    timestamp       : u64,
+    order_id        : Option<u64>,
+    new_order_id    : u64,
+    exec_id         : u64,
+    side            : Option<u8>,
+    price           : Option<u64>,
+    shares          : u32,
+    remaining       : Option<u32>,
+    symbol          : Option<&'a str>,
+    state           : u8,
+    other_state     : u8,
+    auction_type    : u8,
+    buy_shares      : u32,
+    sell_shares     : u32,
+    executed_shares : u32,
+    completed       : bool
+}
+
+impl<'a> EventFields<'a> {
+    fn from_event( e : &'a MarketEvent ) -> EventFields<'a> {
+        match *e {
+            MarketEvent::OrderAdded{ timestamp, order_id, side, price, shares, ref symbol } =>
+                EventFields{ kind : ORDER_ADDED, timestamp, order_id : Some(order_id), side : Some(side as u8),
+                             price : Some(price), shares, symbol : Some(symbol), ..Default::default() },
+            MarketEvent::OrderReduced{ timestamp, order_id, shares } =>
+                EventFields{ kind : ORDER_REDUCED, timestamp, order_id : Some(order_id), shares, ..Default::default() },
+            MarketEvent::OrderDeleted{ timestamp, order_id } =>
+                EventFields{ kind : ORDER_DELETED, timestamp, order_id : Some(order_id), ..Default::default() },
+            MarketEvent::OrderReplaced{ timestamp, order_id, new_order_id, price, shares } =>
+                EventFields{ kind : ORDER_REPLACED, timestamp, order_id : Some(order_id), new_order_id,
+                             price : Some(price), shares, ..Default::default() },
+            MarketEvent::Trade{ timestamp, order_id, ref symbol, side, price, shares, remaining, exec_id } =>
+                EventFields{ kind : TRADE, timestamp, order_id, exec_id, side : side.map(|c| c as u8), price, shares,
+                             remaining, symbol : symbol.as_ref().map(|s| s.as_str()), ..Default::default() },
+            MarketEvent::TradeBroken{ timestamp, exec_id } =>
+                EventFields{ kind : TRADE_BROKEN, timestamp, exec_id, ..Default::default() },
+            MarketEvent::StatusChange{ timestamp, ref symbol, state } => {
+                let (state, other_state) = state_code(state);
+                EventFields{ kind : STATUS_CHANGE, timestamp, symbol : symbol.as_ref().map(|s| s.as_str()), state,
+                             other_state, ..Default::default() }
+            },
+            MarketEvent::AuctionInfo{ timestamp, ref symbol, auction_type, price, buy_shares, sell_shares,
+                                      executed_shares, completed } =>
+                EventFields{ kind : AUCTION_INFO, timestamp, symbol : Some(symbol), auction_type : auction_type as u8,
+                             price : Some(price), buy_shares, sell_shares, executed_shares, completed,
+                             ..Default::default() }
+        }
+    }
+}
+
+// Reuses one builder for every buffer, each call's output is only valid until the next one.
+pub struct FbsEncoder {
+    builder : FlatBufferBuilder<'static>
+}
+
+impl Default for FbsEncoder {
+    fn default() -> FbsEncoder {
+        FbsEncoder::new()
+    }
+}
+
+impl FbsEncoder {
+    pub fn new() -> FbsEncoder {
+        FbsEncoder{ builder : FlatBufferBuilder::with_capacity(256) }
+    }
+
+    pub fn event( &mut self, e : &MarketEvent ) -> &[u8] {
+        let f = EventFields::from_event(e);
+        let b = &mut self.builder;
+        b.reset();
+        let symbol = f.symbol.map(|s| b.create_string(s));
+        let t = b.start_table();
+        b.push_slot::<u64>(EV_TIMESTAMP, f.timestamp, 0);
+        if let Some(v) = f.order_id  { b.push_slot_always::<u64>(EV_ORDER_ID, v); }
+        b.push_slot::<u64>(EV_NEW_ORDER_ID, f.new_order_id, 0);
+        b.push_slot::<u64>(EV_EXEC_ID, f.exec_id, 0);
+        if let Some(v) = f.price     { b.push_slot_always::<u64>(EV_PRICE, v); }
+        if let Some(s) = symbol      { b.push_slot_always(EV_SYMBOL, s); }
+        b.push_slot::<u32>(EV_SHARES, f.shares, 0);
+        if let Some(v) = f.remaining { b.push_slot_always::<u32>(EV_REMAINING, v); }
+        b.push_slot::<u32>(EV_BUY_SHARES, f.buy_shares, 0);
+        b.push_slot::<u32>(EV_SELL_SHARES, f.sell_shares, 0);
+        b.push_slot::<u32>(EV_EXECUTED_SHARES, f.executed_shares, 0);
+        b.push_slot::<u8>(EV_KIND, f.kind, 0);
+        if let Some(v) = f.side      { b.push_slot_always::<u8>(EV_SIDE, v); }
+        b.push_slot::<u8>(EV_STATE, f.state, 0);
+        b.push_slot::<u8>(EV_OTHER_STATE, f.other_state, 0);
+        b.push_slot::<u8>(EV_AUCTION_TYPE, f.auction_type, 0);
+        b.push_slot::<bool>(EV_COMPLETED, f.completed, false);
+        let root = b.end_table(t);
+        b.finish(root, None);
+        b.finished_data()
+    }
+
+    // None for messages that don't map to an event, see MarketEvent::from_bats.
+    pub fn message( &mut self, msg : &BATSMessage, timestamp : u64 ) -> Option<&[u8]> {
+        MarketEvent::from_bats(msg, timestamp).map(move |e| self.event(&e))
+    }
+
+    pub fn book_delta( &mut self, timestamp : u64, symbol : &str, side : char, price : u64, volume : u32 ) -> &[u8] {
+        let b = &mut self.builder;
+        b.reset();
+        let symbol = b.create_string(symbol);
+        let t = b.start_table();
+        b.push_slot::<u64>(BD_TIMESTAMP, timestamp, 0);
+        b.push_slot::<u64>(BD_PRICE, price, 0);
+        b.push_slot_always(BD_SYMBOL, symbol);
+        b.push_slot::<u32>(BD_VOLUME, volume, 0);
+        b.push_slot::<u8>(BD_SIDE, side as u8, 0);
+        let root = b.end_table(t);
+        b.finish(root, None);
+        b.finished_data()
+    }
+}
+
+// accessors reading a field straight out of the table (the buffer was verified up front),
+// missing fields read as their schema default.
+macro_rules! create_fbs_accessors {
+    ($objname : ident, $($field : ident : $t : ty = $slot : expr),+) => (
+        impl<'a> $objname<'a> {
+            $(pub fn $field( &self ) -> $t {
+                unsafe { self.tab.get::<$t>($slot, None).unwrap_or_default() }
+            })+
+        }
+    )
+}
+
+macro_rules! create_fbs_optional_accessors {
+    ($objname : ident, $($field : ident : $t : ty = $slot : expr),+) => (
+        impl<'a> $objname<'a> {
+            $(pub fn $field( &self ) -> Option<$t> {
+                unsafe { self.tab.get::<$t>($slot, None) }
+            })+
+        }
+    )
+}
+
+macro_rules! create_fbs_table_impl {
+    ($objname : ident) => (
+        impl<'a> Follow<'a> for $objname<'a> {
+            type Inner = $objname<'a>;
+
+            unsafe fn follow( buf : &'a [u8], loc : usize ) -> $objname<'a> {
+                $objname{ tab : Table::new(buf, loc) }
+            }
+        }
+    )
+}
+
+#[derive(Clone, Copy)]
+pub struct EventView<'a> {
+    tab : Table<'a>
+}
+
+create_fbs_table_impl!(EventView);
+create_fbs_accessors!(EventView, kind : u8 = EV_KIND, timestamp : u64 = EV_TIMESTAMP,
+                      new_order_id : u64 = EV_NEW_ORDER_ID, exec_id : u64 = EV_EXEC_ID,
+                      shares : u32 = EV_SHARES, state : u8 = EV_STATE,
+                      other_state : u8 = EV_OTHER_STATE, auction_type : u8 = EV_AUCTION_TYPE,
+                      buy_shares : u32 = EV_BUY_SHARES, sell_shares : u32 = EV_SELL_SHARES,
+                      executed_shares : u32 = EV_EXECUTED_SHARES, completed : bool = EV_COMPLETED);
+create_fbs_optional_accessors!(EventView, order_id : u64 = EV_ORDER_ID, side : u8 = EV_SIDE, price : u64 = EV_PRICE,
+                               remaining : u32 = EV_REMAINING);
+
+impl Verifiable for EventView<'_> {
+    fn run_verifier( v : &mut Verifier, pos : usize ) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+         .visit_field::<u8>("kind", EV_KIND, false)?
+         .visit_field::<u64>("timestamp", EV_TIMESTAMP, false)?
+         .visit_field::<u64>("order_id", EV_ORDER_ID, false)?
+         .visit_field::<u64>("new_order_id", EV_NEW_ORDER_ID, false)?
+         .visit_field::<u64>("exec_id", EV_EXEC_ID, false)?
+         .visit_field::<u8>("side", EV_SIDE, false)?
+         .visit_field::<u64>("price", EV_PRICE, false)?
+         .visit_field::<u32>("shares", EV_SHARES, false)?
+         .visit_field::<u32>("remaining", EV_REMAINING, false)?
+         .visit_field::<ForwardsUOffset<&str>>("symbol", EV_SYMBOL, false)?
+         .visit_field::<u8>("state", EV_STATE, false)?
+         .visit_field::<u8>("other_state", EV_OTHER_STATE, false)?
+         .visit_field::<u8>("auction_type", EV_AUCTION_TYPE, false)?
+         .visit_field::<u32>("buy_shares", EV_BUY_SHARES, false)?
+         .visit_field::<u32>("sell_shares", EV_SELL_SHARES, false)?
+         .visit_field::<u32>("executed_shares", EV_EXECUTED_SHARES, false)?
+         .visit_field::<bool>("completed", EV_COMPLETED, false)?
+         .finish();
+        Ok(())
+    }
+}
+
+impl<'a> EventView<'a> {
+    pub fn symbol( &self ) -> Option<&'a str> {
+        unsafe { self.tab.get::<ForwardsUOffset<&str>>(EV_SYMBOL, None) }
+    }
+
+    // back to a MarketEvent, None for an unknown kind.
+    pub fn to_event( &self ) -> Option<MarketEvent> {
+        let timestamp = self.timestamp();
+        let order_id = self.order_id().unwrap_or(0);
+        let price = self.price().unwrap_or(0);
+        let side = self.side().map(char::from);
+        let symbol = self.symbol().map(String::from);
+        let e = match self.kind() {
+            ORDER_ADDED    => MarketEvent::OrderAdded{ timestamp, order_id, side : side.unwrap_or(' '), price,
+                                                       shares : self.shares(), symbol : symbol.unwrap_or_default() },
+            ORDER_REDUCED  => MarketEvent::OrderReduced{ timestamp, order_id, shares : self.shares() },
+            ORDER_DELETED  => MarketEvent::OrderDeleted{ timestamp, order_id },
+            ORDER_REPLACED => MarketEvent::OrderReplaced{ timestamp, order_id, new_order_id : self.new_order_id(), price,
+                                                          shares : self.shares() },
+            TRADE          => MarketEvent::Trade{ timestamp, order_id : self.order_id(), symbol, side, price : self.price(),
+                                                  shares : self.shares(), remaining : self.remaining(),
+                                                  exec_id : self.exec_id() },
+            TRADE_BROKEN   => MarketEvent::TradeBroken{ timestamp, exec_id : self.exec_id() },
+            STATUS_CHANGE  => MarketEvent::StatusChange{ timestamp, symbol,
+                                                         state : state_from_code(self.state(), self.other_state()) },
+            AUCTION_INFO   => MarketEvent::AuctionInfo{ timestamp, symbol : symbol.unwrap_or_default(),
+                                                        auction_type : char::from(self.auction_type()), price,
+                                                        buy_shares : self.buy_shares(), sell_shares : self.sell_shares(),
+                                                        executed_shares : self.executed_shares(),
+                                                        completed : self.completed() },
+            _ => return None
+        };
+        Some(e)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BookDeltaView<'a> {
+    tab : Table<'a>
+}
+
+create_fbs_table_impl!(BookDeltaView);
+create_fbs_accessors!(BookDeltaView, timestamp : u64 = BD_TIMESTAMP, side : u8 = BD_SIDE,
+                      price : u64 = BD_PRICE, volume : u32 = BD_VOLUME);
+
+impl Verifiable for BookDeltaView<'_> {
+    fn run_verifier( v : &mut Verifier, pos : usize ) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+         .visit_field::<u64>("timestamp", BD_TIMESTAMP, false)?
+         .visit_field::<ForwardsUOffset<&str>>("symbol", BD_SYMBOL, false)?
+         .visit_field::<u8>("side", BD_SIDE, false)?
+         .visit_field::<u64>("price", BD_PRICE, false)?
+         .visit_field::<u32>("volume", BD_VOLUME, false)?
+         .finish();
+        Ok(())
+    }
+}
+
+impl<'a> BookDeltaView<'a> {
+    pub fn symbol( &self ) -> &'a str {
+        unsafe { self.tab.get::<ForwardsUOffset<&str>>(BD_SYMBOL, None).unwrap_or("") }
+    }
+}
+
+pub fn read_event( buf : &[u8] ) -> Result<EventView<'_>, InvalidFlatbuffer> {
+    ::flatbuffers::root::<EventView>(buf)
+}
+
+pub fn read_book_delta( buf : &[u8] ) -> Result<BookDeltaView<'_>, InvalidFlatbuffer> {
+    ::flatbuffers::root::<BookDeltaView>(buf)
+}
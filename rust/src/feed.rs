@@ -0,0 +1,114 @@
+
+use nom;
+
+use std::io;
+use std::io::BufRead;
+use std::result::Result;
+use std::str;
+
+use binary::split_packet;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+use multicast::decode_messages;
+
+#[derive(Debug)]
+pub enum FeedEvent {
+    Message(BATSMessage), 
+    SessionComplete // the day's data is done, consumers can finalize stats/snapshots
+}
+
+fn is_end_of_session( msg : &BATSMessage ) -> bool {
+    matches!(*msg, BATSMessage::EndOfSessionMsg(_))
+}
+
+// Reads a PITCH text file/stream with one message per line. The session is complete
+// on an EndOfSessionMsg or, since text captures normally don't carry one, at the end
+// of the input. SessionComplete is only ever emitted once. Lines that aren't a message we
+// decode, UTF-8 or not, are skipped and counted. A read error ends the messages without a
+// SessionComplete, the error's kept for error().
+pub struct PitchReader<R : BufRead> {
+    reader           : R, 
+    line             : Vec<u8>,
+    pending_complete : bool, 
+    session_complete : bool,
+    skipped          : u64,
+    error            : Option<io::Error>
+}
+
+impl<R : BufRead> PitchReader<R> {
+    pub fn new( reader : R ) -> PitchReader<R> {
+        PitchReader{ reader, line : Vec::new(), pending_complete : false, session_complete : false, skipped : 0, error : None }
+    }
+
+    // lines passed over so far as unknown or malformed.
+    pub fn skipped( &self ) -> u64 {
+        self.skipped
+    }
+
+    // what stopped the reading short, if anything did.
+    pub fn error( &self ) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<R : BufRead> Iterator for PitchReader<R> {
+    type Item = FeedEvent;
+
+    fn next(&mut self) -> Option<FeedEvent> {
+        if self.session_complete {
+            return None;
+        }
+        if self.pending_complete {
+            self.session_complete = true;
+            return Some(FeedEvent::SessionComplete);
+        }
+        loop {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => {
+                    self.session_complete = true;
+                    return Some(FeedEvent::SessionComplete);
+                },
+                Ok(_) => {
+                    let line = match str::from_utf8(&self.line) {
+                        Ok(line) => { let line = line.strip_suffix('\n').unwrap_or(line); line.strip_suffix('\r').unwrap_or(line) },
+                        Err(_)   => { self.skipped += 1; continue; }
+                    };
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let msg = match BATSMsgFactory::try_parse(line) {
+                        Some(msg) => msg,
+                        None      => { self.skipped += 1; continue; }
+                    };
+                    self.pending_complete = is_end_of_session(&msg);
+                    return Some(FeedEvent::Message(msg));
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.session_complete = true;
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+// Decodes all messages in a binary sequenced unit packet, appending a SessionComplete
+// after an EndOfSessionMsg. Messages of types we don't decode, or that don't parse, are left
+// out; only a packet that doesn't split is an error.
+pub fn decode_packet( packet : &[u8] ) -> Result<Vec<FeedEvent>, nom::Err<&[u8]>> {
+    let (_, msgs) = split_packet(packet)?;
+    let mut decoded = Vec::with_capacity(msgs.len());
+    decode_messages(msgs.into_iter(), None, &mut decoded);
+    let mut events = Vec::with_capacity(decoded.len());
+    for msg in decoded {
+        let complete = is_end_of_session(&msg);
+        events.push(FeedEvent::Message(msg));
+        if complete {
+            events.push(FeedEvent::SessionComplete);
+        }
+    }
+    Ok(events)
+}
@@ -0,0 +1,376 @@
+
+// C API for feed handlers that can't link Rust directly, see include/rust_orderbook.h (made
+// with the cbindgen command in cbindgen.toml, rerun it after changing anything here).
+// Messages come out as ObEvent, a flat version of MarketEvent, and books are opaque handles
+// from ob_book_new() that are fed those events.
+//
+// Pointers must be valid for the call (out params writable), books are not thread safe and
+// must be freed with ob_book_free(). Nothing here panics across the boundary: bad input is a
+// negative return.
+
+#![allow(clippy::missing_safety_doc)] // the pointer rules above and in the header
+
+use std::convert::TryFrom;
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::slice;
+use std::str;
+
+use binary::TimestampComposer;
+use book::OrderBook;
+use book::Side;
+use event::MarketEvent;
+use event::TradingState;
+use messages::BATSMsgFactory;
+
+// ObEvent.kind, the same values as the flatbuffers schema.
+pub const OB_ORDER_ADDED    : u8 = 0;
+pub const OB_ORDER_REDUCED  : u8 = 1;
+pub const OB_ORDER_DELETED  : u8 = 2;
+pub const OB_ORDER_REPLACED : u8 = 3;
+pub const OB_TRADE          : u8 = 4;
+pub const OB_TRADE_BROKEN   : u8 = 5;
+pub const OB_STATUS_CHANGE  : u8 = 6;
+pub const OB_AUCTION_INFO   : u8 = 7;
+
+// ObEvent.flags, for the MarketEvent fields that are optional.
+pub const OB_HAS_ORDER_ID  : u8 = 1;
+pub const OB_HAS_PRICE     : u8 = 2;
+pub const OB_HAS_REMAINING : u8 = 4;
+pub const OB_COMPLETED     : u8 = 8;
+
+// ObEvent.state for status changes, otherwise the exchange's own code.
+pub const OB_STATE_HALTED         : c_char = b'H' as c_char;
+pub const OB_STATE_SUSPENDED      : c_char = b'S' as c_char;
+pub const OB_STATE_QUOTE_ONLY     : c_char = b'Q' as c_char;
+pub const OB_STATE_TRADING        : c_char = b'T' as c_char;
+pub const OB_STATE_CLEARED        : c_char = b'C' as c_char;
+pub const OB_STATE_END_OF_SESSION : c_char = b'E' as c_char;
+
+pub const OB_SYMBOL_LEN : usize = 16;
+
+// ob_book_levels side.
+pub const OB_BID : c_int = 0;
+pub const OB_ASK : c_int = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ObEvent {
+    pub kind         : u8,
+    pub flags        : u8,
+    pub side         : c_char,   // 'B'/'S', 0 if not known
+    pub state        : c_char,
+    pub auction_type : c_char,
+    pub timestamp    : u64,      // ns past midnight
+    pub order_id     : u64,
+    pub new_order_id : u64,      // replaces
+    pub exec_id      : u64,
+    pub price        : u64,      // 4 implied decimals
+    pub shares       : u32,      // executed shares for auctions
+    pub remaining    : u32,
+    pub buy_shares   : u32,
+    pub sell_shares  : u32,
+    pub symbol       : [c_char; OB_SYMBOL_LEN]  // NUL terminated, empty for the whole unit
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ObLevel {
+    pub price  : u64,
+    pub volume : u32
+}
+
+impl Default for ObEvent {
+    fn default() -> ObEvent {
+        ObEvent{ kind : 0, flags : 0, side : 0, state : 0, auction_type : 0, timestamp : 0, order_id : 0,
+                 new_order_id : 0, exec_id : 0, price : 0, shares : 0, remaining : 0, buy_shares : 0,
+                 sell_shares : 0, symbol : [0; OB_SYMBOL_LEN] }
+    }
+}
+
+fn state_code( state : TradingState ) -> c_char {
+    match state {
+        TradingState::Halted       => OB_STATE_HALTED,
+        TradingState::Suspended    => OB_STATE_SUSPENDED,
+        TradingState::QuoteOnly    => OB_STATE_QUOTE_ONLY,
+        TradingState::Trading      => OB_STATE_TRADING,
+        TradingState::Cleared      => OB_STATE_CLEARED,
+        TradingState::EndOfSession => OB_STATE_END_OF_SESSION,
+        TradingState::Other(c)     => c as c_char
+    }
+}
+
+fn set_symbol( out : &mut ObEvent, symbol : &str ) {
+    // truncated rather than refused, no real symbol gets near it.
+    for (d, s) in out.symbol.iter_mut().zip(symbol.bytes().take(OB_SYMBOL_LEN - 1)) {
+        *d = s as c_char;
+    }
+}
+
+impl From<&MarketEvent> for ObEvent {
+    fn from( e : &MarketEvent ) -> ObEvent {
+        let mut out = ObEvent{ timestamp : e.timestamp(), ..Default::default() };
+        match *e {
+            MarketEvent::OrderAdded{ order_id, side, price, shares, ref symbol, .. } => {
+                out.kind = OB_ORDER_ADDED;
+                out.flags = OB_HAS_ORDER_ID | OB_HAS_PRICE;
+                out.order_id = order_id;
+                out.side = side as c_char;
+                out.price = price;
+                out.shares = shares;
+                set_symbol(&mut out, symbol);
+            },
+            MarketEvent::OrderReduced{ order_id, shares, .. } => {
+                out.kind = OB_ORDER_REDUCED;
+                out.flags = OB_HAS_ORDER_ID;
+                out.order_id = order_id;
+                out.shares = shares;
+            },
+            MarketEvent::OrderDeleted{ order_id, .. } => {
+                out.kind = OB_ORDER_DELETED;
+                out.flags = OB_HAS_ORDER_ID;
+                out.order_id = order_id;
+            },
+            MarketEvent::OrderReplaced{ order_id, new_order_id, price, shares, .. } => {
+                out.kind = OB_ORDER_REPLACED;
+                out.flags = OB_HAS_ORDER_ID | OB_HAS_PRICE;
+                out.order_id = order_id;
+                out.new_order_id = new_order_id;
+                out.price = price;
+                out.shares = shares;
+            },
+            MarketEvent::Trade{ order_id, ref symbol, side, price, shares, remaining, exec_id, .. } => {
+                out.kind = OB_TRADE;
+                if let Some(id) = order_id {
+                    out.flags |= OB_HAS_ORDER_ID;
+                    out.order_id = id;
+                }
+                if let Some(p) = price {
+                    out.flags |= OB_HAS_PRICE;
+                    out.price = p;
+                }
+                if let Some(r) = remaining {
+                    out.flags |= OB_HAS_REMAINING;
+                    out.remaining = r;
+                }
+                out.side = side.map_or(0, |c| c as c_char);
+                out.shares = shares;
+                out.exec_id = exec_id;
+                if let Some(ref s) = *symbol {
+                    set_symbol(&mut out, s);
+                }
+            },
+            MarketEvent::TradeBroken{ exec_id, .. } => {
+                out.kind = OB_TRADE_BROKEN;
+                out.exec_id = exec_id;
+            },
+            MarketEvent::StatusChange{ ref symbol, state, .. } => {
+                out.kind = OB_STATUS_CHANGE;
+                out.state = state_code(state);
+                if let Some(ref s) = *symbol {
+                    set_symbol(&mut out, s);
+                }
+            },
+            MarketEvent::AuctionInfo{ ref symbol, auction_type, price, buy_shares, sell_shares, executed_shares,
+                                      completed, .. } => {
+                out.kind = OB_AUCTION_INFO;
+                out.flags = OB_HAS_PRICE | if completed { OB_COMPLETED } else { 0 };
+                out.auction_type = auction_type as c_char;
+                out.price = price;
+                out.buy_shares = buy_shares;
+                out.sell_shares = sell_shares;
+                out.shares = executed_shares;
+                set_symbol(&mut out, symbol);
+            }
+        }
+        out
+    }
+}
+
+// runs an entry point, a panic coming back as `failed` instead of unwinding into C (which
+// aborts the caller's process).
+fn guarded<T, F : FnOnce() -> T>( failed : T, f : F ) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(failed)
+}
+
+fn side_of( side : c_char ) -> Option<Side> {
+    match side as u8 {
+        b'B' => Some(Side::Bid),
+        b'S' => Some(Side::Ask),
+        _    => None
+    }
+}
+
+// Parses one PITCH text message (no line ending needed) into *out. Returns 1 when *out was
+// written, 0 for a message that has no event (eg. retail price improvement) and -1 when it
+// isn't a message.
+#[no_mangle]
+pub unsafe extern "C" fn ob_parse( msg : *const c_char, len : usize, out : *mut ObEvent ) -> c_int {
+    if msg.is_null() || out.is_null() {
+        return -1;
+    }
+    guarded(-1, || {
+        let msg = match str::from_utf8(slice::from_raw_parts(msg as *const u8, len)) {
+            Ok(s)  => s.trim_end_matches(['\r', '\n']),
+            Err(_) => return -1
+        };
+        let parsed = match BATSMsgFactory::try_parse(msg) {
+            Some(m) => m,
+            None    => return -1
+        };
+        let timestamp = TimestampComposer::new().compose(&parsed);
+        match MarketEvent::from_bats(&parsed, timestamp) {
+            Some(e) => { ptr::write(out, ObEvent::from(&e)); 1 },
+            None    => 0
+        }
+    })
+}
+
+// A book fed with ObEvents, recording what the feed says rests without matching anything: a
+// locked or crossed add just rests. Reduces/deletes/executions find their order by id
+// without the caller having to repeat it.
+pub struct ObBook {
+    book : OrderBook
+}
+
+impl ObBook {
+    fn add( &mut self, timestamp : u64, order_id : u64, side : c_char, price : u64, shares : u32 ) -> bool {
+        match side_of(side) {
+            Some(side) => { self.book.add_order_at(timestamp, order_id, side, price, shares, true); true },
+            None       => false
+        }
+    }
+
+    fn apply( &mut self, e : &ObEvent ) -> bool {
+        match e.kind {
+            OB_ORDER_ADDED   => self.add(e.timestamp, e.order_id, e.side, e.price, e.shares),
+            OB_ORDER_REDUCED => self.book.reduce_order(e.order_id, e.shares),
+            OB_ORDER_DELETED => self.book.delete_order(e.order_id),
+            OB_ORDER_REPLACED => match self.book.get_order(e.order_id) {
+                Some(o) => {
+                    self.book.delete_order(e.order_id);
+                    self.book.add_order_at(e.timestamp, e.new_order_id, o.side, e.price, e.shares, true);
+                    true
+                },
+                None => false
+            },
+            OB_TRADE if e.flags & OB_HAS_ORDER_ID != 0 => {
+                if e.flags & OB_HAS_REMAINING != 0 {
+                    self.book.set_remaining(e.order_id, e.remaining)
+                } else {
+                    self.book.reduce_order(e.order_id, e.shares)
+                }
+            },
+            OB_STATUS_CHANGE if e.state == OB_STATE_CLEARED => {
+                self.book.clear();
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+// what fits in the header's u32 volumes.
+fn volume( shares : u64 ) -> u32 {
+    u32::try_from(shares).unwrap_or(u32::MAX)
+}
+
+#[no_mangle]
+pub extern "C" fn ob_book_new() -> *mut ObBook {
+    guarded(ptr::null_mut(), || Box::into_raw(Box::new(ObBook{ book : OrderBook::new() })))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_free( book : *mut ObBook ) {
+    if !book.is_null() {
+        guarded((), || drop(Box::from_raw(book)));
+    }
+}
+
+// Returns 1 if the event changed the book, 0 if it didn't (another book's order, trades
+// without a resting order, ...) and -1 for null pointers.
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_apply( book : *mut ObBook, e : *const ObEvent ) -> c_int {
+    match (book.as_mut(), e.as_ref()) {
+        (Some(b), Some(e)) => guarded(-1, || b.apply(e) as c_int),
+        _                  => -1
+    }
+}
+
+// side is 'B' or 'S'. Returns 0, or -1 for a bad side or book.
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_add_order( book : *mut ObBook, order_id : u64, side : c_char, price : u64,
+                                            shares : u32 ) -> c_int {
+    match book.as_mut().map(|b| guarded(false, || b.add(0, order_id, side, price, shares))) {
+        Some(true) => 0,
+        _          => -1
+    }
+}
+
+// Returns 0, or -1 for an unknown order.
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_reduce_order( book : *mut ObBook, order_id : u64, shares : u32 ) -> c_int {
+    match book.as_mut().map(|b| guarded(false, || b.book.reduce_order(order_id, shares))) {
+        Some(true) => 0,
+        _          => -1
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_delete_order( book : *mut ObBook, order_id : u64 ) -> c_int {
+    match book.as_mut().map(|b| guarded(false, || b.book.delete_order(order_id))) {
+        Some(true) => 0,
+        _          => -1
+    }
+}
+
+// 0 when the side is empty.
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_best_bid( book : *const ObBook ) -> u64 {
+    book.as_ref().map_or(0, |b| guarded(0, || b.book.best_bid().map_or(0, |q| q.price)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_best_ask( book : *const ObBook ) -> u64 {
+    book.as_ref().map_or(0, |b| guarded(0, || b.book.best_ask().map_or(0, |q| q.price)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_volume( book : *const ObBook, side : c_int, price : u64 ) -> u32 {
+    match book.as_ref() {
+        Some(b) if side == OB_BID => guarded(0, || volume(b.book.shares_at(Side::Bid, price))),
+        Some(b) if side == OB_ASK => guarded(0, || volume(b.book.shares_at(Side::Ask, price))),
+        _ => 0
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_order_count( book : *const ObBook ) -> usize {
+    book.as_ref().map_or(0, |b| b.book.len())
+}
+
+// Writes up to max levels of one side, best first, returning how many were written.
+#[no_mangle]
+pub unsafe extern "C" fn ob_book_levels( book : *mut ObBook, side : c_int, out : *mut ObLevel, max : usize ) -> usize {
+    let b = match book.as_ref() {
+        Some(b) if !out.is_null() => b,
+        _ => return 0
+    };
+    let side = match side {
+        OB_BID => Side::Bid,
+        OB_ASK => Side::Ask,
+        _      => return 0
+    };
+    guarded(0, || {
+        let out = slice::from_raw_parts_mut(out, max);
+        let mut n = 0;
+        for (slot, level) in out.iter_mut().zip(b.book.levels(side)) {
+            *slot = ObLevel{ price : level.price(), volume : volume(level.shares()) };
+            n += 1;
+        }
+        n
+    })
+}
@@ -0,0 +1,208 @@
+
+// Lightweight FIX tag=value parser for market data, ie. MarketDataSnapshotFullRefresh (35=W)
+// and MarketDataIncrementalRefresh (35=X). Fields are split on SOH, '|' is accepted as well
+// since that's what most logs use. Bid/offer entries can be turned into Orders so FIX
+// venues feed the same LimitOrderBook as PITCH does.
+
+use std::result::Result;
+
+use orderbook::Order;
+
+const SOH : char = '\u{1}';
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FixError {
+    InvalidField(String),
+    MissingTag(u32),
+    InvalidValue(u32, String),
+    UnexpectedMsgType(String)
+}
+
+#[derive(Debug)]
+pub struct FixMessage {
+    pub fields : Vec<(u32, String)>
+}
+
+impl FixMessage {
+    pub fn parse( msg : &str ) -> Result<FixMessage, FixError> {
+        let mut fields = Vec::new();
+        for field in msg.split(&[SOH, '|'][..]).filter(|f| !f.is_empty()) {
+            let mut kv = field.splitn(2, '=');
+            let tag = kv.next().and_then(|t| t.parse::<u32>().ok());
+            match (tag, kv.next()) {
+                (Some(t), Some(v)) => fields.push((t, String::from(v))),
+                _ => return Err(FixError::InvalidField(String::from(field)))
+            }
+        }
+        Ok(FixMessage{ fields })
+    }
+
+    pub fn get( &self, tag : u32 ) -> Option<&str> {
+        self.fields.iter().find(|f| f.0 == tag).map(|f| f.1.as_str())
+    }
+
+    pub fn msg_type( &self ) -> Option<&str> {
+        self.get(35)
+    }
+
+    // CheckSum (10) is the sum of every byte up to and including the SOH before it, mod 256.
+    pub fn verify_checksum( &self ) -> bool {
+        let mut sum : u32 = 0;
+        for &(tag, ref value) in &self.fields {
+            if tag == 10 {
+                return value.parse::<u32>().ok() == Some(sum % 256);
+            }
+            let field = format!("{}={}{}", tag, value, SOH);
+            sum += field.bytes().map(u32::from).sum::<u32>();
+        }
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MdUpdateAction {
+    New,
+    Change,
+    Delete
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdEntry {
+    pub action     : MdUpdateAction, // always New on a snapshot
+    pub entry_type : char,           // '0' bid, '1' offer, '2' trade
+    pub symbol     : String,
+    pub price      : u64,            // 4 implied decimal places
+    pub size       : u32,
+    pub entry_id   : Option<String>,
+    pub position   : Option<u32>
+}
+
+impl MdEntry {
+    // bids/offers as book orders. Venues that send MDEntryID get one order per entry,
+    // otherwise the price level itself stands in as the order.
+    pub fn to_order( &self ) -> Option<Order> {
+        let side = match self.entry_type {
+            '0' => 1,
+            '1' => -1,
+            _   => return None
+        };
+        let order_id = match self.entry_id {
+            Some(ref id) => id.parse::<u64>().unwrap_or(self.price),
+            None         => self.price
+        };
+        Some(Order{ order_id, price : self.price, volume : self.size, side, part_id : String::from("") })
+    }
+}
+
+#[derive(Debug)]
+pub struct MarketDataSnapshot {
+    pub symbol  : String,
+    pub entries : Vec<MdEntry>
+}
+
+#[derive(Debug)]
+pub struct MarketDataIncremental {
+    pub entries : Vec<MdEntry>
+}
+
+#[derive(Debug)]
+pub enum FixMarketData {
+    Snapshot(MarketDataSnapshot),
+    Incremental(MarketDataIncremental)
+}
+
+// "183.19" -> 1831900
+pub fn parse_price( value : &str ) -> Option<u64> {
+    let mut parts = value.splitn(2, '.');
+    let whole = parts.next()?.parse::<u64>().ok()?;
+    let frac = match parts.next() {
+        Some(f) if !f.is_empty() => {
+            let digits : String = f.chars().chain(::std::iter::repeat('0')).take(4).collect();
+            digits.parse::<u64>().ok()?
+        },
+        _ => 0
+    };
+    Some(whole * 10_000 + frac)
+}
+
+fn parse_entries( msg : &FixMessage, default_symbol : Option<&str>, snapshot : bool )
+    -> Result<Vec<MdEntry>, FixError> {
+    // entries start at each 279 (incremental) or 269 (snapshot) after NoMDEntries.
+    let start_tag = if snapshot { 269 } else { 279 };
+    let mut entries = Vec::new();
+    let mut current : Option<Vec<(u32, &str)>> = None;
+    let mut in_group = false;
+
+    for &(tag, ref value) in &msg.fields {
+        if tag == 268 {
+            in_group = true;
+            continue;
+        }
+        if !in_group || tag == 10 {
+            continue;
+        }
+        if tag == start_tag {
+            if let Some(fields) = current.take() {
+                entries.push(fields);
+            }
+            current = Some(vec![]);
+        }
+        if let Some(ref mut fields) = current {
+            fields.push((tag, value.as_str()));
+        }
+    }
+    if let Some(fields) = current.take() {
+        entries.push(fields);
+    }
+
+    let mut res = Vec::with_capacity(entries.len());
+    for fields in entries {
+        let get = |t : u32| fields.iter().find(|f| f.0 == t).map(|f| f.1);
+        let action = match get(279) {
+            None | Some("0") => MdUpdateAction::New,
+            Some("1")        => MdUpdateAction::Change,
+            Some("2")        => MdUpdateAction::Delete,
+            Some(v)          => return Err(FixError::InvalidValue(279, String::from(v)))
+        };
+        let entry_type = get(269).and_then(|v| v.chars().next()).ok_or(FixError::MissingTag(269))?;
+        let symbol = get(55).or(default_symbol).ok_or(FixError::MissingTag(55))?;
+        let price = match get(270) {
+            Some(v) => parse_price(v).ok_or_else(|| FixError::InvalidValue(270, String::from(v)))?,
+            None    => 0
+        };
+        let size = match get(271) {
+            Some(v) => v.parse::<f64>().map(|s| s as u32).map_err(|_| FixError::InvalidValue(271, String::from(v)))?,
+            None    => 0
+        };
+        res.push(MdEntry{ action,
+                          entry_type,
+                          symbol   : String::from(symbol),
+                          price,
+                          size,
+                          entry_id : get(278).map(String::from),
+                          position : get(290).and_then(|v| v.parse::<u32>().ok()) });
+    }
+    Ok(res)
+}
+
+impl FixMarketData {
+    pub fn parse( msg : &str ) -> Result<FixMarketData, FixError> {
+        FixMarketData::from_message(&FixMessage::parse(msg)?)
+    }
+
+    pub fn from_message( msg : &FixMessage ) -> Result<FixMarketData, FixError> {
+        match msg.msg_type() {
+            Some("W") => {
+                let symbol = msg.get(55).ok_or(FixError::MissingTag(55))?;
+                let entries = parse_entries(msg, Some(symbol), true)?;
+                Ok(FixMarketData::Snapshot(MarketDataSnapshot{ symbol : String::from(symbol), entries }))
+            },
+            Some("X") => {
+                let entries = parse_entries(msg, None, false)?;
+                Ok(FixMarketData::Incremental(MarketDataIncremental{ entries }))
+            },
+            Some(t) => Err(FixError::UnexpectedMsgType(String::from(t))),
+            None    => Err(FixError::MissingTag(35))
+        }
+    }
+}
@@ -0,0 +1,261 @@
+
+// Decoder for IEX DEEP and TOPS. Both ride on IEX-TP, a 40 byte segment header followed by
+// messages each prefixed with a 2 byte length. Everything is little endian, timestamps are
+// nanoseconds since the epoch and prices are signed with 4 implied decimal places.
+
+use nom;
+use nom::{le_u8, le_u16, le_u32, le_u64, le_i64};
+
+use std::collections::BTreeMap;
+use std::str;
+use std::result::Result;
+
+pub const PRICE_LEVEL_UPDATE_BUY  : u8 = 0x38;
+pub const PRICE_LEVEL_UPDATE_SELL : u8 = 0x35;
+pub const TRADE_REPORT            : u8 = 0x54;
+pub const OFFICIAL_PRICE          : u8 = 0x58;
+pub const QUOTE_UPDATE            : u8 = 0x51;
+
+#[derive(Debug)]
+pub enum IexMessage {
+    PriceLevelUpdate(PriceLevelUpdateMsg), // DEEP
+    TradeReport(TradeReportMsg),           // DEEP and TOPS
+    OfficialPrice(OfficialPriceMsg),       // DEEP and TOPS
+    QuoteUpdate(QuoteUpdateMsg),           // TOPS
+    Unknown(u8)
+}
+
+#[derive(Debug)]
+pub struct SegmentHeader {
+    pub version        : u8,
+    pub protocol_id    : u16,
+    pub channel_id     : u32,
+    pub session_id     : u32,
+    pub payload_length : u16,
+    pub message_count  : u16,
+    pub stream_offset  : u64,
+    pub first_sequence : u64,
+    pub send_time      : u64
+}
+
+#[derive(Debug)]
+pub struct PriceLevelUpdateMsg {
+    pub side        : char, // 'B' or 'S', taken from the message type
+    pub event_flags : u8,   // 1 when the update completes an atomic event
+    pub timestamp   : u64,
+    pub symbol      : String,
+    pub size        : u32,  // aggregate size at the level, 0 means the level is gone
+    pub price       : i64
+}
+
+#[derive(Debug)]
+pub struct TradeReportMsg {
+    pub sale_condition : u8,
+    pub timestamp      : u64,
+    pub symbol         : String,
+    pub size           : u32,
+    pub price          : i64,
+    pub trade_id       : u64
+}
+
+#[derive(Debug)]
+pub struct OfficialPriceMsg {
+    pub price_type : char, // 'Q' opening, 'M' closing
+    pub timestamp  : u64,
+    pub symbol     : String,
+    pub price      : i64
+}
+
+#[derive(Debug)]
+pub struct QuoteUpdateMsg {
+    pub flags     : u8,
+    pub timestamp : u64,
+    pub symbol    : String,
+    pub bid_size  : u32,
+    pub bid_price : i64,
+    pub ask_price : i64,
+    pub ask_size  : u32
+}
+
+impl PriceLevelUpdateMsg {
+    pub fn is_event_complete( &self ) -> bool {
+        self.event_flags & 0x1 == 0x1
+    }
+}
+
+impl IexMessage {
+    pub fn parse( msg : &[u8] ) -> Result<IexMessage, nom::Err<&[u8]>> {
+        match msg.first() {
+            Some(&PRICE_LEVEL_UPDATE_BUY)  |
+            Some(&PRICE_LEVEL_UPDATE_SELL) => parse_price_level_update(msg).map(|o| IexMessage::PriceLevelUpdate(o.1)),
+            Some(&TRADE_REPORT)            => parse_trade_report(msg).map(|o| IexMessage::TradeReport(o.1)),
+            Some(&OFFICIAL_PRICE)          => parse_official_price(msg).map(|o| IexMessage::OfficialPrice(o.1)),
+            Some(&QUOTE_UPDATE)            => parse_quote_update(msg).map(|o| IexMessage::QuoteUpdate(o.1)),
+            Some(&c)                       => Ok(IexMessage::Unknown(c)),
+            None                           => Err(nom::Err::Incomplete(nom::Needed::Size(1)))
+        }
+    }
+}
+
+pub type Segment<'a> = (SegmentHeader, Vec<&'a [u8]>);
+
+// splits an IEX-TP segment into its header and messages.
+pub fn split_segment( segment : &[u8] ) -> Result<Segment<'_>, nom::Err<&[u8]>> {
+    let (mut rest, header) = parse_segment_header(segment)?;
+    let mut msgs = Vec::with_capacity(header.message_count as usize);
+    for _ in 0..header.message_count {
+        let (remaining, msg) = parse_length_prefixed(rest)?;
+        msgs.push(msg);
+        rest = remaining;
+    }
+    Ok((header, msgs))
+}
+
+// DEEP only publishes aggregated levels, so the book is price -> size per side. Queries
+// mirror LimitOrderBook so the same code can be pointed at either.
+#[derive(Debug, Default)]
+pub struct IexBook {
+    bids : BTreeMap<i64, u32>,
+    asks : BTreeMap<i64, u32>
+}
+
+impl IexBook {
+    pub fn new() -> IexBook {
+        IexBook{ bids : BTreeMap::new(), asks : BTreeMap::new() }
+    }
+
+    pub fn apply( &mut self, msg : &PriceLevelUpdateMsg ) {
+        let levels = if msg.side == 'B' { &mut self.bids } else { &mut self.asks };
+        if msg.size == 0 {
+            levels.remove(&msg.price);
+        } else {
+            levels.insert(msg.price, msg.size);
+        }
+    }
+
+    pub fn best_bid( &self ) -> i64 {
+        self.bids.keys().next_back().cloned().unwrap_or(0)
+    }
+
+    pub fn best_ask( &self ) -> i64 {
+        self.asks.keys().next().cloned().unwrap_or(0)
+    }
+
+    pub fn bid_volume_at_price_level( &self, price : i64 ) -> u32 {
+        self.bids.get(&price).cloned().unwrap_or(0)
+    }
+
+    pub fn ask_volume_at_price_level( &self, price : i64 ) -> u32 {
+        self.asks.get(&price).cloned().unwrap_or(0)
+    }
+}
+
+fn from_ascii( input : &[u8] ) -> Result<String, str::Utf8Error> {
+    str::from_utf8(input).map(String::from)
+}
+
+named!(parse_segment_header<&[u8], SegmentHeader>,
+    do_parse!(
+        _1  : le_u8   >>
+        _2  : le_u8   >>
+        _3  : le_u16  >>
+        _4  : le_u32  >>
+        _5  : le_u32  >>
+        _6  : le_u16  >>
+        _7  : le_u16  >>
+        _8  : le_u64  >>
+        _9  : le_u64  >>
+        _10 : le_u64  >>
+        (SegmentHeader{ version        : _1,
+                        protocol_id    : _3,
+                        channel_id     : _4,
+                        session_id     : _5,
+                        payload_length : _6,
+                        message_count  : _7,
+                        stream_offset  : _8,
+                        first_sequence : _9,
+                        send_time      : _10
+                      })
+    )
+);
+
+named!(parse_length_prefixed<&[u8], &[u8]>,
+    do_parse!(
+        _1 : le_u16    >>
+        _2 : take!(_1) >>
+        (_2)
+    )
+);
+
+named!(parse_price_level_update<&[u8], PriceLevelUpdateMsg>,
+    do_parse!(
+        _1 : le_u8                          >>
+        _2 : le_u8                          >>
+        _3 : le_u64                         >>
+        _4 : map_res!(take!(8), from_ascii) >>
+        _5 : le_u32                         >>
+        _6 : le_i64                         >>
+        (PriceLevelUpdateMsg{ side        : if _1 == PRICE_LEVEL_UPDATE_BUY { 'B' } else { 'S' },
+                              event_flags : _2,
+                              timestamp   : _3,
+                              symbol      : _4,
+                              size        : _5,
+                              price       : _6
+                            })
+    )
+);
+
+named!(parse_trade_report<&[u8], TradeReportMsg>,
+    do_parse!(
+        _1 : le_u8                          >>
+        _2 : le_u8                          >>
+        _3 : le_u64                         >>
+        _4 : map_res!(take!(8), from_ascii) >>
+        _5 : le_u32                         >>
+        _6 : le_i64                         >>
+        _7 : le_u64                         >>
+        (TradeReportMsg{ sale_condition : _2,
+                         timestamp      : _3,
+                         symbol         : _4,
+                         size           : _5,
+                         price          : _6,
+                         trade_id       : _7
+                       })
+    )
+);
+
+named!(parse_official_price<&[u8], OfficialPriceMsg>,
+    do_parse!(
+        _1 : le_u8                          >>
+        _2 : map!(le_u8, char::from)        >>
+        _3 : le_u64                         >>
+        _4 : map_res!(take!(8), from_ascii) >>
+        _5 : le_i64                         >>
+        (OfficialPriceMsg{ price_type : _2,
+                           timestamp  : _3,
+                           symbol     : _4,
+                           price      : _5
+                         })
+    )
+);
+
+named!(parse_quote_update<&[u8], QuoteUpdateMsg>,
+    do_parse!(
+        _1 : le_u8                          >>
+        _2 : le_u8                          >>
+        _3 : le_u64                         >>
+        _4 : map_res!(take!(8), from_ascii) >>
+        _5 : le_u32                         >>
+        _6 : le_i64                         >>
+        _7 : le_i64                         >>
+        _8 : le_u32                         >>
+        (QuoteUpdateMsg{ flags     : _2,
+                         timestamp : _3,
+                         symbol    : _4,
+                         bid_size  : _5,
+                         bid_price : _6,
+                         ask_price : _7,
+                         ask_size  : _8
+                       })
+    )
+);
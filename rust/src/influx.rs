@@ -0,0 +1,154 @@
+
+// InfluxDB line protocol for trades and top of book updates, written to any Write (a file,
+// or HttpWriter to post straight to the server). Points look like
+//
+//   trade,symbol=AAPL,side=B price=183.19,shares=100i,exec_id=7i 1538040000000000000
+//   bbo,symbol=AAPL bid=183.18,bid_size=300i,ask=183.2,ask_size=100i 1538040000000000000
+//
+// Event timestamps are ns past midnight, the sink adds the session's midnight (ns since
+// the epoch) to get Influx's absolute time.
+
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::TcpStream;
+
+use event::MarketEvent;
+use messages::format_price;
+
+fn escape_tag( s : &str ) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.trim_end().chars() {
+        if c == ',' || c == '=' || c == ' ' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub struct InfluxSink<W : Write> {
+    writer   : W,
+    midnight : u64,
+    points   : usize
+}
+
+impl<W : Write> InfluxSink<W> {
+    pub fn new( writer : W ) -> InfluxSink<W> {
+        InfluxSink{ writer, midnight : 0, points : 0 }
+    }
+
+    // ns since the epoch of the session's midnight, added to every timestamp.
+    pub fn midnight( mut self, epoch_ns : u64 ) -> InfluxSink<W> {
+        self.midnight = epoch_ns;
+        self
+    }
+
+    // writes trades, returns false for every other event.
+    pub fn write_event( &mut self, e : &MarketEvent ) -> io::Result<bool> {
+        match *e {
+            MarketEvent::Trade{ timestamp, symbol : Some(ref symbol), side, price : Some(p), shares, exec_id, .. } =>
+                self.write_trade(timestamp, symbol, side, p, shares, exec_id).map(|_| true),
+            _ => Ok(false)
+        }
+    }
+
+    pub fn write_trade( &mut self, timestamp : u64, symbol : &str, side : Option<char>, p : u64, shares : u32,
+                        exec_id : u64 ) -> io::Result<()> {
+        write!(self.writer, "trade,symbol={}", escape_tag(symbol))?;
+        if let Some(side) = side {
+            write!(self.writer, ",side={}", side)?;
+        }
+        writeln!(self.writer, " price={},shares={}i,exec_id={}i {}", format_price(p), shares, exec_id,
+                 self.midnight + timestamp)?;
+        self.points += 1;
+        Ok(())
+    }
+
+    // best bid and ask as (price, size), a missing side leaves its fields out.
+    pub fn write_bbo( &mut self, timestamp : u64, symbol : &str, bid : Option<(u64, u32)>,
+                      ask : Option<(u64, u32)> ) -> io::Result<()> {
+        let mut fields = vec![];
+        if let Some((p, size)) = bid {
+            fields.push(format!("bid={},bid_size={}i", format_price(p), size));
+        }
+        if let Some((p, size)) = ask {
+            fields.push(format!("ask={},ask_size={}i", format_price(p), size));
+        }
+        if fields.is_empty() {
+            return Ok(()); // a point needs at least one field
+        }
+        writeln!(self.writer, "bbo,symbol={} {} {}", escape_tag(symbol), fields.join(","), self.midnight + timestamp)?;
+        self.points += 1;
+        Ok(())
+    }
+
+    pub fn points( &self ) -> usize {
+        self.points
+    }
+
+    pub fn flush( &mut self ) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner( self ) -> W {
+        self.writer
+    }
+}
+
+// Buffers lines and POSTs them to the server's write endpoint on flush, eg.
+// HttpWriter::new("localhost:8086", "/api/v2/write?bucket=md&org=desk&precision=ns") with
+// .token(..) for 2.x, or "/write?db=md" for 1.x.
+pub struct HttpWriter {
+    addr  : String,
+    path  : String,
+    token : Option<String>,
+    buf   : Vec<u8>
+}
+
+impl HttpWriter {
+    pub fn new( addr : &str, path : &str ) -> HttpWriter {
+        HttpWriter{ addr : String::from(addr), path : String::from(path), token : None, buf : vec![] }
+    }
+
+    pub fn token( mut self, token : &str ) -> HttpWriter {
+        self.token = Some(String::from(token));
+        self
+    }
+
+    fn post( &self ) -> io::Result<()> {
+        let mut stream = TcpStream::connect(&self.addr)?;
+        write!(stream, "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\n", self.path, self.addr)?;
+        if let Some(ref token) = self.token {
+            write!(stream, "Authorization: Token {}\r\n", token)?;
+        }
+        write!(stream, "Content-Length: {}\r\nConnection: close\r\n\r\n", self.buf.len())?;
+        stream.write_all(&self.buf)?;
+        stream.flush()?;
+
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!("influx write failed: {}", status.trim_end())))
+        }
+    }
+}
+
+impl Write for HttpWriter {
+    fn write( &mut self, data : &[u8] ) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    // the buffer is kept on failure so the caller can retry the flush.
+    fn flush( &mut self ) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.post()?;
+        self.buf.clear();
+        Ok(())
+    }
+}
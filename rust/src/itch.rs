@@ -0,0 +1,382 @@
+
+// Decoder for NASDAQ TotalView-ITCH 5.0. Messages are big endian, timestamps are nanoseconds
+// past midnight sent as 6 bytes and prices are 4 implied decimal places, same as PITCH.
+// Only the messages needed to build a book are decoded, the rest are skipped as Unknown.
+
+use nom;
+use nom::{be_u8, be_u16, be_u32, be_u64};
+
+use std::str;
+use std::result::Result;
+
+#[derive(Debug)]
+pub enum ItchMessage {
+    StockDirectory(StockDirectoryMsg),
+    AddOrder(AddOrderMsg),
+    OrderExecuted(OrderExecutedMsg),
+    OrderExecutedWithPrice(OrderExecutedWithPriceMsg),
+    OrderCancel(OrderCancelMsg),
+    OrderDelete(OrderDeleteMsg),
+    OrderReplace(OrderReplaceMsg),
+    Trade(TradeMsg),
+    Unknown(char)
+}
+
+#[derive(Debug)]
+pub struct StockDirectoryMsg {
+    pub stock_locate         : u16,
+    pub tracking_number      : u16,
+    pub timestamp            : u64,
+    pub symbol               : String,
+    pub market_category      : char,
+    pub financial_status     : char,
+    pub round_lot_size       : u32,
+    pub round_lots_only      : char,
+    pub issue_classification : char,
+    pub issue_subtype        : String,
+    pub authenticity         : char,
+    pub short_sale_threshold : char,
+    pub ipo_flag             : char,
+    pub luld_price_tier      : char,
+    pub etp_flag             : char,
+    pub etp_leverage_factor  : u32,
+    pub inverse_indicator    : char
+}
+
+#[derive(Debug)]
+pub struct AddOrderMsg { // both 'A' and 'F' (with attribution)
+    pub msg_type        : char,
+    pub stock_locate    : u16,
+    pub tracking_number : u16,
+    pub timestamp       : u64,
+    pub order_id        : u64,
+    pub side            : char,
+    pub shares          : u32,
+    pub symbol          : String,
+    pub price           : u64,
+    pub part_id         : String
+}
+
+#[derive(Debug)]
+pub struct OrderExecutedMsg {
+    pub stock_locate    : u16,
+    pub tracking_number : u16,
+    pub timestamp       : u64,
+    pub order_id        : u64,
+    pub shares          : u32,
+    pub exec_id         : u64
+}
+
+#[derive(Debug)]
+pub struct OrderExecutedWithPriceMsg {
+    pub stock_locate    : u16,
+    pub tracking_number : u16,
+    pub timestamp       : u64,
+    pub order_id        : u64,
+    pub shares          : u32,
+    pub exec_id         : u64,
+    pub printable       : char,
+    pub price           : u64
+}
+
+#[derive(Debug)]
+pub struct OrderCancelMsg {
+    pub stock_locate    : u16,
+    pub tracking_number : u16,
+    pub timestamp       : u64,
+    pub order_id        : u64,
+    pub shares          : u32
+}
+
+#[derive(Debug)]
+pub struct OrderDeleteMsg {
+    pub stock_locate    : u16,
+    pub tracking_number : u16,
+    pub timestamp       : u64,
+    pub order_id        : u64
+}
+
+#[derive(Debug)]
+pub struct OrderReplaceMsg {
+    pub stock_locate      : u16,
+    pub tracking_number   : u16,
+    pub timestamp         : u64,
+    pub original_order_id : u64,
+    pub order_id          : u64,
+    pub shares            : u32,
+    pub price             : u64
+}
+
+#[derive(Debug)]
+pub struct TradeMsg { // execution against a non-displayed order
+    pub stock_locate    : u16,
+    pub tracking_number : u16,
+    pub timestamp       : u64,
+    pub order_id        : u64,
+    pub side            : char,
+    pub shares          : u32,
+    pub symbol          : String,
+    pub price           : u64,
+    pub exec_id         : u64
+}
+
+impl ItchMessage {
+    pub fn parse( msg : &[u8] ) -> Result<ItchMessage, nom::Err<&[u8]>> {
+        match msg.first() {
+            Some(&b'R') => parse_stock_directory(msg).map(|o| ItchMessage::StockDirectory(o.1)),
+            Some(&b'A') => parse_add_order(msg).map(|o| ItchMessage::AddOrder(o.1)),
+            Some(&b'F') => parse_add_order_mpid(msg).map(|o| ItchMessage::AddOrder(o.1)),
+            Some(&b'E') => parse_order_executed(msg).map(|o| ItchMessage::OrderExecuted(o.1)),
+            Some(&b'C') => parse_order_executed_with_price(msg).map(|o| ItchMessage::OrderExecutedWithPrice(o.1)),
+            Some(&b'X') => parse_order_cancel(msg).map(|o| ItchMessage::OrderCancel(o.1)),
+            Some(&b'D') => parse_order_delete(msg).map(|o| ItchMessage::OrderDelete(o.1)),
+            Some(&b'U') => parse_order_replace(msg).map(|o| ItchMessage::OrderReplace(o.1)),
+            Some(&b'P') => parse_trade(msg).map(|o| ItchMessage::Trade(o.1)),
+            Some(&c)    => Ok(ItchMessage::Unknown(char::from(c))),
+            None        => Err(nom::Err::Incomplete(nom::Needed::Size(1)))
+        }
+    }
+}
+
+// ITCH files (and SoupBinTCP payloads) frame every message with a 2 byte length. Returns
+// the complete messages found in the buffer and the number of bytes consumed, so the
+// caller can keep any partial trailing message for the next read.
+pub fn split_messages( buf : &[u8] ) -> (Vec<&[u8]>, usize) {
+    let mut msgs = Vec::new();
+    let mut pos = 0;
+    while buf.len() >= pos + 2 {
+        let len = ((buf[pos] as usize) << 8) | buf[pos + 1] as usize;
+        if buf.len() < pos + 2 + len {
+            break;
+        }
+        msgs.push(&buf[pos + 2 .. pos + 2 + len]);
+        pos += 2 + len;
+    }
+    (msgs, pos)
+}
+
+fn from_ascii( input : &[u8] ) -> Result<String, str::Utf8Error> {
+    str::from_utf8(input).map(String::from)
+}
+
+named!(parse_timestamp<&[u8], u64>,
+    do_parse!(
+        _1 : be_u16 >>
+        _2 : be_u32 >>
+        ((u64::from(_1) << 32) | u64::from(_2))
+    )
+);
+
+named!(parse_char<&[u8], char>, map!(be_u8, char::from));
+
+named!(parse_stock_directory<&[u8], StockDirectoryMsg>,
+    do_parse!(
+        _1  : tag!("R")                       >>
+        _2  : be_u16                          >>
+        _3  : be_u16                          >>
+        _4  : parse_timestamp                 >>
+        _5  : map_res!(take!(8), from_ascii)  >>
+        _6  : parse_char                      >>
+        _7  : parse_char                      >>
+        _8  : be_u32                          >>
+        _9  : parse_char                      >>
+        _10 : parse_char                      >>
+        _11 : map_res!(take!(2), from_ascii)  >>
+        _12 : parse_char                      >>
+        _13 : parse_char                      >>
+        _14 : parse_char                      >>
+        _15 : parse_char                      >>
+        _16 : parse_char                      >>
+        _17 : be_u32                          >>
+        _18 : parse_char                      >>
+        (StockDirectoryMsg{ stock_locate         : _2,
+                            tracking_number      : _3,
+                            timestamp            : _4,
+                            symbol               : _5,
+                            market_category      : _6,
+                            financial_status     : _7,
+                            round_lot_size       : _8,
+                            round_lots_only      : _9,
+                            issue_classification : _10,
+                            issue_subtype        : _11,
+                            authenticity         : _12,
+                            short_sale_threshold : _13,
+                            ipo_flag             : _14,
+                            luld_price_tier      : _15,
+                            etp_flag             : _16,
+                            etp_leverage_factor  : _17,
+                            inverse_indicator    : _18
+                          })
+    )
+);
+
+named!(parse_add_order<&[u8], AddOrderMsg>,
+    do_parse!(
+        _1 : tag!("A")                      >>
+        _2 : be_u16                         >>
+        _3 : be_u16                         >>
+        _4 : parse_timestamp                >>
+        _5 : be_u64                         >>
+        _6 : parse_char                     >>
+        _7 : be_u32                         >>
+        _8 : map_res!(take!(8), from_ascii) >>
+        _9 : be_u32                         >>
+        (AddOrderMsg{ msg_type        : 'A',
+                      stock_locate    : _2,
+                      tracking_number : _3,
+                      timestamp       : _4,
+                      order_id        : _5,
+                      side            : _6,
+                      shares          : _7,
+                      symbol          : _8,
+                      price           : u64::from(_9),
+                      part_id         : String::from("")
+                    })
+    )
+);
+
+named!(parse_add_order_mpid<&[u8], AddOrderMsg>,
+    do_parse!(
+        _1  : tag!("F")                      >>
+        _2  : be_u16                         >>
+        _3  : be_u16                         >>
+        _4  : parse_timestamp                >>
+        _5  : be_u64                         >>
+        _6  : parse_char                     >>
+        _7  : be_u32                         >>
+        _8  : map_res!(take!(8), from_ascii) >>
+        _9  : be_u32                         >>
+        _10 : map_res!(take!(4), from_ascii) >>
+        (AddOrderMsg{ msg_type        : 'F',
+                      stock_locate    : _2,
+                      tracking_number : _3,
+                      timestamp       : _4,
+                      order_id        : _5,
+                      side            : _6,
+                      shares          : _7,
+                      symbol          : _8,
+                      price           : u64::from(_9),
+                      part_id         : _10
+                    })
+    )
+);
+
+named!(parse_order_executed<&[u8], OrderExecutedMsg>,
+    do_parse!(
+        _1 : tag!("E")       >>
+        _2 : be_u16          >>
+        _3 : be_u16          >>
+        _4 : parse_timestamp >>
+        _5 : be_u64          >>
+        _6 : be_u32          >>
+        _7 : be_u64          >>
+        (OrderExecutedMsg{ stock_locate    : _2,
+                           tracking_number : _3,
+                           timestamp       : _4,
+                           order_id        : _5,
+                           shares          : _6,
+                           exec_id         : _7
+                         })
+    )
+);
+
+named!(parse_order_executed_with_price<&[u8], OrderExecutedWithPriceMsg>,
+    do_parse!(
+        _1 : tag!("C")       >>
+        _2 : be_u16          >>
+        _3 : be_u16          >>
+        _4 : parse_timestamp >>
+        _5 : be_u64          >>
+        _6 : be_u32          >>
+        _7 : be_u64          >>
+        _8 : parse_char      >>
+        _9 : be_u32          >>
+        (OrderExecutedWithPriceMsg{ stock_locate    : _2,
+                                    tracking_number : _3,
+                                    timestamp       : _4,
+                                    order_id        : _5,
+                                    shares          : _6,
+                                    exec_id         : _7,
+                                    printable       : _8,
+                                    price           : u64::from(_9)
+                                  })
+    )
+);
+
+named!(parse_order_cancel<&[u8], OrderCancelMsg>,
+    do_parse!(
+        _1 : tag!("X")       >>
+        _2 : be_u16          >>
+        _3 : be_u16          >>
+        _4 : parse_timestamp >>
+        _5 : be_u64          >>
+        _6 : be_u32          >>
+        (OrderCancelMsg{ stock_locate    : _2,
+                         tracking_number : _3,
+                         timestamp       : _4,
+                         order_id        : _5,
+                         shares          : _6
+                       })
+    )
+);
+
+named!(parse_order_delete<&[u8], OrderDeleteMsg>,
+    do_parse!(
+        _1 : tag!("D")       >>
+        _2 : be_u16          >>
+        _3 : be_u16          >>
+        _4 : parse_timestamp >>
+        _5 : be_u64          >>
+        (OrderDeleteMsg{ stock_locate    : _2,
+                         tracking_number : _3,
+                         timestamp       : _4,
+                         order_id        : _5
+                       })
+    )
+);
+
+named!(parse_order_replace<&[u8], OrderReplaceMsg>,
+    do_parse!(
+        _1 : tag!("U")       >>
+        _2 : be_u16          >>
+        _3 : be_u16          >>
+        _4 : parse_timestamp >>
+        _5 : be_u64          >>
+        _6 : be_u64          >>
+        _7 : be_u32          >>
+        _8 : be_u32          >>
+        (OrderReplaceMsg{ stock_locate      : _2,
+                          tracking_number   : _3,
+                          timestamp         : _4,
+                          original_order_id : _5,
+                          order_id          : _6,
+                          shares            : _7,
+                          price             : u64::from(_8)
+                        })
+    )
+);
+
+named!(parse_trade<&[u8], TradeMsg>,
+    do_parse!(
+        _1 : tag!("P")                      >>
+        _2 : be_u16                         >>
+        _3 : be_u16                         >>
+        _4 : parse_timestamp                >>
+        _5 : be_u64                         >>
+        _6 : parse_char                     >>
+        _7 : be_u32                         >>
+        _8 : map_res!(take!(8), from_ascii) >>
+        _9 : be_u32                         >>
+        _10 : be_u64                        >>
+        (TradeMsg{ stock_locate    : _2,
+                   tracking_number : _3,
+                   timestamp       : _4,
+                   order_id        : _5,
+                   side            : _6,
+                   shares          : _7,
+                   symbol          : _8,
+                   price           : u64::from(_9),
+                   exec_id         : _10
+                 })
+    )
+);
@@ -0,0 +1,51 @@
+
+// Newline delimited JSON, one message per line. The schema is the serde one, ie. a "type"
+// tag (eg. "add_order") followed by the message's fields, which is what jq and
+// pandas.read_json(lines=True) expect.
+
+use std::io;
+use std::io::Write;
+
+use serde_json;
+
+use messages::BATSMessage;
+
+pub struct JsonlWriter<W : Write> {
+    writer : W,
+    count  : usize
+}
+
+impl<W : Write> JsonlWriter<W> {
+    pub fn new( writer : W ) -> JsonlWriter<W> {
+        JsonlWriter{ writer, count : 0 }
+    }
+
+    pub fn write( &mut self, msg : &BATSMessage ) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, msg)?;
+        self.writer.write_all(b"\n")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    // writes every message, returning how many were written.
+    pub fn write_all<I>( &mut self, msgs : I ) -> io::Result<usize>
+        where I : IntoIterator<Item = BATSMessage> {
+        let start = self.count;
+        for msg in msgs {
+            self.write(&msg)?;
+        }
+        Ok(self.count - start)
+    }
+
+    pub fn count( &self ) -> usize {
+        self.count
+    }
+
+    pub fn flush( &mut self ) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner( self ) -> W {
+        self.writer
+    }
+}
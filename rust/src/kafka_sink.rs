@@ -0,0 +1,197 @@
+
+// Publishes events and book deltas to a Kafka topic, FlatBuffers encoded (see src/fbs.rs).
+// Records are keyed by symbol and sent to partition hash(symbol) % partitions so a
+// symbol's updates stay in order; order events that don't carry a symbol use the one the
+// order was added with, or the empty key if the add wasn't seen.
+//
+// Records are batched and sent on flush (or once batch_size are pending). Partitions that
+// don't acknowledge have their records resent up to `retries` times, after which flush
+// fails with Undelivered and the records stay pending for the next flush.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use kafka;
+use kafka::client::KafkaClient;
+use kafka::client::RequiredAcks;
+use kafka::producer::Partitioner;
+use kafka::producer::Producer;
+use kafka::producer::Record;
+
+use event::MarketEvent;
+use fbs::FbsEncoder;
+
+#[derive(Debug)]
+pub enum SinkError {
+    Kafka(kafka::Error),
+    UnknownTopic(String),
+    Undelivered(usize)   // records still pending after the retries
+}
+
+impl fmt::Display for SinkError {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        match *self {
+            SinkError::Kafka(ref e)        => write!(f, "kafka error: {}", e),
+            SinkError::UnknownTopic(ref t) => write!(f, "unknown topic: {}", t),
+            SinkError::Undelivered(n)      => write!(f, "{} records weren't acknowledged", n)
+        }
+    }
+}
+
+impl From<kafka::Error> for SinkError {
+    fn from( e : kafka::Error ) -> SinkError {
+        SinkError::Kafka(e)
+    }
+}
+
+pub struct PendingRecord {
+    pub partition : i32,
+    pub key       : String,
+    pub value     : Vec<u8>
+}
+
+// What the sink needs from a producer, implemented for kafka's Producer.
+pub trait RecordProducer {
+    // sends the batch, returning the partitions that didn't acknowledge it.
+    fn send_batch( &mut self, topic : &str, batch : &[PendingRecord] ) -> kafka::Result<Vec<i32>>;
+}
+
+impl<P : Partitioner> RecordProducer for Producer<P> {
+    fn send_batch( &mut self, topic : &str, batch : &[PendingRecord] ) -> kafka::Result<Vec<i32>> {
+        let records : Vec<_> = batch.iter().map(|r| {
+            Record::from_key_value(topic, r.key.as_bytes(), &r.value[..]).with_partition(r.partition)
+        }).collect();
+        let mut failed = vec![];
+        for confirm in self.send_all(&records)? {
+            for p in confirm.partition_confirms {
+                if p.offset.is_err() {
+                    failed.push(p.partition);
+                }
+            }
+        }
+        Ok(failed)
+    }
+}
+
+// FNV-1a, any stable hash would do but it has to be the same across processes.
+pub fn partition_for( key : &str, partitions : usize ) -> i32 {
+    let mut h : u32 = 0x811c_9dc5;
+    for b in key.bytes() {
+        h ^= u32::from(b);
+        h = h.wrapping_mul(0x0100_0193);
+    }
+    (h % partitions.max(1) as u32) as i32
+}
+
+pub struct KafkaSink<P : RecordProducer = Producer> {
+    producer   : P,
+    topic      : String,
+    partitions : usize,
+    batch_size : usize,
+    retries    : usize,
+    encoder    : FbsEncoder,
+    symbols    : HashMap<u64, String>,
+    pending    : Vec<PendingRecord>
+}
+
+impl KafkaSink<Producer> {
+    // looks the topic's partition count up in the cluster metadata.
+    pub fn connect( hosts : &[&str], topic : &str ) -> Result<KafkaSink<Producer>, SinkError> {
+        let mut client = KafkaClient::new(hosts.iter().map(|h| String::from(*h)).collect());
+        client.load_metadata(&[topic])?;
+        let partitions = match client.topics().partitions(topic) {
+            Some(p) if !p.is_empty() => p.len(),
+            _ => return Err(SinkError::UnknownTopic(String::from(topic)))
+        };
+        let producer = Producer::from_client(client)
+            .with_ack_timeout(Duration::from_secs(1))
+            .with_required_acks(RequiredAcks::One)
+            .create()?;
+        Ok(KafkaSink::new(producer, topic, partitions))
+    }
+}
+
+impl<P : RecordProducer> KafkaSink<P> {
+    pub fn new( producer : P, topic : &str, partitions : usize ) -> KafkaSink<P> {
+        KafkaSink{ producer, topic : String::from(topic), partitions, batch_size : 100, retries : 3,
+                   encoder : FbsEncoder::new(), symbols : HashMap::new(), pending : vec![] }
+    }
+
+    pub fn batch_size( mut self, n : usize ) -> KafkaSink<P> {
+        self.batch_size = n.max(1);
+        self
+    }
+
+    pub fn retries( mut self, n : usize ) -> KafkaSink<P> {
+        self.retries = n;
+        self
+    }
+
+    pub fn pending( &self ) -> usize {
+        self.pending.len()
+    }
+
+    fn symbol_for( &mut self, e : &MarketEvent ) -> String {
+        match *e {
+            MarketEvent::OrderAdded{ order_id, ref symbol, .. } => {
+                self.symbols.insert(order_id, symbol.clone());
+                symbol.clone()
+            },
+            MarketEvent::OrderReplaced{ order_id, new_order_id, .. } => {
+                let symbol = self.symbols.remove(&order_id).unwrap_or_default();
+                self.symbols.insert(new_order_id, symbol.clone());
+                symbol
+            },
+            MarketEvent::OrderDeleted{ order_id, .. } => self.symbols.remove(&order_id).unwrap_or_default(),
+            MarketEvent::OrderReduced{ order_id, .. } => self.symbols.get(&order_id).cloned().unwrap_or_default(),
+            MarketEvent::Trade{ ref symbol, order_id, .. } => match *symbol {
+                Some(ref s) => s.clone(),
+                None        => order_id.and_then(|id| self.symbols.get(&id).cloned()).unwrap_or_default()
+            },
+            MarketEvent::StatusChange{ ref symbol, .. } => symbol.clone().unwrap_or_default(),
+            MarketEvent::AuctionInfo{ ref symbol, .. } => symbol.clone(),
+            MarketEvent::TradeBroken{ .. } => String::new()
+        }
+    }
+
+    fn queue( &mut self, key : String, value : Vec<u8> ) -> Result<(), SinkError> {
+        self.pending.push(PendingRecord{ partition : partition_for(&key, self.partitions), key, value });
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn send_event( &mut self, e : &MarketEvent ) -> Result<(), SinkError> {
+        let key = self.symbol_for(e);
+        let value = self.encoder.event(e).to_vec();
+        self.queue(key, value)
+    }
+
+    pub fn send_book_delta( &mut self, timestamp : u64, symbol : &str, side : char, price : u64,
+                            volume : u32 ) -> Result<(), SinkError> {
+        let value = self.encoder.book_delta(timestamp, symbol, side, price, volume).to_vec();
+        self.queue(String::from(symbol), value)
+    }
+
+    pub fn flush( &mut self ) -> Result<(), SinkError> {
+        let mut attempts = 0;
+        while !self.pending.is_empty() {
+            let failed = self.producer.send_batch(&self.topic, &self.pending)?;
+            self.pending.retain(|r| failed.contains(&r.partition));
+            if !self.pending.is_empty() {
+                if attempts == self.retries {
+                    return Err(SinkError::Undelivered(self.pending.len()));
+                }
+                attempts += 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn into_inner( self ) -> P {
+        self.producer
+    }
+}
+
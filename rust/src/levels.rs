@@ -0,0 +1,334 @@
+
+// Where an OrderBook keeps the price levels of one side. The book doesn't care how, it only
+// looks levels up by price and walks them in price order, so the container can be picked to
+// suit the symbol:
+//
+//  - BTreeMap: the default, fine for anything.
+//  - SortedLevels: a Vec kept in price order. Inserting shifts the levels past it along, but
+//    lookups and walks stay in one allocation, which wins for books with few levels that mostly
+//    change size rather than come and go.
+//  - PriceLadder: a slot per tick between the lowest and highest level, so a lookup is an index.
+//    For tick-constrained symbols whose levels stay within a band; the space taken is the band
+//    over the tick, however few levels there are in it. Levels off the band go in a BTreeMap.
+//
+//   let book = OrderBook::with_store(None, PriceLadder::new(100));
+//
+// A book's bids and asks each get their own copy of the store it's made with.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::collections::btree_map;
+use std::collections::vec_deque;
+use std::fmt;
+use std::iter;
+use std::slice;
+
+use book::LevelData;
+
+pub trait LevelStore : Clone + fmt::Debug {
+    type Iter<'a> : DoubleEndedIterator<Item = &'a LevelData> where Self : 'a;
+
+    fn get( &self, price : u64 ) -> Option<&LevelData>;
+
+    fn get_mut( &mut self, price : u64 ) -> Option<&mut LevelData>;
+
+    // the level at `price`, made with `make` when there isn't one yet.
+    fn get_or_insert_with<F : FnOnce() -> LevelData>( &mut self, price : u64, make : F ) -> &mut LevelData;
+
+    fn remove( &mut self, price : u64 ) -> Option<LevelData>;
+
+    fn len( &self ) -> usize;
+
+    fn is_empty( &self ) -> bool {
+        self.len() == 0
+    }
+
+    fn clear( &mut self );
+
+    // lowest price first.
+    fn iter( &self ) -> Self::Iter<'_>;
+}
+
+impl LevelStore for BTreeMap<u64, LevelData> {
+    type Iter<'a> = btree_map::Values<'a, u64, LevelData>;
+
+    fn get( &self, price : u64 ) -> Option<&LevelData> {
+        BTreeMap::get(self, &price)
+    }
+
+    fn get_mut( &mut self, price : u64 ) -> Option<&mut LevelData> {
+        BTreeMap::get_mut(self, &price)
+    }
+
+    fn get_or_insert_with<F : FnOnce() -> LevelData>( &mut self, price : u64, make : F ) -> &mut LevelData {
+        self.entry(price).or_insert_with(make)
+    }
+
+    fn remove( &mut self, price : u64 ) -> Option<LevelData> {
+        BTreeMap::remove(self, &price)
+    }
+
+    fn len( &self ) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn clear( &mut self ) {
+        BTreeMap::clear(self)
+    }
+
+    fn iter( &self ) -> btree_map::Values<'_, u64, LevelData> {
+        self.values()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SortedLevels {
+    levels : Vec<LevelData>
+}
+
+impl SortedLevels {
+    pub fn new() -> SortedLevels {
+        SortedLevels::default()
+    }
+
+    pub fn with_capacity( levels : usize ) -> SortedLevels {
+        SortedLevels{ levels : Vec::with_capacity(levels) }
+    }
+
+    fn find( &self, price : u64 ) -> Result<usize, usize> {
+        self.levels.binary_search_by_key(&price, LevelData::price)
+    }
+}
+
+impl LevelStore for SortedLevels {
+    type Iter<'a> = slice::Iter<'a, LevelData>;
+
+    fn get( &self, price : u64 ) -> Option<&LevelData> {
+        self.find(price).ok().map(|i| &self.levels[i])
+    }
+
+    fn get_mut( &mut self, price : u64 ) -> Option<&mut LevelData> {
+        match self.find(price) {
+            Ok(i)  => Some(&mut self.levels[i]),
+            Err(_) => None
+        }
+    }
+
+    fn get_or_insert_with<F : FnOnce() -> LevelData>( &mut self, price : u64, make : F ) -> &mut LevelData {
+        let i = match self.find(price) {
+            Ok(i)  => i,
+            Err(i) => { self.levels.insert(i, make()); i }
+        };
+        &mut self.levels[i]
+    }
+
+    fn remove( &mut self, price : u64 ) -> Option<LevelData> {
+        self.find(price).ok().map(|i| self.levels.remove(i))
+    }
+
+    fn len( &self ) -> usize {
+        self.levels.len()
+    }
+
+    fn clear( &mut self ) {
+        self.levels.clear()
+    }
+
+    fn iter( &self ) -> slice::Iter<'_, LevelData> {
+        self.levels.iter()
+    }
+}
+
+type Slots<'a> = iter::FilterMap<vec_deque::Iter<'a, Option<LevelData>>, fn(&'a Option<LevelData>) -> Option<&'a LevelData>>;
+
+// How many ticks a ladder spans at most unless told otherwise.
+pub const LADDER_SLOTS : usize = 1 << 16;
+
+// A level's price should be a multiple of the tick, pick 1 for a symbol that trades in sub-penny
+// increments. The ladder grows to take a level past its ends, up to `max_slots` ticks from one
+// end to the other, and shrinks back as the levels at its ends go. Levels off the tick or past
+// that span (a stub quote far from the rest, say) are kept in a BTreeMap beside it instead.
+#[derive(Debug, Clone)]
+pub struct PriceLadder {
+    tick      : u64,
+    max_slots : usize,
+    // the price of slots[0]
+    low       : u64,
+    slots     : VecDeque<Option<LevelData>>,
+    len       : usize,
+    spill     : BTreeMap<u64, LevelData>
+}
+
+impl PriceLadder {
+    pub fn new( tick : u64 ) -> PriceLadder {
+        assert!(tick > 0, "a price ladder's tick can't be 0");
+        PriceLadder{ tick, max_slots : LADDER_SLOTS, low : 0, slots : VecDeque::new(), len : 0, spill : BTreeMap::new() }
+    }
+
+    pub fn max_slots( mut self, slots : usize ) -> Self {
+        self.max_slots = slots.max(1);
+        self
+    }
+
+    pub fn tick( &self ) -> u64 {
+        self.tick
+    }
+
+    fn index( &self, price : u64 ) -> Option<usize> {
+        if price < self.low || !(price - self.low).is_multiple_of(self.tick) {
+            return None;
+        }
+        let i = ((price - self.low) / self.tick) as usize;
+        if i < self.slots.len() { Some(i) } else { None }
+    }
+
+    // the slot for `price`, growing the ladder to reach it, None when it's off the tick or
+    // would stretch the ladder past its span.
+    fn slot( &mut self, price : u64 ) -> Option<usize> {
+        if !price.is_multiple_of(self.tick) {
+            return None;
+        }
+        if self.slots.is_empty() {
+            self.low = price;
+        }
+        let high = self.low + (self.slots.len().max(1) as u64 - 1) * self.tick;
+        let span = (high.max(price) - self.low.min(price)) / self.tick;
+        if span >= self.max_slots as u64 {
+            return None;
+        }
+        if price < self.low {
+            for _ in 0..(self.low - price) / self.tick {
+                self.slots.push_front(None);
+            }
+            self.low = price;
+        }
+        let i = ((price - self.low) / self.tick) as usize;
+        if i >= self.slots.len() {
+            self.slots.resize(i + 1, None);
+        }
+        Some(i)
+    }
+}
+
+// The ladder's levels and the spilled ones merged back into price order.
+pub struct LadderIter<'a> {
+    slots : Ends<Slots<'a>>,
+    spill : Ends<btree_map::Values<'a, u64, LevelData>>
+}
+
+// an iterator with its next item from either end looked at.
+struct Ends<I : DoubleEndedIterator> {
+    it    : I,
+    front : Option<I::Item>,
+    back  : Option<I::Item>
+}
+
+impl<'a, I : DoubleEndedIterator<Item = &'a LevelData>> Ends<I> {
+    fn new( it : I ) -> Ends<I> {
+        Ends{ it, front : None, back : None }
+    }
+
+    // the last item left is whichever end looked at it first.
+    fn first( &mut self ) -> Option<u64> {
+        if self.front.is_none() {
+            self.front = self.it.next().or_else(|| self.back.take());
+        }
+        self.front.map(LevelData::price)
+    }
+
+    fn last( &mut self ) -> Option<u64> {
+        if self.back.is_none() {
+            self.back = self.it.next_back().or_else(|| self.front.take());
+        }
+        self.back.map(LevelData::price)
+    }
+}
+
+impl<'a> Iterator for LadderIter<'a> {
+    type Item = &'a LevelData;
+
+    fn next( &mut self ) -> Option<&'a LevelData> {
+        match (self.slots.first(), self.spill.first()) {
+            (Some(a), Some(b)) if b < a => self.spill.front.take(),
+            (Some(_), _)               => self.slots.front.take(),
+            (None, _)                  => self.spill.front.take()
+        }
+    }
+}
+
+impl<'a> DoubleEndedIterator for LadderIter<'a> {
+    fn next_back( &mut self ) -> Option<&'a LevelData> {
+        match (self.slots.last(), self.spill.last()) {
+            (Some(a), Some(b)) if b > a => self.spill.back.take(),
+            (Some(_), _)               => self.slots.back.take(),
+            (None, _)                  => self.spill.back.take()
+        }
+    }
+}
+
+impl LevelStore for PriceLadder {
+    type Iter<'a> = LadderIter<'a>;
+
+    fn get( &self, price : u64 ) -> Option<&LevelData> {
+        match self.index(price).and_then(|i| self.slots[i].as_ref()) {
+            Some(level) => Some(level),
+            None        => self.spill.get(&price)
+        }
+    }
+
+    fn get_mut( &mut self, price : u64 ) -> Option<&mut LevelData> {
+        match self.index(price) {
+            Some(i) if self.slots[i].is_some() => self.slots[i].as_mut(),
+            _                                  => self.spill.get_mut(&price)
+        }
+    }
+
+    // a price already spilled stays there even once the ladder could reach it.
+    fn get_or_insert_with<F : FnOnce() -> LevelData>( &mut self, price : u64, make : F ) -> &mut LevelData {
+        let slot = match self.index(price) {
+            Some(i) if self.slots[i].is_some() => Some(i),
+            _ if self.spill.contains_key(&price) => None,
+            _                                  => self.slot(price)
+        };
+        let i = match slot {
+            Some(i) => i,
+            None    => { return self.spill.entry(price).or_insert_with(make); }
+        };
+        let slot = &mut self.slots[i];
+        if slot.is_none() {
+            self.len += 1;
+        }
+        slot.get_or_insert_with(make)
+    }
+
+    fn remove( &mut self, price : u64 ) -> Option<LevelData> {
+        let level = match self.index(price).and_then(|i| self.slots[i].take()) {
+            Some(level) => level,
+            None        => return self.spill.remove(&price)
+        };
+        self.len -= 1;
+        while let Some(&None) = self.slots.back() {
+            self.slots.pop_back();
+        }
+        while let Some(&None) = self.slots.front() {
+            self.slots.pop_front();
+            self.low += self.tick;
+        }
+        Some(level)
+    }
+
+    fn len( &self ) -> usize {
+        self.len + self.spill.len()
+    }
+
+    fn clear( &mut self ) {
+        self.slots.clear();
+        self.spill.clear();
+        self.len = 0;
+    }
+
+    fn iter( &self ) -> LadderIter<'_> {
+        let slots = self.slots.iter().filter_map(Option::as_ref as fn(&Option<LevelData>) -> Option<&LevelData>);
+        LadderIter{ slots : Ends::new(slots), spill : Ends::new(self.spill.values()) }
+    }
+}
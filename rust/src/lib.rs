@@ -3,9 +3,145 @@
 extern crate nom;
 
 extern crate crossbeam;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "parquet")]
+extern crate parquet;
+#[cfg(feature = "polars")]
+extern crate polars;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tokio")]
+extern crate tokio_stream;
+#[cfg(feature = "flatbuffers")]
+extern crate flatbuffers;
+#[cfg(feature = "kafka")]
+extern crate kafka;
+#[cfg(feature = "ws-server")]
+extern crate tungstenite;
+#[cfg(feature = "protobuf")]
+extern crate prost;
+#[cfg(feature = "node")]
+extern crate napi;
+#[cfg(feature = "node")]
+extern crate napi_derive;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "python")]
+extern crate core; // pyo3's macros expand to ::core paths, which this edition resolves from the crate root
+#[cfg(feature = "python")]
+extern crate pythonize;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wasm")]
+extern crate serde_wasm_bindgen;
+#[cfg(any(test, feature = "json"))]
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 #[cfg(test)]
 mod test;
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "tokio")]
+pub mod async_feed;
+pub mod auction;
+pub mod backtest;
+pub mod bars;
+pub mod bbo_recorder;
+pub mod binary;
+pub mod book;
+pub mod bounded;
+pub mod checkpoint;
+pub mod compressed;
+pub mod consistency;
+pub mod csv;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+pub mod decoder;
+#[cfg(feature = "europe")]
+pub mod europe;
+pub mod event;
+#[cfg(feature = "flatbuffers")]
+pub mod fbs;
+pub mod feed;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fix;
+pub mod iex;
+pub mod influx;
+pub mod itch;
+#[cfg(feature = "json")]
+pub mod jsonl;
+#[cfg(feature = "kafka")]
+pub mod kafka_sink;
+pub mod levels;
+pub mod lifecycle;
+pub mod lifetime;
+pub mod matching;
+pub mod mbp;
+#[cfg(feature = "mdp3")]
+pub mod mdp3;
+pub mod merge;
 pub mod messages;
+pub mod metrics;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod multicast;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod ofi;
+pub mod options;
 pub mod orderbook;
+pub mod ouch;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+#[cfg(feature = "parquet")]
+pub mod parquet_recorder;
+pub mod pcap;
+pub mod pipeline;
+#[cfg(feature = "protobuf")]
+pub mod proto;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod query;
+pub mod queue;
+pub mod recording;
+pub mod recovery;
+pub mod redis;
+pub mod replay;
+pub mod roundtrip;
+pub mod selfmatch;
+pub mod seqlock;
+pub mod session;
+pub mod sharded;
+pub mod slab;
+pub mod status;
+pub mod synthetic;
+pub mod throttle;
+pub mod trades;
+pub mod twap;
+pub mod vwap;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watchdog;
+#[cfg(feature = "ws-server")]
+pub mod ws_server;
+pub mod xdp;
@@ -0,0 +1,236 @@
+
+// Where one trading session ends and the next begins, and what's done about it. A session ends
+// on an EndOfSessionMsg, at the end of the feed (FeedEvent::SessionComplete), or when the
+// timestamps roll back past midnight on a feed that runs for days without saying so; the next
+// message starts another.
+//
+//   let mut sessions = SessionTracker::new()
+//       .rotate(RotatingFile::new("/data/bbo", "bbo-{}.csv"))
+//       .on_end(|summary, books| report(summary, books.trades()));
+//   for event in reader {
+//       for change in sessions.event(&event, &mut books)? { ... }
+//   }
+//
+// At the end of a session the on_end hooks see its summary and the books as they were left,
+// then the output file is flushed and the books reset (unless reset_books(false)). Each session
+// gets a file of its own, {} in the name being its number.
+
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use binary::TimestampComposer;
+use book::BookManager;
+use feed::FeedEvent;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    EndOfSession,
+    // a timestamp earlier than the last by more than the rollover allowance
+    Rollover,
+    FeedComplete
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub session  : u32,   // from 1
+    // nanoseconds past midnight
+    pub first    : u64,
+    pub last     : u64,
+    pub messages : u64,
+    pub symbols  : usize,
+    // still resting when it ended
+    pub orders   : usize
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    Started { session : u32, timestamp : u64 },
+    Ended   { summary : SessionSummary, reason : EndReason }
+}
+
+// A file per session, opened as each one starts.
+pub struct RotatingFile {
+    dir     : PathBuf,
+    pattern : String,
+    current : Option<(PathBuf, BufWriter<File>)>
+}
+
+impl RotatingFile {
+    pub fn new<P : AsRef<Path>>( dir : P, pattern : &str ) -> RotatingFile {
+        RotatingFile{ dir : dir.as_ref().to_path_buf(), pattern : String::from(pattern), current : None }
+    }
+
+    // closes the current file and starts session `session`'s.
+    pub fn rotate( &mut self, session : u32 ) -> io::Result<()> {
+        self.close()?;
+        let path = self.dir.join(self.pattern.replace("{}", &session.to_string()));
+        let file = File::create(&path)?;
+        self.current = Some((path, BufWriter::new(file)));
+        Ok(())
+    }
+
+    pub fn close( &mut self ) -> io::Result<()> {
+        match self.current.take() {
+            Some((_, mut w)) => w.flush(),
+            None             => Ok(())
+        }
+    }
+
+    pub fn path( &self ) -> Option<&Path> {
+        self.current.as_ref().map(|c| c.0.as_path())
+    }
+}
+
+// writes go to the current session's file, an error between sessions.
+impl Write for RotatingFile {
+    fn write( &mut self, buf : &[u8] ) -> io::Result<usize> {
+        match self.current {
+            Some((_, ref mut w)) => w.write(buf),
+            None                 => Err(io::Error::new(io::ErrorKind::NotConnected, "no session file open"))
+        }
+    }
+
+    fn flush( &mut self ) -> io::Result<()> {
+        match self.current {
+            Some((_, ref mut w)) => w.flush(),
+            None                 => Ok(())
+        }
+    }
+}
+
+type EndHook = Box<dyn FnMut(&SessionSummary, &mut BookManager) + Send>;
+
+struct Current {
+    first    : u64,
+    last     : u64,
+    messages : u64
+}
+
+pub struct SessionTracker {
+    reset    : bool,
+    rollover : u64,
+    hooks    : Vec<EndHook>,
+    output   : Option<RotatingFile>,
+    clock    : TimestampComposer,
+    current  : Option<Current>,
+    sessions : u32
+}
+
+impl Default for SessionTracker {
+    fn default() -> SessionTracker {
+        SessionTracker{ reset : true, rollover : 3_600_000_000_000, hooks : Vec::new(), output : None, clock : TimestampComposer::new(),
+                        current : None, sessions : 0 }
+    }
+}
+
+impl SessionTracker {
+    pub fn new() -> SessionTracker {
+        SessionTracker::default()
+    }
+
+    pub fn reset_books( mut self, reset : bool ) -> SessionTracker {
+        self.reset = reset;
+        self
+    }
+
+    // how far a timestamp can go back before it's taken for the next day, an hour by default.
+    // Feeds merged from several units go back a little all the time.
+    pub fn rollover( mut self, allowance : Duration ) -> SessionTracker {
+        self.rollover = allowance.as_nanos() as u64;
+        self
+    }
+
+    pub fn on_end<F>( mut self, hook : F ) -> SessionTracker where F : FnMut(&SessionSummary, &mut BookManager) + Send + 'static {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn rotate( mut self, file : RotatingFile ) -> SessionTracker {
+        self.output = Some(file);
+        self
+    }
+
+    // the current session's file, for whatever's written per session.
+    pub fn output( &mut self ) -> Option<&mut RotatingFile> {
+        self.output.as_mut()
+    }
+
+    // started so far, the current one included.
+    pub fn sessions( &self ) -> u32 {
+        self.sessions
+    }
+
+    pub fn in_session( &self ) -> bool {
+        self.current.is_some()
+    }
+
+    fn end( &mut self, reason : EndReason, books : &mut BookManager ) -> io::Result<Option<SessionEvent>> {
+        let current = match self.current.take() {
+            Some(c) => c,
+            None    => return Ok(None)
+        };
+        let summary = SessionSummary{ session : self.sessions, first : current.first, last : current.last, messages : current.messages,
+                                      symbols : books.len(), orders : books.orders() };
+        for hook in self.hooks.iter_mut() {
+            hook(&summary, books);
+        }
+        if let Some(ref mut output) = self.output {
+            output.close()?;
+        }
+        if self.reset {
+            books.reset();
+        }
+        Ok(Some(SessionEvent::Ended{ summary, reason }))
+    }
+
+    fn start( &mut self, timestamp : u64 ) -> io::Result<SessionEvent> {
+        self.sessions += 1;
+        self.current = Some(Current{ first : timestamp, last : timestamp, messages : 0 });
+        if let Some(ref mut output) = self.output {
+            output.rotate(self.sessions)?;
+        }
+        Ok(SessionEvent::Started{ session : self.sessions, timestamp })
+    }
+
+    // applies the message to the books, with whatever session changes it makes before and after.
+    pub fn apply( &mut self, msg : &BATSMessage, books : &mut BookManager ) -> io::Result<Vec<SessionEvent>> {
+        let mut events = Vec::new();
+        let timestamp = self.clock.compose(msg);
+        let ends = matches!(*msg, BATSMessage::EndOfSessionMsg(_));
+        // an end of session belongs to the session it ends, whatever its time says
+        let rolled = !ends && self.current.as_ref().is_some_and(|c| timestamp + self.rollover < c.last);
+        if rolled {
+            events.extend(self.end(EndReason::Rollover, books)?);
+        }
+        if self.current.is_none() {
+            events.push(self.start(timestamp)?);
+        }
+        books.apply(msg);
+        if let Some(ref mut c) = self.current {
+            c.last = c.last.max(timestamp);
+            c.messages += 1;
+        }
+        if ends {
+            events.extend(self.end(EndReason::EndOfSession, books)?);
+        }
+        Ok(events)
+    }
+
+    // the end of the feed, ending the session if one's still going.
+    pub fn finish( &mut self, books : &mut BookManager ) -> io::Result<Option<SessionEvent>> {
+        self.end(EndReason::FeedComplete, books)
+    }
+
+    pub fn event( &mut self, event : &FeedEvent, books : &mut BookManager ) -> io::Result<Vec<SessionEvent>> {
+        match *event {
+            FeedEvent::Message(ref msg) => self.apply(msg, books),
+            FeedEvent::SessionComplete  => Ok(self.finish(books)?.into_iter().collect())
+        }
+    }
+}
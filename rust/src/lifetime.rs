@@ -0,0 +1,165 @@
+
+// How long orders live and how much of them fills. Each order is followed from its add until
+// nothing is left of it: one that ends on an execution counts toward time-to-fill, one that
+// ends on a cancel toward time-to-cancel, and either way its filled share of what was added
+// goes into the fill ratios. Stats are kept per symbol and, for attributed adds, per
+// participant. Orders a symbol clear takes out aren't counted. Times are nanoseconds.
+
+use std::collections::HashMap;
+
+use binary::TimestampComposer;
+use messages::BATSMessage;
+
+// a distribution's summary, percentiles by nearest rank.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Summary {
+    pub count : usize,
+    pub mean  : f64,
+    pub min   : f64,
+    pub max   : f64,
+    pub p50   : f64,
+    pub p90   : f64,
+    pub p99   : f64
+}
+
+impl Summary {
+    fn of( values : &[f64] ) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let rank = |p : f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(Summary{ count : sorted.len(), mean : sorted.iter().sum::<f64>() / sorted.len() as f64, min : sorted[0],
+                      max : sorted[sorted.len() - 1], p50 : rank(0.5), p90 : rank(0.9), p99 : rank(0.99) })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Samples {
+    to_fill   : Vec<f64>,
+    to_cancel : Vec<f64>,
+    ratios    : Vec<f64>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LifetimeStats {
+    pub filled         : usize,
+    pub cancelled      : usize,
+    pub time_to_fill   : Option<Summary>,
+    pub time_to_cancel : Option<Summary>,
+    pub fill_ratio     : Option<Summary>
+}
+
+impl Samples {
+    fn stats( &self ) -> LifetimeStats {
+        LifetimeStats{ filled : self.to_fill.len(), cancelled : self.to_cancel.len(), time_to_fill : Summary::of(&self.to_fill),
+                       time_to_cancel : Summary::of(&self.to_cancel), fill_ratio : Summary::of(&self.ratios) }
+    }
+}
+
+#[derive(Debug)]
+struct Live {
+    symbol    : String,
+    part_id   : Option<String>,
+    added     : u64,
+    shares    : u32,
+    remaining : u32,
+    filled    : u32
+}
+
+#[derive(Debug, Default)]
+pub struct OrderLifetimes {
+    clock        : TimestampComposer,
+    live         : HashMap<u64, Live>,
+    symbols      : HashMap<String, Samples>,
+    participants : HashMap<String, Samples>
+}
+
+impl OrderLifetimes {
+    pub fn new() -> OrderLifetimes {
+        OrderLifetimes::default()
+    }
+
+    pub fn apply( &mut self, msg : &BATSMessage ) {
+        let now = self.clock.compose(msg);
+        match *msg {
+            BATSMessage::AddOrderMsg(ref m)       => self.add(m.order_id, &m.symbol, &m.part_id, m.shares, now),
+            BATSMessage::BinaryAddOrderMsg(ref m) => self.add(m.order_id, &m.symbol, &m.part_id, m.shares, now),
+            BATSMessage::OrderExecutedMsg(ref m) => self.take(m.order_id, m.shares, None, now, true),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => self.take(m.order_id, m.shares, None, now, true),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => self.take(m.order_id, m.shares, Some(m.remaining_shares), now, true),
+            BATSMessage::OrderCancelMsg(ref m) => self.take(m.order_id, m.shares, None, now, false),
+            BATSMessage::ReduceSizeMsg(ref m)  => self.take(m.order_id, m.shares, None, now, false),
+            BATSMessage::DeleteOrderMsg(ref m) => self.take(m.order_id, 0, Some(0), now, false),
+            BATSMessage::ModifyOrderMsg(ref m) => {
+                // the same order still, sized up it has more that could fill
+                if let Some(o) = self.live.get_mut(&m.order_id) {
+                    o.shares += m.shares.saturating_sub(o.remaining);
+                }
+                self.take(m.order_id, 0, Some(m.shares), now, false);
+            },
+            BATSMessage::SymbolClearMsg(ref m) => {
+                let symbol = m.symbol.trim_end();
+                self.live.retain(|_, o| o.symbol != symbol);
+            },
+            _ => {}
+        }
+    }
+
+    fn add( &mut self, order_id : u64, symbol : &str, part_id : &str, shares : u32, now : u64 ) {
+        // a reused id ends the order it was
+        self.end(order_id, now, false);
+        let part_id = Some(part_id.trim_end()).filter(|p| !p.is_empty()).map(String::from);
+        self.live.insert(order_id, Live{ symbol : String::from(symbol.trim_end()), part_id, added : now,
+                                         shares, remaining : shares, filled : 0 });
+    }
+
+    fn take( &mut self, order_id : u64, shares : u32, remaining : Option<u32>, now : u64, executed : bool ) {
+        let done = match self.live.get_mut(&order_id) {
+            Some(o) => {
+                o.remaining = remaining.unwrap_or_else(|| o.remaining.saturating_sub(shares));
+                if executed {
+                    o.filled += shares;
+                }
+                o.remaining == 0
+            },
+            None => false
+        };
+        if done {
+            self.end(order_id, now, executed);
+        }
+    }
+
+    fn end( &mut self, order_id : u64, now : u64, executed : bool ) {
+        let o = match self.live.remove(&order_id) {
+            Some(o) => o,
+            None    => return
+        };
+        let age = now.saturating_sub(o.added) as f64;
+        let ratio = f64::from(o.filled.min(o.shares)) / f64::from(o.shares.max(1));
+        let update = |s : &mut Samples| {
+            if executed { s.to_fill.push(age) } else { s.to_cancel.push(age) }
+            s.ratios.push(ratio);
+        };
+        update(self.symbols.entry(o.symbol).or_default());
+        if let Some(p) = o.part_id {
+            update(self.participants.entry(p).or_default());
+        }
+    }
+
+    pub fn symbol_stats( &self, symbol : &str ) -> Option<LifetimeStats> {
+        self.symbols.get(symbol.trim_end()).map(Samples::stats)
+    }
+
+    pub fn participant_stats( &self, part_id : &str ) -> Option<LifetimeStats> {
+        self.participants.get(part_id.trim_end()).map(Samples::stats)
+    }
+
+    // orders added and not over yet.
+    pub fn live( &self ) -> usize {
+        self.live.len()
+    }
+}
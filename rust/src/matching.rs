@@ -0,0 +1,418 @@
+
+// An exchange of our own for one symbol: orders go in, match against what's resting by price
+// then time, and come out as the PITCH messages the exchange would have sent, so whatever reads
+// the feed (a BookManager, a recorder, a strategy) can be run against a simulated market:
+//
+//   let mut engine = MatchingEngine::new("AAPL");
+//   let report = engine.submit(NewOrder::limit(1, Side::Bid, 100, 1831900), timestamp)?;
+//   for msg in report.messages.iter() { books.apply(msg); }
+//
+// An execution against a resting order is an OrderExecutedMsg, what's left of a limit order
+// rests as an AddOrderMsg, and a cancel is an OrderCancelMsg; each has already been applied to
+// the engine's book when it's handed back. The book can be seeded from a real feed with apply().
+// Timestamps are the messages' own, as the caller keeps them.
+//
+// Stop and stop-limit orders are held out of the book, unseen on the feed, until the engine
+// trades at or through their trigger price (at or above it for a buy, at or below for a sell);
+// then they go in as market and limit orders, at the time of the order whose trades set them
+// off. Their own trades can set off more. Stops go in the order the prices reach them, in time
+// priority among those the same trade reaches. A stop already through its trigger when it comes
+// in goes straight in.
+//
+// Levels are taken best price first; who gets what at each is the Allocation's call: PriceTime
+// (the default) fills the queue in order, ProRata shares the level out by size as futures
+// markets do.
+
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+
+use book::OrderBook;
+use book::Side;
+use messages::AddOrderMsg;
+use messages::BATSMessage;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    Limit { price : u64 },
+    // takes whatever's there, never rests
+    Market,
+    // a market order once it's triggered
+    Stop { trigger : u64 },
+    // a limit order at `price` once it's triggered
+    StopLimit { trigger : u64, price : u64 }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeInForce {
+    // what doesn't match rests
+    #[default]
+    Day,
+    // what doesn't match is cancelled
+    Ioc
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewOrder {
+    pub order_id      : u64,
+    pub side          : Side,
+    pub shares        : u32,
+    pub order_type    : OrderType,
+    pub time_in_force : TimeInForce
+}
+
+impl NewOrder {
+    pub fn limit( order_id : u64, side : Side, shares : u32, price : u64 ) -> NewOrder {
+        NewOrder{ order_id, side, shares, order_type : OrderType::Limit{ price }, time_in_force : TimeInForce::Day }
+    }
+
+    pub fn market( order_id : u64, side : Side, shares : u32 ) -> NewOrder {
+        NewOrder{ order_id, side, shares, order_type : OrderType::Market, time_in_force : TimeInForce::Ioc }
+    }
+
+    pub fn stop( order_id : u64, side : Side, shares : u32, trigger : u64 ) -> NewOrder {
+        NewOrder{ order_id, side, shares, order_type : OrderType::Stop{ trigger }, time_in_force : TimeInForce::Ioc }
+    }
+
+    pub fn stop_limit( order_id : u64, side : Side, shares : u32, trigger : u64, price : u64 ) -> NewOrder {
+        NewOrder{ order_id, side, shares, order_type : OrderType::StopLimit{ trigger, price }, time_in_force : TimeInForce::Day }
+    }
+
+    pub fn ioc( mut self ) -> NewOrder {
+        self.time_in_force = TimeInForce::Ioc;
+        self
+    }
+
+    // whether an order resting at `price` can be traded with.
+    fn crosses( &self, price : u64 ) -> bool {
+        match (self.order_type, self.side) {
+            (OrderType::Market, _)                     => true,
+            (OrderType::Limit{ price : p }, Side::Bid) => price <= p,
+            (OrderType::Limit{ price : p }, Side::Ask) => price >= p,
+            // not until they're triggered
+            (OrderType::Stop{ .. }, _) | (OrderType::StopLimit{ .. }, _) => false
+        }
+    }
+
+    fn trigger( &self ) -> Option<u64> {
+        match self.order_type {
+            OrderType::Stop{ trigger } | OrderType::StopLimit{ trigger, .. } => Some(trigger),
+            _                                                               => None
+        }
+    }
+
+    // whether a trade at `price` sets it off.
+    fn triggered_by( &self, price : u64 ) -> bool {
+        match (self.trigger(), self.side) {
+            (Some(trigger), Side::Bid) => price >= trigger,
+            (Some(trigger), Side::Ask) => price <= trigger,
+            (None, _)                  => false
+        }
+    }
+
+    // what a stop goes in as.
+    fn elect( self ) -> NewOrder {
+        match self.order_type {
+            OrderType::Stop{ .. }             => NewOrder{ order_type : OrderType::Market, time_in_force : TimeInForce::Ioc, ..self },
+            OrderType::StopLimit{ price, .. } => NewOrder{ order_type : OrderType::Limit{ price }, ..self },
+            _                                 => self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchError {
+    ZeroShares,
+    // an id already resting
+    DuplicateId(u64),
+    UnknownOrder(u64)
+}
+
+impl fmt::Display for MatchError {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        match *self {
+            MatchError::ZeroShares       => write!(f, "an order needs some shares"),
+            MatchError::DuplicateId(id)  => write!(f, "order {} is already resting", id),
+            MatchError::UnknownOrder(id) => write!(f, "order {} isn't resting", id)
+        }
+    }
+}
+
+impl error::Error for MatchError {}
+
+// One execution of the incoming order against a resting one, at the resting order's price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fill {
+    pub exec_id : u64,
+    pub resting : u64,
+    pub price   : u64,
+    pub shares  : u32
+}
+
+// How a stop order went once it was triggered.
+#[derive(Debug, Default)]
+pub struct Triggered {
+    pub order_id  : u64,
+    pub fills     : Vec<Fill>,
+    pub rested    : u32,
+    pub cancelled : u32
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub fills     : Vec<Fill>,
+    // what was left to rest on the book
+    pub rested    : u32,
+    // what was left and cancelled, of a market or IOC order
+    pub cancelled : u32,
+    // of a stop order, held until it's triggered
+    pub held      : u32,
+    // stops the order's trades set off, in the order they went in
+    pub triggered : Vec<Triggered>,
+    // in the order they happened, the triggered stops' too
+    pub messages  : Vec<BATSMessage>
+}
+
+impl Report {
+    pub fn filled( &self ) -> u32 {
+        self.fills.iter().map(|f| f.shares).sum()
+    }
+}
+
+// Shares out an incoming order's `shares` among the orders resting at one price, given as
+// (order id, shares) first in line first. Hands back (order id, shares) for each that gets any,
+// in the order to execute them, never more than `shares` in all nor more than an order has.
+pub trait Allocation : Send {
+    fn allocate( &mut self, queue : &[(u64, u32)], shares : u32 ) -> Vec<(u64, u32)>;
+}
+
+// First in line first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PriceTime;
+
+impl Allocation for PriceTime {
+    fn allocate( &mut self, queue : &[(u64, u32)], shares : u32 ) -> Vec<(u64, u32)> {
+        let mut left = shares;
+        let mut fills = Vec::new();
+        for &(id, resting) in queue {
+            if left == 0 {
+                break;
+            }
+            let take = resting.min(left);
+            fills.push((id, take));
+            left -= take;
+        }
+        fills
+    }
+}
+
+// Each order its share of the level by size, rounded down, with what rounding leaves over going
+// first in line first. An order whose share comes to less than `minimum` gets none of it, only
+// what's left over.
+#[derive(Debug, Clone, Copy)]
+pub struct ProRata {
+    minimum : u32
+}
+
+impl Default for ProRata {
+    fn default() -> ProRata {
+        ProRata{ minimum : 1 }
+    }
+}
+
+impl ProRata {
+    pub fn new() -> ProRata {
+        ProRata::default()
+    }
+
+    pub fn minimum( mut self, shares : u32 ) -> ProRata {
+        self.minimum = shares.max(1);
+        self
+    }
+}
+
+impl Allocation for ProRata {
+    fn allocate( &mut self, queue : &[(u64, u32)], shares : u32 ) -> Vec<(u64, u32)> {
+        let total : u64 = queue.iter().map(|&(_, s)| u64::from(s)).sum();
+        if u64::from(shares) >= total {
+            return PriceTime.allocate(queue, shares);
+        }
+        let mut given : Vec<u32> = queue.iter().map(|&(_, s)| {
+            let share = (u64::from(shares) * u64::from(s) / total) as u32;
+            if share >= self.minimum { share } else { 0 }
+        }).collect();
+        let mut left = shares - given.iter().sum::<u32>();
+        for (g, &(_, s)) in given.iter_mut().zip(queue.iter()) {
+            if left == 0 {
+                break;
+            }
+            let more = (s - *g).min(left);
+            *g += more;
+            left -= more;
+        }
+        queue.iter().zip(given).filter(|&(_, g)| g > 0).map(|(&(id, _), g)| (id, g)).collect()
+    }
+}
+
+pub struct MatchingEngine {
+    symbol     : String,
+    book       : OrderBook,
+    next_exec  : u64,
+    allocation : Box<dyn Allocation>,
+    // waiting for their triggers, first in first
+    stops      : Vec<NewOrder>,
+    last_trade : Option<u64>
+}
+
+impl MatchingEngine {
+    pub fn new( symbol : &str ) -> MatchingEngine {
+        MatchingEngine{ symbol : String::from(symbol.trim_end()), book : OrderBook::for_symbol(symbol), next_exec : 1,
+                        allocation : Box::new(PriceTime), stops : Vec::new(), last_trade : None }
+    }
+
+    pub fn allocation<A : Allocation + 'static>( mut self, allocation : A ) -> MatchingEngine {
+        self.allocation = Box::new(allocation);
+        self
+    }
+
+    // exec ids handed out from `first` on, eg. to keep clear of a seeding feed's.
+    pub fn exec_ids_from( mut self, first : u64 ) -> MatchingEngine {
+        self.next_exec = first;
+        self
+    }
+
+    pub fn book( &self ) -> &OrderBook {
+        &self.book
+    }
+
+    pub fn symbol( &self ) -> &str {
+        &self.symbol
+    }
+
+    // the stop orders not yet triggered, first in first.
+    pub fn stops( &self ) -> &[NewOrder] {
+        &self.stops
+    }
+
+    // the price of the engine's last trade.
+    pub fn last_trade( &self ) -> Option<u64> {
+        self.last_trade
+    }
+
+    // a feed message to the engine's book, eg. to start from the morning's real book.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        self.book.apply(msg)
+    }
+
+    fn publish( &mut self, msg : BATSMessage, messages : &mut Vec<BATSMessage> ) {
+        self.book.apply(&msg);
+        messages.push(msg);
+    }
+
+    pub fn submit( &mut self, order : NewOrder, timestamp : u32 ) -> Result<Report, MatchError> {
+        if order.shares == 0 {
+            return Err(MatchError::ZeroShares);
+        }
+        if self.book.contains(order.order_id) || self.stops.iter().any(|s| s.order_id == order.order_id) {
+            return Err(MatchError::DuplicateId(order.order_id));
+        }
+        let mut report = Report::default();
+        if order.trigger().is_some() && !self.last_trade.is_some_and(|p| order.triggered_by(p)) {
+            self.stops.push(order);
+            report.held = order.shares;
+            return Ok(report);
+        }
+        let done = self.execute(order.elect(), timestamp, &mut report.messages);
+        let prices = done.fills.iter().map(|f| f.price).collect();
+        report.fills = done.fills;
+        report.rested = done.rested;
+        report.cancelled = done.cancelled;
+        self.trigger(prices, timestamp, &mut report);
+        Ok(report)
+    }
+
+    // the stops trades at `prices` set off, and those their own trades do.
+    fn trigger( &mut self, mut prices : Vec<u64>, timestamp : u32, report : &mut Report ) {
+        let mut elected = VecDeque::new();
+        loop {
+            // by the first trade to reach each, then by when it came in
+            let mut hit = Vec::new();
+            let mut kept = Vec::new();
+            for (i, stop) in self.stops.drain(..).enumerate() {
+                match prices.iter().position(|&p| stop.triggered_by(p)) {
+                    Some(at) => hit.push((at, i, stop)),
+                    None     => kept.push(stop)
+                }
+            }
+            self.stops = kept;
+            hit.sort_by_key(|&(at, i, _)| (at, i));
+            elected.extend(hit.into_iter().map(|(_, _, stop)| stop));
+            let order = match elected.pop_front() {
+                Some(order) => order,
+                None        => break
+            };
+            let done = self.execute(order.elect(), timestamp, &mut report.messages);
+            prices = done.fills.iter().map(|f| f.price).collect();
+            report.triggered.push(done);
+        }
+    }
+
+    // matches a market or limit order, resting what's left of it if it should.
+    fn execute( &mut self, order : NewOrder, timestamp : u32, messages : &mut Vec<BATSMessage> ) -> Triggered {
+        let mut done = Triggered{ order_id : order.order_id, ..Triggered::default() };
+        let mut left = order.shares;
+        let against = match order.side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid
+        };
+        while left > 0 {
+            let (price, queue) = match self.book.levels(against).next() {
+                Some(level) if order.crosses(level.price()) => {
+                    let queue : Vec<(u64, u32)> = level.orders().filter_map(|id| self.book.order_shares(id).map(|s| (id, s))).collect();
+                    (level.price(), queue)
+                },
+                _ => break
+            };
+            let fills = self.allocation.allocate(&queue, left);
+            if fills.iter().all(|&(_, shares)| shares == 0) {
+                break;
+            }
+            for (resting, shares) in fills {
+                let exec_id = self.next_exec;
+                self.next_exec += 1;
+                let exec = OrderExecutedMsg{ timestamp, msg_type : 'E', order_id : resting, shares, exec_id };
+                self.publish(BATSMessage::OrderExecutedMsg(exec), messages);
+                done.fills.push(Fill{ exec_id, resting, price, shares });
+                self.last_trade = Some(price);
+                left -= shares;
+            }
+        }
+        match (order.order_type, order.time_in_force) {
+            (OrderType::Limit{ price }, TimeInForce::Day) if left > 0 => {
+                let add = AddOrderMsg{ timestamp, msg_type : 'A', order_id : order.order_id, side : order.side.to_char(), shares : left,
+                                       symbol : self.symbol.clone(), price, display : 'Y', part_id : String::new() };
+                self.publish(BATSMessage::AddOrderMsg(add), messages);
+                done.rested = left;
+            },
+            _ => done.cancelled = left
+        }
+        done
+    }
+
+    // takes what's left of a resting order off the book.
+    pub fn cancel( &mut self, order_id : u64, timestamp : u32 ) -> Result<BATSMessage, MatchError> {
+        let shares = self.book.order_shares(order_id).ok_or(MatchError::UnknownOrder(order_id))?;
+        let msg = BATSMessage::OrderCancelMsg(OrderCancelMsg{ timestamp, msg_type : 'X', order_id, shares });
+        self.book.apply(&msg);
+        Ok(msg)
+    }
+
+    // takes a stop order off before it's triggered; there's nothing on the feed to say so.
+    pub fn cancel_stop( &mut self, order_id : u64 ) -> Result<NewOrder, MatchError> {
+        let i = self.stops.iter().position(|s| s.order_id == order_id).ok_or(MatchError::UnknownOrder(order_id))?;
+        Ok(self.stops.remove(i))
+    }
+}
+
@@ -0,0 +1,128 @@
+
+// Market by price from the order by order book: after each update, the top `depth` levels a
+// side are compared with what they were and the difference goes out as deltas by level index,
+// 0 being the best. New inserts a level at the index and pushes the ones below down, Change
+// sets the shares there and Delete takes the level out, moving the ones below up. Applied in
+// order, the deltas give exactly the levels the book has, and a level pushed past the depth is
+// deleted explicitly.
+
+use std::collections::HashMap;
+
+use book::BookManager;
+use book::OrderBook;
+use book::Side;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MbpAction {
+    New,
+    Change,
+    Delete
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MbpDelta {
+    pub side   : Side,
+    pub index  : u16,
+    pub action : MbpAction,
+    pub price  : u64,
+    pub shares : u64   // 0 for a Delete
+}
+
+impl MbpDelta {
+    // for the receiving end: applies the delta to its side's (price, shares) levels.
+    pub fn apply( &self, levels : &mut Vec<(u64, u64)> ) {
+        let index = usize::from(self.index).min(levels.len());
+        match self.action {
+            MbpAction::New    => levels.insert(index, (self.price, self.shares)),
+            MbpAction::Change => if let Some(l) = levels.get_mut(index) { l.1 = self.shares },
+            MbpAction::Delete => if index < levels.len() { levels.remove(index); }
+        }
+    }
+}
+
+type Sides = (Vec<(u64, u64)>, Vec<(u64, u64)>);
+
+#[derive(Debug)]
+pub struct MarketByPrice {
+    depth  : usize,
+    levels : HashMap<String, Sides>
+}
+
+fn top( book : Option<&OrderBook>, depth : usize ) -> Sides {
+    match book {
+        Some(b) => (b.bids().take(depth).map(|l| (l.price(), l.shares())).collect(),
+                    b.asks().take(depth).map(|l| (l.price(), l.shares())).collect()),
+        None    => (vec![], vec![])
+    }
+}
+
+fn diff( side : Side, old : &[(u64, u64)], new : &[(u64, u64)], out : &mut Vec<MbpDelta> ) {
+    let better = |a : u64, b : u64| if side == Side::Bid { a > b } else { a < b };
+    let delta = |index : usize, action, (price, shares) : (u64, u64)| MbpDelta{ side, index : index as u16, action, price, shares };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        match (old.get(i), new.get(j)) {
+            (Some(&o), Some(&n)) if o.0 == n.0 => {
+                if o.1 != n.1 {
+                    out.push(delta(j, MbpAction::Change, n));
+                }
+                i += 1;
+                j += 1;
+            },
+            (Some(&o), Some(&n)) if better(n.0, o.0) => {
+                out.push(delta(j, MbpAction::New, n));
+                j += 1;
+            },
+            (Some(&o), _) => {
+                out.push(delta(j, MbpAction::Delete, (o.0, 0)));
+                i += 1;
+            },
+            (None, Some(&n)) => {
+                out.push(delta(j, MbpAction::New, n));
+                j += 1;
+            },
+            (None, None) => break
+        }
+    }
+}
+
+impl MarketByPrice {
+    pub fn new( depth : usize ) -> MarketByPrice {
+        MarketByPrice{ depth : depth.clamp(1, usize::from(u16::MAX)), levels : HashMap::new() }
+    }
+
+    // applies the message to the books and returns what it did to its book's levels. A
+    // message changes one book at most.
+    pub fn apply( &mut self, books : &mut BookManager, msg : &BATSMessage ) -> Vec<MbpDelta> {
+        let symbol = books.symbol_for(msg).map(String::from);
+        books.apply(msg);
+        match symbol {
+            Some(s) => self.update(&s, books.book(&s)),
+            None    => vec![]
+        }
+    }
+
+    // for books kept some other way: the deltas since the symbol's book was last seen here. A
+    // book that's gone (None) has all its levels deleted.
+    pub fn update( &mut self, symbol : &str, book : Option<&OrderBook> ) -> Vec<MbpDelta> {
+        let (bids, asks) = top(book, self.depth);
+        let mut deltas = vec![];
+        let old = self.levels.entry(String::from(symbol.trim_end())).or_default();
+        diff(Side::Bid, &old.0, &bids, &mut deltas);
+        diff(Side::Ask, &old.1, &asks, &mut deltas);
+        *old = (bids, asks);
+        deltas
+    }
+
+    // a side's (price, shares) as the deltas so far left them.
+    pub fn levels( &self, symbol : &str, side : Side ) -> &[(u64, u64)] {
+        match (self.levels.get(symbol.trim_end()), side) {
+            (Some((bids, _)), Side::Bid) => bids,
+            (Some((_, asks)), Side::Ask) => asks,
+            (None, _)                    => &[]
+        }
+    }
+}
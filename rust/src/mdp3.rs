@@ -0,0 +1,398 @@
+
+// Decoder for CME MDP 3.0, ie. Simple Binary Encoding over UDP. A packet has a 12 byte header
+// (sequence number + sending time) followed by messages, each led by a 2 byte size and the
+// 8 byte SBE header. Repeating groups carry their own block length, which we use to step
+// over fields added by newer schema versions. Prices are PRICE9, ie. 9 implied decimals.
+//
+// Only the book messages are decoded: MDIncrementalRefreshBook (46) and
+// SnapshotFullRefresh (52). Both are turned into PriceLevelUpdates which MdpBook applies.
+
+use nom;
+use nom::{le_u8, le_i8, le_u16, le_u32, le_i32, le_u64, le_i64};
+
+use std::result::Result;
+
+pub const INCREMENTAL_REFRESH_BOOK : u16 = 46;
+pub const SNAPSHOT_FULL_REFRESH    : u16 = 52;
+
+#[derive(Debug)]
+pub struct PacketHeader {
+    pub sequence     : u32,
+    pub sending_time : u64 // ns since epoch
+}
+
+#[derive(Debug)]
+pub struct MessageHeader {
+    pub block_length : u16,
+    pub template_id  : u16,
+    pub schema_id    : u16,
+    pub version      : u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateAction {
+    New,
+    Change,
+    Delete,
+    DeleteThru, // levels 1 through N
+    DeleteFrom, // levels N through the end of the book
+    Overlay,
+    Unknown(u8)
+}
+
+impl From<u8> for UpdateAction {
+    fn from(action : u8) -> UpdateAction {
+        match action {
+            0 => UpdateAction::New,
+            1 => UpdateAction::Change,
+            2 => UpdateAction::Delete,
+            3 => UpdateAction::DeleteThru,
+            4 => UpdateAction::DeleteFrom,
+            5 => UpdateAction::Overlay,
+            a => UpdateAction::Unknown(a)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BookEntry {
+    pub price       : i64,
+    pub size        : i32,
+    pub security_id : i32,
+    pub rpt_seq     : u32,
+    pub order_count : i32,
+    pub level       : u8,
+    pub action      : UpdateAction,
+    pub entry_type  : char // '0' bid, '1' offer, 'E'/'F' implied bid/offer
+}
+
+#[derive(Debug)]
+pub struct IncrementalRefreshBookMsg {
+    pub transact_time         : u64,
+    pub match_event_indicator : u8,
+    pub entries               : Vec<BookEntry>
+}
+
+#[derive(Debug)]
+pub struct SnapshotEntry {
+    pub price       : i64,
+    pub size        : i32,
+    pub order_count : i32,
+    pub level       : i8,
+    pub entry_type  : char
+}
+
+#[derive(Debug)]
+pub struct SnapshotFullRefreshMsg {
+    pub last_msg_seq_num_processed : u32,
+    pub tot_num_reports            : u32,
+    pub security_id                : i32,
+    pub rpt_seq                    : u32,
+    pub transact_time              : u64,
+    pub last_update_time           : u64,
+    pub trade_date                 : u16,
+    pub trading_status             : u8,
+    pub entries                    : Vec<SnapshotEntry>
+}
+
+#[derive(Debug)]
+pub enum MdpMessage {
+    IncrementalRefreshBook(IncrementalRefreshBookMsg),
+    SnapshotFullRefresh(SnapshotFullRefreshMsg),
+    Unknown(u16)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriceLevelUpdate {
+    pub security_id : i32,
+    pub side        : char, // 'B' or 'S'
+    pub level       : u8,   // 1 is the top of book
+    pub action      : UpdateAction,
+    pub price       : i64,
+    pub size        : i32,
+    pub order_count : i32
+}
+
+fn entry_side( entry_type : char ) -> Option<char> {
+    // implied prices are left out, they don't belong in the outright book.
+    match entry_type {
+        '0' => Some('B'),
+        '1' => Some('S'),
+        _   => None
+    }
+}
+
+impl MdpMessage {
+    // msg starts at the SBE header, ie. just after the 2 byte message size.
+    pub fn parse( msg : &[u8] ) -> Result<MdpMessage, nom::Err<&[u8]>> {
+        let (body, header) = parse_message_header(msg)?;
+        if body.len() < header.block_length as usize {
+            return Err(nom::Err::Incomplete(nom::Needed::Size(header.block_length as usize)));
+        }
+        let (block, groups) = body.split_at(header.block_length as usize);
+        match header.template_id {
+            INCREMENTAL_REFRESH_BOOK => {
+                let (_, (transact_time, match_event_indicator)) = parse_incremental_block(block)?;
+                let (_, entries) = parse_group(groups, parse_book_entry)?;
+                Ok(MdpMessage::IncrementalRefreshBook(IncrementalRefreshBookMsg{ transact_time,
+                                                                                 match_event_indicator,
+                                                                                 entries }))
+            },
+            SNAPSHOT_FULL_REFRESH => {
+                let (_, mut snapshot) = parse_snapshot_block(block)?;
+                let (_, entries) = parse_group(groups, parse_snapshot_entry)?;
+                snapshot.entries = entries;
+                Ok(MdpMessage::SnapshotFullRefresh(snapshot))
+            },
+            t => Ok(MdpMessage::Unknown(t))
+        }
+    }
+
+    pub fn price_level_updates( &self ) -> Vec<PriceLevelUpdate> {
+        match *self {
+            MdpMessage::IncrementalRefreshBook(ref m) => {
+                m.entries.iter().filter_map(|e| entry_side(e.entry_type).map(|side|
+                    PriceLevelUpdate{ security_id : e.security_id,
+                                      side,
+                                      level       : e.level,
+                                      action      : e.action,
+                                      price       : e.price,
+                                      size        : e.size,
+                                      order_count : e.order_count })).collect()
+            },
+            MdpMessage::SnapshotFullRefresh(ref m) => {
+                // a snapshot is a complete book, so it reads as overlays of every level.
+                m.entries.iter().filter(|e| e.level > 0).filter_map(|e| entry_side(e.entry_type).map(|side|
+                    PriceLevelUpdate{ security_id : m.security_id,
+                                      side,
+                                      level       : e.level as u8,
+                                      action      : UpdateAction::Overlay,
+                                      price       : e.price,
+                                      size        : e.size,
+                                      order_count : e.order_count })).collect()
+            },
+            MdpMessage::Unknown(_) => vec![]
+        }
+    }
+}
+
+pub type Packet<'a> = (PacketHeader, Vec<&'a [u8]>);
+
+pub fn split_packet( packet : &[u8] ) -> Result<Packet<'_>, nom::Err<&[u8]>> {
+    let (mut rest, header) = parse_packet_header(packet)?;
+    let mut msgs = Vec::new();
+    while !rest.is_empty() {
+        let (_, size) = le_u16(rest)?;
+        if size < 2 || rest.len() < size as usize {
+            return Err(nom::Err::Incomplete(nom::Needed::Size(size as usize)));
+        }
+        let (msg, remaining) = rest.split_at(size as usize);
+        msgs.push(&msg[2..]);
+        rest = remaining;
+    }
+    Ok((header, msgs))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MdpLevel {
+    pub price       : i64,
+    pub size        : i32,
+    pub order_count : i32
+}
+
+// market by price book for one instrument. CME books are level indexed with a fixed
+// depth (10 for most futures), inserts push the levels below down and deletes pull them up.
+#[derive(Debug)]
+pub struct MdpBook {
+    pub security_id : i32,
+    depth           : usize,
+    bids            : Vec<MdpLevel>,
+    offers          : Vec<MdpLevel>
+}
+
+impl MdpBook {
+    pub fn new( security_id : i32, depth : usize ) -> MdpBook {
+        MdpBook{ security_id, depth, bids : Vec::with_capacity(depth), offers : Vec::with_capacity(depth) }
+    }
+
+    pub fn apply( &mut self, update : &PriceLevelUpdate ) {
+        if update.security_id != self.security_id || update.level == 0 {
+            return;
+        }
+        let depth = self.depth;
+        let levels = if update.side == 'B' { &mut self.bids } else { &mut self.offers };
+        let idx = update.level as usize - 1;
+        let level = MdpLevel{ price : update.price, size : update.size, order_count : update.order_count };
+        match update.action {
+            UpdateAction::New => {
+                if idx <= levels.len() {
+                    levels.insert(idx, level);
+                    levels.truncate(depth);
+                }
+            },
+            UpdateAction::Change | UpdateAction::Overlay => {
+                if idx < levels.len() {
+                    levels[idx] = level;
+                } else if idx == levels.len() {
+                    levels.push(level);
+                }
+            },
+            UpdateAction::Delete => {
+                if idx < levels.len() {
+                    levels.remove(idx);
+                }
+            },
+            UpdateAction::DeleteThru => {
+                let n = (idx + 1).min(levels.len());
+                levels.drain(..n);
+            },
+            UpdateAction::DeleteFrom => {
+                levels.truncate(idx);
+            },
+            UpdateAction::Unknown(_) => {}
+        }
+    }
+
+    pub fn apply_snapshot( &mut self, msg : &MdpMessage ) {
+        if let MdpMessage::SnapshotFullRefresh(ref m) = *msg {
+            if m.security_id == self.security_id {
+                self.bids.clear();
+                self.offers.clear();
+                let mut updates = msg.price_level_updates();
+                updates.sort_by_key(|u| u.level);
+                for u in updates {
+                    self.apply(&u);
+                }
+            }
+        }
+    }
+
+    pub fn best_bid( &self ) -> Option<&MdpLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_offer( &self ) -> Option<&MdpLevel> {
+        self.offers.first()
+    }
+
+    pub fn bids( &self ) -> &[MdpLevel] {
+        &self.bids
+    }
+
+    pub fn offers( &self ) -> &[MdpLevel] {
+        &self.offers
+    }
+}
+
+// walks a repeating group: 2 byte block length + 1 byte count, then count entries.
+fn parse_group<T>( input : &[u8], entry : fn(&[u8]) -> nom::IResult<&[u8], T> )
+    -> nom::IResult<&[u8], Vec<T>> {
+    let (mut rest, (block_length, count)) = parse_group_size(input)?;
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if rest.len() < block_length as usize {
+            return Err(nom::Err::Incomplete(nom::Needed::Size(block_length as usize)));
+        }
+        let (block, remaining) = rest.split_at(block_length as usize);
+        let (_, e) = entry(block)?;
+        entries.push(e);
+        rest = remaining;
+    }
+    Ok((rest, entries))
+}
+
+named!(parse_packet_header<&[u8], PacketHeader>,
+    do_parse!(
+        _1 : le_u32 >>
+        _2 : le_u64 >>
+        (PacketHeader{ sequence : _1, sending_time : _2 })
+    )
+);
+
+named!(parse_message_header<&[u8], MessageHeader>,
+    do_parse!(
+        _1 : le_u16 >>
+        _2 : le_u16 >>
+        _3 : le_u16 >>
+        _4 : le_u16 >>
+        (MessageHeader{ block_length : _1, template_id : _2, schema_id : _3, version : _4 })
+    )
+);
+
+named!(parse_group_size<&[u8], (u16, u8)>,
+    do_parse!(
+        _1 : le_u16 >>
+        _2 : le_u8  >>
+        ((_1, _2))
+    )
+);
+
+named!(parse_incremental_block<&[u8], (u64, u8)>,
+    do_parse!(
+        _1 : le_u64 >>
+        _2 : le_u8  >>
+        ((_1, _2))
+    )
+);
+
+named!(parse_book_entry<&[u8], BookEntry>,
+    do_parse!(
+        _1 : le_i64                   >>
+        _2 : le_i32                   >>
+        _3 : le_i32                   >>
+        _4 : le_u32                   >>
+        _5 : le_i32                   >>
+        _6 : le_u8                    >>
+        _7 : le_u8                    >>
+        _8 : map!(le_u8, char::from)  >>
+        (BookEntry{ price       : _1,
+                    size        : _2,
+                    security_id : _3,
+                    rpt_seq     : _4,
+                    order_count : _5,
+                    level       : _6,
+                    action      : UpdateAction::from(_7),
+                    entry_type  : _8
+                  })
+    )
+);
+
+named!(parse_snapshot_block<&[u8], SnapshotFullRefreshMsg>,
+    do_parse!(
+        _1 : le_u32 >>
+        _2 : le_u32 >>
+        _3 : le_i32 >>
+        _4 : le_u32 >>
+        _5 : le_u64 >>
+        _6 : le_u64 >>
+        _7 : le_u16 >>
+        _8 : le_u8  >>
+        (SnapshotFullRefreshMsg{ last_msg_seq_num_processed : _1,
+                                 tot_num_reports            : _2,
+                                 security_id                : _3,
+                                 rpt_seq                    : _4,
+                                 transact_time              : _5,
+                                 last_update_time           : _6,
+                                 trade_date                 : _7,
+                                 trading_status             : _8,
+                                 entries                    : vec![]
+                               })
+    )
+);
+
+named!(parse_snapshot_entry<&[u8], SnapshotEntry>,
+    do_parse!(
+        _1 : le_i64                  >>
+        _2 : le_i32                  >>
+        _3 : le_i32                  >>
+        _4 : le_i8                   >>
+        _5 : take!(4)                >>
+        _6 : map!(le_u8, char::from) >>
+        (SnapshotEntry{ price       : _1,
+                        size        : _2,
+                        order_count : _3,
+                        level       : _4,
+                        entry_type  : _6
+                      })
+    )
+);
@@ -0,0 +1,125 @@
+
+// Several feeds as one, by time: each unit's capture, another venue's, or a day split over
+// files, merged into a single stream with the earliest message next whichever source it's in:
+//
+//   let files = ["unit1.txt.gz", "unit2.txt.gz"].iter().map(merge::messages).collect::<io::Result<_>>()?;
+//   for (source, timestamp, msg) in merge::merge(files) { ... }
+//
+// Each source is taken to be in time order on its own, as a unit is. Messages at the same time
+// come in source order, so the merge is the same every run. merge() keeps a TimestampComposer
+// per source for PITCH messages; merge_by() takes the times from whatever key it's given, eg.
+// MarketEvent::timestamp for events from other venues.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io;
+use std::path::Path;
+
+use binary::TimestampComposer;
+use compressed;
+use feed::FeedEvent;
+use messages::BATSMessage;
+
+// What times the items of each source, given the source's index. Closures taking the same
+// arguments are keys too.
+pub trait MergeKey<T> {
+    fn key( &mut self, source : usize, item : &T ) -> u64;
+}
+
+impl<T, F : FnMut(usize, &T) -> u64> MergeKey<T> for F {
+    fn key( &mut self, source : usize, item : &T ) -> u64 {
+        self(source, item)
+    }
+}
+
+// PITCH messages are timed by their source's own Time messages.
+pub struct Clocks(Vec<TimestampComposer>);
+
+impl MergeKey<BATSMessage> for Clocks {
+    fn key( &mut self, source : usize, msg : &BATSMessage ) -> u64 {
+        self.0[source].compose(msg)
+    }
+}
+
+struct Head<T> {
+    timestamp : u64,
+    source    : usize,
+    item      : T
+}
+
+// BinaryHeap's a max heap, so the earliest (then the lowest source) is the greatest.
+impl<T> Ord for Head<T> {
+    fn cmp( &self, other : &Head<T> ) -> Ordering {
+        (other.timestamp, other.source).cmp(&(self.timestamp, self.source))
+    }
+}
+
+impl<T> PartialOrd for Head<T> {
+    fn partial_cmp( &self, other : &Head<T> ) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Head<T> {
+    fn eq( &self, other : &Head<T> ) -> bool {
+        (self.timestamp, self.source) == (other.timestamp, other.source)
+    }
+}
+
+impl<T> Eq for Head<T> {}
+
+// Yields (source, timestamp, item), the source being its index in the list given.
+pub struct Merge<I : Iterator, K> {
+    sources : Vec<I>,
+    key     : K,
+    heads   : BinaryHeap<Head<I::Item>>,
+    started : bool
+}
+
+pub fn merge_by<I, K>( sources : Vec<I>, key : K ) -> Merge<I, K> where I : Iterator, K : MergeKey<I::Item> {
+    let heads = BinaryHeap::with_capacity(sources.len());
+    Merge{ sources, key, heads, started : false }
+}
+
+pub fn merge<I>( sources : Vec<I> ) -> Merge<I, Clocks> where I : Iterator<Item = BATSMessage> {
+    let clocks = Clocks(sources.iter().map(|_| TimestampComposer::new()).collect());
+    merge_by(sources, clocks)
+}
+
+// the messages of a capture, compressed or not, to go in a merge.
+pub fn messages<P : AsRef<Path>>( path : P ) -> io::Result<impl Iterator<Item = BATSMessage>> {
+    Ok(compressed::open_pitch(path)?.filter_map(|e| match e {
+        FeedEvent::Message(m) => Some(m),
+        _                     => None
+    }))
+}
+
+impl<I : Iterator, K : MergeKey<I::Item>> Merge<I, K> {
+    fn pull( &mut self, source : usize ) {
+        if let Some(item) = self.sources[source].next() {
+            let timestamp = self.key.key(source, &item);
+            self.heads.push(Head{ timestamp, source, item });
+        }
+    }
+
+    // the sources that still have items, once it's started.
+    pub fn active( &self ) -> usize {
+        self.heads.len()
+    }
+}
+
+impl<I : Iterator, K : MergeKey<I::Item>> Iterator for Merge<I, K> {
+    type Item = (usize, u64, I::Item);
+
+    fn next( &mut self ) -> Option<(usize, u64, I::Item)> {
+        if !self.started {
+            self.started = true;
+            for source in 0..self.sources.len() {
+                self.pull(source);
+            }
+        }
+        let head = self.heads.pop()?;
+        self.pull(head.source);
+        Some((head.source, head.timestamp, head.item))
+    }
+}
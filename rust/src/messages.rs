@@ -3,15 +3,36 @@ use nom;
 use nom::IResult;
 
 use std;
+use std::io;
+use std::io::Write;
 use std::str::FromStr;
 use std::result::Result;
-use std::convert::Into;
+
+use binary;
+use binary::BinaryAddOrderMsg;
+use binary::BinaryOrderExecutedMsg;
+use binary::BinaryTradeMsg;
+use binary::DeleteOrderMsg;
+use binary::ModifyOrderMsg;
+use binary::ReduceSizeMsg;
+use binary::UnitClearMsg;
+use binary::TimeMsg;
+use binary::TradeExpandedMsg;
+use binary::OrderExecutedAtPriceSizeMsg;
+use binary::CalculatedValueMsg;
+use binary::EndOfSessionMsg;
+use binary::LoginMsg;
+use binary::LoginResponseMsg;
+#[cfg(feature = "europe")]
+use europe::TradeReportMsg;
+use options;
+use options::SymbolMappingMsg;
 
 macro_rules! create_into_function {
     ($objname : ident) => (
-        impl Into<Option<$objname>> for BATSMessage {
-            fn into(self) -> Option<$objname> {
-                match self {
+        impl From<BATSMessage> for Option<$objname> {
+            fn from(msg : BATSMessage) -> Option<$objname> {
+                match msg {
                     BATSMessage::$objname(u) => Some(u), 
                     _ => None,
                 }
@@ -36,16 +57,62 @@ macro_rules! create_parse_impl {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
 pub enum BATSMessage { // For implementing message factory
+    #[cfg_attr(feature = "serde", serde(rename = "auction_summary"))]
     AuctionSummaryMsg(AuctionSummaryMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "add_order"))]
     AddOrderMsg(AddOrderMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "auction_update"))]
     AuctionUpdateMsg(AuctionUpdateMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "order_cancel"))]
     OrderCancelMsg(OrderCancelMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "order_executed"))]
     OrderExecutedMsg(OrderExecutedMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "retail_price_improve"))]
     RetailPriceImproveMsg(RetailPriceImproveMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "trade_break"))]
     TradeBreakMsg(TradeBreakMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "trade"))]
     TradeMsg(TradeMsg),
-    TradingStatusMsg(TradingStatusMsg)
+    #[cfg_attr(feature = "serde", serde(rename = "trading_status"))]
+    TradingStatusMsg(TradingStatusMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "symbol_clear"))]
+    SymbolClearMsg(SymbolClearMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "binary_add_order"))]
+    BinaryAddOrderMsg(BinaryAddOrderMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "binary_order_executed"))]
+    BinaryOrderExecutedMsg(BinaryOrderExecutedMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "reduce_size"))]
+    ReduceSizeMsg(ReduceSizeMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "modify_order"))]
+    ModifyOrderMsg(ModifyOrderMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "delete_order"))]
+    DeleteOrderMsg(DeleteOrderMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "binary_trade"))]
+    BinaryTradeMsg(BinaryTradeMsg),
+    #[cfg_attr(feature = "serde", serde(rename = "unit_clear"))]
+    UnitClearMsg(UnitClearMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "time"))]
+    TimeMsg(TimeMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "trade_expanded"))]
+    TradeExpandedMsg(TradeExpandedMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "order_executed_at_price_size"))]
+    OrderExecutedAtPriceSizeMsg(OrderExecutedAtPriceSizeMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "calculated_value"))]
+    CalculatedValueMsg(CalculatedValueMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "end_of_session"))]
+    EndOfSessionMsg(EndOfSessionMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "login"))]
+    LoginMsg(LoginMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "login_response"))]
+    LoginResponseMsg(LoginResponseMsg), 
+    #[cfg_attr(feature = "serde", serde(rename = "symbol_mapping"))]
+    SymbolMappingMsg(SymbolMappingMsg), 
+    #[cfg(feature = "europe")]
+    #[cfg_attr(feature = "serde", serde(rename = "trade_report"))]
+    TradeReportMsg(TradeReportMsg)
 }
 
 // use macros to generate into functions for all msgs
@@ -58,6 +125,24 @@ create_into_function!(RetailPriceImproveMsg);
 create_into_function!(TradeBreakMsg);
 create_into_function!(TradeMsg);
 create_into_function!(TradingStatusMsg);
+create_into_function!(SymbolClearMsg);
+create_into_function!(BinaryAddOrderMsg);
+create_into_function!(BinaryOrderExecutedMsg);
+create_into_function!(ReduceSizeMsg);
+create_into_function!(ModifyOrderMsg);
+create_into_function!(DeleteOrderMsg);
+create_into_function!(BinaryTradeMsg);
+create_into_function!(UnitClearMsg);
+create_into_function!(TimeMsg);
+create_into_function!(TradeExpandedMsg);
+create_into_function!(OrderExecutedAtPriceSizeMsg);
+create_into_function!(CalculatedValueMsg);
+create_into_function!(EndOfSessionMsg);
+create_into_function!(LoginMsg);
+create_into_function!(LoginResponseMsg);
+create_into_function!(SymbolMappingMsg);
+#[cfg(feature = "europe")]
+create_into_function!(TradeReportMsg);
 
 // use macros to generate impl parse_msg functions for all msgs
 create_parse_impl!(AddOrderMsg, parse_add_order);
@@ -69,31 +154,84 @@ create_parse_impl!(RetailPriceImproveMsg, parse_retail_price_improve);
 create_parse_impl!(TradeBreakMsg, parse_trade_break);
 create_parse_impl!(TradeMsg, parse_trade);
 create_parse_impl!(TradingStatusMsg, parse_trading_status);
+create_parse_impl!(SymbolClearMsg, parse_symbol_clear);
 
 pub struct BATSMsgFactory {} // this coupled with impl below makes it like a 
                              // factory method exposed via a static class method.
 impl BATSMsgFactory {
     pub fn parse( msg : &str ) -> BATSMessage {
-        let code = &msg[8..9];
+        match BATSMsgFactory::try_parse(msg) {
+            Some(obj) => obj,
+            None      => unimplemented!()
+        }
+    }
+
+    // same as parse but gives None for anything that isn't a well formed message we decode,
+    // for callers that can't have a panic, eg. the python and C bindings.
+    pub fn try_parse( msg : &str ) -> Option<BATSMessage> {
+        let code = msg.get(8..9)?;
         let obj = match code {
-            "A" => BATSMessage::AddOrderMsg( AddOrderMsg::parse_msg(msg).unwrap() ), 
-            "d" => BATSMessage::AddOrderMsg( AddOrderMsg::parse_msg(msg).unwrap() ),
-            "J" => BATSMessage::AuctionSummaryMsg( AuctionSummaryMsg::parse_msg(msg).unwrap() ),
-            "I" => BATSMessage::AuctionUpdateMsg( AuctionUpdateMsg::parse_msg(msg).unwrap() ),
-            "X" => BATSMessage::OrderCancelMsg( OrderCancelMsg::parse_msg(msg).unwrap() ),
-            "E" => BATSMessage::OrderExecutedMsg( OrderExecutedMsg::parse_msg(msg).unwrap() ),
-            "R" => BATSMessage::RetailPriceImproveMsg( RetailPriceImproveMsg::parse_msg(msg).unwrap() ),
-            "B" => BATSMessage::TradeBreakMsg( TradeBreakMsg::parse_msg(msg).unwrap() ),
-            "P" => BATSMessage::TradeMsg( TradeMsg::parse_msg(msg).unwrap() ),
-            "r" => BATSMessage::TradeMsg( TradeMsg::parse_msg(msg).unwrap() ),
-            "H" => BATSMessage::TradingStatusMsg( TradingStatusMsg::parse_msg(msg).unwrap() ),
-            &_ => unimplemented!(),
+            "A" => BATSMessage::AddOrderMsg( AddOrderMsg::parse_msg(msg).ok()? ), 
+            "d" => BATSMessage::AddOrderMsg( AddOrderMsg::parse_msg(msg).ok()? ),
+            "J" => BATSMessage::AuctionSummaryMsg( AuctionSummaryMsg::parse_msg(msg).ok()? ),
+            "I" => BATSMessage::AuctionUpdateMsg( AuctionUpdateMsg::parse_msg(msg).ok()? ),
+            "X" => BATSMessage::OrderCancelMsg( OrderCancelMsg::parse_msg(msg).ok()? ),
+            "E" => BATSMessage::OrderExecutedMsg( OrderExecutedMsg::parse_msg(msg).ok()? ),
+            "R" => BATSMessage::RetailPriceImproveMsg( RetailPriceImproveMsg::parse_msg(msg).ok()? ),
+            "B" => BATSMessage::TradeBreakMsg( TradeBreakMsg::parse_msg(msg).ok()? ),
+            "P" => BATSMessage::TradeMsg( TradeMsg::parse_msg(msg).ok()? ),
+            "r" => BATSMessage::TradeMsg( TradeMsg::parse_msg(msg).ok()? ),
+            "H" => BATSMessage::TradingStatusMsg( TradingStatusMsg::parse_msg(msg).ok()? ),
+            "s" => BATSMessage::SymbolClearMsg( SymbolClearMsg::parse_msg(msg).ok()? ),
+            &_ => return None,
         };
-        obj
+        Some(obj)
+    }
+
+    pub fn parse_binary( msg : &[u8] ) -> BATSMessage {
+        match BATSMsgFactory::try_parse_binary(msg).unwrap() {
+            Some(obj) => obj,
+            None      => unimplemented!()
+        }
+    }
+
+    // same as parse_binary but hands back parse errors, and None for message types
+    // we don't decode, instead of panicking.
+    pub fn try_parse_binary( msg : &[u8] ) -> Result<Option<BATSMessage>, nom::Err<&[u8]>> {
+        let code = match msg.get(1) {
+            Some(&c) => c,
+            None     => return Err(nom::Err::Incomplete(nom::Needed::Size(2)))
+        };
+        let obj = match code {
+            binary::ADD_ORDER_LONG     => BATSMessage::BinaryAddOrderMsg( BinaryAddOrderMsg::parse_binary(msg)? ),
+            binary::ADD_ORDER_SHORT    => BATSMessage::BinaryAddOrderMsg( BinaryAddOrderMsg::parse_binary(msg)? ),
+            binary::ADD_ORDER_EXPANDED => BATSMessage::BinaryAddOrderMsg( BinaryAddOrderMsg::parse_binary(msg)? ),
+            binary::ORDER_EXECUTED     => BATSMessage::BinaryOrderExecutedMsg( BinaryOrderExecutedMsg::parse_binary(msg)? ),
+            binary::MODIFY_ORDER_LONG  => BATSMessage::ModifyOrderMsg( ModifyOrderMsg::parse_binary(msg)? ),
+            binary::MODIFY_ORDER_SHORT => BATSMessage::ModifyOrderMsg( ModifyOrderMsg::parse_binary(msg)? ),
+            binary::DELETE_ORDER       => BATSMessage::DeleteOrderMsg( DeleteOrderMsg::parse_binary(msg)? ),
+            binary::TRADE_LONG         => BATSMessage::BinaryTradeMsg( BinaryTradeMsg::parse_binary(msg)? ),
+            binary::TRADE_SHORT        => BATSMessage::BinaryTradeMsg( BinaryTradeMsg::parse_binary(msg)? ),
+            binary::REDUCE_SIZE_LONG  => BATSMessage::ReduceSizeMsg( ReduceSizeMsg::parse_binary(msg)? ),
+            binary::REDUCE_SIZE_SHORT => BATSMessage::ReduceSizeMsg( ReduceSizeMsg::parse_binary(msg)? ),
+            binary::UNIT_CLEAR        => BATSMessage::UnitClearMsg( UnitClearMsg::parse_binary(msg)? ),
+            binary::TIME              => BATSMessage::TimeMsg( TimeMsg::parse_binary(msg)? ),
+            binary::TRADE_EXPANDED    => BATSMessage::TradeExpandedMsg( TradeExpandedMsg::parse_binary(msg)? ),
+            binary::ORDER_EXECUTED_AT_PRICE_SIZE => 
+                BATSMessage::OrderExecutedAtPriceSizeMsg( OrderExecutedAtPriceSizeMsg::parse_binary(msg)? ),
+            binary::CALCULATED_VALUE  => BATSMessage::CalculatedValueMsg( CalculatedValueMsg::parse_binary(msg)? ),
+            binary::END_OF_SESSION    => BATSMessage::EndOfSessionMsg( EndOfSessionMsg::parse_binary(msg)? ),
+            binary::LOGIN             => BATSMessage::LoginMsg( LoginMsg::parse_binary(msg)? ),
+            binary::LOGIN_RESPONSE    => BATSMessage::LoginResponseMsg( LoginResponseMsg::parse_binary(msg)? ),
+            options::SYMBOL_MAPPING   => BATSMessage::SymbolMappingMsg( SymbolMappingMsg::parse_binary(msg)? ),
+            _ => return Ok(None),
+        };
+        Ok(Some(obj))
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AuctionSummaryMsg {
     pub timestamp    : u32, 
     pub msg_type     : char,
@@ -104,6 +242,7 @@ pub struct AuctionSummaryMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AddOrderMsg {
     pub timestamp : u32, 
     pub msg_type  : char,
@@ -117,19 +256,23 @@ pub struct AddOrderMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct AuctionUpdateMsg {
     pub timestamp          : u32, 
     pub msg_type           : char,
     pub symbol             : String,
     pub auction_type       : char,
     pub reference_price    : u64,
+    #[cfg_attr(feature = "serde", serde(rename = "buy_shares"))]
     pub buyshares          : u32, 
+    #[cfg_attr(feature = "serde", serde(rename = "sell_shares"))]
     pub sellshares         : u32, 
     pub indicative_price   : u64, 
     pub auction_only_price : u64
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OrderCancelMsg {
     pub timestamp : u32, 
     pub msg_type  : char,
@@ -138,6 +281,7 @@ pub struct OrderCancelMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OrderExecutedMsg {
     pub timestamp : u32, 
     pub msg_type  : char,
@@ -147,6 +291,7 @@ pub struct OrderExecutedMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RetailPriceImproveMsg {
     pub timestamp            : u32, 
     pub msg_type             : char,
@@ -155,6 +300,7 @@ pub struct RetailPriceImproveMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TradeBreakMsg {
     pub timestamp : u32, 
     pub msg_type  : char,
@@ -162,6 +308,7 @@ pub struct TradeBreakMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TradeMsg {
     pub timestamp : u32, 
     pub msg_type  : char,
@@ -174,6 +321,7 @@ pub struct TradeMsg {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TradingStatusMsg {
     pub timestamp      : u32, 
     pub msg_type       : char,
@@ -181,20 +329,309 @@ pub struct TradingStatusMsg {
     pub halt_status    : char, 
     pub reg_sho_action : u8, 
     pub reserved1      : char, 
-    pub reserved2      : char 
+    pub reserved2      : char, 
+    pub halt_reason    : Option<HaltReason>, // only on the expanded format
+    pub halt_flag      : Option<char>        // only on the expanded format
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "String", from = "String"))]
+pub enum HaltReason {
+    NewsPending,               // T1
+    NewsReleased,              // T2
+    SingleStockPause,          // T5
+    ExtraordinaryActivity,     // T6
+    AdditionalInfoRequested,   // T12
+    SecSuspension,             // H10
+    RegulatoryConcern,         // H11
+    OperationsHalt,            // O1
+    IpoNotYetTrading,          // IPO1
+    VolatilityPause,           // LUDP
+    MarketWideCircuitBreaker(u8), // MWC1, MWC2, MWC3
+    Other(String)
+}
+
+impl From<String> for HaltReason {
+    fn from( code : String ) -> HaltReason {
+        HaltReason::from_code(&code)
+    }
+}
+
+impl From<HaltReason> for String {
+    fn from( reason : HaltReason ) -> String {
+        reason.code()
+    }
+}
+
+impl HaltReason {
+    pub fn from_code( code : &str ) -> HaltReason {
+        match code.trim_end() {
+            "T1"   => HaltReason::NewsPending,
+            "T2"   => HaltReason::NewsReleased,
+            "T5"   => HaltReason::SingleStockPause,
+            "T6"   => HaltReason::ExtraordinaryActivity,
+            "T12"  => HaltReason::AdditionalInfoRequested,
+            "H10"  => HaltReason::SecSuspension,
+            "H11"  => HaltReason::RegulatoryConcern,
+            "O1"   => HaltReason::OperationsHalt,
+            "IPO1" => HaltReason::IpoNotYetTrading,
+            "LUDP" => HaltReason::VolatilityPause,
+            "MWC1" => HaltReason::MarketWideCircuitBreaker(1),
+            "MWC2" => HaltReason::MarketWideCircuitBreaker(2),
+            "MWC3" => HaltReason::MarketWideCircuitBreaker(3),
+            other  => HaltReason::Other(String::from(other))
+        }
+    }
+
+    pub fn code( &self ) -> String {
+        match *self {
+            HaltReason::NewsPending                 => String::from("T1"),
+            HaltReason::NewsReleased                => String::from("T2"),
+            HaltReason::SingleStockPause            => String::from("T5"),
+            HaltReason::ExtraordinaryActivity       => String::from("T6"),
+            HaltReason::AdditionalInfoRequested     => String::from("T12"),
+            HaltReason::SecSuspension               => String::from("H10"),
+            HaltReason::RegulatoryConcern           => String::from("H11"),
+            HaltReason::OperationsHalt              => String::from("O1"),
+            HaltReason::IpoNotYetTrading            => String::from("IPO1"),
+            HaltReason::VolatilityPause             => String::from("LUDP"),
+            HaltReason::MarketWideCircuitBreaker(l) => format!("MWC{}", l),
+            HaltReason::Other(ref code)             => code.clone()
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SymbolClearMsg { // all resting orders for the symbol should be dropped
+    pub timestamp : u32, 
+    pub msg_type  : char,
+    pub symbol    : String
+}
+
+// Encoders back to the text format, field widths match the parsers above so the output
+// parses back to the same message. write_pitch writes one line, ie. what PitchReader reads.
+macro_rules! create_write_impl {
+    ($objname : ident) => (
+        impl $objname {
+            pub fn write_pitch<W : Write>( &self, w : &mut W ) -> io::Result<()> {
+                writeln!(w, "{}", self.to_pitch_string())
+            }
+        }
+    )
+}
+
+create_write_impl!(AddOrderMsg);
+create_write_impl!(AuctionSummaryMsg);
+create_write_impl!(AuctionUpdateMsg);
+create_write_impl!(OrderCancelMsg);
+create_write_impl!(OrderExecutedMsg);
+create_write_impl!(RetailPriceImproveMsg);
+create_write_impl!(TradeBreakMsg);
+create_write_impl!(TradeMsg);
+create_write_impl!(TradingStatusMsg);
+create_write_impl!(SymbolClearMsg);
+
+pub(crate) fn to_base36( mut value : u64 ) -> String {
+    let digits = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut buf = [b'0'; 12];
+    for i in (0..12).rev() {
+        buf[i] = digits[(value % 36) as usize];
+        value /= 36;
+    }
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+// a price with its 4 implied decimals as a decimal number, without trailing zeros.
+pub(crate) fn format_price( p : u64 ) -> String {
+    let frac = format!("{:04}", p % 10000);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        format!("{}", p / 10000)
+    } else {
+        format!("{}.{}", p / 10000, frac)
+    }
+}
+
+// alphanumeric fields are left justified and space padded, longer values are cut.
+fn pad( field : &str, width : usize ) -> String {
+    format!("{:<width$.width$}", field, width = width)
+}
+
+impl AuctionSummaryMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}J{}{}{:010}{:010}", self.timestamp, pad(&self.symbol, 8), self.auction_type,
+                self.price, self.shares)
+    }
 }
 
-fn from_base36(input: &str) -> Result<u64, std::num::ParseIntError> {
+impl AddOrderMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        let part_id = if self.msg_type == 'd' { pad(&self.part_id, 4) } else { String::new() };
+        format!("{:08}{}{}{}{:06}{}{:010}{}{}", self.timestamp, self.msg_type, to_base36(self.order_id),
+                self.side, self.shares, pad(&self.symbol, 6), self.price, self.display, part_id)
+    }
+}
+
+impl AuctionUpdateMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}I{}{}{:010}{:010}{:010}{:010}{:010}", self.timestamp, pad(&self.symbol, 8),
+                self.auction_type, self.reference_price, self.buyshares, self.sellshares,
+                self.indicative_price, self.auction_only_price)
+    }
+}
+
+impl OrderCancelMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}X{}{:06}", self.timestamp, to_base36(self.order_id), self.shares)
+    }
+}
+
+impl OrderExecutedMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}E{}{:06}{}", self.timestamp, to_base36(self.order_id), self.shares, to_base36(self.exec_id))
+    }
+}
+
+impl RetailPriceImproveMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}R{}{}", self.timestamp, pad(&self.symbol, 8), self.retail_price_improve)
+    }
+}
+
+impl TradeBreakMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}B{}", self.timestamp, to_base36(self.exec_id))
+    }
+}
+
+impl TradeMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        let width = if self.msg_type == 'P' { 6 } else { 8 };
+        format!("{:08}{}{}{}{:06}{}{:010}{}", self.timestamp, self.msg_type, to_base36(self.order_id),
+                self.side, self.shares, pad(&self.symbol, width), self.price, to_base36(self.exec_id))
+    }
+}
+
+impl TradingStatusMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        let mut s = format!("{:08}H{}{}{}{}{}", self.timestamp, pad(&self.symbol, 8), self.halt_status,
+                            self.reg_sho_action, self.reserved1, self.reserved2);
+        if let Some(ref reason) = self.halt_reason {
+            s.push_str(&pad(&reason.code(), 4));
+            s.push(self.halt_flag.unwrap_or(' '));
+        }
+        s
+    }
+}
+
+impl SymbolClearMsg {
+    pub fn to_pitch_string( &self ) -> String {
+        format!("{:08}s{}", self.timestamp, pad(&self.symbol, 8))
+    }
+}
+
+impl BATSMessage {
+    // None for the binary only messages.
+    pub fn to_pitch_string( &self ) -> Option<String> {
+        match *self {
+            BATSMessage::AuctionSummaryMsg(ref m)     => Some(m.to_pitch_string()),
+            BATSMessage::AddOrderMsg(ref m)           => Some(m.to_pitch_string()),
+            BATSMessage::AuctionUpdateMsg(ref m)      => Some(m.to_pitch_string()),
+            BATSMessage::OrderCancelMsg(ref m)        => Some(m.to_pitch_string()),
+            BATSMessage::OrderExecutedMsg(ref m)      => Some(m.to_pitch_string()),
+            BATSMessage::RetailPriceImproveMsg(ref m) => Some(m.to_pitch_string()),
+            BATSMessage::TradeBreakMsg(ref m)         => Some(m.to_pitch_string()),
+            BATSMessage::TradeMsg(ref m)              => Some(m.to_pitch_string()),
+            BATSMessage::TradingStatusMsg(ref m)      => Some(m.to_pitch_string()),
+            BATSMessage::SymbolClearMsg(ref m)        => Some(m.to_pitch_string()),
+            _ => None
+        }
+    }
+
+    // the binary form, for the messages that have one.
+    pub fn to_binary( &self ) -> Option<Vec<u8>> {
+        match *self {
+            BATSMessage::BinaryAddOrderMsg(ref m)           => Some(m.to_bytes()),
+            BATSMessage::BinaryOrderExecutedMsg(ref m)      => Some(m.to_bytes()),
+            BATSMessage::ReduceSizeMsg(ref m)               => Some(m.to_bytes()),
+            BATSMessage::ModifyOrderMsg(ref m)              => Some(m.to_bytes()),
+            BATSMessage::DeleteOrderMsg(ref m)              => Some(m.to_bytes()),
+            BATSMessage::BinaryTradeMsg(ref m)              => Some(m.to_bytes()),
+            BATSMessage::UnitClearMsg(ref m)                => Some(m.to_bytes()),
+            BATSMessage::TimeMsg(ref m)                     => Some(m.to_bytes()),
+            BATSMessage::TradeExpandedMsg(ref m)            => Some(m.to_bytes()),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => Some(m.to_bytes()),
+            BATSMessage::CalculatedValueMsg(ref m)          => Some(m.to_bytes()),
+            BATSMessage::EndOfSessionMsg(ref m)             => Some(m.to_bytes()),
+            BATSMessage::LoginMsg(ref m)                    => Some(m.to_bytes()),
+            BATSMessage::LoginResponseMsg(ref m)            => Some(m.to_bytes()),
+            BATSMessage::SymbolMappingMsg(ref m)            => Some(m.to_bytes()),
+            _ => None
+        }
+    }
+
+    // snake case name of the message type, same as the serde tag.
+    pub fn type_name( &self ) -> &'static str {
+        match *self {
+            BATSMessage::AuctionSummaryMsg(_)           => "auction_summary",
+            BATSMessage::AddOrderMsg(_)                 => "add_order",
+            BATSMessage::AuctionUpdateMsg(_)            => "auction_update",
+            BATSMessage::OrderCancelMsg(_)              => "order_cancel",
+            BATSMessage::OrderExecutedMsg(_)            => "order_executed",
+            BATSMessage::RetailPriceImproveMsg(_)       => "retail_price_improve",
+            BATSMessage::TradeBreakMsg(_)               => "trade_break",
+            BATSMessage::TradeMsg(_)                    => "trade",
+            BATSMessage::TradingStatusMsg(_)            => "trading_status",
+            BATSMessage::SymbolClearMsg(_)              => "symbol_clear",
+            BATSMessage::BinaryAddOrderMsg(_)           => "binary_add_order",
+            BATSMessage::BinaryOrderExecutedMsg(_)      => "binary_order_executed",
+            BATSMessage::ReduceSizeMsg(_)               => "reduce_size",
+            BATSMessage::ModifyOrderMsg(_)              => "modify_order",
+            BATSMessage::DeleteOrderMsg(_)              => "delete_order",
+            BATSMessage::BinaryTradeMsg(_)              => "binary_trade",
+            BATSMessage::UnitClearMsg(_)                => "unit_clear",
+            BATSMessage::TimeMsg(_)                     => "time",
+            BATSMessage::TradeExpandedMsg(_)            => "trade_expanded",
+            BATSMessage::OrderExecutedAtPriceSizeMsg(_) => "order_executed_at_price_size",
+            BATSMessage::CalculatedValueMsg(_)          => "calculated_value",
+            BATSMessage::EndOfSessionMsg(_)             => "end_of_session",
+            BATSMessage::LoginMsg(_)                    => "login",
+            BATSMessage::LoginResponseMsg(_)            => "login_response",
+            BATSMessage::SymbolMappingMsg(_)            => "symbol_mapping",
+            #[cfg(feature = "europe")]
+            BATSMessage::TradeReportMsg(_)              => "trade_report"
+        }
+    }
+}
+
+pub(crate) fn from_base36(input: &str) -> Result<u64, std::num::ParseIntError> {
     u64::from_str_radix(input, 36)
 }
 
 fn parse_opt_part_id( input : &str ) -> IResult<&str, String>
 {
     if input.is_empty() {
-        Ok(("", String::from("")))
-    } else {
-        let (first, last) = input.split_at(4);
-        Ok((last, String::from(first)))
+        return Ok(("", String::from("")));
+    }
+    // by byte, so one that's short or cut inside a char fails instead of panicking
+    match (input.get(..4), input.get(4..)) {
+        (Some(first), Some(last)) => Ok((last, String::from(first))),
+        _                         => Err(nom::Err::Error(error_position!(input, nom::ErrorKind::Eof)))
+    }
+}
+
+fn parse_opt_halt_reason( input : &str ) -> IResult<&str, (Option<HaltReason>, Option<char>)>
+{
+    // the expanded trading status carries a 4 char reason code and a 1 char flag.
+    if input.len() < 5 {
+        return Ok((input, (None, None)));
+    }
+    // by byte, one that's cut inside a char fails instead of panicking
+    match (input.get(0..4), input.get(4..5), input.get(5..)) {
+        (Some(code), Some(flag), Some(last)) => Ok((last, (Some(HaltReason::from_code(code)), flag.chars().next()))),
+        _                                    => Err(nom::Err::Error(error_position!(input, nom::ErrorKind::Eof)))
     }
 }
 
@@ -354,14 +791,28 @@ named!(parse_trading_status<&str, TradingStatusMsg>,
         _5 : map_res!(take!(1), FromStr::from_str)      >>
         _6 : map_res!(take!(1), FromStr::from_str)      >>
         _7 : map_res!(take!(1), FromStr::from_str)      >>
+        _8 : parse_opt_halt_reason                      >>
         (TradingStatusMsg{ timestamp      : _1, 
                            msg_type       : _2, 
                            symbol         : _3, 
                            halt_status    : _4, 
                            reg_sho_action : _5, 
                            reserved1      : _6, 
-                           reserved2      : _7
+                           reserved2      : _7, 
+                           halt_reason    : _8.0, 
+                           halt_flag      : _8.1
                     })  
     )
 );
 
+named!(parse_symbol_clear<&str, SymbolClearMsg>,  
+    do_parse!(
+        _1 : map_res!(take!(8), FromStr::from_str) >>
+        _2 : char!('s')                            >>
+        _3 : map_res!(take!(8), FromStr::from_str) >>  
+        (SymbolClearMsg{ timestamp : _1, 
+                         msg_type  : _2, 
+                         symbol    : _3
+                    })  
+    )
+);
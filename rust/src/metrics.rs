@@ -0,0 +1,206 @@
+
+// Feed handler metrics: messages parsed by type, parse errors, sequence gaps, book update
+// latency and traded volume per symbol. Everything is updated through &self so one Metrics
+// can be shared (Arc) between the decoding threads and whatever reads it, either with
+// snapshot() or by scraping the text exposition format from serve().
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use messages::BATSMessage;
+
+// upper bounds of the book update latency buckets, in seconds.
+pub const LATENCY_BUCKETS : [f64; 10] = [1e-6, 5e-6, 1e-5, 5e-5, 1e-4, 5e-4, 1e-3, 5e-3, 1e-2, 5e-2];
+
+pub struct Histogram {
+    bounds : Vec<f64>,
+    counts : Vec<AtomicU64>,  // per bucket, the last one is +Inf
+    sum_ns : AtomicU64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets  : Vec<(f64, u64)>,  // (upper bound, cumulative count), without +Inf
+    pub count    : u64,
+    pub sum_secs : f64
+}
+
+impl Histogram {
+    pub fn new( bounds : &[f64] ) -> Histogram {
+        Histogram{ bounds : bounds.to_vec(), counts : (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+                   sum_ns : AtomicU64::new(0) }
+    }
+
+    pub fn observe( &self, d : Duration ) {
+        let secs = d.as_secs_f64();
+        let i = self.bounds.iter().position(|&b| secs <= b).unwrap_or(self.bounds.len());
+        self.counts[i].fetch_add(1, Ordering::Relaxed);
+        self.sum_ns.fetch_add(d.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot( &self ) -> HistogramSnapshot {
+        let mut total = 0;
+        let mut buckets = vec![];
+        for (i, c) in self.counts.iter().enumerate() {
+            total += c.load(Ordering::Relaxed);
+            if i < self.bounds.len() {
+                buckets.push((self.bounds[i], total));
+            }
+        }
+        HistogramSnapshot{ buckets, count : total, sum_secs : self.sum_ns.load(Ordering::Relaxed) as f64 / 1e9 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    pub messages        : BTreeMap<&'static str, u64>,
+    pub parse_errors    : u64,
+    pub gaps            : u64,
+    pub missed_messages : u64,
+    pub book_update     : HistogramSnapshot,
+    pub trade_volume    : BTreeMap<String, u64>
+}
+
+pub struct Metrics {
+    messages        : Mutex<HashMap<&'static str, u64>>,
+    parse_errors    : AtomicU64,
+    gaps            : AtomicU64,
+    missed_messages : AtomicU64,
+    book_update     : Histogram,
+    trade_volume    : Mutex<HashMap<String, u64>>
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics{ messages : Mutex::new(HashMap::new()), parse_errors : AtomicU64::new(0), gaps : AtomicU64::new(0),
+                 missed_messages : AtomicU64::new(0), book_update : Histogram::new(&LATENCY_BUCKETS),
+                 trade_volume : Mutex::new(HashMap::new()) }
+    }
+
+    // counts the message by type, trades also count towards their symbol's volume.
+    pub fn record_message( &self, msg : &BATSMessage ) {
+        *self.messages.lock().unwrap().entry(msg.type_name()).or_insert(0) += 1;
+        match *msg {
+            BATSMessage::TradeMsg(ref m)         => self.record_trade(&m.symbol, m.shares),
+            BATSMessage::TradeExpandedMsg(ref m) => self.record_trade(&m.symbol, m.shares),
+            BATSMessage::BinaryTradeMsg(ref m)   => self.record_trade(&m.symbol, m.shares),
+            _ => {}
+        }
+    }
+
+    pub fn record_parse_error( &self ) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // a sequence gap of `missed` messages.
+    pub fn record_gap( &self, missed : u64 ) {
+        self.gaps.fetch_add(1, Ordering::Relaxed);
+        self.missed_messages.fetch_add(missed, Ordering::Relaxed);
+    }
+
+    // for executions, which don't carry the symbol themselves.
+    pub fn record_trade( &self, symbol : &str, shares : u32 ) {
+        *self.trade_volume.lock().unwrap().entry(String::from(symbol.trim_end())).or_insert(0) += u64::from(shares);
+    }
+
+    pub fn observe_book_update( &self, d : Duration ) {
+        self.book_update.observe(d);
+    }
+
+    // runs and times a book update.
+    pub fn time_book_update<F, R>( &self, f : F ) -> R where F : FnOnce() -> R {
+        let start = Instant::now();
+        let r = f();
+        self.book_update.observe(start.elapsed());
+        r
+    }
+
+    pub fn snapshot( &self ) -> MetricsSnapshot {
+        MetricsSnapshot{
+            messages        : self.messages.lock().unwrap().iter().map(|(k, v)| (*k, *v)).collect(),
+            parse_errors    : self.parse_errors.load(Ordering::Relaxed),
+            gaps            : self.gaps.load(Ordering::Relaxed),
+            missed_messages : self.missed_messages.load(Ordering::Relaxed),
+            book_update     : self.book_update.snapshot(),
+            trade_volume    : self.trade_volume.lock().unwrap().iter().map(|(k, v)| (k.clone(), *v)).collect()
+        }
+    }
+
+    // Prometheus text exposition format.
+    pub fn render( &self ) -> String {
+        let s = self.snapshot();
+        let mut out = String::new();
+        out.push_str("# HELP orderbook_messages_total Messages parsed, by type.\n# TYPE orderbook_messages_total counter\n");
+        for (t, n) in &s.messages {
+            out.push_str(&format!("orderbook_messages_total{{type=\"{}\"}} {}\n", t, n));
+        }
+        out.push_str("# HELP orderbook_parse_errors_total Messages that failed to parse.\n");
+        out.push_str(&format!("# TYPE orderbook_parse_errors_total counter\norderbook_parse_errors_total {}\n",
+                              s.parse_errors));
+        out.push_str("# HELP orderbook_gaps_total Sequence gaps detected.\n");
+        out.push_str(&format!("# TYPE orderbook_gaps_total counter\norderbook_gaps_total {}\n", s.gaps));
+        out.push_str("# HELP orderbook_missed_messages_total Messages lost to sequence gaps.\n");
+        out.push_str(&format!("# TYPE orderbook_missed_messages_total counter\norderbook_missed_messages_total {}\n",
+                              s.missed_messages));
+        out.push_str("# HELP orderbook_book_update_seconds Time to apply an update to the book.\n");
+        out.push_str("# TYPE orderbook_book_update_seconds histogram\n");
+        for &(le, n) in &s.book_update.buckets {
+            out.push_str(&format!("orderbook_book_update_seconds_bucket{{le=\"{}\"}} {}\n", le, n));
+        }
+        out.push_str(&format!("orderbook_book_update_seconds_bucket{{le=\"+Inf\"}} {}\n", s.book_update.count));
+        out.push_str(&format!("orderbook_book_update_seconds_sum {}\n", s.book_update.sum_secs));
+        out.push_str(&format!("orderbook_book_update_seconds_count {}\n", s.book_update.count));
+        out.push_str("# HELP orderbook_trade_volume_total Shares traded, by symbol.\n");
+        out.push_str("# TYPE orderbook_trade_volume_total counter\n");
+        for (symbol, n) in &s.trade_volume {
+            out.push_str(&format!("orderbook_trade_volume_total{{symbol=\"{}\"}} {}\n", symbol.replace('"', "\\\""), n));
+        }
+        out
+    }
+}
+
+// Serves GET /metrics on addr from a background thread, returning the bound address.
+pub fn serve( metrics : Arc<Metrics>, addr : &str ) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s)  => s,
+                Err(_) => continue
+            };
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+            let response = if request.starts_with("GET /metrics ") {
+                let body = metrics.render();
+                format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\
+                         Connection: close\r\n\r\n{}", body.len(), body)
+            } else {
+                String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    Ok(local)
+}
@@ -0,0 +1,136 @@
+
+// PITCH text captures read through a memory map: each line is parsed straight out of the
+// mapped pages, without reading it into a buffer first the way PitchReader's BufRead lines are.
+//
+//   let file = MappedFile::open("pitch_20251014.txt")?;
+//   for event in file.events() { ... }
+//
+// events() gives the same FeedEvents as PitchReader, SessionComplete included, and bytes() is
+// the whole capture, eg. for parallel::parse_lines.
+//
+// The file mustn't be truncated or written to while it's mapped, which would show the reader
+// bytes changing underfoot (or kill the process, on truncation).
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::str;
+
+use memmap2::Mmap;
+
+use feed::FeedEvent;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+
+pub struct MappedFile {
+    // None for an empty file, which can't be mapped
+    map : Option<Mmap>
+}
+
+impl MappedFile {
+    pub fn open<P : AsRef<Path>>( path : P ) -> io::Result<MappedFile> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(MappedFile{ map : None });
+        }
+        // see the top of the file for what the map needs of the file
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MappedFile{ map : Some(map) })
+    }
+
+    pub fn bytes( &self ) -> &[u8] {
+        self.map.as_deref().unwrap_or(&[])
+    }
+
+    pub fn len( &self ) -> usize {
+        self.bytes().len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.bytes().is_empty()
+    }
+
+    pub fn lines( &self ) -> MappedLines<'_> {
+        MappedLines{ rest : self.bytes(), skipped : 0 }
+    }
+
+    pub fn events( &self ) -> MappedReader<'_> {
+        MappedReader{ lines : self.lines(), pending_complete : false, session_complete : false, skipped : 0 }
+    }
+}
+
+// The lines of a capture as they sit in the map, line ends (\n or \r\n) left off. Lines that
+// aren't UTF-8 are passed over and counted, the same as PitchReader does.
+pub struct MappedLines<'a> {
+    rest    : &'a [u8],
+    skipped : u64
+}
+
+impl<'a> MappedLines<'a> {
+    // lines passed over as not UTF-8.
+    pub fn skipped( &self ) -> u64 {
+        self.skipped
+    }
+}
+
+impl<'a> Iterator for MappedLines<'a> {
+    type Item = &'a str;
+
+    fn next( &mut self ) -> Option<&'a str> {
+        while !self.rest.is_empty() {
+            let (line, rest) = match self.rest.iter().position(|&b| b == b'\n') {
+                Some(i) => (&self.rest[..i], &self.rest[i + 1..]),
+                None    => (self.rest, &[][..])
+            };
+            self.rest = rest;
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            match str::from_utf8(line) {
+                Ok(line) => return Some(line),
+                Err(_)   => self.skipped += 1
+            }
+        }
+        None
+    }
+}
+
+// PitchReader over a map: SessionComplete after an EndOfSessionMsg or at the end of the file,
+// and only once, lines that aren't a message we decode (UTF-8 or not) skipped.
+pub struct MappedReader<'a> {
+    lines            : MappedLines<'a>,
+    pending_complete : bool,
+    session_complete : bool,
+    skipped          : u64
+}
+
+impl<'a> MappedReader<'a> {
+    pub fn skipped( &self ) -> u64 {
+        self.skipped + self.lines.skipped()
+    }
+}
+
+impl<'a> Iterator for MappedReader<'a> {
+    type Item = FeedEvent;
+
+    fn next( &mut self ) -> Option<FeedEvent> {
+        if self.session_complete {
+            return None;
+        }
+        if self.pending_complete {
+            self.session_complete = true;
+            return Some(FeedEvent::SessionComplete);
+        }
+        for line in self.lines.by_ref() {
+            if line.is_empty() {
+                continue;
+            }
+            let msg = match BATSMsgFactory::try_parse(line) {
+                Some(msg) => msg,
+                None      => { self.skipped += 1; continue; }
+            };
+            self.pending_complete = matches!(msg, BATSMessage::EndOfSessionMsg(_));
+            return Some(FeedEvent::Message(msg));
+        }
+        self.session_complete = true;
+        Some(FeedEvent::SessionComplete)
+    }
+}
@@ -0,0 +1,233 @@
+
+// The live feed: joins the multicast groups each unit is published on, receives sequenced unit
+// packets into one reused buffer, and hands the messages on in sequence, eg. straight into a
+// BookManager:
+//
+//   let groups = ["224.0.62.2:30001".parse()?, "224.0.62.3:30002".parse()?];
+//   let mut feed = MulticastReceiver::join(&groups, Ipv4Addr::new(10, 0, 0, 5))?;
+//   feed.run(&mut books, &stop)?;
+//
+// Each unit's sequence numbers are followed so packets seen twice (eg. joining both the A and
+// B feeds) are applied once, and the messages a gap skips are counted, in the Metrics too when
+// it's given some. Recovering them is left to the caller (see recovery). An address that isn't multicast is
+// just bound, for unicast replays.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::net::SocketAddrV4;
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use binary::split_packet;
+use book::BookManager;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+use metrics::Metrics;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReceiverStats {
+    pub packets     : u64,
+    pub messages    : u64,
+    pub heartbeats  : u64,
+    // packets (or the start of them) already applied
+    pub duplicates  : u64,
+    pub gaps        : u64,
+    pub missed      : u64,
+    // datagrams that aren't sequenced unit packets
+    pub bad_packets : u64
+}
+
+// the messages split out of a packet onto `out`, skipping types we don't decode. Hands back how
+// many there were.
+pub(crate) fn decode_messages<'a, I>( msgs : I, metrics : Option<&Metrics>, out : &mut Vec<BATSMessage> ) -> u64
+        where I : Iterator<Item = &'a [u8]> {
+    let mut n = 0;
+    for m in msgs {
+        match BATSMsgFactory::try_parse_binary(m) {
+            Ok(Some(msg)) => {
+                if let Some(metrics) = metrics {
+                    metrics.record_message(&msg);
+                }
+                n += 1;
+                out.push(msg);
+            },
+            Ok(None) => {},
+            Err(_)   => if let Some(metrics) = metrics {
+                metrics.record_parse_error();
+            }
+        }
+    }
+    n
+}
+
+// What's been seen of each unit's sequence, so each message is handed on once.
+#[derive(Default)]
+pub(crate) struct Sequencer {
+    // by unit, the sequence number of the next message
+    expected    : HashMap<u8, u32>,
+    pub stats   : ReceiverStats,
+    pub metrics : Option<Arc<Metrics>>
+}
+
+impl Sequencer {
+    // appends the messages of a datagram not seen before to `out`.
+    pub fn packet( &mut self, data : &[u8], out : &mut Vec<BATSMessage> ) {
+        self.stats.packets += 1;
+        let (header, msgs) = match split_packet(data) {
+            Ok(p)  => p,
+            Err(_) => { self.stats.bad_packets += 1; return; }
+        };
+        if msgs.is_empty() {
+            self.stats.heartbeats += 1;
+            return;
+        }
+        // sequence 0 is for unsequenced messages, they're always taken
+        let mut skip = 0;
+        if header.sequence != 0 {
+            let first = header.sequence;
+            let end = first.wrapping_add(msgs.len() as u32);
+            let expected = self.expected.entry(header.unit).or_insert(first);
+            if end <= *expected {
+                self.stats.duplicates += 1;
+                return;
+            }
+            if first > *expected {
+                let missed = u64::from(first - *expected);
+                self.stats.gaps += 1;
+                self.stats.missed += missed;
+                if let Some(ref m) = self.metrics {
+                    m.record_gap(missed);
+                }
+            } else if first < *expected {
+                self.stats.duplicates += 1;
+                skip = (*expected - first) as usize;
+            }
+            *expected = end;
+        }
+        self.stats.messages += decode_messages(msgs.into_iter().skip(skip), self.metrics.as_deref(), out);
+    }
+}
+
+// a non-blocking socket on `port`, joined to the groups on that port.
+pub(crate) fn bind( port : u16, groups : &[SocketAddrV4], interface : Ipv4Addr ) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port))?;
+    for g in groups.iter().filter(|g| g.port() == port && g.ip().is_multicast()) {
+        socket.join_multicast_v4(g.ip(), &interface)?;
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+pub struct MulticastReceiver {
+    sockets : Vec<UdpSocket>,
+    // the socket to try first next time round, so a busy one can't starve the rest
+    next    : usize,
+    buf     : Vec<u8>,
+    timeout : Duration,
+    seq     : Sequencer
+}
+
+impl MulticastReceiver {
+    // a socket for each port, joined on `interface` to every group on it.
+    pub fn join( groups : &[SocketAddrV4], interface : Ipv4Addr ) -> io::Result<MulticastReceiver> {
+        let mut ports : Vec<u16> = groups.iter().map(|g| g.port()).collect();
+        ports.sort();
+        ports.dedup();
+        let sockets = ports.into_iter().map(|port| bind(port, groups, interface)).collect::<io::Result<_>>()?;
+        Ok(MulticastReceiver{ sockets, next : 0, buf : vec![0; 65536], timeout : Duration::from_millis(100), seq : Sequencer::default() })
+    }
+
+    pub fn metrics( mut self, metrics : Arc<Metrics> ) -> MulticastReceiver {
+        self.seq.metrics = Some(metrics);
+        self
+    }
+
+    // how long recv() waits for a packet, and so how often run() looks at its stop flag.
+    pub fn timeout( mut self, timeout : Duration ) -> MulticastReceiver {
+        self.timeout = timeout;
+        self
+    }
+
+    // where the sockets are bound, eg. to find the port picked for a group given port 0.
+    pub fn local_addrs( &self ) -> io::Result<Vec<SocketAddr>> {
+        self.sockets.iter().map(UdpSocket::local_addr).collect()
+    }
+
+    pub fn stats( &self ) -> ReceiverStats {
+        self.seq.stats
+    }
+
+    // the next datagram's length into buf, None when nothing came within the timeout.
+    fn receive( &mut self ) -> io::Result<Option<usize>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut idle = 0u32;
+        loop {
+            for i in 0..self.sockets.len() {
+                let at = (self.next + i) % self.sockets.len();
+                match self.sockets[at].recv_from(&mut self.buf) {
+                    Ok((n, _)) => {
+                        self.next = (at + 1) % self.sockets.len();
+                        return Ok(Some(n));
+                    },
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::Interrupted => {},
+                    Err(e) => return Err(e)
+                }
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            // spin while the feed is busy, back off once it's gone quiet
+            idle += 1;
+            if idle < 100 {
+                thread::yield_now();
+            } else {
+                thread::sleep(Duration::from_micros(50));
+            }
+        }
+    }
+
+    // waits for a datagram and hands it back as it came, for a caller following the sequence
+    // itself (eg. a recovery::Recovery). None when nothing came within the timeout.
+    pub fn recv_packet( &mut self ) -> io::Result<Option<&[u8]>> {
+        Ok(self.receive()?.map(move |n| &self.buf[..n]))
+    }
+
+    // waits for a packet and appends its messages not seen before to `out`, false when none
+    // came within the timeout.
+    pub fn recv( &mut self, out : &mut Vec<BATSMessage> ) -> io::Result<bool> {
+        let n = match self.receive()? {
+            Some(n) => n,
+            None    => return Ok(false)
+        };
+        self.seq.packet(&self.buf[..n], out);
+        Ok(true)
+    }
+
+    // applies packets to the books as they come until `stop` is set, each against its unit so
+    // a Unit Clear only drops that unit's books.
+    pub fn run( &mut self, books : &mut BookManager, stop : &AtomicBool ) -> io::Result<()> {
+        let mut msgs = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            let n = match self.receive()? {
+                Some(n) => n,
+                None    => continue
+            };
+            let unit = match split_packet(&self.buf[..n]) {
+                Ok((header, _)) => header.unit,
+                Err(_)          => 0
+            };
+            self.seq.packet(&self.buf[..n], &mut msgs);
+            for msg in msgs.drain(..) {
+                books.apply_unit(unit, &msg);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+
+// Node.js bindings through napi-rs, for the TypeScript tooling. Build the cdylib with the node
+// feature and load it as a .node addon:
+//
+//   cargo build --release --features node && cp target/release/librust_orderbook.so rust_orderbook.node
+//
+//   const { parse, BookManager } = require("./rust_orderbook.node");
+//   const books = new BookManager();
+//   books.applyAll(fs.readFileSync("pitch.txt", "utf8"));
+//   books.bbo("AAPL")        // { bidPrice, bidShares, askPrice, askShares }
+//   books.depth("AAPL", 5)   // { bids : [{ price, shares }], asks : [...] }
+//
+// parse() gives the serde schema (the jsonl one) as an object, with BigInts for the u64s too
+// big for a JS number, ie. most order ids. Book prices are numbers with 4 implied decimals.
+
+use napi::Env;
+use napi::Error;
+use napi::Result;
+use napi::Status;
+use napi::Unknown;
+use napi_derive::napi;
+
+use book;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+
+fn parse_line( line : &str ) -> Result<BATSMessage> {
+    BATSMsgFactory::try_parse(line).ok_or_else(|| Error::new(Status::InvalidArg, format!("not a PITCH message: {:?}", line)))
+}
+
+fn lines( text : &str ) -> impl Iterator<Item = &str> {
+    text.lines().map(|l| l.trim_end()).filter(|l| !l.is_empty())
+}
+
+#[napi]
+pub fn parse<'env>( env : &'env Env, line : String ) -> Result<Unknown<'env>> {
+    env.to_js_value(&parse_line(&line)?)
+}
+
+// every message in a capture, blank lines skipped.
+#[napi(js_name = "parseAll")]
+pub fn parse_all<'env>( env : &'env Env, text : String ) -> Result<Unknown<'env>> {
+    let msgs = lines(&text).map(parse_line).collect::<Result<Vec<_>>>()?;
+    env.to_js_value(&msgs)
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub price  : f64,
+    pub shares : u32
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bbo {
+    pub bid_price  : Option<f64>,
+    pub bid_shares : Option<u32>,
+    pub ask_price  : Option<f64>,
+    pub ask_shares : Option<u32>
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Depth {
+    pub bids : Vec<Level>,
+    pub asks : Vec<Level>
+}
+
+// shares over u32::MAX (not that a level gets there) read as u32::MAX.
+fn to_level( (price, shares) : (u64, u64) ) -> Level {
+    Level{ price : price as f64, shares : shares.min(u64::from(u32::MAX)) as u32 }
+}
+
+// Books for every symbol on the feed, wrapping book::BookManager.
+#[napi]
+#[derive(Default)]
+pub struct BookManager {
+    books : book::BookManager
+}
+
+impl BookManager {
+    pub fn apply_message( &mut self, msg : &BATSMessage ) -> bool {
+        self.books.apply(msg)
+    }
+}
+
+#[napi]
+impl BookManager {
+    #[napi(constructor)]
+    pub fn new() -> BookManager {
+        BookManager::default()
+    }
+
+    // applies a PITCH line, returns whether a book changed.
+    #[napi]
+    pub fn apply( &mut self, line : String ) -> Result<bool> {
+        Ok(self.apply_message(&parse_line(line.trim_end())?))
+    }
+
+    // applies every line of a capture, returns how many changed a book.
+    #[napi(js_name = "applyAll")]
+    pub fn apply_all( &mut self, text : String ) -> Result<u32> {
+        let mut changed = 0;
+        for line in lines(&text) {
+            if self.apply_message(&parse_line(line)?) {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    #[napi]
+    pub fn symbols( &self ) -> Vec<String> {
+        self.books.symbols().into_iter().map(String::from).collect()
+    }
+
+    #[napi]
+    pub fn bbo( &self, symbol : String ) -> Option<Bbo> {
+        self.books.book(&symbol).map(|b| {
+            let bid = b.best_bid().map(|q| to_level((q.price, q.shares)));
+            let ask = b.best_ask().map(|q| to_level((q.price, q.shares)));
+            Bbo{ bid_price  : bid.as_ref().map(|l| l.price), bid_shares : bid.map(|l| l.shares),
+                 ask_price  : ask.as_ref().map(|l| l.price), ask_shares : ask.map(|l| l.shares) }
+        })
+    }
+
+    #[napi]
+    pub fn depth( &self, symbol : String, levels : u32 ) -> Option<Depth> {
+        self.books.book(&symbol).map(|b| Depth{ bids : b.bids().take(levels as usize).map(|l| to_level((l.price(), l.shares()))).collect(),
+                                                asks : b.asks().take(levels as usize).map(|l| to_level((l.price(), l.shares()))).collect() })
+    }
+
+    #[napi(getter)]
+    pub fn orders( &self ) -> u32 {
+        self.books.orders() as u32
+    }
+}
@@ -0,0 +1,112 @@
+
+// Order flow imbalance: how much the adds, cancels and executions at the top of a book lean to
+// buying or selling. Each change of the bbo counts
+//
+//   bid shares now if the bid didn't drop, less the bid shares before if it didn't rise,
+//   less ask shares now if the ask didn't rise, plus the ask shares before if it didn't drop
+//
+// which is positive for a growing bid or a shrinking ask. Changes are summed into fixed windows
+// per symbol, each closed once a change after it comes in (or flush() closes it). Windows that
+// saw no change aren't in the series.
+
+use std::collections::HashMap;
+
+use binary::TimestampComposer;
+use book::Bbo;
+use book::BookManager;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OfiPoint {
+    pub start   : u64,  // ns past midnight, a multiple of the window
+    pub ofi     : i64,
+    pub changes : u32
+}
+
+pub struct OrderFlowImbalance {
+    window : u64,
+    clock  : TimestampComposer,
+    last   : HashMap<String, Bbo>,
+    open   : HashMap<String, OfiPoint>,
+    series : HashMap<String, Vec<OfiPoint>>
+}
+
+// a missing bid is lower than any price and a missing ask higher.
+fn top( bbo : &Bbo ) -> ((u64, i64), (u64, i64)) {
+    (bbo.bid.map_or((0, 0), |q| (q.price, q.shares as i64)), bbo.ask.map_or((u64::MAX, 0), |q| (q.price, q.shares as i64)))
+}
+
+fn contribution( before : &Bbo, after : &Bbo ) -> i64 {
+    let ((pb, qb), (pa, qa)) = top(before);
+    let ((nb, nqb), (na, nqa)) = top(after);
+    let mut e = 0;
+    if nb >= pb { e += nqb; }
+    if nb <= pb { e -= qb; }
+    if na <= pa { e -= nqa; }
+    if na >= pa { e += qa; }
+    e
+}
+
+impl OrderFlowImbalance {
+    // `window` in nanoseconds.
+    pub fn new( window : u64 ) -> OrderFlowImbalance {
+        OrderFlowImbalance{ window : window.max(1), clock : TimestampComposer::new(), last : HashMap::new(), open : HashMap::new(),
+                            series : HashMap::new() }
+    }
+
+    // applies the message to the books, then takes in whatever it did to its book's bbo.
+    pub fn apply( &mut self, books : &mut BookManager, msg : &BATSMessage ) -> bool {
+        let now = self.clock.compose(msg);
+        let symbol = books.symbol_for(msg).map(String::from);
+        let changed = books.apply(msg);
+        if let Some(symbol) = symbol {
+            let bbo = books.book(&symbol).map(|b| b.bbo()).unwrap_or_default();
+            self.observe(&symbol, now, bbo);
+        }
+        changed
+    }
+
+    // for books kept some other way: the symbol's bbo as of `timestamp`. An unchanged bbo counts
+    // for nothing.
+    pub fn observe( &mut self, symbol : &str, timestamp : u64, bbo : Bbo ) {
+        let before = self.last.insert(String::from(symbol), bbo).unwrap_or_default();
+        if before == bbo {
+            return;
+        }
+        let start = timestamp - timestamp % self.window;
+        let open = self.open.entry(String::from(symbol)).or_insert(OfiPoint{ start, ofi : 0, changes : 0 });
+        if open.start != start {
+            let closed = std::mem::replace(open, OfiPoint{ start, ofi : 0, changes : 0 });
+            if closed.changes > 0 {
+                self.series.entry(String::from(symbol)).or_default().push(closed);
+            }
+        }
+        open.ofi += contribution(&before, &bbo);
+        open.changes += 1;
+    }
+
+    // the symbol's closed windows, oldest first.
+    pub fn series( &self, symbol : &str ) -> &[OfiPoint] {
+        self.series.get(symbol.trim_end()).map_or(&[], |s| s.as_slice())
+    }
+
+    // the window still adding up.
+    pub fn current( &self, symbol : &str ) -> Option<OfiPoint> {
+        self.open.get(symbol.trim_end()).cloned()
+    }
+
+    // hands over the closed windows so far, leaving the open one.
+    pub fn take_series( &mut self, symbol : &str ) -> Vec<OfiPoint> {
+        self.series.remove(symbol.trim_end()).unwrap_or_default()
+    }
+
+    // closes every open window, eg. at the end of a day.
+    pub fn flush( &mut self ) {
+        for (symbol, open) in self.open.drain() {
+            if open.changes > 0 {
+                self.series.entry(symbol).or_default().push(open);
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+
+// Cboe Options PITCH sends orders/trades against a 6 char feed symbol. The Symbol Mapping
+// message ties feed symbols back to the 21 char OSI identifier of the contract, ie.
+// 6 char root + YYMMDD expiry + C/P + 8 digit strike (5 integer, 3 decimal digits).
+
+use nom;
+use nom::le_u8;
+
+use std::collections::HashMap;
+use std::str;
+use std::result::Result;
+
+use binary::pad_ascii;
+use messages::BATSMessage;
+
+pub const SYMBOL_MAPPING : u8 = 0x2E;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PutCall {
+    Call,
+    Put
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsiSymbol {
+    pub root         : String,
+    pub expiry_year  : u16,
+    pub expiry_month : u8,
+    pub expiry_day   : u8,
+    pub put_call     : PutCall,
+    pub strike       : u64 // 4 implied decimal places like other prices in the crate
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OsiError {
+    InvalidLength(usize),
+    InvalidExpiry,
+    InvalidPutCall(char),
+    InvalidStrike
+}
+
+impl OsiSymbol {
+    pub fn parse( symbol : &str ) -> Result<OsiSymbol, OsiError> {
+        if symbol.len() != 21 || !symbol.is_ascii() {
+            return Err(OsiError::InvalidLength(symbol.len()));
+        }
+        let (root, rest)   = symbol.split_at(6);
+        let (expiry, rest) = rest.split_at(6);
+        let (pc, strike)   = rest.split_at(1);
+
+        let parse_2 = |s : &str| s.parse::<u8>().map_err(|_| OsiError::InvalidExpiry);
+        let expiry_year  = 2000 + u16::from(parse_2(&expiry[0..2])?);
+        let expiry_month = parse_2(&expiry[2..4])?;
+        let expiry_day   = parse_2(&expiry[4..6])?;
+        if expiry_month == 0 || expiry_month > 12 || expiry_day == 0 || expiry_day > 31 {
+            return Err(OsiError::InvalidExpiry);
+        }
+
+        let put_call = match pc {
+            "C" => PutCall::Call,
+            "P" => PutCall::Put,
+            _   => return Err(OsiError::InvalidPutCall(pc.chars().next().unwrap_or(' ')))
+        };
+        let strike = strike.parse::<u64>().map_err(|_| OsiError::InvalidStrike)? * 10;
+
+        Ok(OsiSymbol{ root : String::from(root.trim_end()), 
+                      expiry_year, 
+                      expiry_month, 
+                      expiry_day, 
+                      put_call, 
+                      strike })
+    }
+
+    pub fn to_osi_string( &self ) -> String {
+        format!("{:<6}{:02}{:02}{:02}{}{:08}", 
+                self.root, self.expiry_year % 100, self.expiry_month, self.expiry_day, 
+                if self.put_call == PutCall::Call { 'C' } else { 'P' }, self.strike / 10)
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SymbolMappingMsg { // maps the feed symbol used on the options feed to the OSI symbol
+    pub msg_type         : u8,
+    pub feed_symbol      : String,
+    pub osi_symbol       : String,
+    pub symbol_condition : char,
+    pub underlying       : String
+}
+
+impl SymbolMappingMsg {
+    pub fn parse_binary( msg : &[u8] ) -> Result<SymbolMappingMsg, nom::Err<&[u8]>> {
+        parse_symbol_mapping(msg).map(|o| o.1)
+    }
+
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = vec![38, SYMBOL_MAPPING];
+        pad_ascii(&mut buf, &self.feed_symbol, 6);
+        pad_ascii(&mut buf, &self.osi_symbol, 21);
+        buf.push(self.symbol_condition as u8);
+        pad_ascii(&mut buf, &self.underlying, 8);
+        buf
+    }
+
+    pub fn osi( &self ) -> Result<OsiSymbol, OsiError> {
+        OsiSymbol::parse(&self.osi_symbol)
+    }
+}
+
+// feed symbol -> contract lookup built up from the SymbolMappingMsgs seen on the feed.
+#[derive(Debug, Default)]
+pub struct OptionsSymbolMap {
+    symbols : HashMap<String, OsiSymbol>
+}
+
+impl OptionsSymbolMap {
+    pub fn new() -> OptionsSymbolMap {
+        OptionsSymbolMap{ symbols : HashMap::new() }
+    }
+
+    pub fn apply( &mut self, msg : &BATSMessage ) {
+        if let BATSMessage::SymbolMappingMsg(ref m) = *msg {
+            if let Ok(osi) = m.osi() {
+                self.symbols.insert(String::from(m.feed_symbol.trim_end()), osi);
+            }
+        }
+    }
+
+    pub fn get( &self, feed_symbol : &str ) -> Option<&OsiSymbol> {
+        self.symbols.get(feed_symbol.trim_end())
+    }
+
+    pub fn len( &self ) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+fn from_ascii( input : &[u8] ) -> Result<String, str::Utf8Error> {
+    str::from_utf8(input).map(String::from)
+}
+
+named!(parse_symbol_mapping<&[u8], SymbolMappingMsg>,
+    do_parse!(
+        _1 : le_u8                                        >>
+        _2 : verify!(le_u8, |t : u8| t == SYMBOL_MAPPING) >>
+        _3 : map_res!(take!(6), from_ascii)               >>
+        _4 : map_res!(take!(21), from_ascii)              >>
+        _5 : map!(le_u8, char::from)                      >>
+        _6 : map_res!(take!(8), from_ascii)               >>
+        (SymbolMappingMsg{ msg_type         : _2,
+                           feed_symbol      : _3,
+                           osi_symbol       : _4,
+                           symbol_condition : _5,
+                           underlying       : _6
+                         })
+    )
+);
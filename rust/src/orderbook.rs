@@ -34,6 +34,7 @@ pub struct LimitOrderBook {
     ask_book : AskBook, 
     bid_book : BidBook, 
     requests : Arc<MsQueue<Order>>, 
+    #[allow(dead_code)]
     worker_threads : Vec<JoinHandle<()>>,
     dispatch_thread : Option<JoinHandle<()>>, 
 }
@@ -60,9 +61,8 @@ impl OrderManager for PriceBucket {
 
     fn remove_order( &mut self, order : Order ) {
         // just do linear scan for now, worry about performance later.
-        let idx  = self.orders.iter().position(|x| x.order_id == order.order_id );
-        if idx.is_some() {
-            self.orders.remove(idx.unwrap());
+        if let Some(idx) = self.orders.iter().position(|x| x.order_id == order.order_id ) {
+            self.orders.remove(idx);
         }
     }
 }
@@ -70,7 +70,7 @@ impl OrderManager for PriceBucket {
 impl PriceBucket {
 
     pub fn from_price(price_level : u64) -> PriceBucket {
-        PriceBucket{ price_level : price_level, orders : Vec::new() }
+        PriceBucket{ price_level, orders : Vec::new() }
     } 
 
     pub fn from_order(order : Order) -> PriceBucket {
@@ -80,6 +80,18 @@ impl PriceBucket {
     pub fn volume(&self) -> u32 {
         self.orders.iter().map(|x| x.volume ).sum()
     } 
+
+    pub fn set_order_volume( &mut self, order_id : u64, remaining : u32 ) {
+        // the exchange tells us what is left after an execution, so take that as is
+        // instead of working it out from the executed shares.
+        if let Some(idx) = self.orders.iter().position(|x| x.order_id == order_id ) {
+            if remaining == 0 {
+                self.orders.remove(idx);
+            } else {
+                self.orders[idx].volume = remaining;
+            }
+        }
+    }
 }
 
 #[macro_export]
@@ -102,6 +114,27 @@ macro_rules! expand_book_struct {
                     b.volume()
                 } else {0}
             }
+
+            pub fn clear( &mut self ) {
+                self.price_buckets.clear();
+            }
+
+            pub fn set_order_volume( &mut self, order : &Order, remaining : u32 ) {
+                let mut is_empty = false;
+                if let Some(bucket) = self.price_buckets.get_mut(&order.price) {
+                    bucket.set_order_volume(order.order_id, remaining);
+                    is_empty = bucket.orders.is_empty();
+                }
+                if is_empty {
+                    self.price_buckets.remove(&order.price);
+                }
+            }
+        }
+
+        impl Default for $book_struct_name {
+            fn default() -> $book_struct_name {
+                $book_struct_name::new()
+            }
         }
 
         impl OrderManager for $book_struct_name  {
@@ -143,17 +176,17 @@ pub enum IterVariant<'a> {
 
 pub trait PriceBucketIter {
     // iterates through orders in price-time order.
-    fn iter_mut(&mut self) -> IterVariant;
+    fn iter_mut(&mut self) -> IterVariant<'_>;
 }
 
 impl PriceBucketIter for AskBook {
-    fn iter_mut(&mut self) -> IterVariant {
+    fn iter_mut(&mut self) -> IterVariant<'_> {
         IterVariant::AskBookIter( self.price_buckets.iter_mut() )
     }
 }
 
 impl PriceBucketIter for BidBook {
-    fn iter_mut(&mut self) -> IterVariant {
+    fn iter_mut(&mut self) -> IterVariant<'_> {
         IterVariant::BidBookIter( self.price_buckets.iter_mut().rev() )
     }
 }
@@ -198,9 +231,8 @@ impl LimitOrderBook {
 
                 //let best_bid = a.best_price();
                 // let x = self.ask_book.best_price();
-                match queue.try_pop() {
-                    Some(o) => println!("Order = {:?}", o),
-                    _ => {}
+                if let Some(o) = queue.try_pop() {
+                    println!("Order = {:?}", o);
                 }
                 thread::sleep(time::Duration::from_secs(1));
             }
@@ -216,8 +248,24 @@ impl LimitOrderBook {
         self.requests.push(order);
     }
 
-    pub fn best_bid(&self) -> u64 { return self.bid_book.best_price() }
-    pub fn best_ask(&self) -> u64 { return self.ask_book.best_price() }
+    pub fn clear(&mut self) {
+        // purges all resting orders, eg. on a symbol clear from the exchange.
+        self.ask_book.clear();
+        self.bid_book.clear();
+    }
+
+    pub fn set_order_volume(&mut self, order : &Order, remaining : u32) {
+        // eg. from OrderExecutedAtPriceSizeMsg, which reports the remaining shares directly.
+        if order.side == -1 {
+            self.ask_book.set_order_volume(order, remaining)
+        }
+        else {
+            self.bid_book.set_order_volume(order, remaining)
+        }
+    }
+
+    pub fn best_bid(&self) -> u64 { self.bid_book.best_price() }
+    pub fn best_ask(&self) -> u64 { self.ask_book.best_price() }
 
     pub fn ask_volume_at_price_level(&self, price : u64) -> u32 {
         if let Some(bucket) = self.ask_book.price_buckets.get(&price) {
@@ -261,7 +309,7 @@ impl LimitOrderBook {
 
         let price_bucket_iter = book.iter_mut();
 
-        let it : Box<Iterator<Item=(&u64, &mut PriceBucket)>> = match price_bucket_iter {
+        let it : Box<dyn Iterator<Item=(&u64, &mut PriceBucket)>> = match price_bucket_iter {
             IterVariant::AskBookIter(x) => Box::new(x.into_iter()),
             IterVariant::BidBookIter(y) => Box::new(y.into_iter()),
             _ => unimplemented!()
@@ -292,15 +340,21 @@ impl LimitOrderBook {
         ( volume, orders_to_remove ) 
     }
 
-    pub fn ask_iter(&mut self) -> btree_map::IterMut<u64, PriceBucket> {
+    pub fn ask_iter(&mut self) -> btree_map::IterMut<'_, u64, PriceBucket> {
         self.ask_book.price_buckets.iter_mut()
     }
 
-    pub fn bid_iter(&mut self) -> btree_map::IterMut<u64, PriceBucket> {
+    pub fn bid_iter(&mut self) -> btree_map::IterMut<'_, u64, PriceBucket> {
         self.bid_book.price_buckets.iter_mut()
     }
 }
 
+impl Default for LimitOrderBook {
+    fn default() -> LimitOrderBook {
+        LimitOrderBook::new()
+    }
+}
+
 impl OrderManager for LimitOrderBook {
 
     fn add_order( &mut self, order : Order ) {
@@ -0,0 +1,262 @@
+
+// NASDAQ OUCH 4.2 order entry. Inbound (client -> exchange) messages are encoded, outbound
+// ones decoded. Like ITCH everything is big endian and prices are 4 implied decimal places,
+// so they widen to the u64 prices used by the rest of the crate. Order tokens are the
+// client's 14 character ids, order_id is the exchange assigned order reference number.
+
+use nom;
+use nom::{be_u8, be_u32, be_u64};
+
+use orderbook::Order;
+
+use std::str;
+use std::result::Result;
+
+pub const ENTER_ORDER    : u8 = b'O';
+pub const REPLACE_ORDER  : u8 = b'U';
+pub const CANCEL_ORDER   : u8 = b'X';
+pub const ACCEPTED       : u8 = b'A';
+pub const EXECUTED       : u8 = b'E';
+pub const CANCELED       : u8 = b'C';
+
+#[derive(Debug, Clone)]
+pub struct EnterOrderMsg {
+    pub token         : String,
+    pub side          : char,   // 'B', 'S', 'T' short or 'E' short exempt
+    pub shares        : u32,
+    pub symbol        : String,
+    pub price         : u64,
+    pub time_in_force : u32,    // seconds, 0 is IOC and 99999 is day
+    pub firm          : String,
+    pub display       : char,
+    pub capacity      : char,
+    pub iso           : char,
+    pub min_qty       : u32,
+    pub cross_type    : char,
+    pub customer_type : char
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplaceOrderMsg {
+    pub existing_token    : String,
+    pub replacement_token : String,
+    pub shares            : u32,
+    pub price             : u64,
+    pub time_in_force     : u32,
+    pub display           : char,
+    pub iso               : char,
+    pub min_qty           : u32
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelOrderMsg {
+    pub token  : String,
+    pub shares : u32  // shares left on the order afterwards, 0 cancels it entirely
+}
+
+#[derive(Debug)]
+pub struct AcceptedMsg {
+    pub timestamp     : u64,
+    pub token         : String,
+    pub side          : char,
+    pub shares        : u32,
+    pub symbol        : String,
+    pub price         : u64,
+    pub time_in_force : u32,
+    pub firm          : String,
+    pub display       : char,
+    pub order_id      : u64,
+    pub capacity      : char,
+    pub iso           : char,
+    pub min_qty       : u32,
+    pub cross_type    : char,
+    pub order_state   : char,  // 'L' live, 'D' dead
+    pub bbo_weight    : char
+}
+
+#[derive(Debug)]
+pub struct ExecutedMsg {
+    pub timestamp       : u64,
+    pub token           : String,
+    pub shares          : u32,
+    pub price           : u64,
+    pub liquidity_flag  : char,
+    pub match_number    : u64
+}
+
+#[derive(Debug)]
+pub struct CanceledMsg {
+    pub timestamp : u64,
+    pub token     : String,
+    pub shares    : u32,   // shares removed from the order
+    pub reason    : char
+}
+
+#[derive(Debug)]
+pub enum OuchMessage {
+    Accepted(AcceptedMsg),
+    Executed(ExecutedMsg),
+    Canceled(CanceledMsg),
+    Unknown(char)
+}
+
+fn pad_ascii( buf : &mut Vec<u8>, field : &str, width : usize ) {
+    let bytes = field.as_bytes();
+    let n = bytes.len().min(width);
+    buf.extend_from_slice(&bytes[..n]);
+    buf.extend(::std::iter::repeat_n(b' ', width - n));
+}
+
+// OUCH prices are only 32 bits wide, anything above $429496.7295 is clamped.
+fn ouch_price( price : u64 ) -> [u8; 4] {
+    (price.min(u64::from(u32::MAX)) as u32).to_be_bytes()
+}
+
+impl EnterOrderMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(49);
+        buf.push(ENTER_ORDER);
+        pad_ascii(&mut buf, &self.token, 14);
+        buf.push(self.side as u8);
+        buf.extend_from_slice(&self.shares.to_be_bytes());
+        pad_ascii(&mut buf, &self.symbol, 8);
+        buf.extend_from_slice(&ouch_price(self.price));
+        buf.extend_from_slice(&self.time_in_force.to_be_bytes());
+        pad_ascii(&mut buf, &self.firm, 4);
+        buf.push(self.display as u8);
+        buf.push(self.capacity as u8);
+        buf.push(self.iso as u8);
+        buf.extend_from_slice(&self.min_qty.to_be_bytes());
+        buf.push(self.cross_type as u8);
+        buf.push(self.customer_type as u8);
+        buf
+    }
+}
+
+impl ReplaceOrderMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(47);
+        buf.push(REPLACE_ORDER);
+        pad_ascii(&mut buf, &self.existing_token, 14);
+        pad_ascii(&mut buf, &self.replacement_token, 14);
+        buf.extend_from_slice(&self.shares.to_be_bytes());
+        buf.extend_from_slice(&ouch_price(self.price));
+        buf.extend_from_slice(&self.time_in_force.to_be_bytes());
+        buf.push(self.display as u8);
+        buf.push(self.iso as u8);
+        buf.extend_from_slice(&self.min_qty.to_be_bytes());
+        buf
+    }
+}
+
+impl CancelOrderMsg {
+    pub fn to_bytes( &self ) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(19);
+        buf.push(CANCEL_ORDER);
+        pad_ascii(&mut buf, &self.token, 14);
+        buf.extend_from_slice(&self.shares.to_be_bytes());
+        buf
+    }
+}
+
+impl AcceptedMsg {
+    // our own resting order, eg. to track it in a LimitOrderBook next to the market data.
+    pub fn to_order( &self ) -> Order {
+        Order{ order_id : self.order_id,
+               price    : self.price,
+               volume   : self.shares,
+               side     : if self.side == 'B' { 1 } else { -1 },
+               part_id  : self.firm.clone() }
+    }
+}
+
+impl OuchMessage {
+    pub fn parse( msg : &[u8] ) -> Result<OuchMessage, nom::Err<&[u8]>> {
+        match msg.first() {
+            Some(&ACCEPTED) => parse_accepted(msg).map(|o| OuchMessage::Accepted(o.1)),
+            Some(&EXECUTED) => parse_executed(msg).map(|o| OuchMessage::Executed(o.1)),
+            Some(&CANCELED) => parse_canceled(msg).map(|o| OuchMessage::Canceled(o.1)),
+            Some(&c)        => Ok(OuchMessage::Unknown(char::from(c))),
+            None            => Err(nom::Err::Incomplete(nom::Needed::Size(1)))
+        }
+    }
+}
+
+fn from_ascii( input : &[u8] ) -> Result<String, str::Utf8Error> {
+    str::from_utf8(input).map(|s| String::from(s.trim_end()))
+}
+
+named!(parse_char<&[u8], char>, map!(be_u8, char::from));
+
+named!(parse_accepted<&[u8], AcceptedMsg>,
+    do_parse!(
+        _1  : tag!("A")                       >>
+        _2  : be_u64                          >>
+        _3  : map_res!(take!(14), from_ascii) >>
+        _4  : parse_char                      >>
+        _5  : be_u32                          >>
+        _6  : map_res!(take!(8), from_ascii)  >>
+        _7  : be_u32                          >>
+        _8  : be_u32                          >>
+        _9  : map_res!(take!(4), from_ascii)  >>
+        _10 : parse_char                      >>
+        _11 : be_u64                          >>
+        _12 : parse_char                      >>
+        _13 : parse_char                      >>
+        _14 : be_u32                          >>
+        _15 : parse_char                      >>
+        _16 : parse_char                      >>
+        _17 : parse_char                      >>
+        (AcceptedMsg{ timestamp     : _2,
+                      token         : _3,
+                      side          : _4,
+                      shares        : _5,
+                      symbol        : _6,
+                      price         : u64::from(_7),
+                      time_in_force : _8,
+                      firm          : _9,
+                      display       : _10,
+                      order_id      : _11,
+                      capacity      : _12,
+                      iso           : _13,
+                      min_qty       : _14,
+                      cross_type    : _15,
+                      order_state   : _16,
+                      bbo_weight    : _17
+                    })
+    )
+);
+
+named!(parse_executed<&[u8], ExecutedMsg>,
+    do_parse!(
+        _1 : tag!("E")                       >>
+        _2 : be_u64                          >>
+        _3 : map_res!(take!(14), from_ascii) >>
+        _4 : be_u32                          >>
+        _5 : be_u32                          >>
+        _6 : parse_char                      >>
+        _7 : be_u64                          >>
+        (ExecutedMsg{ timestamp      : _2,
+                      token          : _3,
+                      shares         : _4,
+                      price          : u64::from(_5),
+                      liquidity_flag : _6,
+                      match_number   : _7
+                    })
+    )
+);
+
+named!(parse_canceled<&[u8], CanceledMsg>,
+    do_parse!(
+        _1 : tag!("C")                       >>
+        _2 : be_u64                          >>
+        _3 : map_res!(take!(14), from_ascii) >>
+        _4 : be_u32                          >>
+        _5 : parse_char                      >>
+        (CanceledMsg{ timestamp : _2,
+                      token     : _3,
+                      shares    : _4,
+                      reason    : _5
+                    })
+    )
+);
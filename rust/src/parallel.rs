@@ -0,0 +1,92 @@
+
+// Parsing a whole PITCH text capture across threads, for offline work where the file is all
+// there up front: it's split into chunks at line ends, rayon parses the chunks and they're put
+// back together in file order, so the result is what PitchReader would have given.
+//
+//   let msgs = parallel::parse_file("pitch_20251014.txt")?;
+//
+// Empty lines are skipped as PitchReader skips them; a line that isn't a message we decode
+// fails the whole parse, saying which.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::str;
+
+use rayon::prelude::*;
+
+use compressed;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadLine {
+    pub line : usize,   // from 1
+    pub text : String
+}
+
+impl fmt::Display for BadLine {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        write!(f, "line {} isn't a PITCH message: {:?}", self.line, self.text)
+    }
+}
+
+impl error::Error for BadLine {}
+
+// about `chunks` pieces of `data`, each ending at a line end (or the end of the data).
+fn split( data : &[u8], chunks : usize ) -> Vec<&[u8]> {
+    let size = (data.len() / chunks.max(1)).max(1);
+    let mut pieces = Vec::with_capacity(chunks);
+    let mut rest = data;
+    while !rest.is_empty() {
+        let end = match rest.iter().skip(size).position(|&b| b == b'\n') {
+            Some(i) => size + i + 1,
+            None    => rest.len()
+        };
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    pieces
+}
+
+fn parse_chunk( chunk : &[u8] ) -> Result<Vec<BATSMessage>, (usize, String)> {
+    let mut msgs = Vec::with_capacity(chunk.len() / 40);
+    for (i, line) in chunk.split(|&b| b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let text = str::from_utf8(line).map_err(|_| (i, String::from_utf8_lossy(line).into_owned()))?;
+        msgs.push(BATSMsgFactory::try_parse(text).ok_or_else(|| (i, String::from(text)))?);
+    }
+    Ok(msgs)
+}
+
+// on rayon's global pool.
+pub fn parse_lines( data : &[u8] ) -> Result<Vec<BATSMessage>, BadLine> {
+    let chunks = split(data, rayon::current_num_threads() * 4);
+    let parsed : Vec<_> = chunks.par_iter().map(|c| parse_chunk(c)).collect();
+    let mut msgs = Vec::with_capacity(parsed.iter().map(|p| p.as_ref().map_or(0, Vec::len)).sum());
+    for (n, p) in parsed.into_iter().enumerate() {
+        match p {
+            Ok(mut m)          => msgs.append(&mut m),
+            Err((line, text)) => {
+                // only worked out for the line that failed
+                let before : usize = chunks[..n].iter().map(|c| c.iter().filter(|&&b| b == b'\n').count()).sum();
+                return Err(BadLine{ line : before + line + 1, text });
+            }
+        }
+    }
+    Ok(msgs)
+}
+
+// reads the file in whole first, decompressing it if need be. A bad line comes back as
+// InvalidData.
+pub fn parse_file<P : AsRef<Path>>( path : P ) -> io::Result<Vec<BATSMessage>> {
+    let mut data = Vec::new();
+    compressed::open(path)?.read_to_end(&mut data)?;
+    parse_lines(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
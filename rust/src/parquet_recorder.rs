@@ -0,0 +1,221 @@
+
+// Streams messages and book snapshots into Parquet files partitioned hive style, ie.
+// <root>/date=<date>/symbol=<symbol>/<table>.parquet, one table per message type as in the
+// Arrow collector plus a book_snapshot table. Messages that only carry an order id are filed
+// under the symbol of their AddOrder, anything that can't be tied to a symbol (unit clears,
+// trade breaks, orders added before recording started) goes under symbol=_unit.
+
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow_array::ArrayRef;
+use arrow_array::RecordBatch;
+use arrow_array::StringArray;
+use arrow_array::UInt32Array;
+use arrow_array::UInt64Array;
+use arrow_schema::DataType;
+use arrow_schema::Field;
+use arrow_schema::Schema;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::Result;
+
+use arrow::ArrowCollector;
+use messages::BATSMessage;
+
+const UNIT_SYMBOL : &str = "_unit";
+
+#[derive(Default)]
+struct SnapshotRows {
+    timestamp : Vec<u64>,
+    side      : Vec<&'static str>,
+    level     : Vec<u32>,
+    price     : Vec<u64>,
+    volume    : Vec<u32>
+}
+
+impl SnapshotRows {
+    fn push( &mut self, timestamp : u64, side : &'static str, levels : &[(u64, u32)] ) {
+        for (i, &(price, volume)) in levels.iter().enumerate() {
+            self.timestamp.push(timestamp);
+            self.side.push(side);
+            self.level.push(i as u32);
+            self.price.push(price);
+            self.volume.push(volume);
+        }
+    }
+
+    fn take( &mut self ) -> Result<RecordBatch> {
+        let rows = ::std::mem::take(self);
+        let schema = Schema::new(vec![Field::new("timestamp", DataType::UInt64, false),
+                                      Field::new("side",      DataType::Utf8,   false),
+                                      Field::new("level",     DataType::UInt32, false),
+                                      Field::new("price",     DataType::UInt64, false),
+                                      Field::new("volume",    DataType::UInt32, false)]);
+        let columns : Vec<ArrayRef> = vec![Arc::new(UInt64Array::from(rows.timestamp)),
+                                           Arc::new(StringArray::from(rows.side)),
+                                           Arc::new(UInt32Array::from(rows.level)),
+                                           Arc::new(UInt64Array::from(rows.price)),
+                                           Arc::new(UInt32Array::from(rows.volume))];
+        Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+    }
+}
+
+pub struct ParquetRecorder {
+    root              : PathBuf,
+    batch_rows        : usize,
+    snapshot_interval : u64,
+    order_symbols     : HashMap<u64, String>,
+    pending           : HashMap<String, ArrowCollector>,
+    snapshots         : HashMap<String, SnapshotRows>,
+    last_snapshot     : HashMap<String, u64>,
+    writers           : HashMap<(String, &'static str), ArrowWriter<File>>
+}
+
+impl ParquetRecorder {
+    pub fn new<P : AsRef<Path>>( root : P, date : &str ) -> ParquetRecorder {
+        ParquetRecorder{ root              : root.as_ref().join(format!("date={}", date)),
+                         batch_rows        : 65536,
+                         snapshot_interval : 0,
+                         order_symbols     : HashMap::new(),
+                         pending           : HashMap::new(),
+                         snapshots         : HashMap::new(),
+                         last_snapshot     : HashMap::new(),
+                         writers           : HashMap::new() }
+    }
+
+    // rows buffered per symbol before they're written out as a row group.
+    pub fn batch_rows( mut self, rows : usize ) -> ParquetRecorder {
+        self.batch_rows = rows.max(1);
+        self
+    }
+
+    // minimum time between two snapshots of a symbol, in the unit of the timestamps passed in.
+    pub fn snapshot_interval( mut self, interval : u64 ) -> ParquetRecorder {
+        self.snapshot_interval = interval;
+        self
+    }
+
+    fn symbol_of( &mut self, msg : &BATSMessage ) -> String {
+        let (order_id, symbol) = match *msg {
+            BATSMessage::AddOrderMsg(ref m)       => (m.order_id, &m.symbol),
+            BATSMessage::BinaryAddOrderMsg(ref m) => (m.order_id, &m.symbol),
+            _ => return self.symbol_for(msg)
+        };
+        let s = String::from(symbol.trim_end());
+        self.order_symbols.insert(order_id, s.clone());
+        s
+    }
+
+    fn symbol_for( &self, msg : &BATSMessage ) -> String {
+        let symbol = match *msg {
+            BATSMessage::OrderCancelMsg(ref m)              => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::OrderExecutedMsg(ref m)            => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::BinaryOrderExecutedMsg(ref m)      => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::ReduceSizeMsg(ref m)               => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::ModifyOrderMsg(ref m)              => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::DeleteOrderMsg(ref m)              => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => self.order_symbols.get(&m.order_id).cloned(),
+            BATSMessage::TradeMsg(ref m)              => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::TradeExpandedMsg(ref m)      => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::BinaryTradeMsg(ref m)        => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::AuctionSummaryMsg(ref m)     => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::AuctionUpdateMsg(ref m)      => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::RetailPriceImproveMsg(ref m) => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::TradingStatusMsg(ref m)      => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::SymbolClearMsg(ref m)        => Some(String::from(m.symbol.trim_end())),
+            BATSMessage::CalculatedValueMsg(ref m)    => Some(String::from(m.symbol.trim_end())),
+            _ => None
+        };
+        symbol.unwrap_or_else(|| String::from(UNIT_SYMBOL))
+    }
+
+    pub fn record( &mut self, msg : &BATSMessage ) -> Result<()> {
+        let symbol = self.symbol_of(msg);
+        let full = {
+            let collector = self.pending.entry(symbol.clone()).or_default();
+            collector.push(msg);
+            collector.rows() >= self.batch_rows
+        };
+        if full {
+            self.flush_symbol(&symbol)?;
+        }
+        Ok(())
+    }
+
+    // levels are (price, volume) from the top of the book down. Returns false when the
+    // snapshot was skipped because the last one for the symbol is more recent than the interval.
+    pub fn record_snapshot( &mut self, timestamp : u64, symbol : &str,
+                            bids : &[(u64, u32)], asks : &[(u64, u32)] ) -> Result<bool> {
+        if let Some(&last) = self.last_snapshot.get(symbol) {
+            if timestamp < last + self.snapshot_interval {
+                return Ok(false);
+            }
+        }
+        self.last_snapshot.insert(String::from(symbol), timestamp);
+        let full = {
+            let rows = self.snapshots.entry(String::from(symbol)).or_default();
+            rows.push(timestamp, "B", bids);
+            rows.push(timestamp, "S", asks);
+            rows.timestamp.len() >= self.batch_rows
+        };
+        if full {
+            self.flush_snapshots(symbol)?;
+        }
+        Ok(true)
+    }
+
+    fn write( &mut self, symbol : &str, table : &'static str, batch : &RecordBatch ) -> Result<()> {
+        let key = (String::from(symbol), table);
+        if !self.writers.contains_key(&key) {
+            let dir = self.root.join(format!("symbol={}", symbol));
+            fs::create_dir_all(&dir)?;
+            let file = File::create(dir.join(format!("{}.parquet", table)))?;
+            let writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+            self.writers.insert(key.clone(), writer);
+        }
+        if let Some(w) = self.writers.get_mut(&key) {
+            w.write(batch)?;
+        }
+        Ok(())
+    }
+
+    fn flush_symbol( &mut self, symbol : &str ) -> Result<()> {
+        let batches = match self.pending.get_mut(symbol) {
+            Some(c) => c.take()?,
+            None    => return Ok(())
+        };
+        for (table, batch) in batches {
+            self.write(symbol, table, &batch)?;
+        }
+        Ok(())
+    }
+
+    fn flush_snapshots( &mut self, symbol : &str ) -> Result<()> {
+        let batch = match self.snapshots.get_mut(symbol) {
+            Some(ref rows) if rows.timestamp.is_empty() => return Ok(()),
+            Some(rows) => rows.take()?,
+            None       => return Ok(())
+        };
+        self.write(symbol, "book_snapshot", &batch)
+    }
+
+    // writes out everything buffered and closes the files.
+    pub fn close( mut self ) -> Result<()> {
+        let symbols : Vec<String> = self.pending.keys().cloned().collect();
+        for s in symbols {
+            self.flush_symbol(&s)?;
+        }
+        let symbols : Vec<String> = self.snapshots.keys().cloned().collect();
+        for s in symbols {
+            self.flush_snapshots(&s)?;
+        }
+        for (_, w) in self.writers.drain() {
+            w.close()?;
+        }
+        Ok(())
+    }
+}
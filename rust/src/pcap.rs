@@ -0,0 +1,353 @@
+
+// Wire captures (pcap or pcapng, either byte order) replayed without preprocessing: the reader
+// walks the capture's packets, keeps the IPv4 UDP datagrams sent to the configured multicast
+// groups and ports, and strips the link, IP and UDP headers down to the payload, which is a
+// sequenced unit packet for decode_packet (or any FeedDecoder):
+//
+//   let capture = PcapReader::new(BufReader::new(File::open("feed.pcapng")?))?
+//       .group(Ipv4Addr::new(224, 0, 62, 2), 30001);
+//   for event in capture.events() { ... }
+//
+// With no groups or ports set every UDP datagram is kept. Ethernet (VLAN tagged or not), Linux
+// cooked and raw IP captures are understood, other link types and IP fragments are skipped
+// and counted.
+
+use std::io;
+use std::io::Read;
+use std::net::Ipv4Addr;
+use std::net::SocketAddrV4;
+
+use feed::FeedEvent;
+use feed::decode_packet;
+
+const PCAP_MICROS : u32 = 0xa1b2_c3d4;
+const PCAP_NANOS  : u32 = 0xa1b2_3c4d;
+const PCAPNG_SECTION : u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER : u32 = 0x1a2b_3c4d;
+
+const LINK_ETHERNET : u32 = 1;
+const LINK_RAW      : u32 = 101;
+const LINK_LINUX_SLL : u32 = 113;
+const LINK_IPV4     : u32 = 228;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Datagram {
+    // when it was captured, nanoseconds since the epoch
+    pub timestamp : u64,
+    pub source    : SocketAddrV4,
+    pub dest      : SocketAddrV4,
+    pub payload   : Vec<u8>
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PcapStats {
+    pub packets   : u64,
+    pub datagrams : u64,
+    // not IPv4 UDP, fragmented, or a link type we don't read
+    pub skipped   : u64,
+    // UDP, but not for a group or port being followed
+    pub filtered  : u64
+}
+
+fn invalid( what : &str ) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, String::from(what))
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Pcap{ link : u32, nanos : bool },
+    PcapNg
+}
+
+struct Interface {
+    link  : u32,
+    // timestamp units a second
+    units : u64
+}
+
+pub struct PcapReader<R : Read> {
+    input      : R,
+    big        : bool,
+    format     : Format,
+    interfaces : Vec<Interface>,
+    groups     : Vec<(Ipv4Addr, u16)>,
+    ports      : Vec<u16>,
+    stats      : PcapStats
+}
+
+impl<R : Read> PcapReader<R> {
+    // reads the file header (or pcapng's first section header) to tell which format it is.
+    pub fn new( mut input : R ) -> io::Result<PcapReader<R>> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        let (le, be) = (u32::from_le_bytes(magic), u32::from_be_bytes(magic));
+        let mut reader = PcapReader{ input, big : false, format : Format::PcapNg, interfaces : Vec::new(), groups : Vec::new(),
+                                     ports : Vec::new(), stats : PcapStats::default() };
+        if le == PCAPNG_SECTION {
+            reader.section()?;
+            return Ok(reader);
+        }
+        let nanos = match (le, be) {
+            (PCAP_MICROS, _) | (_, PCAP_MICROS) => false,
+            (PCAP_NANOS, _) | (_, PCAP_NANOS)   => true,
+            _ => return Err(invalid("not a pcap or pcapng capture"))
+        };
+        reader.big = be == PCAP_MICROS || be == PCAP_NANOS;
+        let mut header = [0u8; 20];
+        reader.input.read_exact(&mut header)?;
+        reader.format = Format::Pcap{ link : reader.u32_at(&header, 16), nanos };
+        Ok(reader)
+    }
+
+    // keeps datagrams sent to `group:port`.
+    pub fn group( mut self, group : Ipv4Addr, port : u16 ) -> PcapReader<R> {
+        self.groups.push((group, port));
+        self
+    }
+
+    // keeps datagrams sent to `port`, whatever the address.
+    pub fn port( mut self, port : u16 ) -> PcapReader<R> {
+        self.ports.push(port);
+        self
+    }
+
+    pub fn stats( &self ) -> PcapStats {
+        self.stats
+    }
+
+    // the payloads decoded, an InvalidData error for one that isn't a sequenced unit packet.
+    pub fn events( self ) -> PcapEvents<R> {
+        PcapEvents{ reader : self, pending : Vec::new().into_iter() }
+    }
+
+    fn u32_at( &self, b : &[u8], at : usize ) -> u32 {
+        let v = [b[at], b[at + 1], b[at + 2], b[at + 3]];
+        if self.big { u32::from_be_bytes(v) } else { u32::from_le_bytes(v) }
+    }
+
+    fn u16_at( &self, b : &[u8], at : usize ) -> u16 {
+        let v = [b[at], b[at + 1]];
+        if self.big { u16::from_be_bytes(v) } else { u16::from_le_bytes(v) }
+    }
+
+    // the rest of a section header block, after its type. Sections can switch byte order.
+    fn section( &mut self ) -> io::Result<()> {
+        let mut head = [0u8; 8];
+        self.input.read_exact(&mut head)?;
+        let order = [head[4], head[5], head[6], head[7]];
+        self.big = if u32::from_le_bytes(order) == PCAPNG_BYTE_ORDER {
+            false
+        } else if u32::from_be_bytes(order) == PCAPNG_BYTE_ORDER {
+            true
+        } else {
+            return Err(invalid("bad pcapng byte order magic"));
+        };
+        let length = self.u32_at(&head, 0) as usize;
+        if length < 12 {
+            return Err(invalid("short pcapng section header"));
+        }
+        self.skip(length - 12)?;
+        self.interfaces.clear();
+        Ok(())
+    }
+
+    fn skip( &mut self, n : usize ) -> io::Result<()> {
+        let copied = io::copy(&mut (&mut self.input).take(n as u64), &mut io::sink())?;
+        if copied < n as u64 { Err(io::ErrorKind::UnexpectedEof.into()) } else { Ok(()) }
+    }
+
+    // false at a clean end of the input.
+    fn fill( &mut self, buf : &mut [u8] ) -> io::Result<bool> {
+        let mut got = 0;
+        while got < buf.len() {
+            match self.input.read(&mut buf[got..]) {
+                Ok(0) if got == 0 => return Ok(false),
+                Ok(0)  => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n)  => got += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(true)
+    }
+
+    // the next captured frame with its link type and timestamp, None at the end.
+    fn frame( &mut self ) -> io::Result<Option<(u32, u64, Vec<u8>)>> {
+        match self.format {
+            Format::Pcap{ link, nanos } => {
+                let mut head = [0u8; 16];
+                if !self.fill(&mut head)? {
+                    return Ok(None);
+                }
+                let (secs, frac) = (u64::from(self.u32_at(&head, 0)), u64::from(self.u32_at(&head, 4)));
+                let mut data = vec![0u8; self.u32_at(&head, 8) as usize];
+                self.input.read_exact(&mut data)?;
+                Ok(Some((link, secs * 1_000_000_000 + if nanos { frac } else { frac * 1000 }, data)))
+            },
+            Format::PcapNg => loop {
+                let mut head = [0u8; 8];
+                if !self.fill(&mut head)? {
+                    return Ok(None);
+                }
+                let kind = self.u32_at(&head, 0);
+                if kind == PCAPNG_SECTION {
+                    self.section()?;
+                    continue;
+                }
+                let length = self.u32_at(&head, 4) as usize;
+                if length < 12 {
+                    return Err(invalid("short pcapng block"));
+                }
+                let mut body = vec![0u8; length - 12];
+                self.input.read_exact(&mut body)?;
+                self.skip(4)?;
+                match kind {
+                    1 if body.len() >= 8 => {
+                        let link = u32::from(self.u16_at(&body, 0));
+                        let units = self.resolution(&body[8..])?;
+                        self.interfaces.push(Interface{ link, units });
+                    },
+                    // enhanced packet
+                    6 if body.len() >= 20 => {
+                        let interface = self.u32_at(&body, 0) as usize;
+                        let ts = u64::from(self.u32_at(&body, 4)) << 32 | u64::from(self.u32_at(&body, 8));
+                        let captured = (self.u32_at(&body, 12) as usize).min(body.len() - 20);
+                        let (link, units) = match self.interfaces.get(interface) {
+                            Some(i) => (i.link, i.units),
+                            None    => return Err(invalid("pcapng packet for an undescribed interface"))
+                        };
+                        let timestamp = (u128::from(ts) * 1_000_000_000 / u128::from(units)) as u64;
+                        return Ok(Some((link, timestamp, body[20..20 + captured].to_vec())));
+                    },
+                    // simple packet, no timestamp
+                    3 if body.len() >= 4 => {
+                        let link = self.interfaces.first().map_or(LINK_ETHERNET, |i| i.link);
+                        let captured = (self.u32_at(&body, 0) as usize).min(body.len() - 4);
+                        return Ok(Some((link, 0, body[4..4 + captured].to_vec())));
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // an interface's if_tsresol option, microseconds when it has none.
+    fn resolution( &self, mut options : &[u8] ) -> io::Result<u64> {
+        while options.len() >= 4 {
+            let (code, length) = (self.u16_at(options, 0), self.u16_at(options, 2) as usize);
+            if code == 0 {
+                break;
+            }
+            // options are padded to 4 bytes
+            let next = 4 + ((length + 3) & !3);
+            if options.len() < next {
+                return Err(invalid("pcapng option runs past its block"));
+            }
+            if code == 9 && length >= 1 {
+                let v = options[4];
+                let exp = u32::from(v & 0x7f).min(63);
+                return Ok(if v & 0x80 == 0 { 10u64.checked_pow(exp).unwrap_or(u64::MAX) } else { 1u64 << exp });
+            }
+            options = &options[next..];
+        }
+        Ok(1_000_000)
+    }
+
+    fn wanted( &self, dest : &SocketAddrV4 ) -> bool {
+        (self.groups.is_empty() && self.ports.is_empty()) || self.ports.contains(&dest.port()) ||
+            self.groups.contains(&(*dest.ip(), dest.port()))
+    }
+}
+
+// the IPv4 packet in a frame.
+fn ip_payload( link : u32, frame : &[u8] ) -> Option<&[u8]> {
+    let (mut ethertype, mut at) = match link {
+        LINK_ETHERNET  => (u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]), 14),
+        LINK_LINUX_SLL => (u16::from_be_bytes([*frame.get(14)?, *frame.get(15)?]), 16),
+        LINK_RAW | LINK_IPV4 => (0x0800, 0),
+        _ => return None
+    };
+    // 802.1Q and 802.1ad tags
+    while ethertype == 0x8100 || ethertype == 0x88a8 {
+        ethertype = u16::from_be_bytes([*frame.get(at + 2)?, *frame.get(at + 3)?]);
+        at += 4;
+    }
+    if ethertype == 0x0800 { frame.get(at..) } else { None }
+}
+
+// source, destination and payload of a UDP datagram in an unfragmented IPv4 packet.
+fn udp( ip : &[u8] ) -> Option<(SocketAddrV4, SocketAddrV4, &[u8])> {
+    if ip.len() < 20 || ip[0] >> 4 != 4 || ip[9] != 17 {
+        return None;
+    }
+    let flags = u16::from_be_bytes([ip[6], ip[7]]);
+    if flags & 0x3fff != 0 {
+        return None;
+    }
+    let header = usize::from(ip[0] & 0x0f) * 4;
+    let total = usize::from(u16::from_be_bytes([ip[2], ip[3]])).min(ip.len());
+    let udp = ip.get(header..total)?;
+    if udp.len() < 8 {
+        return None;
+    }
+    let length = usize::from(u16::from_be_bytes([udp[4], udp[5]])).clamp(8, udp.len());
+    let source = SocketAddrV4::new(Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]), u16::from_be_bytes([udp[0], udp[1]]));
+    let dest = SocketAddrV4::new(Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]), u16::from_be_bytes([udp[2], udp[3]]));
+    Some((source, dest, &udp[8..length]))
+}
+
+impl<R : Read> Iterator for PcapReader<R> {
+    type Item = io::Result<Datagram>;
+
+    fn next( &mut self ) -> Option<io::Result<Datagram>> {
+        loop {
+            let (link, timestamp, frame) = match self.frame() {
+                Ok(Some(f)) => f,
+                Ok(None)    => return None,
+                Err(e)      => return Some(Err(e))
+            };
+            self.stats.packets += 1;
+            let (source, dest, payload) = match ip_payload(link, &frame).and_then(udp) {
+                Some(d) => d,
+                None    => { self.stats.skipped += 1; continue; }
+            };
+            if !self.wanted(&dest) {
+                self.stats.filtered += 1;
+                continue;
+            }
+            self.stats.datagrams += 1;
+            return Some(Ok(Datagram{ timestamp, source, dest, payload : payload.to_vec() }));
+        }
+    }
+}
+
+pub struct PcapEvents<R : Read> {
+    reader  : PcapReader<R>,
+    pending : ::std::vec::IntoIter<FeedEvent>
+}
+
+impl<R : Read> PcapEvents<R> {
+    pub fn stats( &self ) -> PcapStats {
+        self.reader.stats()
+    }
+}
+
+impl<R : Read> Iterator for PcapEvents<R> {
+    type Item = io::Result<FeedEvent>;
+
+    fn next( &mut self ) -> Option<io::Result<FeedEvent>> {
+        loop {
+            if let Some(event) = self.pending.next() {
+                return Some(Ok(event));
+            }
+            let datagram = match self.reader.next()? {
+                Ok(d)  => d,
+                Err(e) => return Some(Err(e))
+            };
+            match decode_packet(&datagram.payload) {
+                Ok(events) => self.pending = events.into_iter(),
+                Err(e)     => return Some(Err(invalid(&format!("datagram to {} isn't a PITCH packet: {:?}", datagram.dest, e))))
+            }
+        }
+    }
+}
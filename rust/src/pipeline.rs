@@ -0,0 +1,282 @@
+
+// Decoding on one thread and book building on another, with a lock-free single producer single
+// consumer ring between them, so a slow stretch of book updates doesn't stall reading the feed
+// (and the other way round) the way parsing and applying in one loop does:
+//
+//   let msgs = PitchReader::new(reader).filter_map(|e| match e { FeedEvent::Message(m) => Some(m), _ => None });
+//   let mut pipeline = Pipeline::spawn(msgs, 65536, Overflow::Wait);
+//   pipeline.run(&mut books);
+//   pipeline.stats()
+//
+// The iterator given to spawn is what runs on the decoder thread, so do the parsing in it.
+// ring() is the buffer on its own, for other producers.
+
+use std::cell::UnsafeCell;
+use std::io;
+use std::mem::MaybeUninit;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use book::BookManager;
+use messages::BATSMessage;
+
+// what the producer does with a message when the ring is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    // until the consumer makes room, nothing's lost
+    #[default]
+    Wait,
+    // keeps the producer at the feed's pace, the drops are counted
+    Drop
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub pushed     : u64,
+    pub popped     : u64,
+    pub dropped    : u64,
+    // times the producer found the ring full, whether it then waited or dropped
+    pub full       : u64,
+    // most messages ever queued at once
+    pub high_water : u64
+}
+
+struct Ring<T> {
+    slots      : Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask       : usize,
+    // counts of items ever popped and pushed, so head == tail is empty whatever was wrapped
+    head       : AtomicUsize,
+    tail       : AtomicUsize,
+    closed     : AtomicBool,
+    // the consumer's been dropped, nothing pushed will be taken
+    gone       : AtomicBool,
+    dropped    : AtomicU64,
+    full       : AtomicU64,
+    high_water : AtomicU64
+}
+
+// the slots between head and tail belong to the consumer, the rest to the producer, and each
+// side only moves its own counter once it's done with a slot.
+unsafe impl<T : Send> Send for Ring<T> {}
+unsafe impl<T : Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn stats( &self ) -> PipelineStats {
+        PipelineStats{ pushed : self.tail.load(Ordering::Acquire) as u64, popped : self.head.load(Ordering::Acquire) as u64,
+                       dropped : self.dropped.load(Ordering::Relaxed), full : self.full.load(Ordering::Relaxed),
+                       high_water : self.high_water.load(Ordering::Relaxed) }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop( &mut self ) {
+        let tail = *self.tail.get_mut();
+        let mut i = *self.head.get_mut();
+        while i != tail {
+            unsafe { (*self.slots[i & self.mask].get()).assume_init_drop() };
+            i = i.wrapping_add(1);
+        }
+    }
+}
+
+pub struct Producer<T> {
+    ring : Arc<Ring<T>>
+}
+
+pub struct Consumer<T> {
+    ring : Arc<Ring<T>>
+}
+
+// `capacity` is rounded up to a power of two.
+pub fn ring<T : Send>( capacity : usize ) -> (Producer<T>, Consumer<T>) {
+    let capacity = capacity.max(1).next_power_of_two();
+    let ring = Arc::new(Ring{ slots : (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(), mask : capacity - 1,
+                              head : AtomicUsize::new(0), tail : AtomicUsize::new(0), closed : AtomicBool::new(false),
+                              gone : AtomicBool::new(false), dropped : AtomicU64::new(0), full : AtomicU64::new(0),
+                              high_water : AtomicU64::new(0) });
+    (Producer{ ring : ring.clone() }, Consumer{ ring })
+}
+
+// spins a little, then gives the thread up.
+fn backoff( tries : &mut u32 ) {
+    if *tries < 64 {
+        std::hint::spin_loop();
+    } else {
+        thread::yield_now();
+    }
+    *tries += 1;
+}
+
+impl<T> Producer<T> {
+    // the value back when the ring's full.
+    pub fn try_push( &mut self, value : T ) -> Result<(), T> {
+        let ring = &self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let queued = tail.wrapping_sub(ring.head.load(Ordering::Acquire));
+        if queued == ring.slots.len() {
+            return Err(value);
+        }
+        unsafe { (*ring.slots[tail & ring.mask].get()).write(value) };
+        ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+        ring.high_water.fetch_max(queued as u64 + 1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // false when the value was dropped for want of room, a BrokenPipe error once the consumer's gone.
+    pub fn push( &mut self, value : T, overflow : Overflow ) -> io::Result<bool> {
+        if self.ring.gone.load(Ordering::Acquire) {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        let mut value = match self.try_push(value) {
+            Ok(())     => return Ok(true),
+            Err(value) => value
+        };
+        self.ring.full.fetch_add(1, Ordering::Relaxed);
+        if overflow == Overflow::Drop {
+            self.ring.dropped.fetch_add(1, Ordering::Relaxed);
+            return Ok(false);
+        }
+        let mut tries = 0;
+        loop {
+            backoff(&mut tries);
+            if self.ring.gone.load(Ordering::Acquire) {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            value = match self.try_push(value) {
+                Ok(())     => return Ok(true),
+                Err(value) => value
+            };
+        }
+    }
+
+    pub fn stats( &self ) -> PipelineStats {
+        self.ring.stats()
+    }
+
+    pub fn capacity( &self ) -> usize {
+        self.ring.slots.len()
+    }
+}
+
+// the consumer sees the ring closed once everything pushed before has been taken.
+impl<T> Drop for Producer<T> {
+    fn drop( &mut self ) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn try_pop( &mut self ) -> Option<T> {
+        let ring = &self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head == ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { (*ring.slots[head & ring.mask].get()).assume_init_read() };
+        ring.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    // waits for the next value, None once the producer's gone and the ring is empty.
+    pub fn pop( &mut self ) -> Option<T> {
+        let mut tries = 0;
+        loop {
+            if let Some(value) = self.try_pop() {
+                return Some(value);
+            }
+            if self.ring.closed.load(Ordering::Acquire) {
+                // anything pushed before closing is visible now
+                return self.try_pop();
+            }
+            backoff(&mut tries);
+        }
+    }
+
+    pub fn stats( &self ) -> PipelineStats {
+        self.ring.stats()
+    }
+
+    pub fn len( &self ) -> usize {
+        self.ring.tail.load(Ordering::Acquire).wrapping_sub(self.ring.head.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.len() == 0
+    }
+}
+
+// a producer waiting for room gives up.
+impl<T> Drop for Consumer<T> {
+    fn drop( &mut self ) {
+        self.ring.gone.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Iterator for Consumer<T> {
+    type Item = T;
+
+    fn next( &mut self ) -> Option<T> {
+        self.pop()
+    }
+}
+
+pub struct Pipeline {
+    consumer : Consumer<BATSMessage>,
+    decoder  : Option<thread::JoinHandle<()>>
+}
+
+impl Pipeline {
+    // starts the decoder thread pulling messages from `msgs` into a ring of `capacity`.
+    pub fn spawn<I>( msgs : I, capacity : usize, overflow : Overflow ) -> Pipeline
+        where I : Iterator<Item = BATSMessage> + Send + 'static
+    {
+        let (mut producer, consumer) = ring(capacity);
+        let decoder = thread::spawn(move || {
+            // stops decoding once the pipeline's dropped
+            for msg in msgs {
+                if producer.push(msg, overflow).is_err() {
+                    break;
+                }
+            }
+        });
+        Pipeline{ consumer, decoder : Some(decoder) }
+    }
+
+    // applies every message to the books until the decoder runs out, returns how many.
+    pub fn run( &mut self, books : &mut BookManager ) -> u64 {
+        let mut applied = 0;
+        for msg in self.by_ref() {
+            books.apply(&msg);
+            applied += 1;
+        }
+        applied
+    }
+
+    pub fn stats( &self ) -> PipelineStats {
+        self.consumer.stats()
+    }
+
+    // messages decoded but not taken yet.
+    pub fn queued( &self ) -> usize {
+        self.consumer.len()
+    }
+}
+
+impl Iterator for Pipeline {
+    type Item = BATSMessage;
+
+    fn next( &mut self ) -> Option<BATSMessage> {
+        let msg = self.consumer.pop();
+        if msg.is_none() {
+            // a decoder that panicked takes the pipeline down with it
+            if let Some(Err(e)) = self.decoder.take().map(thread::JoinHandle::join) {
+                std::panic::resume_unwind(e);
+            }
+        }
+        msg
+    }
+}
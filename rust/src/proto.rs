@@ -0,0 +1,224 @@
+
+// Protobuf form of MarketEvent, matching proto/market_event.proto. The prost structs are
+// kept by hand rather than generated so building doesn't need protoc, keep the two in sync.
+
+use std::convert::TryFrom;
+
+use prost;
+use prost::Message;
+
+use event;
+use event::TradingState;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrderAdded {
+    #[prost(uint64, tag = "1")] pub timestamp : u64,
+    #[prost(uint64, tag = "2")] pub order_id  : u64,
+    #[prost(string, tag = "3")] pub side      : String,
+    #[prost(uint64, tag = "4")] pub price     : u64,
+    #[prost(uint32, tag = "5")] pub shares    : u32,
+    #[prost(string, tag = "6")] pub symbol    : String
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrderReduced {
+    #[prost(uint64, tag = "1")] pub timestamp : u64,
+    #[prost(uint64, tag = "2")] pub order_id  : u64,
+    #[prost(uint32, tag = "3")] pub shares    : u32
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrderDeleted {
+    #[prost(uint64, tag = "1")] pub timestamp : u64,
+    #[prost(uint64, tag = "2")] pub order_id  : u64
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OrderReplaced {
+    #[prost(uint64, tag = "1")] pub timestamp    : u64,
+    #[prost(uint64, tag = "2")] pub order_id     : u64,
+    #[prost(uint64, tag = "3")] pub new_order_id : u64,
+    #[prost(uint64, tag = "4")] pub price        : u64,
+    #[prost(uint32, tag = "5")] pub shares       : u32
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Trade {
+    #[prost(uint64, tag = "1")]           pub timestamp : u64,
+    #[prost(uint64, optional, tag = "2")] pub order_id  : Option<u64>,
+    #[prost(string, optional, tag = "3")] pub symbol    : Option<String>,
+    #[prost(string, optional, tag = "4")] pub side      : Option<String>,
+    #[prost(uint64, optional, tag = "5")] pub price     : Option<u64>,
+    #[prost(uint32, tag = "6")]           pub shares    : u32,
+    #[prost(uint32, optional, tag = "7")] pub remaining : Option<u32>,
+    #[prost(uint64, tag = "8")]           pub exec_id   : u64
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TradeBroken {
+    #[prost(uint64, tag = "1")] pub timestamp : u64,
+    #[prost(uint64, tag = "2")] pub exec_id   : u64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ProtoTradingState {
+    Other        = 0,
+    Halted       = 1,
+    Suspended    = 2,
+    QuoteOnly    = 3,
+    Trading      = 4,
+    Cleared      = 5,
+    EndOfSession = 6
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StatusChange {
+    #[prost(uint64, tag = "1")]                            pub timestamp   : u64,
+    #[prost(string, optional, tag = "2")]                  pub symbol      : Option<String>,
+    #[prost(enumeration = "ProtoTradingState", tag = "3")] pub state       : i32,
+    #[prost(string, tag = "4")]                            pub other_state : String
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AuctionInfo {
+    #[prost(uint64, tag = "1")] pub timestamp       : u64,
+    #[prost(string, tag = "2")] pub symbol          : String,
+    #[prost(string, tag = "3")] pub auction_type    : String,
+    #[prost(uint64, tag = "4")] pub price           : u64,
+    #[prost(uint32, tag = "5")] pub buy_shares      : u32,
+    #[prost(uint32, tag = "6")] pub sell_shares     : u32,
+    #[prost(uint32, tag = "7")] pub executed_shares : u32,
+    #[prost(bool, tag = "8")]   pub completed       : bool
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum Event {
+    #[prost(message, tag = "1")] OrderAdded(OrderAdded),
+    #[prost(message, tag = "2")] OrderReduced(OrderReduced),
+    #[prost(message, tag = "3")] OrderDeleted(OrderDeleted),
+    #[prost(message, tag = "4")] OrderReplaced(OrderReplaced),
+    #[prost(message, tag = "5")] Trade(Trade),
+    #[prost(message, tag = "6")] TradeBroken(TradeBroken),
+    #[prost(message, tag = "7")] StatusChange(StatusChange),
+    #[prost(message, tag = "8")] AuctionInfo(AuctionInfo)
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MarketEvent {
+    #[prost(oneof = "Event", tags = "1, 2, 3, 4, 5, 6, 7, 8")]
+    pub event : Option<Event>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoError {
+    Decode(prost::DecodeError),
+    MissingEvent,        // the oneof wasn't set
+    InvalidChar(String)  // a side/auction type that isn't a single char
+}
+
+fn to_char( s : &str ) -> Result<char, ProtoError> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _               => Err(ProtoError::InvalidChar(String::from(s)))
+    }
+}
+
+fn to_opt_char( s : Option<String> ) -> Result<Option<char>, ProtoError> {
+    match s {
+        Some(s) => to_char(&s).map(Some),
+        None    => Ok(None)
+    }
+}
+
+impl<'a> From<&'a event::MarketEvent> for MarketEvent {
+    fn from( e : &'a event::MarketEvent ) -> MarketEvent {
+        let event = match *e {
+            event::MarketEvent::OrderAdded{ timestamp, order_id, side, price, shares, ref symbol } =>
+                Event::OrderAdded(OrderAdded{ timestamp, order_id, side : side.to_string(), price, shares,
+                                              symbol : symbol.clone() }),
+            event::MarketEvent::OrderReduced{ timestamp, order_id, shares } =>
+                Event::OrderReduced(OrderReduced{ timestamp, order_id, shares }),
+            event::MarketEvent::OrderDeleted{ timestamp, order_id } =>
+                Event::OrderDeleted(OrderDeleted{ timestamp, order_id }),
+            event::MarketEvent::OrderReplaced{ timestamp, order_id, new_order_id, price, shares } =>
+                Event::OrderReplaced(OrderReplaced{ timestamp, order_id, new_order_id, price, shares }),
+            event::MarketEvent::Trade{ timestamp, order_id, ref symbol, side, price, shares, remaining, exec_id } =>
+                Event::Trade(Trade{ timestamp, order_id, symbol : symbol.clone(), side : side.map(|c| c.to_string()),
+                                    price, shares, remaining, exec_id }),
+            event::MarketEvent::TradeBroken{ timestamp, exec_id } =>
+                Event::TradeBroken(TradeBroken{ timestamp, exec_id }),
+            event::MarketEvent::StatusChange{ timestamp, ref symbol, state } => {
+                let (state, other_state) = match state {
+                    TradingState::Halted       => (ProtoTradingState::Halted, String::new()),
+                    TradingState::Suspended    => (ProtoTradingState::Suspended, String::new()),
+                    TradingState::QuoteOnly    => (ProtoTradingState::QuoteOnly, String::new()),
+                    TradingState::Trading      => (ProtoTradingState::Trading, String::new()),
+                    TradingState::Cleared      => (ProtoTradingState::Cleared, String::new()),
+                    TradingState::EndOfSession => (ProtoTradingState::EndOfSession, String::new()),
+                    TradingState::Other(c)     => (ProtoTradingState::Other, c.to_string())
+                };
+                Event::StatusChange(StatusChange{ timestamp, symbol : symbol.clone(), state : state as i32, other_state })
+            },
+            event::MarketEvent::AuctionInfo{ timestamp, ref symbol, auction_type, price, buy_shares, sell_shares,
+                                             executed_shares, completed } =>
+                Event::AuctionInfo(AuctionInfo{ timestamp, symbol : symbol.clone(), auction_type : auction_type.to_string(),
+                                                price, buy_shares, sell_shares, executed_shares, completed })
+        };
+        MarketEvent{ event : Some(event) }
+    }
+}
+
+impl TryFrom<MarketEvent> for event::MarketEvent {
+    type Error = ProtoError;
+
+    fn try_from( e : MarketEvent ) -> Result<event::MarketEvent, ProtoError> {
+        let e = match e.event {
+            Some(Event::OrderAdded(m)) =>
+                event::MarketEvent::OrderAdded{ timestamp : m.timestamp, order_id : m.order_id, side : to_char(&m.side)?,
+                                                price : m.price, shares : m.shares, symbol : m.symbol },
+            Some(Event::OrderReduced(m)) =>
+                event::MarketEvent::OrderReduced{ timestamp : m.timestamp, order_id : m.order_id, shares : m.shares },
+            Some(Event::OrderDeleted(m)) =>
+                event::MarketEvent::OrderDeleted{ timestamp : m.timestamp, order_id : m.order_id },
+            Some(Event::OrderReplaced(m)) =>
+                event::MarketEvent::OrderReplaced{ timestamp : m.timestamp, order_id : m.order_id,
+                                                   new_order_id : m.new_order_id, price : m.price, shares : m.shares },
+            Some(Event::Trade(m)) =>
+                event::MarketEvent::Trade{ timestamp : m.timestamp, order_id : m.order_id, symbol : m.symbol,
+                                           side : to_opt_char(m.side)?, price : m.price, shares : m.shares,
+                                           remaining : m.remaining, exec_id : m.exec_id },
+            Some(Event::TradeBroken(m)) =>
+                event::MarketEvent::TradeBroken{ timestamp : m.timestamp, exec_id : m.exec_id },
+            Some(Event::StatusChange(m)) => {
+                let state = match ProtoTradingState::try_from(m.state).unwrap_or(ProtoTradingState::Other) {
+                    ProtoTradingState::Halted       => TradingState::Halted,
+                    ProtoTradingState::Suspended    => TradingState::Suspended,
+                    ProtoTradingState::QuoteOnly    => TradingState::QuoteOnly,
+                    ProtoTradingState::Trading      => TradingState::Trading,
+                    ProtoTradingState::Cleared      => TradingState::Cleared,
+                    ProtoTradingState::EndOfSession => TradingState::EndOfSession,
+                    ProtoTradingState::Other        => TradingState::Other(to_char(&m.other_state)?)
+                };
+                event::MarketEvent::StatusChange{ timestamp : m.timestamp, symbol : m.symbol, state }
+            },
+            Some(Event::AuctionInfo(m)) =>
+                event::MarketEvent::AuctionInfo{ timestamp : m.timestamp, symbol : m.symbol,
+                                                 auction_type : to_char(&m.auction_type)?, price : m.price,
+                                                 buy_shares : m.buy_shares, sell_shares : m.sell_shares,
+                                                 executed_shares : m.executed_shares, completed : m.completed },
+            None => return Err(ProtoError::MissingEvent)
+        };
+        Ok(e)
+    }
+}
+
+pub fn encode_event( e : &event::MarketEvent ) -> Vec<u8> {
+    MarketEvent::from(e).encode_to_vec()
+}
+
+pub fn decode_event( buf : &[u8] ) -> Result<event::MarketEvent, ProtoError> {
+    let msg = MarketEvent::decode(buf).map_err(ProtoError::Decode)?;
+    event::MarketEvent::try_from(msg)
+}
@@ -0,0 +1,183 @@
+
+// Python bindings, importable as rust_orderbook once built with the python-extension feature
+// (maturin, or copy the cdylib to rust_orderbook.so). Messages cross over as dicts in the
+// serde schema, the same one jsonl writes: a "type" key (eg. "add_order") plus the message's
+// fields, so parse_file() goes straight into pandas.DataFrame.
+//
+//   import rust_orderbook as ob
+//   book = ob.OrderBook("AAPL")
+//   for m in ob.parse_file("pitch.txt"):
+//       book.apply(m)
+//   book.best_bid(), book.bids(5)
+//
+// Prices are 4 implied decimals, as everywhere else.
+
+use std::io::BufRead;
+
+use pyo3::exceptions::PyAttributeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::types::PyList;
+use pythonize::depythonize;
+use pythonize::pythonize;
+
+use book::OrderBook;
+use book::Side;
+use compressed;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+
+fn parse_line( line : &str ) -> PyResult<BATSMessage> {
+    BATSMsgFactory::try_parse(line).ok_or_else(|| PyValueError::new_err(format!("not a PITCH message: {:?}", line)))
+}
+
+fn to_dict<'py>( py : Python<'py>, msg : &BATSMessage ) -> PyResult<Bound<'py, PyAny>> {
+    Ok(pythonize(py, msg)?)
+}
+
+// A parsed message, for when the dict isn't enough: fields read as attributes (m.symbol).
+#[pyclass(name = "Message", module = "rust_orderbook")]
+pub struct PyMessage {
+    msg : BATSMessage
+}
+
+#[pymethods]
+impl PyMessage {
+    #[new]
+    fn new( line : &str ) -> PyResult<PyMessage> {
+        parse_line(line).map(|msg| PyMessage{ msg })
+    }
+
+    #[getter(r#type)]
+    fn type_name( &self ) -> &'static str {
+        self.msg.type_name()
+    }
+
+    fn to_dict<'py>( &self, py : Python<'py> ) -> PyResult<Bound<'py, PyAny>> {
+        to_dict(py, &self.msg)
+    }
+
+    fn __getattr__<'py>( &self, py : Python<'py>, name : &str ) -> PyResult<Bound<'py, PyAny>> {
+        let dict = to_dict(py, &self.msg)?;
+        match dict.cast::<PyDict>()?.get_item(name)? {
+            Some(v) => Ok(v),
+            None    => Err(PyAttributeError::new_err(format!("{} has no field {}", self.msg.type_name(), name)))
+        }
+    }
+
+    fn __repr__( &self ) -> String {
+        format!("{:?}", self.msg)
+    }
+}
+
+#[pyfunction]
+fn parse<'py>( py : Python<'py>, line : &str ) -> PyResult<Bound<'py, PyAny>> {
+    to_dict(py, &parse_line(line)?)
+}
+
+// every message in a PITCH text file, one dict each.
+#[pyfunction]
+fn parse_file<'py>( py : Python<'py>, path : &str ) -> PyResult<Bound<'py, PyList>> {
+    let list = PyList::empty(py);
+    for (n, line) in compressed::open(path)?.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let msg = parse_line(&line).map_err(|_| PyValueError::new_err(format!("{}:{}: not a PITCH message", path, n + 1)))?;
+        list.append(to_dict(py, &msg)?)?;
+    }
+    Ok(list)
+}
+
+// A live book for one symbol, or for whatever it's given when the symbol is None.
+#[pyclass(name = "OrderBook", module = "rust_orderbook")]
+pub struct PyOrderBook {
+    book : OrderBook
+}
+
+fn parse_side( c : char ) -> PyResult<Side> {
+    match c {
+        'B' | 'S' => Ok(Side::from_char(c)),
+        _         => Err(PyValueError::new_err(format!("side must be 'B' or 'S', not {:?}", c)))
+    }
+}
+
+#[pymethods]
+impl PyOrderBook {
+    #[new]
+    #[pyo3(signature = (symbol = None))]
+    fn new( symbol : Option<&str> ) -> PyOrderBook {
+        PyOrderBook{ book : symbol.map_or_else(OrderBook::new, OrderBook::for_symbol) }
+    }
+
+    // takes a Message or a dict from parse()/parse_file(), returns whether the book changed.
+    fn apply( &mut self, msg : &Bound<'_, PyAny> ) -> PyResult<bool> {
+        if let Ok(m) = msg.cast::<PyMessage>() {
+            return Ok(self.book.apply(&m.borrow().msg));
+        }
+        let msg : BATSMessage = depythonize(msg)?;
+        Ok(self.book.apply(&msg))
+    }
+
+    // side is "B" or "S".
+    fn add_order( &mut self, order_id : u64, side : char, price : u64, shares : u32 ) -> PyResult<()> {
+        self.book.add_order(order_id, parse_side(side)?, price, shares);
+        Ok(())
+    }
+
+    // cancels or executes `shares` of the order, returns False for an unknown order.
+    fn reduce_order( &mut self, order_id : u64, shares : u32 ) -> bool {
+        self.book.reduce_order(order_id, shares)
+    }
+
+    fn delete_order( &mut self, order_id : u64 ) -> bool {
+        self.book.delete_order(order_id)
+    }
+
+    fn clear( &mut self ) {
+        self.book.clear();
+    }
+
+    fn best_bid( &self ) -> Option<u64> {
+        self.book.bids().next().map(|l| l.price())
+    }
+
+    fn best_ask( &self ) -> Option<u64> {
+        self.book.asks().next().map(|l| l.price())
+    }
+
+    fn bid_volume( &self, price : u64 ) -> u64 {
+        self.book.shares_at(Side::Bid, price)
+    }
+
+    fn ask_volume( &self, price : u64 ) -> u64 {
+        self.book.shares_at(Side::Ask, price)
+    }
+
+    // (price, volume) levels, best first.
+    #[pyo3(signature = (depth = None))]
+    fn bids( &self, depth : Option<usize> ) -> Vec<(u64, u64)> {
+        self.book.bids().take(depth.unwrap_or(usize::MAX)).map(|l| (l.price(), l.shares())).collect()
+    }
+
+    #[pyo3(signature = (depth = None))]
+    fn asks( &self, depth : Option<usize> ) -> Vec<(u64, u64)> {
+        self.book.asks().take(depth.unwrap_or(usize::MAX)).map(|l| (l.price(), l.shares())).collect()
+    }
+
+    fn __len__( &self ) -> usize {
+        self.book.len()
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "rust_orderbook")]
+pub fn init_module( m : &Bound<'_, PyModule> ) -> PyResult<()> {
+    m.add_class::<PyMessage>()?;
+    m.add_class::<PyOrderBook>()?;
+    m.add_function(wrap_pyfunction!(self::parse, m)?)?;
+    m.add_function(wrap_pyfunction!(self::parse_file, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+
+// Read-only view of live books, for the servers (gRPC, REST) that answer queries about
+// them without caring what keeps the books. Prices are 4 implied decimals.
+//
+// A BookManager behind a RwLock is one, the feed thread writing to it and the servers reading:
+//
+//   let books = Arc::new(RwLock::new(BookManager::new()));
+//   thread::spawn({ let books = books.clone(); move || for msg in msgs { books.write().unwrap().apply(&msg); } });
+//   serve(books, addr).await?;
+
+use std::sync::PoisonError;
+use std::sync::RwLock;
+
+use book::BookManager;
+use book::OrderBook;
+use book::Side;
+
+// levels as (price, volume), best first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BookLevels {
+    pub timestamp : u64,
+    pub bids      : Vec<(u64, u64)>,
+    pub asks      : Vec<(u64, u64)>
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BookStats {
+    pub timestamp     : u64,
+    pub orders        : usize,
+    pub bid_levels    : usize,
+    pub ask_levels    : usize,
+    pub trades        : u64,
+    pub traded_volume : u64,
+    pub last_price    : Option<u64>
+}
+
+pub trait BookSource : Send + Sync + 'static {
+    // the best `depth` levels of each side (every level for 0), None for an unknown symbol.
+    fn levels( &self, symbol : &str, depth : usize ) -> Option<BookLevels>;
+
+    fn stats( &self, _symbol : &str ) -> Option<BookStats> {
+        None
+    }
+
+    fn symbols( &self ) -> Vec<String> {
+        vec![]
+    }
+}
+
+fn best( book : &OrderBook, side : Side, depth : usize ) -> Vec<(u64, u64)> {
+    let depth = if depth == 0 { usize::MAX } else { depth };
+    book.levels(side).take(depth).map(|l| (l.price(), l.shares())).collect()
+}
+
+// A writer that panicked mid-message leaves the books as they were, still worth reading.
+impl BookSource for RwLock<BookManager> {
+    fn levels( &self, symbol : &str, depth : usize ) -> Option<BookLevels> {
+        let books = self.read().unwrap_or_else(PoisonError::into_inner);
+        let book = books.book(symbol)?;
+        Some(BookLevels{ timestamp : books.time(), bids : best(book, Side::Bid, depth), asks : best(book, Side::Ask, depth) })
+    }
+
+    fn stats( &self, symbol : &str ) -> Option<BookStats> {
+        let books = self.read().unwrap_or_else(PoisonError::into_inner);
+        let book = books.book(symbol)?;
+        let trades = books.trades().stats(symbol).cloned().unwrap_or_default();
+        Some(BookStats{ timestamp : books.time(), orders : book.len(), bid_levels : book.bids().len(), ask_levels : book.asks().len(),
+                        trades : trades.trades, traded_volume : trades.volume, last_price : trades.last_price })
+    }
+
+    fn symbols( &self ) -> Vec<String> {
+        self.read().unwrap_or_else(PoisonError::into_inner).symbols().into_iter().map(String::from).collect()
+    }
+}
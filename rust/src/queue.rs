@@ -0,0 +1,139 @@
+
+// Estimates where a hypothetical order would be in its level's queue. It joins behind
+// everything resting at its price when it's entered, then as the feed goes on orders ahead of
+// it cancel or execute and it moves up; once nothing's ahead, executions at the price would
+// have been against it instead. It's never in the book, so the feed's own orders still trade
+// as reported.
+//
+// An L3 book says which orders are ahead. An L2 one only says how many shares, and cancels are
+// taken to come from behind unless the level drops below what's ahead, which makes the
+// estimate pessimistic.
+//
+// Feed each message to on_message() before applying it to the book, so the order it's for can
+// still be looked up.
+
+use std::collections::HashMap;
+
+use book::OrderBook;
+use book::Side;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone)]
+pub struct QueuePosition {
+    side         : Side,
+    price        : u64,
+    shares       : u32,
+    // the orders ahead and their shares, only known in an L3 book
+    ahead        : HashMap<u64, u32>,
+    known        : bool,
+    shares_ahead : u64,
+    filled       : u32,
+    // (timestamp, shares ahead) each time it moved
+    history      : Vec<(u64, u64)>
+}
+
+impl QueuePosition {
+    pub fn join( book : &OrderBook, timestamp : u64, side : Side, price : u64, shares : u32 ) -> QueuePosition {
+        let level = book.level(side, price);
+        let ahead : HashMap<u64, u32> = level.into_iter().flat_map(|l| l.orders())
+                                             .filter_map(|id| book.order_shares(id).map(|s| (id, s))).collect();
+        let shares_ahead = level.map_or(0, |l| l.shares());
+        QueuePosition{ side, price, shares, known : ahead.len() == level.map_or(0, |l| l.order_count()), ahead,
+                       shares_ahead, filled : 0, history : vec![(timestamp, shares_ahead)] }
+    }
+
+    fn at_level( &self, book : &OrderBook, order_id : u64 ) -> bool {
+        book.get_order(order_id).is_some_and(|o| o.side == self.side && o.price == self.price)
+    }
+
+    pub fn on_message( &mut self, timestamp : u64, book : &OrderBook, msg : &BATSMessage ) {
+        let before = self.shares_ahead;
+        match *msg {
+            BATSMessage::OrderExecutedMsg(ref m) => self.executed(book, m.order_id, m.shares, None),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => self.executed(book, m.order_id, m.shares, None),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => self.executed(book, m.order_id, m.shares, Some(m.remaining_shares)),
+            BATSMessage::OrderCancelMsg(ref m) => self.cancelled(book, m.order_id, m.shares),
+            BATSMessage::ReduceSizeMsg(ref m)  => self.cancelled(book, m.order_id, m.shares),
+            BATSMessage::DeleteOrderMsg(ref m) => self.cancelled(book, m.order_id, book.order_shares(m.order_id).unwrap_or(0)),
+            // fewer shares at the same price keeps the order's place, anything else sends it to the back
+            BATSMessage::ModifyOrderMsg(ref m) => match book.get_order(m.order_id) {
+                Some(o) if o.price == m.price && m.shares <= o.shares && o.displayed == (m.display == 'Y') =>
+                    self.cancelled(book, m.order_id, o.shares - m.shares),
+                Some(o) => self.cancelled(book, m.order_id, o.shares),
+                None    => {}
+            },
+            // a reused id goes to the back, wherever it was
+            BATSMessage::AddOrderMsg(ref m)       => self.requeued(m.order_id),
+            BATSMessage::BinaryAddOrderMsg(ref m) => self.requeued(m.order_id),
+            BATSMessage::SymbolClearMsg(_) => {
+                self.ahead.clear();
+                self.shares_ahead = 0;
+            },
+            _ => {}
+        }
+        if self.shares_ahead != before {
+            self.history.push((timestamp, self.shares_ahead));
+        }
+    }
+
+    fn requeued( &mut self, order_id : u64 ) {
+        if let Some(s) = self.ahead.remove(&order_id) {
+            self.shares_ahead -= u64::from(s);
+        }
+    }
+
+    fn executed( &mut self, book : &OrderBook, order_id : u64, shares : u32, remaining : Option<u32> ) {
+        if let Some(s) = self.ahead.get_mut(&order_id) {
+            let left = remaining.unwrap_or_else(|| s.saturating_sub(shares)).min(*s);
+            self.shares_ahead -= u64::from(*s - left);
+            *s = left;
+            if left == 0 {
+                self.ahead.remove(&order_id);
+            }
+        } else if self.at_level(book, order_id) {
+            // an order behind executing means nothing's ahead any more. Without the ids, the
+            // shares ahead go first and the rest would have been ours.
+            let ahead = if self.known { self.shares_ahead } else { self.shares_ahead.min(u64::from(shares)) };
+            self.shares_ahead -= ahead;
+            self.ahead.clear();
+            let ours = u64::from(shares).saturating_sub(ahead) as u32;
+            self.filled = (self.filled + ours).min(self.shares);
+        }
+    }
+
+    fn cancelled( &mut self, book : &OrderBook, order_id : u64, shares : u32 ) {
+        if let Some(s) = self.ahead.get_mut(&order_id) {
+            let gone = shares.min(*s);
+            *s -= gone;
+            self.shares_ahead -= u64::from(gone);
+            if *s == 0 {
+                self.ahead.remove(&order_id);
+            }
+        } else if !self.known && self.at_level(book, order_id) {
+            let left = book.shares_at(self.side, self.price).saturating_sub(u64::from(shares));
+            self.shares_ahead = self.shares_ahead.min(left);
+        }
+    }
+
+    pub fn shares_ahead( &self ) -> u64 {
+        self.shares_ahead
+    }
+
+    // orders ahead, None for an L2 book.
+    pub fn orders_ahead( &self ) -> Option<usize> {
+        if self.known { Some(self.ahead.len()) } else { None }
+    }
+
+    // shares that would have executed against the order.
+    pub fn filled( &self ) -> u32 {
+        self.filled
+    }
+
+    pub fn is_filled( &self ) -> bool {
+        self.filled == self.shares
+    }
+
+    pub fn history( &self ) -> &[(u64, u64)] {
+        &self.history
+    }
+}
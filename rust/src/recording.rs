@@ -0,0 +1,253 @@
+
+// A session's decoded events on disk, for going over it again without parsing the PITCH:
+//
+//   let mut recorder = Recorder::create("20251014.obr")?;
+//   for msg in msgs { recorder.message(&msg)?; }
+//   recorder.finish()?;
+//
+//   let mut recording = Recording::open("20251014.obr")?;
+//   recording.seek(time_of_day(10, 30, 0))?;
+//   for event in recording { ... }
+//
+// The file is a header (the magic then a version), each event framed as a u16 length and
+// MarketEvent::to_bytes, a zero length after the last, then the index: a (timestamp, offset)
+// entry for every so many events, its entry count, and its own offset and the magic again to
+// find it by from the end. A file that was never finished has no index but reads all the same.
+// The index takes timestamps as being in order, which a single unit's are.
+
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+use binary::TimestampComposer;
+use bounded::EventSink;
+use event::MarketEvent;
+use messages::BATSMessage;
+
+const MAGIC   : [u8; 4] = *b"OBRC";
+const VERSION : u16 = 1;
+// the magic and version
+const HEADER  : u64 = 6;
+// the index's offset and the magic
+const TRAILER : u64 = 12;
+
+fn invalid( what : &str ) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+pub struct Recorder<W : Write> {
+    writer   : W,
+    clock    : TimestampComposer,
+    // where the next frame starts
+    offset   : u64,
+    every    : u64,
+    events   : u64,
+    index    : Vec<(u64, u64)>,
+    finished : bool
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create<P : AsRef<Path>>( path : P ) -> io::Result<Recorder<BufWriter<File>>> {
+        Recorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W : Write> Recorder<W> {
+    pub fn new( mut writer : W ) -> io::Result<Recorder<W>> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        Ok(Recorder{ writer, clock : TimestampComposer::new(), offset : HEADER, every : 4096, events : 0, index : Vec::new(),
+                     finished : false })
+    }
+
+    // how many events apart the index entries are, 4096 by default.
+    pub fn index_every( mut self, events : u64 ) -> Recorder<W> {
+        self.every = events.max(1);
+        self
+    }
+
+    pub fn record( &mut self, event : &MarketEvent ) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::other("the recording is finished"));
+        }
+        let bytes = event.to_bytes();
+        if self.events.is_multiple_of(self.every) {
+            self.index.push((event.timestamp(), self.offset));
+        }
+        self.writer.write_all(&(bytes.len() as u16).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.offset += 2 + bytes.len() as u64;
+        self.events += 1;
+        Ok(())
+    }
+
+    // the message's event, if it has one, timestamped by the recorder's own clock.
+    pub fn message( &mut self, msg : &BATSMessage ) -> io::Result<()> {
+        let timestamp = self.clock.compose(msg);
+        match MarketEvent::from_bats(msg, timestamp) {
+            Some(event) => self.record(&event),
+            None        => Ok(())
+        }
+    }
+
+    pub fn events( &self ) -> u64 {
+        self.events
+    }
+
+    // ends the events and writes the index. Once only, later calls do nothing.
+    pub fn finish( &mut self ) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        let at = self.offset + 2;
+        self.writer.write_all(&0u16.to_le_bytes())?;
+        for &(timestamp, offset) in self.index.iter() {
+            self.writer.write_all(&timestamp.to_le_bytes())?;
+            self.writer.write_all(&offset.to_le_bytes())?;
+        }
+        self.writer.write_all(&(self.index.len() as u64).to_le_bytes())?;
+        self.writer.write_all(&at.to_le_bytes())?;
+        self.writer.write_all(&MAGIC)?;
+        self.writer.flush()
+    }
+
+    pub fn into_inner( mut self ) -> io::Result<W> {
+        self.finish()?;
+        let Recorder{ writer, .. } = self;
+        Ok(writer)
+    }
+}
+
+impl<W : Write + Send> EventSink for Recorder<W> {
+    fn event( &mut self, event : &MarketEvent ) -> io::Result<()> {
+        self.record(event)
+    }
+
+    fn finish( &mut self ) -> io::Result<()> {
+        Recorder::finish(self)
+    }
+}
+
+fn read_u64<R : Read>( r : &mut R ) -> io::Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+// The events of a recording in the order they were recorded.
+pub struct Recording<R> {
+    reader : R,
+    index  : Vec<(u64, u64)>,
+    // held back by a seek, the first at or after its time
+    peeked : Option<MarketEvent>,
+    done   : bool
+}
+
+impl Recording<BufReader<File>> {
+    pub fn open<P : AsRef<Path>>( path : P ) -> io::Result<Recording<BufReader<File>>> {
+        Recording::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R : Read + Seek> Recording<R> {
+    pub fn new( mut reader : R ) -> io::Result<Recording<R>> {
+        let mut header = [0u8; HEADER as usize];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(invalid("not a recording"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(invalid(&format!("recording version {} isn't supported", version)));
+        }
+        let index = Recording::read_index(&mut reader)?;
+        reader.seek(SeekFrom::Start(HEADER))?;
+        Ok(Recording{ reader, index, peeked : None, done : false })
+    }
+
+    // empty for a recording that was never finished.
+    fn read_index( reader : &mut R ) -> io::Result<Vec<(u64, u64)>> {
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < HEADER + TRAILER + 8 {
+            return Ok(Vec::new());
+        }
+        reader.seek(SeekFrom::End(-(TRAILER as i64)))?;
+        let at = read_u64(reader)?;
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC || at < HEADER || at > end - TRAILER - 8 {
+            return Ok(Vec::new());
+        }
+        reader.seek(SeekFrom::End(-(TRAILER as i64) - 8))?;
+        let n = read_u64(reader)?;
+        if n.checked_mul(16) != Some(end - TRAILER - 8 - at) {
+            return Err(invalid("the recording's index is damaged"));
+        }
+        reader.seek(SeekFrom::Start(at))?;
+        (0..n).map(|_| Ok((read_u64(reader)?, read_u64(reader)?))).collect()
+    }
+
+    // (timestamp, offset) for every so many events.
+    pub fn index( &self ) -> &[(u64, u64)] {
+        &self.index
+    }
+
+    // the next events are from `timestamp` on. Goes by the index as far as it can, reading the
+    // rest of the way.
+    pub fn seek( &mut self, timestamp : u64 ) -> io::Result<()> {
+        let i = self.index.partition_point(|&(t, _)| t < timestamp);
+        let start = if i == 0 { HEADER } else { self.index[i - 1].1 };
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.peeked = None;
+        self.done = false;
+        while let Some(event) = self.read()? {
+            if event.timestamp() >= timestamp {
+                self.peeked = Some(event);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn read( &mut self ) -> io::Result<Option<MarketEvent>> {
+        if self.done {
+            return Ok(None);
+        }
+        let mut len = [0u8; 2];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {},
+            // an unfinished recording just stops
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => { self.done = true; return Ok(None); },
+            Err(e) => return Err(e)
+        }
+        let len = u16::from_le_bytes(len) as usize;
+        if len == 0 {
+            self.done = true;
+            return Ok(None);
+        }
+        let mut frame = vec![0u8; len];
+        self.reader.read_exact(&mut frame)?;
+        MarketEvent::read_from(&mut frame.as_slice())?.map(Some).ok_or_else(|| invalid("empty event frame"))
+    }
+}
+
+impl<R : Read + Seek> Iterator for Recording<R> {
+    type Item = io::Result<MarketEvent>;
+
+    fn next( &mut self ) -> Option<io::Result<MarketEvent>> {
+        if let Some(event) = self.peeked.take() {
+            return Some(Ok(event));
+        }
+        match self.read() {
+            Ok(event) => event.map(Ok),
+            Err(e)    => { self.done = true; Some(Err(e)) }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+
+// Getting a unit's book right from a standing start or after a gap too wide to ride out: the
+// live packets are held back while a spin snapshot (every order resting as of some sequence
+// number, sent as AddOrder messages) is fetched, the books are rebuilt from it, and what was
+// held back after its sequence is replayed on top before going live.
+//
+//   let mut recovery = Recovery::new(unit);
+//   while let Some(packet) = feed.recv_packet()? {
+//       match recovery.packet(packet, &mut books) {
+//           Some(RecoveryEvent::SnapshotNeeded{ .. }) => spin.request(unit)?,
+//           ...
+//       }
+//       if let Some(snapshot) = spin.poll()? {
+//           recovery.snapshot(snapshot, &mut books);
+//       }
+//   }
+//
+// Fetching the snapshot is left to the caller, the coordinator only says when one's needed
+// and what to do with it when it comes. The books are the unit's own: applying a snapshot
+// resets them, so units sharing a BookManager would wipe each other out.
+
+use std::collections::VecDeque;
+
+use binary::split_packet;
+use book::BookManager;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+
+// A spin: the unit's book as of `sequence`, the last message it takes in.
+#[derive(Debug)]
+pub struct Snapshot {
+    pub sequence : u32,
+    pub messages : Vec<BATSMessage>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeedReason {
+    Startup,
+    Gap { expected : u32, received : u32 },
+    // the held back messages don't go back as far as the snapshot, the start was dropped
+    // to stay under the limit or missed on the way in
+    SnapshotTooOld { snapshot : u32, buffered_from : u32 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryEvent {
+    SnapshotNeeded { unit : u8, reason : NeedReason },
+    // live from `sequence` on, having replayed `replayed` held back messages
+    Live { unit : u8, sequence : u32, replayed : usize }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryStats {
+    pub recoveries  : u64,   // gone live from a snapshot
+    pub gaps        : u64,
+    pub buffered    : u64,
+    pub dropped     : u64,   // held back past the limit
+    pub replayed    : u64,
+    pub duplicates  : u64,
+    pub bad_packets : u64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // the sequence number of the next message
+    Live(u32),
+    // whether the snapshot's been asked for
+    Recovering(bool)
+}
+
+#[derive(Debug)]
+pub struct Recovery {
+    unit   : u8,
+    state  : State,
+    // None for a sequence number whose message didn't decode, so it isn't taken for a gap
+    buffer : VecDeque<(u32, Option<BATSMessage>)>,
+    limit  : usize,
+    stats  : RecoveryStats
+}
+
+impl Recovery {
+    // recovering, the first packet asking for a snapshot.
+    pub fn new( unit : u8 ) -> Recovery {
+        Recovery{ unit, state : State::Recovering(false), buffer : VecDeque::new(), limit : 1_000_000, stats : RecoveryStats::default() }
+    }
+
+    // live from `next`, for a feed joined at the start of the day (sequence 1).
+    pub fn live( unit : u8, next : u32 ) -> Recovery {
+        Recovery{ state : State::Live(next), ..Recovery::new(unit) }
+    }
+
+    // how many messages are held back at most, a million by default. Past it the oldest go,
+    // and a snapshot from before them can't be used.
+    pub fn limit( mut self, limit : usize ) -> Recovery {
+        self.limit = limit.max(1);
+        self
+    }
+
+    pub fn unit( &self ) -> u8 {
+        self.unit
+    }
+
+    pub fn is_live( &self ) -> bool {
+        matches!(self.state, State::Live(_))
+    }
+
+    // the sequence number of the next message expected, while live.
+    pub fn next_sequence( &self ) -> Option<u32> {
+        match self.state {
+            State::Live(next)    => Some(next),
+            State::Recovering(_) => None
+        }
+    }
+
+    pub fn buffered( &self ) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn stats( &self ) -> RecoveryStats {
+        self.stats
+    }
+
+    // starts over, eg. when the caller's lost the books some other way.
+    pub fn recover( &mut self ) {
+        self.state = State::Recovering(false);
+        self.buffer.clear();
+    }
+
+    // a datagram off the feed. Packets for other units are ignored, as are heartbeats.
+    pub fn packet( &mut self, data : &[u8], books : &mut BookManager ) -> Option<RecoveryEvent> {
+        let (header, parts) = match split_packet(data) {
+            Ok(p)  => p,
+            Err(_) => { self.stats.bad_packets += 1; return None; }
+        };
+        if header.unit != self.unit || parts.is_empty() {
+            return None;
+        }
+        // numbered before decoding, a type we don't decode still takes up its sequence number
+        let msgs = parts.into_iter().enumerate().filter_map(|(i, m)| match BATSMsgFactory::try_parse_binary(m) {
+            Ok(Some(msg)) => Some((header.sequence.wrapping_add(i as u32), msg)),
+            _             => None
+        }).collect();
+        self.messages(header.sequence, header.sequence.wrapping_add(u32::from(header.count)), msgs, books)
+    }
+
+    // the decoded messages of a packet numbered from `first` to before `end`, each with its
+    // sequence number. Numbers in the range without one are messages that didn't decode, held
+    // back as such. Sequence 0 is unsequenced, applied as it comes while live.
+    pub fn messages( &mut self, first : u32, end : u32, msgs : Vec<(u32, BATSMessage)>, books : &mut BookManager ) -> Option<RecoveryEvent> {
+        if first == 0 {
+            if self.is_live() {
+                for (_, msg) in msgs.iter() {
+                    books.apply_unit(self.unit, msg);
+                }
+            }
+            return None;
+        }
+        let mut event = None;
+        let mut msgs = msgs.into_iter();
+        if let State::Live(next) = self.state {
+            if end <= next {
+                self.stats.duplicates += 1;
+                return None;
+            }
+            if first > next {
+                self.stats.gaps += 1;
+                self.state = State::Recovering(false);
+                event = Some(self.needed(NeedReason::Gap{ expected : next, received : first }));
+            } else {
+                for (seq, msg) in msgs.by_ref() {
+                    if seq >= next {
+                        books.apply_unit(self.unit, &msg);
+                    }
+                }
+                self.state = State::Live(end);
+                return None;
+            }
+        }
+        let mut msgs = msgs.peekable();
+        for seq in first..end {
+            let msg = if msgs.peek().is_some_and(|&(s, _)| s == seq) { msgs.next().map(|(_, m)| m) } else { None };
+            if self.buffer.back().is_some_and(|&(last, _)| seq <= last) {
+                continue;
+            }
+            self.buffer.push_back((seq, msg));
+            self.stats.buffered += 1;
+            if self.buffer.len() > self.limit {
+                self.buffer.pop_front();
+                self.stats.dropped += 1;
+            }
+        }
+        if self.state == State::Recovering(false) {
+            event = event.or_else(|| Some(self.needed(NeedReason::Startup)));
+        }
+        event
+    }
+
+    fn needed( &mut self, reason : NeedReason ) -> RecoveryEvent {
+        self.state = State::Recovering(true);
+        RecoveryEvent::SnapshotNeeded{ unit : self.unit, reason }
+    }
+
+    // rebuilds the books from the snapshot and replays what's held back after it, going live
+    // if the two meet. If they don't another snapshot's needed. Ignored while live.
+    pub fn snapshot( &mut self, snapshot : Snapshot, books : &mut BookManager ) -> Option<RecoveryEvent> {
+        if self.is_live() {
+            return None;
+        }
+        while self.buffer.front().is_some_and(|&(seq, _)| seq <= snapshot.sequence) {
+            self.buffer.pop_front();
+        }
+        let mut next = snapshot.sequence.wrapping_add(1);
+        if let Some(&(from, _)) = self.buffer.front() {
+            if from != next {
+                return Some(self.needed(NeedReason::SnapshotTooOld{ snapshot : snapshot.sequence, buffered_from : from }));
+            }
+        }
+        books.reset();
+        for msg in snapshot.messages.iter() {
+            books.apply_unit(self.unit, msg);
+        }
+        let mut replayed = 0;
+        while let Some((seq, msg)) = self.buffer.pop_front() {
+            if seq != next {
+                // a gap inside what was held back, the books are only good up to it
+                self.buffer.push_front((seq, msg));
+                self.stats.gaps += 1;
+                self.stats.replayed += replayed as u64;
+                return Some(self.needed(NeedReason::Gap{ expected : next, received : seq }));
+            }
+            if let Some(msg) = msg {
+                books.apply_unit(self.unit, &msg);
+                replayed += 1;
+            }
+            next = seq.wrapping_add(1);
+        }
+        self.stats.replayed += replayed as u64;
+        self.stats.recoveries += 1;
+        self.state = State::Live(next);
+        Some(RecoveryEvent::Live{ unit : self.unit, sequence : next, replayed })
+    }
+}
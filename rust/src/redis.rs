@@ -0,0 +1,99 @@
+
+// Publishes top of book updates to Redis, one channel per symbol (bbo.<symbol>) so web
+// backends can SUBSCRIBE or PSUBSCRIBE bbo.* without linking the crate. Payloads are JSON:
+//
+//   {"symbol":"AAPL","timestamp":34200000000000,"bid":183.18,"bid_size":300,"ask":183.2,"ask_size":100}
+//
+// an empty side has null price and size. Updates that don't change a symbol's BBO aren't
+// published. The client only speaks the bit of RESP PUBLISH needs.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+
+use book::Bbo;
+use book::Quote;
+use messages::format_price;
+
+fn json_side( out : &mut String, name : &str, side : Option<Quote> ) {
+    match side {
+        Some(q) => out.push_str(&format!(",\"{}\":{},\"{}_size\":{}", name, format_price(q.price), name, q.shares)),
+        None    => out.push_str(&format!(",\"{}\":null,\"{}_size\":null", name, name))
+    }
+}
+
+// what the payload shows of a side, the order count isn't in it.
+fn shown( side : Option<Quote> ) -> Option<(u64, u64)> {
+    side.map(|q| (q.price, q.shares))
+}
+
+pub fn bbo_json( timestamp : u64, symbol : &str, bbo : Bbo ) -> String {
+    let mut out = format!("{{\"symbol\":\"{}\",\"timestamp\":{}", symbol.trim_end(), timestamp);
+    json_side(&mut out, "bid", bbo.bid);
+    json_side(&mut out, "ask", bbo.ask);
+    out.push('}');
+    out
+}
+
+pub struct RedisPublisher<S : Read + Write> {
+    stream : BufReader<S>,
+    prefix : String,
+    last   : HashMap<String, Bbo>
+}
+
+impl RedisPublisher<TcpStream> {
+    pub fn connect( addr : &str ) -> io::Result<RedisPublisher<TcpStream>> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(RedisPublisher::new(stream))
+    }
+}
+
+impl<S : Read + Write> RedisPublisher<S> {
+    pub fn new( stream : S ) -> RedisPublisher<S> {
+        RedisPublisher{ stream : BufReader::new(stream), prefix : String::from("bbo."), last : HashMap::new() }
+    }
+
+    // channel name prefix, "bbo." by default.
+    pub fn prefix( mut self, prefix : &str ) -> RedisPublisher<S> {
+        self.prefix = String::from(prefix);
+        self
+    }
+
+    // PUBLISH, returning how many clients received the message.
+    pub fn publish( &mut self, channel : &str, payload : &str ) -> io::Result<u64> {
+        let cmd = format!("*3\r\n$7\r\nPUBLISH\r\n${}\r\n{}\r\n${}\r\n{}\r\n", channel.len(), channel,
+                          payload.len(), payload);
+        self.stream.get_mut().write_all(cmd.as_bytes())?;
+        let mut reply = String::new();
+        self.stream.read_line(&mut reply)?;
+        let reply = reply.trim_end();
+        match reply.chars().next() {
+            Some(':') => reply[1..].parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, String::from(reply))),
+            Some('-') => Err(io::Error::other(String::from(&reply[1..]))),
+            _         => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected reply: {}", reply)))
+        }
+    }
+
+    // publishes the symbol's BBO if it changed, returns whether it did.
+    pub fn publish_bbo( &mut self, timestamp : u64, symbol : &str, bbo : Bbo ) -> io::Result<bool> {
+        let symbol = symbol.trim_end();
+        if let Some(last) = self.last.get(symbol) {
+            if shown(last.bid) == shown(bbo.bid) && shown(last.ask) == shown(bbo.ask) {
+                return Ok(false);
+            }
+        }
+        let channel = format!("{}{}", self.prefix, symbol);
+        self.publish(&channel, &bbo_json(timestamp, symbol, bbo))?;
+        self.last.insert(String::from(symbol), bbo);
+        Ok(true)
+    }
+
+    pub fn into_inner( self ) -> S {
+        self.stream.into_inner()
+    }
+}
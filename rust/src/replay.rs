@@ -0,0 +1,229 @@
+
+// Replays a day's messages into a BookManager up to a time of day, eg. to look at the books as
+// they were at 10:31:02, then on from there:
+//
+//   let msgs = PitchReader::new(reader).filter_map(|e| match e { FeedEvent::Message(m) => Some(m), _ => None });
+//   let mut replay = Replay::new(msgs);
+//   replay.replay_until(time_of_day(10, 31, 2));
+//   replay.books().book("AAPL")
+//
+// Times are nanoseconds past midnight, from TimestampComposer, so text and binary captures
+// both work.
+//
+// Paced hands the messages on instead, as far apart as they originally came (or some multiple
+// faster), to test what's downstream against the feed's own bursts and lulls:
+//
+//   for msg in Paced::new(msgs, Speed::Times(10.0))? { publisher.send(&msg)?; }
+
+use std::error;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use binary::TimestampComposer;
+use book::BookManager;
+use messages::BATSMessage;
+
+pub fn time_of_day( hours : u32, minutes : u32, seconds : u32 ) -> u64 {
+    (u64::from(hours) * 3600 + u64::from(minutes) * 60 + u64::from(seconds)) * 1_000_000_000
+}
+
+pub struct Replay<I : Iterator<Item = BATSMessage>> {
+    msgs    : I,
+    clock   : TimestampComposer,
+    books   : BookManager,
+    // read but after the time last replayed to
+    next    : Option<(u64, BATSMessage)>,
+    time    : u64,
+    applied : u64,
+    done    : bool
+}
+
+impl<I : Iterator<Item = BATSMessage>> Replay<I> {
+    pub fn new( msgs : I ) -> Replay<I> {
+        Replay::with_books(msgs, BookManager::new())
+    }
+
+    // for a manager set up beforehand, eg. with listeners or another book mode.
+    pub fn with_books( msgs : I, books : BookManager ) -> Replay<I> {
+        Replay{ msgs, clock : TimestampComposer::new(), books, next : None, time : 0, applied : 0, done : false }
+    }
+
+    fn peek_time( &mut self ) -> Option<u64> {
+        if self.next.is_none() {
+            let clock = &mut self.clock;
+            self.next = self.msgs.next().map(|m| (clock.compose(&m), m));
+        }
+        self.next.as_ref().map(|&(t, _)| t)
+    }
+
+    // applies every message up to and including `timestamp`, returns how many. A time before
+    // where the replay already is applies nothing.
+    pub fn replay_until( &mut self, timestamp : u64 ) -> u64 {
+        let mut applied = 0;
+        while let Some(t) = self.peek_time() {
+            if t > timestamp {
+                break;
+            }
+            if let Some((_, msg)) = self.next.take() {
+                self.books.apply(&msg);
+                self.time = t;
+                applied += 1;
+            }
+        }
+        self.done = self.next.is_none();
+        self.applied += applied;
+        applied
+    }
+
+    // the rest of the messages.
+    pub fn replay_all( &mut self ) -> u64 {
+        self.replay_until(u64::MAX)
+    }
+
+    pub fn books( &self ) -> &BookManager {
+        &self.books
+    }
+
+    pub fn books_mut( &mut self ) -> &mut BookManager {
+        &mut self.books
+    }
+
+    pub fn into_books( self ) -> BookManager {
+        self.books
+    }
+
+    // when the last applied message happened.
+    pub fn time( &self ) -> u64 {
+        self.time
+    }
+
+    // the time of the next message to apply, None once they've all been applied.
+    pub fn next_time( &mut self ) -> Option<u64> {
+        self.peek_time()
+    }
+
+    pub fn applied( &self ) -> u64 {
+        self.applied
+    }
+
+    // once a replay ran out of messages.
+    pub fn is_done( &self ) -> bool {
+        self.done
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Speed {
+    #[default]
+    Realtime,
+    // 10.0 for ten times as fast, 0.5 for half
+    Times(f64),
+    // no waiting at all
+    Max
+}
+
+// a Speed::Times that isn't a positive, finite multiple.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BadSpeed {
+    pub times : f64
+}
+
+impl fmt::Display for BadSpeed {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        write!(f, "can't replay {} times as fast", self.times)
+    }
+}
+
+impl error::Error for BadSpeed {}
+
+// An iterator handing on each message once as long has passed since the first as passed on the
+// feed, at the speed given. A message timestamped before the one ahead of it (units merged, or
+// the clock rolling over) goes straight out.
+pub struct Paced<I : Iterator<Item = BATSMessage>> {
+    msgs    : I,
+    clock   : TimestampComposer,
+    speed   : Speed,
+    // the original gap allowed at most, eg. to skip over a lunch break
+    max_gap : Option<u64>,
+    // the feed time and the instant it's been put at
+    start   : Option<(u64, Instant)>,
+    last    : u64,
+    lag     : Duration
+}
+
+impl<I : Iterator<Item = BATSMessage>> Paced<I> {
+    pub fn new( msgs : I, speed : Speed ) -> Result<Paced<I>, BadSpeed> {
+        if let Speed::Times(times) = speed {
+            if !(times > 0.0 && times.is_finite()) {
+                return Err(BadSpeed{ times });
+            }
+        }
+        Ok(Paced{ msgs, clock : TimestampComposer::new(), speed, max_gap : None, start : None, last : 0, lag : Duration::from_secs(0) })
+    }
+
+    // the longest wait between two messages, in feed time (before speeding up).
+    pub fn max_gap( mut self, gap : Duration ) -> Paced<I> {
+        self.max_gap = Some(gap.as_nanos() as u64);
+        self
+    }
+
+    // how late the last message went out, eg. when downstream can't keep up.
+    pub fn lag( &self ) -> Duration {
+        self.lag
+    }
+
+    // the feed time of the last message handed on.
+    pub fn time( &self ) -> u64 {
+        self.last
+    }
+
+    // when a message `elapsed` feed nanoseconds after the start is due.
+    fn due( &self, started : Instant, elapsed : u64 ) -> Instant {
+        let scaled = match self.speed {
+            Speed::Realtime => elapsed as f64,
+            Speed::Times(x) => elapsed as f64 / x,
+            Speed::Max      => 0.0
+        };
+        started + Duration::from_nanos(scaled as u64)
+    }
+
+    fn wait_until( &mut self, due : Instant ) {
+        loop {
+            let now = Instant::now();
+            if now >= due {
+                self.lag = now - due;
+                return;
+            }
+            // sleep through most of it, the scheduler's too coarse for the last bit
+            let left = due - now;
+            if left > Duration::from_millis(2) {
+                thread::sleep(left - Duration::from_millis(1));
+            } else {
+                thread::yield_now();
+            }
+        }
+    }
+}
+
+impl<I : Iterator<Item = BATSMessage>> Iterator for Paced<I> {
+    type Item = BATSMessage;
+
+    fn next( &mut self ) -> Option<BATSMessage> {
+        let msg = self.msgs.next()?;
+        let t = self.clock.compose(&msg);
+        let (mut origin, started) = *self.start.get_or_insert_with(|| (t, Instant::now()));
+        if t > self.last && self.speed != Speed::Max {
+            // the quiet past the allowance comes off the schedule for good
+            if let Some(gap) = self.max_gap.filter(|&g| self.last > 0 && t - self.last > g) {
+                origin += t - self.last - gap;
+                self.start = Some((origin, started));
+            }
+            let due = self.due(started, t - origin);
+            self.wait_until(due);
+        }
+        self.last = self.last.max(t);
+        Some(msg)
+    }
+}
@@ -0,0 +1,116 @@
+
+// Encodes a message with its wire encoder and parses it back, reporting the fields that
+// didn't survive. Meant for integration tests and fuzzers asserting parse(encode(m)) == m.
+// Text fields that are too long get cut by the encoder and show up as diffs, numbers that
+// overflow their width shift the rest of the message and normally fail to parse.
+
+use std::fmt;
+use std::result::Result;
+
+use binary::LoginMsg;
+use binary::LoginResponseMsg;
+use messages::AddOrderMsg;
+use messages::AuctionSummaryMsg;
+use messages::AuctionUpdateMsg;
+use messages::BATSMessage;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+use messages::RetailPriceImproveMsg;
+use messages::SymbolClearMsg;
+use messages::TradeBreakMsg;
+use messages::TradeMsg;
+use messages::TradingStatusMsg;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field    : &'static str,
+    pub original : String,
+    pub reparsed : String
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.original, self.reparsed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundtripError {
+    Unsupported,   // there's no encoder for the message
+    Parse(String)  // the encoded message didn't parse at all
+}
+
+trait Fields {
+    fn fields( &self ) -> Vec<(&'static str, String)>;
+}
+
+macro_rules! create_fields_impl {
+    ($objname : ident, $($field : ident),+) => (
+        impl Fields for $objname {
+            fn fields( &self ) -> Vec<(&'static str, String)> {
+                vec![$((stringify!($field), format!("{:?}", self.$field))),+]
+            }
+        }
+    )
+}
+
+create_fields_impl!(AuctionSummaryMsg, timestamp, msg_type, symbol, auction_type, price, shares);
+create_fields_impl!(AddOrderMsg, timestamp, msg_type, order_id, side, shares, symbol, price, display, part_id);
+create_fields_impl!(AuctionUpdateMsg, timestamp, msg_type, symbol, auction_type, reference_price, buyshares,
+                    sellshares, indicative_price, auction_only_price);
+create_fields_impl!(OrderCancelMsg, timestamp, msg_type, order_id, shares);
+create_fields_impl!(OrderExecutedMsg, timestamp, msg_type, order_id, shares, exec_id);
+create_fields_impl!(RetailPriceImproveMsg, timestamp, msg_type, symbol, retail_price_improve);
+create_fields_impl!(TradeBreakMsg, timestamp, msg_type, exec_id);
+create_fields_impl!(TradeMsg, timestamp, msg_type, order_id, side, shares, symbol, price, exec_id);
+create_fields_impl!(TradingStatusMsg, timestamp, msg_type, symbol, halt_status, reg_sho_action, reserved1,
+                    reserved2, halt_reason, halt_flag);
+create_fields_impl!(SymbolClearMsg, timestamp, msg_type, symbol);
+create_fields_impl!(LoginMsg, msg_type, session_sub_id, username, password);
+create_fields_impl!(LoginResponseMsg, msg_type, status);
+
+fn diff<T : Fields>( original : &T, reparsed : &T ) -> Vec<FieldDiff> {
+    original.fields().into_iter().zip(reparsed.fields())
+        .filter(|(a, b)| a.1 != b.1)
+        .map(|(a, b)| FieldDiff{ field : a.0, original : a.1, reparsed : b.1 })
+        .collect()
+}
+
+fn check<T, E>( original : &T, reparsed : Result<T, E> ) -> Result<Vec<FieldDiff>, RoundtripError>
+    where T : Fields, E : fmt::Debug {
+    match reparsed {
+        Ok(m)  => Ok(diff(original, &m)),
+        Err(e) => Err(RoundtripError::Parse(format!("{:?}", e)))
+    }
+}
+
+// an empty Vec means the message round trips exactly.
+pub fn roundtrip( msg : &BATSMessage ) -> Result<Vec<FieldDiff>, RoundtripError> {
+    match *msg {
+        BATSMessage::AuctionSummaryMsg(ref m)     => check(m, AuctionSummaryMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::AddOrderMsg(ref m)           => check(m, AddOrderMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::AuctionUpdateMsg(ref m)      => check(m, AuctionUpdateMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::OrderCancelMsg(ref m)        => check(m, OrderCancelMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::OrderExecutedMsg(ref m)      => check(m, OrderExecutedMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::RetailPriceImproveMsg(ref m) => check(m, RetailPriceImproveMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::TradeBreakMsg(ref m)         => check(m, TradeBreakMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::TradeMsg(ref m)              => check(m, TradeMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::TradingStatusMsg(ref m)      => check(m, TradingStatusMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::SymbolClearMsg(ref m)        => check(m, SymbolClearMsg::parse_msg(&m.to_pitch_string())),
+        BATSMessage::LoginMsg(ref m)              => check(m, LoginMsg::parse_binary(&m.to_bytes())),
+        BATSMessage::LoginResponseMsg(ref m)      => check(m, LoginResponseMsg::parse_binary(&m.to_bytes())),
+        _ => Err(RoundtripError::Unsupported)
+    }
+}
+
+// panics with the list of diffs, for use in tests.
+pub fn assert_roundtrip( msg : &BATSMessage ) {
+    match roundtrip(msg) {
+        Ok(ref diffs) if diffs.is_empty() => {},
+        Ok(diffs) => {
+            let s : Vec<String> = diffs.iter().map(|d| d.to_string()).collect();
+            panic!("{:?} does not round trip: {}", msg, s.join(", "));
+        },
+        Err(e) => panic!("{:?} does not round trip: {:?}", msg, e)
+    }
+}
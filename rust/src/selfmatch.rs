@@ -0,0 +1,170 @@
+
+// Looks for a participant trading with itself, from the part ids on attributed adds. Two things
+// get flagged:
+//
+//  - Crossing: an add priced through the same participant's own resting order on the other
+//    side, which would have matched it.
+//  - Wash: the participant's orders executing on both sides of one symbol within the window.
+//
+// Only attributed orders ('d' adds, or expanded binary ones, with a part id) are followed, the rest of the feed doesn't
+// say whose they are. Times are nanoseconds past midnight, from TimestampComposer.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use binary::TimestampComposer;
+use book::Side;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SelfMatchKind {
+    Crossing,
+    Wash
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SelfMatch {
+    pub kind       : SelfMatchKind,
+    pub timestamp  : u64,
+    pub symbol     : String,
+    pub part_id    : String,
+    pub buy_order  : u64,
+    pub sell_order : u64
+}
+
+#[derive(Clone)]
+struct Attributed {
+    symbol  : String,
+    part_id : String,
+    side    : Side,
+    price   : u64,
+    shares  : u32
+}
+
+// symbol, part id
+type Participant = (String, String);
+
+pub struct SelfMatchDetector {
+    window  : u64,
+    clock   : TimestampComposer,
+    orders  : HashMap<u64, Attributed>,
+    resting : HashMap<Participant, Vec<u64>>,
+    // (time, side, order id) of recent executions
+    fills   : HashMap<Participant, VecDeque<(u64, Side, u64)>>
+}
+
+impl SelfMatchDetector {
+    // `window` is how far apart, in nanoseconds, executions on either side still count as a wash.
+    pub fn new( window : u64 ) -> SelfMatchDetector {
+        SelfMatchDetector{ window, clock : TimestampComposer::new(), orders : HashMap::new(), resting : HashMap::new(),
+                           fills : HashMap::new() }
+    }
+
+    pub fn apply( &mut self, msg : &BATSMessage ) -> Option<SelfMatch> {
+        let now = self.clock.compose(msg);
+        match *msg {
+            BATSMessage::AddOrderMsg(ref m) => {
+                let order = Attributed::of(&m.symbol, &m.part_id, m.side, m.price, m.shares)?;
+                self.add(m.order_id, order, now)
+            },
+            BATSMessage::BinaryAddOrderMsg(ref m) => {
+                let order = Attributed::of(&m.symbol, &m.part_id, m.side, m.price, m.shares)?;
+                self.add(m.order_id, order, now)
+            },
+            BATSMessage::OrderExecutedMsg(ref m) => self.execution(m.order_id, m.shares, now),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => self.execution(m.order_id, m.shares, now),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => self.execution(m.order_id, m.shares, now),
+            BATSMessage::OrderCancelMsg(ref m) => { self.take(m.order_id, m.shares); None }
+            BATSMessage::ReduceSizeMsg(ref m)  => { self.take(m.order_id, m.shares); None }
+            BATSMessage::DeleteOrderMsg(ref m) => { self.take(m.order_id, u32::MAX); None }
+            // taken off and put back at its new price, which can cross like an add
+            BATSMessage::ModifyOrderMsg(ref m) => {
+                let order = self.orders.get(&m.order_id).map(|o| Attributed{ price : m.price, shares : m.shares, ..o.clone() })?;
+                self.take(m.order_id, u32::MAX);
+                if order.shares == 0 { None } else { self.add(m.order_id, order, now) }
+            },
+            BATSMessage::SymbolClearMsg(ref m) => { self.clear_symbol(m.symbol.trim_end()); None }
+            _ => None
+        }
+    }
+
+    fn add( &mut self, order_id : u64, order : Attributed, now : u64 ) -> Option<SelfMatch> {
+        let hit = self.crossing(order_id, &order, now);
+        self.resting.entry((order.symbol.clone(), order.part_id.clone())).or_default().push(order_id);
+        self.orders.insert(order_id, order);
+        hit
+    }
+
+    fn crossing( &self, order_id : u64, order : &Attributed, now : u64 ) -> Option<SelfMatch> {
+        let own = self.resting.get(&(order.symbol.clone(), order.part_id.clone()))?;
+        let crosses = |o : &Attributed| match order.side {
+            Side::Bid => o.side == Side::Ask && order.price >= o.price,
+            Side::Ask => o.side == Side::Bid && order.price <= o.price
+        };
+        let other = own.iter().filter_map(|id| self.orders.get(id).map(|o| (*id, o))).find(|&(_, o)| crosses(o))?;
+        Some(SelfMatch::pair(SelfMatchKind::Crossing, now, order, (order_id, order.side), (other.0, other.1.side)))
+    }
+
+    fn execution( &mut self, order_id : u64, shares : u32, now : u64 ) -> Option<SelfMatch> {
+        let (key, side) = match self.orders.get(&order_id) {
+            Some(o) => ((o.symbol.clone(), o.part_id.clone()), o.side),
+            None    => return None
+        };
+        let window = self.window;
+        let fills = self.fills.entry(key.clone()).or_default();
+        while fills.front().is_some_and(|&(t, _, _)| t + window < now) {
+            fills.pop_front();
+        }
+        let other = fills.iter().rev().find(|&&(_, s, _)| s != side).map(|&(_, s, id)| (id, s));
+        fills.push_back((now, side, order_id));
+        let hit = other.map(|other| SelfMatch::pair(SelfMatchKind::Wash, now, &self.orders[&order_id], (order_id, side), other));
+        self.take(order_id, shares);
+        hit
+    }
+
+    fn take( &mut self, order_id : u64, shares : u32 ) {
+        let done = match self.orders.get_mut(&order_id) {
+            Some(o) => { o.shares = o.shares.saturating_sub(shares); o.shares == 0 }
+            None    => false
+        };
+        if done {
+            let o = self.orders.remove(&order_id).unwrap();
+            if let Some(ids) = self.resting.get_mut(&(o.symbol, o.part_id)) {
+                ids.retain(|&id| id != order_id);
+            }
+        }
+    }
+
+    fn clear_symbol( &mut self, symbol : &str ) {
+        self.orders.retain(|_, o| o.symbol != symbol);
+        self.resting.retain(|(s, _), _| s != symbol);
+        self.fills.retain(|(s, _), _| s != symbol);
+    }
+
+    // attributed orders still resting.
+    pub fn len( &self ) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.orders.is_empty()
+    }
+}
+
+impl Attributed {
+    // None for an order without a part id.
+    fn of( symbol : &str, part_id : &str, side : char, price : u64, shares : u32 ) -> Option<Attributed> {
+        let part_id = Some(part_id.trim_end()).filter(|p| !p.is_empty())?;
+        Some(Attributed{ symbol : String::from(symbol.trim_end()), part_id : String::from(part_id), side : Side::from_char(side),
+                         price, shares })
+    }
+}
+
+impl SelfMatch {
+    fn pair( kind : SelfMatchKind, timestamp : u64, order : &Attributed, a : (u64, Side), b : (u64, Side) ) -> SelfMatch {
+        let (buy, sell) = if a.1 == Side::Bid { (a.0, b.0) } else { (b.0, a.0) };
+        SelfMatch{ kind, timestamp, symbol : order.symbol.clone(), part_id : order.part_id.clone(), buy_order : buy, sell_order : sell }
+    }
+}
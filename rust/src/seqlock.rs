@@ -0,0 +1,166 @@
+
+// A book one thread writes and any number of others read without taking a lock, for strategy
+// processes polling the top of the book far more often than it changes. The writer owns the
+// OrderBook and, after each message that changed it, publishes the top levels into a seqlock;
+// readers copy them out and retry if a publish happened meanwhile, so the writer never waits
+// on a reader and a reader always sees one publish whole.
+//
+//   let mut writer = BookWriter::new(OrderBook::for_symbol("AAPL"), 5);
+//   let reader = writer.reader();
+//   thread::spawn(move || loop { strategy(reader.depth()) });
+//   for msg in feed { writer.apply(&msg); }
+//
+// Only `depth` levels a side are published, the whole book stays with the writer. There's no
+// room for all of them as with OrderBook::depth(0), so a depth of 0 publishes just the top.
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::atomic;
+
+use book::Bbo;
+use book::Depth;
+use book::OrderBook;
+use book::Quote;
+use messages::BATSMessage;
+
+// what the seqlock guards, all atomics so a read racing a publish is only ever stale, never
+// undefined: the level counts and then price, shares, orders for each level, bids first.
+struct Shared {
+    seq    : AtomicU64,
+    depth  : usize,
+    counts : [AtomicU64; 2],
+    slots  : Vec<AtomicU64>
+}
+
+impl Shared {
+    fn slot( &self, side : usize, level : usize ) -> usize {
+        (side * self.depth + level) * 3
+    }
+
+    fn store( &self, side : usize, quotes : &[Quote] ) {
+        self.counts[side].store(quotes.len() as u64, Ordering::Relaxed);
+        for (i, q) in quotes.iter().enumerate() {
+            let s = self.slot(side, i);
+            self.slots[s].store(q.price, Ordering::Relaxed);
+            self.slots[s + 1].store(q.shares, Ordering::Relaxed);
+            self.slots[s + 2].store(u64::from(q.orders), Ordering::Relaxed);
+        }
+    }
+
+    fn load( &self, side : usize, n : usize ) -> Vec<Quote> {
+        let n = n.min(self.counts[side].load(Ordering::Relaxed) as usize).min(self.depth);
+        (0..n).map(|i| {
+            let s = self.slot(side, i);
+            Quote{ price : self.slots[s].load(Ordering::Relaxed), shares : self.slots[s + 1].load(Ordering::Relaxed),
+                   orders : self.slots[s + 2].load(Ordering::Relaxed) as u32 }
+        }).collect()
+    }
+
+    // the top `n` levels a side as of one publish.
+    fn read( &self, n : usize ) -> (Vec<Quote>, Vec<Quote>, u64) {
+        loop {
+            let before = self.seq.load(Ordering::Acquire);
+            if before % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let bids = self.load(0, n);
+            let asks = self.load(1, n);
+            atomic::fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == before {
+                return (bids, asks, before / 2);
+            }
+        }
+    }
+}
+
+pub struct BookWriter {
+    book   : OrderBook,
+    shared : Arc<Shared>
+}
+
+impl BookWriter {
+    // publishes the book as it already is, so readers made now see it.
+    pub fn new( book : OrderBook, depth : usize ) -> BookWriter {
+        let depth = depth.max(1);
+        let shared = Shared{ seq : AtomicU64::new(0), depth, counts : [AtomicU64::new(0), AtomicU64::new(0)],
+                             slots : (0..depth * 6).map(|_| AtomicU64::new(0)).collect() };
+        let mut writer = BookWriter{ book, shared : Arc::new(shared) };
+        writer.publish();
+        writer
+    }
+
+    pub fn reader( &self ) -> BookReader {
+        BookReader{ shared : self.shared.clone() }
+    }
+
+    // publishes when the message changed the book.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        let changed = self.book.apply(msg);
+        if changed {
+            self.publish();
+        }
+        changed
+    }
+
+    pub fn publish( &mut self ) {
+        let shared = &self.shared;
+        let depth = self.book.depth(shared.depth);
+        let seq = shared.seq.load(Ordering::Relaxed);
+        shared.seq.store(seq + 1, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        shared.store(0, &depth.bids);
+        shared.store(1, &depth.asks);
+        shared.seq.store(seq + 2, Ordering::Release);
+    }
+
+    pub fn book( &self ) -> &OrderBook {
+        &self.book
+    }
+
+    pub fn into_book( self ) -> OrderBook {
+        self.book
+    }
+}
+
+// Cheap to clone, one per reading thread or shared between them.
+#[derive(Clone)]
+pub struct BookReader {
+    shared : Arc<Shared>
+}
+
+impl BookReader {
+    pub fn bbo( &self ) -> Bbo {
+        let (mut bids, mut asks, _) = self.shared.read(1);
+        Bbo{ bid : bids.pop(), ask : asks.pop() }
+    }
+
+    // every published level.
+    pub fn depth( &self ) -> Depth {
+        self.depth_to(self.shared.depth)
+    }
+
+    // 0 is every published level, as with OrderBook::depth().
+    pub fn depth_to( &self, n : usize ) -> Depth {
+        let n = if n == 0 { self.shared.depth } else { n };
+        let (bids, asks, _) = self.shared.read(n);
+        Depth{ bids, asks }
+    }
+
+    // with how many times the book's been published, to tell whether it changed since the last
+    // look.
+    pub fn versioned_depth( &self ) -> (Depth, u64) {
+        let (bids, asks, version) = self.shared.read(self.shared.depth);
+        (Depth{ bids, asks }, version)
+    }
+
+    pub fn version( &self ) -> u64 {
+        self.shared.seq.load(Ordering::Acquire) / 2
+    }
+
+    // how many levels a side the writer publishes.
+    pub fn max_depth( &self ) -> usize {
+        self.shared.depth
+    }
+}
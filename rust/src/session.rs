@@ -0,0 +1,247 @@
+
+// The feed over TCP, for where there's no multicast to join: connects to the session port,
+// logs in, and reads the same sequenced unit packets the multicast feed carries, decoded the
+// way MulticastReceiver decodes them.
+//
+//   let login = LoginMsg::new("0001", "USER", "PASSWORD");
+//   let mut session = SessionClient::connect("10.0.0.7:10001", &login)?;
+//   session.run(&mut books, &stop)?;
+//
+// A heartbeat goes to the server whenever we've sent it nothing for a second, and the session
+// is given up on (TimedOut) when the server goes five seconds without sending anything, its
+// heartbeats included. Both can be changed once connected.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use binary::encode_packet;
+use binary::split_packet;
+use binary::LoginMsg;
+use binary::LoginStatus;
+use binary::SequencedUnitHeader;
+use book::BookManager;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+use metrics::Metrics;
+use multicast::decode_messages;
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Rejected(LoginStatus),
+    BadPacket,
+    TimedOut,   // nothing from the server for the timeout
+    Closed
+}
+
+impl fmt::Display for SessionError {
+    fn fmt( &self, f : &mut fmt::Formatter ) -> fmt::Result {
+        match *self {
+            SessionError::Io(ref e)   => write!(f, "session i/o error: {}", e),
+            SessionError::Rejected(s) => write!(f, "login rejected: {:?}", s),
+            SessionError::BadPacket   => write!(f, "not a sequenced unit packet"),
+            SessionError::TimedOut    => write!(f, "the server stopped sending"),
+            SessionError::Closed      => write!(f, "the server closed the session")
+        }
+    }
+}
+
+impl error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from( e : io::Error ) -> SessionError {
+        SessionError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub packets         : u64,
+    pub messages        : u64,
+    pub heartbeats      : u64,   // from the server
+    pub heartbeats_sent : u64
+}
+
+pub struct SessionClient {
+    stream         : TcpStream,
+    // what's been read and not yet handed on, the start of it a packet
+    buf            : Vec<u8>,
+    chunk          : Vec<u8>,
+    last_sent      : Instant,
+    last_heard     : Instant,
+    heartbeat      : Duration,
+    server_timeout : Duration,
+    timeout        : Duration,
+    ended          : bool,
+    stats          : SessionStats,
+    metrics        : Option<Arc<Metrics>>
+}
+
+impl SessionClient {
+    // connected once the server has accepted the login.
+    pub fn connect<A : ToSocketAddrs>( addr : A, login : &LoginMsg ) -> Result<SessionClient, SessionError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let now = Instant::now();
+        let mut session = SessionClient{ stream, buf : Vec::new(), chunk : vec![0; 65536], last_sent : now, last_heard : now,
+                                         heartbeat : Duration::from_secs(1), server_timeout : Duration::from_secs(5),
+                                         timeout : Duration::from_millis(100), ended : false, stats : SessionStats::default(),
+                                         metrics : None };
+        session.send(&encode_packet(&[login.to_bytes()]))?;
+        let deadline = Instant::now() + session.server_timeout;
+        loop {
+            if let Some(len) = session.next_packet(deadline)? {
+                let reply = match split_packet(&session.buf[..len]) {
+                    // the server may heartbeat before it answers
+                    Ok((_, ref msgs)) if msgs.is_empty() => None,
+                    Ok((_, msgs)) => match BATSMsgFactory::try_parse_binary(msgs[0]) {
+                        Ok(Some(BATSMessage::LoginResponseMsg(r))) => Some(Some(r.status)),
+                        _                                          => Some(None)
+                    },
+                    Err(_) => Some(None)
+                };
+                session.buf.drain(..len);
+                match reply {
+                    None                              => {},
+                    Some(Some(LoginStatus::Accepted)) => return Ok(session),
+                    Some(Some(s))                     => return Err(SessionError::Rejected(s)),
+                    Some(None)                        => return Err(SessionError::BadPacket)
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(SessionError::TimedOut);
+            }
+        }
+    }
+
+    pub fn metrics( mut self, metrics : Arc<Metrics> ) -> SessionClient {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // how long between our heartbeats.
+    pub fn heartbeat( mut self, interval : Duration ) -> SessionClient {
+        self.heartbeat = interval;
+        self
+    }
+
+    // how long the server can be quiet before the session's given up on.
+    pub fn server_timeout( mut self, timeout : Duration ) -> SessionClient {
+        self.server_timeout = timeout;
+        self
+    }
+
+    // how long recv() waits for a packet, and so how often run() looks at its stop flag.
+    pub fn timeout( mut self, timeout : Duration ) -> SessionClient {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn peer_addr( &self ) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn stats( &self ) -> SessionStats {
+        self.stats
+    }
+
+    // whether the server has sent its EndOfSessionMsg.
+    pub fn ended( &self ) -> bool {
+        self.ended
+    }
+
+    fn send( &mut self, packet : &[u8] ) -> io::Result<()> {
+        self.stream.write_all(packet)?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    // the length of the packet at the start of buf once it's all there, heartbeating while it
+    // waits. None at the deadline.
+    fn next_packet( &mut self, deadline : Instant ) -> Result<Option<usize>, SessionError> {
+        loop {
+            if self.buf.len() >= 2 {
+                let len = u16::from_le_bytes([self.buf[0], self.buf[1]]) as usize;
+                if len < 8 {
+                    return Err(SessionError::BadPacket);
+                }
+                if self.buf.len() >= len {
+                    return Ok(Some(len));
+                }
+            }
+            let now = Instant::now();
+            if now.duration_since(self.last_sent) >= self.heartbeat {
+                self.send(&SequencedUnitHeader::heartbeat().to_bytes())?;
+                self.stats.heartbeats_sent += 1;
+            }
+            if now.duration_since(self.last_heard) >= self.server_timeout {
+                return Err(SessionError::TimedOut);
+            }
+            if now >= deadline {
+                return Ok(None);
+            }
+            // wake for whichever comes first of the deadline and the next heartbeat
+            let wait = (deadline - now).min(self.heartbeat.saturating_sub(now.duration_since(self.last_sent)));
+            self.stream.set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+            match self.stream.read(&mut self.chunk) {
+                Ok(0)  => return Err(SessionError::Closed),
+                Ok(n)  => {
+                    self.buf.extend_from_slice(&self.chunk[..n]);
+                    self.last_heard = Instant::now();
+                },
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {},
+                Err(e) => return Err(SessionError::Io(e))
+            }
+        }
+    }
+
+    // waits for a packet and appends its messages to `out`, false when none came within the
+    // timeout.
+    pub fn recv( &mut self, out : &mut Vec<BATSMessage> ) -> Result<bool, SessionError> {
+        let deadline = Instant::now() + self.timeout;
+        let len = match self.next_packet(deadline)? {
+            Some(len) => len,
+            None      => return Ok(false)
+        };
+        self.stats.packets += 1;
+        let start = out.len();
+        match split_packet(&self.buf[..len]) {
+            Ok((_, ref msgs)) if msgs.is_empty() => self.stats.heartbeats += 1,
+            Ok((_, msgs)) => self.stats.messages += decode_messages(msgs.into_iter(), self.metrics.as_deref(), out),
+            Err(_)        => return Err(SessionError::BadPacket)
+        }
+        self.buf.drain(..len);
+        if out[start..].iter().any(|m| matches!(*m, BATSMessage::EndOfSessionMsg(_))) {
+            self.ended = true;
+        }
+        Ok(true)
+    }
+
+    // applies packets to the books as they come until `stop` is set, or the server closes the
+    // session after ending it.
+    pub fn run( &mut self, books : &mut BookManager, stop : &AtomicBool ) -> Result<(), SessionError> {
+        let mut msgs = Vec::new();
+        while !stop.load(Ordering::Relaxed) {
+            match self.recv(&mut msgs) {
+                Ok(_)                                   => {},
+                Err(SessionError::Closed) if self.ended => return Ok(()),
+                Err(e)                                  => return Err(e)
+            }
+            for msg in msgs.drain(..) {
+                books.apply(&msg);
+            }
+        }
+        Ok(())
+    }
+}
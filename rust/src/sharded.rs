@@ -0,0 +1,286 @@
+
+// BookManagers on worker threads, each owning the books of the symbols hashed to it, to build a
+// multi-symbol day in parallel. Messages go in on the caller's thread like BookManager::apply;
+// adds, trades and the other messages naming a symbol go to its shard, executions and cancels
+// follow their order there by id, and the rest (time, unit clear, end of session, trade breaks)
+// go to every shard. The books are read back with a query run on the shard that owns them:
+//
+//   let mut books = ShardedBooks::new(4);
+//   for msg in msgs { books.apply(msg); }
+//   let bbo = books.bbo("AAPL");
+//   let managers = books.finish();
+//
+// Queries wait for the shard to catch up with everything applied before them.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+
+use binary::EndOfSessionMsg;
+use binary::TimeMsg;
+use binary::UnitClearMsg;
+use book::Bbo;
+use book::BookManager;
+use book::Depth;
+use book::OrderBook;
+use messages::BATSMessage;
+use messages::TradeBreakMsg;
+
+// messages held back per shard and sent together, to keep the channel out of the way.
+const BATCH : usize = 256;
+
+type Query = Box<dyn FnOnce(&BookManager) + Send>;
+
+enum Command {
+    Apply(Vec<BATSMessage>),
+    Delete(u64),
+    Query(Query)
+}
+
+struct Shard {
+    tx      : mpsc::SyncSender<Command>,
+    worker  : thread::JoinHandle<BookManager>,
+    pending : Vec<BATSMessage>
+}
+
+impl Shard {
+    fn flush( &mut self ) {
+        if !self.pending.is_empty() {
+            let batch = std::mem::replace(&mut self.pending, Vec::with_capacity(BATCH));
+            self.send(Command::Apply(batch));
+        }
+    }
+
+    fn send( &self, command : Command ) {
+        // the worker only stops when told to or when a book panicked, which join reports
+        let _ = self.tx.send(command);
+    }
+}
+
+pub struct ShardedBooks {
+    shards : Vec<Shard>,
+    // the shard each resting order is on, and its shares left there
+    routes : HashMap<u64, (usize, u32)>
+}
+
+// FNV-1a, so a symbol lands on the same shard from one run to the next.
+fn hash( symbol : &str ) -> u64 {
+    symbol.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ u64::from(b)).wrapping_mul(0x100_0000_01b3))
+}
+
+fn symbol_of( msg : &BATSMessage ) -> Option<&str> {
+    match *msg {
+        BATSMessage::AddOrderMsg(ref m)           => Some(&m.symbol),
+        BATSMessage::BinaryAddOrderMsg(ref m)     => Some(&m.symbol),
+        BATSMessage::TradeMsg(ref m)              => Some(&m.symbol),
+        BATSMessage::BinaryTradeMsg(ref m)        => Some(&m.symbol),
+        BATSMessage::SymbolClearMsg(ref m)        => Some(&m.symbol),
+        BATSMessage::TradingStatusMsg(ref m)      => Some(&m.symbol),
+        BATSMessage::AuctionUpdateMsg(ref m)      => Some(&m.symbol),
+        BATSMessage::AuctionSummaryMsg(ref m)     => Some(&m.symbol),
+        BATSMessage::RetailPriceImproveMsg(ref m) => Some(&m.symbol),
+        _ => None
+    }
+}
+
+// a copy for every shard of a message they all need.
+fn broadcast( msg : &BATSMessage ) -> Option<BATSMessage> {
+    match *msg {
+        BATSMessage::TimeMsg(ref m) => Some(BATSMessage::TimeMsg(TimeMsg{ msg_type : m.msg_type, time : m.time })),
+        BATSMessage::UnitClearMsg(ref m) => {
+            Some(BATSMessage::UnitClearMsg(UnitClearMsg{ time_offset : m.time_offset, msg_type : m.msg_type }))
+        },
+        BATSMessage::EndOfSessionMsg(ref m) => {
+            Some(BATSMessage::EndOfSessionMsg(EndOfSessionMsg{ time_offset : m.time_offset, msg_type : m.msg_type }))
+        },
+        BATSMessage::TradeBreakMsg(ref m) => {
+            Some(BATSMessage::TradeBreakMsg(TradeBreakMsg{ timestamp : m.timestamp, msg_type : m.msg_type, exec_id : m.exec_id }))
+        },
+        _ => None
+    }
+}
+
+impl ShardedBooks {
+    pub fn new( shards : usize ) -> ShardedBooks {
+        ShardedBooks::with_managers(shards, BookManager::new)
+    }
+
+    // `make` builds each shard's manager on its thread, eg. to set a book mode or add
+    // listeners, which then hear only that shard's books.
+    pub fn with_managers<F>( shards : usize, make : F ) -> ShardedBooks
+        where F : Fn() -> BookManager + Send + Sync + 'static
+    {
+        let make = Arc::new(make);
+        let shards = (0..shards.max(1)).map(|_| {
+            let (tx, rx) = mpsc::sync_channel(64);
+            let make = make.clone();
+            let worker = thread::spawn(move || {
+                let mut books = make();
+                for command in rx {
+                    match command {
+                        Command::Apply(msgs) => for msg in &msgs {
+                            books.apply(msg);
+                        },
+                        Command::Delete(order_id) => { books.delete_order(order_id); },
+                        Command::Query(query) => query(&books)
+                    }
+                }
+                books
+            });
+            Shard{ tx, worker, pending : Vec::with_capacity(BATCH) }
+        }).collect();
+        ShardedBooks{ shards, routes : HashMap::new() }
+    }
+
+    pub fn shards( &self ) -> usize {
+        self.shards.len()
+    }
+
+    pub fn shard_of( &self, symbol : &str ) -> usize {
+        (hash(symbol.trim_end()) % self.shards.len() as u64) as usize
+    }
+
+    // the shard for a message on an order, updating what's known of the order.
+    fn route( &mut self, msg : &BATSMessage ) -> Option<usize> {
+        let (order_id, left) = match *msg {
+            BATSMessage::AddOrderMsg(ref m) => return Some(self.added(&m.symbol, m.order_id, m.shares)),
+            BATSMessage::BinaryAddOrderMsg(ref m) => return Some(self.added(&m.symbol, m.order_id, m.shares)),
+            BATSMessage::OrderExecutedMsg(ref m) => (m.order_id, Err(m.shares)),
+            BATSMessage::BinaryOrderExecutedMsg(ref m) => (m.order_id, Err(m.shares)),
+            BATSMessage::ModifyOrderMsg(ref m) => (m.order_id, Ok(m.shares)),
+            BATSMessage::DeleteOrderMsg(ref m) => (m.order_id, Ok(0)),
+            BATSMessage::OrderCancelMsg(ref m) => (m.order_id, Err(m.shares)),
+            BATSMessage::ReduceSizeMsg(ref m) => (m.order_id, Err(m.shares)),
+            BATSMessage::OrderExecutedAtPriceSizeMsg(ref m) => (m.order_id, Ok(m.remaining_shares)),
+            _ => return None
+        };
+        let (shard, shares) = self.routes.get_mut(&order_id)?;
+        let shard = *shard;
+        *shares = match left {
+            Ok(remaining) => remaining,
+            Err(taken)    => shares.saturating_sub(taken)
+        };
+        if *shares == 0 {
+            self.routes.remove(&order_id);
+        }
+        Some(shard)
+    }
+
+    fn added( &mut self, symbol : &str, order_id : u64, shares : u32 ) -> usize {
+        let shard = self.shard_of(symbol);
+        // a reused id moves the order, and the shard it was on has to let it go
+        if let Some((old, _)) = self.routes.insert(order_id, (shard, shares)) {
+            if old != shard {
+                self.shards[old].flush();
+                self.shards[old].send(Command::Delete(order_id));
+            }
+        }
+        shard
+    }
+
+    // messages on orders that none of the shards have are dropped here.
+    pub fn apply( &mut self, msg : BATSMessage ) {
+        if broadcast(&msg).is_some() {
+            for shard in 1..self.shards.len() {
+                if let Some(copy) = broadcast(&msg) {
+                    self.push(shard, copy);
+                }
+            }
+            self.push(0, msg);
+            return;
+        }
+        let shard = match msg {
+            BATSMessage::AddOrderMsg(_) | BATSMessage::OrderExecutedMsg(_) | BATSMessage::OrderCancelMsg(_) |
+            BATSMessage::ReduceSizeMsg(_) | BATSMessage::OrderExecutedAtPriceSizeMsg(_) |
+            BATSMessage::BinaryAddOrderMsg(_) | BATSMessage::BinaryOrderExecutedMsg(_) | BATSMessage::ModifyOrderMsg(_) |
+            BATSMessage::DeleteOrderMsg(_) => self.route(&msg),
+            // anything else without a symbol, eg. a login, only needs going somewhere
+            _ => Some(symbol_of(&msg).map_or(0, |s| self.shard_of(s)))
+        };
+        if let Some(shard) = shard {
+            self.push(shard, msg);
+        }
+    }
+
+    fn push( &mut self, shard : usize, msg : BATSMessage ) {
+        let shard = &mut self.shards[shard];
+        shard.pending.push(msg);
+        if shard.pending.len() >= BATCH {
+            shard.flush();
+        }
+    }
+
+    // sends what's been held back, without waiting for the shards to apply it.
+    pub fn flush( &mut self ) {
+        for shard in &mut self.shards {
+            shard.flush();
+        }
+    }
+
+    // runs `f` on a shard's manager once it's applied everything before.
+    pub fn query<R, F>( &mut self, shard : usize, f : F ) -> R
+        where R : Send + 'static, F : FnOnce(&BookManager) -> R + Send + 'static
+    {
+        let (tx, rx) = mpsc::channel();
+        let shard = &mut self.shards[shard];
+        shard.flush();
+        shard.send(Command::Query(Box::new(move |books| { let _ = tx.send(f(books)); })));
+        rx.recv().expect("a shard's worker stopped")
+    }
+
+    // `f` on every shard's manager, in shard order.
+    pub fn query_all<R, F>( &mut self, f : F ) -> Vec<R>
+        where R : Send + 'static, F : Fn(&BookManager) -> R + Send + Sync + 'static
+    {
+        let f = Arc::new(f);
+        let replies : Vec<_> = self.shards.iter_mut().map(|shard| {
+            let (tx, rx) = mpsc::channel();
+            let f = f.clone();
+            shard.flush();
+            shard.send(Command::Query(Box::new(move |books| { let _ = tx.send(f(books)); })));
+            rx
+        }).collect();
+        replies.into_iter().map(|rx| rx.recv().expect("a shard's worker stopped")).collect()
+    }
+
+    // `f` on the symbol's book, None when it has none.
+    pub fn with_book<R, F>( &mut self, symbol : &str, f : F ) -> R
+        where R : Send + 'static, F : FnOnce(Option<&OrderBook>) -> R + Send + 'static
+    {
+        let shard = self.shard_of(symbol);
+        let symbol = String::from(symbol.trim_end());
+        self.query(shard, move |books| f(books.book(&symbol)))
+    }
+
+    pub fn bbo( &mut self, symbol : &str ) -> Option<Bbo> {
+        self.with_book(symbol, |book| book.map(OrderBook::bbo))
+    }
+
+    pub fn depth( &mut self, symbol : &str, n : usize ) -> Option<Depth> {
+        self.with_book(symbol, move |book| book.map(|b| b.depth(n)))
+    }
+
+    // across every shard, sorted.
+    pub fn symbols( &mut self ) -> Vec<String> {
+        let mut symbols : Vec<String> = self.query_all(|books| books.symbols().into_iter().map(String::from).collect::<Vec<_>>())
+                                            .into_iter().flatten().collect();
+        symbols.sort();
+        symbols
+    }
+
+    // resting orders across every shard.
+    pub fn orders( &mut self ) -> usize {
+        self.query_all(BookManager::orders).into_iter().sum()
+    }
+
+    // stops the workers once they've applied everything, and hands back their managers in shard
+    // order. A worker that panicked panics here.
+    pub fn finish( mut self ) -> Vec<BookManager> {
+        self.flush();
+        self.shards.into_iter().map(|shard| {
+            drop(shard.tx);
+            shard.worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e))
+        }).collect()
+    }
+}
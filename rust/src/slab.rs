@@ -0,0 +1,114 @@
+
+// A slab: values in one Vec, handed out by index. Freed slots are reused before the Vec grows,
+// so a book that holds a million orders through the day keeps them in about a million slots
+// instead of allocating per order, and an index stays valid until its value is removed.
+
+use std::mem;
+
+#[derive(Debug, Clone)]
+enum Slot<T> {
+    Full(T),
+    // the next free slot after this one
+    Free(Option<usize>)
+}
+
+#[derive(Debug, Clone)]
+pub struct Slab<T> {
+    slots : Vec<Slot<T>>,
+    free  : Option<usize>,
+    len   : usize
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Slab<T> {
+        Slab::new()
+    }
+}
+
+impl<T> Slab<T> {
+    pub fn new() -> Slab<T> {
+        Slab{ slots : Vec::new(), free : None, len : 0 }
+    }
+
+    pub fn with_capacity( capacity : usize ) -> Slab<T> {
+        Slab{ slots : Vec::with_capacity(capacity), free : None, len : 0 }
+    }
+
+    pub fn insert( &mut self, value : T ) -> usize {
+        self.len += 1;
+        match self.free {
+            Some(i) => {
+                if let Slot::Free(next) = self.slots[i] {
+                    self.free = next;
+                }
+                self.slots[i] = Slot::Full(value);
+                i
+            },
+            None => {
+                self.slots.push(Slot::Full(value));
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    pub fn remove( &mut self, index : usize ) -> Option<T> {
+        match self.slots.get_mut(index) {
+            Some(slot @ Slot::Full(_)) => {
+                self.len -= 1;
+                match mem::replace(slot, Slot::Free(self.free)) {
+                    Slot::Full(value) => {
+                        self.free = Some(index);
+                        Some(value)
+                    },
+                    Slot::Free(_) => None
+                }
+            },
+            _ => None
+        }
+    }
+
+    pub fn get( &self, index : usize ) -> Option<&T> {
+        match self.slots.get(index) {
+            Some(Slot::Full(value)) => Some(value),
+            _                       => None
+        }
+    }
+
+    pub fn get_mut( &mut self, index : usize ) -> Option<&mut T> {
+        match self.slots.get_mut(index) {
+            Some(Slot::Full(value)) => Some(value),
+            _                       => None
+        }
+    }
+
+    // in index order.
+    pub fn iter( &self ) -> impl Iterator<Item = (usize, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, s)| match s {
+            Slot::Full(value) => Some((i, value)),
+            Slot::Free(_)     => None
+        })
+    }
+
+    pub fn len( &self ) -> usize {
+        self.len
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.len == 0
+    }
+
+    pub fn reserve( &mut self, additional : usize ) {
+        self.slots.reserve(additional);
+    }
+
+    // slots allocated, taken or not.
+    pub fn capacity( &self ) -> usize {
+        self.slots.len()
+    }
+
+    pub fn clear( &mut self ) {
+        self.slots.clear();
+        self.free = None;
+        self.len = 0;
+    }
+}
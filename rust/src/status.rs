@@ -0,0 +1,70 @@
+
+// Per symbol trading state from TradingStatusMsg, and which book updates each state allows.
+// A symbol that never had a status message is Trading.
+
+use messages::TradingStatusMsg;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SymbolState {
+    Accepting,   // orders queue for the opening, nothing executes yet
+    Halted,
+    Suspended,
+    QuoteOnly,
+    #[default]
+    Trading
+}
+
+// what a message does to a book, as far as the state cares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Update {
+    Add,
+    Execution,
+    Cancel
+}
+
+impl SymbolState {
+    pub fn from_char( c : char ) -> Option<SymbolState> {
+        match c {
+            'A' => Some(SymbolState::Accepting),
+            'H' => Some(SymbolState::Halted),
+            'S' => Some(SymbolState::Suspended),
+            'Q' => Some(SymbolState::QuoteOnly),
+            'T' => Some(SymbolState::Trading),
+            _   => None
+        }
+    }
+
+    pub fn from_msg( msg : &TradingStatusMsg ) -> Option<SymbolState> {
+        SymbolState::from_char(msg.halt_status)
+    }
+
+    // cancels are always fine, adds need the symbol to take orders and executions need it to be
+    // trading.
+    pub fn permits( self, update : Update ) -> bool {
+        matches!((self, update), (_, Update::Cancel) | (SymbolState::Trading, _) |
+                                 (SymbolState::Accepting, Update::Add) | (SymbolState::QuoteOnly, Update::Add))
+    }
+}
+
+// what happens to an update its symbol's state doesn't allow. Either way it's kept as a
+// Violation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ViolationPolicy {
+    #[default]
+    Apply,
+    Reject
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Violation {
+    pub timestamp : u64,
+    pub symbol    : String,
+    pub state     : SymbolState,
+    pub update    : Update,
+    pub order_id  : Option<u64>,
+    // false when the policy let it through anyway
+    pub rejected  : bool
+}
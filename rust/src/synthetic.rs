@@ -0,0 +1,364 @@
+
+// A made-up market, for load testing whatever reads the feed without a licensed capture: adds,
+// executions, cancels and hidden trades for each symbol, as text PITCH messages that hang
+// together the way an exchange's do:
+//
+//   let config = SyntheticConfig::new().symbol("AAPL", 1_831_900).symbol("MSFT", 4_102_500).rate(50_000.0).seed(7);
+//   for msg in Generator::new(config).take(10_000_000) {
+//       out.write_all(msg.to_pitch_string().unwrap().as_bytes())?;
+//   }
+//
+// Each symbol's price is a random walk (geometric Brownian motion at the volatility given) and
+// orders are added either side of it, mostly at or near the touch. Executions take the first
+// order at the best price, more often on the side the price has moved towards, so the book
+// follows the walk; cancels go more often to orders far from it. Every execution and cancel is
+// for an order that's resting and no more than it has, no book crosses, and ids are unique
+// across symbols. Messages come at random (a Poisson process at the rate given, spread evenly
+// over the symbols) with the text feed's millisecond timestamps. The same seed gives the same
+// stream.
+
+use backtest::Rng;
+use book::Side;
+use messages::AddOrderMsg;
+use messages::BATSMessage;
+use messages::OrderCancelMsg;
+use messages::OrderExecutedMsg;
+use messages::TradeMsg;
+
+// How often each kind of message comes, relative to the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mix {
+    pub add     : f64,
+    pub cancel  : f64,
+    pub execute : f64,
+    // against hidden orders, which leave the book be
+    pub trade   : f64
+}
+
+impl Default for Mix {
+    fn default() -> Mix {
+        Mix{ add : 0.45, cancel : 0.35, execute : 0.15, trade : 0.05 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticConfig {
+    // with their starting prices
+    symbols    : Vec<(String, u64)>,
+    // messages a second, over all the symbols
+    rate       : f64,
+    // of the price over a second, eg. 0.0005 for 5bp
+    volatility : f64,
+    tick       : u64,
+    mix        : Mix,
+    // resting per symbol, past which it cancels
+    max_orders : usize,
+    max_shares : u32,
+    // milliseconds past midnight
+    start      : u32,
+    seed       : u64
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> SyntheticConfig {
+        SyntheticConfig{ symbols : Vec::new(), rate : 10_000.0, volatility : 0.0005, tick : 100, mix : Mix::default(), max_orders : 500,
+                         max_shares : 1000, start : 34_200_000, seed : 0 }
+    }
+}
+
+impl SyntheticConfig {
+    pub fn new() -> SyntheticConfig {
+        SyntheticConfig::default()
+    }
+
+    // a symbol starting at `price`; with none there are no messages.
+    pub fn symbol( mut self, symbol : &str, price : u64 ) -> SyntheticConfig {
+        self.symbols.push((String::from(symbol.trim_end()), price));
+        self
+    }
+
+    // 10,000 a second by default.
+    pub fn rate( mut self, messages_per_second : f64 ) -> SyntheticConfig {
+        self.rate = messages_per_second.max(f64::MIN_POSITIVE);
+        self
+    }
+
+    pub fn volatility( mut self, per_second : f64 ) -> SyntheticConfig {
+        self.volatility = per_second.max(0.0);
+        self
+    }
+
+    // a cent by default.
+    pub fn tick( mut self, tick : u64 ) -> SyntheticConfig {
+        self.tick = tick.max(1);
+        self
+    }
+
+    pub fn mix( mut self, mix : Mix ) -> SyntheticConfig {
+        self.mix = mix;
+        self
+    }
+
+    pub fn max_orders( mut self, orders : usize ) -> SyntheticConfig {
+        self.max_orders = orders.max(2);
+        self
+    }
+
+    // of an order, 1000 by default; they're in round lots where they can be.
+    pub fn max_shares( mut self, shares : u32 ) -> SyntheticConfig {
+        self.max_shares = shares.clamp(1, 999_999);
+        self
+    }
+
+    // 9:30 by default.
+    pub fn start( mut self, ms_past_midnight : u32 ) -> SyntheticConfig {
+        self.start = ms_past_midnight;
+        self
+    }
+
+    pub fn seed( mut self, seed : u64 ) -> SyntheticConfig {
+        self.seed = seed;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Resting {
+    order_id : u64,
+    side     : Side,
+    price    : u64,
+    shares   : u32
+}
+
+#[derive(Debug)]
+struct Market {
+    symbol : String,
+    mid    : f64,
+    // seconds past midnight the mid was last moved
+    moved  : f64,
+    // in the order they came in
+    orders : Vec<Resting>
+}
+
+impl Market {
+    fn best( &self, side : Side ) -> Option<usize> {
+        let mut best : Option<usize> = None;
+        for (i, o) in self.orders.iter().enumerate() {
+            if o.side != side {
+                continue;
+            }
+            let better = best.is_none_or(|b| match side {
+                Side::Bid => o.price > self.orders[b].price,
+                Side::Ask => o.price < self.orders[b].price
+            });
+            if better {
+                best = Some(i);
+            }
+        }
+        best
+    }
+
+    fn best_price( &self, side : Side ) -> Option<u64> {
+        self.best(side).map(|i| self.orders[i].price)
+    }
+}
+
+enum Kind {
+    Add(Side),
+    Cancel,
+    Execute,
+    Trade
+}
+
+// An endless iterator of the messages.
+#[derive(Debug)]
+pub struct Generator {
+    config     : SyntheticConfig,
+    rng        : Rng,
+    markets    : Vec<Market>,
+    // seconds past midnight
+    clock      : f64,
+    next_order : u64,
+    next_exec  : u64
+}
+
+impl Generator {
+    pub fn new( config : SyntheticConfig ) -> Generator {
+        let clock = f64::from(config.start) / 1000.0;
+        let markets = config.symbols.iter().map(|&(ref symbol, price)| Market{ symbol : symbol.clone(), mid : price as f64, moved : clock,
+                                                                              orders : Vec::new() }).collect();
+        Generator{ rng : Rng::new(config.seed), markets, clock, next_order : 1, next_exec : 1, config }
+    }
+
+    // of the last message, milliseconds past midnight.
+    pub fn time( &self ) -> u32 {
+        (self.clock * 1000.0) as u32
+    }
+
+    // where a symbol's price has walked to.
+    pub fn mid( &self, symbol : &str ) -> Option<f64> {
+        self.markets.iter().find(|m| m.symbol == symbol.trim_end()).map(|m| m.mid)
+    }
+
+    // how many times something with chance `p` fails before it first comes off.
+    fn geometric( &mut self, p : f64, max : u64 ) -> u64 {
+        let mut n = 0;
+        while n < max && self.rng.next_f64() >= p {
+            n += 1;
+        }
+        n
+    }
+
+    fn shares( &mut self ) -> u32 {
+        let max = self.config.max_shares;
+        if max < 100 {
+            return 1 + (self.rng.next_u64() % u64::from(max)) as u32;
+        }
+        let lots = 1 + self.geometric(0.5, u64::from(max / 100) - 1) as u32;
+        lots * 100
+    }
+
+    fn walk( &mut self, m : usize ) {
+        let dt = self.clock - self.markets[m].moved;
+        let sigma = self.config.volatility;
+        let z = self.rng.normal();
+        let floor = 10.0 * self.config.tick as f64;
+        let market = &mut self.markets[m];
+        market.mid = (market.mid * (sigma * dt.sqrt() * z - 0.5 * sigma * sigma * dt).exp()).max(floor);
+        market.moved = self.clock;
+    }
+
+    // which side starts the next trade: the price having gone up from the touch's means a buyer
+    // more often, taking offers.
+    fn aggressor( &mut self, m : usize ) -> Side {
+        let market = &self.markets[m];
+        let touch = match (market.best_price(Side::Bid), market.best_price(Side::Ask)) {
+            (Some(b), Some(a)) => (b + a) as f64 / 2.0,
+            _                  => market.mid
+        };
+        let lean = ((market.mid - touch) / self.config.tick as f64 * 0.25).clamp(-0.45, 0.45);
+        if self.rng.next_f64() < 0.5 + lean { Side::Bid } else { Side::Ask }
+    }
+
+    fn kind( &mut self, m : usize ) -> Kind {
+        let market = &self.markets[m];
+        for side in [Side::Bid, Side::Ask] {
+            if market.best(side).is_none() {
+                return Kind::Add(side);
+            }
+        }
+        if market.orders.len() >= self.config.max_orders {
+            return Kind::Cancel;
+        }
+        let mix = self.config.mix;
+        let r = self.rng.next_f64() * (mix.add + mix.cancel + mix.execute + mix.trade);
+        if r < mix.add {
+            Kind::Add(if self.rng.next_u64() & 1 == 0 { Side::Bid } else { Side::Ask })
+        } else if r < mix.add + mix.cancel {
+            Kind::Cancel
+        } else if r < mix.add + mix.cancel + mix.execute {
+            Kind::Execute
+        } else {
+            Kind::Trade
+        }
+    }
+
+    fn add( &mut self, m : usize, side : Side, timestamp : u32 ) -> BATSMessage {
+        let tick = self.config.tick;
+        let back = self.geometric(0.4, 20) * tick;
+        let shares = self.shares();
+        let market = &self.markets[m];
+        // the touch a penny wide around the mid, never crossing what's resting
+        let bid = (market.mid / tick as f64).floor() as u64 * tick;
+        let price = match side {
+            Side::Bid => {
+                let price = bid.saturating_sub(back).max(tick);
+                market.best_price(Side::Ask).map_or(price, |a| price.min(a - tick))
+            },
+            Side::Ask => {
+                let price = bid + tick + back;
+                market.best_price(Side::Bid).map_or(price, |b| price.max(b + tick))
+            }
+        };
+        let order_id = self.next_order;
+        self.next_order += 1;
+        let symbol = market.symbol.clone();
+        self.markets[m].orders.push(Resting{ order_id, side, price, shares });
+        BATSMessage::AddOrderMsg(AddOrderMsg{ timestamp, msg_type : 'A', order_id, side : side.to_char(), shares, symbol, price,
+                                              display : 'Y', part_id : String::new() })
+    }
+
+    fn cancel( &mut self, m : usize, timestamp : u32 ) -> BATSMessage {
+        // the further from the price of two picked at random
+        let n = self.markets[m].orders.len() as u64;
+        let (a, b) = ((self.rng.next_u64() % n) as usize, (self.rng.next_u64() % n) as usize);
+        let market = &self.markets[m];
+        let away = |i : usize| (market.orders[i].price as f64 - market.mid).abs();
+        let i = if away(a) >= away(b) { a } else { b };
+        let o = self.markets[m].orders[i];
+        // now and then only some of the lots
+        let lots = o.shares / 100;
+        let shares = if lots > 1 && self.rng.next_f64() < 0.2 { 100 * (1 + (self.rng.next_u64() % u64::from(lots - 1)) as u32) } else { o.shares };
+        self.reduce(m, i, shares);
+        BATSMessage::OrderCancelMsg(OrderCancelMsg{ timestamp, msg_type : 'X', order_id : o.order_id, shares })
+    }
+
+    fn execute( &mut self, m : usize, timestamp : u32 ) -> BATSMessage {
+        let against = match self.aggressor(m) {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid
+        };
+        // there's always both sides by now
+        let i = self.markets[m].best(against).unwrap();
+        let o = self.markets[m].orders[i];
+        let shares = self.shares().min(o.shares);
+        self.reduce(m, i, shares);
+        let exec_id = self.next_exec;
+        self.next_exec += 1;
+        BATSMessage::OrderExecutedMsg(OrderExecutedMsg{ timestamp, msg_type : 'E', order_id : o.order_id, shares, exec_id })
+    }
+
+    // against a hidden order somewhere in the spread.
+    fn trade( &mut self, m : usize, timestamp : u32 ) -> BATSMessage {
+        let tick = self.config.tick;
+        let side = self.aggressor(m);
+        let shares = self.shares();
+        let market = &self.markets[m];
+        let (bid, ask) = (market.best_price(Side::Bid).unwrap(), market.best_price(Side::Ask).unwrap());
+        let price = ((bid + ask) / 2 / tick * tick).clamp(bid, ask);
+        let symbol = market.symbol.clone();
+        let (order_id, exec_id) = (self.next_order, self.next_exec);
+        self.next_order += 1;
+        self.next_exec += 1;
+        BATSMessage::TradeMsg(TradeMsg{ timestamp, msg_type : 'P', order_id, side : side.to_char(), shares, symbol, price, exec_id })
+    }
+
+    fn reduce( &mut self, m : usize, i : usize, shares : u32 ) {
+        let orders = &mut self.markets[m].orders;
+        if shares >= orders[i].shares {
+            orders.remove(i);
+        } else {
+            orders[i].shares -= shares;
+        }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = BATSMessage;
+
+    fn next( &mut self ) -> Option<BATSMessage> {
+        if self.markets.is_empty() {
+            return None;
+        }
+        self.clock += -(1.0 - self.rng.next_f64()).ln() / self.config.rate;
+        let timestamp = self.time();
+        let m = (self.rng.next_u64() % self.markets.len() as u64) as usize;
+        self.walk(m);
+        Some(match self.kind(m) {
+            Kind::Add(side) => self.add(m, side, timestamp),
+            Kind::Cancel    => self.cancel(m, timestamp),
+            Kind::Execute   => self.execute(m, timestamp),
+            Kind::Trade     => self.trade(m, timestamp)
+        })
+    }
+}
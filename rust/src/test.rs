@@ -9,7 +9,49 @@ use messages::RetailPriceImproveMsg;
 use messages::TradeBreakMsg;
 use messages::TradeMsg;
 use messages::TradingStatusMsg;
+use messages::HaltReason;
+use messages::SymbolClearMsg;
+use messages::BATSMessage;
 use messages::BATSMsgFactory;
+use roundtrip;
+use roundtrip::RoundtripError;
+use binary::ReduceSizeMsg;
+use binary::UnitClearMsg;
+use binary::split_packet;
+use binary::TimestampComposer;
+use binary::TradeExpandedMsg;
+use binary::OrderExecutedAtPriceSizeMsg;
+use binary::CalculatedValueMsg;
+use binary::LoginMsg;
+use binary::LoginResponseMsg;
+use binary::LoginStatus;
+use binary::SequencedUnitHeader;
+use binary::encode_packet;
+use options::OsiSymbol;
+use options::OsiError;
+use options::PutCall;
+use options::OptionsSymbolMap;
+use iex;
+use iex::IexBook;
+use iex::IexMessage;
+use itch;
+use xdp;
+use xdp::XdpMessage;
+use xdp::XdpSymbolMap;
+use itch::ItchMessage;
+use ouch;
+use ouch::OuchMessage;
+use decoder::DecodeError;
+use decoder::DecoderRegistry;
+use decoder::FeedDecoder;
+use event::MarketEvent;
+use event::TradingState;
+use feed::FeedEvent;
+use feed::PitchReader;
+use feed::decode_packet;
+use fix::FixMessage;
+use fix::FixMarketData;
+use fix::MdUpdateAction;
 
 use orderbook::PriceBucket;
 use orderbook::Order;
@@ -115,6 +157,34 @@ fn test_parse_trade_status() {
     let res = TradingStatusMsg::parse_msg(msg);
     println!("{:?}", res);
     assert!(res.is_ok());    
+    assert_eq!( res.unwrap().halt_reason, None );
+
+    let msg = "28800168HAAPLSPOTH0XYLUDPY"; 
+    let res = TradingStatusMsg::parse_msg(msg);
+    println!("{:?}", res);
+    assert!(res.is_ok());    
+
+    let o = res.unwrap();
+    assert_eq!( o.halt_reason, Some(HaltReason::VolatilityPause) );
+    assert_eq!( o.halt_flag,   Some('Y') );
+    assert_eq!( HaltReason::from_code("MWC2"), HaltReason::MarketWideCircuitBreaker(2) );
+
+    // a reason that isn't ASCII fails rather than panicking
+    assert!( TradingStatusMsg::parse_msg("28800168HAAPLSPOTH0XYLUDé").is_err() );
+    assert!( BATSMsgFactory::try_parse("28800168HAAPLSPOTH0XYLUDé").is_none() );
+}
+
+#[test]
+fn test_parse_symbol_clear() {
+    let msg = "28800168sAAPL    "; 
+    let res = SymbolClearMsg::parse_msg(msg);
+    println!("{:?}", res);
+    assert!(res.is_ok());    
+    assert_eq!( res.unwrap().symbol, "AAPL    " );
+
+    let obj = BATSMsgFactory::parse(msg);
+    let msg_obj : Option<SymbolClearMsg> = obj.into();
+    assert!(msg_obj.is_some());
 }
 
 #[test]
@@ -126,31 +196,2748 @@ fn test_parse_file() {
     let f = File::open(filename).expect("file not found");
     let f = BufReader::new(f);
 
-    for line in f.lines() {
-        let msg = line.unwrap();
-        let buf = msg.as_str();
-        if buf.chars().nth(8) == Some('J') {
-            let res = AuctionSummaryMsg::parse_msg(buf);
-            println!("{:?}", res);
-            assert!(res.is_ok());
-            break;
-        }
+    for line in f.lines() {
+        let msg = line.unwrap();
+        let buf = msg.as_str();
+        if buf.chars().nth(8) == Some('J') {
+            let res = AuctionSummaryMsg::parse_msg(buf);
+            println!("{:?}", res);
+            assert!(res.is_ok());
+            break;
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parallel_parse() {
+    use feed::FeedEvent;
+    use feed::PitchReader;
+    use parallel;
+
+    let data = std::fs::read("src/pitch_example_data").unwrap();
+    let expected : Vec<String> = PitchReader::new(&data[..]).filter_map(|e| match e {
+        FeedEvent::Message(m) => Some(format!("{:?}", m)),
+        _                     => None
+    }).collect();
+    let msgs = parallel::parse_file("src/pitch_example_data").unwrap();
+    assert_eq!( msgs.iter().map(|m| format!("{:?}", m)).collect::<Vec<_>>(), expected );
+
+    // short input ends up in a chunk a line, and a bad line says where it is.
+    let data = b"28800168A1K27GA00000YS000100AAPL  0001831900Y\r\n\n28800168X1K27GA00000Y000500\n28800169Znope\n";
+    let err = parallel::parse_lines(data).unwrap_err();
+    assert_eq!( (err.line, err.text.as_str()), (4, "28800169Znope") );
+    assert_eq!( parallel::parse_lines(&data[..76]).unwrap().len(), 2 );
+    assert_eq!( parallel::parse_lines(b"").unwrap().len(), 0 );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mapped_file() {
+    use std::io::Write;
+    use feed::FeedEvent;
+    use feed::PitchReader;
+    use mmap::MappedFile;
+
+    let data = std::fs::read("src/pitch_example_data").unwrap();
+    let expected : Vec<String> = PitchReader::new(&data[..]).map(|e| format!("{:?}", e)).collect();
+    let file = MappedFile::open("src/pitch_example_data").unwrap();
+    assert_eq!( file.bytes(), &data[..] );
+    assert_eq!( file.events().map(|e| format!("{:?}", e)).collect::<Vec<_>>(), expected );
+
+    // line ends either way, an empty file, and no line end at the end.
+    let path = std::env::temp_dir().join(format!("mapped_file_{}.txt", std::process::id()));
+    std::fs::File::create(&path).unwrap().write_all(b"28800168A1K27GA00000YS000100AAPL  0001831900Y\r\n\n28800168X1K27GA00000Y000500").unwrap();
+    let file = MappedFile::open(&path).unwrap();
+    assert_eq!( file.lines().collect::<Vec<_>>(), vec!["28800168A1K27GA00000YS000100AAPL  0001831900Y", "", "28800168X1K27GA00000Y000500"] );
+    assert_eq!( file.events().count(), 3 );
+    // a short line and an unknown type are passed over
+    std::fs::File::create(&path).unwrap().write_all(b"288\n28800168ZID0000000001\n28800168X1K27GA00000Y000500\n").unwrap();
+    let file = MappedFile::open(&path).unwrap();
+    let mut events = file.events();
+    assert_eq!( events.by_ref().count(), 2 );
+    assert_eq!( events.skipped(), 2 );
+    // as is one that isn't UTF-8, without ending the session there
+    std::fs::File::create(&path).unwrap().write_all(b"28800168X1K27GA00000Y000500\n\xff\xfe\r\n28800168X1K27GA00000Y000500\n").unwrap();
+    let file = MappedFile::open(&path).unwrap();
+    assert_eq!( file.lines().count(), 2 );
+    let mut events = file.events();
+    assert_eq!( events.by_ref().count(), 3 );
+    assert_eq!( events.skipped(), 1 );
+    std::fs::File::create(&path).unwrap();
+    let file = MappedFile::open(&path).unwrap();
+    assert!( file.is_empty() );
+    assert!( matches!(file.events().collect::<Vec<_>>()[..], [FeedEvent::SessionComplete]) );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_compressed_input() {
+    use std::io::Read;
+    use compressed;
+    use compressed::Compression;
+
+    assert_eq!( Compression::detect(&[0x1f, 0x8b, 8, 0]), Compression::Gzip );
+    assert_eq!( Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd]), Compression::Zstd );
+    assert_eq!( Compression::detect(b"2880"), Compression::None );
+    assert_eq!( Compression::detect(b""), Compression::None );
+
+    let data = std::fs::read("src/pitch_example_data").unwrap();
+    let read = |input : Vec<u8>| {
+        let mut out = Vec::new();
+        compressed::reader(std::io::Cursor::new(input))?.read_to_end(&mut out)?;
+        Ok(out)
+    };
+    let plain : std::io::Result<Vec<u8>> = read(data.clone());
+    assert_eq!( plain.unwrap(), data );
+
+    #[cfg(feature = "gzip")]
+    {
+        use std::io::Write;
+        use flate2::write::GzEncoder;
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gz.write_all(&data).unwrap();
+        let gz : std::io::Result<Vec<u8>> = read(gz.finish().unwrap());
+        assert_eq!( gz.unwrap(), data );
+    }
+    #[cfg(feature = "zstd")]
+    {
+        let zst : std::io::Result<Vec<u8>> = read(::zstd::encode_all(&data[..], 1).unwrap());
+        assert_eq!( zst.unwrap(), data );
+    }
+    #[cfg(not(feature = "zstd"))]
+    {
+        let zst : std::io::Result<Vec<u8>> = read(vec![0x28, 0xb5, 0x2f, 0xfd, 0]);
+        assert_eq!( zst.unwrap_err().kind(), std::io::ErrorKind::Unsupported );
+    }
+    let events = compressed::open_pitch("src/pitch_example_data").unwrap().count();
+    assert!( events > 1 );
+}
+
+#[test]
+fn test_to_pitch_string() {
+    let lines = ["28800168JAAPLSPOTC00010068000000020000",
+                 "28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169d1K27GA00000YS000100AAPL  0001831900YBAML",
+                 "28800168IAAPLSPOTC00010068000000020000000001000000015034000001309800",
+                 "28800168X1K27GA00000Y000500",
+                 "28800168E1K27GA00000Y0001001K27GA00000K",
+                 "28800168RAAPLSPOTS",
+                 "28800168B1K27GA00000Y",
+                 "28800168P1K27GA00000YB000300AAPL  00018319001K27GA00000Z",
+                 "28800168r1K27GA00000YB000300AAPLSPOT00018319001K27GA00000Z",
+                 "28800168HAAPLSPOTT0XY",
+                 "28800168HAAPLSPOTH0XYLUDPY",
+                 "28800168sAAPL    "];
+    for line in lines.iter() {
+        assert_eq!( BATSMsgFactory::parse(line).to_pitch_string().unwrap(), *line );
+    }
+
+    let o = AddOrderMsg{ timestamp : 34200000, msg_type : 'A', order_id : 42, side : 'B', shares : 100,
+                         symbol : String::from("MSFT"), price : 3051200, display : 'Y', part_id : String::from("") };
+    let mut buf = Vec::new();
+    o.write_pitch(&mut buf).unwrap();
+    assert_eq!( buf, b"34200000A000000000016B000100MSFT  0003051200Y\n".to_vec() );
+    assert_eq!( AddOrderMsg::parse_msg(o.to_pitch_string().as_str()).unwrap().order_id, 42 );
+}
+
+#[test]
+fn test_roundtrip() {
+    let msg = BATSMsgFactory::parse("28800168HAAPLSPOTH0XYLUDPY");
+    roundtrip::assert_roundtrip(&msg);
+    roundtrip::assert_roundtrip(&BATSMessage::LoginMsg(LoginMsg::new("0001", "TEST", "secret    ")));
+
+    // too long for the 6 char symbol field
+    let o = AddOrderMsg{ timestamp : 34200000, msg_type : 'A', order_id : 42, side : 'B', shares : 100,
+                         symbol : String::from("MSFTXYZ"), price : 3051200, display : 'Y', part_id : String::from("") };
+    let diffs = roundtrip::roundtrip(&BATSMessage::AddOrderMsg(o)).unwrap();
+    assert_eq!( diffs.len(),       1 );
+    assert_eq!( diffs[0].field,    "symbol" );
+    assert_eq!( diffs[0].reparsed, "\"MSFTXY\"" );
+
+    let time = BATSMsgFactory::parse_binary(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00]);
+    assert_eq!( roundtrip::roundtrip(&time), Err(RoundtripError::Unsupported) );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    use serde_json;
+
+    let msg = BATSMsgFactory::parse("28800168HAAPLSPOTH0XYLUDPY");
+    let json = serde_json::to_string(&msg).unwrap();
+    assert!( json.starts_with("{\"type\":\"trading_status\",\"timestamp\":28800168,") );
+    assert!( json.contains("\"halt_reason\":\"LUDP\"") );
+
+    let back : BATSMessage = serde_json::from_str(&json).unwrap();
+    let status : Option<TradingStatusMsg> = back.into();
+    assert_eq!( status.unwrap().halt_reason, Some(HaltReason::VolatilityPause) );
+
+    let msg = BATSMsgFactory::parse("28800168IAAPLSPOTC00010068000000020000000001000000015034000001309800");
+    assert!( serde_json::to_string(&msg).unwrap().contains("\"buy_shares\":20000,") );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_jsonl_writer() {
+    use jsonl::JsonlWriter;
+
+    let msgs = vec![BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"),
+                    BATSMsgFactory::parse("28800168X1K27GA00000Y000500")];
+    let mut w = JsonlWriter::new(Vec::new());
+    assert_eq!( w.write_all(msgs).unwrap(), 2 );
+
+    let out = String::from_utf8(w.into_inner()).unwrap();
+    let lines : Vec<&str> = out.lines().collect();
+    assert_eq!( lines.len(), 2 );
+    assert_eq!( lines[1], "{\"type\":\"order_cancel\",\"timestamp\":28800168,\"msg_type\":\"X\",\"order_id\":204969015920664610,\"shares\":500}" );
+}
+
+#[test]
+fn test_csv_exporter() {
+    use csv::CsvExporter;
+
+    let msgs = vec![BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"),
+                    BATSMsgFactory::parse("28800168P1K27GA00000YB000300AAPL  00018319001K27GA00000Z"),
+                    BATSMsgFactory::parse("28800169A1K27GA00001YB000200AAPL  0001831800Y"),
+                    BATSMsgFactory::parse("28800168HAAPLSPOTH0XY"),
+                    BATSMsgFactory::parse_binary(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00])];
+    let mut exporter = CsvExporter::new(|_| Ok(Vec::new()));
+    assert_eq!( exporter.write_all(msgs).unwrap(), 4 );
+
+    let tables = exporter.into_inner();
+    assert_eq!( tables.len(), 3 );
+    let add_order = String::from_utf8(tables["add_order"].clone()).unwrap();
+    let lines : Vec<&str> = add_order.lines().collect();
+    assert_eq!( lines[0], "timestamp,msg_type,order_id,side,shares,symbol,price,display,part_id" );
+    assert_eq!( lines[1], "28800168,A,204969015920664610,S,100,AAPL,1831900,Y," );
+    assert_eq!( lines.len(), 3 );
+    assert!( String::from_utf8(tables["trading_status"].clone()).unwrap().ends_with("\n28800168,H,AAPLSPOT,H,0,,\n") );
+}
+
+#[test]
+fn test_metrics() {
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use metrics;
+    use metrics::Metrics;
+
+    let m = Arc::new(Metrics::new());
+    m.record_message(&BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"));
+    m.record_message(&BATSMsgFactory::parse("28800168P1K27GA00000YB000300AAPL  00018319001K27GA00000Z"));
+    m.record_message(&BATSMsgFactory::parse("28800168P1K27GA00000YB000200AAPL  00018319001K27GA00001Z"));
+    m.record_trade("MSFT", 50);
+    m.record_parse_error();
+    m.record_gap(3);
+    m.observe_book_update(Duration::from_micros(3));
+    assert_eq!( m.time_book_update(|| 7), 7 );
+
+    let s = m.snapshot();
+    assert_eq!( (s.messages["add_order"], s.messages["trade"]), (1, 2) );
+    assert_eq!( (s.parse_errors, s.gaps, s.missed_messages), (1, 1, 3) );
+    assert_eq!( (s.trade_volume["AAPL"], s.trade_volume["MSFT"]), (500, 50) );
+    assert_eq!( s.book_update.count, 2 );
+    assert_eq!( s.book_update.buckets[0].1 + 1, s.book_update.buckets[1].1 ); // the 3us update is in le=5e-6
+
+    let addr = metrics::serve(m.clone(), "127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut resp = String::new();
+    stream.read_to_string(&mut resp).unwrap();
+    assert!( resp.starts_with("HTTP/1.1 200 OK\r\n") );
+    assert!( resp.contains("\norderbook_messages_total{type=\"trade\"} 2\n") );
+    assert!( resp.contains("\norderbook_trade_volume_total{symbol=\"AAPL\"} 500\n") );
+    assert!( resp.contains("\norderbook_book_update_seconds_bucket{le=\"+Inf\"} 2\n") );
+    assert!( resp.contains("\norderbook_missed_messages_total 3\n") );
+}
+
+#[test]
+fn test_order_book() {
+    use book::Levels;
+    use book::OrderBook;
+    use book::Side;
+
+    let mut book = OrderBook::for_symbol("AAPL");
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800168A1K27GA00001YS000200AAPL  0001832000Y",
+                 "28800169A1K27GA00002YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00003YB000100AAPL  0001831800Y",
+                 "28800169A1K27GA00004YB000200AAPL  0001831700Y"] {
+        assert!( book.apply(&BATSMsgFactory::parse(msg)) );
+    }
+    assert!( !book.apply(&BATSMsgFactory::parse("28800169A1K27GA00005YB000300MSFT  0000450000Y")) );
+    let pairs = |levels : Levels| levels.map(|l| (l.price(), l.shares())).collect::<Vec<_>>();
+    assert_eq!( pairs(book.bids()), vec![(1831800, 300), (1831700, 200)] );
+    assert_eq!( pairs(book.asks()), vec![(1831900, 100), (1832000, 200)] );
+    assert_eq!( book.len(), 5 );
+
+    // partial cancel, then executions that take the rest of the order and its level.
+    assert!( book.apply(&BATSMsgFactory::parse("28800170X1K27GA00002Y000050")) );
+    assert_eq!( book.shares_at(Side::Bid, 1831800), 250 );
+    assert!( book.apply(&BATSMsgFactory::parse("28800170E1K27GA00000Y0000601K27GA00000K")) );
+    assert!( book.apply(&BATSMsgFactory::parse("28800170E1K27GA00000Y0000401K27GA00001K")) );
+    assert_eq!( pairs(book.asks()), vec![(1832000, 200)] );
+    assert_eq!( book.len(), 4 );
+
+    // the order's gone, and the MSFT one was never booked.
+    assert!( !book.apply(&BATSMsgFactory::parse("28800170X1K27GA00000Y000010")) );
+    assert!( !book.apply(&BATSMsgFactory::parse("28800170X1K27GA00005Y000010")) );
+
+    let mut over = OrderBook::new();
+    over.add_order(1, Side::Bid, 100, 10);
+    assert!( over.reduce_order(1, 25) );
+    assert!( over.is_empty() && over.bids().next().is_none() );
+
+    assert!( book.apply(&BATSMsgFactory::parse("28800171sAAPL    ")) );
+    assert!( book.is_empty() && book.asks().next().is_none() );
+}
+
+#[test]
+fn test_order_book_bbo() {
+    use book::Bbo;
+    use book::OrderBook;
+    use book::Quote;
+    use book::Side;
+
+    let mut book = OrderBook::new();
+    assert_eq!( book.bbo(), Bbo::default() );
+    book.add_order(1, Side::Bid, 1831800, 100);
+    book.add_order(2, Side::Bid, 1831800, 200);
+    book.add_order(3, Side::Bid, 1831700, 500);
+    book.add_order(4, Side::Ask, 1832000, 100);
+    assert_eq!( book.best_bid(), Some(Quote{ price : 1831800, shares : 300, orders : 2 }) );
+    assert_eq!( book.best_ask(), Some(Quote{ price : 1832000, shares : 100, orders : 1 }) );
+
+    // a better ask takes over, worse levels changing leave the quote alone.
+    book.add_order(5, Side::Ask, 1831900, 50);
+    book.reduce_order(3, 100);
+    assert_eq!( book.bbo(), Bbo{ bid : Some(Quote{ price : 1831800, shares : 300, orders : 2 }),
+                                 ask : Some(Quote{ price : 1831900, shares : 50, orders : 1 }) } );
+    assert_eq!( (book.spread(), book.mid()), (Some(100), Some(1831850.0)) );
+    // 300 bid against 50 offered leans toward the ask.
+    assert!( (book.microprice().unwrap() - (1831800.0 * 50.0 + 1831900.0 * 300.0) / 350.0).abs() < 1e-6 );
+
+    book.reduce_order(1, 40);
+    assert_eq!( book.best_bid(), Some(Quote{ price : 1831800, shares : 260, orders : 2 }) );
+    book.delete_order(2);
+    book.set_remaining(1, 0);
+    assert_eq!( book.best_bid(), Some(Quote{ price : 1831700, shares : 400, orders : 1 }) );
+    book.delete_order(5);
+    book.delete_order(4);
+    assert_eq!( book.best_ask(), None );
+    assert_eq!( (book.spread(), book.mid(), book.microprice()), (None, None, None) );
+    book.clear();
+    assert_eq!( book.bbo(), Bbo::default() );
+}
+
+#[test]
+fn test_order_book_depth() {
+    use book::Depth;
+    use book::OrderBook;
+    use book::Quote;
+    use book::Side;
+
+    let mut book = OrderBook::new();
+    assert_eq!( book.depth(5), Depth::default() );
+    for (id, side, price, shares) in [(1, Side::Bid, 1831800, 100), (2, Side::Bid, 1831800, 200),
+                                      (3, Side::Bid, 1831700, 500), (4, Side::Bid, 1831600, 100),
+                                      (5, Side::Ask, 1832000, 100), (6, Side::Ask, 1831900, 50)] {
+        book.add_order(id, side, price, shares);
+    }
+    let depth = book.depth(2);
+    assert_eq!( depth.bids, vec![Quote{ price : 1831800, shares : 300, orders : 2 },
+                                 Quote{ price : 1831700, shares : 500, orders : 1 }] );
+    assert_eq!( depth.asks, vec![Quote{ price : 1831900, shares : 50, orders : 1 },
+                                 Quote{ price : 1832000, shares : 100, orders : 1 }] );
+    assert_eq!( book.depth(0).bids.len(), 3 );
+    assert_eq!( book.depth(1).bids[0], book.best_bid().unwrap() );
+}
+
+#[test]
+fn test_level_stores() {
+    use std::collections::BTreeMap;
+    use book::OrderBook;
+    use book::Side;
+    use levels::LevelStore;
+    use levels::PriceLadder;
+    use levels::SortedLevels;
+
+    // bids, asks, the orders at 1831900 and the checksum
+    type Seen = (Vec<(u64, u64)>, Vec<(u64, u64)>, Vec<u64>, u64);
+
+    // the same orders into a book over each store, all should end up alike.
+    fn run<S : LevelStore>( mut book : OrderBook<S> ) -> Seen {
+        book.add_order(1, Side::Bid, 1831700, 100);
+        book.add_order(2, Side::Bid, 1831900, 200);
+        book.add_order(3, Side::Ask, 1832300, 300);
+        book.add_order(4, Side::Ask, 1832000, 400);
+        book.add_order(5, Side::Bid, 1831900, 500);
+        book.add_order(6, Side::Ask, 1832600, 600);
+        book.delete_order(1);
+        book.reduce_order(2, 200);
+        book.delete_order(6);
+        book.add_order(7, Side::Bid, 1831500, 700);
+        assert_eq!( book.recompute_checksum(), book.checksum() );
+        let orders = book.orders_at(Side::Bid, 1831900).map(|o| o.order_id).collect();
+        (book.bids().map(|l| (l.price(), l.shares())).collect(), book.asks().rev().map(|l| (l.price(), l.shares())).collect(),
+         orders, book.checksum())
+    }
+
+    let expected = run(OrderBook::new());
+    assert_eq!( expected.0, vec![(1831900, 500), (1831500, 700)] );
+    assert_eq!( expected.1, vec![(1832300, 300), (1832000, 400)] );
+    assert_eq!( expected.2, vec![5] );
+    assert_eq!( run(OrderBook::with_store(None, BTreeMap::new())), expected );
+    assert_eq!( run(OrderBook::with_store(None, SortedLevels::new())), expected );
+    assert_eq!( run(OrderBook::with_store(None, PriceLadder::new(100))), expected );
+    // levels that don't fit on the ladder, off its tick or past its span, still come out in order.
+    assert_eq!( run(OrderBook::with_store(None, PriceLadder::new(300))), expected );
+    assert_eq!( run(OrderBook::with_store(None, PriceLadder::new(100).max_slots(4))), expected );
+
+    // a stub quote far off doesn't stretch the ladder out to it.
+    let mut book = OrderBook::with_store(None, PriceLadder::new(100).max_slots(10));
+    book.add_order(1, Side::Ask, 1832000, 100);
+    book.add_order(2, Side::Ask, 9999999900, 100);
+    book.add_order(3, Side::Ask, 1832050, 100);
+    book.add_order(4, Side::Ask, 1832500, 100);
+    book.add_order(5, Side::Ask, 100, 100);
+    assert_eq!( book.asks().map(|l| l.price()).collect::<Vec<_>>(), vec![100, 1832000, 1832050, 1832500, 9999999900] );
+    assert_eq!( book.asks().rev().map(|l| l.price()).collect::<Vec<_>>(), vec![9999999900, 1832500, 1832050, 1832000, 100] );
+    book.delete_order(5);
+    book.delete_order(3);
+    assert_eq!( (book.asks().len(), book.best_ask().map(|q| q.price)), (3, Some(1832000)) );
+
+    // the ladder shrinks back to the levels left at its ends.
+    let mut book = OrderBook::with_store(Some("AAPL"), PriceLadder::new(100));
+    book.add_order(1, Side::Ask, 1832000, 100);
+    book.add_order(2, Side::Ask, 1831000, 100);
+    book.add_order(3, Side::Ask, 1833000, 100);
+    book.delete_order(2);
+    book.delete_order(3);
+    assert_eq!( book.asks().len(), 1 );
+    assert_eq!( book.best_ask().map(|q| q.price), Some(1832000) );
+    assert_eq!( book.level(Side::Ask, 1832050), None );
+    assert_eq!( book.symbol(), Some("AAPL") );
+}
+
+#[test]
+fn test_slab() {
+    use slab::Slab;
+
+    let mut slab = Slab::with_capacity(4);
+    let a = slab.insert("a");
+    let b = slab.insert("b");
+    let c = slab.insert("c");
+    assert_eq!( slab.remove(b), Some("b") );
+    assert_eq!( (slab.remove(b), slab.get(b), slab.len()), (None, None, 2) );
+    // the freed slot is the next one used, indexes to the others don't move.
+    assert_eq!( slab.insert("d"), b );
+    assert_eq!( slab.capacity(), 3 );
+    *slab.get_mut(c).unwrap() = "e";
+    assert_eq!( slab.iter().collect::<Vec<_>>(), vec![(a, &"a"), (b, &"d"), (c, &"e")] );
+    slab.clear();
+    assert!( slab.is_empty() && slab.get(a).is_none() );
+}
+
+#[test]
+fn test_order_book_levels() {
+    use book::Level;
+    use book::OrderBook;
+    use book::Side;
+
+    let mut book = OrderBook::new();
+    book.add_order(1, Side::Ask, 1831900, 100);
+    book.add_order(2, Side::Ask, 1831900, 200);
+    book.add_order(3, Side::Ask, 1831900, 300);
+    book.add_order(4, Side::Ask, 1832000, 400);
+
+    let level = book.level(Side::Ask, 1831900).unwrap();
+    assert_eq!( (level.price(), level.shares(), level.order_count()), (1831900, 600, 3) );
+    assert_eq!( level.orders().collect::<Vec<_>>(), vec![1, 2, 3] );
+
+    // a partial fill keeps its place in line, a replaced order goes to the back.
+    book.reduce_order(1, 50);
+    book.add_order(2, Side::Ask, 1831900, 200);
+    let level = book.level(Side::Ask, 1831900).unwrap();
+    assert_eq!( level.orders().collect::<Vec<_>>(), vec![1, 3, 2] );
+    assert_eq!( level.orders().map(|id| book.order_shares(id).unwrap()).collect::<Vec<_>>(), vec![50, 300, 200] );
+
+    book.delete_order(3);
+    assert_eq!( book.asks().map(|l| (l.price(), l.order_count())).collect::<Vec<_>>(),
+                vec![(1831900, 2), (1832000, 1)] );
+    assert_eq!( book.level(Side::Bid, 1831900), None );
+    assert_eq!( book.order_shares(3), None );
+    assert_eq!( book.get_order(1).map(|o| (o.side, o.price, o.shares, o.symbol)), Some((Side::Ask, 1831900, 50, None)) );
+    assert_eq!( book.get_order(3), None );
+
+    // levels best first either side, and orders in time priority within one.
+    book.add_order(5, Side::Bid, 1831700, 100);
+    book.add_order(6, Side::Bid, 1831800, 100);
+    assert_eq!( book.bids().map(Level::price).collect::<Vec<_>>(), vec![1831800, 1831700] );
+    assert_eq!( book.levels(Side::Ask).rev().map(Level::price).collect::<Vec<_>>(), vec![1832000, 1831900] );
+    assert_eq!( book.asks().len(), 2 );
+    assert_eq!( book.orders_at(Side::Ask, 1831900).map(|o| (o.order_id, o.shares)).collect::<Vec<_>>(), vec![(1, 50), (2, 200)] );
+    assert_eq!( book.orders_at(Side::Bid, 1831900).count(), 0 );
+
+    // the front of the line filling, the back leaving and the line emptying out.
+    book.add_order(7, Side::Ask, 1831900, 100);
+    book.add_order(8, Side::Ask, 1831900, 100);
+    book.reduce_order(1, 50);
+    book.delete_order(8);
+    let level = book.level(Side::Ask, 1831900).unwrap();
+    assert_eq!( level.orders().collect::<Vec<_>>(), vec![2, 7] );
+    book.add_order(9, Side::Ask, 1831900, 100);
+    book.delete_order(2);
+    book.delete_order(7);
+    assert_eq!( book.level(Side::Ask, 1831900).unwrap().orders().collect::<Vec<_>>(), vec![9] );
+    book.delete_order(9);
+    assert_eq!( book.level(Side::Ask, 1831900), None );
+    book.add_order(10, Side::Ask, 1831900, 100);
+    assert_eq!( book.orders_at(Side::Ask, 1831900).map(|o| o.order_id).collect::<Vec<_>>(), vec![10] );
+}
+
+#[test]
+fn test_seqlock_book() {
+    use std::thread;
+    use book::OrderBook;
+    use seqlock::BookWriter;
+
+    // each add is a new best bid a tick up, so a publish's version says what it must hold.
+    let msgs : Vec<_> = (0..2000).map(|i| BATSMsgFactory::parse(&format!("28800168AID{:010}B000100AAPL  {:010}Y", i, 1000000 + i * 100)))
+                                 .collect();
+    let mut writer = BookWriter::new(OrderBook::new(), 3);
+    let reader = writer.reader();
+    assert_eq!( (reader.version(), reader.depth().bids.len(), reader.max_depth()), (1, 0, 3) );
+
+    let readers : Vec<_> = (0..2).map(|_| {
+        let reader = reader.clone();
+        thread::spawn(move || {
+            let mut last = 0;
+            while last < 2001 {
+                let (depth, version) = reader.versioned_depth();
+                assert!( version >= last );
+                let added = version - 1;
+                assert_eq!( depth.bids.len() as u64, added.min(3) );
+                if added > 0 {
+                    assert_eq!( depth.bids[0].price, 1000000 + (added - 1) * 100 );
+                    assert!( depth.bids.windows(2).all(|w| w[0].price == w[1].price + 100) );
+                }
+                last = version;
+            }
+        })
+    }).collect();
+    for msg in &msgs {
+        writer.apply(msg);
+    }
+    for r in readers {
+        r.join().unwrap();
+    }
+
+    assert_eq!( reader.bbo(), writer.book().bbo() );
+    assert_eq!( reader.depth(), writer.book().depth(3) );
+    assert_eq!( reader.depth_to(1).bids.len(), 1 );
+
+    // a depth of 0 still has the top level to publish.
+    let mut writer = BookWriter::new(OrderBook::new(), 0);
+    writer.apply(&msgs[0]);
+    writer.apply(&msgs[1]);
+    assert_eq!( (writer.reader().max_depth(), writer.reader().depth(), writer.reader().depth_to(0)), (1, writer.book().depth(1), writer.book().depth(1)) );
+}
+
+#[test]
+fn test_spsc_pipeline() {
+    use std::sync::Arc;
+    use book::BookManager;
+    use pipeline::Overflow;
+    use pipeline::Pipeline;
+    use pipeline::ring;
+
+    let (mut producer, mut consumer) = ring(3);
+    assert_eq!( producer.capacity(), 4 );
+    for i in 0..4 {
+        assert!( producer.push(i, Overflow::Drop).unwrap() );
+    }
+    assert_eq!( producer.try_push(4), Err(4) );
+    assert!( !producer.push(4, Overflow::Drop).unwrap() );
+    assert_eq!( (consumer.len(), consumer.try_pop(), consumer.try_pop()), (4, Some(0), Some(1)) );
+    assert!( producer.push(5, Overflow::Wait).unwrap() );
+    drop(producer);
+    assert_eq!( consumer.by_ref().collect::<Vec<_>>(), vec![2, 3, 5] );
+    assert_eq!( consumer.pop(), None );
+    let stats = consumer.stats();
+    assert_eq!( (stats.pushed, stats.popped, stats.dropped, stats.full, stats.high_water), (5, 5, 1, 1, 4) );
+
+    // whatever's left in the ring is dropped with it.
+    let counted = Arc::new(());
+    let (mut producer, consumer) = ring(8);
+    producer.try_push(counted.clone()).unwrap();
+    producer.try_push(counted.clone()).unwrap();
+    drop((producer, consumer));
+    assert_eq!( Arc::strong_count(&counted), 1 );
+
+    // a producer waiting on a full ring gives up once the consumer's dropped.
+    let (mut producer, consumer) = ring(1);
+    producer.push(0, Overflow::Wait).unwrap();
+    let waiting = std::thread::spawn(move || (producer.push(1, Overflow::Wait).map_err(|e| e.kind()),
+                                              producer.push(2, Overflow::Drop).map_err(|e| e.kind())));
+    drop(consumer);
+    let broken = Err(std::io::ErrorKind::BrokenPipe);
+    assert_eq!( waiting.join().unwrap(), (broken, broken) );
+
+    // a decoder thread feeding the books ends up where applying in one loop does.
+    let msgs : Vec<_> = (0..5000).map(|i| BATSMsgFactory::parse(&format!("28800168AID{:010}{}000100AAPL  {:010}Y", i,
+                                                                         if i % 2 == 0 { 'B' } else { 'S' },
+                                                                         1000000 + (i % 50) * 100 + (i % 2) * 10000)))
+                                 .collect();
+    let mut direct = BookManager::new();
+    for msg in &msgs {
+        direct.apply(msg);
+    }
+    let mut books = BookManager::new();
+    let mut pipeline = Pipeline::spawn(msgs.into_iter(), 64, Overflow::Wait);
+    assert_eq!( pipeline.run(&mut books), 5000 );
+    assert_eq!( books.book("AAPL").unwrap().depth(100), direct.book("AAPL").unwrap().depth(100) );
+    let stats = pipeline.stats();
+    assert_eq!( (stats.pushed, stats.popped, stats.dropped), (5000, 5000, 0) );
+    assert!( stats.high_water <= 64 );
+    assert_eq!( pipeline.queued(), 0 );
+}
+
+#[test]
+fn test_sharded_books() {
+    use book::BookManager;
+    use sharded::ShardedBooks;
+
+    // adds across a handful of symbols, then executions, cancels and a reused id on them.
+    let symbols = ["AAPL  ", "MSFT  ", "IBM   ", "GOOG  ", "SPY   ", "QQQ   "];
+    let mut lines : Vec<String> = (0..600).map(|i| format!("28800168AID{:010}{}000100{}{:010}Y", i, if i % 2 == 0 { 'B' } else { 'S' },
+                                                           symbols[i % 6], 1000000 + (i % 20) * 100 + (i % 2) * 10000))
+                                          .collect();
+    for i in (0..600).step_by(3) {
+        lines.push(format!("28800169EID{:010}0000601K27GA00010K", i));
+        lines.push(format!("28800170XID{:010}000040", i + 1));
+    }
+    lines.push(String::from("28800171AID0000000002B000300MSFT  0001000000Y"));
+    lines.push(String::from("28800171sIBM     "));
+    let parse = || lines.iter().map(|l| BATSMsgFactory::parse(l));
+
+    let mut single = BookManager::new();
+    for msg in parse() {
+        single.apply(&msg);
+    }
+    let mut sharded = ShardedBooks::new(3);
+    for msg in parse() {
+        sharded.apply(msg);
+    }
+    assert_eq!( sharded.shards(), 3 );
+    assert_eq!( sharded.symbols(), single.symbols() );
+    assert_eq!( sharded.orders(), single.orders() );
+    for s in &symbols {
+        assert_eq!( sharded.bbo(s), single.book(s.trim_end()).map(|b| b.bbo()) );
+        assert_eq!( sharded.depth(s, 10), single.book(s.trim_end()).map(|b| b.depth(10)) );
+    }
+    assert_eq!( sharded.bbo("IBM"), None );
+
+    // the managers come back with the symbols split between them.
+    let shard = sharded.shard_of("AAPL");
+    let managers = sharded.finish();
+    assert_eq!( managers.len(), 3 );
+    assert!( managers[shard].book("AAPL").is_some() );
+    assert_eq!( managers.iter().map(BookManager::len).sum::<usize>(), single.len() );
+}
+
+#[test]
+fn test_book_listener() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use book::Bbo;
+    use book::BookEvent;
+    use book::BookManager;
+    use book::Quote;
+    use book::Side;
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let heard = events.clone();
+    let mut books = BookManager::new();
+    books.add_listener(move |symbol : Option<&str>, event : &BookEvent| {
+        heard.lock().unwrap().push((String::from(symbol.unwrap()), event.clone()));
+    });
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YS000200AAPL  0001832000Y",
+                 "28800169A1K27GA00002YB000300MSFT  0000450000Y",
+                 "28800170E1K27GA00000Y0001001K27GA00000K"] {
+        books.apply(&BATSMsgFactory::parse(msg));
+    }
+    let first = match BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y") {
+        BATSMessage::AddOrderMsg(m) => m.order_id,
+        _ => unreachable!()
+    };
+    let exec = match BATSMsgFactory::parse("28800170E1K27GA00000Y0001001K27GA00000K") {
+        BATSMessage::OrderExecutedMsg(m) => m.exec_id,
+        _ => unreachable!()
+    };
+    let ask = |price, shares| Bbo{ bid : None, ask : Some(Quote{ price, shares, orders : 1 }) };
+    let msft = Bbo{ bid : Some(Quote{ price : 450000, shares : 300, orders : 1 }), ask : None };
+    let aapl = |event| (String::from("AAPL"), event);
+    assert_eq!( events.lock().unwrap().drain(..).collect::<Vec<_>>(),
+                vec![aapl(BookEvent::LevelAdded{ side : Side::Ask, price : 1831900 }),
+                     aapl(BookEvent::Bbo(ask(1831900, 100))),
+                     aapl(BookEvent::LevelAdded{ side : Side::Ask, price : 1832000 }),
+                     (String::from("MSFT"), BookEvent::LevelAdded{ side : Side::Bid, price : 450000 }),
+                     (String::from("MSFT"), BookEvent::Bbo(msft)),
+                     aapl(BookEvent::Trade{ order_id : first, exec_id : exec, side : Side::Ask, price : 1831900, shares : 100 }),
+                     aapl(BookEvent::LevelRemoved{ side : Side::Ask, price : 1831900 }),
+                     aapl(BookEvent::Bbo(ask(1832000, 200)))] );
+
+    // a cancel isn't a trade, a clear takes every level off.
+    books.apply(&BATSMsgFactory::parse("28800171X1K27GA00001Y000050"));
+    books.apply(&BATSMsgFactory::parse("28800171sAAPL    "));
+    assert_eq!( events.lock().unwrap().drain(..).collect::<Vec<_>>(),
+                vec![aapl(BookEvent::Bbo(ask(1832000, 150))),
+                     aapl(BookEvent::LevelRemoved{ side : Side::Ask, price : 1832000 }),
+                     aapl(BookEvent::Bbo(Bbo::default()))] );
+}
+
+#[test]
+fn test_hidden_liquidity() {
+    use book::OrderBook;
+    use book::Quote;
+    use book::Side;
+
+    let mut book = OrderBook::new();
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800168A1K27GA00001YS000200AAPL  0001831900N",
+                 "28800169A1K27GA00002YS000300AAPL  0001831850N",
+                 "28800169A1K27GA00003YB000100AAPL  0001831800Y"] {
+        book.apply(&BATSMsgFactory::parse(msg));
+    }
+    assert_eq!( book.depth(0).asks, vec![Quote{ price : 1831850, shares : 300, orders : 1 },
+                                         Quote{ price : 1831900, shares : 300, orders : 2 }] );
+    assert_eq!( book.displayed_depth(0).asks, vec![Quote{ price : 1831900, shares : 100, orders : 1 }] );
+    let level = book.level(Side::Ask, 1831900).unwrap();
+    assert_eq!( (level.displayed_shares(), level.hidden_shares(), level.displayed_count()), (100, 200, 1) );
+
+    // executions against the hidden order come out of its part of the level.
+    let hidden = book.level(Side::Ask, 1831900).unwrap().orders().nth(1).unwrap();
+    assert!( !book.get_order(hidden).unwrap().displayed );
+    book.reduce_order(hidden, 150);
+    let level = book.level(Side::Ask, 1831900).unwrap();
+    assert_eq!( (level.shares(), level.displayed_shares(), level.hidden_shares()), (150, 100, 50) );
+    book.delete_order(hidden);
+    assert_eq!( book.level(Side::Ask, 1831900).unwrap().hidden_shares(), 0 );
+    assert_eq!( book.displayed_depth(1).bids, book.depth(1).bids );
+
+    let restored = OrderBook::restore(&book.snapshot());
+    assert_eq!( restored.displayed_depth(0), book.displayed_depth(0) );
+}
+
+#[test]
+fn test_book_checksum() {
+    use book::OrderBook;
+    use book::Side;
+    use book::level_hash;
+
+    let mut book = OrderBook::new();
+    assert_eq!( (book.checksum(), book.recompute_checksum()), (0, 0) );
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800168A1K27GA00001YS000200AAPL  0001832000Y",
+                 "28800169A1K27GA00002YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00003YB000100AAPL  0001831800Y",
+                 "28800170X1K27GA00002Y000050",
+                 "28800170E1K27GA00000Y0001001K27GA00000K"] {
+        book.apply(&BATSMsgFactory::parse(msg));
+        assert_eq!( book.checksum(), book.recompute_checksum() );
+    }
+    let expected = level_hash(Side::Bid, 1831800, 250).wrapping_add(level_hash(Side::Ask, 1832000, 200));
+    assert_eq!( book.verify_checksum(expected), Ok(()) );
+
+    // the same levels built another way sum the same, a share off doesn't.
+    let mut other = OrderBook::new();
+    other.add_order(9, Side::Ask, 1832000, 200);
+    other.add_order(8, Side::Bid, 1831800, 250);
+    assert_eq!( other.checksum(), book.checksum() );
+    other.reduce_order(8, 1);
+    let err = other.verify_checksum(book.checksum()).unwrap_err();
+    assert_eq!( (err.expected, err.actual), (book.checksum(), other.recompute_checksum()) );
+    assert_ne!( level_hash(Side::Bid, 1831800, 250), level_hash(Side::Ask, 1831800, 250) );
+
+    book.clear();
+    assert_eq!( book.checksum(), 0 );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_book_snapshot() {
+    use book::BookSnapshot;
+    use book::OrderBook;
+    use serde_json;
+
+    let mut book = OrderBook::for_symbol("AAPL");
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800168A1K27GA00001YS000200AAPL  0001831900Y",
+                 "28800169A1K27GA00002YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00003YB000100AAPL  0001831700Y",
+                 "28800170X1K27GA00000Y000050"] {
+        book.apply(&BATSMsgFactory::parse(msg));
+    }
+    let snapshot = book.snapshot();
+    assert_eq!( snapshot.orders.iter().map(|o| o.price).collect::<Vec<_>>(), vec![1831800, 1831700, 1831900, 1831900] );
+
+    let restored = OrderBook::restore(&snapshot);
+    assert_eq!( restored.verify_checksum(snapshot.checksum), Ok(()) );
+    assert_eq!( restored.symbol(), Some("AAPL") );
+    assert_eq!( restored.depth(0), book.depth(0) );
+    for id in book.asks().flat_map(|l| l.orders()) {
+        assert_eq!( restored.get_order(id), book.get_order(id) );
+    }
+    assert_eq!( restored.asks().next().unwrap().orders().collect::<Vec<_>>(),
+                book.asks().next().unwrap().orders().collect::<Vec<_>>() );
+    assert_eq!( restored.snapshot(), snapshot );
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let back : BookSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!( back, snapshot );
+}
+
+#[test]
+fn test_snapshot_diff() {
+    use book::LevelChange;
+    use book::OrderBook;
+    use book::Quote;
+    use book::Side;
+
+    let mut primary = OrderBook::new();
+    primary.add_order(1, Side::Bid, 1831800, 100);
+    primary.add_order(2, Side::Bid, 1831700, 200);
+    primary.add_order(3, Side::Ask, 1831900, 300);
+    let mut standby = OrderBook::restore(&primary.snapshot());
+    assert!( primary.snapshot().diff(&standby.snapshot()).is_empty() );
+
+    // the standby missed a cancel and an add, and has an order that's gone.
+    primary.delete_order(2);
+    primary.add_order(4, Side::Ask, 1832000, 50);
+    standby.add_order(5, Side::Bid, 1831800, 10);
+    let diff = primary.snapshot().diff(&standby.snapshot());
+    assert_eq!( diff.added, vec![(Side::Bid, Quote{ price : 1831700, shares : 200, orders : 1 })] );
+    assert_eq!( diff.removed, vec![(Side::Ask, Quote{ price : 1832000, shares : 50, orders : 1 })] );
+    assert_eq!( diff.changed, vec![LevelChange{ side : Side::Bid, before : Quote{ price : 1831800, shares : 100, orders : 1 },
+                                                after : Quote{ price : 1831800, shares : 110, orders : 2 } }] );
+    assert_eq!( diff.share_drift(), 260 );
+}
+
+#[test]
+fn test_l2_book() {
+    use book::BookManager;
+    use book::BookMode;
+    use book::OrderBook;
+    use book::Side;
+
+    let msgs = ["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                "28800168A1K27GA00001YS000200AAPL  0001831900Y",
+                "28800169A1K27GA00002YB000200AAPL  0001831800Y",
+                "28800169A1K27GA00003YB000100AAPL  0001831700Y",
+                "28800170X1K27GA00000Y000050",
+                "28800170E1K27GA00002Y0002001K27GA00000K"];
+    let mut l2 = OrderBook::new().book_mode(BookMode::L2);
+    let mut l3 = OrderBook::new();
+    for msg in &msgs {
+        assert_eq!( l2.apply(&BATSMsgFactory::parse(msg)), l3.apply(&BATSMsgFactory::parse(msg)) );
+    }
+    assert_eq!( (l2.mode(), l3.mode()), (BookMode::L2, BookMode::L3) );
+    assert_eq!( l2.depth(0), l3.depth(0) );
+    assert_eq!( (l2.bbo(), l2.checksum(), l2.len()), (l3.bbo(), l3.checksum(), l3.len()) );
+    let level = l2.level(Side::Ask, 1831900).unwrap();
+    assert_eq!( (level.order_count(), level.orders().count()), (2, 0) );
+
+    let restored = OrderBook::restore(&l2.snapshot());
+    assert_eq!( (restored.mode(), restored.depth(0)), (BookMode::L2, l2.depth(0)) );
+    // without queues or timestamps an L2 book's orders take well under half the room.
+    let id = match BATSMsgFactory::parse(msgs[1]) { BATSMessage::AddOrderMsg(m) => m.order_id, _ => panic!() };
+    let order = l2.get_order(id).unwrap();
+    assert_eq!( (order.shares, order.timestamp, l3.get_order(id).unwrap().timestamp), (200, 0, 28800168) );
+    let (mut l2, mut l3) = (OrderBook::new().book_mode(BookMode::L2), OrderBook::new());
+    for i in 0..1000 {
+        l2.add_order(i, Side::Bid, 1000000 + i % 20 * 100, 100);
+        l3.add_order(i, Side::Bid, 1000000 + i % 20 * 100, 100);
+    }
+    l2.reduce_order(7, 40);
+    l3.reduce_order(7, 40);
+    assert_eq!( (l2.depth(0), l2.checksum(), l2.len()), (l3.depth(0), l3.checksum(), l3.len()) );
+    assert!( l2.order_memory() * 2 < l3.order_memory() );
+
+    let mut books = BookManager::new().book_mode(BookMode::L2);
+    books.apply(&BATSMsgFactory::parse(msgs[0]));
+    assert_eq!( books.book("AAPL").unwrap().mode(), BookMode::L2 );
+}
+
+#[test]
+fn test_crossed_book() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use book::BookEvent;
+    use book::OrderBook;
+    use book::Side;
+
+    let alerts = Arc::new(Mutex::new(vec![]));
+    let heard = alerts.clone();
+    let mut book = OrderBook::for_symbol("AAPL");
+    book.add_listener(move |_ : Option<&str>, event : &BookEvent| {
+        if let BookEvent::Crossed{ bid, ask, ref bid_orders, ref ask_orders } = *event {
+            heard.lock().unwrap().push((bid.price, ask.price, bid_orders.clone(), ask_orders.clone()));
+        }
+    });
+    book.add_order(1, Side::Bid, 1831800, 100);
+    book.add_order(2, Side::Ask, 1831900, 100);
+    assert!( !book.is_crossed() && alerts.lock().unwrap().is_empty() );
+
+    book.add_order(3, Side::Bid, 1831900, 100);
+    assert!( book.is_locked() && book.is_crossed() );
+    book.add_order(4, Side::Bid, 1832000, 100);
+    book.add_order(5, Side::Bid, 1832000, 100);
+    assert!( book.is_crossed() && !book.is_locked() );
+    assert_eq!( *alerts.lock().unwrap(), vec![(1831900, 1831900, vec![3], vec![2]),
+                                              (1832000, 1831900, vec![4], vec![2]),
+                                              (1832000, 1831900, vec![4, 5], vec![2])] );
+
+    book.delete_order(2);
+    assert!( !book.is_crossed() );
+    assert_eq!( alerts.lock().unwrap().len(), 3 );
+}
+
+#[test]
+fn test_vwap_tracker() {
+    use vwap::VwapTracker;
+    use vwap::VwapWindow;
+
+    // 100 at 183.20 at 08:00:01, 300 at 183.40 at 08:00:03, then the first breaks.
+    let msgs : Vec<BATSMessage> = ["28801000P1K27GA00001YB000100AAPL  00018320001K27GA00010K",
+                                   "28803000P1K27GA00002YS000300AAPL  00018340001K27GA00011K",
+                                   "28804000B1K27GA00010K"].iter().map(|m| BATSMsgFactory::parse(m)).collect();
+    let mut session = VwapTracker::new(VwapWindow::Session);
+    let mut anchored = VwapTracker::new(VwapWindow::Anchored(28_802_000_000_000));
+    let mut rolling = VwapTracker::new(VwapWindow::Rolling(1_000_000_000));
+    for msg in &msgs[..2] {
+        assert!( session.apply(msg) );
+        anchored.apply(msg);
+        rolling.apply(msg);
+    }
+    assert_eq!( session.vwap("AAPL"), Some((1832000.0 * 100.0 + 1834000.0 * 300.0) / 400.0) );
+    assert_eq!( session.volume("AAPL"), 400 );
+    // the first trade's before the anchor, and out of the rolling second by the time of the other.
+    assert_eq!( (anchored.vwap("AAPL"), anchored.volume("AAPL")), (Some(1834000.0), 300) );
+    assert_eq!( (rolling.vwap("AAPL"), rolling.volume("AAPL")), (Some(1834000.0), 300) );
+
+    assert!( session.apply(&msgs[2]) );
+    assert_eq!( (session.vwap("AAPL"), session.volume("AAPL")), (Some(1834000.0), 300) );
+    assert!( !anchored.apply(&msgs[2]) && !rolling.apply(&msgs[2]) );
+    assert_eq!( session.vwap("MSFT"), None );
+
+    // an exec id reused inside the window keeps only its new place in it: by 08:00:11.55 the
+    // trade at 10.5 is out, the one replacing 10.0 at 10.6 isn't.
+    for msg in &["28810000P1K27GA00003YB000100AAPL  00018320001K27GA00020K",
+                 "28810500P1K27GA00004YB000200AAPL  00018330001K27GA00021K",
+                 "28810600P1K27GA00005YB000100AAPL  00018340001K27GA00020K",
+                 "28811550P1K27GA00006YB000100AAPL  00018350001K27GA00022K"] {
+        rolling.apply(&BATSMsgFactory::parse(msg));
+    }
+    assert_eq!( (rolling.vwap("AAPL"), rolling.volume("AAPL")), (Some(1834500.0), 200) );
+}
+
+#[test]
+fn test_twap_tracker() {
+    use book::BookManager;
+    use twap::IntervalStats;
+    use twap::PriceSource;
+    use twap::TwapTracker;
+
+    let msgs : Vec<BATSMessage> = ["28800100A1K27GA00000YS000100AAPL  0001831900Y",
+                                   "28800200A1K27GA00001YB000200AAPL  0001831800Y",
+                                   "28800300P1K27GA00002YB000100AAPL  00018320001K27GA00010K",
+                                   "28802500A1K27GA00003YB000100AAPL  0001831850Y",
+                                   "28804000A1K27GA00004YB000100MSFT  0000450000Y"].iter().map(|m| BATSMsgFactory::parse(m)).collect();
+    let (mut mid, mut mid_books) = (TwapTracker::new(1_000_000_000, PriceSource::Mid), BookManager::new());
+    let (mut last, mut last_books) = (TwapTracker::new(1_000_000_000, PriceSource::Last), BookManager::new());
+    for msg in &msgs {
+        mid.apply(&mut mid_books, msg);
+        last.apply(&mut last_books, msg);
+    }
+
+    // sampled each second from 08:00:01 to 08:00:04, the better bid coming in at 08:00:02.5.
+    let secs = |s : u64| (28_800 + s) * 1_000_000_000;
+    assert_eq!( mid.samples("AAPL").map(|(t, _)| t).collect::<Vec<_>>(), vec![secs(1), secs(2), secs(3), secs(4)] );
+    assert_eq!( mid.stats("AAPL", secs(1), secs(3)), Some(IntervalStats{ twap : 1831850.0, min : 1831850.0, max : 1831850.0, samples : 2 }) );
+    assert_eq!( mid.stats("AAPL", 0, u64::MAX), Some(IntervalStats{ twap : 1831862.5, min : 1831850.0, max : 1831875.0, samples : 4 }) );
+    assert_eq!( mid.stats("AAPL", secs(5), secs(9)), None );
+    // MSFT has no offer so no mid yet.
+    assert_eq!( mid.twap("MSFT"), None );
+
+    assert_eq!( last.twap("AAPL"), Some(1832000.0) );
+}
+
+#[test]
+fn test_bar_builder() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use bars::Bar;
+    use bars::BarBuilder;
+    use bars::BarInterval;
+    use book::BookEvent;
+
+    let trade = |ms : u32, price : u64, shares : u32| BATSMsgFactory::parse(&format!("{}P1K27GA00001YB{:06}AAPL  {:010}1K27GA00010K", ms, shares, price));
+    let bars = Arc::new(Mutex::new(Vec::new()));
+    let seen = bars.clone();
+    let mut builder = BarBuilder::new(BarInterval::Minute).fill_empty(true);
+    builder.add_listener(move |symbol : Option<&str>, e : &BookEvent| if let BookEvent::Bar(bar) = e {
+        seen.lock().unwrap().push((String::from(symbol.unwrap()), *bar));
+    });
+
+    let minute = |m : u64| (480 + m) * 60_000_000_000;
+    assert!( builder.apply(&trade(28810000, 1832000, 100)) );
+    builder.apply(&trade(28850000, 1834000, 200));
+    builder.apply(&trade(28855000, 1831000, 100));
+    assert!( bars.lock().unwrap().is_empty() );
+    assert_eq!( builder.current("AAPL").map(|b| (b.start, b.volume)), Some((minute(0), 400)) );
+
+    // the 08:00 bar finishes when 08:03 trades, with flat bars for the two quiet minutes.
+    builder.apply(&trade(28985000, 1833000, 100));
+    builder.flush();
+    let flat = |m : u64| Bar{ start : minute(m), open : 1831000, high : 1831000, low : 1831000, close : 1831000, volume : 0, trades : 0 };
+    assert_eq!( bars.lock().unwrap().iter().map(|(_, b)| *b).collect::<Vec<_>>(),
+                vec![Bar{ start : minute(0), open : 1832000, high : 1834000, low : 1831000, close : 1831000, volume : 400, trades : 3 },
+                     flat(1), flat(2),
+                     Bar{ start : minute(3), open : 1833000, high : 1833000, low : 1833000, close : 1833000, volume : 100, trades : 1 }] );
+    assert!( bars.lock().unwrap().iter().all(|(s, _)| s == "AAPL") );
+
+    // without filling, a later message of any kind finishes the bar and quiet minutes have none.
+    let count = Arc::new(Mutex::new(0));
+    let counted = count.clone();
+    let mut sparse = BarBuilder::new(BarInterval::Minute);
+    sparse.add_listener(move |_ : Option<&str>, _ : &BookEvent| *counted.lock().unwrap() += 1);
+    sparse.apply(&trade(28810000, 1832000, 100));
+    assert!( !sparse.apply(&BATSMsgFactory::parse("28860000A1K27GA00000YS000100MSFT  0000450000Y")) );
+    assert_eq!( *count.lock().unwrap(), 1 );
+    sparse.apply(&trade(28985000, 1833000, 100));
+    sparse.flush();
+    assert_eq!( *count.lock().unwrap(), 2 );
+}
+
+#[test]
+fn test_volume_profile() {
+    use book::BookManager;
+
+    let mut books = BookManager::new();
+    for msg in &["28800168A1K27GA00000YS000300AAPL  0001831900Y",
+                 "28800169E1K27GA00000Y0001001K27GA00010K",
+                 "28800169E1K27GA00000Y0001001K27GA00011K",
+                 "28800170P1K27GA00001YB000150AAPL  00018320001K27GA00012K",
+                 "28800170P1K27GA00001YB000050AAPL  00018318001K27GA00013K"] {
+        books.apply(&BATSMsgFactory::parse(msg));
+    }
+    // executions against the book and trades off it both count.
+    let profile = books.trades().profile("AAPL").unwrap();
+    assert_eq!( profile.iter().collect::<Vec<_>>(), vec![(1831800, 50), (1831900, 200), (1832000, 150)] );
+    assert_eq!( profile.point_of_control(), Some((1831900, 200)) );
+    assert_eq!( profile.range(1831850, 1835000).map(|(_, v)| v).sum::<u64>(), 350 );
+    assert_eq!( profile.volume_at(1833000), 0 );
+
+    // broken trades come back out, and a price whose trades all broke is gone.
+    books.apply(&BATSMsgFactory::parse("28800171B1K27GA00010K"));
+    books.apply(&BATSMsgFactory::parse("28800171B1K27GA00013K"));
+    let profile = books.trades().profile("AAPL").unwrap();
+    assert_eq!( profile.len(), 2 );
+    assert_eq!( profile.point_of_control(), Some((1832000, 150)) );
+    assert!( books.trades().profile("MSFT").is_none() );
+}
+
+#[test]
+fn test_bbo_recorder() {
+    use bbo_recorder::BboRecorder;
+    use bbo_recorder::BboRow;
+    use book::BookManager;
+
+    let mut books = BookManager::new();
+    let mut recorder = BboRecorder::new();
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00002YB000100AAPL  0001831700Y",
+                 "28800170A1K27GA00003YB000300MSFT  0000450000Y",
+                 "28800171X1K27GA00000Y000100"] {
+        recorder.apply(&mut books, &BATSMsgFactory::parse(msg));
+    }
+    // the bid behind the best one doesn't change the bbo.
+    assert_eq!( recorder.len(), 4 );
+    assert_eq!( recorder.rows("AAPL").collect::<Vec<_>>(),
+                vec![BboRow{ timestamp : 28_800_168_000_000, bid : None, bid_size : None, ask : Some(1831900), ask_size : Some(100) },
+                     BboRow{ timestamp : 28_800_169_000_000, bid : Some(1831800), bid_size : Some(200), ask : Some(1831900), ask_size : Some(100) },
+                     BboRow{ timestamp : 28_800_171_000_000, bid : Some(1831800), bid_size : Some(200), ask : None, ask_size : None }] );
+
+    let mut csv = vec![];
+    recorder.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines : Vec<&str> = csv.lines().collect();
+    assert_eq!( lines[0], "timestamp,symbol,bid,bid_size,ask,ask_size" );
+    assert_eq!( lines[1], "28800168000000,AAPL,,,1831900,100" );
+    assert_eq!( lines[3], "28800170000000,MSFT,450000,300,," );
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_bbo_recorder_arrow() {
+    use arrow_array::Array;
+    use bbo_recorder::BboRecorder;
+    use book::Bbo;
+    use book::Quote;
+
+    let mut recorder = BboRecorder::new();
+    recorder.record("AAPL", 1, Bbo{ bid : Some(Quote{ price : 1831800, shares : 200, orders : 1 }), ask : None });
+    recorder.record("AAPL", 2, Bbo{ bid : Some(Quote{ price : 1831800, shares : 200, orders : 1 }), ask : None });
+    let batch = recorder.to_record_batch().unwrap();
+    assert_eq!( batch.num_rows(), 1 );
+    assert_eq!( batch.column_by_name("ask").unwrap().null_count(), 1 );
+    assert!( batch.schema().field_with_name("ask_size").unwrap().is_nullable() );
+}
+
+#[test]
+fn test_order_lifetimes() {
+    use lifetime::OrderLifetimes;
+
+    let mut lifetimes = OrderLifetimes::new();
+    for msg in &["28800000d1K27GA00000YS000100AAPL  0001831900YBAML",
+                 "28800000A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800000A1K27GA00002YB000100MSFT  0000450000Y",
+                 "28801000E1K27GA00000Y0000601K27GA00010K",
+                 "28801000E1K27GA00001Y0000501K27GA00011K",
+                 "28802000E1K27GA00000Y0000401K27GA00012K",
+                 "28804000X1K27GA00001Y000150"] {
+        lifetimes.apply(&BATSMsgFactory::parse(msg));
+    }
+    // the offer fills in two seconds, the bid is cancelled after four with a quarter filled.
+    let stats = lifetimes.symbol_stats("AAPL").unwrap();
+    assert_eq!( (stats.filled, stats.cancelled), (1, 1) );
+    assert_eq!( stats.time_to_fill.unwrap().mean, 2e9 );
+    assert_eq!( stats.time_to_cancel.unwrap().max, 4e9 );
+    let ratios = stats.fill_ratio.unwrap();
+    assert_eq!( (ratios.count, ratios.mean, ratios.min, ratios.p50, ratios.p99), (2, 0.625, 0.25, 0.25, 1.0) );
+
+    let baml = lifetimes.participant_stats("BAML").unwrap();
+    assert_eq!( (baml.filled, baml.cancelled, baml.time_to_cancel), (1, 0, None) );
+    assert!( lifetimes.symbol_stats("MSFT").is_none() );
+    assert_eq!( lifetimes.live(), 1 );
+}
+
+#[test]
+fn test_queue_position() {
+    use book::BookMode;
+    use book::OrderBook;
+    use book::Side;
+    use queue::QueuePosition;
+
+    let msgs : Vec<BATSMessage> = ["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                                   "28800168A1K27GA00001YS000200AAPL  0001831900Y",
+                                   "28800170A1K27GA00002YS000100AAPL  0001831900Y",
+                                   "28800171X1K27GA00001Y000050",
+                                   "28800172E1K27GA00000Y0001001K27GA00010K",
+                                   "28800173E1K27GA00001Y0001501K27GA00011K",
+                                   "28800174E1K27GA00002Y0001001K27GA00012K"].iter().map(|m| BATSMsgFactory::parse(m)).collect();
+    for mode in [BookMode::L3, BookMode::L2] {
+        let mut book = OrderBook::new().book_mode(mode);
+        book.apply(&msgs[0]);
+        book.apply(&msgs[1]);
+        // 150 offered behind the 300 there, timed by message number.
+        let mut queue = QueuePosition::join(&book, 169, Side::Ask, 1831900, 150);
+        for (i, msg) in msgs.iter().enumerate().skip(2) {
+            queue.on_message(168 + i as u64, &book, msg);
+            book.apply(msg);
+        }
+        match mode {
+            // the cancel ahead moves it up, then it's filled by the order that came after it
+            // trading.
+            BookMode::L3 => {
+                assert_eq!( queue.history(), &[(169, 300), (171, 250), (172, 150), (173, 0)] );
+                assert_eq!( (queue.filled(), queue.is_filled(), queue.orders_ahead()), (100, false, Some(0)) );
+            },
+            // without the ids the cancel is taken to be behind it, so it only gets 50.
+            BookMode::L2 => {
+                assert_eq!( queue.history(), &[(169, 300), (172, 200), (173, 50), (174, 0)] );
+                assert_eq!( (queue.filled(), queue.orders_ahead()), (50, None) );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_market_by_price() {
+    use book::BookManager;
+    use book::Side;
+    use mbp::MarketByPrice;
+    use mbp::MbpAction;
+    use mbp::MbpDelta;
+
+    let mut books = BookManager::new();
+    let mut mbp = MarketByPrice::new(2);
+    let (mut bids, mut asks) = (vec![], vec![]);
+    let mut deltas = vec![];
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00002YB000100AAPL  0001831850Y",
+                 "28800169A1K27GA00003YB000300AAPL  0001831700Y",
+                 "28800170X1K27GA00002Y000100",
+                 "28800171E1K27GA00001Y0000501K27GA00010K"] {
+        let d = mbp.apply(&mut books, &BATSMsgFactory::parse(msg));
+        for delta in &d {
+            delta.apply(if delta.side == Side::Bid { &mut bids } else { &mut asks });
+        }
+        deltas.push(d);
+    }
+    let delta = |side, index, action, price, shares| MbpDelta{ side, index, action, price, shares };
+    assert_eq!( deltas[0], vec![delta(Side::Ask, 0, MbpAction::New, 1831900, 100)] );
+    assert_eq!( deltas[2], vec![delta(Side::Bid, 0, MbpAction::New, 1831850, 100)] );
+    // a third bid is past the depth.
+    assert!( deltas[3].is_empty() );
+    // the best bid going brings the third one up into view.
+    assert_eq!( deltas[4], vec![delta(Side::Bid, 0, MbpAction::Delete, 1831850, 0), delta(Side::Bid, 1, MbpAction::New, 1831700, 300)] );
+    assert_eq!( deltas[5], vec![delta(Side::Bid, 0, MbpAction::Change, 1831800, 150)] );
+
+    // the receiving end has the same levels as the book.
+    assert_eq!( (bids.as_slice(), asks.as_slice()), (mbp.levels("AAPL", Side::Bid), mbp.levels("AAPL", Side::Ask)) );
+    assert_eq!( bids, vec![(1831800, 150), (1831700, 300)] );
+
+    let cleared = mbp.apply(&mut books, &BATSMsgFactory::parse("28800172sAAPL    "));
+    assert_eq!( cleared.iter().filter(|d| d.action == MbpAction::Delete).count(), 3 );
+}
+
+#[test]
+fn test_trade_breaks() {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use book::BookEvent;
+    use book::BookManager;
+
+    let broken = Arc::new(Mutex::new(vec![]));
+    let heard = broken.clone();
+    let mut books = BookManager::new();
+    books.add_listener(move |symbol : Option<&str>, event : &BookEvent| {
+        if let BookEvent::TradeBroken{ price, shares, .. } = *event {
+            heard.lock().unwrap().push((String::from(symbol.unwrap()), price, shares));
+        }
+    });
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169E1K27GA00000Y0000601K27GA00010K",
+                 "28800169E1K27GA00000Y0000401K27GA00011K",
+                 "28800170P1K27GA00001YB000200AAPL  00018320001K27GA00012K"] {
+        books.apply(&BATSMsgFactory::parse(msg));
+    }
+    let stats = books.trades().stats("AAPL").unwrap().clone();
+    assert_eq!( (stats.trades, stats.volume, stats.last_price), (3, 300, Some(1832000)) );
+    assert_eq!( stats.notional, 100 * 1831900 + 200 * 1832000 );
+    assert!( (stats.vwap().unwrap() - 1831966.6667).abs() < 1e-3 );
+
+    // breaking the last trade puts the last price back to the one before it.
+    books.apply(&BATSMsgFactory::parse("28800171B1K27GA00012K"));
+    books.apply(&BATSMsgFactory::parse("28800171B1K27GA00099K"));
+    let stats = books.trades().stats("AAPL").unwrap();
+    assert_eq!( (stats.trades, stats.volume, stats.last_price, stats.breaks), (2, 100, Some(1831900), 1) );
+    assert_eq!( stats.vwap(), Some(1831900.0) );
+    assert_eq!( *broken.lock().unwrap(), vec![(String::from("AAPL"), 1832000, 200)] );
+
+    books.apply(&BATSMsgFactory::parse("28800171B1K27GA00010K"));
+    books.apply(&BATSMsgFactory::parse("28800171B1K27GA00011K"));
+    let stats = books.trades().stats("AAPL").unwrap();
+    assert_eq!( (stats.trades, stats.volume, stats.last_price, stats.vwap()), (0, 0, None, None) );
+
+    // an exec id reused replaces its trade, that's not a break.
+    books.apply(&BATSMsgFactory::parse("28800172P1K27GA00002YB000100AAPL  00018320001K27GA00013K"));
+    books.apply(&BATSMsgFactory::parse("28800173P1K27GA00003YB000300AAPL  00018321001K27GA00013K"));
+    let stats = books.trades().stats("AAPL").unwrap();
+    assert_eq!( (stats.trades, stats.volume, stats.last_price, stats.breaks), (1, 300, Some(1832100), 3) );
+}
+
+#[test]
+fn test_halt_gating() {
+    use book::BookManager;
+    use book::Side;
+    use status::SymbolState;
+    use status::Update;
+    use status::ViolationPolicy;
+
+    let parse = |msg : &str| BATSMsgFactory::parse(msg);
+    let mut books = BookManager::new().violation_policy(ViolationPolicy::Reject);
+    assert!( books.apply(&parse("28800168A1K27GA00000YS000100AAPL  0001831900Y")) );
+    assert!( !books.apply(&parse("28800169HAAPL    H0XY")) );
+    assert_eq!( (books.state("AAPL"), books.state("MSFT")), (SymbolState::Halted, SymbolState::Trading) );
+
+    // nothing executes or gets added during the halt, but orders can still be pulled.
+    assert!( !books.apply(&parse("28800170E1K27GA00000Y0000601K27GA00010K")) );
+    assert!( !books.apply(&parse("28800170A1K27GA00001YB000100AAPL  0001831800Y")) );
+    assert!( books.apply(&parse("28800170X1K27GA00000Y000010")) );
+    assert!( books.apply(&parse("28800170A1K27GA00002YB000300MSFT  0000450000Y")) );
+    let violations = books.take_violations();
+    assert_eq!( violations.iter().map(|v| (v.symbol.as_str(), v.state, v.update, v.rejected)).collect::<Vec<_>>(),
+                vec![("AAPL", SymbolState::Halted, Update::Execution, true), ("AAPL", SymbolState::Halted, Update::Add, true)] );
+    assert_eq!( violations[0].timestamp, 28800170 );
+
+    // quote only takes orders, then trading resumes.
+    books.apply(&parse("28800171HAAPL    Q0XY"));
+    assert!( books.apply(&parse("28800171A1K27GA00001YB000100AAPL  0001831800Y")) );
+    assert!( !books.apply(&parse("28800171E1K27GA00000Y0000101K27GA00011K")) );
+    books.apply(&parse("28800172HAAPL    T0XY"));
+    assert!( books.apply(&parse("28800172E1K27GA00000Y0000101K27GA00012K")) );
+    assert_eq!( books.violations().len(), 1 );
+    assert_eq!( books.book("AAPL").unwrap().shares_at(Side::Ask, 1831900), 80 );
+
+    // the default policy applies them anyway.
+    let mut books = BookManager::new();
+    books.apply(&parse("28800168HMSFT    H0XY"));
+    assert!( books.apply(&parse("28800169A1K27GA00002YB000300MSFT  0000450000Y")) );
+    assert!( !books.violations()[0].rejected );
+}
+
+#[test]
+fn test_auction_state() {
+    use book::BookManager;
+
+    let mut books = BookManager::new();
+    assert!( books.auction("AAPL").is_none() );
+    for msg in &["28800168IAAPL    O00018300000000002000000000100000018310000001831500",
+                 "28800169IAAPL    O00018300000000003000000000150000018320000001832500"] {
+        books.apply(&BATSMsgFactory::parse(msg));
+    }
+    {
+        let auction = books.auction("AAPL").unwrap();
+        assert_eq!( (auction.auction_type, auction.updates, auction.updated), ('O', 2, 28800169) );
+        assert_eq!( (auction.reference_price, auction.indicative_price, auction.auction_only_price), (1830000, 1832000, 1832500) );
+        assert_eq!( (auction.buy_shares, auction.sell_shares, auction.imbalance()), (3000, 1500, 1500) );
+        assert!( !auction.is_final() );
+    }
+
+    books.apply(&BATSMsgFactory::parse("28800170JAAPL    O00018320000000001500"));
+    let result = books.auction("AAPL").unwrap().result.unwrap();
+    assert_eq!( (result.timestamp, result.price, result.shares), (28800170, 1832000, 1500) );
+    assert_eq!( books.auction("AAPL").unwrap().buy_shares, 3000 );
+
+    // the close starts over.
+    books.apply(&BATSMsgFactory::parse("28800171IAAPL    C00018300000000001000000000200000018310000001831500"));
+    let auction = books.auction("AAPL").unwrap();
+    assert_eq!( (auction.auction_type, auction.updates, auction.imbalance(), auction.result), ('C', 1, -1000, None) );
+}
+
+#[test]
+fn test_replay_until() {
+    use replay::Replay;
+    use replay::time_of_day;
+
+    // times are ms past midnight, 28800168 is 08:00:00.168.
+    let msgs = vec!["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                    "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                    "28801000X1K27GA00000Y000040",
+                    "28802500E1K27GA00001Y0000501K27GA00000K",
+                    "28803000A1K27GA00002YB000100AAPL  0001831700Y"];
+    let mut replay = Replay::new(msgs.into_iter().map(BATSMsgFactory::parse));
+    assert_eq!( replay.next_time(), Some(28_800_168_000_000) );
+
+    assert_eq!( replay.replay_until(time_of_day(8, 0, 0)), 0 );
+    assert_eq!( replay.replay_until(time_of_day(8, 0, 1)), 3 );
+    assert_eq!( replay.time(), 28_801_000_000_000 );
+    assert_eq!( replay.books().book("AAPL").unwrap().depth(0).asks[0].shares, 60 );
+    assert_eq!( replay.books().book("AAPL").unwrap().depth(0).bids[0].shares, 200 );
+
+    // going back applies nothing, going on picks up where it was.
+    assert_eq!( replay.replay_until(time_of_day(7, 0, 0)), 0 );
+    assert_eq!( replay.replay_until(28_802_500_000_000), 1 );
+    assert_eq!( replay.books().book("AAPL").unwrap().depth(0).bids[0].shares, 150 );
+    assert!( !replay.is_done() );
+    assert_eq!( replay.replay_all(), 1 );
+    assert!( replay.is_done() && replay.next_time().is_none() );
+    assert_eq!( replay.applied(), 5 );
+    assert_eq!( replay.into_books().book("AAPL").unwrap().len(), 3 );
+}
+
+#[test]
+fn test_self_match() {
+    use selfmatch::SelfMatchDetector;
+    use selfmatch::SelfMatchKind;
+
+    // a 5 second window.
+    let mut detector = SelfMatchDetector::new(5_000_000_000);
+    let msgs : Vec<BATSMessage> = ["28800168d1K27GA00000YS000100AAPL  0001831900YBAML",
+                                   "28800169d1K27GA00001YB000200AAPL  0001831800YBAML",
+                                   "28800170d1K27GA00002YB000100AAPL  0001832000YBAML",
+                                   "28800171A1K27GA00003YB000100AAPL  0001832000Y",
+                                   "28801000E1K27GA00000Y0000501K27GA00010K",
+                                   "28802000E1K27GA00001Y0000501K27GA00011K",
+                                   "28802000E1K27GA00003Y0000501K27GA00012K",
+                                   "28809000E1K27GA00002Y0000501K27GA00013K"].iter().map(|m| BATSMsgFactory::parse(m)).collect();
+    let id = |i : usize| match msgs[i] { BATSMessage::AddOrderMsg(ref m) => m.order_id, _ => panic!() };
+    let hits : Vec<_> = msgs.iter().map(|m| detector.apply(m)).collect();
+
+    // an attributed bid lower than its own offer is fine, one through it is a crossing.
+    assert!( hits[0].is_none() && hits[1].is_none() );
+    let crossing = hits[2].as_ref().unwrap();
+    assert_eq!( crossing.kind, SelfMatchKind::Crossing );
+    assert_eq!( (crossing.buy_order, crossing.sell_order), (id(2), id(0)) );
+    assert_eq!( crossing.part_id, "BAML" );
+    // unattributed orders aren't anyone's.
+    assert!( hits[3].is_none() && hits[6].is_none() );
+
+    // the offer then a bid executing within the window is a wash, outside it isn't.
+    assert!( hits[4].is_none() );
+    let wash = hits[5].as_ref().unwrap();
+    assert_eq!( wash.kind, SelfMatchKind::Wash );
+    assert_eq!( (wash.buy_order, wash.sell_order), (id(1), id(0)) );
+    assert_eq!( wash.timestamp, 28_802_000_000_000 );
+    assert!( hits[7].is_none() );
+    assert_eq!( detector.len(), 3 );
+}
+
+#[test]
+fn test_order_flow_imbalance() {
+    use book::BookManager;
+    use ofi::OfiPoint;
+    use ofi::OrderFlowImbalance;
+
+    // one second windows.
+    let mut ofi = OrderFlowImbalance::new(1_000_000_000);
+    let mut books = BookManager::new();
+    for msg in &["28800100A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800200A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800300A1K27GA00002YB000100AAPL  0001831700Y",
+                 "28801500X1K27GA00000Y000040",
+                 "28801600E1K27GA00001Y0002001K27GA00000K"] {
+        ofi.apply(&mut books, &BATSMsgFactory::parse(msg));
+    }
+    // the offer arriving is selling, the bid buying, and the bid behind it doesn't touch the top.
+    assert_eq!( ofi.series("AAPL"), &[OfiPoint{ start : 28_800_000_000_000, ofi : 100, changes : 2 }] );
+    // the offer shrinking is buying, the bid trading away selling.
+    assert_eq!( ofi.current("AAPL"), Some(OfiPoint{ start : 28_801_000_000_000, ofi : 40 - 200, changes : 2 }) );
+
+    ofi.flush();
+    assert_eq!( ofi.take_series("AAPL").len(), 2 );
+    assert!( ofi.series("AAPL").is_empty() && ofi.current("AAPL").is_none() );
+}
+
+#[test]
+fn test_consistency_checks() {
+    use binary::OrderExecutedAtPriceSizeMsg;
+    use book::BookManager;
+    use consistency::Problem;
+
+    let msgs : Vec<BATSMessage> = ["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                                   "28800169A1K27GA00000YS000100AAPL  0001831900Y",
+                                   "28800170E1K27GA00000Y0001501K27GA00010K",
+                                   "28800171X1K27GA00007Y000010",
+                                   "28800172A1K27GA00001YB000200AAPL  0001831800Y",
+                                   "28800173X1K27GA00001Y000300",
+                                   "28800174A1K27GA00002YB000100AAPL  0001831800Y"].iter().map(|m| BATSMsgFactory::parse(m)).collect();
+    let id = |i : usize| match msgs[i] {
+        BATSMessage::AddOrderMsg(ref m)    => m.order_id,
+        BATSMessage::OrderCancelMsg(ref m) => m.order_id,
+        _ => panic!()
+    };
+    let at_price = BATSMessage::OrderExecutedAtPriceSizeMsg(OrderExecutedAtPriceSizeMsg{ time_offset : 175, msg_type : 0x24, order_id : id(6),
+                                                                                        shares : 30, remaining_shares : 80, exec_id : 11, price : 1831800 });
+    let mut checked = BookManager::new().check_consistency(true);
+    let mut unchecked = BookManager::new();
+    for msg in msgs.iter().chain(Some(&at_price)) {
+        checked.apply(msg);
+        unchecked.apply(msg);
+    }
+    let problems : Vec<_> = checked.inconsistencies().iter().map(|p| (p.timestamp, p.order_id, p.symbol.clone(), p.problem.clone())).collect();
+    let aapl = || Some(String::from("AAPL"));
+    assert_eq!( problems, vec![(28800169, id(0), aapl(), Problem::DuplicateOrder{ symbol : String::from("AAPL"), remaining : 100 }),
+                               (28800170, id(0), aapl(), Problem::Overfill{ remaining : 100, shares : 150 }),
+                               (28800171, id(3), None, Problem::UnknownOrder),
+                               (28800173, id(4), aapl(), Problem::Overcancel{ remaining : 200, shares : 300 }),
+                               (175, id(6), aapl(), Problem::RemainingMismatch{ expected : 70, reported : 80 })] );
+    assert_eq!( checked.inconsistencies()[2].msg_type, 'X' );
+    // the books come out the same either way.
+    assert_eq!( checked.book("AAPL").unwrap().depth(0), unchecked.book("AAPL").unwrap().depth(0) );
+    assert!( unchecked.inconsistencies().is_empty() );
+    assert_eq!( checked.take_inconsistencies().len(), 5 );
+}
+
+#[test]
+fn test_book_manager() {
+    use book::BookManager;
+    use book::Order;
+    use book::Side;
+
+    let mut books = BookManager::new();
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00002YB000300MSFT  0000450000Y"] {
+        assert!( books.apply(&BATSMsgFactory::parse(msg)) );
+    }
+    assert_eq!( books.symbols(), vec!["AAPL", "MSFT"] );
+
+    // executions and cancels carry no symbol, the id finds the book.
+    let msft = BATSMsgFactory::parse("28800169A1K27GA00002YB000300MSFT  0000450000Y");
+    let msft_id = match msft { BATSMessage::AddOrderMsg(ref m) => m.order_id, _ => unreachable!() };
+    assert_eq!( books.symbol_of(msft_id), Some("MSFT") );
+    assert_eq!( books.find_order(msft_id), Some(Order{ order_id : msft_id, symbol : Some(String::from("MSFT")), side : Side::Bid,
+                                                       price : 450000, shares : 300, timestamp : 28800169,
+                                                       displayed : true }) );
+    assert!( books.apply(&BATSMsgFactory::parse("28800170E1K27GA00002Y0001001K27GA00000K")) );
+    assert_eq!( books.book("MSFT").unwrap().bids().next().map(|l| (l.price(), l.shares())), Some((450000, 200)) );
+    assert!( books.apply(&BATSMsgFactory::parse("28800170X1K27GA00002Y000200")) );
+    assert_eq!( books.symbol_of(msft_id), None );
+    assert!( !books.apply(&BATSMsgFactory::parse("28800170X1K27GA00002Y000010")) );
+    assert_eq!( books.book("AAPL").unwrap().len(), 2 );
+    assert_eq!( books.orders(), 2 );
+
+    let mut total = books.iter().map(|(s, b)| (s, b.len())).collect::<Vec<_>>();
+    total.sort();
+    assert_eq!( total, vec![("AAPL", 2), ("MSFT", 0)] );
+
+    assert!( books.apply(&BATSMsgFactory::parse("28800171sAAPL    ")) );
+    assert_eq!( (books.symbols(), books.orders()), (vec!["MSFT"], 0) );
+    assert!( !books.apply(&BATSMsgFactory::parse("28800171X1K27GA00000Y000010")) );
+}
+
+#[test]
+fn test_influx_sink() {
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use influx::HttpWriter;
+    use influx::InfluxSink;
+
+    let trade = MarketEvent::Trade{ timestamp : 5, order_id : None, symbol : Some(String::from("BRK A")), side : Some('B'),
+                                    price : Some(1831900), shares : 100, remaining : None, exec_id : 7 };
+    let mut sink = InfluxSink::new(vec![]).midnight(1_000_000_000);
+    assert!( sink.write_event(&trade).unwrap() );
+    assert!( !sink.write_event(&MarketEvent::OrderDeleted{ timestamp : 6, order_id : 1 }).unwrap() );
+    sink.write_bbo(6, "AAPL", Some((1831800, 300)), Some((1832000, 100))).unwrap();
+    sink.write_bbo(7, "AAPL", None, None).unwrap();
+    assert_eq!( sink.points(), 2 );
+    assert_eq!( String::from_utf8(sink.into_inner()).unwrap(),
+                "trade,symbol=BRK\\ A,side=B price=183.19,shares=100i,exec_id=7i 1000000005\n\
+                 bbo,symbol=AAPL bid=183.18,bid_size=300i,ask=183.2,ask_size=100i 1000000006\n" );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = ::std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(v) = line.strip_prefix("Content-Length: ") {
+                len = v.trim().parse().unwrap();
+            }
+            if line == "\r\n" { break; }
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).unwrap();
+        reader.get_mut().write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+        String::from_utf8(body).unwrap()
+    });
+    let mut sink = InfluxSink::new(HttpWriter::new(&addr, "/write?db=md"));
+    sink.write_event(&trade).unwrap();
+    sink.flush().unwrap();
+    assert_eq!( server.join().unwrap(), "trade,symbol=BRK\\ A,side=B price=183.19,shares=100i,exec_id=7i 5\n" );
+}
+
+#[test]
+fn test_redis_publisher() {
+    use std::io::Cursor;
+    use std::io::Read;
+    use std::io::Write;
+    use book::Bbo;
+    use book::Quote;
+    use redis::RedisPublisher;
+
+    // replies are queued up front, writes go to `sent`.
+    struct MockStream {
+        replies : Cursor<Vec<u8>>,
+        sent    : Vec<u8>
+    }
+
+    impl Read for MockStream {
+        fn read( &mut self, buf : &mut [u8] ) -> ::std::io::Result<usize> { self.replies.read(buf) }
+    }
+
+    impl Write for MockStream {
+        fn write( &mut self, buf : &[u8] ) -> ::std::io::Result<usize> { self.sent.write(buf) }
+        fn flush( &mut self ) -> ::std::io::Result<()> { Ok(()) }
+    }
+
+    let stream = MockStream{ replies : Cursor::new(b":2\r\n:0\r\n-ERR wrong type\r\n".to_vec()), sent : vec![] };
+    let mut publisher = RedisPublisher::new(stream);
+    let bid = Quote{ price : 1831800, shares : 300, orders : 2 };
+    let ask = Quote{ price : 1832000, shares : 100, orders : 1 };
+    assert!( publisher.publish_bbo(1, "AAPL    ", Bbo{ bid : Some(bid), ask : Some(ask) }).unwrap() );
+    // a different order count shows the same, so isn't sent again.
+    assert!( !publisher.publish_bbo(2, "AAPL", Bbo{ bid : Some(Quote{ orders : 3, ..bid }), ask : Some(ask) }).unwrap() );
+    assert!( publisher.publish_bbo(3, "AAPL", Bbo{ bid : Some(bid), ask : None }).unwrap() );
+    assert!( publisher.publish("x", "y").is_err() );
+
+    let sent = String::from_utf8(publisher.into_inner().sent).unwrap();
+    let payload = "{\"symbol\":\"AAPL\",\"timestamp\":1,\"bid\":183.18,\"bid_size\":300,\"ask\":183.2,\"ask_size\":100}";
+    assert!( sent.starts_with(&format!("*3\r\n$7\r\nPUBLISH\r\n$8\r\nbbo.AAPL\r\n${}\r\n{}\r\n", payload.len(), payload)) );
+    assert!( sent.contains("\"ask\":null,\"ask_size\":null}") );
+}
+
+#[cfg(feature = "ws-server")]
+#[test]
+fn test_ws_server() {
+    use std::thread;
+    use std::time::Duration;
+    use tungstenite;
+    use tungstenite::Message;
+    use ws_server::WsServer;
+
+    let server = WsServer::bind("127.0.0.1:0").unwrap();
+    let (mut client, _) = tungstenite::connect(format!("ws://{}", server.local_addr())).unwrap();
+    client.send(Message::Text("{\"op\":\"subscribe\",\"symbols\":[\"AAPL\"]}".into())).unwrap();
+
+    // the subscription lands on the client's thread a little later.
+    let mut sent = 0;
+    for _ in 0..400 {
+        sent = server.publish_book_delta(1, "AAPL", 'B', 1831800, 300);
+        if sent == 1 { break; }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!( sent, 1 );
+    assert_eq!( server.publish_book_delta(2, "MSFT", 'B', 1000000, 100), 0 );
+    let trade = MarketEvent::Trade{ timestamp : 3, order_id : Some(1), symbol : Some(String::from("AAPL")), side : None,
+                                    price : Some(1831900), shares : 100, remaining : None, exec_id : 7 };
+    assert_eq!( server.publish_event(&trade), 1 );
+
+    let read = |client : &mut tungstenite::WebSocket<_>| match client.read().unwrap() {
+        Message::Text(t) => t.as_str().to_string(),
+        m => panic!("unexpected message {:?}", m)
+    };
+    assert_eq!( read(&mut client),
+                "{\"type\":\"book_delta\",\"symbol\":\"AAPL\",\"timestamp\":1,\"side\":\"B\",\"price\":1831800,\"volume\":300}" );
+    assert_eq!( read(&mut client),
+                "{\"type\":\"trade\",\"symbol\":\"AAPL\",\"timestamp\":3,\"side\":null,\"price\":1831900,\"shares\":100,\"exec_id\":7}" );
+    assert_eq!( server.clients(), 1 );
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_arrow_collector() {
+    use arrow::ArrowCollector;
+    use arrow_array::Array;
+    use arrow_array::StringArray;
+    use arrow_array::UInt64Array;
+
+    let msgs = vec![BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"),
+                    BATSMsgFactory::parse("28800169A1K27GA00001YB000200AAPL  0001831800Y"),
+                    BATSMsgFactory::parse("28800168HAAPLSPOTH0XYLUDPY"),
+                    BATSMsgFactory::parse("28800168HAAPLSPOTT0XY")];
+    let mut collector = ArrowCollector::new();
+    collector.extend(&msgs);
+    let batches = collector.finish().unwrap();
+
+    let add_order = &batches["add_order"];
+    assert_eq!( add_order.num_rows(), 2 );
+    let prices = add_order.column_by_name("price").unwrap().as_any().downcast_ref::<UInt64Array>().unwrap();
+    assert_eq!( prices.value(1), 1831800 );
+    let symbols = add_order.column_by_name("symbol").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!( symbols.value(0), "AAPL" );
+
+    let status = &batches["trading_status"];
+    let reasons = status.column_by_name("halt_reason").unwrap();
+    assert!( status.schema().field_with_name("halt_reason").unwrap().is_nullable() );
+    assert_eq!( reasons.null_count(), 1 );
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_recorder() {
+    use parquet::file::reader::FileReader;
+    use parquet::file::reader::SerializedFileReader;
+    use parquet_recorder::ParquetRecorder;
+
+    let root = env::temp_dir().join(format!("orderbook_parquet_{}", std::process::id()));
+    let mut recorder = ParquetRecorder::new(&root, "20261014").batch_rows(2).snapshot_interval(1000);
+    for line in ["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YB000200MSFT  0003051200Y",
+                 "28800170X1K27GA00000Y000050",
+                 "28800171A1K27GA00002YB000300AAPL  0001831800Y"].iter() {
+        recorder.record(&BATSMsgFactory::parse(line)).unwrap();
+    }
+    assert!( recorder.record_snapshot(5000, "AAPL", &[(1831800, 300)], &[(1831900, 50)]).unwrap() );
+    assert!( !recorder.record_snapshot(5500, "AAPL", &[(1831800, 300)], &[]).unwrap() );
+    recorder.close().unwrap();
+
+    let aapl = root.join("date=20261014").join("symbol=AAPL");
+    let rows = |f : &str| SerializedFileReader::new(File::open(aapl.join(f)).unwrap()).unwrap()
+                              .metadata().file_metadata().num_rows();
+    assert_eq!( rows("add_order.parquet"),     2 );
+    assert_eq!( rows("order_cancel.parquet"),  1 );
+    assert_eq!( rows("book_snapshot.parquet"), 2 );
+    assert!( root.join("date=20261014").join("symbol=MSFT").join("add_order.parquet").exists() );
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(feature = "polars")]
+#[test]
+fn test_dataframe() {
+    use dataframe;
+
+    let msgs = vec![BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"),
+                    BATSMsgFactory::parse("28800169A1K27GA00001YB000200AAPL  0001831800Y"),
+                    BATSMsgFactory::parse("28800170X1K27GA00000Y000050")];
+    let df = dataframe::to_dataframe(&msgs, "add_order").unwrap();
+    assert_eq!( df.shape(), (2, 9) );
+    assert_eq!( df.column("price").unwrap().u64().unwrap().get(1), Some(1831800) );
+    assert_eq!( df.column("symbol").unwrap().str().unwrap().get(0), Some("AAPL") );
+    assert_eq!( dataframe::to_dataframe(&msgs, "trade").unwrap().height(), 0 );
+
+    let levels = dataframe::levels_to_dataframe(&[(1831800, 200), (1831700, 100)], &[(1831900, 50)]).unwrap();
+    assert_eq!( levels.height(), 3 );
+    assert_eq!( levels.column("side").unwrap().str().unwrap().get(2), Some("S") );
+}
+
+#[test]
+fn test_factory() {
+    let obj = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
+    println!("Return result from msg factory {:?}", obj);
+    let msg_obj : Option<AddOrderMsg> = obj.into();
+    assert!(msg_obj.is_some());
+    println!("After into {:?}", msg_obj);
+
+    let obj = BATSMsgFactory::parse("28800168JAAPLSPOTC00010068000000020000");
+    println!("Return result from msg factory {:?}", obj);
+    let msg_obj : Option<AuctionSummaryMsg> = obj.into();
+    println!("After into {:?}", msg_obj);
+    assert!(msg_obj.is_some());
+}
+
+#[test]
+fn test_parse_reduce_size() {
+    let msg_long : Vec<u8> = vec![0x12, 0x25, 0x18, 0x00, 0x00, 0x00, 
+                                  0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 
+                                  0x64, 0x00, 0x00, 0x00];
+    let res = ReduceSizeMsg::parse_binary(&msg_long);
+    println!("{:?}", res);
+    assert!(res.is_ok());
+
+    let o = res.unwrap();
+    assert_eq!( o.time_offset, 24 );
+    assert_eq!( o.order_id,    0x0B1D568F775B4005 );
+    assert_eq!( o.shares,      100 );
+
+    let msg_short : Vec<u8> = vec![0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 
+                                   0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 
+                                   0x64, 0x00];
+    let obj = BATSMsgFactory::parse_binary(&msg_short);
+    let msg_obj : Option<ReduceSizeMsg> = obj.into();
+    assert!(msg_obj.is_some());
+    assert_eq!( msg_obj.unwrap().shares, 100 );
+}
+
+#[test]
+fn test_split_packet() {
+    // header for unit 3, seq 120 carrying a unit clear followed by a short reduce size
+    let packet : Vec<u8> = vec![0x1E, 0x00, 0x02, 0x03, 0x78, 0x00, 0x00, 0x00, 
+                                0x06, 0x97, 0x00, 0x00, 0x00, 0x00, 
+                                0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 
+                                0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 
+                                0x64, 0x00];
+    let res = split_packet(&packet);
+    assert!(res.is_ok());
+
+    let (header, msgs) = res.unwrap();
+    assert_eq!( header.unit,     3 );
+    assert_eq!( header.sequence, 120 );
+    assert_eq!( msgs.len(),      2 );
+
+    let msg_obj : Option<UnitClearMsg> = BATSMsgFactory::parse_binary(msgs[0]).into();
+    assert!(msg_obj.is_some());
+    let msg_obj : Option<ReduceSizeMsg> = BATSMsgFactory::parse_binary(msgs[1]).into();
+    assert!(msg_obj.is_some());
+}
+
+#[test]
+fn test_timestamp_composer() {
+    let mut composer = TimestampComposer::new();
+
+    // 8:00:00 followed by a reduce size 24ns later
+    let time = BATSMsgFactory::parse_binary(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00]);
+    assert_eq!( composer.compose(&time), 28800 * 1_000_000_000 );
+
+    let reduce = BATSMsgFactory::parse_binary(&[0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 
+                                               0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 
+                                               0x64, 0x00]);
+    assert_eq!( composer.compose(&reduce), 28800 * 1_000_000_000 + 24 );
+
+    // text messages are in ms past midnight
+    let add = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
+    assert_eq!( composer.compose(&add), 28800168 * 1_000_000 );
+}
+
+#[test]
+fn test_parse_trade_expanded() {
+    let mut msg : Vec<u8> = vec![0x2B, 0x30, 0x18, 0x00, 0x00, 0x00];
+    msg.extend_from_slice(&0x0B1D568F775B4005u64.to_le_bytes());
+    msg.push(b'B');
+    msg.extend_from_slice(&1_000_000u32.to_le_bytes());
+    msg.extend_from_slice(b"AAPLSPOT");
+    msg.extend_from_slice(&1831900u64.to_le_bytes());
+    msg.extend_from_slice(&0x0B1D568F775B4006u64.to_le_bytes());
+
+    let res = TradeExpandedMsg::parse_binary(&msg);
+    println!("{:?}", res);
+    assert!(res.is_ok());
+
+    let o = res.unwrap();
+    assert_eq!( o.side,    'B' );
+    assert_eq!( o.shares,  1_000_000 );
+    assert_eq!( o.symbol,  "AAPLSPOT" );
+    assert_eq!( o.price,   1831900 );
+    assert_eq!( o.exec_id, 0x0B1D568F775B4006 );
+
+    let msg_obj : Option<TradeExpandedMsg> = BATSMsgFactory::parse_binary(&msg).into();
+    assert!(msg_obj.is_some());
+}
+
+#[test]
+fn test_parse_order_executed_at_price_size() {
+    let mut msg : Vec<u8> = vec![0x26, 0x24, 0x18, 0x00, 0x00, 0x00];
+    msg.extend_from_slice(&0x0B1D568F775B4005u64.to_le_bytes());
+    msg.extend_from_slice(&100u32.to_le_bytes());
+    msg.extend_from_slice(&50u32.to_le_bytes());
+    msg.extend_from_slice(&0x0B1D568F775B4006u64.to_le_bytes());
+    msg.extend_from_slice(&1831900u64.to_le_bytes());
+
+    let msg_obj : Option<OrderExecutedAtPriceSizeMsg> = BATSMsgFactory::parse_binary(&msg).into();
+    assert!(msg_obj.is_some());
+
+    let o = msg_obj.unwrap();
+    assert_eq!( o.shares,           100 );
+    assert_eq!( o.remaining_shares, 50 );
+    assert_eq!( o.price,            1831900 );
+
+    let mut b = LimitOrderBook::new();
+    let resting = Order{order_id : o.order_id, price : 1831800, volume : 150, side : -1, part_id : String::from("Acme Corp.")};
+    b.add_order(resting.clone());
+    b.set_order_volume(&resting, o.remaining_shares);
+    assert_eq!(b.ask_volume_at_price_level(1831800), 50);
+    b.set_order_volume(&resting, 0);
+    assert_eq!(b.best_ask(), 0);
+}
+
+#[test]
+fn test_parse_calculated_value() {
+    let mut msg : Vec<u8> = vec![0x21, 0xE3, 0x18, 0x00, 0x00, 0x00];
+    msg.extend_from_slice(b"SPY     ");
+    msg.push(b'1');
+    msg.extend_from_slice(&4101234u64.to_le_bytes());
+    msg.extend_from_slice(&1_340_000_000_000_000_000u64.to_le_bytes());
+
+    let msg_obj : Option<CalculatedValueMsg> = BATSMsgFactory::parse_binary(&msg).into();
+    assert!(msg_obj.is_some());
+
+    let o = msg_obj.unwrap();
+    assert_eq!( o.symbol,          "SPY     " );
+    assert_eq!( o.value_category,  '1' );
+    assert_eq!( o.value,           4101234 );
+    assert_eq!( o.value_timestamp, 1_340_000_000_000_000_000 );
+}
+
+#[test]
+fn test_end_of_session() {
+    let packet : Vec<u8> = vec![0x0E, 0x00, 0x01, 0x01, 0x10, 0x00, 0x00, 0x00, 
+                                0x06, 0x2D, 0x00, 0x00, 0x00, 0x00];
+    let events = decode_packet(&packet).unwrap();
+    assert_eq!( events.len(), 2 );
+    match events[1] {
+        FeedEvent::SessionComplete => {}, 
+        _ => panic!("expected session complete"),
+    }
+
+    let f = File::open("src/pitch_example_data").expect("file not found");
+    let events : Vec<FeedEvent> = PitchReader::new(BufReader::new(f)).collect();
+    assert_eq!( events.len(), 20001 );
+    match events[20000] {
+        FeedEvent::SessionComplete => {}, 
+        _ => panic!("expected session complete"),
+    }
+}
+
+#[test]
+fn test_session_messages() {
+    let login = LoginMsg::new("0001", "TEST", "secret");
+    let bytes = login.to_bytes();
+    assert_eq!( bytes.len(), 22 );
+
+    let res = LoginMsg::parse_binary(&bytes);
+    assert!(res.is_ok());
+    let o = res.unwrap();
+    assert_eq!( o.session_sub_id, "0001" );
+    assert_eq!( o.username,       "TEST" );
+    assert_eq!( o.password,       "secret    " );
+
+    let packet = encode_packet(&[bytes]);
+    let (header, msgs) = split_packet(&packet).unwrap();
+    assert_eq!( header.length as usize, packet.len() );
+    let msg_obj : Option<LoginMsg> = BATSMsgFactory::parse_binary(msgs[0]).into();
+    assert!(msg_obj.is_some());
+
+    let msg_obj : Option<LoginResponseMsg> = BATSMsgFactory::parse_binary(&[0x03, 0x02, b'N']).into();
+    assert_eq!( msg_obj.unwrap().status, LoginStatus::NotAuthorized );
+
+    let heartbeat = SequencedUnitHeader::heartbeat().to_bytes();
+    assert!( SequencedUnitHeader::parse_binary(&heartbeat).unwrap().is_heartbeat() );
+}
+
+#[test]
+fn test_osi_symbol() {
+    let osi = OsiSymbol::parse("AAPL  120622C00580000").unwrap();
+    assert_eq!( osi.root,         "AAPL" );
+    assert_eq!( osi.expiry_year,  2012 );
+    assert_eq!( osi.expiry_month, 6 );
+    assert_eq!( osi.expiry_day,   22 );
+    assert_eq!( osi.put_call,     PutCall::Call );
+    assert_eq!( osi.strike,       5800000 );
+    assert_eq!( osi.to_osi_string(), "AAPL  120622C00580000" );
+
+    assert_eq!( OsiSymbol::parse("AAPL  121322C00580000"), Err(OsiError::InvalidExpiry) );
+    assert_eq!( OsiSymbol::parse("AAPL"), Err(OsiError::InvalidLength(4)) );
+
+    let mut msg : Vec<u8> = vec![0x26, 0x2E];
+    msg.extend_from_slice(b"01AAPL");
+    msg.extend_from_slice(b"AAPL  120622P00575500");
+    msg.push(b'N');
+    msg.extend_from_slice(b"AAPL    ");
+
+    let mut map = OptionsSymbolMap::new();
+    map.apply(&BATSMsgFactory::parse_binary(&msg));
+    let osi = map.get("01AAPL").unwrap();
+    assert_eq!( osi.put_call, PutCall::Put );
+    assert_eq!( osi.strike,   5755000 );
+}
+
+fn itch_add_order( order_id : u64, shares : u32, price : u32 ) -> Vec<u8> {
+    let mut msg : Vec<u8> = vec![b'A', 0x00, 0x01, 0x00, 0x00];
+    msg.extend_from_slice(&(34_200_000_000_000u64.to_be_bytes()[2..]));
+    msg.extend_from_slice(&order_id.to_be_bytes());
+    msg.push(b'B');
+    msg.extend_from_slice(&shares.to_be_bytes());
+    msg.extend_from_slice(b"AAPL    ");
+    msg.extend_from_slice(&price.to_be_bytes());
+    msg
+}
+
+#[test]
+fn test_itch_parse() {
+    let add = itch_add_order(42, 100, 1831900);
+    assert_eq!( add.len(), 36 );
+
+    let mut replace : Vec<u8> = vec![b'U', 0x00, 0x01, 0x00, 0x00];
+    replace.extend_from_slice(&(34_200_000_000_100u64.to_be_bytes()[2..]));
+    replace.extend_from_slice(&42u64.to_be_bytes());
+    replace.extend_from_slice(&43u64.to_be_bytes());
+    replace.extend_from_slice(&200u32.to_be_bytes());
+    replace.extend_from_slice(&1832000u32.to_be_bytes());
+
+    let mut buf : Vec<u8> = Vec::new();
+    for m in &[&add, &replace] {
+        buf.extend_from_slice(&(m.len() as u16).to_be_bytes());
+        buf.extend_from_slice(m);
+    }
+    buf.extend_from_slice(&[0x00, 0x24, b'A']); // partial message left for the next read
+
+    let (msgs, consumed) = itch::split_messages(&buf);
+    assert_eq!( msgs.len(), 2 );
+    assert_eq!( consumed,   buf.len() - 3 );
+
+    match ItchMessage::parse(msgs[0]).unwrap() {
+        ItchMessage::AddOrder(o) => {
+            assert_eq!( o.timestamp, 34_200_000_000_000 );
+            assert_eq!( o.order_id,  42 );
+            assert_eq!( o.side,      'B' );
+            assert_eq!( o.symbol,    "AAPL    " );
+            assert_eq!( o.price,     1831900 );
+        },
+        m => panic!("unexpected {:?}", m),
+    }
+    match ItchMessage::parse(msgs[1]).unwrap() {
+        ItchMessage::OrderReplace(o) => {
+            assert_eq!( o.original_order_id, 42 );
+            assert_eq!( o.order_id,          43 );
+            assert_eq!( o.shares,            200 );
+        },
+        m => panic!("unexpected {:?}", m),
+    }
+}
+
+fn iex_price_level_update( msg_type : u8, size : u32, price : i64 ) -> Vec<u8> {
+    let mut msg : Vec<u8> = vec![msg_type, 0x01];
+    msg.extend_from_slice(&1_494_855_059_605_251_312u64.to_le_bytes());
+    msg.extend_from_slice(b"ZIEXT   ");
+    msg.extend_from_slice(&size.to_le_bytes());
+    msg.extend_from_slice(&price.to_le_bytes());
+    msg
+}
+
+#[test]
+fn test_market_event() {
+    let mut composer = TimestampComposer::new();
+    let msg = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
+    let ts = composer.compose(&msg);
+    match MarketEvent::from_bats(&msg, ts) {
+        Some(MarketEvent::OrderAdded{ timestamp, side, price, ref symbol, .. }) => {
+            assert_eq!( timestamp, 28_800_168_000_000 );
+            assert_eq!( side,      'S' );
+            assert_eq!( price,     1831900 );
+            assert_eq!( symbol,    "AAPL" );
+        },
+        e => panic!("unexpected {:?}", e)
+    }
+
+    let msg = BATSMsgFactory::parse("28800168sAAPL    ");
+    assert_eq!( MarketEvent::from_bats(&msg, 0),
+                Some(MarketEvent::StatusChange{ timestamp : 0, symbol : Some(String::from("AAPL")), state : TradingState::Cleared }) );
+
+    let add = ItchMessage::parse(&itch_add_order(42, 100, 1831900)).unwrap();
+    let e = MarketEvent::from_itch(&add).unwrap();
+    assert_eq!( e.timestamp(), 34_200_000_000_000 );
+    if let MarketEvent::OrderAdded{ order_id, side, .. } = e {
+        assert_eq!( (order_id, side), (42, 'B') );
+    } else {
+        panic!("expected OrderAdded");
+    }
+}
+
+struct NullDecoder {}
+
+impl FeedDecoder for NullDecoder {
+    fn name( &self ) -> &str { "null" }
+
+    fn decode( &mut self, data : &[u8], _out : &mut Vec<MarketEvent> ) -> Result<(), DecodeError> {
+        if data.is_empty() { Err(DecodeError::Incomplete) } else { Ok(()) }
+    }
+}
+
+#[test]
+fn test_decoder_registry() {
+    let mut registry = DecoderRegistry::new();
+    registry.register("null", || Box::new(NullDecoder{}));
+    assert_eq!( registry.names(), vec!["bats", "null"] );
+    assert!( registry.create("itch").is_none() );
+    assert_eq!( registry.create("null").unwrap().decode(&[], &mut vec![]), Err(DecodeError::Incomplete) );
+
+    // time, an unsupported message type and a reduce size
+    let packet = encode_packet(&[vec![0x06, 0x20, 0x80, 0x70, 0x00, 0x00], 
+                                 vec![0x06, 0x99, 0x00, 0x00, 0x00, 0x00], 
+                                 vec![0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 
+                                      0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 
+                                      0x64, 0x00]]);
+    let mut decoder = registry.create("bats").unwrap();
+    let mut events = vec![];
+    assert!( decoder.decode(&packet, &mut events).is_ok() );
+    assert_eq!( events.len(), 1 );
+    assert_eq!( events[0].timestamp(), 28800 * 1_000_000_000 + 24 );
+    assert!( decoder.decode(&packet[..4], &mut events).is_err() );
+}
+
+#[test]
+fn test_pcap_reader() {
+    use std::net::Ipv4Addr;
+    use feed::FeedEvent;
+    use pcap::PcapReader;
+
+    // an Ethernet frame, VLAN tagged or not, with a UDP datagram to 224.0.62.2:`port`.
+    fn frame( port : u16, vlan : bool, payload : &[u8] ) -> Vec<u8> {
+        let mut f = vec![0x01, 0x00, 0x5e, 0x00, 0x3e, 0x02, 0, 1, 2, 3, 4, 5];
+        if vlan {
+            f.extend_from_slice(&[0x81, 0x00, 0x00, 0x64]);
+        }
+        f.extend_from_slice(&[0x08, 0x00]);
+        let total = (20 + 8 + payload.len()) as u16;
+        f.extend_from_slice(&[0x45, 0, (total >> 8) as u8, total as u8, 0, 0, 0x40, 0, 16, 17, 0, 0, 10, 0, 0, 1, 224, 0, 62, 2]);
+        let udp = (8 + payload.len()) as u16;
+        f.extend_from_slice(&[0x75, 0x31, (port >> 8) as u8, port as u8, (udp >> 8) as u8, udp as u8, 0, 0]);
+        f.extend_from_slice(payload);
+        f.extend_from_slice(&[0, 0, 0, 0]);   // ethernet padding, past the IP length
+        f
+    }
+
+    // time, then a reduce size
+    let packet = encode_packet(&[vec![0x06, 0x20, 0x80, 0x70, 0x00, 0x00],
+                                 vec![0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 0x64, 0x00]]);
+    let frames = vec![frame(30001, false, &packet), frame(30002, false, &packet), vec![0xff; 12].into_iter().chain(vec![0x08, 0x06, 0, 0]).collect(),
+                      frame(30001, true, &packet)];
+
+    let mut pcap = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 1, 0, 0, 0];
+    for (i, f) in frames.iter().enumerate() {
+        for v in &[1_700_000_000u32 + i as u32, 250, f.len() as u32, f.len() as u32] {
+            pcap.extend_from_slice(&v.to_le_bytes());
+        }
+        pcap.extend_from_slice(f);
+    }
+    let reader = PcapReader::new(&pcap[..]).unwrap().group(Ipv4Addr::new(224, 0, 62, 2), 30001);
+    let datagrams : Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+    assert_eq!( datagrams.len(), 2 );
+    assert_eq!( (datagrams[0].timestamp, datagrams[0].dest.port(), &datagrams[0].payload[..]), (1_700_000_000_000_250_000, 30001, &packet[..]) );
+    assert_eq!( datagrams[1].source.to_string(), "10.0.0.1:30001" );
+
+    let mut events = PcapReader::new(&pcap[..]).unwrap().port(30001).events();
+    let decoded : Vec<_> = events.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!( decoded.len(), 4 );
+    assert!( matches!(decoded[1], FeedEvent::Message(BATSMessage::ReduceSizeMsg(_))) );
+    let stats = events.stats();
+    assert_eq!( (stats.packets, stats.datagrams, stats.skipped, stats.filtered), (4, 2, 1, 1) );
+
+    // the same frames as big endian pcapng, in nanoseconds.
+    let mut ng = Vec::new();
+    let block = |ng : &mut Vec<u8>, kind : u32, body : &[u8]| {
+        let length = (12 + body.len() + 3) & !3;
+        ng.extend_from_slice(&kind.to_be_bytes());
+        ng.extend_from_slice(&(length as u32).to_be_bytes());
+        ng.extend_from_slice(body);
+        ng.extend(std::iter::repeat_n(0, length - 12 - body.len()));
+        ng.extend_from_slice(&(length as u32).to_be_bytes());
+    };
+    block(&mut ng, 0x0a0d0d0a, &[0x1a, 0x2b, 0x3c, 0x4d, 0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+    block(&mut ng, 1, &[0, 1, 0, 0, 0, 0, 0xff, 0xff, 0, 9, 0, 1, 9, 0, 0, 0, 0, 0, 0, 0]);
+    for f in &frames {
+        let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2];
+        body.extend_from_slice(&(f.len() as u32).to_be_bytes());
+        body.extend_from_slice(&(f.len() as u32).to_be_bytes());
+        body.extend_from_slice(f);
+        block(&mut ng, 6, &body);
+    }
+    let datagrams : Vec<_> = PcapReader::new(&ng[..]).unwrap().port(30001).collect::<Result<_, _>>().unwrap();
+    assert_eq!( datagrams.len(), 2 );
+    assert_eq!( (datagrams[1].timestamp, &datagrams[1].payload[..]), ((1u64 << 32) + 2, &packet[..]) );
+
+    // an interface whose block length cuts an option's padding short is an error.
+    let mut bad = ng[..28].to_vec();
+    bad.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 25, 0, 1, 0, 0, 0, 0, 0xff, 0xff, 0, 2, 0, 1, 7, 0, 0, 0, 25]);
+    assert!( PcapReader::new(&bad[..]).unwrap().next().unwrap().is_err() );
+
+    // a datagram with a message that won't decode gives what else is in it.
+    let packet = encode_packet(&[vec![0x06, 0x20, 0x80, 0x70, 0x00, 0x00], vec![0x04, 0xee, 0x00, 0x00]]);
+    let mut pcap = pcap[..24].to_vec();
+    let f = frame(30001, false, &packet);
+    for v in &[1_700_000_000u32, 0, f.len() as u32, f.len() as u32] {
+        pcap.extend_from_slice(&v.to_le_bytes());
+    }
+    pcap.extend_from_slice(&f);
+    let decoded : Vec<_> = PcapReader::new(&pcap[..]).unwrap().events().collect::<Result<_, _>>().unwrap();
+    assert_eq!( decoded.iter().filter(|e| matches!(e, FeedEvent::Message(_))).count(), 1 );
+
+    assert!( PcapReader::new(&b"not a capture"[..]).is_err() );
+}
+
+#[test]
+fn test_multicast_receiver() {
+    use std::net::Ipv4Addr;
+    use std::net::UdpSocket;
+    use std::time::Duration;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use book::BookManager;
+    use multicast::MulticastReceiver;
+    use multicast::ReceiverStats;
+
+    let time = vec![0x06, 0x20, 0x80, 0x70, 0x00, 0x00];
+    let reduce = vec![0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 0x64, 0x00];
+    fn packet( unit : u8, sequence : u32, msgs : &[&Vec<u8>] ) -> Vec<u8> {
+        let length = 8 + msgs.iter().map(|m| m.len()).sum::<usize>();
+        let mut p = SequencedUnitHeader{ length : length as u16, count : msgs.len() as u8, unit, sequence }.to_bytes();
+        for m in msgs {
+            p.extend_from_slice(m);
+        }
+        p
+    }
+
+    // port 0 and a unicast address, so the test needs neither a free port nor a multicast route
+    let mut feed = MulticastReceiver::join(&["127.0.0.1:0".parse().unwrap()], Ipv4Addr::LOCALHOST).unwrap()
+        .timeout(Duration::from_millis(500));
+    let port = feed.local_addrs().unwrap()[0].port();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let packets = vec![packet(1, 1, &[&time, &reduce]), packet(1, 1, &[&time, &reduce]), packet(1, 2, &[&reduce, &reduce]),
+                       packet(1, 5, &[&reduce]), packet(2, 7, &[&time]), SequencedUnitHeader::heartbeat().to_bytes(), vec![1, 2, 3]];
+    let mut msgs = Vec::new();
+    for p in &packets {
+        sender.send_to(p, ("127.0.0.1", port)).unwrap();
+        assert!( feed.recv(&mut msgs).unwrap() );
+    }
+    assert_eq!( msgs.len(), 5 );
+    assert!( matches!(msgs[0], BATSMessage::TimeMsg(_)) && matches!(msgs[4], BATSMessage::TimeMsg(_)) );
+    assert_eq!( feed.stats(), ReceiverStats{ packets : 7, messages : 5, heartbeats : 1, duplicates : 2, gaps : 1, missed : 1, bad_packets : 1 } );
+
+    let mut feed = feed.timeout(Duration::from_millis(1));
+    assert!( !feed.recv(&mut msgs).unwrap() );
+
+    // a binary feed builds the books run() is given.
+    let time = vec![0x06, 0x20, 0x98, 0x85, 0x00, 0x00];
+    let mut add = vec![34, 0x21, 0xe8, 0x03, 0x00, 0x00, 1, 0, 0, 0, 0, 0, 0, 0, b'B', 0x2c, 0x01, 0x00, 0x00];
+    add.extend_from_slice(b"AAPL  ");
+    add.extend_from_slice(&1831800u64.to_le_bytes());
+    add.push(1);
+    let mut short = vec![26, 0x22, 0xd0, 0x07, 0x00, 0x00, 2, 0, 0, 0, 0, 0, 0, 0, b'S', 0x64, 0x00];
+    short.extend_from_slice(b"AAPL  ");
+    short.extend_from_slice(&18320u16.to_le_bytes());
+    short.push(1);
+    let executed = [vec![26, 0x23, 0xb8, 0x0b, 0x00, 0x00, 1, 0, 0, 0, 0, 0, 0, 0, 0x64, 0, 0, 0], 9u64.to_le_bytes().to_vec()].concat();
+    let modify = vec![19, 0x28, 0xa0, 0x0f, 0x00, 0x00, 2, 0, 0, 0, 0, 0, 0, 0, 0x32, 0x00, 0x8f, 0x47, 1];
+    let mut other = add.clone();
+    other[6] = 3;
+    other[19..25].copy_from_slice(b"MSFT  ");
+    let delete = vec![14, 0x29, 0x88, 0x13, 0x00, 0x00, 3, 0, 0, 0, 0, 0, 0, 0];
+    for p in &[packet(3, 1, &[&time, &add, &short, &executed]), packet(3, 5, &[&modify, &other, &delete])] {
+        sender.send_to(p, ("127.0.0.1", port)).unwrap();
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopper = { let stop = stop.clone(); thread::spawn(move || { thread::sleep(Duration::from_millis(200)); stop.store(true, Ordering::Relaxed); }) };
+    let mut books = BookManager::new();
+    feed.run(&mut books, &stop).unwrap();
+    stopper.join().unwrap();
+    let book = books.book("AAPL").unwrap();
+    assert_eq!( (book.best_bid().map(|q| (q.price, q.shares)), book.best_ask().map(|q| (q.price, q.shares))),
+                (Some((1831800, 200)), Some((1831900, 50))) );
+    assert_eq!( book.get_order(2).unwrap().timestamp, 34_200_000_004_000 );
+    assert_eq!( books.trades().execution(9).map(|e| (e.price, e.shares, e.timestamp)), Some((1831800, 100, 34_200_000_003_000)) );
+    assert_eq!( (books.symbols(), books.orders()), (vec!["AAPL", "MSFT"], 2) );
+
+    // a Unit Clear on the unit takes them all.
+    sender.send_to(&packet(3, 8, &[&vec![0x06, 0x97, 0x00, 0x00, 0x00, 0x00]]), ("127.0.0.1", port)).unwrap();
+    stop.store(false, Ordering::Relaxed);
+    let stopper = { let stop = stop.clone(); thread::spawn(move || { thread::sleep(Duration::from_millis(200)); stop.store(true, Ordering::Relaxed); }) };
+    feed.run(&mut books, &stop).unwrap();
+    stopper.join().unwrap();
+    assert_eq!( books.len(), 0 );
+}
+
+#[cfg(feature = "flatbuffers")]
+#[test]
+fn test_fbs_encoder() {
+    use fbs;
+
+    let events = vec![MarketEvent::OrderAdded{ timestamp : 1, order_id : 42, side : 'B', price : 1831900, shares : 100,
+                                               symbol : String::from("AAPL") },
+                      MarketEvent::Trade{ timestamp : 2, order_id : None, symbol : Some(String::from("AAPL")),
+                                          side : None, price : Some(1831900), shares : 50, remaining : Some(0), exec_id : 7 },
+                      MarketEvent::StatusChange{ timestamp : 3, symbol : None, state : TradingState::Other('S') }];
+    let mut enc = fbs::FbsEncoder::new();
+    for e in &events {
+        let view = fbs::read_event(enc.event(e)).unwrap();
+        assert_eq!( view.to_event().as_ref(), Some(e) );
+    }
+
+    let view = fbs::read_event(enc.event(&events[1])).unwrap();
+    assert_eq!( view.kind(), fbs::TRADE );
+    assert_eq!( view.order_id(), None );
+    assert_eq!( view.remaining(), Some(0) );
+    assert_eq!( view.symbol(), Some("AAPL") );
+
+    let view = fbs::read_book_delta(enc.book_delta(4, "AAPL", 'S', 1832000, 300)).unwrap();
+    assert_eq!( (view.timestamp(), view.symbol(), view.side(), view.price(), view.volume()),
+                (4, "AAPL", b'S', 1832000, 300) );
+    assert!( fbs::read_event(&[1, 2, 3]).is_err() );
+}
+
+#[cfg(feature = "kafka")]
+#[test]
+fn test_kafka_sink() {
+    use fbs;
+    use kafka;
+    use kafka_sink::KafkaSink;
+    use kafka_sink::PendingRecord;
+    use kafka_sink::RecordProducer;
+    use kafka_sink::SinkError;
+    use kafka_sink::partition_for;
+
+    // acks every batch except for the partitions in `failing`, which fail `fails` times.
+    struct MockProducer {
+        failing : Vec<i32>,
+        fails   : usize,
+        sent    : Vec<(i32, String, Vec<u8>)>
+    }
+
+    impl RecordProducer for MockProducer {
+        fn send_batch( &mut self, _ : &str, batch : &[PendingRecord] ) -> kafka::Result<Vec<i32>> {
+            let failing = if self.fails > 0 { self.fails -= 1; self.failing.clone() } else { vec![] };
+            for r in batch.iter().filter(|r| !failing.contains(&r.partition)) {
+                self.sent.push((r.partition, r.key.clone(), r.value.clone()));
+            }
+            Ok(failing)
+        }
+    }
+
+    let add = MarketEvent::OrderAdded{ timestamp : 1, order_id : 42, side : 'B', price : 1831900, shares : 100,
+                                       symbol : String::from("AAPL") };
+    let del = MarketEvent::OrderDeleted{ timestamp : 2, order_id : 42 };
+    let aapl = partition_for("AAPL", 4);
+    assert_ne!( aapl, partition_for("IBM", 4) );
+
+    let producer = MockProducer{ failing : vec![aapl], fails : 1, sent : vec![] };
+    let mut sink = KafkaSink::new(producer, "md", 4).batch_size(3);
+    sink.send_event(&add).unwrap();
+    sink.send_event(&del).unwrap();
+    assert_eq!( sink.pending(), 2 );
+    sink.send_book_delta(3, "IBM", 'S', 1000000, 0).unwrap(); // fills the batch, AAPL is resent once
+    assert_eq!( sink.pending(), 0 );
+
+    let sent = sink.into_inner().sent;
+    assert_eq!( sent.iter().map(|r| r.1.as_str()).collect::<Vec<_>>(), vec!["IBM", "AAPL", "AAPL"] );
+    assert!( sent.iter().filter(|r| r.1 == "AAPL").all(|r| r.0 == aapl) );
+    assert_eq!( fbs::read_event(&sent[1].2).unwrap().to_event(), Some(add.clone()) );
+
+    let producer = MockProducer{ failing : vec![aapl], fails : 10, sent : vec![] };
+    let mut sink = KafkaSink::new(producer, "md", 4).retries(2);
+    sink.send_event(&add).unwrap();
+    match sink.flush() {
+        Err(SinkError::Undelivered(1)) => {},
+        r => panic!("expected Undelivered(1), got {:?}", r)
+    }
+    assert_eq!( sink.pending(), 1 );
+}
+
+#[cfg(feature = "protobuf")]
+#[test]
+fn test_proto_events() {
+    use proto;
+
+    let events = vec![MarketEvent::OrderAdded{ timestamp : 1, order_id : 42, side : 'B', price : 1831900, shares : 100,
+                                               symbol : String::from("AAPL") },
+                      MarketEvent::Trade{ timestamp : 2, order_id : Some(42), symbol : None, side : None, price : None,
+                                          shares : 50, remaining : None, exec_id : 7 },
+                      MarketEvent::StatusChange{ timestamp : 3, symbol : None, state : TradingState::Other('S') }];
+    for e in &events {
+        assert_eq!( &proto::decode_event(&proto::encode_event(e)).unwrap(), e );
+    }
+    assert_eq!( proto::decode_event(&[]), Err(proto::ProtoError::MissingEvent) );
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn test_ffi_book() {
+    use ffi;
+    use ffi::ObEvent;
+    use ffi::ObLevel;
+
+    let mut e = ObEvent::default();
+    let book = ffi::ob_book_new();
+    unsafe {
+        for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                     "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                     "28800170X1K27GA00000Y000040\r\n"] {
+            assert_eq!( ffi::ob_parse(msg.as_ptr() as *const _, msg.len(), &mut e), 1 );
+            assert_eq!( ffi::ob_book_apply(book, &e), 1 );
+        }
+        assert_eq!( (e.kind, e.order_id, e.shares, e.timestamp), (ffi::OB_ORDER_REDUCED, 204969015920664610, 40, 28800170000000) );
+
+        let msg = "28800168A1K27GA00000YS000100AAPL  0001831900Y";
+        ffi::ob_parse(msg.as_ptr() as *const _, msg.len(), &mut e);
+        assert_eq!( (e.side as u8, e.price, e.flags), (b'S', 1831900, ffi::OB_HAS_ORDER_ID | ffi::OB_HAS_PRICE) );
+        assert_eq!( &e.symbol[..5], &[b'A' as _, b'A' as _, b'P' as _, b'L' as _, 0] );
+
+        assert_eq!( (ffi::ob_book_best_bid(book), ffi::ob_book_best_ask(book)), (1831800, 1831900) );
+        assert_eq!( ffi::ob_book_volume(book, ffi::OB_ASK, 1831900), 60 );
+        assert_eq!( ffi::ob_book_add_order(book, 7, b'B' as _, 1831700, 500), 0 );
+        assert_eq!( ffi::ob_book_add_order(book, 8, b'x' as _, 1831700, 500), -1 );
+        let mut levels = [ObLevel{ price : 0, volume : 0 }; 4];
+        assert_eq!( ffi::ob_book_levels(book, ffi::OB_BID, levels.as_mut_ptr(), levels.len()), 2 );
+        assert_eq!( (levels[0].price, levels[1].price, levels[1].volume), (1831800, 1831700, 500) );
+
+        assert_eq!( ffi::ob_book_reduce_order(book, 7, 500), 0 );
+        assert_eq!( ffi::ob_book_delete_order(book, 7), -1 );
+        assert_eq!( ffi::ob_book_order_count(book), 2 );
+
+        // a part id cut short is a bad message, not a panic
+        for junk in &["junk", "28800168A1K27GA00000YS000100AAPL  0001831900YAB", "28800168A1K27GA00000YS000100AAPL  0001831900YABCé"] {
+            assert_eq!( ffi::ob_parse(junk.as_ptr() as *const _, junk.len(), &mut e), -1 );
+        }
+        assert_eq!( ffi::ob_book_apply(std::ptr::null_mut(), &e), -1 );
+        ffi::ob_book_free(book);
+
+        // the feed's book is recorded, a locked add rests rather than matching
+        let book = ffi::ob_book_new();
+        assert_eq!( ffi::ob_book_add_order(book, 1, b'B' as _, 100000, 100), 0 );
+        assert_eq!( ffi::ob_book_add_order(book, 2, b'S' as _, 100000, 100), 0 );
+        assert_eq!( (ffi::ob_book_best_bid(book), ffi::ob_book_best_ask(book), ffi::ob_book_order_count(book)), (100000, 100000, 2) );
+        assert_eq!( (ffi::ob_book_delete_order(book, 1), ffi::ob_book_delete_order(book, 2)), (0, 0) );
+        assert_eq!( (ffi::ob_book_best_bid(book), ffi::ob_book_order_count(book)), (0, 0) );
+        ffi::ob_book_free(book);
+    }
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn test_wasm_book() {
+    use book::Side;
+    use wasm::Book;
+
+    let mut book = Book::new(Some(String::from("AAPL")));
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00003YB000100AAPL  0001831800Y",
+                 "28800169A1K27GA00002YB000300MSFT  0000450000Y",
+                 "28800170X1K27GA00000Y000040"] {
+        book.apply_message(&BATSMsgFactory::parse(msg));
+    }
+    assert_eq!( (book.best_bid(), book.best_ask(), book.orders()), (Some(1831800.0), Some(1831900.0), 3) );
+    assert_eq!( book.levels(Side::Bid, 0), vec![(1831800, 300)] );
+    assert_eq!( book.levels(Side::Ask, 1), vec![(1831900, 60)] );
+
+    assert!( book.apply_message(&BATSMsgFactory::parse("28800170E1K27GA00000Y0000601K27GA00000K")) );
+    assert!( !book.apply_message(&BATSMsgFactory::parse("28800170E1K27GA00000Y0000601K27GA00000K")) );
+    assert_eq!( (book.best_ask(), book.orders()), (None, 2) );
+    assert!( book.apply_message(&BATSMsgFactory::parse("28800171sAAPL    ")) );
+    assert_eq!( book.best_bid(), None );
+}
+
+#[cfg(feature = "node")]
+#[test]
+fn test_node_book_manager() {
+    use node::Bbo;
+    use node::BookManager;
+    use node::Level;
+
+    let mut books = BookManager::new();
+    for msg in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800169A1K27GA00001YB000200AAPL  0001831800Y",
+                 "28800169A1K27GA00002YB000300MSFT  0000450000Y",
+                 "28800170X1K27GA00000Y000040"] {
+        assert!( books.apply_message(&BATSMsgFactory::parse(msg)) );
+    }
+    assert_eq!( books.symbols(), vec!["AAPL", "MSFT"] );
+    assert_eq!( books.bbo(String::from("AAPL")),
+                Some(Bbo{ bid_price : Some(1831800.0), bid_shares : Some(200), ask_price : Some(1831900.0),
+                          ask_shares : Some(60) }) );
+    assert_eq!( books.depth(String::from("MSFT"), 5).unwrap().bids, vec![Level{ price : 450000.0, shares : 300 }] );
+    assert_eq!( books.bbo(String::from("IBM")), None );
+
+    assert!( books.apply_message(&BATSMsgFactory::parse("28800171sAAPL    ")) );
+    assert_eq!( (books.symbols(), books.orders()), (vec![String::from("MSFT")], 1) );
+}
+
+#[cfg(feature = "python")]
+#[test]
+fn test_python_module() {
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+    use std::ffi::CString;
+    use std::io::Write;
+
+    let path = env::temp_dir().join(format!("orderbook_python_{}.txt", std::process::id()));
+    let mut f = File::create(&path).unwrap();
+    writeln!(f, "28800168A1K27GA00000YS000100AAPL  0001831900Y").unwrap();
+    writeln!(f, "28800169A1K27GA00001YB000200AAPL  0001831800Y").unwrap();
+    writeln!(f, "28800169A1K27GA00002YB000300MSFT  0000450000Y").unwrap();
+    writeln!(f, "28800170X1K27GA00000Y000040").unwrap();
+    drop(f);
+
+    let code = CString::new(format!(r#"
+msgs = ob.parse_file({:?})
+assert [m["type"] for m in msgs] == ["add_order"] * 3 + ["order_cancel"]
+assert msgs[0]["price"] == 1831900 and msgs[0]["side"] == "S"
+
+book = ob.OrderBook("AAPL")
+assert [book.apply(m) for m in msgs] == [True, True, False, True]
+assert (book.best_bid(), book.best_ask(), len(book)) == (1831800, 1831900, 2)
+assert book.asks() == [(1831900, 60)] and book.bids(1) == [(1831800, 200)]
+
+m = ob.Message("28800170E1K27GA00001Y0002001K27GA00000K")
+assert m.type == "order_executed" and m.shares == 200
+assert book.apply(m) and book.best_bid() is None
+
+try:
+    ob.parse("junk")
+    assert False
+except ValueError:
+    pass
+"#, path.to_str().unwrap())).unwrap();
+
+    Python::initialize();
+    Python::attach(|py| {
+        let globals = PyDict::new(py);
+        globals.set_item("ob", pyo3::wrap_pymodule!(python::init_module)(py)).unwrap();
+        if let Err(e) = py.run(&code, Some(&globals), None) {
+            panic!("{}", e);
+        }
+    });
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_iex_parse() {
+    let msgs = vec![iex_price_level_update(0x38, 100, 995000), 
+                    iex_price_level_update(0x35, 200, 996000), 
+                    iex_price_level_update(0x38, 300, 994000), 
+                    iex_price_level_update(0x38, 0, 995000)];
+
+    let mut segment : Vec<u8> = vec![0x01, 0x00, 0x04, 0x80];
+    segment.extend_from_slice(&[0u8; 8]);
+    segment.extend_from_slice(&128u16.to_le_bytes());
+    segment.extend_from_slice(&(msgs.len() as u16).to_le_bytes());
+    segment.extend_from_slice(&[0u8; 24]);
+    for m in &msgs {
+        segment.extend_from_slice(&(m.len() as u16).to_le_bytes());
+        segment.extend_from_slice(m);
+    }
+
+    let (header, payloads) = iex::split_segment(&segment).unwrap();
+    assert_eq!( header.protocol_id,   0x8004 );
+    assert_eq!( header.message_count, 4 );
+
+    let mut book = IexBook::new();
+    for p in payloads {
+        match IexMessage::parse(p).unwrap() {
+            IexMessage::PriceLevelUpdate(u) => {
+                assert!(u.is_event_complete());
+                book.apply(&u);
+                if u.size == 200 {
+                    assert_eq!( book.best_bid(), 995000 );
+                }
+            },
+            m => panic!("unexpected {:?}", m),
+        }
+    }
+    assert_eq!( book.best_bid(), 994000 );
+    assert_eq!( book.best_ask(), 996000 );
+    assert_eq!( book.ask_volume_at_price_level(996000), 200 );
+    assert_eq!( book.bid_volume_at_price_level(995000), 0 );
+}
+
+#[test]
+fn test_xdp_parse() {
+    let mut mapping : Vec<u8> = vec![44, 0, 3, 0];
+    mapping.extend_from_slice(&7u32.to_le_bytes());
+    mapping.extend_from_slice(b"IBM\0\0\0\0\0\0\0\0");
+    mapping.extend_from_slice(&[0, 1, 0, 1, b'N', 6, b'A', 100, 0]);
+    mapping.extend_from_slice(&[0u8; 8]);
+    mapping.extend_from_slice(&[1, b'Y', 1, 0, 1, 0, 0, 0]);
+    assert_eq!( mapping.len(), 44 );
+
+    let mut add : Vec<u8> = vec![39, 0, 100, 0];
+    add.extend_from_slice(&500u32.to_le_bytes());
+    add.extend_from_slice(&7u32.to_le_bytes());
+    add.extend_from_slice(&1u32.to_le_bytes());
+    add.extend_from_slice(&9001u64.to_le_bytes());
+    add.extend_from_slice(&183_190_000u32.to_le_bytes());
+    add.extend_from_slice(&300u32.to_le_bytes());
+    add.push(b'B');
+    add.extend_from_slice(b"FIRM1");
+    add.push(0);
+
+    let mut packet : Vec<u8> = vec![0, 0, 0, 2];
+    packet.extend_from_slice(&[0u8; 12]);
+    packet.extend_from_slice(&mapping);
+    packet.extend_from_slice(&add);
+
+    let (header, msgs) = xdp::split_packet(&packet).unwrap();
+    assert_eq!( header.count, 2 );
+
+    let mut symbols = XdpSymbolMap::new();
+    assert!( symbols.apply(XdpMessage::parse(msgs[0]).unwrap()).is_none() );
+    assert_eq!( symbols.symbol(7), Some("IBM") );
+
+    match symbols.apply(XdpMessage::parse(msgs[1]).unwrap()) {
+        Some(XdpMessage::AddOrder(o)) => {
+            assert_eq!( o.order_id, 9001 );
+            assert_eq!( o.side,     'B' );
+            assert_eq!( o.volume,   300 );
+            assert_eq!( symbols.normalize_price(o.symbol_index, o.price), Some(1831900) );
+        },
+        m => panic!("unexpected {:?}", m),
+    }
+}
+
+#[cfg(feature = "mdp3")]
+fn mdp3_book_entry( price : i64, size : i32, level : u8, action : u8, entry_type : u8 ) -> Vec<u8> {
+    let mut entry : Vec<u8> = Vec::new();
+    entry.extend_from_slice(&price.to_le_bytes());
+    entry.extend_from_slice(&size.to_le_bytes());
+    entry.extend_from_slice(&5001i32.to_le_bytes());
+    entry.extend_from_slice(&1u32.to_le_bytes());
+    entry.extend_from_slice(&3i32.to_le_bytes());
+    entry.extend_from_slice(&[level, action, entry_type]);
+    entry.extend_from_slice(&[0u8; 5]);
+    entry
+}
+
+#[test]
+fn test_ouch() {
+    let enter = ouch::EnterOrderMsg{ token         : String::from("ORD1"),
+                                     side          : 'B',
+                                     shares        : 100,
+                                     symbol        : String::from("AAPL"),
+                                     price         : 1831900,
+                                     time_in_force : 99999,
+                                     firm          : String::from("ACME"),
+                                     display       : 'Y',
+                                     capacity      : 'A',
+                                     iso           : 'N',
+                                     min_qty       : 0,
+                                     cross_type    : 'N',
+                                     customer_type : 'R' };
+    let buf = enter.to_bytes();
+    assert_eq!( buf.len(), 49 );
+    assert_eq!( &buf[1..15], b"ORD1          " );
+    assert_eq!( &buf[28..32], &1831900u32.to_be_bytes() );
+
+    let cancel = ouch::CancelOrderMsg{ token : String::from("ORD1"), shares : 0 };
+    assert_eq!( cancel.to_bytes().len(), 19 );
+
+    let mut accepted = vec![b'A'];
+    accepted.extend_from_slice(&34_200_000_000_000u64.to_be_bytes());
+    accepted.extend_from_slice(&buf[1..41]);   // token through display, same layout as the enter
+    accepted.extend_from_slice(&77u64.to_be_bytes());
+    accepted.extend_from_slice(b"AN");
+    accepted.extend_from_slice(&0u32.to_be_bytes());
+    accepted.extend_from_slice(b"NLN");
+    match OuchMessage::parse(&accepted).unwrap() {
+        OuchMessage::Accepted(a) => {
+            assert_eq!( a.token,       "ORD1" );
+            assert_eq!( a.symbol,      "AAPL" );
+            assert_eq!( a.order_state, 'L' );
+            let o = a.to_order();
+            assert_eq!( o.order_id, 77 );
+            assert_eq!( o.price,    1831900 );
+            assert_eq!( o.side,     1 );
+        },
+        _ => panic!("expected accepted")
+    }
+
+    let mut canceled = vec![b'C'];
+    canceled.extend_from_slice(&0u64.to_be_bytes());
+    canceled.extend_from_slice(b"ORD1          ");
+    canceled.extend_from_slice(&100u32.to_be_bytes());
+    canceled.push(b'U');
+    match OuchMessage::parse(&canceled).unwrap() {
+        OuchMessage::Canceled(c) => assert_eq!( (c.shares, c.reason), (100, 'U') ),
+        _ => panic!("expected canceled")
+    }
+}
+
+#[test]
+fn test_fix_market_data() {
+    let snap = "8=FIX.4.4|9=112|35=W|49=VENUE|56=CLIENT|34=2|55=AAPL|268=2|269=0|270=183.19|271=500|269=1|270=183.2|271=300|10=065|";
+    let msg = FixMessage::parse(snap).unwrap();
+    assert_eq!( msg.msg_type(), Some("W") );
+    assert!( msg.verify_checksum() );
+
+    let mut b = LimitOrderBook::new();
+    match FixMarketData::from_message(&msg).unwrap() {
+        FixMarketData::Snapshot(s) => {
+            assert_eq!( s.symbol, "AAPL" );
+            assert_eq!( s.entries.len(), 2 );
+            for e in &s.entries {
+                b.add_order(e.to_order().unwrap());
+            }
+        },
+        _ => panic!("expected snapshot")
+    }
+    assert_eq!( b.best_bid(), 1831900 );
+    assert_eq!( b.best_ask(), 1832000 );
+
+    let inc = "8=FIX.4.4\u{1}35=X\u{1}268=2\u{1}279=1\u{1}269=0\u{1}55=AAPL\u{1}270=183.19\u{1}271=200\u{1}\
+               279=2\u{1}269=1\u{1}55=AAPL\u{1}270=183.20\u{1}271=0\u{1}";
+    match FixMarketData::parse(inc).unwrap() {
+        FixMarketData::Incremental(i) => {
+            assert_eq!( i.entries[0].action, MdUpdateAction::Change );
+            assert_eq!( i.entries[0].size,   200 );
+            assert_eq!( i.entries[1].action, MdUpdateAction::Delete );
+            assert_eq!( i.entries[1].price,  1832000 );
+        },
+        _ => panic!("expected incremental")
+    }
+}
+
+#[cfg(feature = "mdp3")]
+#[test]
+fn test_mdp3_incremental_refresh() {
+    use mdp3;
+    use mdp3::MdpBook;
+    use mdp3::MdpMessage;
+
+    let entries = vec![mdp3_book_entry(4_200_250_000_000, 10, 1, 0, b'0'), 
+                       mdp3_book_entry(4_200_500_000_000, 5, 1, 0, b'0'), 
+                       mdp3_book_entry(4_200_750_000_000, 7, 1, 0, b'1'), 
+                       mdp3_book_entry(4_201_000_000_000, 2, 1, 0, b'E'), 
+                       mdp3_book_entry(4_200_500_000_000, 0, 1, 2, b'0')];
+
+    let mut msg : Vec<u8> = vec![];
+    msg.extend_from_slice(&11u16.to_le_bytes()); // block length, includes 2 padding bytes
+    msg.extend_from_slice(&46u16.to_le_bytes());
+    msg.extend_from_slice(&1u16.to_le_bytes());
+    msg.extend_from_slice(&9u16.to_le_bytes());
+    msg.extend_from_slice(&1_500_000_000_000_000_000u64.to_le_bytes());
+    msg.extend_from_slice(&[0x80, 0, 0]);
+    msg.extend_from_slice(&32u16.to_le_bytes());
+    msg.push(entries.len() as u8);
+    for e in &entries {
+        msg.extend_from_slice(e);
+    }
+
+    let mut packet : Vec<u8> = vec![];
+    packet.extend_from_slice(&77u32.to_le_bytes());
+    packet.extend_from_slice(&1_500_000_000_000_000_000u64.to_le_bytes());
+    packet.extend_from_slice(&(msg.len() as u16 + 2).to_le_bytes());
+    packet.extend_from_slice(&msg);
+
+    let (header, msgs) = mdp3::split_packet(&packet).unwrap();
+    assert_eq!( header.sequence, 77 );
+    assert_eq!( msgs.len(),      1 );
+
+    let parsed = MdpMessage::parse(msgs[0]).unwrap();
+    let updates = parsed.price_level_updates();
+    assert_eq!( updates.len(), 4 ); // implied entry is dropped
+
+    let mut book = MdpBook::new(5001, 10);
+    for u in &updates[..2] {
+        book.apply(u);
+    }
+    assert_eq!( book.best_bid().unwrap().price, 4_200_500_000_000 );
+    assert_eq!( book.bids().len(), 2 );
+
+    for u in &updates[2..] {
+        book.apply(u);
     }
+    assert_eq!( book.best_bid().unwrap().price,   4_200_250_000_000 );
+    assert_eq!( book.best_offer().unwrap().size,  7 );
 }
 
+#[cfg(feature = "europe")]
 #[test]
-fn test_factory() {
-    let obj = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
-    println!("Return result from msg factory {:?}", obj);
+fn test_europe_parse() {
+    use decoder::DecodeError;
+    use europe::EuropeMsgFactory;
+    use europe::TradeReportMsg;
+
+    let obj = EuropeMsgFactory::parse("28800168A1K27GA00000YS0000000100VODl    0000000001551250000Y").unwrap();
     let msg_obj : Option<AddOrderMsg> = obj.into();
     assert!(msg_obj.is_some());
-    println!("After into {:?}", msg_obj);
 
-    let obj = BATSMsgFactory::parse("28800168JAAPLSPOTC00010068000000020000");
-    println!("Return result from msg factory {:?}", obj);
-    let msg_obj : Option<AuctionSummaryMsg> = obj.into();
-    println!("After into {:?}", msg_obj);
+    let o = msg_obj.unwrap();
+    assert_eq!( o.shares, 100 );
+    assert_eq!( o.symbol, "VODl    " );
+    assert_eq!( o.price,  1551250 );
+
+    let obj = EuropeMsgFactory::parse("28800168T1K27GA00000Z0000005000VODl    0000000001551250000GBPX").unwrap();
+    let msg_obj : Option<TradeReportMsg> = obj.into();
     assert!(msg_obj.is_some());
+    assert_eq!( msg_obj.unwrap().currency, "GBP" );
+
+    // short, unknown, and a price past 4 decimals, rather than panicking or truncating it
+    assert!( EuropeMsgFactory::try_parse("288").is_none() );
+    assert!( EuropeMsgFactory::try_parse("28800168Q1K27GA00000Y").is_none() );
+    assert!( EuropeMsgFactory::try_parse("28800168A1K27GA00000YS0000000100VODl    0000000001551250001Y").is_none() );
+    assert!( EuropeMsgFactory::try_parse("28800168A1K27GA00000YS0000000100VODl    0000000001551250000Y").is_some() );
+    // parse gives a malformed line back as an error.
+    for line in &["28800168A1K27GA00000YS0000000100VODl    00000000015512500", "2880016é"] {
+        match EuropeMsgFactory::parse(line) {
+            Err(DecodeError::Invalid(ref s)) => assert_eq!( s, line ),
+            _                                => panic!("{} parsed", line)
+        }
+    }
 }
 
 #[test]
@@ -220,6 +3007,10 @@ fn test_limit_order_book() {
     let o7 = Order{order_id : 2007, price : 10225, volume : 300, side : 1, part_id : String::from("Acme Corp.")};
     b.add_order(o7);
     assert_eq!(b.ask_volume_at_price_level(10200), 100);
+
+    b.clear();
+    assert_eq!(b.best_bid(), 0 );
+    assert_eq!(b.best_ask(), 0 );
 }
 
 #[test]
@@ -237,8 +3028,1203 @@ fn test_lob_threading() {
 
 
 
+
+#[test]
+fn test_session_client() {
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::Duration;
+    use book::BookManager;
+    use session::SessionClient;
+    use session::SessionError;
+
+    fn respond( status : LoginStatus ) -> Vec<u8> {
+        encode_packet(&[LoginResponseMsg{ msg_type : 0x02, status }.to_bytes()])
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        let mut login = [0u8; 30];
+        s.read_exact(&mut login).unwrap();
+        s.write_all(&SequencedUnitHeader::heartbeat().to_bytes()).unwrap();
+        s.write_all(&respond(LoginStatus::Accepted)).unwrap();
+        let mut data = SequencedUnitHeader{ length : 30, count : 2, unit : 1, sequence : 1 }.to_bytes();
+        data.extend_from_slice(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00]);
+        data.extend_from_slice(&[0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 0x64, 0x00]);
+        // a packet split across writes is put back together
+        s.write_all(&data[..11]).unwrap();
+        s.flush().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        s.write_all(&data[11..]).unwrap();
+        // the client's heartbeat
+        let mut heartbeat = [0u8; 8];
+        s.read_exact(&mut heartbeat).unwrap();
+        s.write_all(&[0x0E, 0x00, 0x01, 0x01, 0x02, 0x00, 0x00, 0x00, 0x06, 0x2D, 0x00, 0x00, 0x00, 0x00]).unwrap();
+        (login, heartbeat)
+    });
+
+    let login = LoginMsg::new("0001", "USER", "PASS");
+    let mut session = SessionClient::connect(addr, &login).unwrap().heartbeat(Duration::from_millis(50)).timeout(Duration::from_millis(500));
+    let mut books = BookManager::new();
+    session.run(&mut books, &AtomicBool::new(false)).unwrap();
+    assert!( session.ended() );
+    let stats = session.stats();
+    assert_eq!( (stats.packets, stats.messages, stats.heartbeats), (2, 3, 0) );
+    assert!( stats.heartbeats_sent >= 1 );
+    let (sent, heartbeat) = server.join().unwrap();
+    assert_eq!( &sent[..], &encode_packet(&[login.to_bytes()])[..] );
+    assert_eq!( &heartbeat[..], &SequencedUnitHeader::heartbeat().to_bytes()[..] );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        s.read_exact(&mut [0u8; 30]).unwrap();
+        s.write_all(&respond(LoginStatus::NotAuthorized)).unwrap();
+    });
+    match SessionClient::connect(addr, &login) {
+        Err(SessionError::Rejected(LoginStatus::NotAuthorized)) => {},
+        _ => panic!("expected the login to be rejected")
+    }
+    server.join().unwrap();
+}
+
+
+#[test]
+#[cfg(feature = "tokio")]
+fn test_async_feed() {
+    use std::net::Ipv4Addr;
+    use std::net::UdpSocket;
+    use tokio::runtime::Builder;
+    use tokio_stream::StreamExt;
+    use async_feed;
+    use async_feed::DatagramStream;
+    use async_feed::PacketStream;
+    use async_feed::PitchStream;
+    use book::BookManager;
+    use event::MarketEvent;
+
+    let rt = Builder::new_current_thread().enable_io().build().unwrap();
+
+    let text = "28800168A1K27GA00000YS000100AAPL  0001831900Y\n\nnot a message\n28800170X1K27GA00000Y000040\n";
+    let events = rt.block_on(PitchStream::new(text.as_bytes()).collect::<Vec<_>>());
+    assert_eq!( events.len(), 4 );
+    assert!( matches!(events[0], Ok(FeedEvent::Message(BATSMessage::AddOrderMsg(_)))) );
+    assert_eq!( events[1].as_ref().unwrap_err().kind(), ::std::io::ErrorKind::InvalidData );
+    assert!( matches!(events[2], Ok(FeedEvent::Message(BATSMessage::OrderCancelMsg(_)))) );
+    assert!( matches!(events[3], Ok(FeedEvent::SessionComplete)) );
+
+    let mut books = BookManager::new();
+    let applied = rt.block_on(async_feed::apply_all(PitchStream::new(&text.as_bytes()[..46]), &mut books)).unwrap();
+    assert_eq!( applied, 1 );
+    assert_eq!( books.book("AAPL").unwrap().best_ask().map(|q| q.price), Some(1831900) );
+
+    let reduce = vec![0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 0x64, 0x00];
+    let mut data = encode_packet(&[vec![0x06, 0x20, 0x80, 0x70, 0x00, 0x00], reduce.clone()]);
+    data.extend_from_slice(&[0x0E, 0x00, 0x01, 0x01, 0x10, 0x00, 0x00, 0x00, 0x06, 0x2D, 0x00, 0x00, 0x00, 0x00]);
+    let events = rt.block_on(PacketStream::new(&data[..]).collect::<Vec<_>>());
+    assert_eq!( events.len(), 4 );
+    assert!( matches!(events[2], Ok(FeedEvent::Message(BATSMessage::EndOfSessionMsg(_)))) );
+    assert!( matches!(events[3], Ok(FeedEvent::SessionComplete)) );
+    let cut = rt.block_on(PacketStream::new(&data[..20]).collect::<Vec<_>>());
+    assert_eq!( cut.len(), 1 );
+    assert_eq!( cut[0].as_ref().unwrap_err().kind(), ::std::io::ErrorKind::UnexpectedEof );
+
+    let events = rt.block_on(async_feed::market_events(PacketStream::new(&data[..])).collect::<Vec<_>>());
+    match events[0] {
+        Ok(MarketEvent::OrderReduced{ timestamp, shares, .. }) => assert_eq!( (timestamp, shares), (28800 * 1_000_000_000 + 24, 100) ),
+        ref e => panic!("expected a reduce, got {:?}", e)
+    }
+
+    let _runtime = rt.enter();
+    let mut stream = DatagramStream::join(0, &["127.0.0.1:0".parse().unwrap()], Ipv4Addr::LOCALHOST).unwrap();
+    let port = stream.socket().local_addr().unwrap().port();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut sequenced = SequencedUnitHeader{ length : 24, count : 1, unit : 1, sequence : 1 }.to_bytes();
+    sequenced.extend_from_slice(&reduce);
+    for _ in 0..2 {
+        sender.send_to(&sequenced, ("127.0.0.1", port)).unwrap();
+    }
+    sender.send_to(&data[..data.len() - 14], ("127.0.0.1", port)).unwrap();
+    let first = rt.block_on(stream.next()).unwrap().unwrap();
+    assert!( matches!(first, FeedEvent::Message(BATSMessage::ReduceSizeMsg(_))) );
+    let next = rt.block_on(stream.next()).unwrap().unwrap();
+    assert!( matches!(next, FeedEvent::Message(BATSMessage::TimeMsg(_))) );
+    assert_eq!( (stream.stats().packets, stream.stats().duplicates), (3, 1) );
+}
+
+
+#[test]
+fn test_bounded_pipeline() {
+    use std::io;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread;
+    use bounded;
+    use bounded::Backpressure;
+    use bounded::PipelineBuilder;
+    use event::MarketEvent;
+
+    let (tx, rx) = bounded::channel(2, Backpressure::DropOldest);
+    for exec_id in 0..5 {
+        tx.send(MarketEvent::TradeBroken{ timestamp : 0, exec_id }).unwrap();
+    }
+    drop(tx);
+    let kept : Vec<_> = ::std::iter::from_fn(|| rx.recv().unwrap()).collect();
+    assert_eq!( kept, vec![MarketEvent::TradeBroken{ timestamp : 0, exec_id : 3 }, MarketEvent::TradeBroken{ timestamp : 0, exec_id : 4 }] );
+    assert_eq!( (rx.stats().dropped, rx.stats().high_water), (3, 2) );
+
+    // text and binary messages both come back off disk, in order
+    let dir = ::std::env::temp_dir();
+    let lines : Vec<String> = (0..6).map(|i| format!("28800168AID{:010}S000100AAPL  0001831900Y", i)).collect();
+    let (tx, rx) = bounded::channel(2, Backpressure::Spill(dir.clone()));
+    for line in &lines {
+        tx.send(BATSMsgFactory::parse(line)).unwrap();
+    }
+    tx.send(BATSMsgFactory::parse_binary(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00])).unwrap();
+    assert_eq!( (rx.len(), rx.stats().spilled), (7, 5) );
+    for line in &lines[..3] {
+        assert_eq!( rx.recv().unwrap().unwrap().to_pitch_string().as_ref(), Some(line) );
+    }
+    // still behind what's on disk
+    tx.send(BATSMsgFactory::parse_binary(&[0x06, 0x2D, 0x00, 0x00, 0x00, 0x00])).unwrap();
+    for line in &lines[3..] {
+        assert_eq!( rx.recv().unwrap().unwrap().to_pitch_string().as_ref(), Some(line) );
+    }
+    assert!( matches!(rx.recv().unwrap(), Some(BATSMessage::TimeMsg(_))) );
+    assert!( matches!(rx.recv().unwrap(), Some(BATSMessage::EndOfSessionMsg(_))) );
+    assert_eq!( rx.stats().spilled, 6 );
+
+    let (tx, rx) = bounded::channel(1, Backpressure::Block);
+    let sender = thread::spawn(move || {
+        for exec_id in 0..3 {
+            tx.send(MarketEvent::TradeBroken{ timestamp : 0, exec_id }).unwrap();
+        }
+    });
+    thread::sleep(::std::time::Duration::from_millis(20));
+    let got : Vec<_> = ::std::iter::from_fn(|| rx.recv().unwrap()).collect();
+    sender.join().unwrap();
+    assert_eq!( got.len(), 3 );
+    assert!( rx.stats().blocked >= 1 );
+
+    let mut msgs : Vec<BATSMessage> = lines.iter().map(|l| BATSMsgFactory::parse(l)).collect();
+    msgs.push(BATSMsgFactory::parse(&format!("28800169EID{:010}0000601K27GA00010K", 0)));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let into = seen.clone();
+    let pipeline = PipelineBuilder::new()
+        .capacity(2)
+        .events(Backpressure::Spill(dir))
+        .sink(move |e : &MarketEvent| { into.lock().unwrap().push(e.clone()); Ok(()) })
+        .spawn(msgs.into_iter());
+    let books = pipeline.join().unwrap();
+    assert_eq!( books.orders(), 6 );
+    let seen = seen.lock().unwrap();
+    assert_eq!( seen.len(), 7 );
+    assert!( matches!(seen[6], MarketEvent::Trade{ shares : 60, .. }) );
+
+    let pipeline = PipelineBuilder::new()
+        .sink(|_ : &MarketEvent| Err(io::Error::other("sink down")))
+        .spawn(lines.iter().map(|l| BATSMsgFactory::parse(l)).collect::<Vec<_>>().into_iter());
+    assert_eq!( pipeline.join().unwrap_err().to_string(), "sink down" );
+}
+
+
+#[test]
+fn test_encode_binary_messages() {
+    use event::MarketEvent;
+    use event::TradingState;
+
+    let mut calculated : Vec<u8> = vec![0x1F, 0xE3, 0x18, 0x00, 0x00, 0x00];
+    calculated.extend_from_slice(b"SPY     1");
+    calculated.extend_from_slice(&4101234u64.to_le_bytes());
+    calculated.extend_from_slice(&1_340_000_000_000_000_000u64.to_le_bytes());
+    let wire = vec![vec![0x10, 0x26, 0x18, 0x00, 0x00, 0x00, 0x05, 0x40, 0x5B, 0x77, 0x8F, 0x56, 0x1D, 0x0B, 0x64, 0x00],
+                    vec![0x06, 0x20, 0x80, 0x70, 0x00, 0x00], vec![0x06, 0x97, 0x01, 0x00, 0x00, 0x00], calculated];
+    for bytes in &wire {
+        assert_eq!( &BATSMsgFactory::parse_binary(bytes).to_binary().unwrap(), bytes );
+    }
+    assert!( BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y").to_binary().is_none() );
+
+    let events = vec![MarketEvent::Trade{ timestamp : 1, order_id : None, symbol : Some(String::from("AAPL")), side : Some('B'),
+                                          price : Some(1831900), shares : 100, remaining : None, exec_id : 7 },
+                      MarketEvent::StatusChange{ timestamp : 2, symbol : None, state : TradingState::Other('R') },
+                      MarketEvent::OrderReplaced{ timestamp : 3, order_id : 4, new_order_id : 5, price : 6, shares : 7 }];
+    let mut buf = Vec::new();
+    for e in &events {
+        e.write_to(&mut buf).unwrap();
+    }
+    let mut r = &buf[..];
+    let back : Vec<_> = ::std::iter::from_fn(|| MarketEvent::read_from(&mut r).unwrap()).collect();
+    assert_eq!( back, events );
+}
+
+
+#[test]
+fn test_staleness_watchdog() {
+    use std::time::Duration;
+    use std::time::Instant;
+    use book::BookManager;
+    use watchdog::Source;
+    use watchdog::Staleness;
+    use watchdog::Watchdog;
+
+    let secs = Duration::from_secs;
+    let t0 = Instant::now();
+    let mut books = BookManager::new();
+    let mut watchdog = Watchdog::new(secs(2), secs(10)).symbol_threshold("IBM", secs(30));
+    let add = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
+    books.apply(&add);
+    watchdog.packet(1, t0);
+    watchdog.message(&add, &books, t0);
+    watchdog.symbol("IBM     ", t0);
+    assert!( watchdog.check(t0 + secs(1)).is_empty() );
+
+    let aapl = Source::Symbol(String::from("AAPL"));
+    let changes = watchdog.check(t0 + secs(11));
+    assert_eq!( changes, vec![Staleness::Stale{ source : Source::Unit(1), quiet : secs(11) },
+                              Staleness::Stale{ source : aapl.clone(), quiet : secs(11) }] );
+    // reported once
+    assert!( watchdog.check(t0 + secs(12)).is_empty() );
+    assert_eq!( watchdog.stale(), vec![&Source::Unit(1), &aapl] );
+
+    // an execute finds its symbol through the books
+    let exec = BATSMsgFactory::parse("28800169E1K27GA00000Y0000601K27GA00010K");
+    watchdog.packet(1, t0 + secs(13));
+    watchdog.message(&exec, &books, t0 + secs(13));
+    assert_eq!( watchdog.check(t0 + secs(13)), vec![Staleness::Fresh{ source : Source::Unit(1), quiet : secs(13) },
+                                                    Staleness::Fresh{ source : aapl.clone(), quiet : secs(13) }] );
+    assert!( !watchdog.is_stale(&aapl) );
+    assert_eq!( watchdog.check(t0 + secs(31)).len(), 3 );
+    assert_eq!( watchdog.quiet(&Source::Symbol(String::from("IBM")), t0 + secs(31)), Some(secs(31)) );
+    assert_eq!( watchdog.quiet(&Source::Unit(2), t0), None );
+}
+
+
+#[test]
+fn test_session_lifecycle() {
+    use std::fs;
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use book::BookManager;
+    use lifecycle::EndReason;
+    use lifecycle::RotatingFile;
+    use lifecycle::SessionEvent;
+    use lifecycle::SessionTracker;
+
+    let dir = ::std::env::temp_dir().join(format!("orderbook-sessions-{}", ::std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let summaries = Arc::new(Mutex::new(Vec::new()));
+    let seen = summaries.clone();
+    let mut sessions = SessionTracker::new()
+        .rotate(RotatingFile::new(&dir, "day-{}.txt"))
+        .on_end(move |s, books| seen.lock().unwrap().push((s.clone(), books.book("AAPL").is_some())));
+    let mut books = BookManager::new();
+
+    let add = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
+    let events = sessions.apply(&add, &mut books).unwrap();
+    assert_eq!( events, vec![SessionEvent::Started{ session : 1, timestamp : 28_800_168_000_000 }] );
+    writeln!(sessions.output().unwrap(), "first").unwrap();
+    sessions.apply(&BATSMsgFactory::parse("57600000X1K27GA00000Y000040"), &mut books).unwrap();
+
+    // the next morning's open, without an end of session in between
+    let events = sessions.apply(&BATSMsgFactory::parse("28800168A1K27GA00001YS000100IBM   0001831900Y"), &mut books).unwrap();
+    assert_eq!( events.len(), 2 );
+    match events[0] {
+        SessionEvent::Ended{ ref summary, reason : EndReason::Rollover } =>
+            assert_eq!( (summary.session, summary.first, summary.last, summary.messages, summary.orders), (1, 28_800_168_000_000, 57_600_000_000_000, 2, 1) ),
+        ref e => panic!("expected a rollover, got {:?}", e)
+    }
+    assert_eq!( events[1], SessionEvent::Started{ session : 2, timestamp : 28_800_168_000_000 } );
+    // reset before the new session's first message
+    assert!( books.book("AAPL").is_none() && books.book("IBM").is_some() );
+    writeln!(sessions.output().unwrap(), "second").unwrap();
+
+    let end = BATSMsgFactory::parse_binary(&[0x06, 0x2D, 0x00, 0x00, 0x00, 0x00]);
+    let events = sessions.apply(&end, &mut books).unwrap();
+    assert!( matches!(events[..], [SessionEvent::Ended{ reason : EndReason::EndOfSession, .. }]) );
+    assert!( !sessions.in_session() && books.is_empty() );
+    assert_eq!( sessions.event(&FeedEvent::SessionComplete, &mut books).unwrap(), vec![] );
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!( summaries.len(), 2 );
+    assert!( summaries[0].1 && !summaries[1].1 );
+    assert_eq!( fs::read_to_string(dir.join("day-1.txt")).unwrap(), "first\n" );
+    assert_eq!( fs::read_to_string(dir.join("day-2.txt")).unwrap(), "second\n" );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn test_recovery() {
+    use binary::SequencedUnitHeader;
+    use book::BookManager;
+    use recovery::NeedReason;
+    use recovery::Recovery;
+    use recovery::RecoveryEvent;
+    use recovery::Snapshot;
+
+    let add = |i : u32| BATSMsgFactory::parse(&format!("28800168AID{:010}S000100AAPL  0001831900Y", i));
+    let mut recovery = Recovery::new(1);
+    let mut books = BookManager::new();
+
+    // held back until there's a snapshot
+    let exec = BATSMsgFactory::parse("28800169EID00000000010000601K27GA00010K");
+    assert_eq!( recovery.messages(10, 12, vec![(10, add(1)), (11, exec)], &mut books),
+                Some(RecoveryEvent::SnapshotNeeded{ unit : 1, reason : NeedReason::Startup }) );
+    assert_eq!( recovery.messages(12, 13, vec![(12, add(2))], &mut books), None );
+    assert_eq!( (recovery.buffered(), books.orders()), (3, 0) );
+
+    // the snapshot has 10 in it already
+    let spin = Snapshot{ sequence : 10, messages : vec![add(0), add(1)] };
+    assert_eq!( recovery.snapshot(spin, &mut books), Some(RecoveryEvent::Live{ unit : 1, sequence : 13, replayed : 2 }) );
+    assert_eq!( books.orders(), 3 );
+    assert_eq!( books.book("AAPL").unwrap().best_ask().map(|q| q.shares), Some(240) );
+
+    // 13 and 14 never come
+    assert_eq!( recovery.messages(15, 16, vec![(15, add(3))], &mut books),
+                Some(RecoveryEvent::SnapshotNeeded{ unit : 1, reason : NeedReason::Gap{ expected : 13, received : 15 } }) );
+    assert!( !recovery.is_live() );
+    let stale = Snapshot{ sequence : 12, messages : vec![add(0), add(1), add(2)] };
+    assert_eq!( recovery.snapshot(stale, &mut books),
+                Some(RecoveryEvent::SnapshotNeeded{ unit : 1, reason : NeedReason::SnapshotTooOld{ snapshot : 12, buffered_from : 15 } }) );
+    assert_eq!( books.orders(), 3 );
+    let spin = Snapshot{ sequence : 14, messages : vec![add(0)] };
+    assert_eq!( recovery.snapshot(spin, &mut books), Some(RecoveryEvent::Live{ unit : 1, sequence : 16, replayed : 1 }) );
+    assert_eq!( books.orders(), 2 );
+
+    // live packets, other units' ignored
+    let mut packet = SequencedUnitHeader{ length : 14, count : 1, unit : 1, sequence : 16 }.to_bytes();
+    packet.extend_from_slice(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00]);
+    assert_eq!( recovery.packet(&packet, &mut books), None );
+    assert_eq!( recovery.next_sequence(), Some(17) );
+    packet[3] = 2;
+    packet[4] = 30;
+    assert_eq!( recovery.packet(&packet, &mut books), None );
+    assert_eq!( recovery.messages(15, 16, vec![(15, add(3))], &mut books), None );
+    let stats = recovery.stats();
+    assert_eq!( (stats.recoveries, stats.gaps, stats.replayed, stats.duplicates), (2, 1, 3, 1) );
+
+    // a message that doesn't decode is held back as one, not taken for a gap
+    let mut recovery = Recovery::new(1);
+    let mut packet = SequencedUnitHeader{ length : 18, count : 2, unit : 1, sequence : 5 }.to_bytes();
+    packet.extend_from_slice(&[0x04, 0xEE, 0x00, 0x00, 0x06, 0x20, 0x80, 0x70, 0x00, 0x00]);
+    assert_eq!( recovery.packet(&packet, &mut books), Some(RecoveryEvent::SnapshotNeeded{ unit : 1, reason : NeedReason::Startup }) );
+    assert_eq!( recovery.buffered(), 2 );
+    let spin = Snapshot{ sequence : 4, messages : vec![add(0)] };
+    assert_eq!( recovery.snapshot(spin, &mut books), Some(RecoveryEvent::Live{ unit : 1, sequence : 7, replayed : 1 }) );
+}
+
+
+#[test]
+fn test_paced_replay() {
+    use std::time::Duration;
+    use std::time::Instant;
+    use replay::Paced;
+    use replay::Speed;
+
+    let lines = ["28800000A1K27GA00000YS000100AAPL  0001831900Y",
+                 "28800100E1K27GA00000Y0000401K27GA00010K",
+                 "28800200X1K27GA00000Y000010",
+                 // an hour on, past the allowance
+                 "32400200X1K27GA00000Y000010"];
+    let msgs = || lines.iter().map(|l| BATSMsgFactory::parse(l)).collect::<Vec<_>>().into_iter();
+
+    let started = Instant::now();
+    let paced = Paced::new(msgs(), Speed::Times(10.0)).unwrap().max_gap(Duration::from_secs(1));
+    assert_eq!( paced.count(), 4 );
+    let took = started.elapsed();
+    // 200ms and the second allowed of the hour, ten times as fast
+    assert!( took >= Duration::from_millis(120) && took < Duration::from_secs(1), "took {:?}", took );
+
+    let started = Instant::now();
+    let mut paced = Paced::new(msgs(), Speed::Max).unwrap();
+    assert_eq!( paced.by_ref().count(), 4 );
+    assert!( started.elapsed() < Duration::from_millis(25) );
+    assert_eq!( paced.time(), 32_400_200_000_000 );
+
+    // no speed that would divide by zero or never get there
+    for times in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+        assert!( Paced::new(msgs(), Speed::Times(times)).is_err() );
+    }
+    assert_eq!( Paced::new(msgs(), Speed::Times(-2.0)).err().map(|e| e.to_string()), Some(String::from("can't replay -2 times as fast")) );
+}
+
+
+#[test]
+fn test_throttle() {
+    use std::time::Duration;
+    use book::BookManager;
+    use bounded::EventSink;
+    use event::MarketEvent;
+    use throttle::BboThrottle;
+    use throttle::RateLimited;
+
+    let mut books = BookManager::new();
+    let mut bbos = BboThrottle::new(Duration::from_millis(100));
+    let mut apply = |line : &str| bbos.apply(&mut books, &BATSMsgFactory::parse(line));
+    let first = apply("28800000AID0000000001S000100AAPL  0001831900Y");
+    assert_eq!( (first.len(), first[0].bbo.ask.map(|q| q.shares)), (1, Some(100)) );
+    // held, then replaced by the later one
+    assert!( apply("28800010AID0000000002S000100AAPL  0001831900Y").is_empty() );
+    assert!( apply("28800020EID0000000002000040ID000000001K").is_empty() );
+    let next = apply("28800150AID0000000003B000100IBM   0001500000Y");
+    assert_eq!( next.iter().map(|u| (u.symbol.as_str(), u.timestamp)).collect::<Vec<_>>(),
+                vec![("IBM", 28_800_150_000_000), ("AAPL", 28_800_020_000_000)] );
+    assert_eq!( next[1].bbo.ask.map(|q| q.shares), Some(160) );
+    assert!( apply("28800160XID0000000001000010").is_empty() );
+    let held = bbos.flush();
+    assert_eq!( (held.len(), held[0].bbo.ask.map(|q| q.shares)), (1, Some(150)) );
+    let stats = bbos.stats();
+    assert_eq!( (stats.offered, stats.passed, stats.conflated), (5, 4, 1) );
+
+    let mut got = Vec::new();
+    {
+        let mut limited = RateLimited::new(|e : &MarketEvent| { got.push(e.timestamp()); Ok(()) }, 2, Duration::from_secs(1));
+        for &t in [0, 1_000_000, 2_000_000, 1_500_000_000].iter() {
+            limited.event(&MarketEvent::OrderDeleted{ timestamp : t, order_id : 1 }).unwrap();
+        }
+        assert_eq!( limited.dropped(), 1 );
+    }
+    assert_eq!( got, vec![0, 1_000_000, 1_500_000_000] );
+}
+
+
+#[test]
+fn test_recording() {
+    use std::io::Cursor;
+    use event::MarketEvent;
+    use recording::Recorder;
+    use recording::Recording;
+
+    let mut recorder = Recorder::new(Vec::new()).unwrap().index_every(3);
+    for i in 0..10 {
+        recorder.message(&BATSMsgFactory::parse(&format!("{}AID{:010}S000100AAPL  0001831900Y", 28_800_000 + i * 10, i))).unwrap();
+    }
+    // no event of its own
+    recorder.message(&BATSMsgFactory::parse_binary(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00])).unwrap();
+    assert_eq!( recorder.events(), 10 );
+    let bytes = recorder.into_inner().unwrap();
+
+    let recording = Recording::new(Cursor::new(bytes.clone())).unwrap();
+    assert_eq!( recording.index().iter().map(|e| e.0).collect::<Vec<_>>(),
+                vec![28_800_000_000_000, 28_800_030_000_000, 28_800_060_000_000, 28_800_090_000_000] );
+    let events : Vec<MarketEvent> = recording.map(Result::unwrap).collect();
+    assert_eq!( events.len(), 10 );
+    match events[4] {
+        MarketEvent::OrderAdded{ timestamp, shares, ref symbol, .. } => assert_eq!( (timestamp, shares, symbol.as_str()), (28_800_040_000_000, 100, "AAPL") ),
+        ref e => panic!("expected an add, got {:?}", e)
+    }
+
+    let mut recording = Recording::new(Cursor::new(bytes.clone())).unwrap();
+    recording.seek(28_800_065_000_000).unwrap();
+    let rest : Vec<u64> = recording.map(|e| e.unwrap().timestamp()).collect();
+    assert_eq!( rest, vec![28_800_070_000_000, 28_800_080_000_000, 28_800_090_000_000] );
+
+    // cut off before the end marker and the index, as a crash would leave it
+    let cut = bytes.len() - 2 - 4 * 16 - 20;
+    let recording = Recording::new(Cursor::new(bytes[..cut].to_vec())).unwrap();
+    assert!( recording.index().is_empty() );
+    assert_eq!( recording.count(), 10 );
+    assert!( Recording::new(Cursor::new(b"PITCH!".to_vec())).is_err() );
+}
+
+
+#[test]
+fn test_checkpoint() {
+    use std::fs;
+    use book::BookManager;
+    use checkpoint::Checkpointer;
+
+    let dir = ::std::env::temp_dir().join(format!("orderbook-checkpoint-{}", ::std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("books.ckpt");
+    let msgs : Vec<_> = (0..5).map(|i| BATSMsgFactory::parse(&format!("2880016{}AID{:010}{}000100AAPL  00018{}1900Y", i, i, if i % 2 == 0 { 'B' } else { 'S' }, 2 + i))).collect();
+
+    let mut books = BookManager::new();
+    let mut checkpoints = Checkpointer::open(&path, &mut books).unwrap().every(2);
+    assert!( !checkpoints.resumed() );
+    for msg in msgs.iter() {
+        checkpoints.apply(msg, &mut books).unwrap();
+    }
+    assert_eq!( checkpoints.saved(), 2 );
+
+    // after a crash, from the last save on
+    let mut restarted = BookManager::new();
+    let mut checkpoints = Checkpointer::open(&path, &mut restarted).unwrap().every(2);
+    assert!( checkpoints.resumed() );
+    assert_eq!( (checkpoints.position(), checkpoints.timestamp(), restarted.orders()), (4, 28_800_163_000_000, 4) );
+    for msg in msgs.iter().skip(checkpoints.position() as usize) {
+        checkpoints.apply(msg, &mut restarted).unwrap();
+    }
+    assert_eq!( restarted.snapshot(), books.snapshot() );
+    let id = |msg : &BATSMessage| match *msg { BATSMessage::AddOrderMsg(ref m) => m.order_id, _ => unreachable!() };
+    assert_eq!( restarted.symbol_of(id(&msgs[1])), Some("AAPL") );
+
+    // sequenced, what's in the checkpoint is passed over
+    let cancel = BATSMsgFactory::parse("28800170XID0000000000000040");
+    assert!( checkpoints.apply_sequenced(1, 10, &cancel, &mut restarted).unwrap() );
+    checkpoints.save(&restarted).unwrap();
+    let mut again = BookManager::new();
+    let mut checkpoints = Checkpointer::open(&path, &mut again).unwrap();
+    assert_eq!( checkpoints.next_sequence(1), Some(11) );
+    assert!( !checkpoints.apply_sequenced(1, 10, &cancel, &mut again).unwrap() );
+    assert!( checkpoints.apply_sequenced(1, 11, &cancel, &mut again).unwrap() );
+    assert_eq!( again.find_order(id(&msgs[0])).map(|o| o.shares), Some(20) );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn test_merge() {
+    use std::fs;
+    use event::MarketEvent;
+    use merge;
+
+    let dir = ::std::env::temp_dir().join(format!("orderbook-merge-{}", ::std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let unit = |times : &[u32]| times.iter().enumerate()
+        .map(|(i, t)| format!("{}AID{:010}S000100AAPL  0001831900Y\n", t, i)).collect::<String>();
+    fs::write(dir.join("a.txt"), unit(&[28_800_000, 28_800_020, 28_800_030])).unwrap();
+    fs::write(dir.join("b.txt"), unit(&[28_800_010, 28_800_020])).unwrap();
+    let sources = vec![merge::messages(dir.join("a.txt")).unwrap(), merge::messages(dir.join("b.txt")).unwrap()];
+    let merged : Vec<(usize, u64)> = merge::merge(sources).map(|(source, t, _)| (source, t / 1_000_000)).collect();
+    // a tie goes to the first source
+    assert_eq!( merged, vec![(0, 28_800_000), (1, 28_800_010), (0, 28_800_020), (1, 28_800_020), (0, 28_800_030)] );
+
+    let events = |times : Vec<u64>| times.into_iter().map(|t| MarketEvent::OrderDeleted{ timestamp : t, order_id : t }).collect::<Vec<_>>().into_iter();
+    let merged : Vec<u64> = merge::merge_by(vec![events(vec![5, 9]), events(vec![]), events(vec![1, 7, 8])], |_ : usize, e : &MarketEvent| e.timestamp())
+        .map(|(_, t, _)| t).collect();
+    assert_eq!( merged, vec![1, 5, 7, 8, 9] );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+
+#[test]
+fn test_matching_engine() {
+    use book::BookManager;
+    use book::Side;
+    use matching::MatchError;
+    use matching::MatchingEngine;
+    use matching::NewOrder;
+
+    let seed = ["28800168AID0000000001S000100AAPL  0001000000Y",
+                "28800169AID0000000002S000200AAPL  0001000000Y",
+                "28800170AID0000000003S000100AAPL  0001000100Y"];
+    let mut engine = MatchingEngine::new("AAPL").exec_ids_from(1000);
+    let mut books = BookManager::new();
+    let mut ids = Vec::new();
+    for line in seed.iter() {
+        let msg = BATSMsgFactory::parse(line);
+        if let BATSMessage::AddOrderMsg(ref m) = msg {
+            ids.push(m.order_id);
+        }
+        engine.apply(&msg);
+        books.apply(&msg);
+    }
+
+    // the first in line at the best price goes first
+    let report = engine.submit(NewOrder::limit(1, Side::Bid, 250, 1_000_000), 28_800_200).unwrap();
+    assert_eq!( report.fills.iter().map(|f| (f.exec_id, f.resting, f.price, f.shares)).collect::<Vec<_>>(),
+                vec![(1000, ids[0], 1_000_000, 100), (1001, ids[1], 1_000_000, 150)] );
+    assert_eq!( (report.filled(), report.rested, report.messages.len()), (250, 0, 2) );
+    for msg in report.messages.iter() {
+        books.apply(msg);
+    }
+
+    // through two levels, the rest resting
+    let report = engine.submit(NewOrder::limit(2, Side::Bid, 300, 1_000_100), 28_800_210).unwrap();
+    assert_eq!( report.fills.iter().map(|f| (f.price, f.shares)).collect::<Vec<_>>(), vec![(1_000_000, 50), (1_000_100, 100)] );
+    assert_eq!( (report.rested, report.cancelled), (150, 0) );
+    for msg in report.messages.iter() {
+        books.apply(msg);
+    }
+    assert_eq!( books.book("AAPL").unwrap().bbo(), engine.book().bbo() );
+    assert_eq!( engine.book().best_bid().map(|q| (q.price, q.shares)), Some((1_000_100, 150)) );
+    assert!( engine.book().best_ask().is_none() );
+
+    let report = engine.submit(NewOrder::market(3, Side::Ask, 1000), 28_800_220).unwrap();
+    assert_eq!( (report.filled(), report.rested, report.cancelled), (150, 0, 850) );
+    assert!( engine.book().best_bid().is_none() );
+
+    engine.submit(NewOrder::limit(4, Side::Ask, 10, 1_000_500), 28_800_230).unwrap();
+    assert_eq!( engine.submit(NewOrder::limit(4, Side::Ask, 10, 1_000_500), 28_800_230).unwrap_err(), MatchError::DuplicateId(4) );
+    // an IOC that doesn't cross leaves nothing
+    let report = engine.submit(NewOrder::limit(5, Side::Bid, 10, 1_000_000).ioc(), 28_800_240).unwrap();
+    assert_eq!( (report.filled(), report.cancelled, report.messages.len()), (0, 10, 0) );
+    assert!( engine.cancel(4, 28_800_250).is_ok() );
+    assert_eq!( engine.cancel(4, 28_800_250).unwrap_err(), MatchError::UnknownOrder(4) );
+    assert_eq!( engine.book().len(), 0 );
+}
+
+
+#[test]
+fn test_pro_rata_allocation() {
+    use book::Side;
+    use matching::Allocation;
+    use matching::MatchingEngine;
+    use matching::NewOrder;
+    use matching::PriceTime;
+    use matching::ProRata;
+
+    let queue = [(1, 100), (2, 300), (3, 600)];
+    assert_eq!( PriceTime.allocate(&queue, 350), vec![(1, 100), (2, 250)] );
+    // 10.5, 31.5 and 63 rounded down, the share left over to the first in line
+    assert_eq!( ProRata::new().allocate(&queue, 105), vec![(1, 11), (2, 31), (3, 63)] );
+    assert_eq!( ProRata::new().minimum(20).allocate(&queue, 105), vec![(1, 11), (2, 31), (3, 63)] );
+    assert_eq!( ProRata::new().minimum(40).allocate(&queue, 105), vec![(1, 42), (3, 63)] );
+    assert_eq!( ProRata::new().allocate(&queue, 2000), vec![(1, 100), (2, 300), (3, 600)] );
+
+    let mut engine = MatchingEngine::new("ES").allocation(ProRata::new());
+    for (id, shares) in queue.iter() {
+        engine.submit(NewOrder::limit(*id, Side::Bid, *shares, 58_000_000), 0).unwrap();
+    }
+    let report = engine.submit(NewOrder::market(9, Side::Ask, 105), 1).unwrap();
+    assert_eq!( report.fills.iter().map(|f| (f.resting, f.shares)).collect::<Vec<_>>(), vec![(1, 11), (2, 31), (3, 63)] );
+    assert_eq!( engine.book().best_bid().map(|q| (q.shares, q.orders)), Some((895, 3)) );
+}
+
+
+#[test]
+fn test_fill_simulator() {
+    use backtest::FillSimulator;
+    use book::Side;
+
+    let mut sim = FillSimulator::new();
+    let feed = |sim : &mut FillSimulator, line : &str| { sim.apply(&BATSMsgFactory::parse(line)); };
+    feed(&mut sim, "28800100AID0000000001S000100AAPL  0001000000Y");
+    feed(&mut sim, "28800110AID0000000002S000100AAPL  0001000000Y");
+    // behind the 200 shares there
+    let ours = sim.place("AAPL", Side::Ask, 1_000_000, 150);
+    assert!( sim.fills().is_empty() );
+    feed(&mut sim, "28800200EID0000000001000100ID000000001K");
+    feed(&mut sim, "28800210EID0000000002000060ID000000002K");
+    feed(&mut sim, "28800220XID0000000002000040");
+    assert!( sim.fills().is_empty() );
+    // an order behind ours executing would have been ours
+    feed(&mut sim, "28800300AID0000000004S000100AAPL  0001000000Y");
+    feed(&mut sim, "28800310EID0000000004000070ID000000003K");
+    // a trade above our offer would have been ours too
+    feed(&mut sim, "28800400AID0000000005S000100AAPL  0001000100Y");
+    feed(&mut sim, "28800410EID0000000005000050ID000000004K");
+    assert_eq!( sim.fills().iter().map(|f| (f.order, f.timestamp / 1_000_000, f.price, f.shares, f.aggressive)).collect::<Vec<_>>(),
+                vec![(ours, 28_800_310, 1_000_000, 70, false), (ours, 28_800_410, 1_000_000, 50, false)] );
+    assert_eq!( (sim.filled(ours), sim.remaining(ours)), (120, 30) );
+    assert!( sim.cancel(ours) );
+    assert_eq!( sim.remaining(ours), 0 );
+
+    // through the offers: 30 left at 100.00 then 50 at 100.01
+    let buy = sim.place("AAPL", Side::Bid, 1_000_100, 60);
+    assert_eq!( sim.fills()[2..].iter().map(|f| (f.price, f.shares, f.aggressive)).collect::<Vec<_>>(),
+                vec![(1_000_000, 30, true), (1_000_100, 30, true)] );
+    assert_eq!( sim.average_price(buy), Some((60, 1_000_050.0)) );
+    assert!( !sim.cancel(buy) );
+}
+
+
+#[test]
+fn test_fill_simulator_latency() {
+    use backtest::FillSimulator;
+    use backtest::Latency;
+    use book::Side;
+    use std::time::Duration;
+
+    let feed = |sim : &mut FillSimulator, line : &str| { sim.apply(&BATSMsgFactory::parse(line)); };
+    let mut sim = FillSimulator::new().order_entry_latency(Latency::Fixed(Duration::from_millis(5)));
+    feed(&mut sim, "28800100AID0000000001S000100AAPL  0001000000Y");
+    let ours = sim.place("AAPL", Side::Bid, 1_000_000, 100);
+    assert_eq!( sim.arrived(ours), None );
+    // the offer's gone by the time ours gets there, so it rests instead
+    feed(&mut sim, "28800102EID0000000001000100ID000000001K");
+    feed(&mut sim, "28800110AID0000000002B000100AAPL  0000999900Y");
+    assert_eq!( sim.arrived(ours), Some(28_800_105_000_000) );
+    assert!( sim.fills().is_empty() );
+    // trades below our bid fill it until the cancel lands
+    assert!( sim.cancel(ours) );
+    assert!( !sim.cancel(ours) );
+    feed(&mut sim, "28800112EID0000000002000030ID000000002K");
+    assert_eq!( sim.remaining(ours), 70 );
+    feed(&mut sim, "28800120EID0000000002000030ID000000003K");
+    assert_eq!( (sim.filled(ours), sim.remaining(ours)), (30, 0) );
+
+    // drawn the same way for the same seed
+    let arrivals = |seed| {
+        let mut sim = FillSimulator::new().seed(seed).market_data_latency(Latency::Uniform{ min : Duration::from_micros(100), max : Duration::from_micros(900) })
+                                                      .order_entry_latency(Latency::Normal{ mean : Duration::from_micros(50), sd : Duration::from_micros(20) });
+        let orders : Vec<usize> = (0..20).map(|_| sim.place("AAPL", Side::Bid, 1_000_000, 100)).collect();
+        feed(&mut sim, "28801000AID0000000001S000100AAPL  0001000000Y");
+        orders.iter().map(|&o| sim.arrived(o).unwrap()).collect::<Vec<u64>>()
+    };
+    let first = arrivals(7);
+    assert_eq!( first, arrivals(7) );
+    assert_ne!( first, arrivals(8) );
+    assert!( first.iter().all(|t| (100_000..2_000_000).contains(t)) );
+}
+
+
+#[test]
+fn test_fill_simulator_fees() {
+    use backtest::FeeModel;
+    use backtest::FillSimulator;
+    use backtest::MakerTaker;
+    use backtest::SimFill;
+    use backtest::Tiered;
+    use book::Side;
+
+    // sells 100 resting, then buys 50 back taking an offer at 100.005
+    let run = |mut sim : FillSimulator| {
+        let feed = |sim : &mut FillSimulator, line : &str| { sim.apply(&BATSMsgFactory::parse(line)); };
+        feed(&mut sim, "28800100AID0000000001S000100AAPL  0001000000Y");
+        let sell = sim.place("AAPL", Side::Ask, 1_000_000, 100);
+        feed(&mut sim, "28800200EID0000000001000100ID000000001K");
+        feed(&mut sim, "28800300AID0000000002S000100AAPL  0001000000Y");
+        feed(&mut sim, "28800310EID0000000002000100ID000000002K");
+        feed(&mut sim, "28800400AID0000000003S000100AAPL  0001000050Y");
+        let buy = sim.place("AAPL", Side::Bid, 1_000_100, 50);
+        (sim, sell, buy)
+    };
+
+    let (sim, sell, buy) = run(FillSimulator::new().fee_model(MakerTaker::new(-20.0, 30.0)));
+    assert_eq!( sim.fills().iter().map(|f| (f.order, f.shares, f.aggressive)).collect::<Vec<_>>(), vec![(sell, 100, false), (buy, 50, true)] );
+    assert_eq!( (sim.fees(sell), sim.fees(buy), sim.total_fees()), (-2000.0, 1500.0, -500.0) );
+    assert_eq!( sim.position("AAPL  "), -50 );
+    assert_eq!( sim.gross_pnl("AAPL", 1_000_000), -2500.0 );
+    assert_eq!( sim.net_pnl("AAPL", 1_000_000), -2000.0 );
+    assert_eq!( (sim.position("MSFT"), sim.net_pnl("MSFT", 1)), (0, 0.0) );
+
+    // the buy's in the better tier once the sell's done
+    let tiers = Tiered::new(MakerTaker::new(-20.0, 30.0)).tier(100, MakerTaker::new(-30.0, 25.0));
+    let (sim, sell, buy) = run(FillSimulator::new().fee_model(tiers));
+    assert_eq!( (sim.fees(sell), sim.fees(buy)), (-2000.0, 1250.0) );
+    let mut started = Tiered::new(MakerTaker::new(-20.0, 30.0)).tier(100, MakerTaker::new(-30.0, 25.0)).traded(500);
+    assert_eq!( started.fee(&sim.fills()[0]), -3000.0 );
+    assert_eq!( started.volume(), 600 );
+
+    let (sim, _, _) = run(FillSimulator::new().fee_model(|_ : &SimFill| 1.0));
+    assert_eq!( sim.total_fees(), 2.0 );
+    let (sim, _, _) = run(FillSimulator::new());
+    assert_eq!( sim.net_pnl("AAPL", 1_000_000), sim.gross_pnl("AAPL", 1_000_000) );
+}
+
+
+#[test]
+fn test_stop_orders() {
+    use book::Side;
+    use matching::MatchError;
+    use matching::MatchingEngine;
+    use matching::NewOrder;
+
+    let mut engine = MatchingEngine::new("AAPL");
+    for (id, price) in [(11, 1_000_000), (12, 1_000_100), (13, 1_000_200), (14, 1_000_500)] {
+        engine.submit(NewOrder::limit(id, Side::Ask, 100, price), 28_800_100).unwrap();
+    }
+    let stops = [NewOrder::stop(21, Side::Bid, 50, 1_000_100),
+                 NewOrder::stop_limit(22, Side::Bid, 100, 1_000_000, 1_000_100),
+                 NewOrder::stop(23, Side::Ask, 100, 990_000),
+                 NewOrder::stop(24, Side::Bid, 10, 1_000_500),
+                 NewOrder::stop(25, Side::Bid, 10, 1_000_300)];
+    for stop in stops.iter() {
+        let report = engine.submit(*stop, 28_800_110).unwrap();
+        assert_eq!( (report.held, report.messages.len()), (stop.shares, 0) );
+    }
+    assert_eq!( engine.submit(NewOrder::limit(23, Side::Bid, 10, 900_000), 28_800_120).unwrap_err(), MatchError::DuplicateId(23) );
+
+    // the print at 100.00 sets off 22, whose print at 100.01 sets off 21
+    let report = engine.submit(NewOrder::limit(1, Side::Bid, 100, 1_000_000), 28_800_200).unwrap();
+    assert_eq!( report.fills.iter().map(|f| (f.price, f.shares)).collect::<Vec<_>>(), vec![(1_000_000, 100)] );
+    assert_eq!( report.triggered.iter().map(|t| (t.order_id, t.fills.iter().map(|f| (f.price, f.shares)).collect::<Vec<_>>())).collect::<Vec<_>>(),
+                vec![(22, vec![(1_000_100, 100)]), (21, vec![(1_000_200, 50)])] );
+    assert_eq!( report.messages.len(), 3 );
+    assert_eq!( engine.stops().iter().map(|s| s.order_id).collect::<Vec<_>>(), vec![23, 24, 25] );
+    assert_eq!( engine.last_trade(), Some(1_000_200) );
+
+    // both reached by the print at 100.05, so in time priority
+    let report = engine.submit(NewOrder::market(2, Side::Bid, 100), 28_800_300).unwrap();
+    assert_eq!( report.fills.iter().map(|f| (f.price, f.shares)).collect::<Vec<_>>(), vec![(1_000_200, 50), (1_000_500, 50)] );
+    assert_eq!( report.triggered.iter().map(|t| (t.order_id, t.fills.len(), t.cancelled)).collect::<Vec<_>>(), vec![(24, 1, 0), (25, 1, 0)] );
+    assert_eq!( engine.book().best_ask().map(|q| q.shares), Some(30) );
+
+    // already through its trigger
+    let report = engine.submit(NewOrder::stop(30, Side::Bid, 10, 1_000_000), 28_800_400).unwrap();
+    assert_eq!( (report.held, report.filled()), (0, 10) );
+    // a stop limit resting once it's set off
+    assert_eq!( engine.submit(NewOrder::stop_limit(31, Side::Ask, 500, 1_000_000, 999_900), 28_800_410).unwrap().held, 500 );
+    engine.submit(NewOrder::limit(3, Side::Bid, 40, 1_000_000), 28_800_420).unwrap();
+    let report = engine.submit(NewOrder::market(4, Side::Ask, 10), 28_800_430).unwrap();
+    assert_eq!( report.triggered.iter().map(|t| (t.order_id, t.fills.len(), t.rested)).collect::<Vec<_>>(), vec![(31, 1, 470)] );
+    assert_eq!( engine.book().best_ask().map(|q| (q.price, q.shares)), Some((999_900, 470)) );
+
+    assert_eq!( engine.cancel_stop(23).map(|s| s.shares), Ok(100) );
+    assert_eq!( engine.cancel_stop(23).unwrap_err(), MatchError::UnknownOrder(23) );
+    assert!( engine.stops().is_empty() );
+}
+
+
+#[test]
+fn test_synthetic_feed() {
+    use std::collections::HashMap;
+    use book::BookManager;
+    use synthetic::Generator;
+    use synthetic::SyntheticConfig;
+
+    let config = SyntheticConfig::new().symbol("AAPL", 1_831_900).symbol("MSFT", 4_102_500).rate(5_000.0).max_orders(100).seed(3);
+    let mut books = BookManager::new().check_consistency(true);
+    let mut kinds = HashMap::new();
+    let mut last = 0;
+    for msg in Generator::new(config.clone()).take(50_000) {
+        // and back through the text feed
+        let line = msg.to_pitch_string().unwrap();
+        let msg = BATSMsgFactory::parse(&line);
+        *kinds.entry(msg.type_name()).or_insert(0) += 1;
+        let ts = TimestampComposer::new().compose(&msg);
+        assert!( ts >= last );
+        last = ts;
+        books.apply(&msg);
+        for symbol in ["AAPL", "MSFT"] {
+            if let Some(book) = books.book(symbol) {
+                if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+                    assert!( bid.price < ask.price, "{} crossed by {}", symbol, line );
+                }
+                assert!( book.len() <= 100 );
+            }
+        }
+    }
+    assert!( books.inconsistencies().is_empty(), "{:?}", &books.inconsistencies()[..1] );
+    assert_eq!( kinds.len(), 4 );
+    // ten seconds or so at 5,000 a second
+    assert!( (34_200_000_000_000..34_212_000_000_000).contains(&last) );
+
+    // the same every time for a seed
+    let first : Vec<String> = Generator::new(config.clone()).take(1000).map(|m| m.to_pitch_string().unwrap()).collect();
+    assert_eq!( first, Generator::new(config.clone()).take(1000).map(|m| m.to_pitch_string().unwrap()).collect::<Vec<_>>() );
+    assert_ne!( first, Generator::new(config.seed(4)).take(1000).map(|m| m.to_pitch_string().unwrap()).collect::<Vec<_>>() );
+    assert_eq!( Generator::new(SyntheticConfig::new()).next().map(|m| m.type_name()), None );
+}
+
+
+#[test]
+fn test_feed_skips_undecoded() {
+    use std::io;
+
+    // a type we don't decode, a Time message cut short, then a whole one
+    let packet = encode_packet(&[vec![0x06, 0x99, 0, 0, 0, 0], vec![0x04, 0x20, 0x80, 0x70], vec![0x06, 0x20, 0x80, 0x70, 0, 0]]);
+    let events = decode_packet(&packet).unwrap();
+    assert_eq!( events.len(), 1 );
+    match events[0] {
+        FeedEvent::Message(BATSMessage::TimeMsg(ref m)) => assert_eq!( m.time, 0x7080 ),
+        ref e => panic!("expected the time message, got {:?}", e)
+    }
+
+    let data = "288\n28800168ZID0000000001\n28800168AID0000000001S000100AAPL  0001831900Y\n";
+    let mut reader = PitchReader::new(data.as_bytes());
+    let events : Vec<FeedEvent> = reader.by_ref().collect();
+    assert_eq!( events.len(), 2 );
+    assert!( matches!(events[0], FeedEvent::Message(BATSMessage::AddOrderMsg(_))) );
+    assert_eq!( reader.skipped(), 2 );
+
+    // a line that isn't UTF-8 is skipped like any other, the session goes on past it
+    let add = "28800168AID0000000001S000100AAPL  0001831900Y\n";
+    let data = [add.as_bytes(), b"\xff\xfe\r\n", add.as_bytes()].concat();
+    let mut reader = PitchReader::new(&data[..]);
+    let events : Vec<FeedEvent> = reader.by_ref().collect();
+    assert_eq!( events.len(), 3 );
+    assert!( matches!(events[1], FeedEvent::Message(BATSMessage::AddOrderMsg(_))) );
+    assert!( matches!(events[2], FeedEvent::SessionComplete) );
+    assert_eq!( (reader.skipped(), reader.error().is_none()), (1, true) );
+
+    // and a read error isn't taken for the end of the session
+    struct Broken;
+    impl io::Read for Broken {
+        fn read( &mut self, _ : &mut [u8] ) -> io::Result<usize> {
+            Err(io::Error::other("gone"))
+        }
+    }
+    let mut reader = PitchReader::new(io::BufReader::new(io::Read::chain(add.as_bytes(), Broken)));
+    let events : Vec<FeedEvent> = reader.by_ref().collect();
+    assert_eq!( events.len(), 1 );
+    assert!( matches!(events[0], FeedEvent::Message(_)) );
+    assert_eq!( reader.error().map(|e| e.to_string()), Some(String::from("gone")) );
+}
+
+
+#[test]
+fn test_unit_clear() {
+    use book::BookManager;
+
+    let clear = BATSMessage::UnitClearMsg(UnitClearMsg{ time_offset : 0, msg_type : 0x97 });
+    let mut books = BookManager::new();
+    books.apply_unit(1, &BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y"));
+    books.apply_unit(1, &BATSMsgFactory::parse("28800168A1K27GA00001YB000100AAPL  0001831800Y"));
+    books.apply_unit(2, &BATSMsgFactory::parse("28800168A1K27GA00002YS000100MSFT  0000500000Y"));
+    books.apply(&BATSMsgFactory::parse("28800168A1K27GA00003YS000100IBM   0001000000Y"));
+    assert_eq!( books.orders(), 4 );
+
+    // only unit 1's books go, and their orders with them.
+    assert!( books.apply_unit(1, &clear) );
+    assert_eq!( books.symbols(), vec!["IBM", "MSFT"] );
+    assert_eq!( books.orders(), 2 );
+    assert!( !books.apply_unit(1, &clear) );
+
+    // a later add to the cleared id starts afresh.
+    books.apply_unit(1, &BATSMsgFactory::parse("28800169A1K27GA00000YS000200AAPL  0001832000Y"));
+    assert_eq!( books.book("AAPL").unwrap().best_ask().map(|q| (q.price, q.shares)), Some((1832000, 200)) );
+
+    // plain apply() is a unit of its own.
+    assert!( books.apply(&clear) );
+    assert_eq!( books.symbols(), vec!["AAPL", "MSFT"] );
+    assert_eq!( books.orders(), 2 );
+}
+
+
+#[test]
+fn test_binary_execution_timestamps() {
+    use binary::OrderExecutedAtPriceSizeMsg;
+    use binary::TimeMsg;
+    use book::BookManager;
+
+    // each takes 10 of the 100 shares.
+    let at_price = |time_offset, order_id, exec_id : u64| BATSMessage::OrderExecutedAtPriceSizeMsg(OrderExecutedAtPriceSizeMsg{
+        time_offset, msg_type : 0x24, order_id, shares : 10, remaining_shares : 100 - 10 * (exec_id as u32 - 10), exec_id, price : 1831800 });
+    let add = BATSMsgFactory::parse("28800168A1K27GA00000YS000100AAPL  0001831900Y");
+    let id = match add { BATSMessage::AddOrderMsg(ref m) => m.order_id, _ => panic!() };
+    let mut books = BookManager::new();
+    books.apply(&add);
+    books.apply(&BATSMessage::TimeMsg(TimeMsg{ msg_type : 0x20, time : 34200 }));
+    books.apply(&at_price(500, id, 11));
+    assert_eq!( books.trades().execution(11).unwrap().timestamp, 34_200_000_000_500 );
+
+    // each unit keeps its own time.
+    books.apply_unit(2, &BATSMessage::TimeMsg(TimeMsg{ msg_type : 0x20, time : 34201 }));
+    books.apply_unit(2, &at_price(600, id, 12));
+    books.apply(&at_price(700, id, 13));
+    assert_eq!( books.trades().execution(12).unwrap().timestamp, 34_201_000_000_600 );
+    assert_eq!( books.trades().execution(13).unwrap().timestamp, 34_200_000_000_700 );
+
+    // and so does a violation.
+    books.apply(&BATSMsgFactory::parse("28800169HAAPL    H0XY"));
+    books.apply(&at_price(900, id, 14));
+    assert_eq!( books.violations()[0].timestamp, 34_200_000_000_900 );
+}
+
+
+#[test]
+fn test_binary_order_messages() {
+    use binary::BinaryAddOrderMsg;
+    use binary::ModifyOrderMsg;
+
+    // add long: order 1, buy 300 AAPL at 183.18, displayed
+    let mut add = vec![34, 0x21, 0xe8, 0x03, 0x00, 0x00, 1, 0, 0, 0, 0, 0, 0, 0, b'B', 0x2c, 0x01, 0x00, 0x00];
+    add.extend_from_slice(b"AAPL  ");
+    add.extend_from_slice(&1831800u64.to_le_bytes());
+    add.push(1);
+    let msg : Option<BinaryAddOrderMsg> = BATSMsgFactory::parse_binary(&add).into();
+    let msg = msg.unwrap();
+    assert_eq!( (msg.time_offset, msg.order_id, msg.side, msg.shares, msg.symbol.as_str(), msg.price, msg.display),
+                (1000, 1, 'B', 300, "AAPL  ", 1831800, 'Y') );
+
+    // short forms carry 2 decimal prices and 16 bit shares.
+    let mut short = vec![26, 0x22, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, b'S', 0x64, 0x00];
+    short.extend_from_slice(b"AAPL  ");
+    short.extend_from_slice(&18320u16.to_le_bytes());
+    short.push(0);
+    let msg : Option<BinaryAddOrderMsg> = BATSMsgFactory::parse_binary(&short).into();
+    let msg = msg.unwrap();
+    assert_eq!( (msg.shares, msg.price, msg.display), (100, 1832000, 'N') );
+    let modify = vec![19, 0x28, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0x32, 0x00, 0x8f, 0x47, 1];
+    let msg : Option<ModifyOrderMsg> = BATSMsgFactory::parse_binary(&modify).into();
+    let msg = msg.unwrap();
+    assert_eq!( (msg.order_id, msg.shares, msg.price, msg.display), (2, 50, 1831900, 'Y') );
+
+    let mut expanded = vec![40, 0x2f, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, b'B', 0x0a, 0x00, 0x00, 0x00];
+    expanded.extend_from_slice(b"BRK.A   ");
+    expanded.extend_from_slice(&6000000000u64.to_le_bytes());
+    expanded.push(1);
+    expanded.extend_from_slice(b"MPID");
+    let executed = [vec![26, 0x23, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0x64, 0, 0, 0], 9u64.to_le_bytes().to_vec()].concat();
+    let delete = vec![14, 0x29, 0x10, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0];
+    let mut trade = vec![33, 0x2b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, b'B', 0x0a, 0x00];
+    trade.extend_from_slice(b"AAPL  ");
+    trade.extend_from_slice(&18319u16.to_le_bytes());
+    trade.extend_from_slice(&10u64.to_le_bytes());
+    let mut long_trade = vec![41, 0x2a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, b'S', 0x0a, 0x00, 0x00, 0x00];
+    long_trade.extend_from_slice(b"AAPL  ");
+    long_trade.extend_from_slice(&1831950u64.to_le_bytes());
+    long_trade.extend_from_slice(&11u64.to_le_bytes());
+    let mut long_modify = vec![27, 0x27, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0xc8, 0, 0, 0];
+    long_modify.extend_from_slice(&1831700u64.to_le_bytes());
+    long_modify.push(1);
+    for bytes in &[&add, &short, &modify, &expanded, &executed, &delete, &trade, &long_trade, &long_modify] {
+        assert_eq!( bytes[0] as usize, bytes.len() );
+        assert_eq!( &BATSMsgFactory::parse_binary(bytes).to_binary().unwrap(), *bytes );
+    }
+    // cut short they're errors, not panics.
+    assert!( BATSMsgFactory::try_parse_binary(&add[..20]).is_err() );
+}
+
+
+#[test]
+fn test_spill_round_trip() {
+    use bounded;
+    use bounded::Backpressure;
+    #[cfg(feature = "europe")]
+    use europe::TradeReportMsg;
+
+    // fields too wide for their PITCH columns and messages with no wire form come back as they went
+    let msgs = || vec![
+        BATSMessage::AddOrderMsg(AddOrderMsg{ timestamp : 28800168, msg_type : 'A', order_id : u64::MAX, side : 'S',
+                                              shares : 12_345_678, symbol : String::from("LONGSYMBOL"), price : u64::MAX,
+                                              display : 'Y', part_id : String::from("WIDEST") }),
+        BATSMessage::TradingStatusMsg(TradingStatusMsg{ timestamp : 28800168, msg_type : 'H', symbol : String::from("AAPL"),
+                                                        halt_status : 'H', reg_sho_action : 0, reserved1 : 'X', reserved2 : 'Y',
+                                                        halt_reason : Some(HaltReason::MarketWideCircuitBreaker(2)),
+                                                        halt_flag : None }),
+        BATSMessage::LoginResponseMsg(LoginResponseMsg{ msg_type : 0x02, status : LoginStatus::Unknown('é') }),
+        BATSMsgFactory::parse_binary(&[0x06, 0x20, 0x80, 0x70, 0x00, 0x00]),
+        #[cfg(feature = "europe")]
+        BATSMessage::TradeReportMsg(TradeReportMsg{ timestamp : 28800168, msg_type : 'R', exec_id : 7, shares : 100,
+                                                    symbol : String::from("VOD.L"), price : 1_234_500,
+                                                    currency : String::from("GBX"), flags : 'N' })
+    ];
+    let (tx, rx) = bounded::channel(1, Backpressure::Spill(::std::env::temp_dir()));
+    for msg in msgs() {
+        tx.send(msg).unwrap();
+    }
+    assert_eq!( rx.stats().spilled, msgs().len() as u64 - 1 );
+    for msg in msgs() {
+        assert_eq!( format!("{:?}", rx.recv().unwrap().unwrap()), format!("{:?}", msg) );
+    }
+}
+
+
+#[test]
+fn test_book_source() {
+    use std::sync::RwLock;
+    use book::BookManager;
+    use book::Side;
+    use query::BookSource;
+
+    let books = RwLock::new(BookManager::new());
+    for line in &["28800168A1K27GA00000YS000100AAPL  0001831900Y",
+                  "28800169A1K27GA00001YB000200AAPL  0001831700Y",
+                  "28800171P1K27GA00009YB000300AAPL  00018319001K27GA00000Z"] {
+        books.write().unwrap().apply(&BATSMsgFactory::parse(line));
+    }
+    // more shares at a level than a u32 holds
+    for order_id in 10..12 {
+        books.write().unwrap().book_mut("AAPL").unwrap().add_order(order_id, Side::Bid, 1831800, u32::MAX);
+    }
+    let levels = books.levels("AAPL", 1).unwrap();
+    assert_eq!( (levels.timestamp, levels.bids, levels.asks), (28_800_171_000_000, vec![(1831800, 2 * u64::from(u32::MAX))], vec![(1831900, 100)]) );
+    assert_eq!( books.levels("AAPL", 0).unwrap().bids.len(), 2 );
+    assert_eq!( books.levels("MSFT", 0), None );
+    let stats = books.stats("AAPL").unwrap();
+    assert_eq!( (stats.orders, stats.bid_levels, stats.ask_levels, stats.trades, stats.last_price), (4, 2, 1, 1, Some(1831900)) );
+    assert_eq!( books.symbols(), vec![String::from("AAPL")] );
+}
+
+
+#[test]
+fn test_binary_consumers() {
+    use bars::BarBuilder;
+    use bars::BarInterval;
+    use book::BookManager;
+    use book::OrderBook;
+    use book::Side;
+    use consistency::Problem;
+    use lifetime::OrderLifetimes;
+    use metrics::Metrics;
+    use queue::QueuePosition;
+    use selfmatch::SelfMatchDetector;
+    use selfmatch::SelfMatchKind;
+    use vwap::VwapTracker;
+    use vwap::VwapWindow;
+
+    let add = |offset : u32, id : u64, side : u8, shares : u32, price : u64| {
+        let mut add = vec![40, 0x2f];
+        add.extend_from_slice(&offset.to_le_bytes());
+        add.extend_from_slice(&id.to_le_bytes());
+        add.push(side);
+        add.extend_from_slice(&shares.to_le_bytes());
+        add.extend_from_slice(b"AAPL    ");
+        add.extend_from_slice(&price.to_le_bytes());
+        add.push(1);
+        add.extend_from_slice(b"BAML");
+        add
+    };
+    let delete = |id : u64| [vec![14, 0x29, 0x00, 0x5e, 0xd0, 0xb2], id.to_le_bytes().to_vec()].concat();
+    let mut modify = vec![27, 0x27, 0x00, 0xf9, 0x02, 0x95, 2, 0, 0, 0, 0, 0, 0, 0, 0x64, 0, 0, 0];
+    modify.extend_from_slice(&1831700u64.to_le_bytes());
+    modify.push(1);
+    let mut trade = vec![41, 0x2a, 0x00, 0x28, 0x6b, 0xee, 0, 0, 0, 0, 0, 0, 0, 0, b'S', 0xc8, 0x00, 0x00, 0x00];
+    trade.extend_from_slice(b"AAPL  ");
+    trade.extend_from_slice(&1832000u64.to_le_bytes());
+    trade.extend_from_slice(&10u64.to_le_bytes());
+    // at 8:00, two BAML orders, the bid partly filled at 2s, the offer moved through it at 2.5s,
+    // the bid deleted then an unknown order at 3s and a trade at 4s.
+    let msgs : Vec<BATSMessage> = [vec![6, 0x20, 0x80, 0x70, 0, 0],
+                                   add(0, 1, b'B', 300, 1831800),
+                                   add(1000, 2, b'S', 100, 1831900),
+                                   [vec![26, 0x23, 0x00, 0x94, 0x35, 0x77, 1, 0, 0, 0, 0, 0, 0, 0, 0x64, 0, 0, 0],
+                                    9u64.to_le_bytes().to_vec()].concat(),
+                                   modify,
+                                   delete(1),
+                                   delete(99),
+                                   trade].iter().map(|m| BATSMsgFactory::parse_binary(m)).collect();
+
+    let mut manager = BookManager::new().check_consistency(true);
+    let mut vwap = VwapTracker::new(VwapWindow::Session);
+    let mut bars = BarBuilder::new(BarInterval::Minute);
+    let metrics = Metrics::new();
+    let mut lifetimes = OrderLifetimes::new();
+    let mut detector = SelfMatchDetector::new(5_000_000_000);
+    let mut book = OrderBook::new();
+    let mut queue = None;
+    let mut hits = Vec::new();
+    for (i, msg) in msgs.iter().enumerate() {
+        if i == 3 {
+            queue = Some(QueuePosition::join(&book, 3, Side::Ask, 1831900, 50));
+        }
+        if let Some(ref mut queue) = queue {
+            queue.on_message(i as u64, &book, msg);
+        }
+        book.apply(msg);
+        manager.apply(msg);
+        vwap.apply(msg);
+        bars.apply(msg);
+        metrics.record_message(msg);
+        lifetimes.apply(msg);
+        hits.push(detector.apply(msg));
+    }
+
+    // the delete for an order that isn't there is stamped with its composed time.
+    let found = manager.inconsistencies();
+    assert_eq!( found.len(), 1 );
+    assert_eq!( (found[0].timestamp, found[0].msg_type, found[0].order_id, &found[0].problem),
+                (28_803_000_000_000, ')', 99, &Problem::UnknownOrder) );
+    // the binary trade's counted everywhere trades are.
+    assert_eq!( vwap.vwap("AAPL"), Some(1832000.0) );
+    assert_eq!( vwap.volume("AAPL"), 200 );
+    assert_eq!( bars.current("AAPL").map(|b| (b.close, b.volume)), Some((1832000, 200)) );
+    assert_eq!( metrics.snapshot().trade_volume.get("AAPL"), Some(&200) );
+    // the delete ends the bid, the modified offer's still live.
+    let stats = lifetimes.symbol_stats("AAPL").unwrap();
+    assert_eq!( (stats.filled, stats.cancelled), (0, 1) );
+    assert_eq!( lifetimes.live(), 1 );
+    // the offer moving through the bid is a crossing, and takes it out of the queue ahead.
+    let crossing = hits[4].as_ref().unwrap();
+    assert_eq!( crossing.kind, SelfMatchKind::Crossing );
+    assert_eq!( (crossing.buy_order, crossing.sell_order), (1, 2) );
+    assert!( hits.iter().enumerate().all(|(i, hit)| i == 4 || hit.is_none()) );
+    assert_eq!( queue.unwrap().shares_ahead(), 0 );
+}
+
 #[test]
 fn test_example() {
 
 
-}
\ No newline at end of file
+}
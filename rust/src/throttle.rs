@@ -0,0 +1,211 @@
+
+// Slowing the output down for consumers that can't take every change, eg. a UI: Conflator
+// passes on at most one value per key per interval and always the latest, BboThrottle does
+// that with each symbol's best bid and offer, and RateLimited caps how many events a sink gets.
+//
+//   let mut bbos = BboThrottle::new(Duration::from_millis(250));
+//   for msg in msgs {
+//       for update in bbos.apply(&mut books, &msg) { ui.send(&update)?; }
+//   }
+//   for update in bbos.flush() { ui.send(&update)?; }
+//
+// Times are nanoseconds on whatever clock the caller keeps, the feed's (past midnight) in
+// BboThrottle and RateLimited, so a replay throttles the same however fast it runs. Nothing's
+// emitted on a timer: what's held back goes out with the next call past its interval, or at
+// flush().
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+use binary::TimestampComposer;
+use book::Bbo;
+use book::BookManager;
+use bounded::EventSink;
+use event::MarketEvent;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleStats {
+    pub offered    : u64,
+    pub passed     : u64,
+    // replaced by a later value before they could go
+    pub conflated  : u64
+}
+
+#[derive(Debug)]
+struct Slot<V> {
+    sent    : Option<u64>,
+    pending : Option<V>
+}
+
+impl<V> Slot<V> {
+    fn ready( &self, now : u64, interval : u64 ) -> bool {
+        self.sent.is_none_or(|t| now >= t.saturating_add(interval))
+    }
+}
+
+#[derive(Debug)]
+pub struct Conflator<K : Ord, V> {
+    interval : u64,
+    slots    : BTreeMap<K, Slot<V>>,
+    stats    : ThrottleStats
+}
+
+impl<K : Ord + Clone, V> Conflator<K, V> {
+    pub fn new( interval : Duration ) -> Conflator<K, V> {
+        Conflator{ interval : interval.as_nanos() as u64, slots : BTreeMap::new(), stats : ThrottleStats::default() }
+    }
+
+    // the value back if it can go now, otherwise held (in place of any held before) until the
+    // key's interval is up.
+    pub fn offer( &mut self, key : K, value : V, now : u64 ) -> Option<V> {
+        self.stats.offered += 1;
+        let interval = self.interval;
+        let slot = self.slots.entry(key).or_insert(Slot{ sent : None, pending : None });
+        if slot.pending.take().is_some() {
+            self.stats.conflated += 1;
+        }
+        if !slot.ready(now, interval) {
+            slot.pending = Some(value);
+            return None;
+        }
+        slot.sent = Some(now);
+        self.stats.passed += 1;
+        Some(value)
+    }
+
+    // what's held whose interval is up by `now`, by key.
+    pub fn due( &mut self, now : u64 ) -> Vec<(K, V)> {
+        let interval = self.interval;
+        let mut due = Vec::new();
+        for (key, slot) in self.slots.iter_mut() {
+            if slot.pending.is_some() && slot.ready(now, interval) {
+                slot.sent = Some(now);
+                due.extend(slot.pending.take().map(|v| (key.clone(), v)));
+            }
+        }
+        self.stats.passed += due.len() as u64;
+        due
+    }
+
+    // everything held, whatever the time, eg. at the end of the feed.
+    pub fn flush( &mut self ) -> Vec<(K, V)> {
+        let mut held = Vec::new();
+        for (key, slot) in self.slots.iter_mut() {
+            held.extend(slot.pending.take().map(|v| (key.clone(), v)));
+        }
+        self.stats.passed += held.len() as u64;
+        held
+    }
+
+    pub fn pending( &self ) -> usize {
+        self.slots.values().filter(|s| s.pending.is_some()).count()
+    }
+
+    pub fn stats( &self ) -> ThrottleStats {
+        self.stats
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BboUpdate {
+    pub symbol    : String,
+    // when the book got to it, which for one held back is before it went out
+    pub timestamp : u64,
+    pub bbo       : Bbo
+}
+
+// Each symbol's bbo as it changes, no more than once per interval.
+#[derive(Debug)]
+pub struct BboThrottle {
+    clock     : TimestampComposer,
+    last      : HashMap<String, Bbo>,
+    conflator : Conflator<String, (u64, Bbo)>
+}
+
+fn updates( held : Vec<(String, (u64, Bbo))> ) -> impl Iterator<Item = BboUpdate> {
+    held.into_iter().map(|(symbol, (timestamp, bbo))| BboUpdate{ symbol, timestamp, bbo })
+}
+
+impl BboThrottle {
+    pub fn new( interval : Duration ) -> BboThrottle {
+        BboThrottle{ clock : TimestampComposer::new(), last : HashMap::new(), conflator : Conflator::new(interval) }
+    }
+
+    // applies the message to the books, handing back the updates to send now: the message's
+    // own if its book's bbo changed and may go, then any held back that are due.
+    pub fn apply( &mut self, books : &mut BookManager, msg : &BATSMessage ) -> Vec<BboUpdate> {
+        let now = self.clock.compose(msg);
+        let symbol = books.symbol_for(msg).map(|s| String::from(s.trim_end()));
+        books.apply(msg);
+        let mut out = Vec::new();
+        if let Some(symbol) = symbol {
+            let bbo = books.book(&symbol).map(|b| b.bbo()).unwrap_or_default();
+            if self.last.get(&symbol) != Some(&bbo) {
+                self.last.insert(symbol.clone(), bbo);
+                if let Some((timestamp, bbo)) = self.conflator.offer(symbol.clone(), (now, bbo), now) {
+                    out.push(BboUpdate{ symbol, timestamp, bbo });
+                }
+            }
+        }
+        out.extend(updates(self.conflator.due(now)));
+        out
+    }
+
+    pub fn flush( &mut self ) -> Vec<BboUpdate> {
+        updates(self.conflator.flush()).collect()
+    }
+
+    pub fn stats( &self ) -> ThrottleStats {
+        self.conflator.stats()
+    }
+}
+
+// A sink given at most `max` events in any `per` of feed time, with bursts of up to `max`; the
+// rest are dropped and counted. For feeds where losing some is fine, eg. a trade ticker.
+pub struct RateLimited<S> {
+    sink    : S,
+    max     : f64,
+    // tokens per nanosecond
+    rate    : f64,
+    tokens  : f64,
+    last    : Option<u64>,
+    dropped : u64
+}
+
+impl<S : EventSink> RateLimited<S> {
+    pub fn new( sink : S, max : u32, per : Duration ) -> RateLimited<S> {
+        let max = f64::from(max.max(1));
+        RateLimited{ sink, max, rate : max / per.as_nanos().max(1) as f64, tokens : max, last : None, dropped : 0 }
+    }
+
+    pub fn dropped( &self ) -> u64 {
+        self.dropped
+    }
+
+    pub fn into_inner( self ) -> S {
+        self.sink
+    }
+}
+
+impl<S : EventSink> EventSink for RateLimited<S> {
+    fn event( &mut self, event : &MarketEvent ) -> io::Result<()> {
+        let now = event.timestamp();
+        if let Some(last) = self.last {
+            self.tokens = (self.tokens + now.saturating_sub(last) as f64 * self.rate).min(self.max);
+        }
+        self.last = Some(self.last.map_or(now, |l| l.max(now)));
+        if self.tokens < 1.0 {
+            self.dropped += 1;
+            return Ok(());
+        }
+        self.tokens -= 1.0;
+        self.sink.event(event)
+    }
+
+    fn finish( &mut self ) -> io::Result<()> {
+        self.sink.finish()
+    }
+}
@@ -0,0 +1,165 @@
+
+// Trade statistics per symbol, kept by exec id so a TradeBreakMsg can take its trade back out.
+// Volume, notional, the last price and the volume profile only count trades that still stand,
+// so they end the day where the exchange's do.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::btree_map;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    pub exec_id   : u64,
+    pub symbol    : String,
+    pub price     : u64,
+    pub shares    : u32,
+    pub timestamp : u64
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeStats {
+    pub trades     : u64,
+    pub volume     : u64,
+    // price * shares, still with the price's 4 implied decimals
+    pub notional   : u128,
+    pub last_price : Option<u64>,
+    pub breaks     : u64
+}
+
+impl TradeStats {
+    // volume weighted average price, 4 implied decimals like the prices it's made of.
+    pub fn vwap( &self ) -> Option<f64> {
+        if self.volume == 0 { None } else { Some(self.notional as f64 / self.volume as f64) }
+    }
+}
+
+// a symbol's traded volume by price over the session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VolumeProfile {
+    volumes : BTreeMap<u64, u64>
+}
+
+impl VolumeProfile {
+    fn add( &mut self, price : u64, shares : u32 ) {
+        *self.volumes.entry(price).or_insert(0) += u64::from(shares);
+    }
+
+    fn remove( &mut self, price : u64, shares : u32 ) {
+        if let btree_map::Entry::Occupied(mut e) = self.volumes.entry(price) {
+            *e.get_mut() -= u64::from(shares);
+            if *e.get() == 0 {
+                e.remove();
+            }
+        }
+    }
+
+    pub fn volume_at( &self, price : u64 ) -> u64 {
+        self.volumes.get(&price).cloned().unwrap_or(0)
+    }
+
+    // the price that traded the most, the lowest of any that tie.
+    pub fn point_of_control( &self ) -> Option<(u64, u64)> {
+        self.volumes.iter().fold(None, |poc : Option<(u64, u64)>, (&p, &v)| match poc {
+            Some((_, most)) if most >= v => poc,
+            _                            => Some((p, v))
+        })
+    }
+
+    // (price, volume) from the lowest price up.
+    pub fn iter( &self ) -> impl DoubleEndedIterator<Item = (u64, u64)> + '_ {
+        self.volumes.iter().map(|(&p, &v)| (p, v))
+    }
+
+    // prices in [low, high].
+    pub fn range( &self, low : u64, high : u64 ) -> impl DoubleEndedIterator<Item = (u64, u64)> + '_ {
+        self.volumes.range(low..=high.max(low)).map(|(&p, &v)| (p, v))
+    }
+
+    pub fn len( &self ) -> usize {
+        self.volumes.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.volumes.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TradeTracker {
+    // with the order they were recorded in, to find the last trade again after a break.
+    execs : HashMap<u64, (u64, Execution)>,
+    last  : HashMap<String, u64>,
+    stats    : HashMap<String, TradeStats>,
+    profiles : HashMap<String, VolumeProfile>,
+    seq      : u64
+}
+
+impl TradeTracker {
+    pub fn new() -> TradeTracker {
+        TradeTracker::default()
+    }
+
+    // an exec id seen before replaces its trade.
+    pub fn record( &mut self, exec : Execution ) {
+        self.remove(exec.exec_id);
+        self.seq += 1;
+        let stats = self.stats.entry(exec.symbol.clone()).or_default();
+        stats.trades += 1;
+        stats.volume += u64::from(exec.shares);
+        stats.notional += u128::from(exec.price) * u128::from(exec.shares);
+        stats.last_price = Some(exec.price);
+        self.profiles.entry(exec.symbol.clone()).or_default().add(exec.price, exec.shares);
+        self.last.insert(exec.symbol.clone(), exec.exec_id);
+        self.execs.insert(exec.exec_id, (self.seq, exec));
+    }
+
+    // takes a broken trade out of its symbol's stats, None when the exec id isn't known.
+    pub fn retract( &mut self, exec_id : u64 ) -> Option<Execution> {
+        let exec = self.remove(exec_id)?;
+        if let Some(stats) = self.stats.get_mut(&exec.symbol) {
+            stats.breaks += 1;
+        }
+        Some(exec)
+    }
+
+    // takes a trade out of the stats without counting it as a break.
+    fn remove( &mut self, exec_id : u64 ) -> Option<Execution> {
+        let (_, exec) = self.execs.remove(&exec_id)?;
+        let stats = self.stats.get_mut(&exec.symbol)?;
+        stats.trades -= 1;
+        stats.volume -= u64::from(exec.shares);
+        stats.notional -= u128::from(exec.price) * u128::from(exec.shares);
+        if let Some(profile) = self.profiles.get_mut(&exec.symbol) {
+            profile.remove(exec.price, exec.shares);
+        }
+        if self.last.get(&exec.symbol) == Some(&exec_id) {
+            // rare enough that looking through the day's trades is fine
+            let last = self.execs.values().filter(|(_, e)| e.symbol == exec.symbol).max_by_key(|&&(seq, _)| seq).map(|(_, e)| e);
+            stats.last_price = last.map(|e| e.price);
+            match last.map(|e| e.exec_id) {
+                Some(id) => self.last.insert(exec.symbol.clone(), id),
+                None     => self.last.remove(&exec.symbol)
+            };
+        }
+        Some(exec)
+    }
+
+    pub fn execution( &self, exec_id : u64 ) -> Option<&Execution> {
+        self.execs.get(&exec_id).map(|(_, e)| e)
+    }
+
+    pub fn stats( &self, symbol : &str ) -> Option<&TradeStats> {
+        self.stats.get(symbol.trim_end())
+    }
+
+    pub fn profile( &self, symbol : &str ) -> Option<&VolumeProfile> {
+        self.profiles.get(symbol.trim_end())
+    }
+
+    pub fn clear( &mut self ) {
+        self.execs.clear();
+        self.last.clear();
+        self.stats.clear();
+        self.profiles.clear();
+    }
+}
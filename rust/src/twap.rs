@@ -0,0 +1,109 @@
+
+// Time weighted prices per symbol. Every `interval` the price each symbol has then, its mid or
+// its last trade, is sampled; the TWAP over a window is the mean of the samples in it, as
+// they're evenly spaced. A symbol is sampled from its first price on, and keeps its last price
+// through quiet spells. Times are nanoseconds past midnight.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use binary::TimestampComposer;
+use book::BookManager;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PriceSource {
+    #[default]
+    Mid,
+    Last
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IntervalStats {
+    pub twap    : f64,
+    pub min     : f64,
+    pub max     : f64,
+    pub samples : usize
+}
+
+#[derive(Debug)]
+pub struct TwapTracker {
+    interval : u64,
+    source   : PriceSource,
+    clock    : TimestampComposer,
+    // the next time to sample at
+    next     : Option<u64>,
+    prices   : HashMap<String, f64>,
+    samples  : HashMap<String, BTreeMap<u64, f64>>
+}
+
+impl TwapTracker {
+    // `interval` in nanoseconds.
+    pub fn new( interval : u64, source : PriceSource ) -> TwapTracker {
+        TwapTracker{ interval : interval.max(1), source, clock : TimestampComposer::new(), next : None, prices : HashMap::new(),
+                     samples : HashMap::new() }
+    }
+
+    // applies the message to the books, sampling first if it's past a sample time, then takes
+    // the symbol's new price.
+    pub fn apply( &mut self, books : &mut BookManager, msg : &BATSMessage ) -> bool {
+        let now = self.clock.compose(msg);
+        self.advance(now);
+        let symbol = books.symbol_for(msg).map(String::from);
+        let changed = books.apply(msg);
+        if let Some(symbol) = symbol {
+            let price = match self.source {
+                PriceSource::Mid  => books.book(&symbol).and_then(|b| b.mid()),
+                PriceSource::Last => books.trades().stats(&symbol).and_then(|s| s.last_price).map(|p| p as f64)
+            };
+            match price {
+                Some(p) => self.observe(&symbol, p),
+                // a cleared book or all its trades broken
+                None    => { self.prices.remove(&symbol); }
+            }
+        }
+        changed
+    }
+
+    // for prices from elsewhere: the symbol's price from now on. Call advance() with the time
+    // first.
+    pub fn observe( &mut self, symbol : &str, price : f64 ) {
+        self.prices.insert(String::from(symbol.trim_end()), price);
+    }
+
+    // takes the samples due up to and including `now`.
+    pub fn advance( &mut self, now : u64 ) {
+        let interval = self.interval;
+        let mut next = self.next.unwrap_or(now - now % interval);
+        while next <= now {
+            for (symbol, &price) in &self.prices {
+                self.samples.entry(symbol.clone()).or_default().insert(next, price);
+            }
+            next += interval;
+        }
+        self.next = Some(next);
+    }
+
+    // over the samples taken in [from, to).
+    pub fn stats( &self, symbol : &str, from : u64, to : u64 ) -> Option<IntervalStats> {
+        let samples = self.samples.get(symbol.trim_end())?;
+        let mut stats : Option<IntervalStats> = None;
+        for &p in samples.range(from..to.max(from)).map(|(_, p)| p) {
+            stats = Some(match stats {
+                None    => IntervalStats{ twap : p, min : p, max : p, samples : 1 },
+                Some(s) => IntervalStats{ twap : s.twap + p, min : s.min.min(p), max : s.max.max(p), samples : s.samples + 1 }
+            });
+        }
+        stats.map(|s| IntervalStats{ twap : s.twap / s.samples as f64, ..s })
+    }
+
+    // over every sample so far.
+    pub fn twap( &self, symbol : &str ) -> Option<f64> {
+        self.stats(symbol, 0, u64::MAX).map(|s| s.twap)
+    }
+
+    pub fn samples( &self, symbol : &str ) -> impl Iterator<Item = (u64, f64)> + '_ {
+        self.samples.get(symbol.trim_end()).into_iter().flat_map(|s| s.iter().map(|(&t, &p)| (t, p)))
+    }
+}
@@ -0,0 +1,133 @@
+
+// Volume weighted average price per symbol from the trades it's fed. The average is over the
+// whole session, from an anchor time on, or over a rolling window ending at the symbol's last
+// trade. A broken trade comes back out of it.
+//
+// apply() takes TradeMsg and TradeBreakMsg; executions against the book carry no symbol or
+// price, so those are record()ed by whoever has the book, eg. from BookEvent::Trade. Times are
+// nanoseconds past midnight.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use binary::TimestampComposer;
+use messages::BATSMessage;
+use trades::Execution;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VwapWindow {
+    #[default]
+    Session,
+    Anchored(u64),  // trades at or after the time
+    Rolling(u64)    // the last so many nanoseconds
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    volume   : u64,
+    notional : u128,
+    // (seq, exec id) oldest first, only kept for a rolling window
+    recent   : VecDeque<(u64, u64)>
+}
+
+#[derive(Debug, Default)]
+pub struct VwapTracker {
+    window : VwapWindow,
+    clock  : TimestampComposer,
+    // with the order they were recorded in, so a reused exec id's older place in a window is
+    // told apart from its trade now.
+    execs  : HashMap<u64, (u64, Execution)>,
+    totals : HashMap<String, Totals>,
+    seq    : u64
+}
+
+impl VwapTracker {
+    pub fn new( window : VwapWindow ) -> VwapTracker {
+        VwapTracker{ window, ..VwapTracker::default() }
+    }
+
+    // whether the message was a trade or a break of one taken in.
+    pub fn apply( &mut self, msg : &BATSMessage ) -> bool {
+        let now = self.clock.compose(msg);
+        match *msg {
+            BATSMessage::TradeMsg(ref m) => {
+                self.record(Execution{ exec_id : m.exec_id, symbol : String::from(m.symbol.trim_end()), price : m.price,
+                                       shares : m.shares, timestamp : now })
+            },
+            BATSMessage::BinaryTradeMsg(ref m) => {
+                self.record(Execution{ exec_id : m.exec_id, symbol : String::from(m.symbol.trim_end()), price : m.price,
+                                       shares : m.shares, timestamp : now })
+            },
+            BATSMessage::TradeExpandedMsg(ref m) => {
+                self.record(Execution{ exec_id : m.exec_id, symbol : String::from(m.symbol.trim_end()), price : m.price,
+                                       shares : m.shares, timestamp : now })
+            },
+            BATSMessage::TradeBreakMsg(ref m) => self.retract(m.exec_id).is_some(),
+            _ => false
+        }
+    }
+
+    // false for a trade before the anchor. An exec id seen before replaces its trade.
+    pub fn record( &mut self, exec : Execution ) -> bool {
+        if let VwapWindow::Anchored(from) = self.window {
+            if exec.timestamp < from {
+                return false;
+            }
+        }
+        self.retract(exec.exec_id);
+        self.seq += 1;
+        let totals = self.totals.entry(exec.symbol.clone()).or_default();
+        totals.volume += u64::from(exec.shares);
+        totals.notional += u128::from(exec.price) * u128::from(exec.shares);
+        let (seq, exec_id, now) = (self.seq, exec.exec_id, exec.timestamp);
+        self.execs.insert(exec_id, (seq, exec));
+        if let VwapWindow::Rolling(length) = self.window {
+            totals.recent.push_back((seq, exec_id));
+            let execs = &mut self.execs;
+            while let Some(&(seq, id)) = totals.recent.front() {
+                match execs.get(&id) {
+                    Some(&(s, ref old)) if s == seq => {
+                        if old.timestamp + length >= now {
+                            break;
+                        }
+                        totals.volume -= u64::from(old.shares);
+                        totals.notional -= u128::from(old.price) * u128::from(old.shares);
+                        execs.remove(&id);
+                    },
+                    // broken or replaced since
+                    _ => {}
+                }
+                totals.recent.pop_front();
+            }
+        }
+        true
+    }
+
+    // takes a broken trade back out, None when it isn't in any average (any more).
+    pub fn retract( &mut self, exec_id : u64 ) -> Option<Execution> {
+        let (_, exec) = self.execs.remove(&exec_id)?;
+        if let Some(totals) = self.totals.get_mut(&exec.symbol) {
+            totals.volume -= u64::from(exec.shares);
+            totals.notional -= u128::from(exec.price) * u128::from(exec.shares);
+        }
+        Some(exec)
+    }
+
+    // 4 implied decimals like the prices, None before any volume.
+    pub fn vwap( &self, symbol : &str ) -> Option<f64> {
+        self.totals.get(symbol.trim_end()).filter(|t| t.volume > 0).map(|t| t.notional as f64 / t.volume as f64)
+    }
+
+    pub fn volume( &self, symbol : &str ) -> u64 {
+        self.totals.get(symbol.trim_end()).map_or(0, |t| t.volume)
+    }
+
+    pub fn window( &self ) -> VwapWindow {
+        self.window
+    }
+
+    pub fn clear( &mut self ) {
+        self.execs.clear();
+        self.totals.clear();
+    }
+}
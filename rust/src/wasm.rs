@@ -0,0 +1,124 @@
+
+// wasm-bindgen exports, so captured PITCH samples can be parsed and looked at in the browser
+// without a server:
+//
+//   cargo build --release --target wasm32-unknown-unknown --features wasm
+//   wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/rust_orderbook.wasm
+//
+//   import init, { parse, parseAll, Book } from "./pkg/rust_orderbook.js";
+//   const book = new Book("AAPL");
+//   for (const line of text.split("\n")) book.apply(line);
+//   book.bids(10)   // [[price, shares], ...] best first
+//
+// Messages are plain objects in the serde schema (the jsonl one). Book wraps book::OrderBook,
+// its prices and shares are plain JS numbers, prices with 4 implied decimals.
+
+use serde::Serialize;
+use serde_wasm_bindgen;
+use wasm_bindgen::prelude::*;
+
+use book::OrderBook;
+use book::Side;
+use messages::BATSMessage;
+use messages::BATSMsgFactory;
+
+fn parse_line( line : &str ) -> Result<BATSMessage, JsError> {
+    BATSMsgFactory::try_parse(line).ok_or_else(|| JsError::new(&format!("not a PITCH message: {:?}", line)))
+}
+
+// order and exec ids don't fit in a JS number, so every u64 in a message is a BigInt.
+fn to_js<T : Serialize>( v : &T ) -> Result<JsValue, JsError> {
+    v.serialize(&serde_wasm_bindgen::Serializer::new().serialize_large_number_types_as_bigints(true))
+     .map_err(JsError::from)
+}
+
+#[wasm_bindgen]
+pub fn parse( line : &str ) -> Result<JsValue, JsError> {
+    to_js(&parse_line(line)?)
+}
+
+// every message in a capture, blank lines skipped.
+#[wasm_bindgen(js_name = parseAll)]
+pub fn parse_all( text : &str ) -> Result<JsValue, JsError> {
+    let msgs = text.lines().map(|l| l.trim_end()).filter(|l| !l.is_empty())
+                   .map(parse_line).collect::<Result<Vec<_>, _>>()?;
+    to_js(&msgs)
+}
+
+#[wasm_bindgen]
+pub struct Book {
+    book : OrderBook
+}
+
+impl Book {
+    pub fn apply_message( &mut self, msg : &BATSMessage ) -> bool {
+        self.book.apply(msg)
+    }
+
+    // (price, shares) best first, every level for a depth of 0.
+    pub fn levels( &self, side : Side, depth : usize ) -> Vec<(u64, u64)> {
+        let depth = if depth == 0 { usize::MAX } else { depth };
+        match side {
+            Side::Bid => self.book.bids().take(depth).map(|l| (l.price(), l.shares())).collect(),
+            Side::Ask => self.book.asks().take(depth).map(|l| (l.price(), l.shares())).collect()
+        }
+    }
+
+    fn js_levels( &self, side : Side, depth : Option<usize> ) -> Result<JsValue, JsError> {
+        let levels : Vec<(f64, f64)> = self.levels(side, depth.unwrap_or(0)).into_iter()
+                                           .map(|(p, v)| (p as f64, v as f64)).collect();
+        to_js(&levels)
+    }
+}
+
+#[wasm_bindgen]
+impl Book {
+    // books every symbol when it's left out.
+    #[wasm_bindgen(constructor)]
+    pub fn new( symbol : Option<String> ) -> Book {
+        Book{ book : symbol.as_ref().map_or_else(OrderBook::new, |s| OrderBook::for_symbol(s)) }
+    }
+
+    // applies a PITCH line, returns whether the book changed.
+    pub fn apply( &mut self, line : &str ) -> Result<bool, JsError> {
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(false);
+        }
+        Ok(self.book.apply(&parse_line(line)?))
+    }
+
+    // applies a message object from parse()/parseAll().
+    #[wasm_bindgen(js_name = applyObject)]
+    pub fn apply_object( &mut self, msg : JsValue ) -> Result<bool, JsError> {
+        let msg : BATSMessage = serde_wasm_bindgen::from_value(msg)?;
+        Ok(self.book.apply(&msg))
+    }
+
+    pub fn clear( &mut self ) {
+        self.book.clear();
+    }
+
+    #[wasm_bindgen(js_name = bestBid)]
+    pub fn best_bid( &self ) -> Option<f64> {
+        self.book.best_bid().map(|q| q.price as f64)
+    }
+
+    #[wasm_bindgen(js_name = bestAsk)]
+    pub fn best_ask( &self ) -> Option<f64> {
+        self.book.best_ask().map(|q| q.price as f64)
+    }
+
+    pub fn bids( &self, depth : Option<usize> ) -> Result<JsValue, JsError> {
+        self.js_levels(Side::Bid, depth)
+    }
+
+    pub fn asks( &self, depth : Option<usize> ) -> Result<JsValue, JsError> {
+        self.js_levels(Side::Ask, depth)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn orders( &self ) -> usize {
+        self.book.len()
+    }
+}
@@ -0,0 +1,138 @@
+
+// How long since each unit last sent a packet (heartbeats count) and each symbol last had a
+// message, and which of them have been quiet past their threshold, so a consumer can fail
+// over to the other feed or mark the data suspect:
+//
+//   let mut watchdog = Watchdog::new(Duration::from_secs(2), Duration::from_secs(60));
+//   watchdog.packet(unit, Instant::now());
+//   watchdog.message(&msg, &books, Instant::now());
+//   for change in watchdog.check(Instant::now()) { ... }
+//
+// check() reports each source once as it goes stale and once more when it's heard from again.
+// Only what's been heard from at least once is watched. A halted symbol goes quiet too, it's
+// for the caller to tell that apart (BookManager::state).
+
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+use book::BookManager;
+use messages::BATSMessage;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Source {
+    Unit(u8),
+    Symbol(String)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Staleness {
+    // nothing for `quiet`, which is past the threshold
+    Stale { source : Source, quiet : Duration },
+    // heard from again after being stale for `quiet`
+    Fresh { source : Source, quiet : Duration }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Watched {
+    last       : Instant,
+    // when it went stale, while it is
+    stale_from : Option<Instant>
+}
+
+#[derive(Debug)]
+pub struct Watchdog {
+    unit_threshold   : Duration,
+    symbol_threshold : Duration,
+    // for symbols that trade more or less often than most
+    overrides        : HashMap<String, Duration>,
+    watched          : HashMap<Source, Watched>
+}
+
+impl Watchdog {
+    pub fn new( unit_threshold : Duration, symbol_threshold : Duration ) -> Watchdog {
+        Watchdog{ unit_threshold, symbol_threshold, overrides : HashMap::new(), watched : HashMap::new() }
+    }
+
+    pub fn symbol_threshold( mut self, symbol : &str, threshold : Duration ) -> Watchdog {
+        self.overrides.insert(String::from(symbol.trim_end()), threshold);
+        self
+    }
+
+    fn threshold( &self, source : &Source ) -> Duration {
+        match *source {
+            Source::Unit(_)       => self.unit_threshold,
+            Source::Symbol(ref s) => self.overrides.get(s).cloned().unwrap_or(self.symbol_threshold)
+        }
+    }
+
+    fn heard( &mut self, source : Source, now : Instant ) {
+        self.watched.entry(source).and_modify(|w| w.last = w.last.max(now)).or_insert(Watched{ last : now, stale_from : None });
+    }
+
+    // a packet (or heartbeat) arrived on `unit`.
+    pub fn packet( &mut self, unit : u8, now : Instant ) {
+        self.heard(Source::Unit(unit), now);
+    }
+
+    pub fn symbol( &mut self, symbol : &str, now : Instant ) {
+        self.heard(Source::Symbol(String::from(symbol.trim_end())), now);
+    }
+
+    // the symbol a message is for, by the books for those that only carry an order or exec id.
+    // Messages for no symbol (eg. Time) are ignored.
+    pub fn message( &mut self, msg : &BATSMessage, books : &BookManager, now : Instant ) {
+        if let Some(symbol) = books.symbol_for(msg) {
+            self.symbol(symbol, now);
+        }
+    }
+
+    // stale and fresh again since the last check, units first then symbols, each by name.
+    pub fn check( &mut self, now : Instant ) -> Vec<Staleness> {
+        let mut changes = Vec::new();
+        let thresholds : Vec<_> = self.watched.keys().map(|s| (s.clone(), self.threshold(s))).collect();
+        for (source, threshold) in thresholds {
+            let w = self.watched.get_mut(&source).unwrap();
+            let quiet = now.saturating_duration_since(w.last);
+            match w.stale_from {
+                None if quiet > threshold => {
+                    w.stale_from = Some(w.last);
+                    changes.push(Staleness::Stale{ source, quiet });
+                },
+                Some(from) if quiet <= threshold => {
+                    w.stale_from = None;
+                    changes.push(Staleness::Fresh{ source, quiet : w.last.saturating_duration_since(from) });
+                },
+                _ => {}
+            }
+        }
+        changes.sort_by(|a, b| source_of(a).cmp(source_of(b)));
+        changes
+    }
+
+    pub fn is_stale( &self, source : &Source ) -> bool {
+        self.watched.get(source).is_some_and(|w| w.stale_from.is_some())
+    }
+
+    // as of the last check, by name.
+    pub fn stale( &self ) -> Vec<&Source> {
+        let mut stale : Vec<_> = self.watched.iter().filter(|&(_, w)| w.stale_from.is_some()).map(|(s, _)| s).collect();
+        stale.sort();
+        stale
+    }
+
+    // how long since `source` was last heard from, None if it never was.
+    pub fn quiet( &self, source : &Source, now : Instant ) -> Option<Duration> {
+        self.watched.get(source).map(|w| now.saturating_duration_since(w.last))
+    }
+
+    pub fn forget( &mut self, source : &Source ) {
+        self.watched.remove(source);
+    }
+}
+
+fn source_of( change : &Staleness ) -> &Source {
+    match *change {
+        Staleness::Stale{ ref source, .. } | Staleness::Fresh{ ref source, .. } => source
+    }
+}
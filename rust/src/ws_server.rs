@@ -0,0 +1,195 @@
+
+// WebSocket gateway streaming trades and book deltas as JSON to UIs. Clients pick symbols
+// with
+//
+//   {"op":"subscribe","symbols":["AAPL","MSFT"]}     ("*" is every symbol)
+//   {"op":"unsubscribe","symbols":["MSFT"]}
+//
+// and get one text message per update, eg.
+//
+//   {"type":"book_delta","symbol":"AAPL","timestamp":34200000000000,"side":"B","price":1831800,"volume":300}
+//   {"type":"trade","symbol":"AAPL","timestamp":34200000000000,"side":"B","price":1831900,"shares":100,"exec_id":7}
+//
+// Each client gets a thread that alternates between sending what's been published and
+// polling the socket for subscription changes, so updates go out within POLL_INTERVAL.
+
+use std::collections::HashSet;
+use std::io;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use serde_json;
+use tungstenite;
+use tungstenite::Message;
+use tungstenite::WebSocket;
+
+use event::MarketEvent;
+
+const POLL_INTERVAL : Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsUpdate {
+    Trade     { symbol : String, timestamp : u64, side : Option<char>, price : u64, shares : u32, exec_id : u64 },
+    BookDelta { symbol : String, timestamp : u64, side : char, price : u64, volume : u32 }   // volume 0 removes the level
+}
+
+impl WsUpdate {
+    fn symbol( &self ) -> &str {
+        match *self {
+            WsUpdate::Trade{ ref symbol, .. } | WsUpdate::BookDelta{ ref symbol, .. } => symbol
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Subscribe   { symbols : Vec<String> },
+    Unsubscribe { symbols : Vec<String> }
+}
+
+struct Client {
+    symbols : Arc<Mutex<HashSet<String>>>,
+    updates : Sender<Arc<String>>
+}
+
+impl Client {
+    fn wants( &self, symbol : &str ) -> bool {
+        let symbols = self.symbols.lock().unwrap();
+        symbols.contains(symbol) || symbols.contains("*")
+    }
+}
+
+fn handle_request( text : &str, symbols : &Mutex<HashSet<String>> ) {
+    match serde_json::from_str(text) {
+        Ok(Request::Subscribe{ symbols : s })   => symbols.lock().unwrap().extend(s),
+        Ok(Request::Unsubscribe{ symbols : s }) => {
+            let mut symbols = symbols.lock().unwrap();
+            for symbol in s {
+                symbols.remove(&symbol);
+            }
+        },
+        Err(_) => {} // ignore anything that isn't a request
+    }
+}
+
+fn is_timeout( e : &tungstenite::Error ) -> bool {
+    match *e {
+        tungstenite::Error::Io(ref e) => e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut,
+        _ => false
+    }
+}
+
+fn serve( mut ws : WebSocket<TcpStream>, symbols : Arc<Mutex<HashSet<String>>>, updates : Receiver<Arc<String>> ) {
+    loop {
+        let mut sent = false;
+        while let Ok(update) = updates.try_recv() {
+            if ws.write(Message::Text(update.as_str().into())).is_err() {
+                return;
+            }
+            sent = true;
+        }
+        if sent && ws.flush().is_err() {
+            return;
+        }
+        match ws.read() {
+            Ok(Message::Text(text)) => handle_request(text.as_str(), &symbols),
+            Ok(Message::Close(_))   => return,
+            Ok(_)                   => {},
+            Err(ref e) if is_timeout(e) => {},
+            Err(_)                  => return
+        }
+    }
+}
+
+pub struct WsServer {
+    addr    : SocketAddr,
+    clients : Arc<Mutex<Vec<Client>>>
+}
+
+impl WsServer {
+    // listens on addr (eg. "0.0.0.0:9001", port 0 picks one), accepting clients on a
+    // background thread.
+    pub fn bind( addr : &str ) -> io::Result<WsServer> {
+        let listener = TcpListener::bind(addr)?;
+        let clients : Arc<Mutex<Vec<Client>>> = Arc::new(Mutex::new(vec![]));
+        let server = WsServer{ addr : listener.local_addr()?, clients : clients.clone() };
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(s)  => s,
+                    Err(_) => continue
+                };
+                let clients = clients.clone();
+                thread::spawn(move || {
+                    let ws = match tungstenite::accept(stream) {
+                        Ok(ws) => ws,
+                        Err(_) => return
+                    };
+                    if ws.get_ref().set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+                        return;
+                    }
+                    let symbols = Arc::new(Mutex::new(HashSet::new()));
+                    let (tx, rx) = mpsc::channel();
+                    clients.lock().unwrap().push(Client{ symbols : symbols.clone(), updates : tx });
+                    serve(ws, symbols, rx);
+                });
+            }
+        });
+        Ok(server)
+    }
+
+    pub fn local_addr( &self ) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn clients( &self ) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    // sends the update to every client subscribed to its symbol, returning how many.
+    pub fn publish( &self, update : &WsUpdate ) -> usize {
+        let mut clients = self.clients.lock().unwrap();
+        let wanted : Vec<usize> = (0..clients.len()).filter(|&i| clients[i].wants(update.symbol())).collect();
+        if wanted.is_empty() {
+            return 0;
+        }
+        let text = Arc::new(serde_json::to_string(update).unwrap());
+        let mut sent = 0;
+        let mut gone = vec![];
+        for i in wanted {
+            if clients[i].updates.send(text.clone()).is_ok() {
+                sent += 1;
+            } else {
+                gone.push(i);
+            }
+        }
+        for i in gone.into_iter().rev() {
+            clients.remove(i);
+        }
+        sent
+    }
+
+    // publishes trades that carry a symbol and price, other events are ignored.
+    pub fn publish_event( &self, e : &MarketEvent ) -> usize {
+        match *e {
+            MarketEvent::Trade{ timestamp, symbol : Some(ref symbol), side, price : Some(price), shares, exec_id, .. } =>
+                self.publish(&WsUpdate::Trade{ symbol : symbol.clone(), timestamp, side, price, shares, exec_id }),
+            _ => 0
+        }
+    }
+
+    pub fn publish_book_delta( &self, timestamp : u64, symbol : &str, side : char, price : u64, volume : u32 ) -> usize {
+        self.publish(&WsUpdate::BookDelta{ symbol : String::from(symbol), timestamp, side, price, volume })
+    }
+}
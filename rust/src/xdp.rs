@@ -0,0 +1,424 @@
+
+// Decoder for the NYSE XDP Integrated Feed. Packets start with a 16 byte header followed by
+// messages that each lead with a 2 byte size and 2 byte type. Everything is little endian.
+// Orders refer to symbols by index and prices need the scale from the Symbol Index Mapping,
+// so XdpSymbolMap keeps track of both to bring prices to the crate's 4 implied decimals.
+
+use nom;
+use nom::{le_u8, le_u16, le_u32, le_u64};
+
+use std::collections::HashMap;
+use std::str;
+use std::result::Result;
+
+pub const SOURCE_TIME_REFERENCE : u16 = 2;
+pub const SYMBOL_INDEX_MAPPING  : u16 = 3;
+pub const ADD_ORDER             : u16 = 100;
+pub const MODIFY_ORDER          : u16 = 101;
+pub const DELETE_ORDER          : u16 = 102;
+pub const EXECUTION             : u16 = 103;
+pub const IMBALANCE             : u16 = 105;
+
+#[derive(Debug)]
+pub enum XdpMessage {
+    SourceTimeReference(SourceTimeReferenceMsg),
+    SymbolIndexMapping(SymbolIndexMappingMsg),
+    AddOrder(AddOrderMsg),
+    ModifyOrder(ModifyOrderMsg),
+    DeleteOrder(DeleteOrderMsg),
+    Execution(ExecutionMsg),
+    Imbalance(ImbalanceMsg),
+    Unknown(u16)
+}
+
+#[derive(Debug)]
+pub struct PacketHeader {
+    pub size          : u16,
+    pub delivery_flag : u8,
+    pub count         : u8,
+    pub sequence      : u32,
+    pub send_time     : u32, // seconds since epoch
+    pub send_time_ns  : u32
+}
+
+#[derive(Debug)]
+pub struct SourceTimeReferenceMsg {
+    pub id             : u32,
+    pub symbol_seq_num : u32,
+    pub source_time    : u32 // seconds since epoch, order messages only carry the ns part
+}
+
+#[derive(Debug)]
+pub struct SymbolIndexMappingMsg {
+    pub symbol_index      : u32,
+    pub symbol            : String,
+    pub market_id         : u16,
+    pub system_id         : u8,
+    pub exchange_code     : char,
+    pub price_scale_code  : u8,
+    pub security_type     : char,
+    pub lot_size          : u16,
+    pub prev_close_price  : u32,
+    pub prev_close_volume : u32,
+    pub price_resolution  : u8,
+    pub round_lot         : char,
+    pub mpv               : u16,
+    pub unit_of_trade     : u16
+}
+
+#[derive(Debug)]
+pub struct AddOrderMsg {
+    pub source_time_ns : u32,
+    pub symbol_index   : u32,
+    pub symbol_seq_num : u32,
+    pub order_id       : u64,
+    pub price          : u32, // scaled by the symbol's price_scale_code
+    pub volume         : u32,
+    pub side           : char,
+    pub firm_id        : String
+}
+
+#[derive(Debug)]
+pub struct ModifyOrderMsg {
+    pub source_time_ns  : u32,
+    pub symbol_index    : u32,
+    pub symbol_seq_num  : u32,
+    pub order_id        : u64,
+    pub price           : u32,
+    pub volume          : u32,
+    pub position_change : u8, // 1 when the order lost its time priority
+    pub side            : char
+}
+
+#[derive(Debug)]
+pub struct DeleteOrderMsg {
+    pub source_time_ns : u32,
+    pub symbol_index   : u32,
+    pub symbol_seq_num : u32,
+    pub order_id       : u64,
+    pub side           : char
+}
+
+#[derive(Debug)]
+pub struct ExecutionMsg {
+    pub source_time_ns  : u32,
+    pub symbol_index    : u32,
+    pub symbol_seq_num  : u32,
+    pub order_id        : u64,
+    pub trade_id        : u32,
+    pub price           : u32,
+    pub volume          : u32,
+    pub printable_flag  : u8,
+    pub trade_condition : String
+}
+
+#[derive(Debug)]
+pub struct ImbalanceMsg {
+    pub source_time               : u32,
+    pub source_time_ns            : u32,
+    pub symbol_index              : u32,
+    pub symbol_seq_num            : u32,
+    pub reference_price           : u32,
+    pub paired_qty                : u32,
+    pub total_imbalance_qty       : u32,
+    pub market_imbalance_qty      : u32,
+    pub auction_time              : u16, // hhmm
+    pub auction_type              : char,
+    pub imbalance_side            : char,
+    pub continuous_clearing_price : u32,
+    pub auction_clearing_price    : u32,
+    pub ssr_filing_price          : u32,
+    pub indicative_match_price    : u32,
+    pub upper_collar              : u32,
+    pub lower_collar              : u32,
+    pub auction_status            : u8,
+    pub freeze_status             : u8,
+    pub num_extensions            : u8
+}
+
+impl XdpMessage {
+    pub fn parse( msg : &[u8] ) -> Result<XdpMessage, nom::Err<&[u8]>> {
+        let (_, (_, msg_type)) = parse_msg_header(msg)?;
+        match msg_type {
+            SOURCE_TIME_REFERENCE => parse_source_time_reference(msg).map(|o| XdpMessage::SourceTimeReference(o.1)),
+            SYMBOL_INDEX_MAPPING  => parse_symbol_index_mapping(msg).map(|o| XdpMessage::SymbolIndexMapping(o.1)),
+            ADD_ORDER             => parse_add_order(msg).map(|o| XdpMessage::AddOrder(o.1)),
+            MODIFY_ORDER          => parse_modify_order(msg).map(|o| XdpMessage::ModifyOrder(o.1)),
+            DELETE_ORDER          => parse_delete_order(msg).map(|o| XdpMessage::DeleteOrder(o.1)),
+            EXECUTION             => parse_execution(msg).map(|o| XdpMessage::Execution(o.1)),
+            IMBALANCE             => parse_imbalance(msg).map(|o| XdpMessage::Imbalance(o.1)),
+            t                     => Ok(XdpMessage::Unknown(t))
+        }
+    }
+}
+
+pub type Packet<'a> = (PacketHeader, Vec<&'a [u8]>);
+
+// splits a packet into its header and messages, each message slice includes its own
+// size/type header and can be handed to XdpMessage::parse.
+pub fn split_packet( packet : &[u8] ) -> Result<Packet<'_>, nom::Err<&[u8]>> {
+    let (mut rest, header) = parse_packet_header(packet)?;
+    let mut msgs = Vec::with_capacity(header.count as usize);
+    for _ in 0..header.count {
+        let (_, (size, _)) = parse_msg_header(rest)?;
+        if rest.len() < size as usize {
+            return Err(nom::Err::Incomplete(nom::Needed::Size(size as usize)));
+        }
+        let (msg, remaining) = rest.split_at(size as usize);
+        msgs.push(msg);
+        rest = remaining;
+    }
+    Ok((header, msgs))
+}
+
+#[derive(Debug, Default)]
+pub struct XdpSymbolMap {
+    symbols : HashMap<u32, SymbolIndexMappingMsg>
+}
+
+impl XdpSymbolMap {
+    pub fn new() -> XdpSymbolMap {
+        XdpSymbolMap{ symbols : HashMap::new() }
+    }
+
+    pub fn apply( &mut self, msg : XdpMessage ) -> Option<XdpMessage> {
+        // keeps mapping messages, hands everything else back to the caller.
+        match msg {
+            XdpMessage::SymbolIndexMapping(m) => { self.symbols.insert(m.symbol_index, m); None },
+            other => Some(other)
+        }
+    }
+
+    pub fn symbol( &self, symbol_index : u32 ) -> Option<&str> {
+        self.symbols.get(&symbol_index).map(|m| m.symbol.trim_end_matches('\0').trim_end())
+    }
+
+    // converts a raw XDP price into 4 implied decimal places.
+    pub fn normalize_price( &self, symbol_index : u32, price : u32 ) -> Option<u64> {
+        self.symbols.get(&symbol_index).map(|m| {
+            let scale = u32::from(m.price_scale_code);
+            if scale <= 4 {
+                u64::from(price) * 10u64.pow(4 - scale)
+            } else {
+                u64::from(price) / 10u64.pow(scale - 4)
+            }
+        })
+    }
+}
+
+fn from_ascii( input : &[u8] ) -> Result<String, str::Utf8Error> {
+    str::from_utf8(input).map(String::from)
+}
+
+named!(parse_char<&[u8], char>, map!(le_u8, char::from));
+
+named!(parse_packet_header<&[u8], PacketHeader>,
+    do_parse!(
+        _1 : le_u16 >>
+        _2 : le_u8  >>
+        _3 : le_u8  >>
+        _4 : le_u32 >>
+        _5 : le_u32 >>
+        _6 : le_u32 >>
+        (PacketHeader{ size          : _1,
+                       delivery_flag : _2,
+                       count         : _3,
+                       sequence      : _4,
+                       send_time     : _5,
+                       send_time_ns  : _6
+                     })
+    )
+);
+
+named!(parse_msg_header<&[u8], (u16, u16)>,
+    do_parse!(
+        _1 : le_u16 >>
+        _2 : le_u16 >>
+        ((_1, _2))
+    )
+);
+
+named!(parse_source_time_reference<&[u8], SourceTimeReferenceMsg>,
+    do_parse!(
+        _1 : parse_msg_header >>
+        _2 : le_u32           >>
+        _3 : le_u32           >>
+        _4 : le_u32           >>
+        (SourceTimeReferenceMsg{ id             : _2,
+                                 symbol_seq_num : _3,
+                                 source_time    : _4
+                               })
+    )
+);
+
+named!(parse_symbol_index_mapping<&[u8], SymbolIndexMappingMsg>,
+    do_parse!(
+        _1  : parse_msg_header                >>
+        _2  : le_u32                          >>
+        _3  : map_res!(take!(11), from_ascii) >>
+        _4  : take!(1)                        >>
+        _5  : le_u16                          >>
+        _6  : le_u8                           >>
+        _7  : parse_char                      >>
+        _8  : le_u8                           >>
+        _9  : parse_char                      >>
+        _10 : le_u16                          >>
+        _11 : le_u32                          >>
+        _12 : le_u32                          >>
+        _13 : le_u8                           >>
+        _14 : parse_char                      >>
+        _15 : le_u16                          >>
+        _16 : le_u16                          >>
+        (SymbolIndexMappingMsg{ symbol_index      : _2,
+                                symbol            : _3,
+                                market_id         : _5,
+                                system_id         : _6,
+                                exchange_code     : _7,
+                                price_scale_code  : _8,
+                                security_type     : _9,
+                                lot_size          : _10,
+                                prev_close_price  : _11,
+                                prev_close_volume : _12,
+                                price_resolution  : _13,
+                                round_lot         : _14,
+                                mpv               : _15,
+                                unit_of_trade     : _16
+                              })
+    )
+);
+
+named!(parse_add_order<&[u8], AddOrderMsg>,
+    do_parse!(
+        _1 : parse_msg_header               >>
+        _2 : le_u32                         >>
+        _3 : le_u32                         >>
+        _4 : le_u32                         >>
+        _5 : le_u64                         >>
+        _6 : le_u32                         >>
+        _7 : le_u32                         >>
+        _8 : parse_char                     >>
+        _9 : map_res!(take!(5), from_ascii) >>
+        (AddOrderMsg{ source_time_ns : _2,
+                      symbol_index   : _3,
+                      symbol_seq_num : _4,
+                      order_id       : _5,
+                      price          : _6,
+                      volume         : _7,
+                      side           : _8,
+                      firm_id        : _9
+                    })
+    )
+);
+
+named!(parse_modify_order<&[u8], ModifyOrderMsg>,
+    do_parse!(
+        _1 : parse_msg_header >>
+        _2 : le_u32           >>
+        _3 : le_u32           >>
+        _4 : le_u32           >>
+        _5 : le_u64           >>
+        _6 : le_u32           >>
+        _7 : le_u32           >>
+        _8 : le_u8            >>
+        _9 : parse_char       >>
+        (ModifyOrderMsg{ source_time_ns  : _2,
+                         symbol_index    : _3,
+                         symbol_seq_num  : _4,
+                         order_id        : _5,
+                         price           : _6,
+                         volume          : _7,
+                         position_change : _8,
+                         side            : _9
+                       })
+    )
+);
+
+named!(parse_delete_order<&[u8], DeleteOrderMsg>,
+    do_parse!(
+        _1 : parse_msg_header >>
+        _2 : le_u32           >>
+        _3 : le_u32           >>
+        _4 : le_u32           >>
+        _5 : le_u64           >>
+        _6 : parse_char       >>
+        (DeleteOrderMsg{ source_time_ns : _2,
+                         symbol_index   : _3,
+                         symbol_seq_num : _4,
+                         order_id       : _5,
+                         side           : _6
+                       })
+    )
+);
+
+named!(parse_execution<&[u8], ExecutionMsg>,
+    do_parse!(
+        _1  : parse_msg_header               >>
+        _2  : le_u32                         >>
+        _3  : le_u32                         >>
+        _4  : le_u32                         >>
+        _5  : le_u64                         >>
+        _6  : take!(4)                       >>
+        _7  : le_u32                         >>
+        _8  : le_u32                         >>
+        _9  : le_u32                         >>
+        _10 : le_u8                          >>
+        _11 : map_res!(take!(4), from_ascii) >>
+        (ExecutionMsg{ source_time_ns  : _2,
+                       symbol_index    : _3,
+                       symbol_seq_num  : _4,
+                       order_id        : _5,
+                       trade_id        : _7,
+                       price           : _8,
+                       volume          : _9,
+                       printable_flag  : _10,
+                       trade_condition : _11
+                     })
+    )
+);
+
+named!(parse_imbalance<&[u8], ImbalanceMsg>,
+    do_parse!(
+        _1  : parse_msg_header >>
+        _2  : le_u32           >>
+        _3  : le_u32           >>
+        _4  : le_u32           >>
+        _5  : le_u32           >>
+        _6  : le_u32           >>
+        _7  : le_u32           >>
+        _8  : le_u32           >>
+        _9  : le_u32           >>
+        _10 : le_u16           >>
+        _11 : parse_char       >>
+        _12 : parse_char       >>
+        _13 : le_u32           >>
+        _14 : le_u32           >>
+        _15 : le_u32           >>
+        _16 : le_u32           >>
+        _17 : le_u32           >>
+        _18 : le_u32           >>
+        _19 : le_u8            >>
+        _20 : le_u8            >>
+        _21 : le_u8            >>
+        (ImbalanceMsg{ source_time               : _2,
+                       source_time_ns            : _3,
+                       symbol_index              : _4,
+                       symbol_seq_num            : _5,
+                       reference_price           : _6,
+                       paired_qty                : _7,
+                       total_imbalance_qty       : _8,
+                       market_imbalance_qty      : _9,
+                       auction_time              : _10,
+                       auction_type              : _11,
+                       imbalance_side            : _12,
+                       continuous_clearing_price : _13,
+                       auction_clearing_price    : _14,
+                       ssr_filing_price          : _15,
+                       indicative_match_price    : _16,
+                       upper_collar              : _17,
+                       lower_collar              : _18,
+                       auction_status            : _19,
+                       freeze_status             : _20,
+                       num_extensions            : _21
+                     })
+    )
+);